@@ -1,7 +1,6 @@
 use anyhow::Result;
-use pollster::FutureExt;
+use playground_core::GpuContext;
 use std::sync::Arc;
-use wgpu::{Adapter, Device, Instance, Queue, Surface, SurfaceCapabilities};
 use winit::{
     application::ApplicationHandler,
     dpi::{LogicalSize, PhysicalSize, Size},
@@ -10,106 +9,15 @@ use winit::{
     window::{Window, WindowId},
 };
 
-// GPU Context handling
-struct GpuContext<'a> {
-    device: Device,
-    queue: Queue,
-    surface: Surface<'a>,
-    config: wgpu::SurfaceConfiguration,
-}
-
-impl<'a> GpuContext<'a> {
-    pub fn new(window: &'a Window) -> Result<Self> {
-        let instance = Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            ..Default::default()
-        });
-
-        let surface = instance.create_surface(window)?;
-        let adapter = Self::create_adapter(&instance, &surface)?;
-        let (device, queue) = Self::create_device(&adapter)?;
-        let surface_caps = surface.get_capabilities(&adapter);
-        let config = Self::create_surface_config(window.inner_size(), surface_caps);
-
-        surface.configure(&device, &config);
-
-        Ok(Self {
-            device,
-            queue,
-            surface,
-            config,
-        })
-    }
-
-    fn create_adapter(instance: &Instance, surface: &Surface) -> Result<Adapter> {
-        instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(surface),
-                force_fallback_adapter: false,
-            })
-            .block_on()
-            .ok_or_else(|| anyhow::anyhow!("No adapter found"))
-    }
-
-    fn create_device(adapter: &Adapter) -> Result<(Device, Queue)> {
-        adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                    memory_hints: wgpu::MemoryHints::default(),
-                    label: None,
-                },
-                None,
-            )
-            .block_on()
-            .map_err(|e| e.into())
-    }
-
-    fn create_surface_config(
-        size: PhysicalSize<u32>,
-        capabilities: SurfaceCapabilities,
-    ) -> wgpu::SurfaceConfiguration {
-        let format = capabilities
-            .formats
-            .iter()
-            .find(|f| f.is_srgb())
-            .copied()
-            .unwrap_or(capabilities.formats[0]);
-
-        wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format,
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::AutoNoVsync,
-            alpha_mode: capabilities.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        }
-    }
-
-    fn resize(&mut self, size: PhysicalSize<u32>) {
-        self.config.width = size.width;
-        self.config.height = size.height;
-        self.surface.configure(&self.device, &self.config);
-    }
-}
-
 // Renderer handles all drawing operations
 struct Renderer {
-    gpu: GpuContext<'static>,
-    _window: Arc<Window>, // Keep window alive as long as renderer exists
+    gpu: GpuContext,
 }
 
 impl Renderer {
     pub fn new(window: Arc<Window>) -> Result<Self> {
-        let gpu = unsafe { std::mem::transmute(GpuContext::new(&window)?) };
-        Ok(Self {
-            gpu,
-            _window: window,
-        })
+        let gpu = GpuContext::new(window)?;
+        Ok(Self { gpu })
     }
 
     pub fn render(&mut self) -> Result<()> {
@@ -152,7 +60,7 @@ impl Renderer {
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
-        self.gpu.resize(new_size);
+        self.gpu.resize(&new_size);
     }
 }
 
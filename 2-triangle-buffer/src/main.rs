@@ -1,12 +1,9 @@
 use anyhow::Result;
-use pollster::FutureExt;
+use playground_core::GpuContext;
 use std::sync::Arc;
-use tracing::info;
 use tracing_subscriber::EnvFilter;
 use vertex::{Vertex, VERTICES};
-use wgpu::{
-    util::DeviceExt, Adapter, Device, Instance, Queue, RenderPipeline, Surface, SurfaceCapabilities,
-};
+use wgpu::{util::DeviceExt, RenderPipeline};
 use winit::{
     application::ApplicationHandler,
     dpi::{LogicalSize, PhysicalSize, Size},
@@ -17,139 +14,9 @@ use winit::{
 
 mod vertex;
 
-// GPU Context handling
-struct GpuContext<'a> {
-    device: Device,
-    queue: Queue,
-    surface: Surface<'a>,
-    config: wgpu::SurfaceConfiguration,
-}
-
-impl<'a> GpuContext<'a> {
-    pub fn new(window: &'a Window) -> Result<Self> {
-        let instance = Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            ..Default::default()
-        });
-
-        let surface = instance.create_surface(window)?;
-        let adapter = Self::create_adapter(&instance, &surface)?;
-        let (device, queue) = Self::create_device(&adapter)?;
-        let surface_caps = surface.get_capabilities(&adapter);
-        let config = Self::create_surface_config(window.inner_size(), surface_caps);
-
-        surface.configure(&device, &config);
-
-        Ok(Self {
-            device,
-            queue,
-            surface,
-            config,
-        })
-    }
-
-    fn create_adapter(instance: &Instance, surface: &Surface) -> Result<Adapter> {
-        instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(surface),
-                force_fallback_adapter: false,
-            })
-            .block_on()
-            .ok_or_else(|| anyhow::anyhow!("No adapter found"))
-    }
-
-    fn create_device(adapter: &Adapter) -> Result<(Device, Queue)> {
-        adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                    memory_hints: wgpu::MemoryHints::default(),
-                    label: None,
-                },
-                None,
-            )
-            .block_on()
-            .map_err(|e| e.into())
-    }
-
-    fn create_surface_config(
-        size: PhysicalSize<u32>,
-        capabilities: SurfaceCapabilities,
-    ) -> wgpu::SurfaceConfiguration {
-        let formats = capabilities.formats.iter().map(|f| *f).collect::<Vec<_>>();
-        let supports_hdr = formats.iter().any(|format| {
-            matches!(
-                format,
-                wgpu::TextureFormat::Bgra8UnormSrgb
-                    | wgpu::TextureFormat::Rgba16Float
-                    | wgpu::TextureFormat::Rgba32Float // Add other HDR formats as needed
-            )
-        });
-        info!("Surface supports HDR: {}", supports_hdr);
-        // List all formats supported by the surface
-        info!("Supported surface formats: {:#?}", formats);
-        let format = formats
-            .iter()
-            .cloned()
-            .max_by(|a, b| {
-                let a_score = GpuContext::format_score(*a);
-                let b_score = GpuContext::format_score(*b);
-                a_score.cmp(&b_score)
-            })
-            .unwrap_or(formats[0].clone());
-        info!("Using surface format: {:?}", format);
-
-        wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format,
-            width: size.width,
-            height: size.height,
-            present_mode: capabilities
-                .present_modes
-                .iter()
-                .cloned()
-                .max_by(|a, b| Self::present_mode_score(*a).cmp(&Self::present_mode_score(*b)))
-                .unwrap_or(wgpu::PresentMode::AutoNoVsync),
-            alpha_mode: capabilities.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        }
-    }
-
-    fn format_score(format: wgpu::TextureFormat) -> u32 {
-        match format {
-            // Assign higher scores to preferred formats
-            wgpu::TextureFormat::Rgba16Float => 9,
-            wgpu::TextureFormat::Rgba32Float => 8,
-            wgpu::TextureFormat::Bgra8UnormSrgb => 7,
-            wgpu::TextureFormat::Rgba8UnormSrgb => 6,
-            _ => 0, // Default score for other formats
-        }
-    }
-
-    fn present_mode_score(present_mode: wgpu::PresentMode) -> u32 {
-        match present_mode {
-            // Assign higher scores to preferred present modes
-            wgpu::PresentMode::Fifo => 10,
-            wgpu::PresentMode::Mailbox => 9,
-            wgpu::PresentMode::Immediate => 8,
-            _ => 0, // Default score for other present modes
-        }
-    }
-
-    fn resize(&mut self, size: PhysicalSize<u32>) {
-        self.config.width = size.width;
-        self.config.height = size.height;
-        self.surface.configure(&self.device, &self.config);
-    }
-}
-
 // Renderer handles all drawing operations
 struct Renderer {
-    _window: Arc<Window>, // Keep window alive as long as renderer exists
-    gpu: GpuContext<'static>,
+    gpu: GpuContext,
     render_pipeline: RenderPipeline,
     vertex_buffer: wgpu::Buffer,
     num_vertices: u32,
@@ -157,7 +24,7 @@ struct Renderer {
 
 impl Renderer {
     pub fn new(window: Arc<Window>) -> Result<Self> {
-        let gpu: GpuContext<'_> = unsafe { std::mem::transmute(GpuContext::new(&window)?) };
+        let gpu = GpuContext::new(window)?;
 
         let shader = gpu
             .device
@@ -225,7 +92,6 @@ impl Renderer {
         let num_vertices = VERTICES.len() as u32;
 
         Ok(Self {
-            _window: window,
             gpu,
             render_pipeline,
             vertex_buffer,
@@ -280,7 +146,7 @@ impl Renderer {
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
-        self.gpu.resize(new_size);
+        self.gpu.resize(&new_size);
     }
 }
 
@@ -1,14 +1,11 @@
 use anyhow::Result;
 use pipeline::{GPUPipeline, GPUPipelineBuilder};
-use pollster::FutureExt;
+use playground_core::GpuContext;
 use std::sync::Arc;
-use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, EnvFilter};
 use tracing_tracy::client::{frame_name, ProfiledAllocator};
 use vertex::{DepthVertex, Vertex, DEPTH_VERTICES, VERTICES};
-use wgpu::{
-    util::DeviceExt, Adapter, Device, Instance, Queue, RenderPipeline, Surface, SurfaceCapabilities,
-};
+use wgpu::{util::DeviceExt, RenderPipeline};
 use winit::{
     application::ApplicationHandler,
     dpi::{LogicalSize, PhysicalSize, Size},
@@ -26,33 +23,15 @@ mod texture;
 mod uniform;
 mod vertex;
 
-// GPU Context handling
-struct GpuContext<'a> {
-    device: Device,
-    queue: Queue,
-    surface: Surface<'a>,
-    depth: wgpu::Texture,
-    depth_view: wgpu::TextureView,
-    depth_sampler: wgpu::Sampler,
-    config: wgpu::SurfaceConfiguration,
+/// Depth buffer of the scene pass, sampled by the depth pass to show it.
+struct DepthTarget {
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
 }
 
-impl<'a> GpuContext<'a> {
-    pub fn new(window: &'a Window) -> Result<Self> {
-        let instance = Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
-            ..Default::default()
-        });
-
-        let surface = instance.create_surface(window)?;
-        let adapter = Self::create_adapter(&instance, &surface)?;
-        let (device, queue) = Self::create_device(&adapter)?;
-        let surface_caps = surface.get_capabilities(&adapter);
-        let config = Self::create_surface_config(window.inner_size(), surface_caps);
-
-        surface.configure(&device, &config);
-
-        let depth = device.create_texture(&wgpu::TextureDescriptor {
+impl DepthTarget {
+    fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Depth Texture"),
             size: wgpu::Extent3d {
                 width: config.width,
@@ -66,8 +45,8 @@ impl<'a> GpuContext<'a> {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
-        let depth_view = depth.create_view(&Default::default());
-        let depth_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        let view = texture.create_view(&Default::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("Depth Sampler"),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
@@ -77,138 +56,14 @@ impl<'a> GpuContext<'a> {
             mipmap_filter: wgpu::FilterMode::Linear,
             ..Default::default()
         });
-
-        Ok(Self {
-            device,
-            queue,
-            surface,
-            depth,
-            depth_view,
-            depth_sampler,
-            config,
-        })
-    }
-
-    fn create_adapter(instance: &Instance, surface: &Surface) -> Result<Adapter> {
-        instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(surface),
-                force_fallback_adapter: false,
-            })
-            .block_on()
-            .ok_or_else(|| anyhow::anyhow!("No adapter found"))
-    }
-
-    fn create_device(adapter: &Adapter) -> Result<(Device, Queue)> {
-        adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                    memory_hints: wgpu::MemoryHints::default(),
-                    label: None,
-                },
-                None,
-            )
-            .block_on()
-            .map_err(|e| e.into())
-    }
-
-    fn create_surface_config(
-        size: PhysicalSize<u32>,
-        capabilities: SurfaceCapabilities,
-    ) -> wgpu::SurfaceConfiguration {
-        let formats = capabilities.formats.iter().map(|f| *f).collect::<Vec<_>>();
-        let supports_hdr = formats.iter().any(|format| {
-            matches!(
-                format,
-                wgpu::TextureFormat::Bgra8UnormSrgb
-                    | wgpu::TextureFormat::Rgba16Float
-                    | wgpu::TextureFormat::Rgba32Float // Add other HDR formats as needed
-            )
-        });
-        info!("Surface supports HDR: {}", supports_hdr);
-        // List all formats supported by the surface
-        info!("Supported surface formats: {:#?}", formats);
-        let format = formats
-            .iter()
-            .cloned()
-            .max_by(|a, b| {
-                let a_score = GpuContext::format_score(*a);
-                let b_score = GpuContext::format_score(*b);
-                a_score.cmp(&b_score)
-            })
-            .unwrap_or(formats[0].clone());
-        info!("Using surface format: {:?}", format);
-
-        wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format,
-            width: size.width,
-            height: size.height,
-            present_mode: capabilities
-                .present_modes
-                .iter()
-                .cloned()
-                .max_by(|a, b| Self::present_mode_score(*a).cmp(&Self::present_mode_score(*b)))
-                .unwrap_or(wgpu::PresentMode::AutoNoVsync),
-            alpha_mode: capabilities.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        }
-    }
-
-    fn format_score(format: wgpu::TextureFormat) -> u32 {
-        match format {
-            // Assign higher scores to preferred formats
-            wgpu::TextureFormat::Rgba16Float => 9,
-            wgpu::TextureFormat::Rgba32Float => 8,
-            wgpu::TextureFormat::Bgra8UnormSrgb => 7,
-            wgpu::TextureFormat::Rgba8UnormSrgb => 6,
-            _ => 0, // Default score for other formats
-        }
-    }
-
-    fn present_mode_score(present_mode: wgpu::PresentMode) -> u32 {
-        match present_mode {
-            wgpu::PresentMode::AutoVsync => 11,
-            wgpu::PresentMode::Mailbox => 10,
-            wgpu::PresentMode::Fifo => 9,
-            wgpu::PresentMode::Immediate => 8,
-            wgpu::PresentMode::AutoNoVsync => 7,
-            _ => 0,
-        }
-    }
-
-    fn resize(&mut self, size: PhysicalSize<u32>) {
-        self.config.width = size.width;
-        self.config.height = size.height;
-        self.surface.configure(&self.device, &self.config);
-
-        // Recreate depth texture
-        self.depth = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Depth Texture"),
-            size: wgpu::Extent3d {
-                width: self.config.width,
-                height: self.config.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        });
-        self.depth_view = self.depth.create_view(&Default::default());
+        Self { view, sampler }
     }
 }
 
 // Renderer handles all drawing operations
 struct Renderer {
-    _window: Arc<Window>, // Keep window alive as long as renderer exists
-    gpu: GpuContext<'static>,
+    gpu: GpuContext,
+    depth: DepthTarget,
     render_pipeline: RenderPipeline,
     vertex_buffer: wgpu::Buffer,
     num_vertices: u32,
@@ -230,7 +85,8 @@ struct Renderer {
 
 impl Renderer {
     pub fn new(window: Arc<Window>) -> Result<Self> {
-        let gpu: GpuContext<'_> = unsafe { std::mem::transmute(GpuContext::new(&window)?) };
+        let gpu = GpuContext::new(window)?;
+        let depth = DepthTarget::new(&gpu.device, &gpu.config);
 
         // ================== TEXTURE ==================
         let diffuse_bytes = include_bytes!("../../assets/stone.png");
@@ -400,11 +256,11 @@ impl Renderer {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&gpu.depth_view),
+                    resource: wgpu::BindingResource::TextureView(&depth.view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&gpu.depth_sampler),
+                    resource: wgpu::BindingResource::Sampler(&depth.sampler),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
@@ -442,8 +298,8 @@ impl Renderer {
         let depth_num_vertices = DEPTH_VERTICES.len() as u32;
 
         Ok(Self {
-            _window: window,
             gpu,
+            depth,
             render_pipeline,
             vertex_buffer,
             num_vertices,
@@ -503,7 +359,7 @@ impl Renderer {
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.gpu.depth_view,
+                    view: &self.depth.view,
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Clear(1.0),
                         store: wgpu::StoreOp::Store,
@@ -563,9 +419,14 @@ impl Renderer {
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
-        self.gpu.resize(new_size);
+        // Minimized windows report a zero size, which can't be configured
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
+        }
+        self.gpu.resize(&new_size);
 
         // Recreate all resources related to the depth texture
+        self.depth = DepthTarget::new(&self.gpu.device, &self.gpu.config);
         self.depth_bind_group = self
             .gpu
             .device
@@ -574,11 +435,11 @@ impl Renderer {
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&self.gpu.depth_view),
+                        resource: wgpu::BindingResource::TextureView(&self.depth.view),
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&self.gpu.depth_sampler),
+                        resource: wgpu::BindingResource::Sampler(&self.depth.sampler),
                     },
                     wgpu::BindGroupEntry {
                         binding: 2,
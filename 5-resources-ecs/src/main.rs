@@ -1,14 +1,14 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bevy_ecs::{
     component::Component,
-    event::{Event, EventReader, Events},
+    event::{EventReader, Events},
     observer::{Observer, Trigger, TriggerEvent},
     schedule::Schedule,
     system::{Res, ResMut, Resource, RunSystemOnce},
     world::World,
 };
 use debouncer::Debouncer;
-use gpu::{setup_gpu, GpuContext};
+use gpu::{setup_gpu, shutdown_gpu, GpuContext};
 use pipeline::{
     depth::{setup_depth, DepthTexture},
     diffuse::setup_diffuse,
@@ -16,6 +16,7 @@ use pipeline::{
     render::setup_rendering,
     GPUPipeline, GPUPipelineBuilder,
 };
+use playground_app::{App, WindowTriggerEvent};
 use pollster::FutureExt;
 use std::{sync::Arc, time::Duration};
 use time::{setup_time, TimeContext};
@@ -27,13 +28,7 @@ use vertex::{setup_vertex_buffers, DepthVertex, Vertex, DEPTH_VERTICES, VERTICES
 use wgpu::{
     util::DeviceExt, Adapter, Device, Instance, Queue, RenderPipeline, Surface, SurfaceCapabilities,
 };
-use winit::{
-    application::ApplicationHandler,
-    dpi::{LogicalSize, PhysicalSize, Size},
-    event::WindowEvent,
-    event_loop::{ActiveEventLoop, EventLoop},
-    window::{Window, WindowId},
-};
+use winit::{dpi::PhysicalSize, event::WindowEvent, window::Window};
 
 #[global_allocator]
 static GLOBAL: ProfiledAllocator<std::alloc::System> =
@@ -61,11 +56,6 @@ impl Default for ResizeState {
     }
 }
 
-#[derive(Event)]
-pub struct ResizeEvent {
-    pub size: PhysicalSize<u32>,
-}
-
 fn resize_system(
     mut resize_state: ResMut<ResizeState>,
     mut gpu: ResMut<GpuContext>,
@@ -88,100 +78,28 @@ fn resize_system(
     }
 }
 
-// Application handling
-struct Application {
-    world: World,
-    schedule: Schedule,
-}
-
-impl Application {
-    pub fn new() -> Self {
-        let world = World::default();
-
-        Self {
-            world,
-            schedule: Schedule::default(),
-        }
-    }
-}
-
-impl ApplicationHandler for Application {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let window = event_loop
-            .create_window(
-                Window::default_attributes()
-                    .with_title("WGPU Engine")
-                    .with_inner_size(Size::Logical(LogicalSize::new(800.0, 600.0)))
-                    .with_min_inner_size(Size::Logical(LogicalSize::new(400.0, 300.0))),
-            )
-            .expect("Failed to create window");
-
-        setup_time(&mut self.world, &mut self.schedule).expect("Failed to setup time");
-        setup_gpu(&mut self.world, &mut self.schedule, window).expect("Failed to setup GPU");
-        setup_uniforms(&mut self.world, &mut self.schedule).expect("Failed to setup uniforms");
-        setup_frame_buffer(&mut self.world, &mut self.schedule)
-            .expect("Failed to setup frame buffer");
-        setup_diffuse(&mut self.world, &mut self.schedule)
-            .expect("Failed to setup diffuse pipeline");
-        setup_depth(&mut self.world, &mut self.schedule).expect("Failed to setup depth pipeline");
-        setup_vertex_buffers(&mut self.world, &mut self.schedule)
-            .expect("Failed to setup vertex buffers");
-        setup_present(&mut self.world, &mut self.schedule)
-            .expect("Failed to setup present pipeline");
-        setup_rendering(&mut self.world, &mut self.schedule).expect("Failed to setup rendering");
-
-        self.world.insert_resource(ResizeState::default());
-        self.world.add_observer(
-            |trigger: Trigger<ResizeEvent>, mut resize_state: ResMut<ResizeState>| {
-                let size = (*trigger.event()).size;
+// =============================== SETUP ===============================
+fn setup(world: &mut World, schedule: &mut Schedule, window: Arc<Window>) -> Result<()> {
+    setup_time(world, schedule).context("Failed to setup time")?;
+    setup_gpu(world, schedule, window).context("Failed to setup GPU")?;
+    setup_uniforms(world, schedule).context("Failed to setup uniforms")?;
+    setup_frame_buffer(world, schedule).context("Failed to setup frame buffer")?;
+    setup_diffuse(world, schedule).context("Failed to setup diffuse pipeline")?;
+    setup_depth(world, schedule).context("Failed to setup depth pipeline")?;
+    setup_vertex_buffers(world, schedule).context("Failed to setup vertex buffers")?;
+    setup_present(world, schedule).context("Failed to setup present pipeline")?;
+    setup_rendering(world, schedule).context("Failed to setup rendering")?;
+
+    world.insert_resource(ResizeState::default());
+    world.add_observer(
+        |trigger: Trigger<WindowTriggerEvent>, mut resize_state: ResMut<ResizeState>| {
+            if let WindowEvent::Resized(size) = trigger.event().event {
                 resize_state.debouncer.push(size);
-            },
-        );
-        self.schedule.add_systems(resize_system);
-        self.world.flush();
-    }
-
-    fn window_event(
-        &mut self,
-        event_loop: &ActiveEventLoop,
-        window_id: WindowId,
-        event: WindowEvent,
-    ) {
-        let current_window_id = {
-            let gpu = self
-                .world
-                .get_resource::<GpuContext>()
-                .expect("GpuContext not found");
-            gpu.window.id()
-        };
-
-        if current_window_id == window_id {
-            match event {
-                WindowEvent::CloseRequested => event_loop.exit(),
-                WindowEvent::Resized(size) => {
-                    self.world.trigger(ResizeEvent { size });
-                }
-                WindowEvent::RedrawRequested => {
-                    self.schedule.run(&mut self.world);
-                }
-                _ => {}
             }
-        }
-    }
-
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        let gpu = self
-            .world
-            .get_resource::<GpuContext>()
-            .expect("GpuContext not found");
-        gpu.window.request_redraw();
-    }
-}
+        },
+    );
+    schedule.add_systems(resize_system);
 
-pub async fn run() -> Result<()> {
-    let event_loop = EventLoop::new()?;
-    let mut app = Application::new();
-    event_loop.run_app(&mut app)?;
     Ok(())
 }
 
@@ -202,6 +120,8 @@ fn main() -> Result<()> {
     .expect("setup tracing");
     better_panic::install();
 
-    pollster::block_on(run())?;
+    App::new("WGPU Engine")
+        .on_shutdown(shutdown_gpu)
+        .run(setup)?;
     Ok(())
 }
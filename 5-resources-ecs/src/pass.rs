@@ -1,4 +1,8 @@
-use anyhow::{Context, Result};
+#[derive(Debug, thiserror::Error)]
+pub enum PassError {
+    #[error("No color attachment provided")]
+    NoColorAttachment,
+}
 
 pub struct RenderPassBuilder<'a> {
     encoder: &'a mut wgpu::CommandEncoder,
@@ -32,8 +36,8 @@ impl<'a> RenderPassBuilder<'a> {
         self
     }
 
-    pub fn build(self) -> Result<wgpu::RenderPass<'a>> {
-        let color_view = self.color_view.context("No color attachment provided")?;
+    pub fn build(self) -> Result<wgpu::RenderPass<'a>, PassError> {
+        let color_view = self.color_view.ok_or(PassError::NoColorAttachment)?;
 
         let depth_stencil_attachment =
             self.depth_view.map(
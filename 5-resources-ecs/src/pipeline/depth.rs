@@ -197,8 +197,7 @@ impl DepthPipeline {
             .depth_stencil_state(None)
             .default_multisample_state()
             .default_primitive_state()
-            .build()
-            .map_err(|e| anyhow::anyhow!(e))?;
+            .build()?;
 
         let result = Self {
             shader: depth_shader,
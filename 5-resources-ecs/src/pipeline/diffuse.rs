@@ -128,8 +128,7 @@ impl DiffusePipeline {
             .default_depth_stencil_state()
             .default_multisample_state()
             .default_primitive_state()
-            .build()
-            .map_err(|e| anyhow::anyhow!(e))?;
+            .build()?;
 
         Ok(Self {
             pipeline: diffuse_pipeline,
@@ -24,6 +24,12 @@ impl GPUPipeline {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineError {
+    #[error("Vertex shader is required")]
+    MissingVertexShader,
+}
+
 // Define the GPUPipelineBuilder struct
 pub struct GPUPipelineBuilder<'a> {
     device: &'a wgpu::Device,
@@ -147,11 +153,10 @@ impl<'a> GPUPipelineBuilder<'a> {
         self
     }
 
-    pub fn build(self) -> Result<GPUPipeline, &'static str> {
-        if self.vertex_shader.is_none() {
-            return Err("Vertex shader is required");
-        }
-        let vertex_shader = self.vertex_shader.expect("Vertex shader is required");
+    pub fn build(self) -> Result<GPUPipeline, PipelineError> {
+        let Some(vertex_shader) = self.vertex_shader else {
+            return Err(PipelineError::MissingVertexShader);
+        };
 
         let layout = self
             .device
@@ -212,8 +212,7 @@ impl PresentPipeline {
             .depth_stencil_state(None)
             .default_multisample_state()
             .default_primitive_state()
-            .build()
-            .map_err(|e| anyhow::anyhow!(e))?;
+            .build()?;
 
         Ok(Self { pipeline })
     }
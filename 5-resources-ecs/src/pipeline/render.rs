@@ -1,13 +1,17 @@
 use anyhow::Result;
-use bevy_ecs::{schedule::Schedule, system::Res, world::World};
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut},
+    world::World,
+};
+use playground_app::FrameCapture;
 use tracing::error;
 use tracing_tracy::client::frame_name;
 
 use crate::{
     gpu::GpuContext,
     pass::RenderPassBuilder,
-    time::TimeContext,
-    vertex::{self, VertexBuffers},
+    vertex::{self, spin_system, Spin, VertexBuffers},
 };
 
 use super::{
@@ -17,12 +21,12 @@ use super::{
 };
 
 pub fn setup_rendering(_world: &mut World, schedule: &mut Schedule) -> Result<()> {
-    schedule.add_systems(render_system);
+    schedule.add_systems(render_system.after(spin_system));
     Ok(())
 }
 
 pub fn render_system(
-    time: Res<TimeContext>,
+    spin: Res<Spin>,
     gpu: Res<GpuContext>,
     depth: Res<DepthTexture>,
     diffuse_bind_group: Res<DiffuseBindGroup>,
@@ -33,8 +37,9 @@ pub fn render_system(
     present_pipeline: Res<PresentPipeline>,
     vertex_buffers: Res<VertexBuffers>,
     frame_buffer: Res<FrameBuffer>,
+    mut capture: Option<ResMut<FrameCapture>>,
 ) {
-    let f = || -> Result<()> {
+    let mut f = || -> Result<()> {
         let _render_guard = tracing_tracy::client::Client::running()
             .expect("client must be running")
             .non_continuous_frame(frame_name!("rendering"));
@@ -43,7 +48,7 @@ pub fn render_system(
         let view = output.texture.create_view(&Default::default());
 
         // Update the vertex buffer with new data
-        let new_vertices = vertex::rotated_vertices(time.total);
+        let new_vertices = vertex::rotated_vertices(spin.angle);
         gpu.queue.write_buffer(
             &vertex_buffers.vertex_buffer,
             0,
@@ -97,6 +102,9 @@ pub fn render_system(
 
         gpu.queue.submit(std::iter::once(encoder.finish()));
         drop(_render_guard);
+        if let Some(capture) = capture.as_mut().filter(|capture| capture.is_due()) {
+            capture.capture(&gpu.device, &gpu.queue, &output.texture);
+        }
 
         let _present_guard = tracing_tracy::client::Client::running()
             .expect("client must be running")
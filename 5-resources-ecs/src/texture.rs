@@ -1,8 +1,17 @@
-use anyhow::*;
 use image::GenericImageView;
 use tracing::info;
 use wgpu::util::DeviceExt;
 
+#[derive(Debug, thiserror::Error)]
+pub enum TextureError {
+    #[error("Failed to decode image '{label}'")]
+    Decode {
+        label: String,
+        #[source]
+        source: image::ImageError,
+    },
+}
+
 pub struct Texture {
     pub label: String,
     #[allow(unused)]
@@ -18,8 +27,11 @@ impl Texture {
         queue: &wgpu::Queue,
         bytes: &[u8],
         label: &str,
-    ) -> Result<Self> {
-        let img = image::load_from_memory(bytes)?;
+    ) -> Result<Self, TextureError> {
+        let img = image::load_from_memory(bytes).map_err(|source| TextureError::Decode {
+            label: label.to_string(),
+            source,
+        })?;
         Self::from_image(device, queue, &img, Some(label))
     }
 
@@ -28,7 +40,7 @@ impl Texture {
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
-    ) -> Result<Self> {
+    ) -> Result<Self, TextureError> {
         let rgba = img.to_rgba8();
         let dimensions = img.dimensions();
 
@@ -7,6 +7,8 @@ use bevy_ecs::{
     world::World,
 };
 
+use playground_app::FrameCapture;
+
 use crate::gpu::GpuContext;
 
 pub fn setup_time(world: &mut World, schedule: &mut Schedule) -> Result<()> {
@@ -43,6 +45,12 @@ impl TimeContext {
         self.total += delta;
         self.last_frame = now;
     }
+    /// Advances by a fixed `delta` instead of the wall clock.
+    pub fn step(&mut self, delta: f32) {
+        self.delta = delta;
+        self.total += delta;
+        self.last_frame = Instant::now();
+    }
 }
 
 #[derive(Resource)]
@@ -77,8 +85,12 @@ pub fn time_system(
     mut time: ResMut<TimeContext>,
     mut time_history: ResMut<TimeHistory>,
     gpu: Res<GpuContext>,
+    capture: Option<Res<FrameCapture>>,
 ) {
-    time.update();
+    match capture {
+        Some(_) => time.step(FrameCapture::TIME_STEP),
+        None => time.update(),
+    }
     time_history.update(time.delta);
 
     let average_frame_time = time_history.average_frame_time();
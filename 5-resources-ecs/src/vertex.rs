@@ -1,12 +1,23 @@
 use anyhow::Result;
 use bevy_ecs::{
-    schedule::Schedule,
+    schedule::{IntoSystemConfigs, Schedule},
     system::{Res, ResMut, Resource},
     world::World,
 };
+use playground_app::Input;
+use tracing::info;
 use wgpu::util::DeviceExt;
+use winit::{event::MouseButton, keyboard::KeyCode};
 
-use crate::{gpu::GpuContext, time::TimeContext};
+use crate::{
+    gpu::GpuContext,
+    time::{time_system, TimeContext},
+};
+
+/// How far dragging across the window turns the triangle.
+const DRAG_RADIANS_PER_PIXEL: f32 = 0.01;
+/// Speed change per scrolled line.
+const SCROLL_SPEED_FACTOR: f32 = 1.25;
 
 pub fn setup_vertex_buffers(world: &mut World, schedule: &mut Schedule) -> Result<()> {
     let gpu = world
@@ -38,18 +49,44 @@ pub fn setup_vertex_buffers(world: &mut World, schedule: &mut Schedule) -> Resul
         num_depth_vertices,
     });
 
-    schedule.add_systems(rotate_vertices_system);
+    world.insert_resource(Spin::default());
+
+    schedule.add_systems((
+        spin_system.after(time_system),
+        rotate_vertices_system.after(spin_system),
+    ));
 
     Ok(())
 }
 
+/// Space pauses the spin, dragging with the left button turns the triangle
+/// by hand and scrolling changes how fast it spins.
+pub fn spin_system(time: Res<TimeContext>, input: Res<Input>, mut spin: ResMut<Spin>) {
+    if input.keys.just_pressed(KeyCode::Space) {
+        spin.paused = !spin.paused;
+    }
+    spin.speed = (spin.speed * SCROLL_SPEED_FACTOR.powf(input.scroll.y))
+        .clamp(Spin::MIN_SPEED, Spin::MAX_SPEED);
+    if input.mouse_buttons.pressed(MouseButton::Left) {
+        spin.angle += input.cursor_delta.x * DRAG_RADIANS_PER_PIXEL;
+    } else if !spin.paused {
+        spin.angle += spin.speed * time.delta;
+    }
+    if input.mouse_buttons.just_released(MouseButton::Left) {
+        info!(
+            "Triangle turned to {:.0} degrees",
+            spin.angle.to_degrees().rem_euclid(360.0)
+        );
+    }
+}
+
 pub fn rotate_vertices_system(
     gpu: Res<GpuContext>,
-    time: Res<TimeContext>,
+    spin: Res<Spin>,
     vertex_buffers: ResMut<VertexBuffers>,
 ) {
     // Update the vertex buffer with new data
-    let new_vertices = rotated_vertices(time.total);
+    let new_vertices = rotated_vertices(spin.angle);
     gpu.queue.write_buffer(
         &vertex_buffers.vertex_buffer,
         0,
@@ -65,6 +102,29 @@ pub struct VertexBuffers {
     pub num_depth_vertices: u32,
 }
 
+/// Rotation of the triangle around the vertical axis.
+#[derive(Resource)]
+pub struct Spin {
+    /// Radians.
+    pub angle: f32,
+    /// Radians a second.
+    pub speed: f32,
+    pub paused: bool,
+}
+impl Spin {
+    pub const MIN_SPEED: f32 = 0.1;
+    pub const MAX_SPEED: f32 = 8.0 * std::f32::consts::PI;
+}
+impl Default for Spin {
+    fn default() -> Self {
+        Self {
+            angle: 0.0,
+            speed: std::f32::consts::PI,
+            paused: false,
+        }
+    }
+}
+
 // =================================== VERTEX ===================================
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -107,8 +167,8 @@ pub const VERTICES: &[Vertex] = &[
     },
 ];
 
-pub fn rotated_vertices(time: f32) -> [Vertex; 3] {
-    let rotation = glam::Mat4::from_rotation_y(time * std::f32::consts::PI);
+pub fn rotated_vertices(angle: f32) -> [Vertex; 3] {
+    let rotation = glam::Mat4::from_rotation_y(angle);
     // Create orthographic projection matrix
     let ortho = glam::Mat4::orthographic_rh(-1.0, 1.0, -1.0, 1.0, -1.5, 1.5);
 
@@ -0,0 +1,736 @@
+//! GPU assets that can be dropped and loaded again. Every asset keeps what it
+//! was made from on the CPU, so the GPU side can be evicted whenever the
+//! memory budget runs out and brought back the next time it is used.
+//!
+//! Users call [`AssetServer::use_texture`] or [`AssetServer::use_mesh`] every
+//! frame they need an asset, before rendering. That loads it if needed and
+//! protects it from eviction for the frame. Large textures start out with
+//! their small mips only and stream in the rest over the next frames,
+//! [`TextureAsset::version`] changes with every level so bind groups know
+//! when to be rebuilt.
+//!
+//! Textures from `assets/` load their baked version from `baked/` instead
+//! when `cargo xtask assets` made one and the GPU samples BC formats. Those
+//! come with their mips compressed and go up in one piece, without streaming.
+//! A bake that is stale or damaged is skipped with the reason shown in the
+//! asset panel, which also says when everything runs from the raw assets.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use tracing::{info, info_span, warn};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    baked::{read_ktx2, BakedManifest, BAKED_DIR},
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pipeline::{mesh::GpuMesh, render::submit_system, ui::UiPanels},
+    texture::{mip_chain, Texture},
+    vertex::{unweld, IndexBuffer, MeshVertex},
+};
+
+const MIB: u64 = 1024 * 1024;
+/// Textures up to this size load completely right away.
+const STREAM_MIN_SIZE: u32 = 256;
+/// Mips up to this size load right away, the larger ones are streamed.
+const STREAM_INITIAL_SIZE: u32 = 64;
+
+pub fn setup_assets(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let (baked, baked_error) = match BakedManifest::load(Path::new(BAKED_DIR)) {
+        Ok(Some(manifest)) => {
+            info!("Using {} baked assets", manifest.entries.len());
+            (Some(manifest), None)
+        }
+        Ok(None) => {
+            info!("No baked assets, run `cargo xtask assets` to bake them");
+            (None, None)
+        }
+        Err(e) => {
+            warn!("Ignoring baked assets: {:?}", e);
+            (None, Some(format!("{:#}", e)))
+        }
+    };
+    world.insert_resource(AssetServer {
+        baked,
+        baked_error,
+        ..Default::default()
+    });
+    world.insert_resource(AssetSettings::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(asset_panel);
+
+    schedule.add_systems(
+        (texture_streaming_system, asset_eviction_system)
+            .chain()
+            .after(submit_system),
+    );
+
+    Ok(())
+}
+
+// =============================== ASSETS ===============================
+#[derive(Debug, thiserror::Error)]
+pub enum AssetError {
+    #[error("Unknown texture {0:?}")]
+    UnknownTexture(TextureHandle),
+    #[error("Unknown mesh {0:?}")]
+    UnknownMesh(MeshHandle),
+    /// Known, but not used this frame and evicted since.
+    #[error("Texture {0:?} is not resident")]
+    TextureNotResident(TextureHandle),
+    #[error("Mesh {0:?} is not resident")]
+    MeshNotResident(MeshHandle),
+    #[error("Failed to decode texture '{label}'")]
+    Decode {
+        label: String,
+        #[source]
+        source: image::ImageError,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshHandle(u32);
+
+/// Where a texture is loaded from, again after every eviction.
+#[derive(Clone, Debug)]
+pub enum TextureSource {
+    /// `name` in `assets/`, from `baked/` when it was baked and otherwise
+    /// decoded from `embedded`, the image compiled into the binary.
+    Asset {
+        name: &'static str,
+        embedded: &'static [u8],
+    },
+    /// An image on disk, like the textures a model's materials name.
+    File(PathBuf),
+}
+
+pub struct TextureAsset {
+    pub label: String,
+    pub source: TextureSource,
+    /// `None` while evicted.
+    pub gpu: Option<Texture>,
+    /// Size of the last load, 0 before the first one.
+    pub bytes: u64,
+    pub last_used: u64,
+    pub loads: u32,
+    /// Changes whenever `gpu` or its view does.
+    pub version: u32,
+    /// Finest level the view includes.
+    pub resident_mip: u32,
+    pub mip_count: u32,
+    /// Whether the last load came from `baked/`.
+    pub baked: bool,
+    /// Why the last load didn't, when there was a bake to load.
+    pub baked_error: Option<String>,
+    /// Levels still to upload, finest first so the next one is last.
+    pending: Vec<PendingMip>,
+}
+
+struct PendingMip {
+    level: u32,
+    image: image::RgbaImage,
+    next_row: u32,
+}
+
+pub struct MeshAsset {
+    pub label: String,
+    pub vertices: Vec<MeshVertex>,
+    /// `None` when `vertices` is a plain triangle list.
+    pub indices: Option<Vec<u32>>,
+    pub gpu: Option<GpuMesh>,
+    pub bytes: u64,
+    pub last_used: u64,
+    pub loads: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AssetStats {
+    pub loads: u64,
+    pub evictions: u64,
+    pub evicted_bytes: u64,
+    pub streamed_bytes: u64,
+}
+
+#[derive(Resource, Default)]
+pub struct AssetServer {
+    textures: Vec<TextureAsset>,
+    meshes: Vec<MeshAsset>,
+    /// Advanced after rendering, so everything used in a frame shares it.
+    frame: u64,
+    pub stats: AssetStats,
+    /// `None` when nothing was baked or the manifest was rejected.
+    pub baked: Option<BakedManifest>,
+    /// Why the manifest was rejected.
+    pub baked_error: Option<String>,
+}
+impl AssetServer {
+    /// Registers a texture without loading it yet.
+    pub fn add_texture(&mut self, label: &str, source: TextureSource) -> TextureHandle {
+        self.textures.push(TextureAsset {
+            label: label.to_string(),
+            source,
+            gpu: None,
+            bytes: 0,
+            last_used: 0,
+            loads: 0,
+            version: 0,
+            resident_mip: 0,
+            mip_count: 0,
+            baked: false,
+            baked_error: None,
+            pending: Vec::new(),
+        });
+        TextureHandle(self.textures.len() as u32 - 1)
+    }
+
+    /// Registers a mesh without uploading it yet. Without `indices` the
+    /// vertices are a plain triangle list, see [`weld`](crate::vertex::weld)
+    /// for making indices.
+    pub fn add_mesh(
+        &mut self,
+        label: &str,
+        vertices: Vec<MeshVertex>,
+        indices: Option<Vec<u32>>,
+    ) -> MeshHandle {
+        let bytes = std::mem::size_of_val(vertices.as_slice()) as u64
+            + indices.as_ref().map_or(0, |indices| {
+                std::mem::size_of_val(indices.as_slice()) as u64
+            });
+        self.meshes.push(MeshAsset {
+            label: label.to_string(),
+            vertices,
+            indices,
+            gpu: None,
+            bytes,
+            last_used: 0,
+            loads: 0,
+        });
+        MeshHandle(self.meshes.len() as u32 - 1)
+    }
+
+    /// Keeps the texture resident this frame, loading it if it isn't.
+    pub fn use_texture(
+        &mut self,
+        gpu: &GpuContext,
+        handle: TextureHandle,
+    ) -> Result<&TextureAsset, AssetError> {
+        let frame = self.frame;
+        let asset = self
+            .textures
+            .get_mut(handle.0 as usize)
+            .ok_or(AssetError::UnknownTexture(handle))?;
+        asset.last_used = frame;
+        if asset.gpu.is_none() {
+            let _span = info_span!("load_texture", label = asset.label.as_str()).entered();
+            let compression = gpu
+                .device
+                .features()
+                .contains(wgpu::Features::TEXTURE_COMPRESSION_BC);
+            let baked = match (&asset.source, &self.baked) {
+                (TextureSource::Asset { name, embedded }, Some(manifest)) if compression => {
+                    manifest.entry("texture", name).map(|entry| {
+                        manifest
+                            .read(entry, embedded)
+                            .and_then(|bytes| read_ktx2(&bytes))
+                    })
+                }
+                _ => None,
+            };
+            if let Some(loaded) = baked {
+                match loaded {
+                    Ok(baked) => {
+                        let texture = Texture::compressed(
+                            &gpu.device,
+                            &gpu.queue,
+                            baked.format,
+                            baked.width,
+                            baked.height,
+                            &baked.levels,
+                            &asset.label,
+                        );
+                        asset.mip_count = baked.levels.len() as u32;
+                        asset.resident_mip = 0;
+                        asset.bytes = texture_bytes(&texture.texture);
+                        asset.gpu = Some(texture);
+                        asset.baked = true;
+                        asset.baked_error = None;
+                        asset.loads += 1;
+                        asset.version += 1;
+                        self.stats.loads += 1;
+                        return Ok(asset);
+                    }
+                    Err(e) => {
+                        warn!("Not using the baked {}: {:?}", asset.label, e);
+                        asset.baked_error = Some(format!("{:#}", e));
+                    }
+                }
+            }
+
+            let image = match &asset.source {
+                TextureSource::Asset { embedded, .. } => image::load_from_memory(embedded)
+                    .map_err(|source| AssetError::Decode {
+                        label: asset.label.clone(),
+                        source,
+                    })?,
+                TextureSource::File(path) => {
+                    image::open(path).map_err(|source| AssetError::Decode {
+                        label: asset.label.clone(),
+                        source,
+                    })?
+                }
+            };
+            let mut texture =
+                Texture::streamed(&gpu.device, image.width(), image.height(), &asset.label);
+            let stream = image.width().max(image.height()) > STREAM_MIN_SIZE;
+            let mut pending: Vec<_> = mip_chain(&image)
+                .into_iter()
+                .enumerate()
+                .map(|(level, image)| PendingMip {
+                    level: level as u32,
+                    image,
+                    next_row: 0,
+                })
+                .collect();
+            asset.mip_count = pending.len() as u32;
+            asset.resident_mip = asset.mip_count;
+            // Something has to be there to sample from, even while streaming
+            while let Some(mip) = pending.last() {
+                if stream && mip.image.width().max(mip.image.height()) > STREAM_INITIAL_SIZE {
+                    break;
+                }
+                texture.write_mip_rows(&gpu.queue, mip.level, &mip.image, 0, mip.image.height());
+                asset.resident_mip = mip.level;
+                pending.pop();
+            }
+            texture.set_base_mip(asset.resident_mip);
+            asset.bytes = texture_bytes(&texture.texture);
+            asset.gpu = Some(texture);
+            asset.pending = pending;
+            asset.baked = false;
+            asset.loads += 1;
+            asset.version += 1;
+            self.stats.loads += 1;
+        }
+        Ok(asset)
+    }
+
+    /// Keeps the mesh resident this frame, uploading it if it isn't.
+    pub fn use_mesh(
+        &mut self,
+        gpu: &GpuContext,
+        handle: MeshHandle,
+    ) -> Result<&MeshAsset, AssetError> {
+        let frame = self.frame;
+        let asset = self
+            .meshes
+            .get_mut(handle.0 as usize)
+            .ok_or(AssetError::UnknownMesh(handle))?;
+        asset.last_used = frame;
+        if asset.gpu.is_none() {
+            let vertex_buffer = gpu
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&asset.label),
+                    contents: bytemuck::cast_slice(&asset.vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+            let index_buffer = asset
+                .indices
+                .as_ref()
+                .map(|indices| IndexBuffer::new(&gpu.device, &asset.label, indices));
+            asset.gpu = Some(GpuMesh {
+                count: index_buffer
+                    .as_ref()
+                    .map_or(asset.vertices.len() as u32, |indices| indices.count),
+                vertex_buffer,
+                index_buffer,
+            });
+            asset.loads += 1;
+            self.stats.loads += 1;
+        }
+        Ok(asset)
+    }
+
+    /// A texture [`use_texture`](Self::use_texture) loaded this frame.
+    pub fn texture(&self, handle: TextureHandle) -> Result<&Texture, AssetError> {
+        self.textures
+            .get(handle.0 as usize)
+            .ok_or(AssetError::UnknownTexture(handle))?
+            .gpu
+            .as_ref()
+            .ok_or(AssetError::TextureNotResident(handle))
+    }
+
+    /// The triangles of a mesh as a plain list, whether it's indexed or not.
+    pub fn mesh_triangles(&self, handle: MeshHandle) -> Result<Vec<MeshVertex>, AssetError> {
+        let asset = self
+            .meshes
+            .get(handle.0 as usize)
+            .ok_or(AssetError::UnknownMesh(handle))?;
+        Ok(match &asset.indices {
+            Some(indices) => unweld(&asset.vertices, indices),
+            None => asset.vertices.clone(),
+        })
+    }
+
+    /// A mesh [`use_mesh`](Self::use_mesh) uploaded this frame.
+    pub fn mesh(&self, handle: MeshHandle) -> Result<&GpuMesh, AssetError> {
+        self.meshes
+            .get(handle.0 as usize)
+            .ok_or(AssetError::UnknownMesh(handle))?
+            .gpu
+            .as_ref()
+            .ok_or(AssetError::MeshNotResident(handle))
+    }
+
+    /// Uploads pending mip rows, coarse levels first, until `budget` bytes
+    /// went up this frame. Returns the bytes uploaded.
+    pub fn stream_mips(&mut self, queue: &wgpu::Queue, budget: u64) -> u64 {
+        let mut uploaded = 0;
+        for asset in &mut self.textures {
+            let Some(texture) = &mut asset.gpu else {
+                continue;
+            };
+            while let Some(mip) = asset.pending.last_mut() {
+                let row_bytes = mip.image.width() as u64 * 4;
+                let left = budget.saturating_sub(uploaded);
+                // One row always goes up, or a budget below a row would stall
+                if left < row_bytes && uploaded > 0 {
+                    self.stats.streamed_bytes += uploaded;
+                    return uploaded;
+                }
+                let rows =
+                    ((left / row_bytes).max(1) as u32).min(mip.image.height() - mip.next_row);
+                texture.write_mip_rows(queue, mip.level, &mip.image, mip.next_row, rows);
+                mip.next_row += rows;
+                uploaded += rows as u64 * row_bytes;
+                if mip.next_row == mip.image.height() {
+                    asset.resident_mip = mip.level;
+                    texture.set_base_mip(mip.level);
+                    asset.version += 1;
+                    asset.pending.pop();
+                }
+            }
+        }
+        self.stats.streamed_bytes += uploaded;
+        uploaded
+    }
+
+    pub fn resident_bytes(&self) -> u64 {
+        let textures = self.textures.iter().filter(|t| t.gpu.is_some());
+        let meshes = self.meshes.iter().filter(|m| m.gpu.is_some());
+        textures.map(|t| t.bytes).sum::<u64>() + meshes.map(|m| m.bytes).sum::<u64>()
+    }
+
+    /// Evicts assets not used this frame, least recently used first, until
+    /// at most `budget` bytes are resident. What this frame uses always stays,
+    /// even over budget.
+    pub fn evict_to_budget(&mut self, budget: u64) {
+        let mut resident = self.resident_bytes();
+        if resident <= budget {
+            return;
+        }
+        let mut candidates = self.unused_resident();
+        candidates.sort_by_key(|&(last_used, _)| last_used);
+        for (_, asset) in candidates {
+            if resident <= budget {
+                break;
+            }
+            resident -= self.evict(asset);
+        }
+    }
+
+    /// Evicts every asset that wasn't used this frame.
+    pub fn evict_unused(&mut self) {
+        for (_, asset) in self.unused_resident() {
+            self.evict(asset);
+        }
+    }
+
+    fn unused_resident(&self) -> Vec<(u64, AssetRef)> {
+        let textures = self.textures.iter().enumerate().filter_map(|(i, t)| {
+            (t.gpu.is_some() && t.last_used < self.frame)
+                .then_some((t.last_used, AssetRef::Texture(i)))
+        });
+        let meshes = self.meshes.iter().enumerate().filter_map(|(i, m)| {
+            (m.gpu.is_some() && m.last_used < self.frame)
+                .then_some((m.last_used, AssetRef::Mesh(i)))
+        });
+        textures.chain(meshes).collect()
+    }
+
+    /// Destroys the GPU side right away rather than when the last bind group
+    /// holding it goes, returns the bytes freed.
+    fn evict(&mut self, asset: AssetRef) -> u64 {
+        let (label, bytes) = match asset {
+            AssetRef::Texture(i) => {
+                let asset = &mut self.textures[i];
+                if let Some(texture) = asset.gpu.take() {
+                    texture.texture.destroy();
+                }
+                asset.pending.clear();
+                (&asset.label, asset.bytes)
+            }
+            AssetRef::Mesh(i) => {
+                let asset = &mut self.meshes[i];
+                if let Some(mesh) = asset.gpu.take() {
+                    mesh.vertex_buffer.destroy();
+                    if let Some(index_buffer) = mesh.index_buffer {
+                        index_buffer.buffer.destroy();
+                    }
+                }
+                (&asset.label, asset.bytes)
+            }
+        };
+        info!("Evicted {} ({:.2} MiB)", label, bytes as f64 / MIB as f64);
+        self.stats.evictions += 1;
+        self.stats.evicted_bytes += bytes;
+        bytes
+    }
+}
+
+#[derive(Clone, Copy)]
+enum AssetRef {
+    Texture(usize),
+    Mesh(usize),
+}
+
+/// Every mip level of every layer, as tightly packed as the format allows.
+fn texture_bytes(texture: &wgpu::Texture) -> u64 {
+    let format = texture.format();
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(4) as u64;
+    let size = texture.size();
+    (0..texture.mip_level_count())
+        .map(|level| {
+            let mip = size.mip_level_size(level, texture.dimension());
+            let blocks_x = mip.width.div_ceil(block_width) as u64;
+            let blocks_y = mip.height.div_ceil(block_height) as u64;
+            blocks_x * blocks_y * mip.depth_or_array_layers as u64 * block_size
+        })
+        .sum()
+}
+
+// =============================== BUDGET ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct AssetSettings {
+    pub budget_mib: u32,
+    /// Texture data streamed per frame.
+    pub upload_kib: u32,
+    /// Tints streamed textures from green at full resolution to red.
+    pub tint_mips: bool,
+}
+impl Default for AssetSettings {
+    fn default() -> Self {
+        Self {
+            budget_mib: 256,
+            upload_kib: 512,
+            tint_mips: false,
+        }
+    }
+}
+
+/// Debug tint of a texture with `resident_mip` as its finest level, white
+/// when tinting is off.
+pub fn mip_tint(settings: &AssetSettings, asset: &TextureAsset) -> [f32; 4] {
+    if !settings.tint_mips {
+        return [1.0; 4];
+    }
+    let missing = asset.resident_mip as f32 / (asset.mip_count.max(2) - 1) as f32;
+    [missing.min(1.0), 1.0 - missing.min(1.0), 0.0, 1.0]
+}
+
+/// Runs after rendering, the uploads land before the next frame's commands.
+pub fn texture_streaming_system(
+    gpu: Res<GpuContext>,
+    settings: Res<AssetSettings>,
+    mut assets: ResMut<AssetServer>,
+) {
+    assets.stream_mips(&gpu.queue, settings.upload_kib as u64 * 1024);
+}
+
+/// Runs after rendering, when this frame's assets are known, and then starts
+/// the next frame.
+pub fn asset_eviction_system(settings: Res<AssetSettings>, mut assets: ResMut<AssetServer>) {
+    assets.evict_to_budget(settings.budget_mib as u64 * MIB);
+    assets.frame += 1;
+}
+
+fn asset_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<AssetSettings>().clone();
+    let mut evict_unused = false;
+    let compression = world
+        .resource::<GpuContext>()
+        .device
+        .features()
+        .contains(wgpu::Features::TEXTURE_COMPRESSION_BC);
+    let assets = world.resource::<AssetServer>();
+    let resident = assets.resident_bytes();
+
+    egui::Window::new(tr("Assets"))
+        .id(egui::Id::new("Assets"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            match (&assets.baked, &assets.baked_error) {
+                (Some(_), _) if !compression => {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        tr("Raw assets, the GPU can't sample BC compressed textures"),
+                    );
+                }
+                (Some(manifest), _) => {
+                    ui.label(trf!("{} baked assets", manifest.entries.len()));
+                }
+                (None, Some(error)) => {
+                    ui.colored_label(
+                        egui::Color32::RED,
+                        trf!("Raw assets, the bake was rejected: {}", error),
+                    );
+                }
+                (None, None) => {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        tr("Raw assets, run `cargo xtask assets` to bake them"),
+                    );
+                }
+            }
+            for texture in &assets.textures {
+                if let Some(error) = &texture.baked_error {
+                    ui.colored_label(egui::Color32::RED, format!("{}: {}", texture.label, error));
+                }
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(tr("Budget"));
+                ui.add(
+                    egui::DragValue::new(&mut settings.budget_mib)
+                        .range(1..=8192)
+                        .suffix(" MiB"),
+                );
+            });
+            let budget = settings.budget_mib as u64 * MIB;
+            let used = resident as f32 / budget.max(1) as f32;
+            ui.add(egui::ProgressBar::new(used.min(1.0)).text(trf!(
+                "{:.2} / {} MiB",
+                resident as f64 / MIB as f64,
+                settings.budget_mib
+            )));
+            if resident > budget {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    tr("Over budget, everything resident is in use"),
+                );
+            }
+            ui.label(trf!(
+                "{} loads, {} evictions ({:.2} MiB)",
+                assets.stats.loads,
+                assets.stats.evictions,
+                assets.stats.evicted_bytes as f64 / MIB as f64
+            ));
+            evict_unused = ui.button(tr("Evict unused now")).clicked();
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(tr("Mip streaming"));
+                ui.add(
+                    egui::DragValue::new(&mut settings.upload_kib)
+                        .range(4..=65536)
+                        .suffix(" KiB"),
+                );
+                ui.label(tr("per frame"));
+            });
+            ui.checkbox(&mut settings.tint_mips, tr("Tint textures by resident mip"));
+            ui.label(trf!(
+                "{:.2} MiB streamed",
+                assets.stats.streamed_bytes as f64 / MIB as f64
+            ));
+
+            ui.separator();
+            egui::Grid::new("assets").striped(true).show(ui, |ui| {
+                for texture in &assets.textures {
+                    let mip = texture.gpu.as_ref().map(|_| {
+                        if texture.baked {
+                            trf!("baked, {} mips", texture.mip_count)
+                        } else {
+                            trf!("mip {} of {}", texture.resident_mip, texture.mip_count)
+                        }
+                    });
+                    let row = (texture.gpu.is_some(), texture.bytes, texture.last_used);
+                    asset_row(ui, tr("Texture"), &texture.label, mip, row);
+                }
+                for mesh in &assets.meshes {
+                    let row = (mesh.gpu.is_some(), mesh.bytes, mesh.last_used);
+                    asset_row(ui, tr("Mesh"), &mesh.label, None, row);
+                }
+            });
+        });
+
+    if evict_unused {
+        world.resource_mut::<AssetServer>().evict_unused();
+    }
+    let mut current = world.resource_mut::<AssetSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+fn asset_row(
+    ui: &mut egui::Ui,
+    kind: &str,
+    label: &str,
+    mip: Option<String>,
+    (resident, bytes, last_used): (bool, u64, u64),
+) {
+    ui.label(kind);
+    ui.label(label);
+    ui.label(trf!("{:.2} MiB", bytes as f64 / MIB as f64));
+    ui.label(mip.unwrap_or_default());
+    if resident {
+        ui.label(trf!("last used in frame {}", last_used));
+    } else {
+        ui.weak(tr("evicted"));
+    }
+    ui.end_row();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups_tell_unknown_from_evicted() {
+        let mut assets = AssetServer::default();
+        let stone = assets.add_texture(
+            "stone",
+            TextureSource::Asset {
+                name: "stone.png",
+                embedded: &[],
+            },
+        );
+        let missing = TextureHandle(stone.0 + 1);
+
+        assert!(matches!(
+            assets.texture(stone),
+            Err(AssetError::TextureNotResident(handle)) if handle == stone
+        ));
+        assert!(matches!(
+            assets.texture(missing),
+            Err(AssetError::UnknownTexture(handle)) if handle == missing
+        ));
+        assert!(matches!(
+            assets.mesh_triangles(MeshHandle(0)),
+            Err(AssetError::UnknownMesh(_))
+        ));
+    }
+}
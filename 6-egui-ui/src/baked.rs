@@ -0,0 +1,217 @@
+//! Reads what `cargo xtask assets` bakes into `baked/`: the manifest of baked
+//! sources, and the KTX2 textures with their precomputed BCn mip chains.
+//!
+//! The manifest has the content hash of every source and output. A baked
+//! output is only used when its source still hashes the same, so an edited
+//! asset isn't shadowed by an old bake, and when the output matches its
+//! recorded size and hash, so a damaged file isn't uploaded.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+pub const BAKED_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../baked");
+/// Has to match the xtask's, older or newer bakes are ignored.
+const BAKE_VERSION: u32 = 2;
+
+#[derive(Debug, Deserialize)]
+pub struct BakedManifest {
+    pub version: u32,
+    pub entries: Vec<BakedEntry>,
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BakedEntry {
+    /// Relative to `assets/`.
+    pub source: String,
+    pub source_hash: String,
+    pub kind: String,
+    /// Relative to `baked/`.
+    pub output: String,
+    pub output_hash: String,
+    pub output_size: u64,
+}
+
+impl BakedManifest {
+    /// `None` when nothing was baked yet.
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join("manifest.json");
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut manifest: Self = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        if manifest.version != BAKE_VERSION {
+            bail!(
+                "{} is from bake version {}, expected {}. Run `cargo xtask assets`",
+                path.display(),
+                manifest.version,
+                BAKE_VERSION
+            );
+        }
+        manifest.dir = dir.to_path_buf();
+        Ok(Some(manifest))
+    }
+
+    /// The baked `kind` of `source`, if there is one.
+    pub fn entry(&self, kind: &str, source: &str) -> Option<&BakedEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.kind == kind && entry.source == source)
+    }
+
+    /// Reads the output of `entry` after [`verify`]ing it against `source`,
+    /// the bytes the asset would be loaded from without it.
+    pub fn read(&self, entry: &BakedEntry, source: &[u8]) -> Result<Vec<u8>> {
+        let path = self.dir.join(&entry.output);
+        let output =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        verify(entry, source, &output)?;
+        Ok(output)
+    }
+}
+
+/// Checks that `source` is what was baked and `output` is what the baker
+/// wrote.
+pub fn verify(entry: &BakedEntry, source: &[u8], output: &[u8]) -> Result<()> {
+    let source_hash = content_hash(source);
+    if source_hash != entry.source_hash {
+        bail!(
+            "{} is stale, {} changed since it was baked (hash {}, baked from {}). Run `cargo xtask assets`",
+            entry.output,
+            entry.source,
+            source_hash,
+            entry.source_hash
+        );
+    }
+    if output.len() as u64 != entry.output_size {
+        bail!(
+            "{} is corrupt, it has {} bytes instead of {}. Run `cargo xtask assets`",
+            entry.output,
+            output.len(),
+            entry.output_size
+        );
+    }
+    let output_hash = content_hash(output);
+    if output_hash != entry.output_hash {
+        bail!(
+            "{} is corrupt, it hashes to {} instead of {}. Run `cargo xtask assets`",
+            entry.output,
+            output_hash,
+            entry.output_hash
+        );
+    }
+    Ok(())
+}
+
+/// 64 bit FNV-1a as hex, the same as the baker's.
+pub fn content_hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+// =============================== KTX2 ===============================
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// A 2D texture with every level ready to upload, finest first.
+pub struct BakedTexture {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+/// Parses the subset of KTX2 the baker writes: 2D, a single layer and face,
+/// BC1 or BC3 and no supercompression.
+pub fn read_ktx2(bytes: &[u8]) -> Result<BakedTexture> {
+    if bytes.len() < 80 || bytes[..12] != KTX2_IDENTIFIER {
+        bail!("Not a KTX2 file");
+    }
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+    let format = match u32_at(12) {
+        131 | 133 => wgpu::TextureFormat::Bc1RgbaUnorm,
+        132 | 134 => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+        137 => wgpu::TextureFormat::Bc3RgbaUnorm,
+        138 => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+        other => bail!("Unsupported KTX2 format {}", other),
+    };
+    let (width, height, depth) = (u32_at(20), u32_at(24), u32_at(28));
+    let (layers, faces, level_count, supercompression) =
+        (u32_at(32), u32_at(36), u32_at(40), u32_at(44));
+    if depth != 0 || layers > 1 || faces != 1 || supercompression != 0 {
+        bail!("Only plain 2D KTX2 textures are supported");
+    }
+
+    let level_count = level_count.max(1) as usize;
+    let index_end = 80 + level_count * 24;
+    if bytes.len() < index_end {
+        bail!("KTX2 level index is truncated");
+    }
+    let mut levels = Vec::with_capacity(level_count);
+    for level in 0..level_count {
+        let entry = 80 + level * 24;
+        let offset = u64_at(entry) as usize;
+        let length = u64_at(entry + 8) as usize;
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| bytes.get(offset..end))
+            .with_context(|| format!("KTX2 level {} is out of bounds", level))?;
+        let (block_width, block_height) = format.block_dimensions();
+        let expected = (width >> level).max(1).div_ceil(block_width)
+            * (height >> level).max(1).div_ceil(block_height)
+            * format.block_copy_size(None).unwrap_or(0);
+        if data.len() != expected as usize {
+            bail!(
+                "KTX2 level {} has {} bytes, expected {}",
+                level,
+                data.len(),
+                expected
+            );
+        }
+        levels.push(data.to_vec());
+    }
+    Ok(BakedTexture {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stale_and_corrupt_outputs_are_rejected() {
+        let source = b"source image";
+        let output = b"baked texture";
+        let entry = BakedEntry {
+            source: "stone.png".to_string(),
+            source_hash: content_hash(source),
+            kind: "texture".to_string(),
+            output: "stone.png.ktx2".to_string(),
+            output_hash: content_hash(output),
+            output_size: output.len() as u64,
+        };
+        assert!(verify(&entry, source, output).is_ok());
+
+        let stale = verify(&entry, b"edited image", output).unwrap_err();
+        assert!(stale.to_string().contains("stale"), "{}", stale);
+        let truncated = verify(&entry, source, &output[..4]).unwrap_err();
+        assert!(truncated.to_string().contains("corrupt"), "{}", truncated);
+        let flipped = verify(&entry, source, b"baked texturE").unwrap_err();
+        assert!(flipped.to_string().contains("hashes to"), "{}", flipped);
+    }
+}
@@ -0,0 +1,518 @@
+//! Camera flythroughs: poses recorded from the interactive camera become the
+//! keys of a centripetal Catmull-Rom spline, which is flown at a constant
+//! speed. The eye and the target each follow their own spline through the
+//! keys, or the target stays on a fixed look-at point.
+//!
+//! A path plays as part of a [`Timeline`], so it gets the timeline's fixed
+//! step and every frame of the flight, including the ones a frame capture
+//! grabs, is the same on every run.
+
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::Resource,
+    world::World,
+};
+use glam::{Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    i18n::{tr, trf},
+    pipeline::{debug_draw::DebugDraw, render::render_system, ui::UiPanels},
+    scene::Camera,
+    time::TimeContext,
+    timeline::{Timeline, TimelinePlayer},
+};
+
+/// Points per segment of the arc length table, and of the drawn path.
+const SEGMENT_SAMPLES: usize = 32;
+
+pub fn setup_camera_path(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    world.insert_resource(CameraRecorder::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(camera_path_panel);
+
+    schedule.add_systems(camera_record_system.before(render_system));
+
+    Ok(())
+}
+
+// =============================== PATH ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraKey {
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+}
+impl CameraKey {
+    fn eye(&self) -> Vec3 {
+        Vec3::from_array(self.eye)
+    }
+
+    fn target(&self) -> Vec3 {
+        Vec3::from_array(self.target)
+    }
+}
+
+/// Missing fields take their defaults, so hand-written paths stay short.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraPath {
+    /// Seconds into the timeline the flight starts.
+    pub start: f32,
+    /// World units per second along the eye's path.
+    pub speed: f32,
+    /// Looked at the whole way instead of the keyed targets.
+    pub look_at: Option<[f32; 3]>,
+    pub keys: Vec<CameraKey>,
+}
+impl Default for CameraPath {
+    fn default() -> Self {
+        Self {
+            start: 0.0,
+            speed: 4.0,
+            look_at: None,
+            keys: Vec::new(),
+        }
+    }
+}
+impl CameraPath {
+    pub fn validate(&self) -> Result<()> {
+        if self.keys.len() > 1 && (self.speed.is_nan() || self.speed <= 0.0) {
+            anyhow::bail!(
+                "The camera path's speed has to be positive, not {}",
+                self.speed
+            );
+        }
+        Ok(())
+    }
+
+    /// Length of the eye's path.
+    pub fn length(&self) -> f32 {
+        self.arc_lengths()
+            .last()
+            .map_or(0.0, |&(length, ..)| length)
+    }
+
+    /// Seconds from the first key to the last.
+    pub fn duration(&self) -> f32 {
+        if self.speed > 0.0 {
+            self.length() / self.speed
+        } else {
+            0.0
+        }
+    }
+
+    /// Eye and target `seconds` after the start, held at the ends.
+    pub fn pose(&self, seconds: f32) -> Option<(Vec3, Vec3)> {
+        let (segment, u) = self.at_distance(seconds.max(0.0) * self.speed)?;
+        Some(self.pose_at(segment, u))
+    }
+
+    fn pose_at(&self, segment: usize, u: f32) -> (Vec3, Vec3) {
+        let eyes: Vec<Vec3> = self.keys.iter().map(CameraKey::eye).collect();
+        let eye = catmull_rom(&eyes, segment, u);
+        let target = match self.look_at {
+            Some(look_at) => Vec3::from_array(look_at),
+            None => {
+                let targets: Vec<Vec3> = self.keys.iter().map(CameraKey::target).collect();
+                catmull_rom(&targets, segment, u)
+            }
+        };
+        (eye, target)
+    }
+
+    /// Segment and parameter `distance` along the eye's path.
+    fn at_distance(&self, distance: f32) -> Option<(usize, f32)> {
+        if self.keys.len() < 2 {
+            return self.keys.first().map(|_| (0, 0.0));
+        }
+        let table = self.arc_lengths();
+        let next = table.partition_point(|&(length, ..)| length < distance);
+        let Some(&(b_length, b_segment, b_u)) = table.get(next) else {
+            return Some((self.keys.len() - 2, 1.0));
+        };
+        let Some(&(a_length, _, a_u)) = next.checked_sub(1).map(|i| &table[i]) else {
+            return Some((0, 0.0));
+        };
+        // Neighbors in the table can straddle a segment boundary
+        let a_u = if b_u < a_u { 0.0 } else { a_u };
+        let t = (distance - a_length) / (b_length - a_length).max(f32::EPSILON);
+        Some((b_segment, a_u + (b_u - a_u) * t))
+    }
+
+    /// Cumulative length, segment and parameter of points along the eye's
+    /// path. Rebuilt every call, which is cheap at the size of recorded paths.
+    fn arc_lengths(&self) -> Vec<(f32, usize, f32)> {
+        let eyes: Vec<Vec3> = self.keys.iter().map(CameraKey::eye).collect();
+        let mut table = Vec::with_capacity(eyes.len().saturating_sub(1) * SEGMENT_SAMPLES + 1);
+        let Some(&first) = eyes.first() else {
+            return table;
+        };
+        let (mut length, mut previous) = (0.0, first);
+        table.push((0.0, 0, 0.0));
+        for segment in 0..eyes.len().saturating_sub(1) {
+            for sample in 1..=SEGMENT_SAMPLES {
+                let u = sample as f32 / SEGMENT_SAMPLES as f32;
+                let point = catmull_rom(&eyes, segment, u);
+                length += point.distance(previous);
+                previous = point;
+                table.push((length, segment, u));
+            }
+        }
+        table
+    }
+
+    /// Drops keys the spline through their neighbors passes within
+    /// `tolerance` of anyway, for both the eye and the target. Returns how
+    /// many were dropped.
+    pub fn simplify(&mut self, tolerance: f32) -> usize {
+        let before = self.keys.len();
+        let mut index = 1;
+        while index + 1 < self.keys.len() {
+            let key = self.keys[index];
+            let mut without = self.clone();
+            without.keys.remove(index);
+            let (eyes, targets): (Vec<Vec3>, Vec<Vec3>) = (0..=SEGMENT_SAMPLES)
+                .map(|sample| without.pose_at(index - 1, sample as f32 / SEGMENT_SAMPLES as f32))
+                .unzip();
+            let covered = distance_to_polyline(&eyes, key.eye()) <= tolerance
+                && (self.look_at.is_some()
+                    || distance_to_polyline(&targets, key.target()) <= tolerance);
+            if covered {
+                self.keys.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+        before - self.keys.len()
+    }
+}
+
+fn distance_to_polyline(points: &[Vec3], point: Vec3) -> f32 {
+    points
+        .windows(2)
+        .map(|line| {
+            let direction = line[1] - line[0];
+            let t = ((point - line[0]).dot(direction)
+                / direction.length_squared().max(f32::EPSILON))
+            .clamp(0.0, 1.0);
+            point.distance(line[0] + direction * t)
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// Point `u` of the way through segment `segment`, between `points[segment]`
+/// and the next one. Centripetal parametrization, which doesn't overshoot or
+/// loop at sharp turns the way the uniform one does. The ends are mirrored to
+/// get their missing neighbors.
+pub fn catmull_rom(points: &[Vec3], segment: usize, u: f32) -> Vec3 {
+    let p1 = points[segment];
+    let Some(&p2) = points.get(segment + 1) else {
+        return p1;
+    };
+    let p0 = segment
+        .checked_sub(1)
+        .map_or(2.0 * p1 - p2, |previous| points[previous]);
+    let p3 = points.get(segment + 2).copied().unwrap_or(2.0 * p2 - p1);
+
+    let knot = |a: Vec3, b: Vec3| a.distance(b).sqrt().max(1e-4);
+    let t0 = 0.0;
+    let t1 = t0 + knot(p0, p1);
+    let t2 = t1 + knot(p1, p2);
+    let t3 = t2 + knot(p2, p3);
+    let t = t1 + (t2 - t1) * u;
+
+    let mix = |a: Vec3, b: Vec3, ta: f32, tb: f32| {
+        a * ((tb - t) / (tb - ta)) + b * ((t - ta) / (tb - ta))
+    };
+    let a1 = mix(p0, p1, t0, t1);
+    let a2 = mix(p1, p2, t1, t2);
+    let a3 = mix(p2, p3, t2, t3);
+    let b1 = mix(a1, a2, t0, t2);
+    let b2 = mix(a2, a3, t1, t3);
+    mix(b1, b2, t1, t2)
+}
+
+// =============================== RECORDING ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct CameraRecorder {
+    pub path: CameraPath,
+    pub recording: bool,
+    /// Seconds between recorded poses.
+    pub interval: f32,
+    /// Poses that moved the eye and target less than this together since the
+    /// last key aren't recorded.
+    pub min_distance: f32,
+    /// For [`CameraPath::simplify`] after recording.
+    pub tolerance: f32,
+    pub show_path: bool,
+    pub last_save: Option<Result<(), String>>,
+    elapsed: f32,
+}
+impl Default for CameraRecorder {
+    fn default() -> Self {
+        Self {
+            path: CameraPath::default(),
+            recording: false,
+            interval: 0.25,
+            min_distance: 0.5,
+            tolerance: 0.1,
+            show_path: true,
+            last_save: None,
+            elapsed: 0.0,
+        }
+    }
+}
+impl CameraRecorder {
+    pub fn add_key(&mut self, camera: &Camera) {
+        self.path.keys.push(CameraKey {
+            eye: camera.eye.to_array(),
+            target: camera.target.to_array(),
+        });
+    }
+}
+
+/// Records the camera while recording, and draws the path while the
+/// timeline isn't playing it.
+pub fn camera_record_system(world: &mut World) {
+    let delta = world.resource::<TimeContext>().delta;
+    let camera = *world.resource::<Camera>();
+    let playing = world
+        .get_resource::<TimelinePlayer>()
+        .is_some_and(|player| player.playing);
+    let mut recorder = world.resource_mut::<CameraRecorder>();
+
+    if recorder.recording {
+        recorder.elapsed += delta;
+        if recorder.elapsed >= recorder.interval {
+            recorder.elapsed = 0.0;
+            let moved = recorder.path.keys.last().map_or(f32::INFINITY, |last| {
+                camera.eye.distance(last.eye()) + camera.target.distance(last.target())
+            });
+            if moved >= recorder.min_distance {
+                recorder.add_key(&camera);
+            }
+        }
+    }
+
+    if !recorder.show_path || playing || recorder.path.keys.is_empty() {
+        return;
+    }
+    let path = recorder.path.clone();
+    let Some(mut debug) = world.get_resource_mut::<DebugDraw>() else {
+        return;
+    };
+    let color = Vec4::new(1.0, 0.8, 0.2, 1.0);
+    for key in &path.keys {
+        debug.wire_sphere(key.eye(), 0.15, color);
+        let target = path.look_at.map_or(key.target(), Vec3::from_array);
+        debug.line(key.eye(), target, color.with_w(0.3));
+    }
+    for segment in 0..path.keys.len().saturating_sub(1) {
+        let mut previous = path.keys[segment].eye();
+        for sample in 1..=SEGMENT_SAMPLES {
+            let (eye, _) = path.pose_at(segment, sample as f32 / SEGMENT_SAMPLES as f32);
+            debug.line(previous, eye, color);
+            previous = eye;
+        }
+    }
+}
+
+// =============================== PANEL ===============================
+/// Puts the recorded path into the loaded timeline, or a new one without
+/// tracks.
+fn into_timeline(world: &mut World, path: &CameraPath) {
+    let mut player = world.resource_mut::<TimelinePlayer>();
+    player
+        .timeline
+        .get_or_insert_with(Timeline::default)
+        .camera_path = Some(path.clone());
+}
+
+fn camera_path_panel(ctx: &egui::Context, world: &mut World) {
+    let mut recorder = world.resource::<CameraRecorder>().clone();
+    let camera = *world.resource::<Camera>();
+    let timeline_path = world.resource::<TimelinePlayer>().path.clone();
+    let (mut play, mut save) = (false, false);
+
+    egui::Window::new(tr("Camera path"))
+        .id(egui::Id::new("Camera path"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if recorder.recording {
+                    if ui.button(tr("Stop recording")).clicked() {
+                        recorder.recording = false;
+                        let removed = recorder.path.simplify(recorder.tolerance);
+                        info!(
+                            "Recorded {} camera keys, {} dropped as redundant",
+                            recorder.path.keys.len(),
+                            removed
+                        );
+                    }
+                } else if ui.button(tr("Record")).clicked() {
+                    recorder.recording = true;
+                    recorder.path.keys.clear();
+                    recorder.add_key(&camera);
+                }
+                if ui.button(tr("Add key")).clicked() {
+                    recorder.add_key(&camera);
+                }
+                if ui.button(tr("Clear")).clicked() {
+                    recorder.path.keys.clear();
+                }
+            });
+            ui.label(trf!(
+                "{} keys, {:.1} m, {:.1} s",
+                recorder.path.keys.len(),
+                recorder.path.length(),
+                recorder.path.duration()
+            ));
+
+            egui::Grid::new("camera_path").show(ui, |ui| {
+                ui.label(tr("Speed"));
+                ui.add(
+                    egui::DragValue::new(&mut recorder.path.speed)
+                        .range(0.1..=100.0)
+                        .speed(0.1)
+                        .suffix(" m/s"),
+                );
+                ui.end_row();
+                ui.label(tr("Start"));
+                ui.add(
+                    egui::DragValue::new(&mut recorder.path.start)
+                        .range(0.0..=3600.0)
+                        .speed(0.1)
+                        .suffix(" s"),
+                );
+                ui.end_row();
+                ui.label(tr("Record every"));
+                ui.add(
+                    egui::DragValue::new(&mut recorder.interval)
+                        .range(0.05..=5.0)
+                        .speed(0.01)
+                        .suffix(" s"),
+                );
+                ui.end_row();
+                ui.label(tr("Simplify tolerance"));
+                ui.add(
+                    egui::DragValue::new(&mut recorder.tolerance)
+                        .range(0.0..=5.0)
+                        .speed(0.01)
+                        .suffix(" m"),
+                );
+                ui.end_row();
+            });
+
+            let mut fixed = recorder.path.look_at.is_some();
+            if ui
+                .checkbox(&mut fixed, tr("Look at a fixed point"))
+                .changed()
+            {
+                recorder.path.look_at = fixed.then_some(camera.target.to_array());
+            }
+            if let Some(look_at) = &mut recorder.path.look_at {
+                ui.horizontal(|ui| {
+                    for value in look_at.iter_mut() {
+                        ui.add(egui::DragValue::new(value).speed(0.1));
+                    }
+                    if ui.button(tr("Use camera target")).clicked() {
+                        *look_at = camera.target.to_array();
+                    }
+                });
+            }
+            ui.checkbox(&mut recorder.show_path, tr("Show path"));
+
+            ui.separator();
+            ui.add_enabled_ui(recorder.path.keys.len() > 1 && !recorder.recording, |ui| {
+                ui.horizontal(|ui| {
+                    play = ui.button(tr("Play in timeline")).clicked();
+                    save = ui
+                        .button(trf!("Save to {}", timeline_path.display()))
+                        .clicked();
+                });
+            });
+            match &recorder.last_save {
+                Some(Ok(())) => {
+                    ui.label(tr("Saved"));
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(egui::Color32::RED, trf!("Save failed: {}", e));
+                }
+                None => {}
+            }
+        });
+
+    if play || save {
+        into_timeline(world, &recorder.path);
+    }
+    if play {
+        world.resource_mut::<TimelinePlayer>().play();
+    }
+    if save {
+        let player = world.resource::<TimelinePlayer>();
+        let timeline = player.timeline.as_ref().expect("the path was just added");
+        recorder.last_save = Some(timeline.save(&player.path).map_err(|e| format!("{:#}", e)));
+    }
+
+    let mut current = world.resource_mut::<CameraRecorder>();
+    if *current != recorder {
+        *current = recorder;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_fly_at_constant_speed_through_their_keys() {
+        let key = |x: f32, z: f32| CameraKey {
+            eye: [x, 0.0, z],
+            target: [0.0; 3],
+        };
+        let path = CameraPath {
+            speed: 2.0,
+            keys: vec![
+                key(0.0, 0.0),
+                key(10.0, 0.0),
+                key(10.0, 10.0),
+                key(0.0, 10.0),
+            ],
+            ..Default::default()
+        };
+        // Interpolates: every key is on the path
+        let eyes: Vec<Vec3> = path.keys.iter().map(CameraKey::eye).collect();
+        for segment in 0..3 {
+            assert!(catmull_rom(&eyes, segment, 0.0).distance(eyes[segment]) < 1e-4);
+            assert!(catmull_rom(&eyes, segment, 1.0).distance(eyes[segment + 1]) < 1e-3);
+        }
+
+        // Equal times cover equal distances, however the keys are spaced
+        let length = path.length();
+        assert!(length > 30.0, "at least the polyline, {}", length);
+        assert!((path.duration() - length / 2.0).abs() < 1e-4);
+        let steps = 60;
+        let dt = path.duration() / steps as f32;
+        let poses: Vec<Vec3> = (0..=steps)
+            .map(|step| path.pose(step as f32 * dt).unwrap().0)
+            .collect();
+        for pair in poses.windows(2) {
+            let moved = pair[0].distance(pair[1]);
+            assert!((moved - 2.0 * dt).abs() < 0.05 * 2.0 * dt, "{}", moved);
+        }
+        assert!(poses[0].distance(eyes[0]) < 1e-4);
+        assert!(poses[steps].distance(eyes[3]) < 1e-2);
+
+        // Keys along a straight line add nothing, corners do
+        let mut straight = CameraPath {
+            keys: vec![key(0.0, 0.0), key(5.0, 0.0), key(10.0, 0.0), key(15.0, 0.0)],
+            ..Default::default()
+        };
+        assert_eq!(straight.simplify(0.05), 2);
+        assert_eq!(straight.keys, vec![key(0.0, 0.0), key(15.0, 0.0)]);
+        assert_eq!(path.clone().simplify(0.05), 0);
+    }
+}
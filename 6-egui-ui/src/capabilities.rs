@@ -0,0 +1,157 @@
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+use wgpu::{TextureFormat, TextureFormatFeatureFlags, TextureUsages};
+
+use crate::gpu::GpuContext;
+use crate::i18n::tr;
+
+/// Formats worth comparing across backends: the usual color targets, data
+/// formats, depth formats and one of each compressed family.
+pub const PROBED_FORMATS: &[TextureFormat] = &[
+    TextureFormat::R8Unorm,
+    TextureFormat::Rg8Unorm,
+    TextureFormat::Rgba8Unorm,
+    TextureFormat::Rgba8UnormSrgb,
+    TextureFormat::Bgra8Unorm,
+    TextureFormat::Bgra8UnormSrgb,
+    TextureFormat::Rgb10a2Unorm,
+    TextureFormat::Rg11b10Ufloat,
+    TextureFormat::R16Float,
+    TextureFormat::Rg16Float,
+    TextureFormat::Rgba16Float,
+    TextureFormat::R32Float,
+    TextureFormat::Rg32Float,
+    TextureFormat::Rgba32Float,
+    TextureFormat::R32Uint,
+    TextureFormat::Rgba32Uint,
+    TextureFormat::Depth16Unorm,
+    TextureFormat::Depth24PlusStencil8,
+    TextureFormat::Depth32Float,
+    TextureFormat::Bc1RgbaUnorm,
+    TextureFormat::Bc7RgbaUnorm,
+    TextureFormat::Etc2Rgba8Unorm,
+];
+
+pub fn setup_capabilities(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let capabilities = FormatCapabilities::probe(gpu);
+    world.insert_resource(capabilities);
+
+    Ok(())
+}
+
+// =============================== PROBE ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Support {
+    /// Guaranteed by WebGPU, so it works on every backend.
+    Everywhere,
+    /// Only this adapter has it; needs `TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`
+    /// to be usable, and can't be relied on elsewhere.
+    AdapterOnly,
+    Unsupported,
+}
+impl Support {
+    fn new(guaranteed: bool, adapter: bool) -> Self {
+        match (guaranteed, adapter) {
+            (true, _) => Self::Everywhere,
+            (false, true) => Self::AdapterOnly,
+            (false, false) => Self::Unsupported,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Everywhere => "✔",
+            Self::AdapterOnly => "●",
+            Self::Unsupported => "✖",
+        }
+    }
+}
+
+/// One row of the compatibility matrix.
+pub struct FormatSupport {
+    pub format: TextureFormat,
+    /// Device features the format needs that weren't enabled.
+    pub needs: wgpu::Features,
+    pub render: Support,
+    pub storage: Support,
+    pub filter: Support,
+    pub blend: Support,
+    pub multisample: Support,
+}
+
+/// What the adapter can do with each of [`PROBED_FORMATS`], next to what
+/// WebGPU guarantees for it.
+#[derive(Resource)]
+pub struct FormatCapabilities {
+    pub formats: Vec<FormatSupport>,
+}
+impl FormatCapabilities {
+    pub fn probe(gpu: &GpuContext) -> Self {
+        let device_features = gpu.device.features();
+        let formats = PROBED_FORMATS
+            .iter()
+            .map(|format| {
+                let adapter = gpu.adapter.get_texture_format_features(*format);
+                let guaranteed = format.guaranteed_format_features(device_features);
+                let usage = |usage: TextureUsages| {
+                    Support::new(
+                        guaranteed.allowed_usages.contains(usage),
+                        adapter.allowed_usages.contains(usage),
+                    )
+                };
+                let flag = |flag: TextureFormatFeatureFlags| {
+                    Support::new(
+                        guaranteed.flags.contains(flag),
+                        adapter.flags.contains(flag),
+                    )
+                };
+                FormatSupport {
+                    format: *format,
+                    needs: format.required_features() - device_features,
+                    render: usage(TextureUsages::RENDER_ATTACHMENT),
+                    storage: usage(TextureUsages::STORAGE_BINDING),
+                    filter: flag(TextureFormatFeatureFlags::FILTERABLE),
+                    blend: flag(TextureFormatFeatureFlags::BLENDABLE),
+                    multisample: flag(TextureFormatFeatureFlags::MULTISAMPLE_X4),
+                }
+            })
+            .collect();
+
+        Self { formats }
+    }
+
+    /// Draws the matrix as a grid, one format per row.
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        ui.label(tr("✔ everywhere  ● this adapter only  ✖ unsupported"));
+        egui::Grid::new("format_capabilities")
+            .striped(true)
+            .show(ui, |ui| {
+                for header in [
+                    "Format", "Render", "Storage", "Filter", "Blend", "MSAA 4x", "Needs",
+                ] {
+                    ui.strong(tr(header));
+                }
+                ui.end_row();
+                for row in &self.formats {
+                    ui.label(format!("{:?}", row.format));
+                    for support in [
+                        row.render,
+                        row.storage,
+                        row.filter,
+                        row.blend,
+                        row.multisample,
+                    ] {
+                        ui.label(support.symbol());
+                    }
+                    if !row.needs.is_empty() {
+                        ui.label(format!("{:?}", row.needs));
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+}
@@ -0,0 +1,367 @@
+//! A region of the desktop streamed into a texture, behind the `capture`
+//! feature. A thread grabs the region with xcap at up to [`CAPTURE_RATE`]
+//! frames a second, and the newest capture goes up to the GPU a few rows a
+//! frame, within the same per frame upload budget the asset server streams
+//! mips with. While capturing, the textured quad shows the desktop instead
+//! of its stone.
+//!
+//! xcap hands out RGBA8 whatever the platform captured, swizzling BGRA on
+//! the capture thread. The texture is sRGB like every other color texture,
+//! so the sampler does the conversion to linear.
+
+use std::{
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use bevy_ecs::{
+    change_detection::DetectChanges,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use image::RgbaImage;
+use tracing::{error, info, warn};
+
+use crate::{
+    assets::{AssetServer, AssetSettings},
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pipeline::{
+        diffuse::{
+            diffuse_bind_group_system, DiffuseBindGroup, DiffuseBindGroupLayout, DiffuseTexture,
+        },
+        render::render_system,
+        ui::UiPanels,
+    },
+    sampler::{SamplerCache, SamplerKey, SamplerSettings},
+    texture::Texture,
+};
+
+/// Captures a second at most, grabbing a screen is far from free.
+const CAPTURE_RATE: f32 = 30.0;
+
+pub fn setup_capture(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    let thread_shared = Arc::downgrade(&shared);
+    std::thread::Builder::new()
+        .name("desktop-capture".to_string())
+        .spawn(move || capture_thread(thread_shared))
+        .context("Failed to start the capture thread")?;
+
+    world.insert_resource(DesktopCapture {
+        shared,
+        texture: None,
+        upload: None,
+        bound: false,
+        stats: CaptureStats::default(),
+    });
+    world.insert_resource(CaptureSettings::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(capture_panel);
+
+    schedule.add_systems(
+        capture_upload_system
+            .after(diffuse_bind_group_system)
+            .before(render_system),
+    );
+
+    Ok(())
+}
+
+// =============================== THREAD ===============================
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Region {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+/// What the capture thread and the renderer share.
+#[derive(Default)]
+struct Shared {
+    /// `None` while capturing is off.
+    region: Option<Region>,
+    /// The newest capture nobody took yet.
+    latest: Option<RgbaImage>,
+    error: Option<String>,
+    captured: u64,
+    /// Captures replaced before they were taken.
+    dropped: u64,
+    capture_ms: f32,
+}
+
+/// Runs until the [`DesktopCapture`] holding the other end is gone.
+fn capture_thread(weak: Weak<Mutex<Shared>>) {
+    let interval = Duration::from_secs_f32(1.0 / CAPTURE_RATE);
+    loop {
+        let started = Instant::now();
+        let Some(shared) = weak.upgrade() else {
+            return;
+        };
+        let region = shared.lock().map(|shared| shared.region).unwrap_or(None);
+        if let Some(region) = region {
+            let result = capture(region);
+            let Ok(mut shared) = shared.lock() else {
+                return;
+            };
+            match result {
+                Ok(image) => {
+                    shared.captured += 1;
+                    shared.capture_ms = started.elapsed().as_secs_f32() * 1000.0;
+                    shared.error = None;
+                    if shared.latest.replace(image).is_some() {
+                        shared.dropped += 1;
+                    }
+                }
+                Err(e) => shared.error = Some(format!("{:#}", e)),
+            }
+        }
+        drop(shared);
+        std::thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+}
+
+/// `region` is in desktop coordinates, on the monitor its top left is on.
+fn capture(region: Region) -> Result<RgbaImage> {
+    let monitor = xcap::Monitor::from_point(region.x, region.y)
+        .context("No monitor at the top left of the region")?;
+    let x = (region.x - monitor.x()?) as u32;
+    let y = (region.y - monitor.y()?) as u32;
+    // Clamped to the monitor, xcap refuses regions that reach past it
+    let width = region.width.min(monitor.width()?.saturating_sub(x)).max(1);
+    let height = region
+        .height
+        .min(monitor.height()?.saturating_sub(y))
+        .max(1);
+    Ok(monitor.capture_region(x, y, width, height)?)
+}
+
+// =============================== UPLOAD ===============================
+/// A capture on its way to the texture.
+struct Upload {
+    image: RgbaImage,
+    next_row: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CaptureStats {
+    /// Captures completely in the texture.
+    pub uploaded: u64,
+    pub uploaded_bytes: u64,
+}
+
+#[derive(Resource)]
+pub struct DesktopCapture {
+    shared: Arc<Mutex<Shared>>,
+    /// Single level, sized like the last capture.
+    texture: Option<Texture>,
+    upload: Option<Upload>,
+    /// Whether the textured quad samples `texture` right now.
+    bound: bool,
+    pub stats: CaptureStats,
+}
+
+/// Uploads as much of the newest capture as the budget allows, and puts it on
+/// the textured quad once a whole capture is in. Turning capturing off gives
+/// the quad its own texture back.
+#[allow(clippy::too_many_arguments)]
+pub fn capture_upload_system(
+    gpu: Res<GpuContext>,
+    settings: Res<CaptureSettings>,
+    (asset_settings, sampler_settings): (Res<AssetSettings>, Res<SamplerSettings>),
+    mut capture: ResMut<DesktopCapture>,
+    mut assets: ResMut<AssetServer>,
+    mut samplers: ResMut<SamplerCache>,
+    (layout, mut diffuse): (Res<DiffuseBindGroupLayout>, ResMut<DiffuseTexture>),
+    mut bind_group: ResMut<DiffuseBindGroup>,
+) {
+    let region = settings.enabled.then_some(Region {
+        x: settings.x,
+        y: settings.y,
+        width: settings.width.max(1),
+        height: settings.height.max(1),
+    });
+    let latest = match capture.shared.lock() {
+        Ok(mut shared) => {
+            shared.region = region;
+            // A capture half way up is finished first, or the quad would tear
+            if capture.upload.is_none() {
+                shared.latest.take()
+            } else {
+                None
+            }
+        }
+        Err(_) => {
+            error!("The capture thread panicked");
+            return;
+        }
+    };
+
+    if !settings.enabled {
+        capture.upload = None;
+        capture.texture = None;
+        if capture.bound {
+            // No asset version is 0, the diffuse system rebuilds with its own
+            // texture next frame
+            diffuse.version = 0;
+            capture.bound = false;
+        }
+        return;
+    }
+
+    if let Some(image) = latest {
+        let size = capture
+            .texture
+            .as_ref()
+            .map(|texture| texture.texture.size());
+        if size.map(|size| (size.width, size.height)) != Some(image.dimensions()) {
+            info!(
+                "Capturing {}x{} from the desktop",
+                image.width(),
+                image.height()
+            );
+            let mut texture = Texture::streamed(
+                &gpu.device,
+                image.width(),
+                image.height(),
+                "desktop_capture",
+            );
+            // Only the top level is written, a mip chain per capture would
+            // double the uploads
+            texture.view = texture.texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("desktop_capture_view"),
+                mip_level_count: Some(1),
+                ..Default::default()
+            });
+            capture.texture = Some(texture);
+            capture.bound = false;
+        }
+        capture.upload = Some(Upload { image, next_row: 0 });
+    }
+
+    let capture = &mut *capture;
+    let Some(texture) = &capture.texture else {
+        return;
+    };
+    let mut complete = false;
+    if let Some(upload) = &mut capture.upload {
+        let row_bytes = upload.image.width() as u64 * 4;
+        let budget = asset_settings.upload_kib as u64 * 1024;
+        // One row always goes up, or a budget below a row would stall
+        let rows =
+            ((budget / row_bytes).max(1) as u32).min(upload.image.height() - upload.next_row);
+        texture.write_mip_rows(&gpu.queue, 0, &upload.image, upload.next_row, rows);
+        upload.next_row += rows;
+        let bytes = rows as u64 * row_bytes;
+        capture.stats.uploaded_bytes += bytes;
+        assets.stats.streamed_bytes += bytes;
+        complete = upload.next_row == upload.image.height();
+    }
+    if complete {
+        capture.upload = None;
+        capture.stats.uploaded += 1;
+    }
+
+    // Bound again when the diffuse system replaced it, e.g. for new filtering
+    let rebind = if capture.bound {
+        bind_group.is_changed()
+    } else {
+        complete
+    };
+    if rebind {
+        let key = SamplerKey::material(&sampler_settings, wgpu::AddressMode::ClampToEdge);
+        let sampler = samplers.get(&gpu.device, key);
+        match DiffuseBindGroup::new(&gpu, &layout, texture, sampler, &diffuse.tint) {
+            Ok(capture_bind_group) => {
+                *bind_group = capture_bind_group;
+                capture.bound = true;
+            }
+            Err(e) => warn!("Failed to bind the desktop capture: {}", e),
+        }
+    }
+}
+
+// =============================== SETTINGS ===============================
+/// The captured region, in desktop pixels.
+#[derive(Resource, Clone, PartialEq)]
+pub struct CaptureSettings {
+    pub enabled: bool,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            x: 0,
+            y: 0,
+            width: 1280,
+            height: 720,
+        }
+    }
+}
+
+fn capture_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<CaptureSettings>().clone();
+    let capture = world.resource::<DesktopCapture>();
+    let stats = capture.stats;
+    let (captured, dropped, capture_ms, error) = match capture.shared.lock() {
+        Ok(shared) => (
+            shared.captured,
+            shared.dropped,
+            shared.capture_ms,
+            shared.error.clone(),
+        ),
+        Err(_) => (
+            0,
+            0,
+            0.0,
+            Some(tr("The capture thread panicked").to_string()),
+        ),
+    };
+
+    egui::Window::new(tr("Desktop capture"))
+        .id(egui::Id::new("Desktop capture"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Capture"))
+                .on_hover_text(tr("Shows the region on the textured quad"));
+            egui::Grid::new("capture_region").show(ui, |ui| {
+                ui.label(tr("Position"));
+                ui.add(egui::DragValue::new(&mut settings.x));
+                ui.add(egui::DragValue::new(&mut settings.y));
+                ui.end_row();
+                ui.label(tr("Size"));
+                ui.add(egui::DragValue::new(&mut settings.width).range(1..=7680));
+                ui.add(egui::DragValue::new(&mut settings.height).range(1..=4320));
+                ui.end_row();
+            });
+            ui.label(trf!(
+                "{} captured, {} dropped, {:.1} ms per capture",
+                captured,
+                dropped,
+                capture_ms
+            ));
+            ui.label(trf!(
+                "{} uploaded, {:.2} MiB",
+                stats.uploaded,
+                stats.uploaded_bytes as f64 / (1024.0 * 1024.0)
+            ));
+            ui.label(tr(
+                "Uploads share the mip streaming budget of the asset panel",
+            ));
+            if let Some(error) = error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        });
+
+    if settings != *world.resource::<CaptureSettings>() {
+        world.insert_resource(settings);
+    }
+}
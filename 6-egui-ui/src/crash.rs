@@ -0,0 +1,280 @@
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Write as _},
+    panic::PanicHookInfo,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use bevy_ecs::{
+    change_detection::DetectChanges,
+    observer::Trigger,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, Resource},
+    world::World,
+};
+use playground_app::FrameStart;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::{gpu::GpuContext, pipeline::render::render_system};
+
+pub fn setup_crash_reporter(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let reporter = world
+        .get_resource::<CrashReporter>()
+        .ok_or_else(|| anyhow::anyhow!("CrashReporter resource not found"))?;
+    {
+        let mut state = reporter.state.lock().unwrap();
+        let info = &gpu.adapter_info;
+        state.adapter = format!(
+            "{} ({:?}, {:?}), driver {} {}",
+            info.name, info.backend, info.device_type, info.driver, info.driver_info
+        );
+        state.features = format!("{:?}", gpu.device.features());
+        state.limits = format!("{:#?}", gpu.device.limits());
+    }
+
+    world.add_observer(crash_frame_start_observer);
+    schedule.add_systems(crash_context_system.before(render_system));
+
+    Ok(())
+}
+
+// =============================== REPORTER ===============================
+/// Keeps what a crash report needs up to date, so the panic hook only has to
+/// write it out. Shared with the tracing layer that collects log lines and
+/// the passes being recorded.
+#[derive(Resource, Clone, Default)]
+pub struct CrashReporter {
+    state: Arc<Mutex<CrashState>>,
+}
+
+#[derive(Default)]
+struct CrashState {
+    frame: u64,
+    adapter: String,
+    features: String,
+    limits: String,
+    surface: String,
+    /// Innermost last, more than one when a pass records a nested one.
+    open_passes: Vec<String>,
+    frame_passes: Vec<String>,
+    log: VecDeque<String>,
+}
+
+impl CrashReporter {
+    /// Log lines kept for the report.
+    const LOG_LINES: usize = 200;
+
+    pub fn layer(&self) -> CrashLayer {
+        CrashLayer {
+            reporter: self.clone(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Chains onto the current panic hook, so install it after
+    /// `better_panic` to keep its backtrace output.
+    pub fn install_hook(&self) {
+        let reporter = self.clone();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            match reporter.write_report(info) {
+                Ok(path) => eprintln!("Crash report written to {}", path.display()),
+                Err(e) => eprintln!("Failed to write crash report: {}", e),
+            }
+            previous(info);
+        }));
+    }
+
+    /// Writes `crash-<unix time>.txt` to the working directory.
+    fn write_report(&self, info: &PanicHookInfo) -> std::io::Result<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let path = PathBuf::from(format!("crash-{}.txt", timestamp));
+        std::fs::write(&path, self.report(info, timestamp))?;
+        Ok(path)
+    }
+
+    fn report(&self, info: &PanicHookInfo, timestamp: u64) -> String {
+        let mut report = String::new();
+        let thread = std::thread::current();
+        let _ = writeln!(
+            report,
+            "wgpu-playground crash report, unix time {}",
+            timestamp
+        );
+        let _ = writeln!(
+            report,
+            "Panic on thread '{}': {}",
+            thread.name().unwrap_or("<unnamed>"),
+            info
+        );
+
+        // The panic may come from inside the layer while it holds the lock
+        let Ok(state) = self.state.try_lock() else {
+            report.push_str(
+                "\nRenderer state unavailable, the panic happened while it was being updated\n",
+            );
+            return report;
+        };
+        let _ = writeln!(report, "Frame: {}", state.frame);
+        let _ = writeln!(report, "Adapter: {}", state.adapter);
+        let _ = writeln!(report, "Surface: {}", state.surface);
+        let _ = writeln!(report, "Passes open: {}", list(&state.open_passes));
+        let _ = writeln!(report, "Passes this frame: {}", list(&state.frame_passes));
+        let _ = writeln!(report, "\nFeatures: {}", state.features);
+        let _ = writeln!(report, "\nLimits: {}", state.limits);
+        let _ = writeln!(
+            report,
+            "\nBacktrace:\n{}",
+            std::backtrace::Backtrace::force_capture()
+        );
+        let _ = writeln!(report, "Last {} log lines:", state.log.len());
+        for line in &state.log {
+            let _ = writeln!(report, "{}", line);
+        }
+        report
+    }
+}
+
+fn list(items: &[String]) -> String {
+    if items.is_empty() {
+        "none".to_string()
+    } else {
+        items.join(", ")
+    }
+}
+
+// =============================== SYSTEMS ===============================
+/// Starts a new frame in the report.
+pub fn crash_frame_start_observer(trigger: Trigger<FrameStart>, reporter: Res<CrashReporter>) {
+    let mut state = reporter.state.lock().unwrap();
+    state.frame = trigger.event().frame;
+    state.frame_passes.clear();
+}
+
+/// Keeps the surface config in the report current.
+pub fn crash_context_system(reporter: Res<CrashReporter>, gpu: Res<GpuContext>) {
+    let mut state = reporter.state.lock().unwrap();
+    if gpu.is_changed() || state.surface.is_empty() {
+        let config = &gpu.config;
+        state.surface = format!(
+            "{}x{} {:?}, {:?}, {:?}, view formats {:?}, scale {:.2}",
+            config.width,
+            config.height,
+            config.format,
+            config.present_mode,
+            config.alpha_mode,
+            config.view_formats,
+            gpu.scale
+        );
+    }
+}
+
+// =============================== TRACING LAYER ===============================
+/// Label of a render graph pass span, from its `pass` field.
+struct PassLabel(String);
+
+struct PassVisitor(Option<String>);
+
+impl Visit for PassVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "pass" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "pass" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+/// Formats the message first, then the other fields as `name=value`.
+#[derive(Default)]
+struct LineVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Tracing layer recording log lines and open render passes for a [`CrashReporter`].
+pub struct CrashLayer {
+    reporter: CrashReporter,
+    start: Instant,
+}
+
+impl<S> Layer<S> for CrashLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut visitor = PassVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(label), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(PassLabel(label));
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        if let Some(PassLabel(label)) = extensions.get::<PassLabel>() {
+            let mut state = self.reporter.state.lock().unwrap();
+            state.open_passes.push(label.clone());
+            state.frame_passes.push(label.clone());
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if span.extensions().get::<PassLabel>().is_some() {
+            self.reporter.state.lock().unwrap().open_passes.pop();
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = LineVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let line = format!(
+            "{:>10.3}s {:>5} {}: {}{}",
+            self.start.elapsed().as_secs_f32(),
+            metadata.level(),
+            metadata.target(),
+            visitor.message,
+            visitor.fields
+        );
+
+        let mut state = self.reporter.state.lock().unwrap();
+        if state.log.len() == CrashReporter::LOG_LINES {
+            state.log.pop_front();
+        }
+        state.log.push_back(line);
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, world::World};
+
+use crate::{
+    capabilities::FormatCapabilities,
+    gpu::{GpuContext, OPTIONAL_FEATURES},
+    i18n::{tr, trf},
+    layout::LayoutCache,
+    pipeline::{subgroups::SubgroupDemo, ui::UiPanels},
+};
+
+pub fn setup_diagnostics(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(diagnostics_panel);
+
+    Ok(())
+}
+
+/// What the device negotiation ended up with, and which paths that selected.
+fn diagnostics_panel(ctx: &egui::Context, world: &mut World) {
+    let gpu = world.resource::<GpuContext>();
+    let info = &gpu.adapter_info;
+    let features = gpu.device.features();
+
+    egui::Window::new(tr("Diagnostics"))
+        .id(egui::Id::new("Diagnostics"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.label(trf!(
+                "Adapter: {} ({})",
+                info.name,
+                format!("{:?}", info.device_type)
+            ));
+            ui.label(trf!("Backend: {}", format!("{:?}", info.backend)));
+            ui.label(trf!("Driver: {} {}", info.driver, info.driver_info));
+
+            ui.separator();
+            ui.label(tr("Optional features"));
+            for (name, feature) in OPTIONAL_FEATURES.iter_names() {
+                let enabled = features.contains(feature);
+                ui.label(format!("{} {}", if enabled { "✔" } else { "✖" }, name));
+            }
+
+            ui.separator();
+            match gpu.subgroup_sizes() {
+                Some((min, max)) => ui.label(trf!("Subgroup size: {}..={}", min, max)),
+                None => ui.label(tr("Subgroups unsupported")),
+            };
+            if let Some(demo) = world.get_resource::<SubgroupDemo>() {
+                ui.label(trf!("Subgroup demo path: {}", demo.path.name()));
+                match &demo.result {
+                    Ok(result) => ui.label(trf!(
+                        "{} odd, maximum {} ({})",
+                        result.odd,
+                        result.maximum,
+                        if result.correct {
+                            tr("matches the CPU")
+                        } else {
+                            tr("does not match the CPU")
+                        }
+                    )),
+                    Err(e) => ui.label(trf!("Failed: {}", e)),
+                };
+            }
+
+            if let Some(layouts) = world.get_resource::<LayoutCache>() {
+                ui.separator();
+                ui.label(trf!(
+                    "{} bind group layouts, {} reused",
+                    layouts.cached(),
+                    layouts.shared()
+                ));
+            }
+
+            if let Some(capabilities) = world.get_resource::<FormatCapabilities>() {
+                ui.separator();
+                egui::CollapsingHeader::new(tr("Texture formats"))
+                    .id_salt("Texture formats")
+                    .show(ui, |ui| {
+                        capabilities.ui(ui);
+                    });
+            }
+        });
+}
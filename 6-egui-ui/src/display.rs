@@ -0,0 +1,303 @@
+use anyhow::Result;
+use bevy_ecs::{
+    observer::Trigger,
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use tracing::{info, warn};
+use winit::{
+    monitor::{MonitorHandle, VideoModeHandle},
+    window::Fullscreen,
+};
+
+use crate::{
+    gpu::{GpuContext, SurfaceChanged},
+    i18n::{tr, trf},
+    pipeline::ui::UiPanels,
+};
+
+pub fn setup_display(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let monitors = Monitors::enumerate(gpu);
+    info!("Found {} monitors", monitors.monitors.len());
+    let settings = DisplaySettings {
+        monitor: monitors.current,
+        ..Default::default()
+    };
+    world.insert_resource(monitors);
+    world.insert_resource(settings);
+    world.init_resource::<LastSurfaceChange>();
+    world.add_observer(surface_changed_observer);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(display_panel);
+
+    schedule.add_systems(apply_display_system.run_if(resource_changed::<DisplaySettings>));
+
+    Ok(())
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayMode {
+    Windowed,
+    /// A borderless window covering the monitor, keeps the desktop mode.
+    Borderless,
+    /// Takes over the monitor with the selected video mode.
+    Exclusive,
+}
+impl DisplayMode {
+    pub const ALL: [DisplayMode; 3] = [Self::Windowed, Self::Borderless, Self::Exclusive];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Windowed => "Windowed",
+            Self::Borderless => "Borderless fullscreen",
+            Self::Exclusive => "Exclusive fullscreen",
+        }
+    }
+}
+
+/// Indices into [`Monitors`], applied to the window whenever they change.
+#[derive(Resource, Clone, PartialEq)]
+pub struct DisplaySettings {
+    pub mode: DisplayMode,
+    pub monitor: usize,
+    pub video_mode: usize,
+}
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            mode: DisplayMode::Windowed,
+            monitor: 0,
+            video_mode: 0,
+        }
+    }
+}
+
+// =============================== MONITORS ===============================
+pub struct MonitorInfo {
+    pub handle: MonitorHandle,
+    pub name: String,
+    /// Sorted from the largest resolution and highest refresh rate down.
+    pub modes: Vec<VideoModeHandle>,
+}
+
+/// Monitors and their video modes, as reported when last enumerated.
+#[derive(Resource, Default)]
+pub struct Monitors {
+    pub monitors: Vec<MonitorInfo>,
+    /// The monitor the window is on, as of enumerating or the last surface
+    /// change.
+    pub current: usize,
+}
+impl Monitors {
+    pub fn enumerate(gpu: &GpuContext) -> Self {
+        let current = gpu.window.current_monitor();
+        let monitors = gpu
+            .window
+            .available_monitors()
+            .enumerate()
+            .map(|(i, handle)| {
+                let mut modes = handle.video_modes().collect::<Vec<_>>();
+                modes.sort_by_key(|mode| {
+                    std::cmp::Reverse((
+                        mode.size().width,
+                        mode.size().height,
+                        mode.refresh_rate_millihertz(),
+                        mode.bit_depth(),
+                    ))
+                });
+                MonitorInfo {
+                    name: handle.name().unwrap_or_else(|| trf!("Monitor {}", i + 1)),
+                    handle,
+                    modes,
+                }
+            })
+            .collect::<Vec<_>>();
+        let current = current
+            .and_then(|current| monitors.iter().position(|m| m.handle == current))
+            .unwrap_or(0);
+
+        Self { monitors, current }
+    }
+
+    pub fn mode_name(mode: &VideoModeHandle) -> String {
+        format!(
+            "{}x{} @ {:.2} Hz ({} bit)",
+            mode.size().width,
+            mode.size().height,
+            mode.refresh_rate_millihertz() as f32 / 1000.0,
+            mode.bit_depth()
+        )
+    }
+}
+
+/// The most recent [`SurfaceChanged`], shown in the display panel.
+#[derive(Resource, Default)]
+pub struct LastSurfaceChange(pub Option<SurfaceChanged>);
+
+fn surface_changed_observer(
+    trigger: Trigger<SurfaceChanged>,
+    gpu: Res<GpuContext>,
+    mut monitors: ResMut<Monitors>,
+    mut last: ResMut<LastSurfaceChange>,
+) {
+    if let Some(current) = gpu
+        .monitor
+        .as_ref()
+        .and_then(|monitor| monitors.monitors.iter().position(|m| m.handle == *monitor))
+    {
+        monitors.current = current;
+    }
+    last.0 = Some(trigger.event().clone());
+}
+
+/// Switches the window to the selected mode. The resize that follows goes
+/// through the usual path and reconfigures the surface.
+pub fn apply_display_system(
+    gpu: Res<GpuContext>,
+    monitors: Res<Monitors>,
+    settings: Res<DisplaySettings>,
+) {
+    let monitor = monitors.monitors.get(settings.monitor);
+    let fullscreen = match settings.mode {
+        DisplayMode::Windowed => None,
+        DisplayMode::Borderless => Some(Fullscreen::Borderless(
+            monitor.map(|monitor| monitor.handle.clone()),
+        )),
+        DisplayMode::Exclusive => {
+            let Some(mode) = monitor.and_then(|monitor| monitor.modes.get(settings.video_mode))
+            else {
+                warn!("No video mode selected, staying in the current mode");
+                return;
+            };
+            Some(Fullscreen::Exclusive(mode.clone()))
+        }
+    };
+    if gpu.window.fullscreen() == fullscreen {
+        return;
+    }
+    info!("Switching display mode: {:?}", fullscreen);
+    gpu.window.set_fullscreen(fullscreen);
+}
+
+fn display_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<DisplaySettings>().clone();
+    let mut refresh = false;
+    {
+        let monitors = world.resource::<Monitors>();
+        let config = &world.resource::<GpuContext>().config;
+        let last_change = &world.resource::<LastSurfaceChange>().0;
+
+        egui::Window::new(tr("Display"))
+            .id(egui::Id::new("Display"))
+            .default_open(false)
+            .show(ctx, |ui| {
+                egui::ComboBox::from_label(tr("Mode"))
+                    .selected_text(tr(settings.mode.name()))
+                    .show_ui(ui, |ui| {
+                        for mode in DisplayMode::ALL {
+                            ui.selectable_value(&mut settings.mode, mode, tr(mode.name()));
+                        }
+                    });
+
+                let monitor_name = |i: usize| {
+                    monitors
+                        .monitors
+                        .get(i)
+                        .map_or(tr("None"), |monitor| monitor.name.as_str())
+                };
+                ui.add_enabled_ui(settings.mode != DisplayMode::Windowed, |ui| {
+                    egui::ComboBox::from_label(tr("Monitor"))
+                        .selected_text(monitor_name(settings.monitor))
+                        .show_ui(ui, |ui| {
+                            for i in 0..monitors.monitors.len() {
+                                // Modes belong to a monitor, start from its best one
+                                if ui
+                                    .selectable_value(&mut settings.monitor, i, monitor_name(i))
+                                    .changed()
+                                {
+                                    settings.video_mode = 0;
+                                }
+                            }
+                        });
+                });
+
+                let modes = monitors
+                    .monitors
+                    .get(settings.monitor)
+                    .map_or(&[][..], |monitor| monitor.modes.as_slice());
+                ui.add_enabled_ui(settings.mode == DisplayMode::Exclusive, |ui| {
+                    egui::ComboBox::from_label(tr("Video mode"))
+                        .selected_text(
+                            modes
+                                .get(settings.video_mode)
+                                .map_or(tr("None").to_string(), Monitors::mode_name),
+                        )
+                        .show_ui(ui, |ui| {
+                            for (i, mode) in modes.iter().enumerate() {
+                                ui.selectable_value(
+                                    &mut settings.video_mode,
+                                    i,
+                                    Monitors::mode_name(mode),
+                                );
+                            }
+                        });
+                });
+
+                ui.separator();
+                ui.label(trf!("Surface: {}x{}", config.width, config.height));
+                ui.label(format!("{:?}, {:?}", config.format, config.present_mode));
+                if let Some(change) = last_change {
+                    ui.label(trf!(
+                        "Last change on {}:",
+                        change.monitor.as_deref().unwrap_or(tr("unknown monitor"))
+                    ));
+                    if change.format_changed() {
+                        ui.label(format!(
+                            "  format {:?} -> {:?}",
+                            change.previous_format, change.format
+                        ));
+                    }
+                    if change.present_mode != change.previous_present_mode {
+                        ui.label(format!(
+                            "  present mode {:?} -> {:?}",
+                            change.previous_present_mode, change.present_mode
+                        ));
+                    }
+                    if change.scale != change.previous_scale {
+                        ui.label(trf!(
+                            "  scale {:.2} -> {:.2}",
+                            change.previous_scale,
+                            change.scale
+                        ));
+                    }
+                }
+                refresh = ui
+                    .button(tr("Refresh monitors"))
+                    .on_hover_text(tr("Enumerate again after plugging in a display"))
+                    .clicked();
+            });
+    }
+
+    if refresh {
+        let monitors = Monitors::enumerate(world.resource::<GpuContext>());
+        // Indices may point elsewhere now, fall back to a mode that exists
+        if settings.monitor >= monitors.monitors.len() {
+            settings.monitor = monitors.current;
+            settings.video_mode = 0;
+        }
+        world.insert_resource(monitors);
+    }
+
+    let mut display = world.resource_mut::<DisplaySettings>();
+    if *display != settings {
+        *display = settings;
+    }
+}
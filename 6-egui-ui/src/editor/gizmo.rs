@@ -0,0 +1,381 @@
+use bevy_ecs::{
+    entity::Entity,
+    system::{Query, Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4, Vec4Swizzles};
+use playground_app::Input;
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use crate::{
+    i18n::{tr, trf},
+    input::InputState,
+    pipeline::{debug_draw::DebugDraw, present::PresentViewport, ui::EguiState},
+    raycast::{Ray, RayHit, Raycast},
+    scene::{Camera, GlobalTransform, Parent, Transform},
+};
+
+use super::{
+    history::{CommandHistory, ComponentEdit},
+    Selection,
+};
+
+/// Gizmo size as a fraction of its distance to the camera, so it keeps a
+/// constant size on screen.
+const GIZMO_SCALE: f32 = 0.15;
+/// Pick tolerance around a handle, relative to the gizmo size.
+const HANDLE_TOLERANCE: f32 = 0.08;
+const AXES: [Vec3; 3] = [Vec3::X, Vec3::Y, Vec3::Z];
+const AXIS_COLORS: [Vec4; 3] = [
+    Vec4::new(0.9, 0.2, 0.2, 1.0),
+    Vec4::new(0.2, 0.9, 0.2, 1.0),
+    Vec4::new(0.2, 0.4, 1.0, 1.0),
+];
+const ACTIVE_COLOR: Vec4 = Vec4::new(1.0, 0.9, 0.1, 1.0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+#[derive(Resource)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    pub snapping: bool,
+    pub translate_step: f32,
+    /// Degrees.
+    pub rotate_step: f32,
+    pub scale_step: f32,
+    pub hovered: Option<usize>,
+    pub drag: Option<GizmoDrag>,
+    /// Draws the raycast hit under the cursor.
+    pub show_hit: bool,
+    pub cursor_hit: Option<RayHit>,
+}
+impl Default for Gizmo {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            snapping: false,
+            translate_step: 0.5,
+            rotate_step: 15.0,
+            scale_step: 0.1,
+            hovered: None,
+            drag: None,
+            show_hit: false,
+            cursor_hit: None,
+        }
+    }
+}
+impl Gizmo {
+    fn snap(&self, value: f32, step: f32) -> f32 {
+        if self.snapping && step > 0.0 {
+            (value / step).round() * step
+        } else {
+            value
+        }
+    }
+}
+
+/// An in-progress handle drag, relative to the state at mouse down.
+#[derive(Clone, Copy, Debug)]
+pub struct GizmoDrag {
+    pub entity: Entity,
+    pub axis: usize,
+    pub start: Transform,
+    /// World matrix of the parent, to bring world-space deltas into local space.
+    pub parent: Mat4,
+    /// Axis parameter (translate, scale) or plane hit direction (rotate) at mouse down.
+    pub origin: Vec3,
+}
+
+/// W, E and R switch the gizmo mode, unless they are flying the camera.
+fn mode_shortcut(input: &Input, camera_input: &InputState) -> Option<GizmoMode> {
+    if input.modifiers.control_key() || camera_input.mouse.looking {
+        return None;
+    }
+    [
+        (KeyCode::KeyW, GizmoMode::Translate),
+        (KeyCode::KeyE, GizmoMode::Rotate),
+        (KeyCode::KeyR, GizmoMode::Scale),
+    ]
+    .into_iter()
+    .find(|(key, _)| input.keys.just_pressed(*key))
+    .map(|(_, mode)| mode)
+}
+
+/// Click-selects entities and drives handle drags. Runs before transform
+/// propagation so edits show up in the same frame.
+pub fn gizmo_interaction_system(
+    (viewport, ui, camera): (PresentViewport, Res<EguiState>, Res<Camera>),
+    (input, camera_input): (Res<Input>, Res<InputState>),
+    mut gizmo: ResMut<Gizmo>,
+    (mut selection, mut history): (ResMut<Selection>, ResMut<CommandHistory>),
+    mut transforms: Query<&mut Transform>,
+    globals: Query<(&GlobalTransform, Option<&Parent>)>,
+    raycast: Raycast,
+) {
+    let just_pressed = input.mouse_buttons.just_pressed(MouseButton::Left);
+    let just_released = input.mouse_buttons.just_released(MouseButton::Left);
+    let context = ui.renderer.context();
+    if let Some(mode) = mode_shortcut(&input, &camera_input) {
+        if !context.wants_keyboard_input() {
+            gizmo.mode = mode;
+        }
+    }
+    if just_released {
+        gizmo.drag = None;
+    }
+
+    let [x, y, width, height] = viewport.rect();
+    let Some(ray) = input.cursor.map(|cursor| {
+        Ray::from_screen(&camera, cursor - Vec2::new(x, y), Vec2::new(width, height))
+    }) else {
+        gizmo.hovered = None;
+        gizmo.cursor_hit = None;
+        return;
+    };
+    gizmo.cursor_hit = gizmo.show_hit.then(|| raycast.cast(&ray)).flatten();
+
+    // Continue an active drag
+    if let Some(drag) = gizmo.drag {
+        if let Ok(mut transform) = transforms.get_mut(drag.entity) {
+            let center = drag.parent.transform_point3(drag.start.translation);
+            let handles = Handles::new(&camera, center, &drag.start, &drag.parent);
+            if let Some(edited) = gizmo.apply_drag(&drag, &handles, &ray) {
+                history.record(ComponentEdit {
+                    entity: drag.entity,
+                    before: *transform,
+                    after: edited,
+                });
+                *transform = edited;
+            }
+        }
+        return;
+    }
+
+    let pointer_over_ui = context.is_pointer_over_area() || context.wants_pointer_input();
+    let selected = selection.entity.and_then(|entity| {
+        Some((
+            entity,
+            transforms.get(entity).ok()?,
+            globals.get(entity).ok()?,
+        ))
+    });
+    let handles = selected.map(|(_, transform, (global, parent))| {
+        let parent = parent_matrix(&globals, parent);
+        let center = global.0.w_axis.xyz();
+        (Handles::new(&camera, center, transform, &parent), parent)
+    });
+
+    gizmo.hovered = handles
+        .as_ref()
+        .filter(|_| !pointer_over_ui)
+        .and_then(|(handles, _)| handles.pick(gizmo.mode, &ray));
+
+    if !just_pressed || pointer_over_ui {
+        return;
+    }
+    if let (Some(axis), Some((entity, transform, _)), Some((handles, parent))) =
+        (gizmo.hovered, selected, handles)
+    {
+        gizmo.drag = Some(GizmoDrag {
+            entity,
+            axis,
+            start: *transform,
+            parent,
+            origin: handles.drag_origin(gizmo.mode, axis, &ray),
+        });
+        return;
+    }
+
+    // Nothing grabbed: select whatever is under the cursor
+    selection.entity = raycast.cast(&ray).map(|hit| hit.entity);
+}
+
+pub fn gizmo_draw_system(
+    camera: Res<Camera>,
+    gizmo: Res<Gizmo>,
+    selection: Res<Selection>,
+    query: Query<(&Transform, &GlobalTransform, Option<&Parent>)>,
+    globals: Query<(&GlobalTransform, Option<&Parent>)>,
+    mut debug_draw: ResMut<DebugDraw>,
+) {
+    if let Some(hit) = gizmo.cursor_hit {
+        let size = (camera.eye - hit.point).length() * GIZMO_SCALE;
+        debug_draw.wire_sphere(hit.point, size * 0.05, ACTIVE_COLOR);
+        debug_draw.arrow(hit.point, hit.point + hit.normal * size * 0.5, ACTIVE_COLOR);
+    }
+    let Some((transform, global, parent)) = selection.entity.and_then(|e| query.get(e).ok()) else {
+        return;
+    };
+    let parent = parent_matrix(&globals, parent);
+    let handles = Handles::new(&camera, global.0.w_axis.xyz(), transform, &parent);
+    let active = gizmo.drag.map(|drag| drag.axis).or(gizmo.hovered);
+
+    for (axis, axis_color) in AXIS_COLORS.into_iter().enumerate() {
+        let color = if active == Some(axis) {
+            ACTIVE_COLOR
+        } else {
+            axis_color
+        };
+        let direction = handles.axis(gizmo.mode, axis);
+        let end = handles.center + direction * handles.size;
+        match gizmo.mode {
+            GizmoMode::Translate => debug_draw.arrow(handles.center, end, color),
+            GizmoMode::Rotate => debug_draw.circle(handles.center, direction, handles.size, color),
+            GizmoMode::Scale => {
+                debug_draw.line(handles.center, end, color);
+                let half = Vec3::splat(handles.size * HANDLE_TOLERANCE * 0.5);
+                debug_draw.wire_box(end - half, end + half, color);
+            }
+        }
+    }
+}
+
+pub fn gizmo_panel(ctx: &egui::Context, world: &mut World) {
+    let mut gizmo = world.resource_mut::<Gizmo>();
+
+    egui::Window::new(tr("Gizmo"))
+        .id(egui::Id::new("Gizmo"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut gizmo.mode, GizmoMode::Translate, tr("Translate (W)"));
+                ui.selectable_value(&mut gizmo.mode, GizmoMode::Rotate, tr("Rotate (E)"));
+                ui.selectable_value(&mut gizmo.mode, GizmoMode::Scale, tr("Scale (R)"));
+            });
+            ui.checkbox(&mut gizmo.snapping, tr("Snapping"));
+            ui.add_enabled_ui(gizmo.snapping, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut gizmo.translate_step, 0.05..=5.0)
+                        .text(tr("translate step")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut gizmo.rotate_step, 1.0..=90.0)
+                        .text(tr("rotate step (°)")),
+                );
+                ui.add(egui::Slider::new(&mut gizmo.scale_step, 0.01..=1.0).text(tr("scale step")));
+            });
+            ui.checkbox(&mut gizmo.show_hit, tr("Show cursor hit"));
+            if let Some(hit) = gizmo.cursor_hit {
+                ui.label(trf!("{} at {:.2}", hit.entity, hit.distance));
+            }
+        });
+}
+
+fn parent_matrix(
+    globals: &Query<(&GlobalTransform, Option<&Parent>)>,
+    parent: Option<&Parent>,
+) -> Mat4 {
+    parent
+        .and_then(|parent| globals.get(parent.0).ok())
+        .map_or(Mat4::IDENTITY, |(global, _)| global.0)
+}
+
+// =============================== HANDLES ===============================
+/// World-space gizmo geometry for one entity.
+struct Handles {
+    center: Vec3,
+    size: f32,
+    /// Translate and rotate work along world axes, scale along the entity's own.
+    axes: [Vec3; 3],
+    scale_axes: [Vec3; 3],
+}
+impl Handles {
+    fn new(camera: &Camera, center: Vec3, transform: &Transform, parent: &Mat4) -> Self {
+        let (_, parent_rotation, _) = parent.to_scale_rotation_translation();
+        let rotation = parent_rotation * transform.rotation;
+        Self {
+            center,
+            size: (camera.eye - center).length() * GIZMO_SCALE,
+            axes: AXES,
+            scale_axes: AXES.map(|axis| rotation * axis),
+        }
+    }
+
+    fn axis(&self, mode: GizmoMode, axis: usize) -> Vec3 {
+        match mode {
+            GizmoMode::Scale => self.scale_axes[axis],
+            _ => self.axes[axis],
+        }
+    }
+
+    /// The handle under the ray, if any.
+    fn pick(&self, mode: GizmoMode, ray: &Ray) -> Option<usize> {
+        let tolerance = self.size * HANDLE_TOLERANCE;
+        (0..3)
+            .filter_map(|axis| {
+                let direction = self.axis(mode, axis);
+                let distance = match mode {
+                    GizmoMode::Translate | GizmoMode::Scale => {
+                        let (ray_t, axis_t) = ray.closest_to_line(self.center, direction)?;
+                        let on_handle = (0.0..=self.size).contains(&axis_t);
+                        let distance =
+                            (ray.at(ray_t) - (self.center + direction * axis_t)).length();
+                        on_handle.then_some(distance)?
+                    }
+                    GizmoMode::Rotate => {
+                        let t = ray.intersect_plane(self.center, direction)?;
+                        ((ray.at(t) - self.center).length() - self.size).abs()
+                    }
+                };
+                (distance < tolerance).then_some((axis, distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(axis, _)| axis)
+    }
+
+    fn drag_origin(&self, mode: GizmoMode, axis: usize, ray: &Ray) -> Vec3 {
+        let direction = self.axis(mode, axis);
+        match mode {
+            GizmoMode::Translate | GizmoMode::Scale => {
+                let axis_t = ray
+                    .closest_to_line(self.center, direction)
+                    .map_or(0.0, |(_, t)| t);
+                Vec3::splat(axis_t)
+            }
+            GizmoMode::Rotate => ray
+                .intersect_plane(self.center, direction)
+                .map_or(Vec3::ZERO, |t| ray.at(t) - self.center),
+        }
+    }
+}
+
+impl Gizmo {
+    /// The dragged entity's transform for the current cursor ray.
+    fn apply_drag(&self, drag: &GizmoDrag, handles: &Handles, ray: &Ray) -> Option<Transform> {
+        let direction = handles.axis(self.mode, drag.axis);
+        let mut transform = drag.start;
+        match self.mode {
+            GizmoMode::Translate => {
+                let (_, axis_t) = ray.closest_to_line(handles.center, direction)?;
+                let delta = self.snap(axis_t - drag.origin.x, self.translate_step);
+                let local = drag.parent.inverse().transform_vector3(direction * delta);
+                transform.translation = drag.start.translation + local;
+            }
+            GizmoMode::Rotate => {
+                let current =
+                    ray.at(ray.intersect_plane(handles.center, direction)?) - handles.center;
+                let angle = direction
+                    .dot(drag.origin.cross(current))
+                    .atan2(drag.origin.dot(current));
+                let angle = self.snap(angle.to_degrees(), self.rotate_step).to_radians();
+                let (_, parent_rotation, _) = drag.parent.to_scale_rotation_translation();
+                let local_axis = parent_rotation.inverse() * direction;
+                transform.rotation = Quat::from_axis_angle(local_axis, angle) * drag.start.rotation;
+            }
+            GizmoMode::Scale => {
+                let (_, axis_t) = ray.closest_to_line(handles.center, direction)?;
+                if drag.origin.x.abs() <= f32::EPSILON {
+                    return None;
+                }
+                let factor = self.snap(axis_t / drag.origin.x, self.scale_step).max(0.01);
+                transform.scale[drag.axis] = drag.start.scale[drag.axis] * factor;
+            }
+        }
+        Some(transform)
+    }
+}
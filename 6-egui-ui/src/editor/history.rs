@@ -0,0 +1,385 @@
+use std::{any::Any, collections::VecDeque};
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+
+use playground_app::Input;
+use winit::{
+    event::MouseButton,
+    keyboard::{KeyCode, ModifiersState},
+};
+
+use crate::{
+    i18n::{tr, trf},
+    pipeline::ui::EguiState,
+    scene::{MaterialDesc, MaterialTable},
+};
+
+/// Oldest steps are dropped once the history grows past this.
+const HISTORY_BUDGET_BYTES: usize = 1 << 20;
+
+/// A reversible change to the world.
+pub trait Edit: Send + Sync + 'static {
+    fn undo(&self, world: &mut World);
+    fn redo(&self, world: &mut World);
+    /// Folds a later edit of the same target into this one.
+    fn merge(&mut self, next: &dyn Edit) -> bool;
+    fn label(&self) -> String;
+    fn as_any(&self) -> &dyn Any;
+    /// Approximate heap and inline footprint, for the memory budget.
+    fn size(&self) -> usize;
+}
+
+/// Undo/redo stacks shared by the inspector, the gizmo and tracked settings.
+#[derive(Resource, Default)]
+pub struct CommandHistory {
+    undo: VecDeque<Box<dyn Edit>>,
+    redo: Vec<Box<dyn Edit>>,
+    bytes: usize,
+    /// Set while a pointer button is held; edits made meanwhile collapse
+    /// into the step that opened the drag.
+    dragging: bool,
+    group_open: bool,
+}
+impl CommandHistory {
+    pub fn record(&mut self, edit: impl Edit) {
+        self.redo.clear();
+        if self.group_open {
+            if let Some(last) = self.undo.back_mut() {
+                if last.merge(&edit) {
+                    return;
+                }
+            }
+        }
+        self.bytes += edit.size();
+        self.undo.push_back(Box::new(edit));
+        self.group_open = self.dragging;
+        while self.bytes > HISTORY_BUDGET_BYTES {
+            let Some(oldest) = self.undo.pop_front() else {
+                break;
+            };
+            self.bytes -= oldest.size();
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(world: &mut World) {
+        let Some(edit) = world.resource_mut::<CommandHistory>().undo.pop_back() else {
+            return;
+        };
+        edit.undo(world);
+        let mut history = world.resource_mut::<CommandHistory>();
+        history.bytes -= edit.size();
+        history.group_open = false;
+        history.redo.push(edit);
+    }
+
+    pub fn redo(world: &mut World) {
+        let Some(edit) = world.resource_mut::<CommandHistory>().redo.pop() else {
+            return;
+        };
+        edit.redo(world);
+        let mut history = world.resource_mut::<CommandHistory>();
+        history.bytes += edit.size();
+        history.group_open = false;
+        history.undo.push_back(edit);
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.bytes = 0;
+        self.group_open = false;
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = String> + '_ {
+        self.undo.iter().map(|edit| edit.label())
+    }
+}
+
+/// Tracks whether a drag is in progress, in egui or in the viewport.
+pub fn history_grouping_system(
+    ui: Res<EguiState>,
+    input: Res<Input>,
+    mut history: ResMut<CommandHistory>,
+) {
+    let pointer_down = ui.renderer.context().input(|i| i.pointer.any_down());
+    let dragging = pointer_down || input.mouse_buttons.pressed(MouseButton::Left);
+    if history.dragging != dragging {
+        history.dragging = dragging;
+        history.group_open &= dragging;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HistoryShortcut {
+    Undo,
+    Redo,
+}
+
+/// Ctrl+Z undoes, Ctrl+Shift+Z and Ctrl+Y redo. Matched by physical key, with
+/// Shift held the logical key is an uppercase Z.
+fn history_shortcut(key: KeyCode, modifiers: ModifiersState) -> Option<HistoryShortcut> {
+    if !modifiers.control_key() {
+        return None;
+    }
+    match key {
+        KeyCode::KeyZ if modifiers.shift_key() => Some(HistoryShortcut::Redo),
+        KeyCode::KeyZ => Some(HistoryShortcut::Undo),
+        KeyCode::KeyY => Some(HistoryShortcut::Redo),
+        _ => None,
+    }
+}
+
+/// Applies Ctrl+Z / Ctrl+Y pressed this frame.
+pub fn history_shortcut_system(world: &mut World) {
+    let input = world.resource::<Input>();
+    let shortcut = [KeyCode::KeyZ, KeyCode::KeyY]
+        .into_iter()
+        .filter(|key| input.keys.just_pressed(*key))
+        .find_map(|key| history_shortcut(key, input.modifiers));
+    let typing = world
+        .resource::<EguiState>()
+        .renderer
+        .context()
+        .wants_keyboard_input();
+    if typing {
+        return;
+    }
+    match shortcut {
+        Some(HistoryShortcut::Undo) => CommandHistory::undo(world),
+        Some(HistoryShortcut::Redo) => CommandHistory::redo(world),
+        None => {}
+    }
+}
+
+pub fn history_panel(ctx: &egui::Context, world: &mut World) {
+    let (mut undo, mut redo) = (false, false);
+    let history = world.resource::<CommandHistory>();
+
+    egui::Window::new(tr("History"))
+        .id(egui::Id::new("History"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                undo = ui
+                    .add_enabled(history.can_undo(), egui::Button::new(tr("Undo (Ctrl+Z)")))
+                    .clicked();
+                redo = ui
+                    .add_enabled(history.can_redo(), egui::Button::new(tr("Redo (Ctrl+Y)")))
+                    .clicked();
+            });
+            ui.label(trf!(
+                "{} steps, {:.1} KiB",
+                history.undo.len(),
+                history.bytes as f32 / 1024.0
+            ));
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for label in history.labels().collect::<Vec<_>>().into_iter().rev() {
+                        ui.label(label);
+                    }
+                });
+        });
+
+    if undo {
+        CommandHistory::undo(world);
+    }
+    if redo {
+        CommandHistory::redo(world);
+    }
+}
+
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+// =============================== EDITS ===============================
+pub struct ComponentEdit<T: Component + Clone> {
+    pub entity: Entity,
+    pub before: T,
+    pub after: T,
+}
+impl<T: Component + Clone> Edit for ComponentEdit<T> {
+    fn undo(&self, world: &mut World) {
+        if let Some(mut component) = world.get_mut::<T>(self.entity) {
+            *component = self.before.clone();
+        }
+    }
+    fn redo(&self, world: &mut World) {
+        if let Some(mut component) = world.get_mut::<T>(self.entity) {
+            *component = self.after.clone();
+        }
+    }
+    fn merge(&mut self, next: &dyn Edit) -> bool {
+        match next.as_any().downcast_ref::<Self>() {
+            Some(next) if next.entity == self.entity => {
+                self.after = next.after.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+    fn label(&self) -> String {
+        format!("{} on {}", short_type_name::<T>(), self.entity)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn size(&self) -> usize {
+        std::mem::size_of::<Self>()
+    }
+}
+
+/// Change to one entry of the [`MaterialTable`].
+pub struct MaterialEdit {
+    pub index: usize,
+    pub before: MaterialDesc,
+    pub after: MaterialDesc,
+}
+impl Edit for MaterialEdit {
+    fn undo(&self, world: &mut World) {
+        if let Some(material) = world
+            .resource_mut::<MaterialTable>()
+            .materials
+            .get_mut(self.index)
+        {
+            *material = self.before;
+        }
+    }
+    fn redo(&self, world: &mut World) {
+        if let Some(material) = world
+            .resource_mut::<MaterialTable>()
+            .materials
+            .get_mut(self.index)
+        {
+            *material = self.after;
+        }
+    }
+    fn merge(&mut self, next: &dyn Edit) -> bool {
+        match next.as_any().downcast_ref::<Self>() {
+            Some(next) if next.index == self.index => {
+                self.after = next.after;
+                true
+            }
+            _ => false,
+        }
+    }
+    fn label(&self) -> String {
+        format!("Material #{}", self.index)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn size(&self) -> usize {
+        std::mem::size_of::<Self>()
+    }
+}
+
+pub struct ResourceEdit<R: Resource + Clone> {
+    pub before: R,
+    pub after: R,
+}
+impl<R: Resource + Clone> ResourceEdit<R> {
+    fn set(world: &mut World, value: &R) {
+        *world.resource_mut::<R>() = value.clone();
+        // Keep the tracker from recording the undo as a new edit
+        if let Some(mut snapshot) = world.get_resource_mut::<Snapshot<R>>() {
+            snapshot.0 = value.clone();
+        }
+    }
+}
+impl<R: Resource + Clone> Edit for ResourceEdit<R> {
+    fn undo(&self, world: &mut World) {
+        Self::set(world, &self.before);
+    }
+    fn redo(&self, world: &mut World) {
+        Self::set(world, &self.after);
+    }
+    fn merge(&mut self, next: &dyn Edit) -> bool {
+        match next.as_any().downcast_ref::<Self>() {
+            Some(next) => {
+                self.after = next.after.clone();
+                true
+            }
+            None => false,
+        }
+    }
+    fn label(&self) -> String {
+        short_type_name::<R>().to_string()
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn size(&self) -> usize {
+        std::mem::size_of::<Self>()
+    }
+}
+
+// =============================== TRACKING ===============================
+/// Last recorded value of a tracked resource.
+#[derive(Resource)]
+struct Snapshot<R: Resource + Clone>(R);
+
+/// Records every change to `R` as an undo step. Settings panels edit their
+/// resources directly, so changes are found by comparing against a snapshot.
+pub fn track_resource<R: Resource + Clone + PartialEq>(world: &mut World, schedule: &mut Schedule) {
+    let Some(current) = world.get_resource::<R>().cloned() else {
+        return;
+    };
+    world.insert_resource(Snapshot(current));
+    schedule.add_systems(track_resource_system::<R>.after(history_grouping_system));
+}
+
+fn track_resource_system<R: Resource + Clone + PartialEq>(
+    current: Res<R>,
+    mut snapshot: ResMut<Snapshot<R>>,
+    mut history: ResMut<CommandHistory>,
+) {
+    if *current == snapshot.0 {
+        return;
+    }
+    history.record(ResourceEdit {
+        before: std::mem::replace(&mut snapshot.0, current.clone()),
+        after: current.clone(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_shortcuts_ignore_shift_case() {
+        let ctrl = ModifiersState::CONTROL;
+        let ctrl_shift = ModifiersState::CONTROL | ModifiersState::SHIFT;
+        assert_eq!(
+            history_shortcut(KeyCode::KeyZ, ctrl),
+            Some(HistoryShortcut::Undo)
+        );
+        assert_eq!(
+            history_shortcut(KeyCode::KeyZ, ctrl_shift),
+            Some(HistoryShortcut::Redo)
+        );
+        assert_eq!(
+            history_shortcut(KeyCode::KeyY, ctrl),
+            Some(HistoryShortcut::Redo)
+        );
+        assert_eq!(history_shortcut(KeyCode::KeyZ, ModifiersState::SHIFT), None);
+        assert_eq!(history_shortcut(KeyCode::KeyX, ctrl), None);
+    }
+}
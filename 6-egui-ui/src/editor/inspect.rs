@@ -0,0 +1,231 @@
+use glam::{EulerRot, Quat, Vec3};
+
+use crate::{
+    i18n::tr,
+    lights::{DirectionalLight, PointLight, SpotLight},
+    pipeline::geometry_debug::GeometryDebug,
+    scene::{BlendMode, MaterialDesc, Name, Spin, Transform},
+};
+
+/// Editable view of a component, the inspector's stand-in for reflection.
+pub trait Inspect {
+    /// Draws the fields as egui widgets, returning whether any of them changed.
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool;
+}
+
+impl Inspect for Name {
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.text_edit_singleline(&mut self.0).changed()
+    }
+}
+
+impl Inspect for Transform {
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        egui::Grid::new("transform").num_columns(2).show(ui, |ui| {
+            ui.label(tr("translation"));
+            changed |= vec3(ui, &mut self.translation, 0.05);
+            ui.end_row();
+
+            let (y, x, z) = self.rotation.to_euler(EulerRot::YXZ);
+            let mut degrees = Vec3::new(x, y, z) * 180.0 / std::f32::consts::PI;
+            ui.label(tr("rotation"));
+            if vec3(ui, &mut degrees, 0.5) {
+                let radians = degrees * std::f32::consts::PI / 180.0;
+                self.rotation = Quat::from_euler(EulerRot::YXZ, radians.y, radians.x, radians.z);
+                changed = true;
+            }
+            ui.end_row();
+
+            ui.label(tr("scale"));
+            changed |= vec3(ui, &mut self.scale, 0.01);
+            ui.end_row();
+        });
+        changed
+    }
+}
+
+impl Inspect for Spin {
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        egui::Grid::new("spin").num_columns(2).show(ui, |ui| {
+            ui.label(tr("axis"));
+            let mut axis = self.axis;
+            if vec3(ui, &mut axis, 0.01) && axis.length_squared() > f32::EPSILON {
+                self.axis = axis.normalize();
+                changed = true;
+            }
+            ui.end_row();
+
+            ui.label(tr("speed"));
+            changed |= ui
+                .add(egui::DragValue::new(&mut self.speed).speed(0.01))
+                .changed();
+            ui.end_row();
+        });
+        changed
+    }
+}
+
+impl Inspect for DirectionalLight {
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        egui::Grid::new("directional_light")
+            .num_columns(2)
+            .show(ui, |ui| {
+                changed |= light_fields(ui, &mut self.color, &mut self.intensity);
+                ui.label(tr("cast shadows"));
+                changed |= ui.checkbox(&mut self.cast_shadows, "").changed();
+                ui.end_row();
+            });
+        changed
+    }
+}
+
+impl Inspect for PointLight {
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        egui::Grid::new("point_light")
+            .num_columns(2)
+            .show(ui, |ui| {
+                changed |= light_fields(ui, &mut self.color, &mut self.intensity);
+                ui.label(tr("range"));
+                changed |= ui
+                    .add(
+                        egui::DragValue::new(&mut self.range)
+                            .speed(0.1)
+                            .range(0.0..=f32::MAX),
+                    )
+                    .changed();
+                ui.end_row();
+            });
+        changed
+    }
+}
+
+impl Inspect for SpotLight {
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        egui::Grid::new("spot_light").num_columns(2).show(ui, |ui| {
+            changed |= light_fields(ui, &mut self.color, &mut self.intensity);
+            ui.label(tr("range"));
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut self.range)
+                        .speed(0.1)
+                        .range(0.0..=f32::MAX),
+                )
+                .changed();
+            ui.end_row();
+
+            ui.label(tr("inner angle"));
+            changed |= ui.drag_angle(&mut self.inner_angle).changed();
+            ui.end_row();
+            ui.label(tr("outer angle"));
+            changed |= ui.drag_angle(&mut self.outer_angle).changed();
+            ui.end_row();
+            self.outer_angle = self.outer_angle.clamp(0.0, 89f32.to_radians());
+            self.inner_angle = self.inner_angle.clamp(0.0, self.outer_angle);
+
+            ui.label(tr("cast shadows"));
+            changed |= ui.checkbox(&mut self.cast_shadows, "").changed();
+            ui.end_row();
+        });
+        changed
+    }
+}
+
+impl Inspect for MaterialDesc {
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        egui::Grid::new("material").num_columns(2).show(ui, |ui| {
+            ui.label(tr("base color"));
+            let mut color = self.base_color.to_array();
+            if ui.color_edit_button_rgba_unmultiplied(&mut color).changed() {
+                self.base_color = color.into();
+                changed = true;
+            }
+            ui.end_row();
+
+            ui.label(tr("blend"));
+            egui::ComboBox::from_id_salt("material_blend")
+                .selected_text(format!("{:?}", self.blend))
+                .show_ui(ui, |ui| {
+                    for blend in [BlendMode::Opaque, BlendMode::Transparent] {
+                        changed |= ui
+                            .selectable_value(&mut self.blend, blend, format!("{:?}", blend))
+                            .changed();
+                    }
+                });
+            ui.end_row();
+
+            ui.label(tr("roughness"));
+            changed |= ui
+                .add(egui::Slider::new(&mut self.roughness, 0.0..=1.0))
+                .changed();
+            ui.end_row();
+
+            ui.label(tr("reflectivity"));
+            changed |= ui
+                .add(egui::Slider::new(&mut self.reflectivity, 0.0..=1.0))
+                .changed();
+            ui.end_row();
+        });
+        changed
+    }
+}
+
+impl Inspect for GeometryDebug {
+    fn inspect(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        egui::Grid::new("geometry_debug")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for (label, value) in [
+                    ("bounds", &mut self.bounds),
+                    ("normals", &mut self.normals),
+                    ("tangents", &mut self.tangents),
+                ] {
+                    ui.label(tr(label));
+                    changed |= ui.checkbox(value, "").changed();
+                    ui.end_row();
+                }
+            });
+        changed
+    }
+}
+
+fn vec3(ui: &mut egui::Ui, value: &mut Vec3, speed: f32) -> bool {
+    ui.horizontal(|ui| {
+        let mut changed = false;
+        for component in [&mut value.x, &mut value.y, &mut value.z] {
+            changed |= ui
+                .add(egui::DragValue::new(component).speed(speed))
+                .changed();
+        }
+        changed
+    })
+    .inner
+}
+
+fn light_fields(ui: &mut egui::Ui, color: &mut Vec3, intensity: &mut f32) -> bool {
+    let mut changed = false;
+    ui.label(tr("color"));
+    let mut rgb = color.to_array();
+    if ui.color_edit_button_rgb(&mut rgb).changed() {
+        *color = rgb.into();
+        changed = true;
+    }
+    ui.end_row();
+
+    ui.label(tr("intensity"));
+    changed |= ui
+        .add(
+            egui::DragValue::new(intensity)
+                .speed(0.01)
+                .range(0.0..=f32::MAX),
+        )
+        .changed();
+    ui.end_row();
+    changed
+}
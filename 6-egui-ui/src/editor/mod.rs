@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use bevy_ecs::{
+    change_detection::DetectChangesMut,
+    component::Component,
+    entity::Entity,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Query, Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Vec4, Vec4Swizzles};
+
+use crate::{
+    i18n::{tr, trf},
+    input::mouse_input_system,
+    lights::{DirectionalLight, PointLight, SpotLight},
+    pipeline::{
+        ao::AoSettings, cascades::CascadeSettings, debug_draw::DebugDraw, depth::DepthPreview,
+        filtering::FilteringDemoSettings, geometry_debug::GeometryDebug, god_rays::GodRaySettings,
+        grading::ColorGradingSettings, marching_cubes::MarchingCubesSettings,
+        mesh::MeshShaderSettings, particles::ParticleSettings, post::PostSettings,
+        present::PresentSettings, render::render_system, ssr::SsrSettings, ui::UiPanels,
+        visibility::VisibilitySettings, volume::VolumeSettings,
+    },
+    sampler::SamplerSettings,
+    scene::{
+        spin_system, transform_propagation_system, Aabb, GlobalTransform, MaterialId,
+        MaterialTable, MeshId, Name, Parent, Renderable, Spin, Transform,
+    },
+};
+
+mod gizmo;
+mod history;
+mod inspect;
+
+use gizmo::{gizmo_draw_system, gizmo_interaction_system, gizmo_panel, Gizmo};
+use history::{
+    history_grouping_system, history_panel, history_shortcut_system, track_resource,
+    CommandHistory, ComponentEdit, MaterialEdit,
+};
+pub use inspect::Inspect;
+
+const SELECTION_COLOR: Vec4 = Vec4::new(1.0, 0.6, 0.1, 1.0);
+
+pub fn setup_editor(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    world.insert_resource(Selection::default());
+    world.insert_resource(Gizmo::default());
+    world.insert_resource(CommandHistory::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(entity_inspector_panel)
+        .add_panel(gizmo_panel)
+        .add_panel(history_panel);
+
+    schedule.add_systems((
+        (history_grouping_system, history_shortcut_system)
+            .chain()
+            .before(gizmo_interaction_system),
+        // Keys that fly the camera don't switch the gizmo mode
+        gizmo_interaction_system
+            .after(mouse_input_system)
+            .after(spin_system)
+            .before(transform_propagation_system),
+        (selection_highlight_system, gizmo_draw_system)
+            .after(transform_propagation_system)
+            .before(render_system),
+    ));
+    track_resource::<VolumeSettings>(world, schedule);
+    track_resource::<CascadeSettings>(world, schedule);
+    track_resource::<DepthPreview>(world, schedule);
+    track_resource::<ParticleSettings>(world, schedule);
+    track_resource::<MarchingCubesSettings>(world, schedule);
+    track_resource::<VisibilitySettings>(world, schedule);
+    track_resource::<MeshShaderSettings>(world, schedule);
+    track_resource::<PresentSettings>(world, schedule);
+    track_resource::<SamplerSettings>(world, schedule);
+    track_resource::<FilteringDemoSettings>(world, schedule);
+    track_resource::<ColorGradingSettings>(world, schedule);
+    track_resource::<PostSettings>(world, schedule);
+    track_resource::<SsrSettings>(world, schedule);
+    track_resource::<AoSettings>(world, schedule);
+    track_resource::<GodRaySettings>(world, schedule);
+
+    Ok(())
+}
+
+/// Drops editor state pointing at despawned entities. Clears the undo history
+/// too, its edits would land on entities and materials that are gone.
+pub fn forget_entities(world: &mut World, despawned: &[Entity]) {
+    let mut selection = world.resource_mut::<Selection>();
+    if selection
+        .entity
+        .is_some_and(|entity| despawned.contains(&entity))
+    {
+        selection.entity = None;
+    }
+    let mut gizmo = world.resource_mut::<Gizmo>();
+    if gizmo
+        .drag
+        .is_some_and(|drag| despawned.contains(&drag.entity))
+    {
+        gizmo.drag = None;
+    }
+    world.resource_mut::<CommandHistory>().clear();
+}
+
+#[derive(Resource, Default)]
+pub struct Selection {
+    pub entity: Option<Entity>,
+}
+
+/// Outlines the selected entity: its world bounds, or a small sphere for
+/// entities without any.
+pub fn selection_highlight_system(
+    selection: Res<Selection>,
+    query: Query<(&GlobalTransform, Option<&Aabb>)>,
+    mut debug_draw: ResMut<DebugDraw>,
+) {
+    let Some((global, aabb)) = selection.entity.and_then(|entity| query.get(entity).ok()) else {
+        return;
+    };
+    match aabb {
+        Some(aabb) => {
+            let bounds = aabb.transformed(&global.0);
+            debug_draw.wire_box(bounds.min, bounds.max, SELECTION_COLOR);
+        }
+        None => debug_draw.wire_sphere(global.0.w_axis.xyz(), 0.3, SELECTION_COLOR),
+    }
+}
+
+// =============================== PANEL ===============================
+type InspectFn = fn(&mut World, Entity, &str, &mut egui::Ui);
+
+/// Components with an editor, in display order.
+const INSPECTORS: &[(&str, InspectFn)] = &[
+    ("Name", inspect_component::<Name>),
+    ("Transform", inspect_component::<Transform>),
+    ("Spin", inspect_component::<Spin>),
+    ("Directional light", inspect_component::<DirectionalLight>),
+    ("Point light", inspect_component::<PointLight>),
+    ("Spot light", inspect_component::<SpotLight>),
+    ("Renderable", inspect_renderable),
+    ("Debug view", inspect_component::<GeometryDebug>),
+];
+
+fn entity_inspector_panel(ctx: &egui::Context, world: &mut World) {
+    let hierarchy = Hierarchy::build(world);
+    let mut selected = world
+        .resource::<Selection>()
+        .entity
+        .filter(|&entity| world.get_entity(entity).is_ok());
+
+    egui::Window::new(tr("Entities"))
+        .id(egui::Id::new("Entities"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .id_salt("entity_hierarchy")
+                .max_height(240.0)
+                .show(ui, |ui| {
+                    for &root in &hierarchy.roots {
+                        hierarchy.show(ui, root, &mut selected);
+                    }
+                });
+
+            ui.separator();
+            let Some(entity) = selected else {
+                ui.label(tr("Select an entity to inspect it"));
+                return;
+            };
+            ui.heading(hierarchy.label(entity));
+            egui::ScrollArea::vertical()
+                .id_salt("entity_components")
+                .show(ui, |ui| {
+                    for (name, inspect) in INSPECTORS {
+                        ui.push_id(name, |ui| inspect(world, entity, name, ui));
+                    }
+                });
+        });
+
+    let mut selection = world.resource_mut::<Selection>();
+    if selection.entity != selected {
+        selection.entity = selected;
+    }
+}
+
+/// Edits a component in place and records the change for undo. Change
+/// detection only fires when a field was actually edited, so systems filtering
+/// on `Changed<T>` aren't woken every frame.
+fn inspect_component<T: Component + Inspect + Clone>(
+    world: &mut World,
+    entity: Entity,
+    name: &str,
+    ui: &mut egui::Ui,
+) {
+    let Some(mut component) = world.get_mut::<T>(entity) else {
+        return;
+    };
+    let before = component.clone();
+    let edited = egui::CollapsingHeader::new(tr(name))
+        .id_salt(name)
+        .default_open(true)
+        .show(ui, |ui| component.bypass_change_detection().inspect(ui))
+        .body_returned
+        .unwrap_or(false);
+    if !edited {
+        return;
+    }
+    component.set_changed();
+    let after = component.clone();
+    world
+        .resource_mut::<CommandHistory>()
+        .record(ComponentEdit {
+            entity,
+            before,
+            after,
+        });
+}
+
+/// Mesh and material choice, plus the shared material itself.
+fn inspect_renderable(world: &mut World, entity: Entity, name: &str, ui: &mut egui::Ui) {
+    let (renderable_edit, material_edit) =
+        world.resource_scope::<MaterialTable, _>(|world, mut table| {
+            let Some(mut renderable) = world.get_mut::<Renderable>(entity) else {
+                return (None, None);
+            };
+            let (mut renderable_edit, mut material_edit) = (None, None);
+            egui::CollapsingHeader::new(tr(name))
+                .id_salt(name)
+                .default_open(true)
+                .show(ui, |ui| {
+                    let mut edited = *renderable;
+                    egui::ComboBox::from_label(tr("mesh"))
+                        .selected_text(mesh_label(edited.mesh))
+                        .show_ui(ui, |ui| {
+                            for mesh in [MeshId::CUBE, MeshId::QUAD] {
+                                ui.selectable_value(&mut edited.mesh, mesh, mesh_label(mesh));
+                            }
+                        });
+                    egui::ComboBox::from_label(tr("material"))
+                        .selected_text(format!("#{}", edited.material.0))
+                        .show_ui(ui, |ui| {
+                            for id in 0..table.materials.len() as u32 {
+                                let material = MaterialId(id);
+                                ui.selectable_value(
+                                    &mut edited.material,
+                                    material,
+                                    format!("#{}", id),
+                                );
+                            }
+                        });
+                    if (edited.mesh, edited.material) != (renderable.mesh, renderable.material) {
+                        renderable_edit = Some(ComponentEdit {
+                            entity,
+                            before: *renderable,
+                            after: edited,
+                        });
+                        *renderable = edited;
+                    }
+
+                    let index = edited.material.0 as usize;
+                    if let Some(material) = table.bypass_change_detection().materials.get_mut(index)
+                    {
+                        ui.label(tr("Shared with every entity using this material"));
+                        let before = *material;
+                        if material.inspect(ui) {
+                            material_edit = Some(MaterialEdit {
+                                index,
+                                before,
+                                after: *material,
+                            });
+                            table.set_changed();
+                        }
+                    }
+                });
+            (renderable_edit, material_edit)
+        });
+
+    let mut history = world.resource_mut::<CommandHistory>();
+    if let Some(edit) = renderable_edit {
+        history.record(edit);
+    }
+    if let Some(edit) = material_edit {
+        history.record(edit);
+    }
+}
+
+fn mesh_label(mesh: MeshId) -> &'static str {
+    match mesh {
+        MeshId::CUBE => tr("Cube"),
+        MeshId::QUAD => tr("Quad"),
+        _ => tr("Unknown"),
+    }
+}
+
+// =============================== HIERARCHY ===============================
+/// Parent/child snapshot of the world, rebuilt every time the panel is drawn.
+struct Hierarchy {
+    roots: Vec<Entity>,
+    children: HashMap<Entity, Vec<Entity>>,
+    names: HashMap<Entity, String>,
+}
+impl Hierarchy {
+    fn build(world: &mut World) -> Self {
+        let mut roots = Vec::new();
+        let mut children: HashMap<Entity, Vec<Entity>> = HashMap::new();
+        let mut names = HashMap::new();
+        let mut query = world.query::<(Entity, Option<&Parent>, Option<&Name>)>();
+        for (entity, parent, name) in query.iter(world) {
+            match parent {
+                Some(parent) => children.entry(parent.0).or_default().push(entity),
+                None => roots.push(entity),
+            }
+            if let Some(name) = name {
+                names.insert(entity, name.0.clone());
+            }
+        }
+        roots.sort();
+        children.values_mut().for_each(|children| children.sort());
+
+        Self {
+            roots,
+            children,
+            names,
+        }
+    }
+
+    fn label(&self, entity: Entity) -> String {
+        self.names
+            .get(&entity)
+            .cloned()
+            .unwrap_or_else(|| trf!("Entity {}", entity))
+    }
+
+    fn show(&self, ui: &mut egui::Ui, entity: Entity, selected: &mut Option<Entity>) {
+        let label = self.label(entity);
+        let is_selected = *selected == Some(entity);
+        let Some(children) = self.children.get(&entity) else {
+            if ui.selectable_label(is_selected, label).clicked() {
+                *selected = Some(entity);
+            }
+            return;
+        };
+
+        let id = ui.make_persistent_id(entity);
+        egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+            .show_header(ui, |ui| {
+                if ui.selectable_label(is_selected, label).clicked() {
+                    *selected = Some(entity);
+                }
+            })
+            .body(|ui| {
+                for &child in children {
+                    self.show(ui, child, selected);
+                }
+            });
+    }
+}
@@ -0,0 +1,319 @@
+//! Opens shaders and other source files in the user's editor. The command
+//! comes from `editor.json`, then `$VISUAL` and `$EDITOR`, and falls back to
+//! the platform's default opener.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    i18n::{tr, trf},
+    pipeline::ui::UiPanels,
+    shader::ShaderWatcher,
+};
+
+/// Read at startup and written from the editor panel, in the working directory.
+pub const EDITOR_CONFIG: &str = "editor.json";
+
+pub fn setup_external_editor(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    let config = match EditorConfig::load(Path::new(EDITOR_CONFIG)) {
+        Ok(Some(config)) => config,
+        Ok(None) => EditorConfig::default(),
+        Err(e) => {
+            warn!("Ignoring {}: {:?}", EDITOR_CONFIG, e);
+            EditorConfig::default()
+        }
+    };
+    world.insert_resource(config);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(external_editor_panel);
+
+    Ok(())
+}
+
+/// Opens `path` in the configured editor, at a 1-based line and column when
+/// the editor takes one. Failures end up in [`EditorConfig::last_error`] for
+/// the panels to show.
+pub fn open_in_editor(world: &mut World, path: &Path, position: Option<(u32, u32)>) {
+    let mut config = world.resource_mut::<EditorConfig>();
+    config.last_error = match config.open(path, position) {
+        Ok(()) => None,
+        Err(e) => {
+            warn!("Failed to open {} in an editor: {:?}", path.display(), e);
+            Some(format!("{:#}", e))
+        }
+    };
+}
+
+// =============================== CONFIG ===============================
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorConfig {
+    /// Overrides the environment when not empty. `{file}`, `{line}` and
+    /// `{column}` are replaced, without them the position is passed the way
+    /// the editor is known to take it.
+    pub command: String,
+    #[serde(skip)]
+    pub last_error: Option<String>,
+    #[serde(skip)]
+    pub last_save: Option<Result<(), String>>,
+}
+impl EditorConfig {
+    /// `None` when there is no config file yet.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(config))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        info!("Saved editor config to {}", path.display());
+        Ok(())
+    }
+
+    /// The command that will be used and where it came from.
+    pub fn resolved(&self) -> (String, &'static str) {
+        if !self.command.trim().is_empty() {
+            return (self.command.clone(), EDITOR_CONFIG);
+        }
+        for variable in ["VISUAL", "EDITOR"] {
+            if let Some(command) = std::env::var(variable)
+                .ok()
+                .filter(|c| !c.trim().is_empty())
+            {
+                return (command, variable);
+            }
+        }
+        (DEFAULT_OPENER.to_string(), "system default")
+    }
+
+    fn open(&self, path: &Path, position: Option<(u32, u32)>) -> Result<()> {
+        if !path.exists() {
+            anyhow::bail!("{} does not exist", path.display());
+        }
+        let (command, _) = self.resolved();
+        let args = editor_args(&command, path, position);
+        let (program, args) = args.split_first().context("The editor command is empty")?;
+
+        let mut child = platform_command(program)
+            .args(args)
+            .spawn()
+            .with_context(|| format!("Failed to start '{}'", command))?;
+        // Reaped on a thread so a long running editor doesn't block the frame
+        std::thread::spawn(move || match child.wait() {
+            Ok(status) if !status.success() => warn!("Editor exited with {}", status),
+            Ok(_) => {}
+            Err(e) => warn!("Failed to wait for the editor: {}", e),
+        });
+        Ok(())
+    }
+}
+
+// =============================== PLATFORM ===============================
+/// Opens files with whatever the desktop associates them with, which can't
+/// jump to a line.
+#[cfg(target_os = "windows")]
+const DEFAULT_OPENER: &str = "start \"\"";
+#[cfg(target_os = "macos")]
+const DEFAULT_OPENER: &str = "open -t";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const DEFAULT_OPENER: &str = "xdg-open";
+
+/// Editors on Windows are often `.cmd` shims, which only the shell runs.
+#[cfg(target_os = "windows")]
+fn platform_command(program: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(program);
+    command
+}
+#[cfg(not(target_os = "windows"))]
+fn platform_command(program: &str) -> Command {
+    Command::new(program)
+}
+
+/// Splits `command` like a shell would, minus everything but double quotes,
+/// and appends the file with the position in the editor's syntax.
+pub fn editor_args(command: &str, path: &Path, position: Option<(u32, u32)>) -> Vec<String> {
+    let mut args = split_command(command);
+    let file = path.display().to_string();
+    let (line, column) = position.unwrap_or((1, 1));
+
+    if command.contains("{file}") {
+        for arg in &mut args {
+            *arg = arg
+                .replace("{file}", &file)
+                .replace("{line}", &line.to_string())
+                .replace("{column}", &column.to_string());
+        }
+        return args;
+    }
+
+    let stem = args
+        .first()
+        .and_then(|program| Path::new(program).file_stem())
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_string();
+    match (stem.as_str(), position) {
+        ("code" | "code-insiders" | "codium" | "cursor", Some(_)) => {
+            args.push("--goto".to_string());
+            args.push(format!("{}:{}:{}", file, line, column));
+        }
+        ("subl" | "zed" | "hx", Some(_)) => args.push(format!("{}:{}:{}", file, line, column)),
+        (
+            "vi" | "vim" | "nvim" | "gvim" | "nano" | "emacs" | "emacsclient" | "micro" | "kak",
+            Some(_),
+        ) => {
+            args.push(format!("+{}", line));
+            args.push(file);
+        }
+        // Anything else might take `+12` for a file name
+        _ => args.push(file),
+    }
+    args
+}
+
+fn split_command(command: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = None::<String>;
+    let mut quoted = false;
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => args.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    args
+}
+
+// =============================== UI ===============================
+fn external_editor_panel(ctx: &egui::Context, world: &mut World) {
+    let mut config = world.resource::<EditorConfig>().clone();
+    let mut files: Vec<PathBuf> = world
+        .get_resource::<ShaderWatcher>()
+        .map(|watcher| watcher.files().map(Path::to_path_buf).collect())
+        .unwrap_or_default();
+    files.sort();
+    let mut open = None;
+
+    egui::Window::new(tr("External editor"))
+        .id(egui::Id::new("External editor"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(tr("Command"));
+                ui.text_edit_singleline(&mut config.command)
+                    .on_hover_text(tr(
+                        "Empty uses $VISUAL or $EDITOR. {file}, {line} and {column} are replaced",
+                    ));
+            });
+            let (command, source) = config.resolved();
+            ui.label(trf!("Using {} ({})", command, tr(source)));
+            if let Some(error) = &config.last_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button(trf!("Save to {}", EDITOR_CONFIG)).clicked() {
+                    config.last_save = Some(
+                        config
+                            .save(Path::new(EDITOR_CONFIG))
+                            .map_err(|e| format!("{:#}", e)),
+                    );
+                }
+                match &config.last_save {
+                    Some(Ok(())) => {
+                        ui.label(tr("Saved"));
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(egui::Color32::RED, trf!("Save failed: {}", e));
+                    }
+                    None => {}
+                }
+            });
+
+            ui.separator();
+            ui.label(tr("Watched files"));
+            for file in &files {
+                let name = file.file_name().unwrap_or(file.as_os_str());
+                if ui
+                    .link(name.to_string_lossy())
+                    .on_hover_text(file.display().to_string())
+                    .clicked()
+                {
+                    open = Some(file.clone());
+                }
+            }
+        });
+
+    let mut current = world.resource_mut::<EditorConfig>();
+    if *current != config {
+        *current = config;
+    }
+    if let Some(file) = open {
+        open_in_editor(world, &file, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editor_commands_get_the_position_in_their_syntax() {
+        let path = Path::new("shaders/mesh.wgsl");
+        let position = Some((12, 5));
+
+        assert_eq!(
+            editor_args("code --wait", path, position),
+            ["code", "--wait", "--goto", "shaders/mesh.wgsl:12:5"]
+        );
+        assert_eq!(
+            editor_args("/usr/bin/nvim", path, position),
+            ["/usr/bin/nvim", "+12", "shaders/mesh.wgsl"]
+        );
+        assert_eq!(
+            editor_args(
+                "\"C:/Program Files/Editor/edit.exe\" -l {line} {file}",
+                path,
+                position
+            ),
+            [
+                "C:/Program Files/Editor/edit.exe",
+                "-l",
+                "12",
+                "shaders/mesh.wgsl"
+            ]
+        );
+        // Unknown editors and missing positions only get the file
+        assert_eq!(
+            editor_args("xdg-open", path, position),
+            ["xdg-open", "shaders/mesh.wgsl"]
+        );
+        assert_eq!(editor_args("vim", path, None), ["vim", "shaders/mesh.wgsl"]);
+        assert_eq!(
+            editor_args("cmd /C start \"\"", path, None),
+            ["cmd", "/C", "start", "", "shaders/mesh.wgsl"]
+        );
+    }
+}
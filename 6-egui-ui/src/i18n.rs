@@ -0,0 +1,228 @@
+//! UI string translation. The English text is the key, so untranslated strings
+//! fall back to it and panels stay readable while writing them. Catalogs are
+//! plain `English = Translation` lines under `locales/`, compiled into the
+//! binary.
+//!
+//! The language is global rather than a resource because panels translate
+//! inside egui closures that already borrow the world.
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
+
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+use tracing::info;
+
+use crate::pipeline::ui::UiPanels;
+
+pub fn setup_i18n(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    let language = std::env::var(LANGUAGE_ENV)
+        .ok()
+        .and_then(|code| Language::from_code(&code))
+        .unwrap_or_default();
+    set_language(language);
+    world.insert_resource(Localization { language });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(language_panel);
+
+    Ok(())
+}
+
+// =============================== LANGUAGES ===============================
+/// Picks the startup language by code, e.g. `PLAYGROUND_LANG=de`.
+pub const LANGUAGE_ENV: &str = "PLAYGROUND_LANG";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+}
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::German];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::German => "de",
+        }
+    }
+
+    /// Name in the language itself, so it can be found from any other one.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::English => "English",
+            Self::German => "Deutsch",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|language| language.code() == code)
+    }
+
+    /// English has no catalog, the keys already are English.
+    fn source(self) -> Option<&'static str> {
+        match self {
+            Self::English => None,
+            Self::German => Some(include_str!("locales/de.txt")),
+        }
+    }
+
+    fn catalog(self) -> Option<&'static Catalog> {
+        static CATALOGS: [OnceLock<Catalog>; Language::ALL.len()] =
+            [const { OnceLock::new() }; Language::ALL.len()];
+        let source = self.source()?;
+        Some(CATALOGS[self as usize].get_or_init(|| Catalog::parse(source)))
+    }
+}
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn language() -> Language {
+    Language::ALL[CURRENT.load(Ordering::Relaxed)]
+}
+
+pub fn set_language(language: Language) {
+    CURRENT.store(language as usize, Ordering::Relaxed);
+}
+
+// =============================== CATALOG ===============================
+struct Catalog {
+    strings: HashMap<&'static str, &'static str>,
+}
+impl Catalog {
+    /// `English = Translation` per line, `#` starts a comment line. Lines
+    /// without a separator are skipped, the test below catches them.
+    fn parse(source: &'static str) -> Self {
+        let strings = source
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once(" = "))
+            .collect();
+        Self { strings }
+    }
+}
+
+/// The current translation of `key`, or `key` itself without one.
+pub fn tr(key: &str) -> &str {
+    language()
+        .catalog()
+        .and_then(|catalog| catalog.strings.get(key).copied())
+        .unwrap_or(key)
+}
+
+/// Fills a translated template. `{}` takes the next argument and `{1}` a
+/// given one, so translations can reorder them, and `{:.2}` sets the
+/// precision. Anything else needs to be formatted by the caller.
+pub fn format_template(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut next = 0;
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        let (index, precision) = match rest[start + 1..end].split_once(':') {
+            Some((index, spec)) => (index, spec.strip_prefix('.').and_then(|p| p.parse().ok())),
+            None => (&rest[start + 1..end], None),
+        };
+        let index = index.parse().unwrap_or_else(|_| {
+            next += 1;
+            next - 1
+        });
+        match (args.get(index), precision) {
+            (Some(arg), Some(precision)) => write!(out, "{:.*}", precision, arg).unwrap(),
+            (Some(arg), None) => write!(out, "{}", arg).unwrap(),
+            (None, _) => out.push_str(&rest[start..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `format!` for translated text, see [`format_template`] for the syntax.
+macro_rules! trf {
+    ($key:literal $(, $arg:expr)* $(,)?) => {
+        $crate::i18n::format_template(
+            $crate::i18n::tr($key),
+            &[$(&$arg as &dyn std::fmt::Display),*],
+        )
+    };
+}
+pub(crate) use trf;
+
+// =============================== PANEL ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct Localization {
+    pub language: Language,
+}
+
+fn language_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<Localization>().clone();
+
+    egui::Window::new(tr("Language"))
+        .id(egui::Id::new("Language"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            for language in Language::ALL {
+                ui.radio_value(&mut settings.language, language, language.name());
+            }
+        });
+
+    let mut current = world.resource_mut::<Localization>();
+    if *current != settings {
+        info!("Switching UI language to {}", settings.language.name());
+        set_language(settings.language);
+        *current = settings;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalogs_are_well_formed() {
+        for language in Language::ALL {
+            let (Some(source), Some(catalog)) = (language.source(), language.catalog()) else {
+                continue;
+            };
+            let entries = source
+                .lines()
+                .filter(|line| !line.trim().is_empty() && !line.starts_with('#'));
+            for line in entries {
+                let (key, value) = line
+                    .split_once(" = ")
+                    .unwrap_or_else(|| panic!("{:?}: no ' = ' in {:?}", language, line));
+                assert_eq!(
+                    catalog.strings.get(key),
+                    Some(&value),
+                    "duplicate key {:?}",
+                    key
+                );
+                // Placeholders have to survive translation
+                let placeholders = |s: &str| s.matches('{').count();
+                assert_eq!(placeholders(key), placeholders(value), "{:?}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn templates_fill_in_order_by_index_and_with_precision() {
+        let args: [&dyn Display; 2] = [&1.2345f32, &"b"];
+        assert_eq!(format_template("{} and {}", &args), "1.2345 and b");
+        assert_eq!(format_template("{1} before {0:.1}", &args), "b before 1.2");
+        assert_eq!(format_template("{:.2} {} {}", &args), "1.23 b {}");
+    }
+}
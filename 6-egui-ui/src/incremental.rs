@@ -0,0 +1,296 @@
+//! Long CPU work spread over frames. A task does its work in small steps and
+//! the scheduler runs steps each frame until the frame's budget is spent, so
+//! packing an atlas or generating a LUT takes a few frames longer instead of
+//! stalling one. Work that doesn't need the world while it runs is better off
+//! on the job pool, see [`crate::jobs`].
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::Resource,
+    world::{Mut, World},
+};
+use tracing::{info, info_span};
+
+use crate::{
+    i18n::{tr, trf},
+    pipeline::{render::render_system, ui::UiPanels},
+};
+
+/// Recently finished tasks kept for the panel.
+const FINISHED_HISTORY: usize = 8;
+
+pub fn setup_incremental(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    world.insert_resource(IncrementalWork::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(incremental_panel);
+
+    schedule.add_systems(incremental_system.before(render_system));
+
+    Ok(())
+}
+
+pub enum Step {
+    /// More to do, with the fraction done so far.
+    Pending(f32),
+    Done,
+}
+
+pub trait IncrementalTask: Send + Sync {
+    /// Does a slice of the work, small enough to be a fraction of the budget.
+    fn step(&mut self) -> Step;
+
+    /// Hands the result over after [`Step::Done`].
+    fn finish(self: Box<Self>, _world: &mut World) {}
+}
+
+struct QueuedTask {
+    name: String,
+    task: Box<dyn IncrementalTask>,
+    progress: f32,
+    steps: u64,
+    frames: u32,
+    spent: Duration,
+}
+
+pub struct FinishedTask {
+    pub name: String,
+    pub steps: u64,
+    pub frames: u32,
+    pub spent: Duration,
+}
+
+#[derive(Resource)]
+pub struct IncrementalWork {
+    /// Milliseconds of task steps per frame.
+    pub budget_ms: f32,
+    tasks: VecDeque<QueuedTask>,
+    finished: VecDeque<FinishedTask>,
+    /// Time the last frame spent on steps, can overshoot the budget by a step.
+    last_frame: Duration,
+}
+impl Default for IncrementalWork {
+    fn default() -> Self {
+        Self {
+            budget_ms: 2.0,
+            tasks: VecDeque::new(),
+            finished: VecDeque::new(),
+            last_frame: Duration::ZERO,
+        }
+    }
+}
+impl IncrementalWork {
+    /// Queues `task` behind the ones already running.
+    pub fn spawn(&mut self, name: impl Into<String>, task: impl IncrementalTask + 'static) {
+        self.tasks.push_back(QueuedTask {
+            name: name.into(),
+            task: Box::new(task),
+            progress: 0.0,
+            steps: 0,
+            frames: 0,
+            spent: Duration::ZERO,
+        });
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Steps tasks, oldest first, until the budget is spent, and returns the
+    /// ones that are done. At least one step runs every frame, so a budget
+    /// smaller than a step still makes progress.
+    fn run(&mut self) -> Vec<Box<dyn IncrementalTask>> {
+        let budget = Duration::from_secs_f32(self.budget_ms.max(0.0) / 1000.0);
+        let start = Instant::now();
+        let mut done = Vec::new();
+        let mut stepped = false;
+        while let Some(queued) = self.tasks.front_mut() {
+            if stepped && start.elapsed() >= budget {
+                break;
+            }
+            if queued.steps == 0 || !stepped {
+                queued.frames += 1;
+            }
+            let step_start = Instant::now();
+            let step = queued.task.step();
+            queued.spent += step_start.elapsed();
+            queued.steps += 1;
+            stepped = true;
+            match step {
+                Step::Pending(progress) => queued.progress = progress.clamp(0.0, 1.0),
+                Step::Done => {
+                    let queued = self.tasks.pop_front().expect("stepped the front task");
+                    info!(
+                        "{} finished in {} steps over {} frames, {:.1} ms",
+                        queued.name,
+                        queued.steps,
+                        queued.frames,
+                        queued.spent.as_secs_f64() * 1000.0
+                    );
+                    if self.finished.len() == FINISHED_HISTORY {
+                        self.finished.pop_front();
+                    }
+                    self.finished.push_back(FinishedTask {
+                        name: queued.name,
+                        steps: queued.steps,
+                        frames: queued.frames,
+                        spent: queued.spent,
+                    });
+                    done.push(queued.task);
+                }
+            }
+        }
+        self.last_frame = start.elapsed();
+        done
+    }
+}
+
+pub fn incremental_system(world: &mut World) {
+    world.resource_scope(|world, mut work: Mut<IncrementalWork>| {
+        if work.is_idle() {
+            return;
+        }
+        let _span = info_span!("incremental_work").entered();
+        for task in work.run() {
+            task.finish(world);
+        }
+    });
+}
+
+fn incremental_panel(ctx: &egui::Context, world: &mut World) {
+    let mut work = world.resource_mut::<IncrementalWork>();
+    let mut budget_ms = work.budget_ms;
+
+    egui::Window::new(tr("Background work"))
+        .id(egui::Id::new("Background work"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(tr("Budget per frame"));
+                ui.add(
+                    egui::DragValue::new(&mut budget_ms)
+                        .range(0.1..=33.0)
+                        .speed(0.1)
+                        .suffix(" ms"),
+                );
+            });
+            ui.label(trf!(
+                "Last frame: {:.2} ms",
+                work.last_frame.as_secs_f64() * 1000.0
+            ));
+
+            ui.separator();
+            if work.tasks.is_empty() {
+                ui.label(tr("Idle"));
+            }
+            for task in &work.tasks {
+                ui.add(egui::ProgressBar::new(task.progress).text(trf!(
+                    "{}: {:.0}%, {} frames",
+                    task.name,
+                    task.progress * 100.0,
+                    task.frames
+                )));
+            }
+
+            if !work.finished.is_empty() {
+                ui.separator();
+                ui.label(tr("Finished"));
+                for task in work.finished.iter().rev() {
+                    ui.label(trf!(
+                        "{}: {} steps over {} frames, {:.1} ms",
+                        task.name,
+                        task.steps,
+                        task.frames,
+                        task.spent.as_secs_f64() * 1000.0
+                    ));
+                }
+            }
+        });
+
+    if budget_ms != work.budget_ms {
+        work.budget_ms = budget_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Total(u32);
+
+    struct Sum {
+        next: u32,
+        end: u32,
+        total: u32,
+    }
+    impl IncrementalTask for Sum {
+        fn step(&mut self) -> Step {
+            self.total += self.next;
+            self.next += 1;
+            if self.next == self.end {
+                Step::Done
+            } else {
+                Step::Pending(self.next as f32 / self.end as f32)
+            }
+        }
+
+        fn finish(self: Box<Self>, world: &mut World) {
+            world.resource_mut::<Total>().0 += self.total;
+        }
+    }
+
+    #[test]
+    fn tasks_step_within_the_budget_and_finish_in_order() {
+        let mut world = World::new();
+        world.init_resource::<Total>();
+        let mut work = IncrementalWork {
+            budget_ms: 0.0,
+            ..Default::default()
+        };
+        work.spawn(
+            "first",
+            Sum {
+                next: 0,
+                end: 4,
+                total: 0,
+            },
+        );
+        work.spawn(
+            "second",
+            Sum {
+                next: 4,
+                end: 6,
+                total: 0,
+            },
+        );
+        world.insert_resource(work);
+
+        // Without a budget every frame still does a single step
+        for frame in 0..3 {
+            incremental_system(&mut world);
+            let work = world.resource::<IncrementalWork>();
+            assert_eq!(work.tasks.front().unwrap().steps, frame + 1);
+        }
+        assert_eq!(world.resource::<Total>().0, 0);
+        incremental_system(&mut world);
+        assert_eq!(world.resource::<Total>().0, 1 + 2 + 3);
+        let work = world.resource::<IncrementalWork>();
+        assert_eq!(work.finished[0].name, "first");
+        assert_eq!(work.finished[0].frames, 4);
+        assert_eq!(work.tasks.len(), 1);
+
+        // A budget no step can use up finishes everything in one frame
+        world.resource_mut::<IncrementalWork>().budget_ms = 60_000.0;
+        incremental_system(&mut world);
+        assert!(world.resource::<IncrementalWork>().is_idle());
+        assert_eq!(world.resource::<Total>().0, 1 + 2 + 3 + 4 + 5);
+        assert_eq!(world.resource::<IncrementalWork>().finished[1].frames, 1);
+    }
+}
@@ -0,0 +1,134 @@
+//! gilrs backed gamepads. gilrs reads evdev through libudev on Linux, which is
+//! why it sits behind the `gamepad` feature.
+
+use bevy_ecs::{
+    system::{NonSendMut, Res, ResMut},
+    world::World,
+};
+use gilrs::{Axis, Button, EventType, Gamepad, Gilrs, GilrsBuilder};
+use glam::Vec2;
+use tracing::{info, warn};
+
+use super::{GamepadAxes, GamepadButton, GamepadInfo, GamepadSettings, GamepadState, InputState};
+
+pub fn setup_gamepads(world: &mut World) {
+    // Deadzones are applied by GamepadState, the raw values stay visible
+    match GilrsBuilder::new().with_default_filters(false).build() {
+        Ok(gilrs) => {
+            let mut input = world.resource_mut::<InputState>();
+            input.gamepad.connected = connected(&gilrs);
+            input.gamepad.active = input.gamepad.connected.first().map(|info| info.id);
+            info!("Found {} gamepads", input.gamepad.connected.len());
+            world.insert_non_send_resource(gilrs);
+        }
+        Err(e) => {
+            warn!("Gamepads unavailable: {}", e);
+            world.resource_mut::<InputState>().gamepad.error = Some(e.to_string());
+        }
+    }
+}
+
+fn connected(gilrs: &Gilrs) -> Vec<GamepadInfo> {
+    gilrs
+        .gamepads()
+        .map(|(id, gamepad)| GamepadInfo {
+            id: id.into(),
+            name: gamepad.name().to_string(),
+            power: format!("{:?}", gamepad.power_info()),
+        })
+        .collect()
+}
+
+fn button(button: Button) -> Option<GamepadButton> {
+    Some(match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::North => GamepadButton::North,
+        Button::West => GamepadButton::West,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::LeftThumb => GamepadButton::LeftStick,
+        Button::RightThumb => GamepadButton::RightStick,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+fn axes(gamepad: &Gamepad) -> GamepadAxes {
+    let trigger = |button| gamepad.button_data(button).map_or(0.0, |data| data.value());
+    GamepadAxes {
+        left_stick: Vec2::new(
+            gamepad.value(Axis::LeftStickX),
+            gamepad.value(Axis::LeftStickY),
+        ),
+        right_stick: Vec2::new(
+            gamepad.value(Axis::RightStickX),
+            gamepad.value(Axis::RightStickY),
+        ),
+        left_trigger: trigger(Button::LeftTrigger2),
+        right_trigger: trigger(Button::RightTrigger2),
+    }
+}
+
+pub fn gamepad_poll_system(
+    gilrs: Option<NonSendMut<Gilrs>>,
+    settings: Res<GamepadSettings>,
+    mut input: ResMut<InputState>,
+) {
+    if let Some(mut gilrs) = gilrs {
+        poll_gamepads(&mut gilrs, &settings, &mut input.gamepad);
+    }
+}
+
+/// Drains gilrs events for hot-plugging and button edges, then samples the
+/// active pad.
+pub fn poll_gamepads(gilrs: &mut Gilrs, settings: &GamepadSettings, state: &mut GamepadState) {
+    while let Some(event) = gilrs.next_event() {
+        let id: usize = event.id.into();
+        match event.event {
+            EventType::Connected => {
+                info!("Gamepad connected: {}", gilrs.gamepad(event.id).name());
+                state.connected = connected(gilrs);
+                state.active.get_or_insert(id);
+            }
+            EventType::Disconnected => {
+                info!("Gamepad disconnected: {}", gilrs.gamepad(event.id).name());
+                state.connected = connected(gilrs);
+                if state.active == Some(id) {
+                    state.release_all();
+                    state.active = state.connected.first().map(|info| info.id);
+                }
+            }
+            EventType::ButtonPressed(pressed, _) => {
+                // Whatever pad is touched last takes over
+                if state.active != Some(id) {
+                    state.release_all();
+                    state.active = Some(id);
+                }
+                if let Some(button) = button(pressed) {
+                    state.set_pressed(button, true);
+                }
+            }
+            EventType::ButtonReleased(released, _) if state.active == Some(id) => {
+                if let Some(button) = button(released) {
+                    state.set_pressed(button, false);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(gamepad) = state
+        .active
+        .and_then(|active| gilrs.gamepads().find(|(id, _)| usize::from(*id) == active))
+        .map(|(_, gamepad)| gamepad)
+    {
+        state.raw = axes(&gamepad);
+    }
+    state.apply_deadzones(settings);
+}
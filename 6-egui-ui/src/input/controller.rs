@@ -0,0 +1,244 @@
+//! Mouse and keyboard camera. Orbit mode turns around the target with the
+//! right mouse button, pans with the middle one and zooms with the wheel,
+//! the same orbit the gamepad and touch drive. Fly mode looks around while
+//! the right mouse button is held, moves with WASD and Q, E for down and up,
+//! and shift to go faster. Anything that starts on top of egui is left to it.
+
+use std::collections::HashSet;
+
+use bevy_ecs::system::{Res, ResMut, Resource};
+use glam::{Quat, Vec2, Vec3};
+use playground_app::Input;
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use super::{orbit_camera, InputState, MAX_PITCH};
+use crate::{gpu::GpuContext, i18n::tr, pipeline::ui::EguiState, scene::Camera, time::TimeContext};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    Orbit,
+    Fly,
+}
+impl CameraMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Orbit => tr("Orbit"),
+            Self::Fly => tr("Fly"),
+        }
+    }
+}
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct CameraController {
+    pub enabled: bool,
+    pub mode: CameraMode,
+    /// Radians per physical pixel dragged, for orbiting and looking around.
+    pub look_speed: f32,
+    /// Distance scale per line scrolled.
+    pub zoom_step: f32,
+    /// Units per second flown.
+    pub fly_speed: f32,
+    /// Speed multiplier while shift is held.
+    pub boost: f32,
+}
+impl Default for CameraController {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mode: CameraMode::Orbit,
+            look_speed: 0.005,
+            zoom_step: 0.9,
+            fly_speed: 10.0,
+            boost: 4.0,
+        }
+    }
+}
+
+/// Mouse and keyboard input of this frame, as far as the camera cares.
+#[derive(Default)]
+pub struct MouseState {
+    /// Pixels dragged with the right button.
+    pub look: Vec2,
+    /// Pixels dragged with the middle button.
+    pub pan: Vec2,
+    /// Lines scrolled, positive away from the user.
+    pub scroll: f32,
+    /// The right button is down and it went down outside of egui.
+    pub looking: bool,
+    pub panning: bool,
+    /// Movement keys held that weren't pressed while typing into egui.
+    pub keys: HashSet<KeyCode>,
+}
+impl MouseState {
+    /// Right, up and forward, each in [-1, 1].
+    pub fn movement(&self) -> Vec3 {
+        let axis = |negative, positive| {
+            self.keys.contains(&positive) as i32 as f32
+                - self.keys.contains(&negative) as i32 as f32
+        };
+        Vec3::new(
+            axis(KeyCode::KeyA, KeyCode::KeyD),
+            axis(KeyCode::KeyQ, KeyCode::KeyE),
+            axis(KeyCode::KeyS, KeyCode::KeyW),
+        )
+    }
+
+    pub fn boosted(&self) -> bool {
+        self.keys.contains(&KeyCode::ShiftLeft) || self.keys.contains(&KeyCode::ShiftRight)
+    }
+}
+
+const MOVEMENT_KEYS: [KeyCode; 8] = [
+    KeyCode::KeyW,
+    KeyCode::KeyA,
+    KeyCode::KeyS,
+    KeyCode::KeyD,
+    KeyCode::KeyQ,
+    KeyCode::KeyE,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+];
+
+/// Turns this frame's [`Input`] into camera drags, scrolling and held
+/// movement keys. A drag that starts on top of egui is left to it.
+pub fn mouse_input_system(
+    input: Res<Input>,
+    ui: Res<EguiState>,
+    mut camera_input: ResMut<InputState>,
+) {
+    let context = ui.renderer.context();
+    let over_ui = input.cursor.is_some_and(|position| {
+        let point = position / context.pixels_per_point();
+        context.layer_id_at(egui::pos2(point.x, point.y)).is_some()
+    }) || context.is_using_pointer();
+    let state = &mut camera_input.mouse;
+
+    for (button, grabbed) in [
+        (MouseButton::Right, &mut state.looking),
+        (MouseButton::Middle, &mut state.panning),
+    ] {
+        if input.mouse_buttons.just_pressed(button) {
+            *grabbed = !over_ui;
+        }
+        // Also ends drags cut short by losing focus
+        if !input.mouse_buttons.pressed(button) {
+            *grabbed = false;
+        }
+    }
+    state.look = if state.looking {
+        input.cursor_delta
+    } else {
+        Vec2::ZERO
+    };
+    state.pan = if state.panning {
+        input.cursor_delta
+    } else {
+        Vec2::ZERO
+    };
+    state.scroll = if over_ui { 0.0 } else { input.scroll.y };
+
+    for key in MOVEMENT_KEYS {
+        if input.keys.just_pressed(key) && !context.wants_keyboard_input() {
+            state.keys.insert(key);
+        } else if !input.keys.pressed(key) {
+            state.keys.remove(&key);
+        }
+    }
+}
+
+pub fn mouse_camera_system(
+    gpu: Res<GpuContext>,
+    time: Res<TimeContext>,
+    controller: Res<CameraController>,
+    input: Res<InputState>,
+    mut camera: ResMut<Camera>,
+) {
+    if !controller.enabled {
+        return;
+    }
+    let mouse = &input.mouse;
+    match controller.mode {
+        CameraMode::Orbit => {
+            if mouse.look == Vec2::ZERO && mouse.pan == Vec2::ZERO && mouse.scroll == 0.0 {
+                return;
+            }
+            // World units per pixel at the target, so the ground follows the cursor
+            let distance = camera.eye.distance(camera.target);
+            let units_per_pixel =
+                2.0 * distance * (camera.fov_y / 2.0).tan() / gpu.config.height.max(1) as f32;
+            orbit_camera(
+                &mut camera,
+                -mouse.look.x * controller.look_speed,
+                mouse.look.y * controller.look_speed,
+                controller.zoom_step.powf(mouse.scroll),
+                Vec2::new(-mouse.pan.x, mouse.pan.y) * units_per_pixel,
+            );
+        }
+        CameraMode::Fly => {
+            // Keys only fly while looking, otherwise they belong to the editor
+            let movement = if mouse.looking {
+                mouse.movement()
+            } else {
+                Vec3::ZERO
+            };
+            if mouse.look == Vec2::ZERO && movement == Vec3::ZERO {
+                return;
+            }
+            let speed = controller.fly_speed
+                * if mouse.boosted() {
+                    controller.boost
+                } else {
+                    1.0
+                };
+            fly_camera(
+                &mut camera,
+                -mouse.look.x * controller.look_speed,
+                -mouse.look.y * controller.look_speed,
+                movement * speed * time.delta,
+            );
+        }
+    }
+}
+
+/// Turns the camera in place and moves it by `movement`, given as right, up
+/// and forward. The target moves along at the same distance, so orbiting
+/// afterwards turns around what the camera looks at.
+pub fn fly_camera(camera: &mut Camera, yaw: f32, pitch: f32, movement: Vec3) {
+    let offset = camera.target - camera.eye;
+    let distance = offset.length().max(1e-3);
+    let forward = offset / distance;
+    let current_pitch = forward.y.clamp(-1.0, 1.0).asin();
+    let pitch = (current_pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH) - current_pitch;
+    let right = forward.cross(Vec3::Y).normalize_or(Vec3::X);
+    let forward = Quat::from_rotation_y(yaw) * Quat::from_axis_angle(right, pitch) * forward;
+
+    let right = forward.cross(Vec3::Y).normalize_or(Vec3::X);
+    camera.eye += right * movement.x + Vec3::Y * movement.y + forward * movement.z;
+    camera.target = camera.eye + forward * distance;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flying_keeps_the_target_in_front() {
+        let mut camera = Camera::default();
+        let distance = camera.eye.distance(camera.target);
+
+        // A quarter turn left, then a step forward
+        fly_camera(&mut camera, std::f32::consts::FRAC_PI_2, 0.0, Vec3::ZERO);
+        let eye = camera.eye;
+        fly_camera(&mut camera, 0.0, 0.0, Vec3::new(0.0, 0.0, 2.0));
+        assert!((camera.eye.distance(camera.target) - distance).abs() < 1e-3);
+        let moved = camera.eye - eye;
+        assert!((moved.length() - 2.0).abs() < 1e-4);
+        // Turned left from looking down -z, still pitched down at the target
+        assert!(moved.x < 0.0 && moved.z.abs() < 1e-4, "{moved}");
+
+        // Looking straight down stops short of the pole
+        fly_camera(&mut camera, 0.0, -10.0, Vec3::ZERO);
+        let forward = (camera.target - camera.eye).normalize();
+        assert!((forward.y.asin() + MAX_PITCH).abs() < 1e-4);
+    }
+}
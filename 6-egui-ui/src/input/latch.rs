@@ -0,0 +1,285 @@
+//! Late latching: the camera is moved once more right before the frame is
+//! submitted, from input sampled then, instead of only at the start of the
+//! frame. Everything recorded in between is unaffected, only the camera
+//! uniform is written again, which the queue applies on submit. Input only
+//! gets fresher where it can be sampled mid-frame, that's the gamepad, touch
+//! and window events arrive between frames.
+//!
+//! The latency measured is the age of the camera's input when the frame is
+//! presented. With vsync the present waits for the display anyway, the win
+//! shows with vsync off or a mailbox present mode.
+
+use std::{collections::VecDeque, time::Instant};
+
+#[cfg(feature = "gamepad")]
+use bevy_ecs::system::NonSendMut;
+use bevy_ecs::{
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+#[cfg(feature = "gamepad")]
+use gilrs::Gilrs;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pipeline::{
+        checkerboard::Checkerboard,
+        mesh::CameraBuffer,
+        render::FrameTarget,
+        stereo::{StereoCameras, StereoMode, StereoSettings},
+    },
+    scene::Camera,
+    settings::RendererSettings,
+    time::TimeContext,
+};
+
+use super::{gamepad_orbit, GamepadAxes, GamepadSettings, InputState};
+
+// =============================== LATCH ===============================
+#[derive(Resource, Default)]
+pub struct LateLatch {
+    /// When the input the camera moved by was sampled.
+    sampled_at: Option<Instant>,
+    /// The camera was moved again this frame.
+    latched: bool,
+    /// Seconds the late latch already moved the camera by, which the next
+    /// frame's camera update must leave out.
+    carry: f32,
+    /// A frame was acquired and is about to be presented.
+    presenting: bool,
+    /// Per frame without and with late latching.
+    pub stats: [LatencyStats; 2],
+}
+impl LateLatch {
+    pub fn take_carry(&mut self) -> f32 {
+        std::mem::take(&mut self.carry)
+    }
+
+    /// Moves the camera for the time since the input was last sampled.
+    pub fn latch(
+        &mut self,
+        camera: &mut Camera,
+        axes: GamepadAxes,
+        settings: &GamepadSettings,
+        now: Instant,
+    ) {
+        let Some(sampled_at) = self.sampled_at else {
+            return;
+        };
+        let delta = now.duration_since(sampled_at).as_secs_f32();
+        gamepad_orbit(camera, axes, settings, delta);
+        self.carry += delta;
+        self.sampled_at = Some(now);
+        self.latched = true;
+    }
+}
+
+/// Input age at present over the last frames, in milliseconds.
+#[derive(Default)]
+pub struct LatencyStats {
+    samples: VecDeque<f32>,
+}
+impl LatencyStats {
+    const SAMPLES: usize = 240;
+
+    pub fn record(&mut self, ms: f32) {
+        if self.samples.len() == Self::SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ms);
+    }
+
+    pub fn average(&self) -> Option<f32> {
+        (!self.samples.is_empty())
+            .then(|| self.samples.iter().sum::<f32>() / self.samples.len() as f32)
+    }
+
+    pub fn max(&self) -> Option<f32> {
+        self.samples.iter().copied().reduce(f32::max)
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+}
+
+// =============================== SYSTEMS ===============================
+/// Right after the input is polled, before anything moves the camera.
+pub fn input_sample_system(mut latch: ResMut<LateLatch>) {
+    latch.sampled_at = Some(Instant::now());
+    latch.latched = false;
+}
+
+/// Between recording and submitting, the last moment the camera can change.
+/// Skipped for fixed steps, which have to come out the same every run.
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(not(feature = "gamepad"), allow(unused_mut))]
+pub fn late_latch_system(
+    #[cfg(feature = "gamepad")] gilrs: Option<NonSendMut<Gilrs>>,
+    settings: Res<RendererSettings>,
+    gamepad: Res<GamepadSettings>,
+    time: Res<TimeContext>,
+    target: Res<FrameTarget>,
+    gpu: Res<GpuContext>,
+    mut input: ResMut<InputState>,
+    mut camera: ResMut<Camera>,
+    mut camera_buffer: ResMut<CameraBuffer>,
+    stereo: Option<Res<StereoSettings>>,
+    stereo_cameras: Option<ResMut<StereoCameras>>,
+    checkerboard: Option<ResMut<Checkerboard>>,
+    mut latch: ResMut<LateLatch>,
+) {
+    latch.presenting = target.acquired.is_some();
+    if !latch.presenting || !settings.late_latch || time.fixed_step.is_some() {
+        return;
+    }
+    #[cfg(feature = "gamepad")]
+    if let Some(mut gilrs) = gilrs {
+        // Buttons were already handled this frame, new presses wait for the next
+        let before = input.gamepad.just_pressed;
+        super::backend::poll_gamepads(&mut gilrs, &gamepad, &mut input.gamepad);
+        input.gamepad.defer_edges_since(before);
+    }
+    latch.latch(&mut camera, input.gamepad.axes, &gamepad, Instant::now());
+    camera_buffer.rewrite(&gpu, camera.view_projection(), camera.eye);
+    if let (Some(stereo), Some(mut cameras)) = (stereo, stereo_cameras) {
+        if stereo.mode != StereoMode::Off {
+            cameras.rewrite(&gpu, &camera, &stereo);
+        }
+    }
+    if let Some(mut checkerboard) = checkerboard {
+        if checkerboard.active {
+            checkerboard.rewrite(&gpu, &camera);
+        }
+    }
+}
+
+pub fn input_latency_system(mut latch: ResMut<LateLatch>) {
+    if !std::mem::take(&mut latch.presenting) {
+        return;
+    }
+    let Some(sampled_at) = latch.sampled_at else {
+        return;
+    };
+    let ms = sampled_at.elapsed().as_secs_f32() * 1000.0;
+    let latched = latch.latched as usize;
+    latch.stats[latched].record(ms);
+}
+
+pub fn input_latency_panel(ctx: &egui::Context, world: &mut World) {
+    let mut late_latch = world.resource::<RendererSettings>().late_latch;
+    let present_mode = world.resource::<GpuContext>().config.present_mode;
+    let mut reset = false;
+    let latch = world.resource::<LateLatch>();
+
+    egui::Window::new(tr("Input latency"))
+        .id(egui::Id::new("Input latency"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut late_latch, tr("Late latch the camera"))
+                .on_hover_text(tr(
+                    "Moves the camera again right before submitting, from freshly sampled input",
+                ));
+            ui.label(trf!("Present mode: {}", format!("{:?}", present_mode)));
+            if matches!(
+                present_mode,
+                wgpu::PresentMode::Fifo | wgpu::PresentMode::FifoRelaxed
+            ) {
+                ui.label(tr(
+                    "With vsync the difference mostly hides behind the wait for the display",
+                ));
+            }
+            if !cfg!(feature = "gamepad") {
+                ui.label(tr(
+                    "Built without the gamepad feature, nothing can be sampled late",
+                ));
+            }
+
+            ui.separator();
+            ui.label(tr("Input age when presented"));
+            egui::Grid::new("input_latency")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label(tr("Average"));
+                    ui.label(tr("Max"));
+                    ui.label(tr("Frames"));
+                    ui.end_row();
+                    for (name, stats) in [tr("Start of frame"), tr("Late latched")]
+                        .into_iter()
+                        .zip(&latch.stats)
+                    {
+                        let ms = |value: Option<f32>| {
+                            value.map_or("-".to_string(), |ms| format!("{:.2} ms", ms))
+                        };
+                        ui.label(name);
+                        ui.label(ms(stats.average()));
+                        ui.label(ms(stats.max()));
+                        ui.label(stats.len().to_string());
+                        ui.end_row();
+                    }
+                });
+            reset = ui.button(tr("Reset")).clicked();
+        });
+
+    if reset {
+        world.resource_mut::<LateLatch>().stats = Default::default();
+    }
+    let mut settings = world.resource_mut::<RendererSettings>();
+    if settings.late_latch != late_latch {
+        settings.late_latch = late_latch;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy_ecs::{schedule::Schedule, world::Mut};
+    use glam::Vec2;
+
+    use super::{super::gamepad_camera_system, *};
+
+    #[test]
+    fn late_latched_time_is_not_moved_twice() {
+        let mut world = World::new();
+        let axes = GamepadAxes {
+            right_stick: Vec2::new(0.5, 0.0),
+            ..Default::default()
+        };
+        let mut input = InputState::default();
+        input.gamepad.axes = axes;
+        world.insert_resource(input);
+        world.insert_resource(GamepadSettings::default());
+        world.insert_resource(Camera::default());
+        world.insert_resource(LateLatch::default());
+        let mut time = TimeContext::new();
+        time.step(0.1);
+        world.insert_resource(time);
+        let mut schedule = Schedule::default();
+        schedule.add_systems(gamepad_camera_system);
+
+        let start = Instant::now();
+        world.resource_mut::<LateLatch>().sampled_at = Some(start);
+        schedule.run(&mut world);
+        world.resource_scope(|world, mut latch: Mut<LateLatch>| {
+            let mut camera = world.resource_mut::<Camera>();
+            let settings = GamepadSettings::default();
+            latch.latch(
+                &mut camera,
+                axes,
+                &settings,
+                start + Duration::from_millis(40),
+            );
+        });
+        schedule.run(&mut world);
+
+        // Two frames of 0.1 s, however they were split up
+        let mut expected = Camera::default();
+        gamepad_orbit(&mut expected, axes, &GamepadSettings::default(), 0.2);
+        let camera = world.resource::<Camera>();
+        assert!(camera.eye.distance(expected.eye) < 1e-4);
+        assert_eq!(world.resource_mut::<LateLatch>().take_carry(), 0.0);
+    }
+}
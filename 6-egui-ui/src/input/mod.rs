@@ -0,0 +1,557 @@
+use anyhow::Result;
+use bevy_ecs::{
+    observer::Trigger,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Vec2, Vec3};
+use playground_app::FrameEnd;
+
+use crate::{
+    i18n::{tr, trf},
+    pipeline::{
+        render::{render_system, submit_system},
+        ui::{EguiState, UiPanels},
+    },
+    scene::{camera_aspect_system, Camera},
+    time::TimeContext,
+};
+
+#[cfg(feature = "gamepad")]
+mod backend;
+mod controller;
+pub mod latch;
+mod touch;
+
+pub use controller::{
+    mouse_camera_system, mouse_input_system, CameraController, CameraMode, MouseState,
+};
+pub use touch::{touch_camera_system, TouchSettings, TouchState};
+
+use latch::LateLatch;
+
+pub fn setup_input(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    world.insert_resource(InputState::default());
+    world.insert_resource(GamepadSettings::default());
+    world.insert_resource(TouchSettings::default());
+    world.insert_resource(CameraController::default());
+    world.insert_resource(LateLatch::default());
+    world.add_observer(touch::touch_input_observer);
+    world.add_observer(input_frame_end_observer);
+    let mut panels = world.get_resource_or_insert_with(UiPanels::default);
+    panels.add_panel(camera_controller_panel);
+    panels.add_panel(gamepad_panel);
+    panels.add_panel(touch_panel);
+    panels.add_panel(latch::input_latency_panel);
+
+    #[cfg(feature = "gamepad")]
+    {
+        backend::setup_gamepads(world);
+        schedule.add_systems(
+            backend::gamepad_poll_system
+                .before(latch::input_sample_system)
+                .before(gamepad_camera_system)
+                .before(gamepad_ui_system),
+        );
+    }
+    schedule.add_systems((
+        latch::input_sample_system.before(gamepad_camera_system),
+        gamepad_camera_system.before(camera_aspect_system),
+        latch::late_latch_system
+            .after(render_system)
+            .before(submit_system),
+        latch::input_latency_system.after(submit_system),
+        touch::touch_camera_system.before(camera_aspect_system),
+        mouse_input_system.before(mouse_camera_system),
+        mouse_camera_system.before(camera_aspect_system),
+        gamepad_ui_system.before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Drops button edges and touch gestures once every system had a chance to
+/// read them.
+pub fn input_frame_end_observer(_trigger: Trigger<FrameEnd>, mut input: ResMut<InputState>) {
+    input.gamepad.end_frame();
+    input.touch.reset();
+}
+
+// =============================== STATE ===============================
+/// Input the camera and UI read, besides what egui handles itself.
+#[derive(Resource, Default)]
+pub struct InputState {
+    pub gamepad: GamepadState,
+    pub touch: TouchState,
+    pub mouse: MouseState,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 14] = [
+        Self::South,
+        Self::East,
+        Self::North,
+        Self::West,
+        Self::LeftBumper,
+        Self::RightBumper,
+        Self::Select,
+        Self::Start,
+        Self::LeftStick,
+        Self::RightStick,
+        Self::DPadUp,
+        Self::DPadDown,
+        Self::DPadLeft,
+        Self::DPadRight,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::South => "South",
+            Self::East => "East",
+            Self::North => "North",
+            Self::West => "West",
+            Self::LeftBumper => "LB",
+            Self::RightBumper => "RB",
+            Self::Select => "Select",
+            Self::Start => "Start",
+            Self::LeftStick => "LS",
+            Self::RightStick => "RS",
+            Self::DPadUp => "Up",
+            Self::DPadDown => "Down",
+            Self::DPadLeft => "Left",
+            Self::DPadRight => "Right",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Sticks in [-1, 1] with y up, triggers in [0, 1].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GamepadAxes {
+    pub left_stick: Vec2,
+    pub right_stick: Vec2,
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct GamepadInfo {
+    pub id: usize,
+    pub name: String,
+    pub power: String,
+}
+
+#[derive(Default)]
+pub struct GamepadState {
+    pub connected: Vec<GamepadInfo>,
+    /// The pad driving the camera and the UI, the last one that was used.
+    pub active: Option<usize>,
+    /// As reported by the device.
+    pub raw: GamepadAxes,
+    /// With the deadzones from [`GamepadSettings`] applied.
+    pub axes: GamepadAxes,
+    pressed: [bool; GamepadButton::ALL.len()],
+    just_pressed: [bool; GamepadButton::ALL.len()],
+    /// Edges that came in after this frame's were read, see
+    /// [`Self::defer_edges_since`].
+    deferred: [bool; GamepadButton::ALL.len()],
+    /// Set when the backend couldn't be initialized.
+    pub error: Option<String>,
+}
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+impl GamepadState {
+    pub fn is_pressed(&self, button: GamepadButton) -> bool {
+        self.pressed[button.index()]
+    }
+
+    /// Pressed since the previous frame.
+    pub fn just_pressed(&self, button: GamepadButton) -> bool {
+        self.just_pressed[button.index()]
+    }
+
+    pub fn set_pressed(&mut self, button: GamepadButton, pressed: bool) {
+        self.just_pressed[button.index()] |= pressed && !self.pressed[button.index()];
+        self.pressed[button.index()] = pressed;
+    }
+
+    /// Clears the edge state, called once the frame is done with it.
+    pub fn end_frame(&mut self) {
+        self.just_pressed = std::mem::take(&mut self.deferred);
+    }
+
+    /// Moves presses that are new since `before` to the next frame. For polls
+    /// late in the frame, after everything reading the edges already ran.
+    pub fn defer_edges_since(&mut self, before: [bool; GamepadButton::ALL.len()]) {
+        for (i, deferred) in self.deferred.iter_mut().enumerate() {
+            *deferred |= self.just_pressed[i] && !before[i];
+        }
+        self.just_pressed = before;
+    }
+
+    /// Forgets everything about the active pad, e.g. after it was unplugged.
+    pub fn release_all(&mut self) {
+        self.pressed = Default::default();
+        self.raw = GamepadAxes::default();
+        self.axes = GamepadAxes::default();
+    }
+
+    pub fn apply_deadzones(&mut self, settings: &GamepadSettings) {
+        let stick = |v| radial_deadzone(v, settings.stick_deadzone, settings.stick_outer);
+        let trigger = |t| axial_deadzone(t, settings.trigger_deadzone);
+        self.axes = GamepadAxes {
+            left_stick: stick(self.raw.left_stick),
+            right_stick: stick(self.raw.right_stick),
+            left_trigger: trigger(self.raw.left_trigger),
+            right_trigger: trigger(self.raw.right_trigger),
+        };
+    }
+}
+
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+/// Zero inside `inner`, full tilt past `outer` and rescaled in between, so
+/// small movements still register right outside the deadzone. Radial keeps
+/// diagonals from snapping to the axes.
+pub fn radial_deadzone(v: Vec2, inner: f32, outer: f32) -> Vec2 {
+    let length = v.length();
+    if length <= inner {
+        return Vec2::ZERO;
+    }
+    let scaled = ((length - inner) / (outer - inner).max(1e-4)).min(1.0);
+    v / length * scaled
+}
+
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+pub fn axial_deadzone(value: f32, deadzone: f32) -> f32 {
+    ((value - deadzone) / (1.0 - deadzone).max(1e-4)).clamp(0.0, 1.0)
+}
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct GamepadSettings {
+    pub stick_deadzone: f32,
+    pub stick_outer: f32,
+    pub trigger_deadzone: f32,
+    /// Radians per second at full tilt.
+    pub look_speed: f32,
+    /// Units per second at full tilt.
+    pub move_speed: f32,
+    pub camera: bool,
+    pub ui_navigation: bool,
+}
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            stick_deadzone: 0.15,
+            stick_outer: 0.95,
+            trigger_deadzone: 0.05,
+            look_speed: 2.0,
+            move_speed: 12.0,
+            camera: true,
+            ui_navigation: true,
+        }
+    }
+}
+
+// =============================== SYSTEMS ===============================
+const MIN_DISTANCE: f32 = 1.0;
+const MAX_DISTANCE: f32 = 150.0;
+/// Keeps the orbit away from the poles, where the up vector flips.
+const MAX_PITCH: f32 = 1.5;
+
+/// Orbits the camera around its target with the right stick, moves the target
+/// along the ground with the left stick and zooms with the triggers.
+pub fn gamepad_camera_system(
+    input: Res<InputState>,
+    settings: Res<GamepadSettings>,
+    time: Res<TimeContext>,
+    mut latch: ResMut<LateLatch>,
+    mut camera: ResMut<Camera>,
+) {
+    // Whatever the late latch covered last frame was already moved
+    let delta = (time.delta - latch.take_carry()).max(0.0);
+    gamepad_orbit(&mut camera, input.gamepad.axes, &settings, delta);
+}
+
+/// Moves the camera as the sticks say over `delta` seconds.
+pub fn gamepad_orbit(
+    camera: &mut Camera,
+    axes: GamepadAxes,
+    settings: &GamepadSettings,
+    delta: f32,
+) {
+    let zoom = axes.left_trigger - axes.right_trigger;
+    if !settings.camera
+        || (axes.left_stick == Vec2::ZERO && axes.right_stick == Vec2::ZERO && zoom == 0.0)
+    {
+        return;
+    }
+
+    let look = settings.look_speed * delta;
+    orbit_camera(
+        camera,
+        -axes.right_stick.x * look,
+        -axes.right_stick.y * look,
+        1.0 + zoom * delta,
+        axes.left_stick * settings.move_speed * delta,
+    );
+}
+
+/// Turns the camera around its target, scales the distance to it and moves
+/// the target along the ground by `movement`, given as right and forward.
+pub fn orbit_camera(camera: &mut Camera, yaw: f32, pitch: f32, zoom: f32, movement: Vec2) {
+    let offset = camera.eye - camera.target;
+    let distance = offset.length();
+    let yaw = offset.x.atan2(offset.z) + yaw;
+    let pitch = ((offset.y / distance).asin() + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    let distance = (distance * zoom).clamp(MIN_DISTANCE, MAX_DISTANCE);
+
+    let forward = Vec3::new(-yaw.sin(), 0.0, -yaw.cos());
+    let right = forward.cross(Vec3::Y);
+    camera.target += right * movement.x + forward * movement.y;
+    camera.eye = camera.target
+        + distance
+            * Vec3::new(
+                yaw.sin() * pitch.cos(),
+                pitch.sin(),
+                yaw.cos() * pitch.cos(),
+            );
+}
+
+/// Turns the d-pad and face buttons into the keys egui moves focus and
+/// activates widgets with.
+pub fn gamepad_ui_system(
+    input: Res<InputState>,
+    settings: Res<GamepadSettings>,
+    mut ui: ResMut<EguiState>,
+) {
+    if !settings.ui_navigation {
+        return;
+    }
+    let shift = egui::Modifiers::SHIFT;
+    let none = egui::Modifiers::NONE;
+    let keys = [
+        (GamepadButton::DPadDown, egui::Key::Tab, none),
+        (GamepadButton::DPadUp, egui::Key::Tab, shift),
+        (GamepadButton::DPadLeft, egui::Key::ArrowLeft, none),
+        (GamepadButton::DPadRight, egui::Key::ArrowRight, none),
+        (GamepadButton::South, egui::Key::Enter, none),
+        (GamepadButton::East, egui::Key::Escape, none),
+    ];
+    for (button, key, modifiers) in keys {
+        if input.gamepad.just_pressed(button) {
+            ui.renderer.press_key(key, modifiers);
+        }
+    }
+}
+
+fn camera_controller_panel(ctx: &egui::Context, world: &mut World) {
+    let mut controller = world.resource::<CameraController>().clone();
+
+    egui::Window::new(tr("Camera controls"))
+        .id(egui::Id::new("Camera controls"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut controller.enabled, tr("Mouse and keyboard"));
+            ui.horizontal(|ui| {
+                for mode in [CameraMode::Orbit, CameraMode::Fly] {
+                    ui.radio_value(&mut controller.mode, mode, mode.label());
+                }
+            });
+            ui.add(
+                egui::Slider::new(&mut controller.look_speed, 0.001..=0.02)
+                    .logarithmic(true)
+                    .text(tr("look speed")),
+            );
+            match controller.mode {
+                CameraMode::Orbit => {
+                    ui.add(
+                        egui::Slider::new(&mut controller.zoom_step, 0.5..=0.99)
+                            .text(tr("zoom per wheel step")),
+                    );
+                    ui.label(tr("Right drag orbits, middle drag pans, the wheel zooms"));
+                }
+                CameraMode::Fly => {
+                    ui.add(
+                        egui::Slider::new(&mut controller.fly_speed, 1.0..=100.0)
+                            .logarithmic(true)
+                            .text(tr("fly speed")),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut controller.boost, 1.0..=10.0)
+                            .text(tr("shift boost")),
+                    );
+                    ui.label(tr(
+                        "Hold the right button to look around, WASD moves, Q and E go down and up",
+                    ));
+                }
+            }
+        });
+
+    let mut current = world.resource_mut::<CameraController>();
+    if *current != controller {
+        *current = controller;
+    }
+}
+
+fn gamepad_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<GamepadSettings>().clone();
+    let mut active = world.resource::<InputState>().gamepad.active;
+    let gamepad = &world.resource::<InputState>().gamepad;
+
+    egui::Window::new(tr("Gamepad"))
+        .id(egui::Id::new("Gamepad"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            if !cfg!(feature = "gamepad") {
+                ui.label(tr("Built without the gamepad feature"));
+            }
+            if let Some(error) = &gamepad.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            if gamepad.connected.is_empty() {
+                ui.label(tr("No gamepad connected"));
+            }
+            for info in &gamepad.connected {
+                ui.radio_value(
+                    &mut active,
+                    Some(info.id),
+                    format!("{} ({})", info.name, info.power),
+                );
+            }
+
+            ui.checkbox(&mut settings.camera, tr("Camera control"));
+            ui.checkbox(&mut settings.ui_navigation, tr("UI navigation"));
+            ui.add(
+                egui::Slider::new(&mut settings.stick_deadzone, 0.0..=0.5)
+                    .text(tr("stick deadzone")),
+            );
+            ui.add(egui::Slider::new(&mut settings.stick_outer, 0.5..=1.0).text(tr("stick outer")));
+            ui.add(
+                egui::Slider::new(&mut settings.trigger_deadzone, 0.0..=0.5)
+                    .text(tr("trigger deadzone")),
+            );
+            ui.add(egui::Slider::new(&mut settings.look_speed, 0.5..=5.0).text(tr("look speed")));
+            ui.add(egui::Slider::new(&mut settings.move_speed, 1.0..=50.0).text(tr("move speed")));
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                stick_plot(
+                    ui,
+                    tr("Left"),
+                    gamepad.raw.left_stick,
+                    gamepad.axes.left_stick,
+                    &settings,
+                );
+                stick_plot(
+                    ui,
+                    tr("Right"),
+                    gamepad.raw.right_stick,
+                    gamepad.axes.right_stick,
+                    &settings,
+                );
+            });
+            for (name, raw, value) in [
+                ("LT", gamepad.raw.left_trigger, gamepad.axes.left_trigger),
+                ("RT", gamepad.raw.right_trigger, gamepad.axes.right_trigger),
+            ] {
+                ui.add(egui::ProgressBar::new(value).text(trf!(
+                    "{} {:.3} raw {:.3}",
+                    name,
+                    value,
+                    raw
+                )));
+            }
+            ui.horizontal_wrapped(|ui| {
+                for button in GamepadButton::ALL {
+                    let color = if gamepad.is_pressed(button) {
+                        egui::Color32::LIGHT_GREEN
+                    } else {
+                        egui::Color32::DARK_GRAY
+                    };
+                    ui.colored_label(color, button.name());
+                }
+            });
+        });
+
+    world.resource_mut::<InputState>().gamepad.active = active;
+    let mut current = world.resource_mut::<GamepadSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+/// The raw position in gray and the processed one in green, over the inner
+/// and outer deadzone circles.
+fn stick_plot(ui: &mut egui::Ui, name: &str, raw: Vec2, value: Vec2, settings: &GamepadSettings) {
+    ui.vertical(|ui| {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(96.0, 96.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        let center = rect.center();
+        let radius = rect.width() / 2.0 - 2.0;
+        let position = |v: Vec2| center + egui::vec2(v.x, -v.y) * radius;
+        let stroke = egui::Stroke::new(1.0, egui::Color32::GRAY);
+        painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(180));
+        painter.circle_stroke(center, radius, stroke);
+        painter.circle_stroke(center, radius * settings.stick_deadzone, stroke);
+        painter.circle_stroke(center, radius * settings.stick_outer, stroke);
+        painter.circle_filled(position(raw), 3.0, egui::Color32::GRAY);
+        painter.circle_filled(position(value), 4.0, egui::Color32::LIGHT_GREEN);
+        ui.label(format!("{} {:.2}, {:.2}", name, raw.x, raw.y));
+    });
+}
+
+fn touch_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<TouchSettings>().clone();
+    let touch = &world.resource::<InputState>().touch;
+
+    egui::Window::new(tr("Touch"))
+        .id(egui::Id::new("Touch"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.camera, tr("Camera control"));
+            ui.add(
+                egui::Slider::new(&mut settings.orbit_speed, 0.001..=0.02)
+                    .logarithmic(true)
+                    .text(tr("orbit speed")),
+            );
+            ui.label(tr("One finger orbits, two fingers pinch to zoom and pan"));
+            ui.separator();
+            ui.label(trf!("Gesture: {}", format!("{:?}", touch.gesture())));
+            let mut touches: Vec<_> = touch.touches.iter().collect();
+            touches.sort_by_key(|(id, _)| **id);
+            for (id, touch) in touches {
+                ui.label(trf!(
+                    "#{} at {:.0}, {:.0}{}",
+                    id,
+                    touch.position.x,
+                    touch.position.y,
+                    if touch.claimed { " (UI)" } else { "" }
+                ));
+            }
+        });
+
+    let mut current = world.resource_mut::<TouchSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
@@ -0,0 +1,175 @@
+//! Touch gestures for the camera. egui-winit already turns touches into egui
+//! touch events and a simulated pointer, so this only has to leave alone the
+//! fingers that landed on a window.
+
+use std::collections::HashMap;
+
+use bevy_ecs::{
+    observer::Trigger,
+    system::{Res, ResMut, Resource},
+};
+use glam::Vec2;
+use playground_app::WindowTriggerEvent;
+use winit::event::{TouchPhase, WindowEvent};
+
+use super::{orbit_camera, InputState};
+use crate::{gpu::GpuContext, pipeline::ui::EguiState, scene::Camera};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Touch {
+    /// Physical pixels.
+    pub position: Vec2,
+    /// Started on top of egui, which keeps it until it's lifted.
+    pub claimed: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gesture {
+    None,
+    Orbit,
+    PinchPan,
+}
+
+/// Fingers on the screen and the gesture they made since the last frame.
+pub struct TouchState {
+    pub touches: HashMap<u64, Touch>,
+    /// Pixels dragged with one finger.
+    pub orbit: Vec2,
+    /// Pixels the two finger centroid moved.
+    pub pan: Vec2,
+    /// Ratio of the finger spread now to the spread before, 1 is no pinch.
+    pub pinch: f32,
+}
+impl Default for TouchState {
+    fn default() -> Self {
+        Self {
+            touches: HashMap::new(),
+            orbit: Vec2::ZERO,
+            pan: Vec2::ZERO,
+            pinch: 1.0,
+        }
+    }
+}
+impl TouchState {
+    /// Only the first two free fingers count, a third one is ignored.
+    fn camera_touches(&self) -> Vec<(u64, Vec2)> {
+        let mut touches: Vec<_> = self
+            .touches
+            .iter()
+            .filter(|(_, touch)| !touch.claimed)
+            .map(|(id, touch)| (*id, touch.position))
+            .collect();
+        touches.sort_by_key(|(id, _)| *id);
+        touches.truncate(2);
+        touches
+    }
+
+    pub fn gesture(&self) -> Gesture {
+        match self.camera_touches().len() {
+            0 => Gesture::None,
+            1 => Gesture::Orbit,
+            _ => Gesture::PinchPan,
+        }
+    }
+
+    /// Forgets the gesture deltas accumulated over the frame.
+    pub fn reset(&mut self) {
+        self.orbit = Vec2::ZERO;
+        self.pan = Vec2::ZERO;
+        self.pinch = 1.0;
+    }
+
+    fn moved(&mut self, id: u64, position: Vec2) {
+        let before = self.camera_touches();
+        let Some(touch) = self.touches.get_mut(&id) else {
+            return;
+        };
+        let delta = position - touch.position;
+        touch.position = position;
+        if touch.claimed || !before.iter().any(|(camera_id, _)| *camera_id == id) {
+            return;
+        }
+
+        match before.as_slice() {
+            [_] => self.orbit += delta,
+            [a, b] => {
+                let moved =
+                    |(touch_id, p): &(u64, Vec2)| if *touch_id == id { position } else { *p };
+                let (a_after, b_after) = (moved(a), moved(b));
+                self.pan += (a_after + b_after) / 2.0 - (a.1 + b.1) / 2.0;
+                let spread = a.1.distance(b.1);
+                if spread > 1.0 {
+                    self.pinch *= a_after.distance(b_after) / spread;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct TouchSettings {
+    pub camera: bool,
+    /// Radians per physical pixel dragged.
+    pub orbit_speed: f32,
+}
+impl Default for TouchSettings {
+    fn default() -> Self {
+        Self {
+            camera: true,
+            orbit_speed: 0.005,
+        }
+    }
+}
+
+pub fn touch_input_observer(
+    trigger: Trigger<WindowTriggerEvent>,
+    ui: Res<EguiState>,
+    mut input: ResMut<InputState>,
+) {
+    let WindowEvent::Touch(touch) = &trigger.event().event else {
+        return;
+    };
+    let position = Vec2::new(touch.location.x as f32, touch.location.y as f32);
+    let state = &mut input.touch;
+    match touch.phase {
+        TouchPhase::Started => {
+            let context = ui.renderer.context();
+            let point = position / context.pixels_per_point();
+            let claimed = context.layer_id_at(egui::pos2(point.x, point.y)).is_some()
+                || context.is_using_pointer();
+            state.touches.insert(touch.id, Touch { position, claimed });
+        }
+        TouchPhase::Moved => state.moved(touch.id, position),
+        TouchPhase::Ended | TouchPhase::Cancelled => {
+            state.touches.remove(&touch.id);
+        }
+    }
+}
+
+/// One finger orbits, two fingers pinch to zoom and drag to pan the target
+/// along the ground.
+pub fn touch_camera_system(
+    gpu: Res<GpuContext>,
+    settings: Res<TouchSettings>,
+    input: Res<InputState>,
+    mut camera: ResMut<Camera>,
+) {
+    let touch = &input.touch;
+    let (orbit, pan, pinch) = (touch.orbit, touch.pan, touch.pinch);
+    if !settings.camera || (orbit == Vec2::ZERO && pan == Vec2::ZERO && pinch == 1.0) {
+        return;
+    }
+
+    // World units per pixel at the target, so the ground follows the fingers
+    let distance = camera.eye.distance(camera.target);
+    let units_per_pixel =
+        2.0 * distance * (camera.fov_y / 2.0).tan() / gpu.config.height.max(1) as f32;
+    orbit_camera(
+        &mut camera,
+        -orbit.x * settings.orbit_speed,
+        orbit.y * settings.orbit_speed,
+        1.0 / pinch.max(1e-3),
+        Vec2::new(-pan.x, pan.y) * units_per_pixel,
+    );
+}
@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use bevy_ecs::{
+    schedule::{ExecutorKind, Schedule},
+    system::Resource,
+    world::World,
+};
+use rayon::prelude::*;
+use tracing::{info, info_span};
+use tracing_tracy::client::Client;
+
+pub fn setup_jobs(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4);
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("job-{}", i))
+        .start_handler(|i| {
+            if let Some(client) = Client::running() {
+                client.set_thread_name(&format!("job-{}", i));
+            }
+        })
+        .build_global()
+        .context("Failed to build the job thread pool")?;
+
+    // Without the `multi_threaded` feature bevy silently falls back to running
+    // every system on the main thread, so be explicit about what we expect.
+    schedule.set_executor_kind(ExecutorKind::MultiThreaded);
+
+    info!("Job system running on {} threads", threads);
+    world.insert_resource(JobSystem { threads });
+
+    Ok(())
+}
+
+#[derive(Resource)]
+pub struct JobSystem {
+    pub threads: usize,
+}
+
+/// Items processed by one job; small enough to balance, large enough that
+/// span overhead stays negligible.
+const CHUNK_SIZE: usize = 256;
+
+/// Runs `f` over `items` on the job pool, tracing every chunk as `label`.
+pub fn par_for_each<T: Send>(label: &'static str, items: &mut [T], f: impl Fn(&mut T) + Sync) {
+    items.par_chunks_mut(CHUNK_SIZE).for_each(|chunk| {
+        let _span = info_span!("job", label, len = chunk.len()).entered();
+        chunk.iter_mut().for_each(&f);
+    });
+}
+
+/// Sorts `items` by `key` on the job pool.
+pub fn par_sort_by_key<T: Send, K: Ord>(
+    label: &'static str,
+    items: &mut [T],
+    key: impl Fn(&T) -> K + Sync,
+) {
+    let _span = info_span!("job", label, len = items.len()).entered();
+    items.par_sort_unstable_by_key(key);
+}
@@ -0,0 +1,51 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+
+pub fn setup_layouts(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    world.insert_resource(LayoutCache::default());
+    Ok(())
+}
+
+// =============================== CACHE ===============================
+/// Shares bind group layouts between pipelines. Passes asking for the same
+/// entries get the same layout, so a bind group made for one of them can be
+/// set on the others too.
+#[derive(Resource, Default)]
+pub struct LayoutCache {
+    layouts: HashMap<Vec<wgpu::BindGroupLayoutEntry>, Arc<wgpu::BindGroupLayout>>,
+    requests: usize,
+}
+impl LayoutCache {
+    /// Entries may be listed in any order, they are keyed by binding.
+    pub fn get(
+        &mut self,
+        device: &wgpu::Device,
+        entries: &[wgpu::BindGroupLayoutEntry],
+    ) -> Arc<wgpu::BindGroupLayout> {
+        let mut key = entries.to_vec();
+        key.sort_by_key(|entry| entry.binding);
+        self.requests += 1;
+        self.layouts
+            .entry(key)
+            .or_insert_with_key(|entries| {
+                Arc::new(
+                    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        label: Some("cached_bind_group_layout"),
+                        entries,
+                    }),
+                )
+            })
+            .clone()
+    }
+
+    pub fn cached(&self) -> usize {
+        self.layouts.len()
+    }
+
+    /// Requests answered with a layout some other pass already made.
+    pub fn shared(&self) -> usize {
+        self.requests - self.layouts.len()
+    }
+}
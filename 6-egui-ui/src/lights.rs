@@ -0,0 +1,463 @@
+use anyhow::Result;
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Query, Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Mat4, Quat, Vec3, Vec4Swizzles};
+use tracing::warn;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pipeline::{
+        cascades::CascadedShadows, debug_draw::DebugDraw, render::render_system,
+        shadow::ShadowAtlas, ui::UiPanels,
+    },
+    scene::{transform_propagation_system, Camera, GlobalTransform, Name, Parent, Spin, Transform},
+};
+
+/// Lights beyond this count are dropped, furthest from the camera first.
+pub const MAX_LIGHTS: usize = 64;
+
+pub fn setup_lights(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let atlas = world
+        .get_resource::<ShadowAtlas>()
+        .ok_or_else(|| anyhow::anyhow!("ShadowAtlas resource not found"))?;
+
+    let cascades = world
+        .get_resource::<CascadedShadows>()
+        .ok_or_else(|| anyhow::anyhow!("CascadedShadows resource not found"))?;
+
+    let light_buffer = LightBuffer::new(gpu, atlas, cascades);
+    world.insert_resource(light_buffer);
+    world.insert_resource(LightGizmos { enabled: true });
+
+    spawn_demo_lights(world);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(lights_panel);
+
+    schedule.add_systems(
+        (light_gathering_system, light_gizmo_system)
+            .after(transform_propagation_system)
+            .before(render_system),
+    );
+
+    Ok(())
+}
+
+fn spawn_demo_lights(world: &mut World) {
+    let sun_direction = Vec3::new(-0.4, -1.0, -0.3).normalize();
+    world.spawn((
+        Name::new("Sun"),
+        Transform::from_translation(Vec3::new(0.0, 10.0, 0.0))
+            .with_rotation(Quat::from_rotation_arc(Vec3::NEG_Z, sun_direction)),
+        GlobalTransform::default(),
+        DirectionalLight {
+            color: Vec3::new(1.0, 0.95, 0.85),
+            intensity: 0.6,
+            cast_shadows: true,
+        },
+    ));
+
+    let pivot = world
+        .spawn((
+            Name::new("Light pivot"),
+            Transform::from_translation(Vec3::new(0.0, 2.0, 0.0)),
+            GlobalTransform::default(),
+            Spin {
+                axis: Vec3::Y,
+                speed: 0.5,
+            },
+        ))
+        .id();
+    let colors = [
+        Vec3::new(1.0, 0.3, 0.2),
+        Vec3::new(0.2, 1.0, 0.4),
+        Vec3::new(0.3, 0.4, 1.0),
+    ];
+    for (i, color) in colors.into_iter().enumerate() {
+        let angle = i as f32 / colors.len() as f32 * std::f32::consts::TAU;
+        world.spawn((
+            Name::new(format!("Point light {}", i)),
+            Transform::from_translation(Vec3::new(angle.cos(), 0.0, angle.sin()) * 9.0),
+            GlobalTransform::default(),
+            Parent(pivot),
+            PointLight {
+                color,
+                intensity: 2.0,
+                range: 8.0,
+            },
+        ));
+    }
+
+    let spots = [
+        (Vec3::new(-6.0, 8.0, 12.0), Vec3::new(-6.0, 0.0, 6.0), 3.0),
+        (Vec3::new(10.0, 7.0, 10.0), Vec3::new(6.0, 0.0, 6.0), 2.5),
+        (Vec3::new(10.0, 7.0, -10.0), Vec3::new(6.0, 0.0, -6.0), 2.0),
+        (
+            Vec3::new(-10.0, 6.0, -10.0),
+            Vec3::new(-6.0, 0.0, -6.0),
+            1.5,
+        ),
+    ];
+    for (i, (position, target, intensity)) in spots.into_iter().enumerate() {
+        world.spawn((
+            Name::new(format!("Spot light {}", i)),
+            Transform::from_translation(position).with_rotation(Quat::from_rotation_arc(
+                Vec3::NEG_Z,
+                (target - position).normalize(),
+            )),
+            GlobalTransform::default(),
+            SpotLight {
+                color: Vec3::new(1.0, 0.9, 0.6),
+                intensity,
+                range: 20.0,
+                inner_angle: 15f32.to_radians(),
+                outer_angle: 25f32.to_radians(),
+                cast_shadows: true,
+            },
+        ));
+    }
+}
+
+fn lights_panel(ctx: &egui::Context, world: &mut World) {
+    let stats = world.resource::<LightBuffer>().stats;
+    let mut gizmos = world.resource_mut::<LightGizmos>();
+
+    egui::Window::new(tr("Lights"))
+        .id(egui::Id::new("Lights"))
+        .show(ctx, |ui| {
+            ui.label(trf!(
+                "Gathered: {} / {} (max {})",
+                stats.gathered,
+                stats.active,
+                MAX_LIGHTS
+            ));
+            if stats.dropped > 0 {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    trf!("{} lights dropped", stats.dropped),
+                );
+            }
+            ui.checkbox(&mut gizmos.enabled, tr("Gizmos"));
+        });
+}
+
+// =============================== COMPONENTS ===============================
+/// Shines along the forward (-Z) axis of the entity, everywhere.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct DirectionalLight {
+    pub color: Vec3,
+    pub intensity: f32,
+    /// Only the first shadow casting directional light gets cascades; see
+    /// [`CascadedShadows`].
+    pub cast_shadows: bool,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct PointLight {
+    pub color: Vec3,
+    pub intensity: f32,
+    pub range: f32,
+}
+
+/// Shines along the forward (-Z) axis of the entity, fading out between the
+/// inner and outer half-angles.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct SpotLight {
+    pub color: Vec3,
+    pub intensity: f32,
+    pub range: f32,
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    /// Requests a tile in the shadow atlas; see [`ShadowAtlas`].
+    pub cast_shadows: bool,
+}
+
+fn light_position(global: &GlobalTransform) -> Vec3 {
+    global.0.w_axis.xyz()
+}
+
+fn light_direction(global: &GlobalTransform) -> Vec3 {
+    global
+        .0
+        .transform_vector3(Vec3::NEG_Z)
+        .normalize_or(Vec3::NEG_Z)
+}
+
+// =============================== GPU ===============================
+const KIND_DIRECTIONAL: f32 = 0.0;
+const KIND_POINT: f32 = 1.0;
+const KIND_SPOT: f32 = 2.0;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GpuLight {
+    pub position: [f32; 4],
+    pub direction: [f32; 4],
+    /// Color in rgb, intensity in a.
+    pub color: [f32; 4],
+    /// Kind, range, cosine of the inner and outer spot angles.
+    pub params: [f32; 4],
+    pub shadow_view_proj: [[f32; 4]; 4],
+    /// Atlas offset in xy, scale in z, w is 1 when the light has a shadow map.
+    /// Directional lights only use w, their matrices live with the cascades.
+    pub shadow_rect: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LightStats {
+    pub active: usize,
+    pub gathered: usize,
+    pub dropped: usize,
+}
+
+/// Storage buffer holding a light count followed by up to [`MAX_LIGHTS`]
+/// lights, bound together with the shadow atlas and cascades.
+#[derive(Resource)]
+pub struct LightBuffer {
+    pub buffer: wgpu::Buffer,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    pub stats: LightStats,
+}
+impl LightBuffer {
+    const HEADER_SIZE: usize = 16;
+
+    pub fn new(gpu: &GpuContext, atlas: &ShadowAtlas, cascades: &CascadedShadows) -> Self {
+        let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("light_buffer"),
+            size: (Self::HEADER_SIZE + MAX_LIGHTS * std::mem::size_of::<GpuLight>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("light_bind_group_layout"),
+            });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&atlas.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&atlas.texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&cascades.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: cascades.uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("light_bind_group"),
+        });
+
+        Self {
+            buffer,
+            layout,
+            bind_group,
+            stats: LightStats::default(),
+        }
+    }
+
+    pub fn write(&self, gpu: &GpuContext, lights: &[GpuLight]) {
+        let mut data = Vec::with_capacity(Self::HEADER_SIZE + std::mem::size_of_val(lights));
+        data.extend_from_slice(bytemuck::bytes_of(&[lights.len() as u32, 0, 0, 0]));
+        data.extend_from_slice(bytemuck::cast_slice(lights));
+        gpu.queue.write_buffer(&self.buffer, 0, &data);
+    }
+}
+
+// =============================== SYSTEMS ===============================
+pub fn light_gathering_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    (atlas, cascades): (Res<ShadowAtlas>, Res<CascadedShadows>),
+    mut light_buffer: ResMut<LightBuffer>,
+    directional: Query<(Entity, &GlobalTransform, &DirectionalLight)>,
+    point: Query<(&GlobalTransform, &PointLight)>,
+    spot: Query<(Entity, &GlobalTransform, &SpotLight)>,
+) {
+    // Directional lights always make the cut, the rest by distance to the eye
+    let mut lights: Vec<(f32, GpuLight)> = Vec::new();
+    for (entity, global, light) in directional.iter().filter(|(_, _, l)| l.intensity > 0.0) {
+        let shadowed = cascades.light == Some(entity) && cascades.count > 0;
+        lights.push((
+            0.0,
+            GpuLight {
+                position: light_position(global).extend(1.0).to_array(),
+                direction: light_direction(global).extend(0.0).to_array(),
+                color: light.color.extend(light.intensity).to_array(),
+                params: [KIND_DIRECTIONAL, 0.0, 0.0, 0.0],
+                shadow_rect: [0.0, 0.0, 0.0, shadowed as u32 as f32],
+                ..Default::default()
+            },
+        ));
+    }
+    for (global, light) in point.iter().filter(|(_, l)| l.intensity > 0.0) {
+        let position = light_position(global);
+        lights.push((
+            position.distance(camera.eye),
+            GpuLight {
+                position: position.extend(1.0).to_array(),
+                direction: [0.0; 4],
+                color: light.color.extend(light.intensity).to_array(),
+                params: [KIND_POINT, light.range, 0.0, 0.0],
+                ..Default::default()
+            },
+        ));
+    }
+    for (entity, global, light) in spot.iter().filter(|(_, _, l)| l.intensity > 0.0) {
+        let position = light_position(global);
+        let shadow = atlas.slots.get(&entity);
+        lights.push((
+            position.distance(camera.eye),
+            GpuLight {
+                position: position.extend(1.0).to_array(),
+                direction: light_direction(global).extend(0.0).to_array(),
+                color: light.color.extend(light.intensity).to_array(),
+                params: [
+                    KIND_SPOT,
+                    light.range,
+                    light.inner_angle.cos(),
+                    light.outer_angle.cos(),
+                ],
+                shadow_view_proj: shadow
+                    .map_or(Mat4::IDENTITY, |slot| slot.view_proj)
+                    .to_cols_array_2d(),
+                shadow_rect: shadow.map_or([0.0; 4], |slot| slot.atlas_transform().to_array()),
+            },
+        ));
+    }
+
+    let active = lights.len();
+    if active > MAX_LIGHTS {
+        lights.sort_by(|a, b| a.0.total_cmp(&b.0));
+        lights.truncate(MAX_LIGHTS);
+    }
+    let stats = LightStats {
+        active,
+        gathered: lights.len(),
+        dropped: active - lights.len(),
+    };
+    if stats.dropped > 0 && stats.dropped != light_buffer.stats.dropped {
+        warn!(
+            "{} active lights exceed the limit of {}, dropping the {} furthest",
+            active, MAX_LIGHTS, stats.dropped
+        );
+    }
+
+    let lights: Vec<GpuLight> = lights.into_iter().map(|(_, light)| light).collect();
+    light_buffer.write(&gpu, &lights);
+    light_buffer.stats = stats;
+}
+
+#[derive(Resource)]
+pub struct LightGizmos {
+    pub enabled: bool,
+}
+
+pub fn light_gizmo_system(
+    gizmos: Res<LightGizmos>,
+    mut debug: ResMut<DebugDraw>,
+    directional: Query<(&GlobalTransform, &DirectionalLight)>,
+    point: Query<(&GlobalTransform, &PointLight)>,
+    spot: Query<(&GlobalTransform, &SpotLight)>,
+) {
+    if !gizmos.enabled {
+        return;
+    }
+    let color = |color: Vec3| color.extend(1.0);
+    for (global, light) in directional.iter() {
+        let position = light_position(global);
+        debug.wire_sphere(position, 0.3, color(light.color));
+        debug.arrow(
+            position,
+            position + light_direction(global) * 3.0,
+            color(light.color),
+        );
+    }
+    for (global, light) in point.iter() {
+        let position = light_position(global);
+        debug.wire_sphere(position, 0.25, color(light.color));
+        debug.circle(position, Vec3::Y, light.range, light.color.extend(0.3));
+    }
+    for (global, light) in spot.iter() {
+        let position = light_position(global);
+        debug.wire_sphere(position, 0.25, color(light.color));
+        debug.cone(
+            position,
+            light_direction(global),
+            light.range,
+            light.outer_angle,
+            light.color.extend(0.5),
+        );
+    }
+}
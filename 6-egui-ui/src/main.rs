@@ -1,51 +1,145 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use assets::setup_assets;
 use bevy_ecs::{
     component::Component,
-    event::{Event, EventReader, Events},
+    event::{EventReader, Events},
     observer::{Observer, Trigger, TriggerEvent},
-    schedule::Schedule,
-    system::{Res, ResMut, Resource, RunSystemOnce},
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Commands, Res, ResMut, Resource, RunSystemOnce},
     world::World,
 };
+use camera_path::setup_camera_path;
+use capabilities::setup_capabilities;
+#[cfg(feature = "capture")]
+use capture::setup_capture;
+use crash::{setup_crash_reporter, CrashReporter};
 use debouncer::Debouncer;
-use gpu::{setup_gpu, GpuContext};
+use diagnostics::setup_diagnostics;
+use display::setup_display;
+use editor::setup_editor;
+use external_editor::setup_external_editor;
+use gpu::{setup_gpu, shutdown_gpu, GpuContext};
+use i18n::setup_i18n;
+use incremental::setup_incremental;
+use input::setup_input;
+use jobs::setup_jobs;
+use layout::setup_layouts;
+use lights::setup_lights;
 use pipeline::{
+    annotations::setup_annotations,
+    ao::{ao_resize_system, setup_ambient_occlusion},
+    arena::setup_frame_arena,
+    binding_benchmark::setup_binding_benchmark,
+    cascades::setup_cascades,
+    checkerboard::setup_checkerboard,
+    counters::setup_gpu_counters,
+    debug_draw::setup_debug_draw,
+    debug_view::setup_debug_views,
     depth::{setup_depth, DepthTexture},
+    depth_precision::setup_depth_precision,
     diffuse::setup_diffuse,
-    present::{setup_frame_buffer, setup_present, FrameBuffer},
+    dof::{coc_resize_system, dof_bind_group_system, setup_depth_of_field},
+    environment::setup_environment,
+    filtering::setup_filtering_demo,
+    geometry_debug::setup_geometry_debug,
+    god_rays::setup_god_rays,
+    grading::setup_color_grading,
+    histogram::setup_histogram,
+    hud::setup_hud,
+    inspector::setup_texture_inspector,
+    layers::setup_layer_demo,
+    life::setup_life,
+    loading::setup_loading_screen,
+    marching_cubes::setup_marching_cubes,
+    mesh::setup_mesh,
+    particles::setup_particles,
+    paths::setup_paths,
+    plot::setup_plot,
+    post::setup_post_effects,
+    present::{setup_frame_buffer, setup_present, FrameBuffer, PresentSettings},
+    procedural::setup_procedural,
+    quality::setup_quality,
+    reduction::setup_reduction,
     render::setup_rendering,
+    scaled::{scaled_depth_resize_system, setup_scaled_depth},
+    shadow::setup_shadows,
+    ssr::{setup_reflections, ssr_resize_system},
+    stereo::setup_stereo,
+    subgroups::setup_subgroup_demo,
+    text::setup_text,
     ui::{setup_ui, EguiRenderer, EguiState},
+    validation::setup_validation,
+    velocity::{setup_velocity, velocity_resize_system},
+    visibility::setup_visibility,
+    volume::setup_volume,
     GPUPipeline, GPUPipelineBuilder,
 };
+use playground_app::{App, TextInputEvent, WindowTriggerEvent};
 use pollster::FutureExt;
+use profiler::{setup_profiler, SystemTimings, TraceCapture};
+use raycast::setup_raycast;
+use rng::setup_rng;
+use sampler::setup_samplers;
+use scene::setup_scene;
+use scene_file::setup_scene_file;
+use settings::setup_renderer_settings;
+use shader::setup_shaders;
+use shader_log::setup_shader_log;
 use std::{sync::Arc, time::Duration};
 use time::{setup_time, TimeContext};
+use timeline::setup_timeline;
 use tracing::info;
 use tracing_subscriber::{layer::SubscriberExt, EnvFilter};
 use tracing_tracy::client::{frame_name, ProfiledAllocator};
+use ui_layout::setup_ui_layout;
 use uniform::{setup_uniforms, Uniforms};
 use vertex::{setup_vertex_buffers, DepthVertex, Vertex, DEPTH_VERTICES, VERTICES};
 use wgpu::{
     util::DeviceExt, Adapter, Device, Instance, Queue, RenderPipeline, Surface, SurfaceCapabilities,
 };
-use winit::{
-    application::ApplicationHandler,
-    dpi::{LogicalSize, PhysicalSize, Size},
-    event::WindowEvent,
-    event_loop::{ActiveEventLoop, EventLoop},
-    window::{Window, WindowId},
-};
+use winit::{dpi::PhysicalSize, event::WindowEvent, window::Window};
 
 #[global_allocator]
 static GLOBAL: ProfiledAllocator<std::alloc::System> =
     ProfiledAllocator::new(std::alloc::System, 100);
 
+mod assets;
+mod baked;
+mod camera_path;
+mod capabilities;
+#[cfg(feature = "capture")]
+mod capture;
+mod crash;
 mod debouncer;
+mod diagnostics;
+mod display;
+mod editor;
+mod external_editor;
 mod gpu;
+mod i18n;
+mod incremental;
+mod input;
+mod jobs;
+mod layout;
+mod lights;
+mod noise;
 mod pass;
 mod pipeline;
+mod profiler;
+mod raycast;
+mod rng;
+mod sampler;
+mod scene;
+mod scene_file;
+mod settings;
+mod shader;
+mod shader_log;
+#[cfg(test)]
+mod shader_test;
 mod texture;
 mod time;
+mod timeline;
+mod ui_layout;
 mod uniform;
 mod vertex;
 
@@ -63,143 +157,171 @@ impl Default for ResizeState {
     }
 }
 
-#[derive(Event)]
-pub struct WindowTriggerEvent {
-    pub event: WindowEvent,
-}
-
 fn window_event_system(
     mut resize_state: ResMut<ResizeState>,
     gpu: ResMut<GpuContext>,
     mut depth_texture: ResMut<DepthTexture>,
     mut uniforms: ResMut<Uniforms>,
     mut frame_buffer: ResMut<FrameBuffer>,
+    present: Res<PresentSettings>,
     time: Res<TimeContext>,
 ) {
     // Resize event handling
     resize_state.debouncer.tick(time.delta);
     if let Some(size) = resize_state.debouncer.get() {
+        // Minimized, keep the buffers for when the window comes back
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
         info!("Resize event: {:?}", size);
+        let (width, height) = present.internal_size(size.width, size.height);
         frame_buffer
             .texture
-            .resize(&gpu.device, &gpu.queue, size.width, size.height);
-        depth_texture.resize(&gpu.device, size.width, size.height);
-        let resolution = [size.width as f32, size.height as f32];
+            .resize(&gpu.device, &gpu.queue, width, height);
+        depth_texture.resize(&gpu.device, width, height);
+        let resolution = [width as f32, height as f32];
         uniforms.update_resolution(&gpu, resolution);
     }
 }
 
-// Application handling
-struct Application {
-    world: World,
-    schedule: Schedule,
-}
-
-impl Application {
-    pub fn new() -> Self {
-        let world = World::default();
-
-        Self {
-            world,
-            schedule: Schedule::default(),
-        }
-    }
-}
-
-impl ApplicationHandler for Application {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let window = event_loop
-            .create_window(
-                Window::default_attributes()
-                    .with_title("WGPU Engine")
-                    .with_inner_size(Size::Logical(LogicalSize::new(800.0, 600.0)))
-                    .with_min_inner_size(Size::Logical(LogicalSize::new(400.0, 300.0))),
-            )
-            .expect("Failed to create window");
+// =============================== SETUP ===============================
+fn setup(
+    world: &mut World,
+    schedule: &mut Schedule,
+    window: Arc<Window>,
+    trace_capture: TraceCapture,
+    system_timings: SystemTimings,
+    crash_reporter: CrashReporter,
+) -> Result<()> {
+    world.insert_resource(trace_capture);
+    world.insert_resource(system_timings);
+    world.insert_resource(crash_reporter);
 
-        setup_time(&mut self.world, &mut self.schedule).expect("Failed to setup time");
-        setup_gpu(&mut self.world, &mut self.schedule, window).expect("Failed to setup GPU");
-        setup_uniforms(&mut self.world, &mut self.schedule).expect("Failed to setup uniforms");
-        setup_frame_buffer(&mut self.world, &mut self.schedule)
-            .expect("Failed to setup frame buffer");
-        setup_diffuse(&mut self.world, &mut self.schedule)
-            .expect("Failed to setup diffuse pipeline");
-        setup_depth(&mut self.world, &mut self.schedule).expect("Failed to setup depth pipeline");
-        setup_vertex_buffers(&mut self.world, &mut self.schedule)
-            .expect("Failed to setup vertex buffers");
-        setup_present(&mut self.world, &mut self.schedule)
-            .expect("Failed to setup present pipeline");
-        setup_ui(&mut self.world, &mut self.schedule).expect("Failed to setup UI pipeline");
-        setup_rendering(&mut self.world, &mut self.schedule).expect("Failed to setup rendering");
+    setup_time(world, schedule).context("Failed to setup time")?;
+    setup_rng(world, schedule).context("Failed to setup random numbers")?;
+    setup_i18n(world, schedule).context("Failed to setup localization")?;
+    setup_jobs(world, schedule).context("Failed to setup job system")?;
+    setup_incremental(world, schedule).context("Failed to setup incremental work")?;
+    setup_shaders(world, schedule).context("Failed to setup shaders")?;
+    setup_shader_log(world, schedule).context("Failed to setup shader log")?;
+    setup_external_editor(world, schedule).context("Failed to setup external editor")?;
+    setup_gpu(world, schedule, window).context("Failed to setup GPU")?;
+    setup_renderer_settings(world, schedule).context("Failed to setup renderer settings")?;
+    setup_crash_reporter(world, schedule).context("Failed to setup crash reporter")?;
+    setup_uniforms(world, schedule).context("Failed to setup uniforms")?;
+    setup_frame_arena(world, schedule).context("Failed to setup frame arena")?;
+    setup_frame_buffer(world, schedule).context("Failed to setup frame buffer")?;
+    setup_velocity(world, schedule).context("Failed to setup velocity buffer")?;
+    setup_layouts(world, schedule).context("Failed to setup layout cache")?;
+    setup_samplers(world, schedule).context("Failed to setup samplers")?;
+    setup_assets(world, schedule).context("Failed to setup asset server")?;
+    setup_diffuse(world, schedule).context("Failed to setup diffuse pipeline")?;
+    #[cfg(feature = "capture")]
+    setup_capture(world, schedule).context("Failed to setup desktop capture")?;
+    setup_depth(world, schedule).context("Failed to setup depth pipeline")?;
+    setup_vertex_buffers(world, schedule).context("Failed to setup vertex buffers")?;
+    setup_depth_of_field(world, schedule).context("Failed to setup depth of field")?;
+    setup_color_grading(world, schedule).context("Failed to setup color grading")?;
+    setup_post_effects(world, schedule).context("Failed to setup post effects")?;
+    setup_present(world, schedule).context("Failed to setup present pipeline")?;
+    setup_ui(world, schedule).context("Failed to setup UI pipeline")?;
+    setup_ui_layout(world, schedule).context("Failed to setup UI layout")?;
+    setup_display(world, schedule).context("Failed to setup display")?;
+    setup_profiler(world, schedule).context("Failed to setup profiler")?;
+    setup_gpu_counters(world, schedule).context("Failed to setup GPU counters")?;
+    setup_texture_inspector(world, schedule).context("Failed to setup texture inspector")?;
+    setup_procedural(world, schedule).context("Failed to setup procedural compute pipeline")?;
+    setup_life(world, schedule).context("Failed to setup game of life")?;
+    setup_histogram(world, schedule).context("Failed to setup histogram")?;
+    setup_layer_demo(world, schedule).context("Failed to setup texture array demo")?;
+    setup_environment(world, schedule).context("Failed to setup environment map")?;
+    setup_scaled_depth(world, schedule).context("Failed to setup scaled depth")?;
+    setup_reflections(world, schedule).context("Failed to setup reflections")?;
+    setup_ambient_occlusion(world, schedule).context("Failed to setup ambient occlusion")?;
+    setup_scene(world, schedule).context("Failed to setup scene")?;
+    setup_shadows(world, schedule).context("Failed to setup shadows")?;
+    setup_cascades(world, schedule).context("Failed to setup cascades")?;
+    setup_lights(world, schedule).context("Failed to setup lights")?;
+    setup_mesh(world, schedule).context("Failed to setup mesh pipeline")?;
+    setup_stereo(world, schedule).context("Failed to setup stereo rendering")?;
+    setup_checkerboard(world, schedule).context("Failed to setup checkerboard rendering")?;
+    setup_filtering_demo(world, schedule).context("Failed to setup texture filtering demo")?;
+    setup_depth_precision(world, schedule).context("Failed to setup depth precision tool")?;
+    setup_hud(world, schedule).context("Failed to setup HUD quads")?;
+    setup_text(world, schedule).context("Failed to setup text rendering")?;
+    setup_paths(world, schedule).context("Failed to setup vector paths")?;
+    setup_plot(world, schedule).context("Failed to setup plots")?;
+    setup_volume(world, schedule).context("Failed to setup volume")?;
+    setup_god_rays(world, schedule).context("Failed to setup god rays")?;
+    setup_particles(world, schedule).context("Failed to setup particles")?;
+    setup_marching_cubes(world, schedule).context("Failed to setup marching cubes")?;
+    setup_visibility(world, schedule).context("Failed to setup visibility buffer")?;
+    setup_reduction(world, schedule).context("Failed to setup reduction benchmark")?;
+    setup_binding_benchmark(world, schedule).context("Failed to setup binding benchmark")?;
+    setup_subgroup_demo(world, schedule).context("Failed to setup subgroup demo")?;
+    setup_capabilities(world, schedule).context("Failed to probe format capabilities")?;
+    setup_diagnostics(world, schedule).context("Failed to setup diagnostics")?;
+    setup_debug_draw(world, schedule).context("Failed to setup debug draw")?;
+    setup_geometry_debug(world, schedule).context("Failed to setup geometry debug views")?;
+    setup_annotations(world, schedule).context("Failed to setup annotations")?;
+    setup_debug_views(world, schedule).context("Failed to setup debug views")?;
+    setup_validation(world, schedule).context("Failed to setup frame validation")?;
+    setup_raycast(world, schedule).context("Failed to setup raycast")?;
+    setup_quality(world, schedule).context("Failed to setup quality presets")?;
+    setup_editor(world, schedule).context("Failed to setup editor")?;
+    setup_scene_file(world, schedule).context("Failed to setup scene reloading")?;
+    setup_loading_screen(world, schedule).context("Failed to setup loading screen")?;
+    setup_timeline(world, schedule).context("Failed to setup timeline")?;
+    setup_camera_path(world, schedule).context("Failed to setup camera path")?;
+    setup_input(world, schedule).context("Failed to setup input")?;
+    setup_rendering(world, schedule).context("Failed to setup rendering")?;
+    // Every startup pipeline exists by now
+    world.resource::<GpuContext>().save_pipeline_cache();
 
-        self.world.insert_resource(ResizeState::default());
-        self.world.add_observer(
-            |trigger: Trigger<WindowTriggerEvent>,
-             mut resize_state: ResMut<ResizeState>,
-             mut ui: ResMut<EguiState>,
-             mut gpu: ResMut<GpuContext>| {
-                let event = &trigger.event().event;
-
-                // Resize event handling
-                match event {
-                    WindowEvent::Resized(size) => {
-                        let size = PhysicalSize::new(size.width, size.height);
-                        gpu.resize(&size);
-                        resize_state.debouncer.push(size);
-                    }
-                    _ => {}
-                }
-
-                // UI event handling
-                let _ui_response = ui.renderer.handle_input(&gpu.window, event);
-            },
-        );
-        self.schedule.add_systems(window_event_system);
-        self.world.flush();
-    }
-
-    fn window_event(
-        &mut self,
-        event_loop: &ActiveEventLoop,
-        window_id: WindowId,
-        event: WindowEvent,
-    ) {
-        let current_window_id = {
-            let gpu = self
-                .world
-                .get_resource::<GpuContext>()
-                .expect("GpuContext not found");
-            gpu.window.id()
-        };
-
-        if current_window_id == window_id {
-            self.world.trigger(WindowTriggerEvent {
-                event: event.clone(),
-            });
+    world.insert_resource(ResizeState::default());
+    world.add_observer(
+        |trigger: Trigger<WindowTriggerEvent>,
+         mut resize_state: ResMut<ResizeState>,
+         mut ui: ResMut<EguiState>,
+         mut gpu: ResMut<GpuContext>,
+         mut commands: Commands| {
+            let event = &trigger.event().event;
 
+            // Resize event handling
             match event {
-                WindowEvent::CloseRequested => event_loop.exit(),
-                WindowEvent::RedrawRequested => {
-                    self.schedule.run(&mut self.world);
+                WindowEvent::Resized(size) => {
+                    let size = PhysicalSize::new(size.width, size.height);
+                    gpu.resize(&size);
+                    resize_state.debouncer.push(size);
+                }
+                // Capabilities can differ per monitor, e.g. HDR or DPI
+                WindowEvent::Moved(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                    if let Some(changed) = gpu.refresh_surface(ui.renderer.output_format()) {
+                        commands.trigger(changed);
+                    }
                 }
                 _ => {}
             }
-        }
-    }
 
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        let gpu = self
-            .world
-            .get_resource::<GpuContext>()
-            .expect("GpuContext not found");
-        gpu.window.request_redraw();
-    }
-}
+            // UI event handling
+            let _ui_response = ui.renderer.handle_input(&gpu.window, event);
+        },
+    );
+    world.add_observer(
+        |trigger: Trigger<TextInputEvent>, mut ui: ResMut<EguiState>| {
+            ui.renderer.handle_text_input(trigger.event());
+        },
+    );
+    schedule.add_systems(
+        window_event_system
+            .before(velocity_resize_system)
+            .before(coc_resize_system)
+            .before(dof_bind_group_system)
+            .before(ssr_resize_system)
+            .before(scaled_depth_resize_system)
+            .before(ao_resize_system),
+    );
 
-pub async fn run() -> Result<()> {
-    let event_loop = EventLoop::new()?;
-    let mut app = Application::new();
-    event_loop.run_app(&mut app)?;
     Ok(())
 }
 
@@ -211,15 +333,33 @@ fn main() -> Result<()> {
         .add_directive("debug".parse().unwrap());
 
     // Initialize the subscriber with the filter
+    let trace_capture = TraceCapture::default();
+    let system_timings = SystemTimings::default();
+    let crash_reporter = CrashReporter::default();
     tracing::subscriber::set_global_default(
         tracing_subscriber::registry()
             .with(tracing_tracy::TracyLayer::default())
+            .with(trace_capture.layer())
+            .with(system_timings.layer())
+            .with(crash_reporter.layer())
             .with(env_filter)
             .with(tracing_subscriber::fmt::layer()),
     )
     .expect("setup tracing");
     better_panic::install();
+    crash_reporter.install_hook();
 
-    pollster::block_on(run())?;
+    App::new("WGPU Engine")
+        .on_shutdown(shutdown_gpu)
+        .run(move |world, schedule, window| {
+            setup(
+                world,
+                schedule,
+                window,
+                trace_capture,
+                system_timings,
+                crash_reporter,
+            )
+        })?;
     Ok(())
 }
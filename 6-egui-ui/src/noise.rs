@@ -0,0 +1,261 @@
+//! Lattice noise on the CPU. `shaders/noise.wgsl` has the same functions with
+//! a `noise_` prefix, written operation for operation so textures baked here
+//! line up with noise evaluated in shaders.
+//!
+//! `period` makes the lattice wrap every `period` cells so the noise tiles, 0
+//! disables wrapping.
+
+// Kept complete to mirror the WGSL side, not every function has a CPU caller
+#![allow(dead_code)]
+
+use glam::Vec3;
+
+/// Integer hash of a lattice point, stable across CPU and GPU.
+pub fn hash(x: u32, y: u32, z: u32, seed: u32) -> u32 {
+    let mut h =
+        x.wrapping_mul(0x8da6b343) ^ y.wrapping_mul(0xd8163841) ^ z.wrapping_mul(0xcb1ab31f) ^ seed;
+    h = (h ^ (h >> 13)).wrapping_mul(0x5bd1e995);
+    h ^ (h >> 15)
+}
+
+fn wrap(cell: f32, offset: i32, period: u32) -> u32 {
+    let c = cell as i32 + offset;
+    if period == 0 {
+        c as u32
+    } else {
+        c.rem_euclid(period as i32) as u32
+    }
+}
+
+fn lattice(cell: Vec3, offset: [i32; 3], period: u32, seed: u32) -> u32 {
+    hash(
+        wrap(cell.x, offset[0], period),
+        wrap(cell.y, offset[1], period),
+        wrap(cell.z, offset[2], period),
+        seed,
+    )
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn trilinear(corners: [f32; 8], t: Vec3) -> f32 {
+    let x00 = lerp(corners[0], corners[1], t.x);
+    let x10 = lerp(corners[2], corners[3], t.x);
+    let x01 = lerp(corners[4], corners[5], t.x);
+    let x11 = lerp(corners[6], corners[7], t.x);
+    lerp(lerp(x00, x10, t.y), lerp(x01, x11, t.y), t.z)
+}
+
+/// Corners in x, then y, then z order, matching [`trilinear`].
+const CORNERS: [[i32; 3]; 8] = [
+    [0, 0, 0],
+    [1, 0, 0],
+    [0, 1, 0],
+    [1, 1, 0],
+    [0, 0, 1],
+    [1, 0, 1],
+    [0, 1, 1],
+    [1, 1, 1],
+];
+
+// =============================== VALUE ===============================
+/// Smoothly interpolated random values in [0, 1].
+pub fn value(p: Vec3, period: u32, seed: u32) -> f32 {
+    let cell = p.floor();
+    let f = p - cell;
+    let t = f * f * (Vec3::splat(3.0) - 2.0 * f);
+    let corners =
+        CORNERS.map(|offset| (lattice(cell, offset, period, seed) & 0xffff) as f32 / 65535.0);
+    trilinear(corners, t)
+}
+
+// =============================== PERLIN ===============================
+/// One of the 12 cube edge directions, dotted with `d`.
+fn gradient(h: u32, d: Vec3) -> f32 {
+    let h = h & 15;
+    let u = if h < 8 { d.x } else { d.y };
+    let v = if h < 4 {
+        d.y
+    } else if h == 12 || h == 14 {
+        d.x
+    } else {
+        d.z
+    };
+    let u = if h & 1 == 0 { u } else { -u };
+    let v = if h & 2 == 0 { v } else { -v };
+    u + v
+}
+
+/// Improved Perlin noise, roughly in [-1, 1].
+pub fn perlin(p: Vec3, period: u32, seed: u32) -> f32 {
+    let cell = p.floor();
+    let f = p - cell;
+    let t = f * f * f * (f * (f * 6.0 - Vec3::splat(15.0)) + Vec3::splat(10.0));
+    let corners = CORNERS.map(|offset| {
+        let d = f - Vec3::new(offset[0] as f32, offset[1] as f32, offset[2] as f32);
+        gradient(lattice(cell, offset, period, seed), d)
+    });
+    trilinear(corners, t)
+}
+
+// =============================== SIMPLEX ===============================
+const SKEW: f32 = 1.0 / 3.0;
+const UNSKEW: f32 = 1.0 / 6.0;
+
+/// 3D simplex noise, roughly in [-1, 1]. Cheaper than Perlin at higher
+/// octave counts and without its axis aligned artifacts, but doesn't tile.
+pub fn simplex(p: Vec3, seed: u32) -> f32 {
+    let s = (p.x + p.y + p.z) * SKEW;
+    let cell = (p + Vec3::splat(s)).floor();
+    let t = (cell.x + cell.y + cell.z) * UNSKEW;
+    let x0 = p - (cell - Vec3::splat(t));
+
+    // Which of the six simplices of the skewed cube contains the point
+    let (i1, i2) = if x0.x >= x0.y {
+        if x0.y >= x0.z {
+            ([1, 0, 0], [1, 1, 0])
+        } else if x0.x >= x0.z {
+            ([1, 0, 0], [1, 0, 1])
+        } else {
+            ([0, 0, 1], [1, 0, 1])
+        }
+    } else if x0.y < x0.z {
+        ([0, 0, 1], [0, 1, 1])
+    } else if x0.x < x0.z {
+        ([0, 1, 0], [0, 1, 1])
+    } else {
+        ([0, 1, 0], [1, 1, 0])
+    };
+
+    let to_vec = |o: [i32; 3]| Vec3::new(o[0] as f32, o[1] as f32, o[2] as f32);
+    let corner = |offset: [i32; 3], x: Vec3| {
+        let falloff = 0.6 - x.dot(x);
+        if falloff < 0.0 {
+            return 0.0;
+        }
+        let falloff = falloff * falloff;
+        falloff * falloff * gradient(lattice(cell, offset, 0, seed), x)
+    };
+    let x1 = x0 - to_vec(i1) + Vec3::splat(UNSKEW);
+    let x2 = x0 - to_vec(i2) + Vec3::splat(2.0 * UNSKEW);
+    let x3 = x0 - Vec3::ONE + Vec3::splat(3.0 * UNSKEW);
+    32.0 * (corner([0, 0, 0], x0) + corner(i1, x1) + corner(i2, x2) + corner([1, 1, 1], x3))
+}
+
+// =============================== FBM ===============================
+/// Octaves of [`value`] noise at doubling frequency and halving amplitude,
+/// in [0, 1]. The period doubles with the frequency, so it still tiles.
+pub fn fbm_value(p: Vec3, octaves: u32, period: u32, seed: u32) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 0.5;
+    let mut total = 0.0;
+    let mut frequency = 1.0;
+    let mut period = period;
+    for _ in 0..octaves {
+        sum += value(p * frequency, period, seed) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+        period *= 2;
+    }
+    sum / total
+}
+
+/// Octaves of [`simplex`] noise, roughly in [-1, 1].
+pub fn fbm_simplex(p: Vec3, octaves: u32, seed: u32) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 0.5;
+    let mut total = 0.0;
+    let mut frequency = 1.0;
+    for _ in 0..octaves {
+        sum += simplex(p * frequency, seed) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / total
+}
+
+// =============================== TESTS ===============================
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::{
+        rng::Rng,
+        shader_test::{gpu_or_skip, ShaderTest},
+    };
+
+    const TOLERANCE: f32 = 1e-4;
+
+    fn points() -> Vec<[f32; 4]> {
+        let mut rng = Rng::new(1);
+        let mut coordinate = || (rng.next_u32() as f64 / u32::MAX as f64 * 64.0 - 32.0) as f32;
+        (0..1024)
+            .map(|_| [coordinate(), coordinate(), coordinate(), 0.0])
+            .collect()
+    }
+
+    /// Runs `expression` on the GPU and `cpu` on the CPU for the same points.
+    fn assert_agree(expression: &str, cpu: impl Fn(Vec3) -> f32) -> Result<()> {
+        let Some(gpu) = gpu_or_skip() else {
+            return Ok(());
+        };
+        let test = ShaderTest::new("noise.wgsl")?.whole();
+        let points = points();
+        let results: Vec<f32> = test.run(&gpu, expression, &points)?;
+        for (point, gpu_value) in points.iter().zip(&results) {
+            let p = Vec3::new(point[0], point[1], point[2]);
+            let cpu_value = cpu(p);
+            assert!(
+                (cpu_value - gpu_value).abs() < TOLERANCE,
+                "{} at {}: CPU {}, GPU {}",
+                expression,
+                p,
+                cpu_value,
+                gpu_value
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn value_matches_gpu() -> Result<()> {
+        assert_agree("noise_value(input.xyz, 0u, 7u)", |p| value(p, 0, 7))?;
+        assert_agree("noise_value(input.xyz, 8u, 7u)", |p| value(p, 8, 7))
+    }
+
+    #[test]
+    fn perlin_matches_gpu() -> Result<()> {
+        assert_agree("noise_perlin(input.xyz, 0u, 7u)", |p| perlin(p, 0, 7))?;
+        assert_agree("noise_perlin(input.xyz, 8u, 7u)", |p| perlin(p, 8, 7))
+    }
+
+    #[test]
+    fn simplex_matches_gpu() -> Result<()> {
+        assert_agree("noise_simplex(input.xyz, 7u)", |p| simplex(p, 7))
+    }
+
+    #[test]
+    fn fbm_matches_gpu() -> Result<()> {
+        assert_agree("noise_fbm_value(input.xyz, 4u, 8u, 7u)", |p| {
+            fbm_value(p, 4, 8, 7)
+        })?;
+        assert_agree("noise_fbm_simplex(input.xyz, 4u, 7u)", |p| {
+            fbm_simplex(p, 4, 7)
+        })
+    }
+
+    #[test]
+    fn periodic_noise_tiles() {
+        for point in points() {
+            let p = Vec3::new(point[0], point[1], point[2]);
+            let shifted = p + Vec3::new(8.0, -16.0, 24.0);
+            assert!((value(p, 8, 3) - value(shifted, 8, 3)).abs() < TOLERANCE);
+            assert!((perlin(p, 8, 3) - perlin(shifted, 8, 3)).abs() < TOLERANCE);
+        }
+    }
+}
@@ -1,10 +1,19 @@
-use anyhow::{Context, Result};
+#[derive(Debug, thiserror::Error)]
+pub enum PassError {
+    #[error("No color or depth attachment provided")]
+    NoAttachments,
+}
 
 pub struct RenderPassBuilder<'a> {
     encoder: &'a mut wgpu::CommandEncoder,
     label: Option<&'a str>,
     color_view: Option<&'a wgpu::TextureView>,
+    /// Additional targets that are cleared even when the pass loads.
+    cleared_views: Vec<(&'a wgpu::TextureView, wgpu::Color)>,
     depth_view: Option<(&'a wgpu::TextureView, f32)>,
+    timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'a>>,
+    occlusion_query_set: Option<&'a wgpu::QuerySet>,
+    load: bool,
 }
 
 impl<'a> RenderPassBuilder<'a> {
@@ -13,7 +22,11 @@ impl<'a> RenderPassBuilder<'a> {
             encoder,
             label: None,
             color_view: None,
+            cleared_views: Vec::new(),
             depth_view: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            load: false,
         }
     }
 
@@ -27,39 +40,101 @@ impl<'a> RenderPassBuilder<'a> {
         self
     }
 
+    /// Adds another color target after the main one, cleared to `clear` at
+    /// the start of the pass, e.g. a velocity buffer next to the frame buffer.
+    pub fn with_cleared_color_view(
+        mut self,
+        view: &'a wgpu::TextureView,
+        clear: wgpu::Color,
+    ) -> Self {
+        self.cleared_views.push((view, clear));
+        self
+    }
+
     pub fn with_depth(mut self, view: &'a wgpu::TextureView, clear_value: f32) -> Self {
         self.depth_view = Some((view, clear_value));
         self
     }
 
-    pub fn build(self) -> Result<wgpu::RenderPass<'a>> {
-        let color_view = self.color_view.context("No color attachment provided")?;
+    /// Writes timestamps `first` and `first + 1` of `query_set` at the start
+    /// and the end of the pass.
+    pub fn with_timestamps(mut self, query_set: &'a wgpu::QuerySet, first: u32) -> Self {
+        self.timestamp_writes = Some(wgpu::RenderPassTimestampWrites {
+            query_set,
+            beginning_of_pass_write_index: Some(first),
+            end_of_pass_write_index: Some(first + 1),
+        });
+        self
+    }
+
+    /// Query set for `begin_occlusion_query` calls inside the pass.
+    pub fn with_occlusion_queries(mut self, query_set: &'a wgpu::QuerySet) -> Self {
+        self.occlusion_query_set = Some(query_set);
+        self
+    }
+
+    /// Keeps the previous contents of the attachments instead of clearing them.
+    pub fn load(mut self) -> Self {
+        self.load = true;
+        self
+    }
+
+    pub fn build(self) -> Result<wgpu::RenderPass<'a>, PassError> {
+        if self.color_view.is_none() && self.cleared_views.is_empty() && self.depth_view.is_none() {
+            return Err(PassError::NoAttachments);
+        }
+        let load = self.load;
 
         let depth_stencil_attachment =
             self.depth_view.map(
                 |(view, clear_value)| wgpu::RenderPassDepthStencilAttachment {
                     view,
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(clear_value),
+                        load: if load {
+                            wgpu::LoadOp::Load
+                        } else {
+                            wgpu::LoadOp::Clear(clear_value)
+                        },
                         store: wgpu::StoreOp::Store,
                     }),
                     stencil_ops: None,
                 },
             );
 
+        let color_attachments: Vec<_> =
+            self.color_view
+                .map(|view| wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: if load {
+                            wgpu::LoadOp::Load
+                        } else {
+                            wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                        },
+                        store: wgpu::StoreOp::Store,
+                    },
+                })
+                .into_iter()
+                .chain(self.cleared_views.iter().map(|(view, clear)| {
+                    wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(*clear),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }
+                }))
+                .map(Some)
+                .collect();
+
         Ok(self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: self.label,
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: color_view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
+            color_attachments: &color_attachments,
             depth_stencil_attachment,
-            timestamp_writes: None,
-            occlusion_query_set: None,
+            timestamp_writes: self.timestamp_writes,
+            occlusion_query_set: self.occlusion_query_set,
         }))
     }
 }
@@ -0,0 +1,218 @@
+//! Immediate-mode annotations in world space, for seeing what an algorithm
+//! thinks while it runs: a label at a position, an arrow between two points.
+//! Anything queued on [`Annotations`] is shown for the current frame only.
+//!
+//! Labels are projected through the camera into the presented viewport and
+//! drawn as [`DebugText`] on top of everything, arrows go to [`DebugDraw`]
+//! and are depth tested against the scene like any other debug line.
+
+use anyhow::Result;
+use bevy_ecs::{
+    query::With,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Query, Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use crate::{
+    i18n::tr,
+    scene::{transform_propagation_system, Aabb, Camera, GlobalTransform, Name, Renderable},
+};
+
+use super::{
+    debug_draw::DebugDraw,
+    present::PresentViewport,
+    render::render_system,
+    text::{text_layout_system, DebugText, TextEffects, TextStyle},
+    ui::UiPanels,
+};
+
+/// Space between a label and the point it annotates, in pixels.
+const LABEL_OFFSET: Vec2 = Vec2::new(6.0, -6.0);
+
+pub fn setup_annotations(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    world.init_resource::<Annotations>();
+    world.insert_resource(AnnotationSettings::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(annotations_panel);
+
+    schedule.add_systems((
+        annotation_demo_system
+            .after(transform_propagation_system)
+            .before(annotation_system),
+        annotation_system
+            .before(text_layout_system)
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Labels every named renderable, with an arrow down to the top of its
+/// bounds.
+pub fn annotation_demo_system(
+    settings: Res<AnnotationSettings>,
+    mut annotations: ResMut<Annotations>,
+    entities: Query<(&Name, &GlobalTransform, &Aabb), With<Renderable>>,
+) {
+    if !settings.label_entities {
+        return;
+    }
+    for (name, global, bounds) in entities.iter() {
+        let bounds = bounds.transformed(&global.0);
+        let top = Vec3::new(bounds.center().x, bounds.max.y, bounds.center().z);
+        let label = top + Vec3::Y;
+        annotations.annotate(label, name.0.clone());
+        annotations.annotate_arrow(label, top, Vec4::new(1.0, 0.8, 0.2, 1.0));
+    }
+}
+
+/// Hands this frame's annotations to the text and line drawing, then clears
+/// them.
+pub fn annotation_system(
+    settings: Res<AnnotationSettings>,
+    (camera, viewport): (Res<Camera>, PresentViewport),
+    mut annotations: ResMut<Annotations>,
+    mut text: ResMut<DebugText>,
+    mut debug_draw: ResMut<DebugDraw>,
+) {
+    let labels = std::mem::take(&mut annotations.labels);
+    for arrow in std::mem::take(&mut annotations.arrows) {
+        debug_draw.arrow(arrow.from, arrow.to, arrow.color);
+    }
+
+    let view_proj = camera.view_projection();
+    let rect = viewport.rect();
+    for label in labels {
+        let Some(point) = project(view_proj, label.position, rect) else {
+            continue;
+        };
+        // Anchored at the bottom left, so the text sits above the point
+        text.styled(
+            point + LABEL_OFFSET - Vec2::new(0.0, settings.size),
+            settings.size,
+            label.color,
+            TextStyle::DistanceField(TextEffects::default()),
+            label.text,
+        );
+    }
+}
+
+/// Where `position` ends up in the window, given the viewport `rect` as
+/// `[x, y, width, height]` in pixels. `None` behind the camera or outside
+/// the viewport.
+fn project(view_proj: Mat4, position: Vec3, rect: [f32; 4]) -> Option<Vec2> {
+    let clip = view_proj * position.extend(1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let ndc = clip.truncate() / clip.w;
+    if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 || ndc.z > 1.0 {
+        return None;
+    }
+    let [x, y, width, height] = rect;
+    Some(Vec2::new(
+        x + (ndc.x + 1.0) * 0.5 * width,
+        y + (1.0 - ndc.y) * 0.5 * height,
+    ))
+}
+
+fn annotations_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<AnnotationSettings>().clone();
+
+    egui::Window::new(tr("Annotations"))
+        .id(egui::Id::new("Annotations"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.label_entities, tr("Label entities"))
+                .on_hover_text(tr("Names every mesh in the scene where it is"));
+            ui.add(egui::Slider::new(&mut settings.size, 8.0..=48.0).text(tr("Size")));
+        });
+
+    if settings != *world.resource::<AnnotationSettings>() {
+        world.insert_resource(settings);
+    }
+}
+
+// =============================== API ===============================
+pub struct Label {
+    pub position: Vec3,
+    pub text: String,
+    pub color: Vec4,
+}
+
+pub struct Arrow {
+    pub from: Vec3,
+    pub to: Vec3,
+    pub color: Vec4,
+}
+
+/// Frame-scoped annotations, queued by any system that runs before
+/// [`annotation_system`].
+#[derive(Resource, Default)]
+pub struct Annotations {
+    pub labels: Vec<Label>,
+    pub arrows: Vec<Arrow>,
+}
+impl Annotations {
+    pub fn annotate(&mut self, position: Vec3, text: impl Into<String>) {
+        self.annotate_colored(position, text, Vec4::ONE);
+    }
+
+    pub fn annotate_colored(&mut self, position: Vec3, text: impl Into<String>, color: Vec4) {
+        self.labels.push(Label {
+            position,
+            text: text.into(),
+            color,
+        });
+    }
+
+    pub fn annotate_arrow(&mut self, from: Vec3, to: Vec3, color: Vec4) {
+        self.arrows.push(Arrow { from, to, color });
+    }
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct AnnotationSettings {
+    pub label_entities: bool,
+    /// Label text size in pixels.
+    pub size: f32,
+}
+impl Default for AnnotationSettings {
+    fn default() -> Self {
+        Self {
+            label_entities: false,
+            size: 16.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_project_into_the_viewport() {
+        let camera = Camera {
+            eye: Vec3::new(0.0, 0.0, 10.0),
+            target: Vec3::ZERO,
+            aspect: 1.0,
+            ..Default::default()
+        };
+        let view_proj = camera.view_projection();
+        let rect = [100.0, 50.0, 400.0, 400.0];
+
+        // The target lands in the middle of the viewport, not of the window
+        let center = project(view_proj, Vec3::ZERO, rect).unwrap();
+        assert!((center - Vec2::new(300.0, 250.0)).length() < 1e-3);
+        // Up in the world is up on screen, where y grows downwards
+        let above = project(view_proj, Vec3::Y, rect).unwrap();
+        assert!(above.y < center.y);
+
+        assert_eq!(project(view_proj, Vec3::new(0.0, 0.0, 20.0), rect), None);
+        assert_eq!(project(view_proj, Vec3::new(100.0, 0.0, 0.0), rect), None);
+    }
+}
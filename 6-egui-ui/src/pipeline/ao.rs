@@ -0,0 +1,789 @@
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{Condition, IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::tr,
+    pass::RenderPassBuilder,
+    scene::Camera,
+    shader::{load_shader_source, parse_wgsl, preprocess},
+    texture::Texture,
+};
+
+use super::{
+    depth::DepthTexture,
+    graph::PassContext,
+    inspector::TextureRegistry,
+    present::{render_scale_system, FrameBuffer},
+    render::{render_system, submit_system},
+    scaled::{scaled_depth_resize_system, ResolutionScale, ScaledDepth},
+    ssr::{ssr_resize_system, SurfaceBuffer},
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+/// Visibility, 1 for unoccluded.
+pub const AO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+pub fn setup_ambient_occlusion(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let frame_buffer = world
+        .get_resource::<FrameBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("FrameBuffer resource not found"))?;
+    let depth = world
+        .get_resource::<DepthTexture>()
+        .ok_or_else(|| anyhow::anyhow!("DepthTexture resource not found"))?;
+    let surface = world
+        .get_resource::<SurfaceBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("SurfaceBuffer resource not found"))?;
+    let scaled = world
+        .get_resource::<ScaledDepth>()
+        .ok_or_else(|| anyhow::anyhow!("ScaledDepth resource not found"))?;
+
+    let settings = AoSettings::default();
+    let size = frame_buffer.texture.texture.size();
+    let (width, height) = settings.resolution.size(size.width, size.height);
+    let buffer = AoBuffer {
+        texture: Texture::render_target(&gpu.device, width, height, AO_FORMAT, "ao_buffer"),
+    };
+    let ao_depth = scaled.view(settings.resolution, depth);
+    let ao = AmbientOcclusion::new(gpu, &settings, depth, ao_depth, surface, &buffer)?;
+    let timer = AoTimer::new(gpu);
+
+    world.insert_resource(buffer);
+    world.insert_resource(ao);
+    world.insert_resource(timer);
+    world.insert_resource(settings);
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("ambient_occlusion", |world| {
+            world
+                .get_resource::<AoBuffer>()
+                .map(|buffer| &buffer.texture.texture)
+        });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(ao_panel);
+
+    schedule.add_systems((
+        ao_resize_system
+            .run_if(
+                resource_changed::<FrameBuffer>
+                    .or(resource_changed::<DepthTexture>)
+                    .or(resource_changed::<AoSettings>),
+            )
+            .after(render_scale_system)
+            .after(ssr_resize_system)
+            .after(scaled_depth_resize_system)
+            .before(render_system),
+        ao_params_system
+            .run_if(resource_changed::<AoSettings>.or(resource_changed::<Camera>))
+            .before(render_system),
+        ao_timing_system.after(submit_system),
+    ));
+
+    Ok(())
+}
+
+/// Follows the frame buffer, through the depth texture that matches it, at
+/// the configured resolution scale. Also picks up the depth textures and the
+/// surface buffer after they were recreated.
+pub fn ao_resize_system(
+    gpu: Res<GpuContext>,
+    depth: Res<DepthTexture>,
+    scaled: Res<ScaledDepth>,
+    surface: Res<SurfaceBuffer>,
+    settings: Res<AoSettings>,
+    mut buffer: ResMut<AoBuffer>,
+    mut ao: ResMut<AmbientOcclusion>,
+) {
+    let size = depth.texture.texture.size();
+    let (width, height) = settings.resolution.size(size.width, size.height);
+    let current = buffer.texture.texture.size();
+    if width != current.width || height != current.height {
+        buffer
+            .texture
+            .resize(&gpu.device, &gpu.queue, width, height);
+    }
+    let ao_depth = scaled.view(settings.resolution, &depth);
+    let (bind_group, composite_bind_group) =
+        ao.create_bind_groups(&gpu, &depth, ao_depth, &surface, &buffer);
+    ao.bind_group = bind_group;
+    ao.composite_bind_group = composite_bind_group;
+}
+
+pub fn ao_params_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    settings: Res<AoSettings>,
+    ao: Res<AmbientOcclusion>,
+) {
+    let params = AoParams::new(&settings, &camera);
+    gpu.queue
+        .write_buffer(&ao.params, 0, bytemuck::bytes_of(&params));
+}
+
+/// Maps the timestamps resolved by the last timed frame once the GPU is done
+/// with them, without ever waiting on it.
+pub fn ao_timing_system(mut timer: ResMut<AoTimer>) {
+    let timer = &mut *timer;
+    let Some(queries) = &timer.queries else {
+        return;
+    };
+    match timer.state {
+        TimerState::Idle => {}
+        TimerState::Resolved => {
+            let mapped = timer.mapped.clone();
+            queries
+                .readback_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    *mapped.lock().unwrap() = Some(result.is_ok());
+                });
+            timer.state = TimerState::Mapping;
+        }
+        TimerState::Mapping => {
+            let Some(ok) = timer.mapped.lock().unwrap().take() else {
+                return;
+            };
+            if ok {
+                let ticks: Vec<u64> = {
+                    let data = queries.readback_buffer.slice(..).get_mapped_range();
+                    bytemuck::cast_slice(&data).to_vec()
+                };
+                queries.readback_buffer.unmap();
+                for technique in AoTechnique::ALL {
+                    let coverage = timer.coverage[technique.index()];
+                    if coverage <= 0.0 {
+                        continue;
+                    }
+                    let first = technique.index() * TIMESTAMP_STRIDE as usize / 8;
+                    let elapsed = ticks[first + 1].saturating_sub(ticks[first]);
+                    let ms = elapsed as f64 * queries.period as f64 / 1e6 / coverage as f64;
+                    let smoothed = &mut timer.full_screen_ms[technique.index()];
+                    *smoothed = Some(smoothed.map_or(ms, |previous| previous * 0.9 + ms * 0.1));
+                }
+            }
+            timer.state = TimerState::Idle;
+        }
+    }
+}
+
+/// The logical AO pass: renders the selected technique, or both split down
+/// the screen, into the AO buffer and applies it to the frame buffer.
+pub fn ao_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let settings = world.resource::<AoSettings>().clone();
+    if !settings.enabled {
+        return Ok(());
+    }
+
+    world
+        .resource::<ScaledDepth>()
+        .downsample(ctx.encoder, settings.resolution)?;
+    world.resource_scope::<AoTimer, _>(|world, mut timer| {
+        let timer = &mut *timer;
+        let frame_buffer = world.resource::<FrameBuffer>();
+        let buffer = world.resource::<AoBuffer>();
+        let ao = world.resource::<AmbientOcclusion>();
+
+        let size = buffer.texture.texture.size();
+        let runs = settings.runs(size.width);
+        let queries = timer
+            .queries
+            .as_ref()
+            .filter(|_| timer.state == TimerState::Idle);
+        let mut coverage = [0.0; 2];
+        for (i, (technique, columns)) in runs.iter().enumerate() {
+            if columns.is_empty() {
+                continue;
+            }
+            let mut builder = RenderPassBuilder::new(ctx.encoder)
+                .with_label(technique.label())
+                .with_color_view(&buffer.texture.view);
+            if i > 0 {
+                builder = builder.load();
+            }
+            if let Some(queries) = queries {
+                builder = builder.with_timestamps(&queries.query_set, technique.index() as u32 * 2);
+            }
+            let mut render_pass = builder.build()?;
+            render_pass.set_scissor_rect(columns.start, 0, columns.len() as u32, size.height);
+            render_pass.set_pipeline(&ao.variants[technique.index()].render_pipeline);
+            render_pass.set_bind_group(0, &ao.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+            coverage[technique.index()] = columns.len() as f32 / size.width as f32;
+        }
+        if let Some(queries) = queries {
+            for technique in AoTechnique::ALL {
+                if coverage[technique.index()] > 0.0 {
+                    let first = technique.index() as u32 * 2;
+                    ctx.encoder.resolve_query_set(
+                        &queries.query_set,
+                        first..first + 2,
+                        &queries.resolve_buffer,
+                        technique.index() as u64 * TIMESTAMP_STRIDE,
+                    );
+                }
+            }
+            ctx.encoder.copy_buffer_to_buffer(
+                &queries.resolve_buffer,
+                0,
+                &queries.readback_buffer,
+                0,
+                queries.resolve_buffer.size(),
+            );
+            timer.coverage = coverage;
+            timer.state = TimerState::Resolved;
+        }
+
+        let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+            .with_label("ao_composite")
+            .with_color_view(&frame_buffer.texture.view)
+            .load()
+            .build()?;
+        let composite = if settings.visualize {
+            &ao.visualize
+        } else {
+            &ao.apply
+        };
+        render_pass.set_pipeline(&composite.render_pipeline);
+        render_pass.set_bind_group(0, &ao.composite_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    })
+}
+
+fn ao_panel(ctx: &egui::Context, world: &mut World) {
+    let timer = world.resource::<AoTimer>();
+    let (timed, timings) = (timer.queries.is_some(), timer.full_screen_ms);
+    let mut settings = world.resource::<AoSettings>().clone();
+
+    egui::Window::new(tr("Ambient occlusion"))
+        .id(egui::Id::new("Ambient occlusion"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Enabled"));
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                ui.checkbox(&mut settings.compare, tr("Compare"))
+                    .on_hover_text(tr("SSAO left of the divider, GTAO right of it"));
+                if settings.compare {
+                    ui.add(egui::Slider::new(&mut settings.split, 0.0..=1.0).text(tr("Divider")));
+                } else {
+                    egui::ComboBox::from_label(tr("Technique"))
+                        .selected_text(settings.technique.name())
+                        .show_ui(ui, |ui| {
+                            for technique in AoTechnique::ALL {
+                                ui.selectable_value(
+                                    &mut settings.technique,
+                                    technique,
+                                    technique.name(),
+                                );
+                            }
+                        });
+                }
+                ui.checkbox(&mut settings.visualize, tr("Show AO only"));
+                ui.horizontal(|ui| {
+                    ui.label(tr("Resolution"));
+                    settings.resolution.combo(ui, "ao_resolution");
+                });
+
+                ui.separator();
+                ui.add(
+                    egui::Slider::new(&mut settings.radius, 0.1..=5.0)
+                        .logarithmic(true)
+                        .text(tr("Radius")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.intensity, 0.25..=4.0).text(tr("Intensity")),
+                );
+                ui.add(egui::Slider::new(&mut settings.samples, 4..=64).text(tr("SSAO samples")));
+                ui.add(egui::Slider::new(&mut settings.slices, 1..=8).text(tr("GTAO slices")));
+                ui.add(egui::Slider::new(&mut settings.steps, 2..=16).text(tr("GTAO steps")));
+
+                ui.separator();
+                if !timed {
+                    ui.label(tr("GPU timings need timestamp queries"));
+                    return;
+                }
+                ui.label(tr("GPU time, scaled to the full screen"));
+                egui::Grid::new("ao_timings").num_columns(2).show(ui, |ui| {
+                    for technique in AoTechnique::ALL {
+                        ui.label(technique.name());
+                        ui.label(
+                            timings[technique.index()]
+                                .map_or("-".to_string(), |ms| format!("{:.3} ms", ms)),
+                        );
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+
+    let mut current = world.resource_mut::<AoSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== SETTINGS ===============================
+/// Implementations of the AO pass. Both read the same inputs and write the
+/// same buffer, so either can stand in for the other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AoTechnique {
+    /// Hemisphere sampling, cheap and noisy.
+    Ssao,
+    /// Horizon based ground truth AO, closer to a ray traced reference.
+    Gtao,
+}
+impl AoTechnique {
+    pub const ALL: [Self; 2] = [Self::Ssao, Self::Gtao];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ssao => "SSAO",
+            Self::Gtao => "GTAO",
+        }
+    }
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Ssao => "ssao",
+            Self::Gtao => "gtao",
+        }
+    }
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct AoSettings {
+    pub enabled: bool,
+    pub technique: AoTechnique,
+    /// Runs SSAO left of `split` and GTAO right of it, ignoring `technique`.
+    pub compare: bool,
+    /// Position of the divider as a fraction of the screen width.
+    pub split: f32,
+    /// Shows the AO buffer instead of applying it.
+    pub visualize: bool,
+    /// Resolution the AO buffer renders at before it's upsampled.
+    pub resolution: ResolutionScale,
+    /// View space radius of the occlusion.
+    pub radius: f32,
+    pub intensity: f32,
+    pub samples: u32,
+    pub slices: u32,
+    pub steps: u32,
+}
+impl Default for AoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            technique: AoTechnique::Gtao,
+            compare: false,
+            split: 0.5,
+            visualize: false,
+            resolution: ResolutionScale::Half,
+            radius: 1.0,
+            intensity: 1.0,
+            samples: 16,
+            slices: 2,
+            steps: 6,
+        }
+    }
+}
+impl AoSettings {
+    /// Techniques to run this frame and the columns each one covers.
+    fn runs(&self, width: u32) -> Vec<(AoTechnique, Range<u32>)> {
+        if self.compare {
+            let split = ((self.split * width as f32) as u32).min(width);
+            vec![
+                (AoTechnique::Ssao, 0..split),
+                (AoTechnique::Gtao, split..width),
+            ]
+        } else {
+            vec![(self.technique, 0..width)]
+        }
+    }
+}
+
+// =============================== RESOURCES ===============================
+#[derive(Resource)]
+pub struct AoBuffer {
+    pub texture: Texture,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct AoParams {
+    pub proj: [[f32; 4]; 4],
+    pub inv_proj: [[f32; 4]; 4],
+    pub view: [[f32; 4]; 4],
+    pub radius: f32,
+    pub intensity: f32,
+    pub samples: u32,
+    pub slices: u32,
+    pub steps: u32,
+    /// Divider in UV, negative when not comparing.
+    pub split: f32,
+    pub _padding: [f32; 2],
+}
+impl AoParams {
+    pub fn new(settings: &AoSettings, camera: &Camera) -> Self {
+        let proj = camera.projection();
+        Self {
+            proj: proj.to_cols_array_2d(),
+            inv_proj: proj.inverse().to_cols_array_2d(),
+            view: camera.view().to_cols_array_2d(),
+            radius: settings.radius,
+            intensity: settings.intensity,
+            samples: settings.samples,
+            slices: settings.slices,
+            steps: settings.steps,
+            split: if settings.compare {
+                settings.split
+            } else {
+                -1.0
+            },
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct AmbientOcclusion {
+    pub params: wgpu::Buffer,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    pub composite_layout: wgpu::BindGroupLayout,
+    pub composite_bind_group: wgpu::BindGroup,
+    /// One permutation of ao.wgsl per technique, indexed by `AoTechnique`.
+    pub variants: Vec<GPUPipeline>,
+    /// Multiplies the frame buffer by the AO.
+    pub apply: GPUPipeline,
+    /// Replaces the frame buffer with the AO.
+    pub visualize: GPUPipeline,
+}
+impl AmbientOcclusion {
+    pub fn new(
+        gpu: &GpuContext,
+        settings: &AoSettings,
+        depth: &DepthTexture,
+        ao_depth: &wgpu::TextureView,
+        surface: &SurfaceBuffer,
+        buffer: &AoBuffer,
+    ) -> Result<Self> {
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("ao_params"),
+                contents: bytemuck::bytes_of(&AoParams::new(settings, &Camera::default())),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type,
+            },
+            count: None,
+        };
+        let uniform = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let unfilterable = wgpu::TextureSampleType::Float { filterable: false };
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    texture(0, wgpu::TextureSampleType::Depth),
+                    texture(1, unfilterable),
+                    uniform(2),
+                ],
+                label: Some("ao_bind_group_layout"),
+            });
+        let composite_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &[
+                        texture(0, unfilterable),
+                        texture(1, wgpu::TextureSampleType::Depth),
+                        texture(2, wgpu::TextureSampleType::Depth),
+                        uniform(3),
+                    ],
+                    label: Some("ao_composite_bind_group_layout"),
+                });
+
+        let source = load_shader_source("ao.wgsl", include_str!("../shaders/ao.wgsl"));
+        let variants = AoTechnique::ALL
+            .iter()
+            .map(|technique| {
+                let source = preprocess("ao.wgsl", &source, &[technique.name()])?;
+                parse_wgsl("ao.wgsl", &source)?;
+                let shader = gpu
+                    .device
+                    .create_shader_module(wgpu::ShaderModuleDescriptor {
+                        label: Some(technique.label()),
+                        source: wgpu::ShaderSource::Wgsl(source.into()),
+                    });
+                GPUPipelineBuilder::new(&gpu.device)
+                    .label(technique.label())
+                    .pipeline_cache(gpu.pipeline_cache())
+                    .bind_group_layout(&layout)
+                    .vertex_shader(&shader, "vs_main")
+                    .fragment_shader(&shader, "fs_main")
+                    .color_target(wgpu::ColorTargetState {
+                        format: AO_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })
+                    .depth_stencil_state(None)
+                    .default_multisample_state()
+                    .primitive_state(wgpu::PrimitiveState::default())
+                    .build()
+                    .map_err(|e| anyhow::anyhow!(e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let composite_shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("ao_composite_shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/ao_composite.wgsl").into(),
+                ),
+            });
+        let composite = |label, blend| {
+            GPUPipelineBuilder::new(&gpu.device)
+                .label(label)
+                .pipeline_cache(gpu.pipeline_cache())
+                .bind_group_layout(&composite_layout)
+                .vertex_shader(&composite_shader, "vs_main")
+                .fragment_shader(&composite_shader, "fs_main")
+                .color_target(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })
+                .depth_stencil_state(None)
+                .default_multisample_state()
+                .primitive_state(wgpu::PrimitiveState::default())
+                .build()
+                .map_err(|e| anyhow::anyhow!(e))
+        };
+        // Darkens everything, the mesh shader has no separate ambient term
+        // to apply it to after the fact
+        let apply = composite(
+            "ao_apply_pipeline",
+            Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::Src,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+        )?;
+        let visualize = composite("ao_visualize_pipeline", None)?;
+
+        let bind_group = technique_bind_group(gpu, &layout, &params, ao_depth, surface);
+        let composite_bind_group =
+            composite_bind_group(gpu, &composite_layout, &params, buffer, ao_depth, depth);
+
+        Ok(Self {
+            params,
+            layout,
+            bind_group,
+            composite_layout,
+            composite_bind_group,
+            variants,
+            apply,
+            visualize,
+        })
+    }
+
+    /// `ao_depth` is the depth at the AO buffer's resolution.
+    pub fn create_bind_groups(
+        &self,
+        gpu: &GpuContext,
+        depth: &DepthTexture,
+        ao_depth: &wgpu::TextureView,
+        surface: &SurfaceBuffer,
+        buffer: &AoBuffer,
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
+        (
+            technique_bind_group(gpu, &self.layout, &self.params, ao_depth, surface),
+            composite_bind_group(
+                gpu,
+                &self.composite_layout,
+                &self.params,
+                buffer,
+                ao_depth,
+                depth,
+            ),
+        )
+    }
+}
+
+fn technique_bind_group(
+    gpu: &GpuContext,
+    layout: &wgpu::BindGroupLayout,
+    params: &wgpu::Buffer,
+    ao_depth: &wgpu::TextureView,
+    surface: &SurfaceBuffer,
+) -> wgpu::BindGroup {
+    gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(ao_depth),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&surface.texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: params.as_entire_binding(),
+            },
+        ],
+        label: Some("ao_bind_group"),
+    })
+}
+
+fn composite_bind_group(
+    gpu: &GpuContext,
+    layout: &wgpu::BindGroupLayout,
+    params: &wgpu::Buffer,
+    buffer: &AoBuffer,
+    ao_depth: &wgpu::TextureView,
+    depth: &DepthTexture,
+) -> wgpu::BindGroup {
+    gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&buffer.texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(ao_depth),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&depth.texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: params.as_entire_binding(),
+            },
+        ],
+        label: Some("ao_composite_bind_group"),
+    })
+}
+
+// =============================== TIMING ===============================
+/// Query resolves have to start on 256 byte boundaries, so every technique
+/// gets its own block of the resolve buffer.
+const TIMESTAMP_STRIDE: u64 = wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT;
+
+struct AoQueries {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    period: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TimerState {
+    /// Free to record timestamps this frame.
+    Idle,
+    /// Timestamps were resolved into the readback buffer, not mapped yet.
+    Resolved,
+    /// Waiting on the map callback.
+    Mapping,
+}
+
+/// GPU time of each technique, measured with pass timestamps whenever the
+/// previous measurement was read back. Unlike the profiler's timer it runs
+/// all the time, the panel shows a live comparison.
+#[derive(Resource)]
+pub struct AoTimer {
+    /// `None` when the device has no timestamp queries.
+    queries: Option<AoQueries>,
+    state: TimerState,
+    /// Set by the map callback, `false` when mapping failed.
+    mapped: Arc<Mutex<Option<bool>>>,
+    /// Fraction of the screen each technique covered in the timed frame.
+    coverage: [f32; 2],
+    /// Smoothed GPU time per technique in ms, scaled up to the full screen so
+    /// the halves of a split view compare fairly.
+    pub full_screen_ms: [Option<f64>; 2],
+}
+impl AoTimer {
+    pub fn new(gpu: &GpuContext) -> Self {
+        let queries = gpu
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let size = AoTechnique::ALL.len() as u64 * TIMESTAMP_STRIDE;
+                AoQueries {
+                    query_set: gpu.device.create_query_set(&wgpu::QuerySetDescriptor {
+                        label: Some("ao_query_set"),
+                        ty: wgpu::QueryType::Timestamp,
+                        count: AoTechnique::ALL.len() as u32 * 2,
+                    }),
+                    resolve_buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("ao_resolve_buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    }),
+                    readback_buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("ao_timestamp_readback"),
+                        size,
+                        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }),
+                    period: gpu.queue.get_timestamp_period(),
+                }
+            });
+        Self {
+            queries,
+            state: TimerState::Idle,
+            mapped: Arc::new(Mutex::new(None)),
+            coverage: [0.0; 2],
+            full_screen_ms: [None; 2],
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::Result;
+use bevy_ecs::{
+    observer::Trigger,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use playground_app::FrameEnd;
+use tracing::info_span;
+
+use crate::gpu::GpuContext;
+
+use super::render::render_system;
+
+/// Frames the CPU can get ahead of the GPU, matching the surface's
+/// `desired_maximum_frame_latency`. Each frame owns a buffer, so an upload
+/// never touches memory an earlier frame may still be reading.
+pub const FRAMES_IN_FLIGHT: usize = 2;
+/// Bytes visible through the bind group at each dynamic offset. Every
+/// allocation has to fit in this window.
+pub const BINDING_SIZE: u64 = 256;
+const INITIAL_CAPACITY: u64 = 256 * 1024;
+
+pub fn setup_frame_arena(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    world.insert_resource(FrameArena::new(gpu, INITIAL_CAPACITY));
+    world.add_observer(frame_arena_advance_observer);
+
+    // Systems that allocate order themselves before the upload
+    schedule.add_systems(frame_arena_upload_system.before(render_system));
+
+    Ok(())
+}
+
+pub fn frame_arena_upload_system(gpu: Res<GpuContext>, mut arena: ResMut<FrameArena>) {
+    arena.upload(&gpu);
+}
+
+pub fn frame_arena_advance_observer(_trigger: Trigger<FrameEnd>, mut arena: ResMut<FrameArena>) {
+    arena.advance();
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ArenaStats {
+    pub bytes: usize,
+    pub allocations: usize,
+    pub capacity: u64,
+}
+
+struct ArenaFrame {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    capacity: u64,
+}
+impl ArenaFrame {
+    fn new(gpu: &GpuContext, layout: &wgpu::BindGroupLayout, capacity: u64) -> Self {
+        let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame_arena_buffer"),
+            size: capacity,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(BINDING_SIZE),
+                }),
+            }],
+            label: Some("frame_arena_bind_group"),
+        });
+
+        Self {
+            buffer,
+            bind_group,
+            capacity,
+        }
+    }
+}
+
+/// Per-frame uniform data bump-allocated into one buffer and bound with
+/// dynamic offsets, so a frame costs a single `write_buffer` however many
+/// objects it draws.
+#[derive(Resource)]
+pub struct FrameArena {
+    pub layout: wgpu::BindGroupLayout,
+    frames: Vec<ArenaFrame>,
+    current: usize,
+    staging: Vec<u8>,
+    allocations: usize,
+    alignment: usize,
+    pub stats: ArenaStats,
+}
+impl FrameArena {
+    pub fn new(gpu: &GpuContext, capacity: u64) -> Self {
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("frame_arena_bind_group_layout"),
+            });
+        let frames = (0..FRAMES_IN_FLIGHT)
+            .map(|_| ArenaFrame::new(gpu, &layout, capacity))
+            .collect();
+
+        Self {
+            layout,
+            frames,
+            current: 0,
+            staging: Vec::new(),
+            allocations: 0,
+            alignment: gpu.device.limits().min_uniform_buffer_offset_alignment as usize,
+            stats: ArenaStats::default(),
+        }
+    }
+
+    /// Copies `value` into this frame's data, returning its dynamic offset.
+    pub fn alloc<T: bytemuck::Pod>(&mut self, value: &T) -> u32 {
+        let bytes = bytemuck::bytes_of(value);
+        debug_assert!(bytes.len() as u64 <= BINDING_SIZE);
+        let offset = self.staging.len().next_multiple_of(self.alignment);
+        self.staging.resize(offset, 0);
+        self.staging.extend_from_slice(bytes);
+        self.allocations += 1;
+        offset as u32
+    }
+
+    /// Bind group of the frame being recorded.
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.frames[self.current].bind_group
+    }
+
+    /// Uploads everything allocated this frame, growing its buffer if needed.
+    pub fn upload(&mut self, gpu: &GpuContext) {
+        let _span = info_span!("frame_arena_upload", bytes = self.staging.len()).entered();
+        self.staging
+            .resize(self.staging.len().next_multiple_of(4), 0);
+        // The binding window of the last allocation has to lie inside the buffer
+        let required = self.staging.len() as u64 + BINDING_SIZE;
+        let frame = &mut self.frames[self.current];
+        if required > frame.capacity {
+            *frame = ArenaFrame::new(gpu, &self.layout, required.next_power_of_two());
+        }
+        if !self.staging.is_empty() {
+            gpu.queue.write_buffer(&frame.buffer, 0, &self.staging);
+        }
+        self.stats = ArenaStats {
+            bytes: self.staging.len(),
+            allocations: self.allocations,
+            capacity: frame.capacity,
+        };
+    }
+
+    /// Switches to the next frame's buffer and starts allocating from zero.
+    pub fn advance(&mut self) {
+        self.current = (self.current + 1) % self.frames.len();
+        self.staging.clear();
+        self.allocations = 0;
+    }
+}
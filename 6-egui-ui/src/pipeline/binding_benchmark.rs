@@ -0,0 +1,610 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::Schedule,
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Mat4, Quat, Vec3};
+use tracing::{info, info_span, warn};
+use wgpu::util::DeviceExt;
+
+use crate::i18n::{tr, trf};
+use crate::{
+    gpu::GpuContext,
+    pass::RenderPassBuilder,
+    shader::load_shader_source,
+    vertex::{cube_vertices, MeshVertex},
+};
+
+use super::{compute::read_buffer, ui::UiPanels, GPUPipeline, GPUPipelineBuilder};
+
+/// Objects per side of the grid, every one of them its own draw call.
+const GRID: u32 = 100;
+const OBJECTS: u32 = GRID * GRID;
+/// Runs per variant; the reported times are their average.
+const ITERATIONS: u32 = 10;
+const TARGET_SIZE: u32 = 512;
+const TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+pub fn setup_binding_benchmark(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let benchmark = BindingBenchmark::new(gpu)?;
+
+    world.insert_resource(benchmark);
+    world.insert_resource(BindingResults::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(binding_benchmark_panel);
+
+    schedule.add_systems(binding_benchmark_system);
+
+    Ok(())
+}
+
+/// Runs the benchmark on its own submission when the panel asks for it.
+pub fn binding_benchmark_system(
+    gpu: Res<GpuContext>,
+    benchmark: Res<BindingBenchmark>,
+    mut results: ResMut<BindingResults>,
+) {
+    if !results.requested {
+        return;
+    }
+    results.requested = false;
+
+    match benchmark.run(&gpu) {
+        Ok(runs) => {
+            for run in &runs {
+                info!(
+                    "Binding '{}': {:.3} ms CPU, {:?} ms GPU ({})",
+                    run.name,
+                    run.cpu_ms,
+                    run.gpu_ms,
+                    if run.matches {
+                        "same image"
+                    } else {
+                        "DIFFERENT IMAGE"
+                    }
+                );
+            }
+            results.runs = runs;
+        }
+        Err(e) => warn!("Binding benchmark failed: {}", e),
+    }
+}
+
+fn binding_benchmark_panel(ctx: &egui::Context, world: &mut World) {
+    let benchmark = world.resource::<BindingBenchmark>();
+    let timestamps = benchmark.timestamps.is_some();
+    let missing = benchmark.missing;
+    let mut results = world.resource_mut::<BindingResults>();
+
+    egui::Window::new(tr("Binding benchmark"))
+        .id(egui::Id::new("Binding benchmark"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.label(trf!(
+                "Draws {} objects one by one, {} runs per variant",
+                OBJECTS,
+                ITERATIONS
+            ));
+            if let Some(missing) = missing {
+                ui.label(trf!("Skipping push constants: {}", tr(missing)));
+            }
+            if !timestamps {
+                ui.label(tr(
+                    "Timestamp queries unsupported, only CPU times are measured",
+                ));
+            }
+            if ui.button(tr("Run")).clicked() {
+                results.requested = true;
+            }
+            egui::Grid::new("binding_results")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label(tr("CPU"));
+                    ui.label(tr("GPU"));
+                    ui.label("");
+                    ui.end_row();
+                    for run in &results.runs {
+                        ui.label(run.name);
+                        ui.label(format!("{:.3} ms", run.cpu_ms));
+                        ui.label(
+                            run.gpu_ms
+                                .map_or("-".to_string(), |ms| format!("{:.3} ms", ms)),
+                        );
+                        ui.label(tr(if run.matches {
+                            "same image"
+                        } else {
+                            "different image"
+                        }));
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+// =============================== RESOURCES ===============================
+/// What every variant gets per object, see `binding_benchmark.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ObjectData {
+    pub transform: [[f32; 4]; 4],
+    pub color: [f32; 4],
+}
+
+pub struct BindingRun {
+    pub name: &'static str,
+    /// Recording the draws of one run.
+    pub cpu_ms: f64,
+    /// `None` when the device has no timestamp queries.
+    pub gpu_ms: Option<f64>,
+    /// Drew the same image as the first variant.
+    pub matches: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct BindingResults {
+    pub requested: bool,
+    pub runs: Vec<BindingRun>,
+}
+
+enum Binding {
+    /// Every object at its own offset, `stride` apart.
+    Uniform {
+        bind_group: wgpu::BindGroup,
+        stride: u32,
+    },
+    PushConstants,
+    /// All objects in one buffer, picked by the instance index.
+    Storage {
+        bind_group: wgpu::BindGroup,
+    },
+}
+
+struct BindingVariant {
+    name: &'static str,
+    pipeline: GPUPipeline,
+    binding: Binding,
+}
+
+struct Timestamps {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    period: f32,
+}
+
+/// A grid of small spinning cubes in clip space, colored by position.
+fn grid_objects() -> Vec<ObjectData> {
+    let cell = 2.0 / GRID as f32;
+    (0..OBJECTS)
+        .map(|i| {
+            let (x, y) = (i % GRID, i / GRID);
+            let center = Vec3::new(
+                -1.0 + (x as f32 + 0.5) * cell,
+                -1.0 + (y as f32 + 0.5) * cell,
+                0.5,
+            );
+            let transform = Mat4::from_scale_rotation_translation(
+                Vec3::splat(cell * 0.5),
+                Quat::from_euler(glam::EulerRot::YXZ, i as f32 * 0.1, 0.6, 0.0),
+                center,
+            );
+            ObjectData {
+                transform: transform.to_cols_array_2d(),
+                color: [x as f32 / GRID as f32, y as f32 / GRID as f32, 0.6, 1.0],
+            }
+        })
+        .collect()
+}
+
+/// The objects at offsets a uniform can be bound at, and the stride.
+fn uniform_data(objects: &[ObjectData], alignment: u32) -> (Vec<u8>, u32) {
+    let size = std::mem::size_of::<ObjectData>() as u32;
+    let stride = size.next_multiple_of(alignment);
+    let mut data = vec![0; (objects.len() as u32 * stride) as usize];
+    for (object, chunk) in objects.iter().zip(data.chunks_exact_mut(stride as usize)) {
+        chunk[..size as usize].copy_from_slice(bytemuck::bytes_of(object));
+    }
+    (data, stride)
+}
+
+// =============================== BENCHMARK ===============================
+/// Draws the same grid of objects, one draw call each, with three ways of
+/// getting an object's data to its draw: a uniform buffer bound at a dynamic
+/// offset, push constants, and a storage buffer indexed by instance. Only
+/// how a draw finds its data differs, so that's what the times compare.
+#[derive(Resource)]
+pub struct BindingBenchmark {
+    variants: Vec<BindingVariant>,
+    objects: Vec<ObjectData>,
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    target: wgpu::Texture,
+    target_view: wgpu::TextureView,
+    image_readback: wgpu::Buffer,
+    timestamps: Option<Timestamps>,
+    /// Why the device can't run the push constant variant.
+    missing: Option<&'static str>,
+}
+impl BindingBenchmark {
+    pub fn new(gpu: &GpuContext) -> Result<Self> {
+        let objects = grid_objects();
+        let object_size = std::mem::size_of::<ObjectData>() as u64;
+        let vertices = cube_vertices();
+        let vertex_buffer = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("binding_benchmark_vertices"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+
+        let alignment = gpu.device.limits().min_uniform_buffer_offset_alignment;
+        let (uniform_contents, stride) = uniform_data(&objects, alignment);
+        let uniforms = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("binding_benchmark_uniforms"),
+                contents: &uniform_contents,
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let storage = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("binding_benchmark_storage"),
+                contents: bytemuck::cast_slice(&objects),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+
+        let layout = |binding, ty, has_dynamic_offset| {
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty,
+                            has_dynamic_offset,
+                            min_binding_size: wgpu::BufferSize::new(object_size),
+                        },
+                        count: None,
+                    }],
+                    label: Some("binding_benchmark_bind_group_layout"),
+                })
+        };
+        let uniform_layout = layout(0, wgpu::BufferBindingType::Uniform, true);
+        let storage_layout = layout(
+            1,
+            wgpu::BufferBindingType::Storage { read_only: true },
+            false,
+        );
+        let uniform_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &uniforms,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(object_size),
+                }),
+            }],
+            label: Some("binding_benchmark_uniform_bind_group"),
+        });
+        let storage_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &storage_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 1,
+                resource: storage.as_entire_binding(),
+            }],
+            label: Some("binding_benchmark_storage_bind_group"),
+        });
+
+        let source = load_shader_source(
+            "binding_benchmark.wgsl",
+            include_str!("../shaders/binding_benchmark.wgsl"),
+        );
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("binding_benchmark_shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let build = |label: &str,
+                     shader: &wgpu::ShaderModule,
+                     entry_point: &str,
+                     bind_group_layout: Option<&wgpu::BindGroupLayout>,
+                     push_constants: bool| {
+            let mut builder = GPUPipelineBuilder::new(&gpu.device)
+                .label(label)
+                .pipeline_cache(gpu.pipeline_cache())
+                .vertex_shader(shader, entry_point)
+                .fragment_shader(shader, "fs_main")
+                .vertex_buffer_layout(MeshVertex::desc())
+                .default_color_target(TARGET_FORMAT)
+                .default_primitive_state()
+                .default_multisample_state();
+            if let Some(layout) = bind_group_layout {
+                builder = builder.bind_group_layout(layout);
+            }
+            if push_constants {
+                builder = builder.push_constant_range(wgpu::PushConstantRange {
+                    stages: wgpu::ShaderStages::VERTEX,
+                    range: 0..object_size as u32,
+                });
+            }
+            builder.build().map_err(|e| anyhow::anyhow!(e))
+        };
+
+        let mut variants = vec![BindingVariant {
+            name: "uniform, dynamic offset",
+            pipeline: build(
+                "binding_benchmark_uniform",
+                &shader,
+                "vs_uniform",
+                Some(&uniform_layout),
+                false,
+            )?,
+            binding: Binding::Uniform {
+                bind_group: uniform_bind_group,
+                stride,
+            },
+        }];
+        // Push constants fail validation on devices without the feature
+        let missing = if !gpu
+            .device
+            .features()
+            .contains(wgpu::Features::PUSH_CONSTANTS)
+        {
+            Some("the device has no push constants")
+        } else if (gpu.device.limits().max_push_constant_size as u64) < object_size {
+            Some("the device has too little push constant space")
+        } else {
+            None
+        };
+        if missing.is_none() {
+            let source = load_shader_source(
+                "binding_benchmark_push.wgsl",
+                include_str!("../shaders/binding_benchmark_push.wgsl"),
+            );
+            let shader = gpu
+                .device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("binding_benchmark_push_shader"),
+                    source: wgpu::ShaderSource::Wgsl(source.into()),
+                });
+            variants.push(BindingVariant {
+                name: "push constants",
+                pipeline: build("binding_benchmark_push", &shader, "vs_push", None, true)?,
+                binding: Binding::PushConstants,
+            });
+        }
+        variants.push(BindingVariant {
+            name: "storage, by instance",
+            pipeline: build(
+                "binding_benchmark_storage",
+                &shader,
+                "vs_storage",
+                Some(&storage_layout),
+                false,
+            )?,
+            binding: Binding::Storage {
+                bind_group: storage_bind_group,
+            },
+        });
+
+        let target = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("binding_benchmark_target"),
+            size: wgpu::Extent3d {
+                width: TARGET_SIZE,
+                height: TARGET_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TARGET_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let target_view = target.create_view(&Default::default());
+        let image_readback = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("binding_benchmark_image_readback"),
+            size: variants.len() as u64 * Self::image_size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let timestamps = gpu
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let count = variants.len() as u32 * ITERATIONS * 2;
+                let size = count as u64 * wgpu::QUERY_SIZE as u64;
+                Timestamps {
+                    query_set: gpu.device.create_query_set(&wgpu::QuerySetDescriptor {
+                        label: Some("binding_benchmark_query_set"),
+                        ty: wgpu::QueryType::Timestamp,
+                        count,
+                    }),
+                    resolve_buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("binding_benchmark_resolve_buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    }),
+                    readback_buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("binding_benchmark_timestamp_readback"),
+                        size,
+                        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }),
+                    period: gpu.queue.get_timestamp_period(),
+                }
+            });
+
+        Ok(Self {
+            variants,
+            objects,
+            vertex_buffer,
+            vertex_count: vertices.len() as u32,
+            target,
+            target_view,
+            image_readback,
+            timestamps,
+            missing,
+        })
+    }
+
+    /// Bytes of one rendered image, whose rows need no padding.
+    fn image_size() -> u64 {
+        TARGET_SIZE as u64 * TARGET_SIZE as u64 * 4
+    }
+
+    /// Records every variant `ITERATIONS` times, each in its own timed render
+    /// pass, and blocks until the images and timestamps are back.
+    pub fn run(&self, gpu: &GpuContext) -> Result<Vec<BindingRun>> {
+        let _span = info_span!("binding_benchmark").entered();
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("binding_benchmark_encoder"),
+            });
+        let mut cpu_ms = Vec::with_capacity(self.variants.len());
+        for (v, variant) in self.variants.iter().enumerate() {
+            let started = Instant::now();
+            for iteration in 0..ITERATIONS {
+                let mut builder = RenderPassBuilder::new(&mut encoder)
+                    .with_label(variant.name)
+                    .with_color_view(&self.target_view);
+                if let Some(t) = &self.timestamps {
+                    builder = builder
+                        .with_timestamps(&t.query_set, (v as u32 * ITERATIONS + iteration) * 2);
+                }
+                let mut render_pass = builder.build()?;
+                render_pass.set_pipeline(&variant.pipeline.render_pipeline);
+                render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                let vertices = 0..self.vertex_count;
+                match &variant.binding {
+                    Binding::Uniform { bind_group, stride } => {
+                        for i in 0..OBJECTS {
+                            render_pass.set_bind_group(0, bind_group, &[i * stride]);
+                            render_pass.draw(vertices.clone(), 0..1);
+                        }
+                    }
+                    Binding::PushConstants => {
+                        for object in &self.objects {
+                            render_pass.set_push_constants(
+                                wgpu::ShaderStages::VERTEX,
+                                0,
+                                bytemuck::bytes_of(object),
+                            );
+                            render_pass.draw(vertices.clone(), 0..1);
+                        }
+                    }
+                    Binding::Storage { bind_group } => {
+                        render_pass.set_bind_group(0, bind_group, &[]);
+                        for i in 0..OBJECTS {
+                            render_pass.draw(vertices.clone(), i..i + 1);
+                        }
+                    }
+                }
+            }
+            cpu_ms.push(started.elapsed().as_secs_f64() * 1000.0 / ITERATIONS as f64);
+
+            encoder.copy_texture_to_buffer(
+                self.target.as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &self.image_readback,
+                    layout: wgpu::ImageDataLayout {
+                        offset: v as u64 * Self::image_size(),
+                        bytes_per_row: Some(TARGET_SIZE * 4),
+                        rows_per_image: None,
+                    },
+                },
+                self.target.size(),
+            );
+        }
+        if let Some(t) = &self.timestamps {
+            let count = self.variants.len() as u32 * ITERATIONS * 2;
+            encoder.resolve_query_set(&t.query_set, 0..count, &t.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                &t.resolve_buffer,
+                0,
+                &t.readback_buffer,
+                0,
+                t.resolve_buffer.size(),
+            );
+        }
+        gpu.queue.submit(Some(encoder.finish()));
+
+        let images: Vec<u32> = read_buffer(&gpu.device, &self.image_readback)?;
+        let ticks: Option<Vec<u64>> = self
+            .timestamps
+            .as_ref()
+            .map(|t| read_buffer(&gpu.device, &t.readback_buffer))
+            .transpose()?;
+
+        let pixels = (TARGET_SIZE * TARGET_SIZE) as usize;
+        let reference = &images[..pixels];
+        Ok(self
+            .variants
+            .iter()
+            .enumerate()
+            .zip(cpu_ms)
+            .map(|((v, variant), cpu_ms)| {
+                let gpu_ms = ticks
+                    .as_ref()
+                    .zip(self.timestamps.as_ref())
+                    .map(|(ticks, t)| {
+                        let range = v * ITERATIONS as usize * 2..(v + 1) * ITERATIONS as usize * 2;
+                        let total: u64 = ticks[range]
+                            .chunks_exact(2)
+                            .map(|pair| pair[1].saturating_sub(pair[0]))
+                            .sum();
+                        total as f64 * t.period as f64 / ITERATIONS as f64 / 1e6
+                    });
+                BindingRun {
+                    name: variant.name,
+                    cpu_ms,
+                    gpu_ms,
+                    matches: images[v * pixels..(v + 1) * pixels] == *reference,
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform_objects_sit_at_bindable_offsets() {
+        let objects = grid_objects();
+        assert_eq!(objects.len(), OBJECTS as usize);
+        assert_eq!(std::mem::size_of::<ObjectData>(), 80);
+
+        let (data, stride) = uniform_data(&objects[..3], 256);
+        assert_eq!(stride, 256);
+        assert_eq!(data.len(), 3 * 256);
+        let second = &data[256..256 + 80];
+        assert_eq!(second, bytemuck::bytes_of(&objects[1]));
+        assert!(data[80..256].iter().all(|&byte| byte == 0));
+
+        // Every cube stays inside its own cell, so the images can be compared
+        let corner = Mat4::from_cols_array_2d(&objects[0].transform);
+        for vertex in cube_vertices() {
+            let position = corner.transform_point3(Vec3::from(vertex.position));
+            assert!(position.x > -1.0 && position.x < -1.0 + 2.0 / GRID as f32);
+            assert!(position.z > 0.0 && position.z < 1.0);
+        }
+    }
+}
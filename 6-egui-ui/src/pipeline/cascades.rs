@@ -0,0 +1,322 @@
+use anyhow::Result;
+use bevy_ecs::{
+    entity::Entity,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Query, Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Mat4, Vec3, Vec3Swizzles};
+
+use crate::{
+    assets::AssetServer,
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    lights::{light_gathering_system, DirectionalLight},
+    pass::RenderPassBuilder,
+    scene::{camera_aspect_system, transform_propagation_system, Camera, GlobalTransform},
+    texture::Texture,
+};
+
+use super::{
+    arena::FrameArena,
+    graph::PassContext,
+    inspector::TextureRegistry,
+    mesh::Meshes,
+    render::render_system,
+    shadow::{draw_shadow_casters, ShadowAtlas, ShadowCasters, ShadowPipeline, ShadowViewUniform},
+    ui::UiPanels,
+};
+
+pub const MAX_CASCADES: usize = 4;
+pub const CASCADE_RESOLUTION: u32 = 2048;
+/// How far behind a cascade, towards the light, casters are still captured.
+const CASTER_EXTENSION: f32 = 50.0;
+
+pub fn setup_cascades(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let atlas = world
+        .get_resource::<ShadowAtlas>()
+        .ok_or_else(|| anyhow::anyhow!("ShadowAtlas resource not found"))?;
+
+    let cascades = CascadedShadows::new(gpu, atlas);
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("shadow_cascades", |world| {
+            world
+                .get_resource::<CascadedShadows>()
+                .map(|c| &c.texture.texture)
+        });
+    world.insert_resource(cascades);
+    world.insert_resource(CascadeSettings::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(cascade_panel);
+
+    schedule.add_systems(
+        cascade_update_system
+            .after(transform_propagation_system)
+            .after(camera_aspect_system)
+            .before(light_gathering_system)
+            .before(render_system),
+    );
+
+    Ok(())
+}
+
+/// Renders the shadow casters once per cascade, each into its own layer.
+pub fn cascade_shadow_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let cascades = world.resource::<CascadedShadows>();
+    let casters = world.resource::<ShadowCasters>();
+    let pipeline = world.resource::<ShadowPipeline>();
+    let meshes = world.resource::<Meshes>();
+    let assets = world.resource::<AssetServer>();
+    let arena = world.resource::<FrameArena>();
+
+    for (i, layer_view) in cascades.layer_views.iter().enumerate() {
+        let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+            .with_label(ctx.label)
+            .with_depth(layer_view, 1.0)
+            .build()?;
+        if i >= cascades.count {
+            // Still cleared, so a stale cascade never shadows anything
+            continue;
+        }
+        render_pass.set_pipeline(&pipeline.pipeline.render_pipeline);
+        render_pass.set_bind_group(
+            0,
+            &cascades.views_bind_group,
+            &[i as u32 * cascades.view_stride],
+        );
+        draw_shadow_casters(&mut render_pass, casters, meshes, assets, arena)?;
+    }
+
+    Ok(())
+}
+
+fn cascade_panel(ctx: &egui::Context, world: &mut World) {
+    let splits = world.resource::<CascadedShadows>().splits;
+    let mut settings = world.resource_mut::<CascadeSettings>();
+
+    egui::Window::new(tr("Cascades"))
+        .id(egui::Id::new("Cascades"))
+        .show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut settings.count, 1..=MAX_CASCADES).text(tr("cascades")));
+            ui.add(egui::Slider::new(&mut settings.distance, 10.0..=200.0).text(tr("distance")));
+            ui.add(
+                egui::Slider::new(&mut settings.lambda, 0.0..=1.0).text(tr("log/uniform split")),
+            );
+            ui.add(egui::Slider::new(&mut settings.blend, 0.0..=0.5).text(tr("blend band")));
+            ui.checkbox(&mut settings.debug, tr("Color cascades"));
+            for (i, split) in splits.iter().take(settings.count).enumerate() {
+                ui.label(trf!("Cascade {}: up to {:.1}", i, split));
+            }
+        });
+}
+
+// =============================== MATH ===============================
+/// Far distance of every cascade, mixing logarithmic and uniform splits.
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> [f32; MAX_CASCADES] {
+    let mut splits = [far; MAX_CASCADES];
+    for (i, split) in splits.iter_mut().enumerate().take(count) {
+        let t = (i + 1) as f32 / count as f32;
+        let log = near * (far / near).powf(t);
+        let uniform = near + (far - near) * t;
+        *split = lambda * log + (1.0 - lambda) * uniform;
+    }
+    splits
+}
+
+/// Fits an orthographic light projection around the bounding sphere of the
+/// camera frustum slice `[near, far]`. The sphere keeps the size constant as
+/// the camera turns and the texel snapping stops edges shimmering as it moves.
+pub fn cascade_view_projection(camera: &Camera, near: f32, far: f32, direction: Vec3) -> Mat4 {
+    let projection = Mat4::perspective_rh(camera.fov_y, camera.aspect, near, far);
+    let inverse = (projection * camera.view()).inverse();
+    let corners: Vec<Vec3> = [-1.0, 1.0]
+        .into_iter()
+        .flat_map(|x| [-1.0, 1.0].map(|y| (x, y)))
+        .flat_map(|(x, y)| [0.0, 1.0].map(|z| inverse.project_point3(Vec3::new(x, y, z))))
+        .collect();
+    let center = corners.iter().sum::<Vec3>() / corners.len() as f32;
+    let radius = corners
+        .iter()
+        .map(|corner| corner.distance(center))
+        .fold(0.0, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let up = if direction.y.abs() > 0.99 {
+        Vec3::X
+    } else {
+        Vec3::Y
+    };
+    let view = Mat4::look_to_rh(Vec3::ZERO, direction, up);
+    let texel = radius * 2.0 / CASCADE_RESOLUTION as f32;
+    let light_center = view.transform_point3(center);
+    let snapped = (light_center.xy() / texel).floor() * texel;
+    let projection = Mat4::orthographic_rh(
+        snapped.x - radius,
+        snapped.x + radius,
+        snapped.y - radius,
+        snapped.y + radius,
+        -(light_center.z + radius + CASTER_EXTENSION),
+        -(light_center.z - radius),
+    );
+    projection * view
+}
+
+// =============================== RESOURCES ===============================
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct CascadeSettings {
+    pub count: usize,
+    /// Shadows fade out past this view distance.
+    pub distance: f32,
+    /// 0 splits uniformly, 1 logarithmically.
+    pub lambda: f32,
+    /// Fraction of each cascade blended into the next one.
+    pub blend: f32,
+    pub debug: bool,
+}
+impl Default for CascadeSettings {
+    fn default() -> Self {
+        Self {
+            count: MAX_CASCADES,
+            distance: 80.0,
+            lambda: 0.75,
+            blend: 0.1,
+            debug: false,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CascadeUniform {
+    pub view_proj: [[[f32; 4]; 4]; MAX_CASCADES],
+    pub splits: [f32; MAX_CASCADES],
+    /// Cascade count, blend band, debug coloring, unused.
+    pub params: [f32; 4],
+}
+
+/// Depth array texture with one layer per cascade of the directional light.
+#[derive(Resource)]
+pub struct CascadedShadows {
+    pub texture: Texture,
+    pub layer_views: Vec<wgpu::TextureView>,
+    pub uniform_buffer: wgpu::Buffer,
+    pub views_buffer: wgpu::Buffer,
+    pub views_bind_group: wgpu::BindGroup,
+    pub view_stride: u32,
+    /// The directional light the cascades were fitted for.
+    pub light: Option<Entity>,
+    pub count: usize,
+    pub splits: [f32; MAX_CASCADES],
+}
+impl CascadedShadows {
+    pub fn new(gpu: &GpuContext, atlas: &ShadowAtlas) -> Self {
+        let texture = Texture::array(
+            &gpu.device,
+            CASCADE_RESOLUTION,
+            CASCADE_RESOLUTION,
+            MAX_CASCADES as u32,
+            wgpu::TextureFormat::Depth32Float,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            "cascade_shadow_texture",
+        );
+        let layer_views = (0..texture.layers())
+            .map(|layer| texture.layer_view(layer))
+            .collect();
+
+        let uniform_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cascade_uniform_buffer"),
+            size: std::mem::size_of::<CascadeUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Same layout as the spot light views, so the shadow pipeline is shared
+        let view_stride = atlas.view_stride;
+        let views_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cascade_views_buffer"),
+            size: view_stride as u64 * MAX_CASCADES as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let views_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &atlas.views_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &views_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<ShadowViewUniform>() as u64),
+                }),
+            }],
+            label: Some("cascade_views_bind_group"),
+        });
+
+        Self {
+            texture,
+            layer_views,
+            uniform_buffer,
+            views_buffer,
+            views_bind_group,
+            view_stride,
+            light: None,
+            count: 0,
+            splits: [0.0; MAX_CASCADES],
+        }
+    }
+}
+
+// =============================== SYSTEMS ===============================
+/// Fits the cascades to the camera for the first shadow casting directional
+/// light, or disables them when there is none.
+pub fn cascade_update_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    settings: Res<CascadeSettings>,
+    mut cascades: ResMut<CascadedShadows>,
+    lights: Query<(Entity, &GlobalTransform, &DirectionalLight)>,
+) {
+    let light = lights
+        .iter()
+        .find(|(_, _, light)| light.cast_shadows && light.intensity > 0.0);
+    let count = settings.count.clamp(1, MAX_CASCADES);
+    let far = settings.distance.min(camera.far);
+    let splits = cascade_splits(camera.near, far, count, settings.lambda);
+
+    let mut uniform = CascadeUniform {
+        view_proj: [Mat4::IDENTITY.to_cols_array_2d(); MAX_CASCADES],
+        splits,
+        params: [0.0, settings.blend, settings.debug as u32 as f32, 0.0],
+    };
+    let mut views = vec![0u8; cascades.view_stride as usize * MAX_CASCADES];
+    if let Some((_, global, _)) = light {
+        let direction = global
+            .0
+            .transform_vector3(Vec3::NEG_Z)
+            .normalize_or(Vec3::NEG_Z);
+        let mut near = camera.near;
+        for (i, split) in splits.iter().take(count).enumerate() {
+            let view_proj = cascade_view_projection(&camera, near, *split, direction);
+            uniform.view_proj[i] = view_proj.to_cols_array_2d();
+            let offset = i * cascades.view_stride as usize;
+            let view = ShadowViewUniform {
+                view_proj: view_proj.to_cols_array_2d(),
+            };
+            views[offset..offset + std::mem::size_of::<ShadowViewUniform>()]
+                .copy_from_slice(bytemuck::bytes_of(&view));
+            near = *split;
+        }
+        uniform.params[0] = count as f32;
+    }
+
+    gpu.queue
+        .write_buffer(&cascades.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+    gpu.queue.write_buffer(&cascades.views_buffer, 0, &views);
+    cascades.light = light.map(|(entity, _, _)| entity);
+    cascades.count = if light.is_some() { count } else { 0 };
+    cascades.splits = splits;
+}
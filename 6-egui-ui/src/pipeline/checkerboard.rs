@@ -0,0 +1,718 @@
+//! Checkerboard rendering: each frame the meshes are drawn for half the
+//! pixels, a checkerboard that swaps every frame, into a half size target. A
+//! compute pass rebuilds the full frame, taking the missing pixels from the
+//! last reconstruction. The even and odd rows of the checkerboard are each a
+//! regular grid at half the resolution both ways, so they're drawn one after
+//! the other into the top and bottom of the half frame, jittered onto their
+//! pixels.
+//!
+//! An experiment rather than a speedup: the full resolution mesh pass keeps
+//! running, as the reference the comparison views show against. Only the
+//! meshes are checkerboarded, the reconstruction goes over whatever was in
+//! the frame buffer before them.
+
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{
+    assets::AssetServer,
+    gpu::GpuContext,
+    i18n::tr,
+    lights::LightBuffer,
+    pass::RenderPassBuilder,
+    scene::{Camera, DrawList},
+    shader::load_shader_source,
+    texture::Texture,
+};
+
+use super::{
+    compute::{DispatchSite, GPUComputePipeline},
+    graph::PassContext,
+    inspector::TextureRegistry,
+    mesh::{
+        draw_commands, mesh_prepare_system, CameraBuffer, InstanceBuffer, Materials, MeshPipelines,
+        Meshes,
+    },
+    present::{render_scale_system, FrameBuffer},
+    render::render_system,
+    ssr::SURFACE_FORMAT,
+    stereo::{StereoMode, StereoSettings},
+    ui::UiPanels,
+    velocity::VELOCITY_FORMAT,
+};
+
+const SHADER_NAME: &str = "checkerboard.wgsl";
+/// The reconstruction and the history it's built from.
+const RESOLVED_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+const FLAG_HISTORY: u32 = 1;
+const FLAG_REPROJECT: u32 = 2;
+const FLAG_CLAMP: u32 = 4;
+
+pub fn setup_checkerboard(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let frame_buffer = world
+        .get_resource::<FrameBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("FrameBuffer resource not found"))?;
+
+    let checkerboard = Checkerboard::new(gpu, frame_buffer)?;
+
+    world.insert_resource(checkerboard);
+    world.insert_resource(CheckerboardSettings::default());
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("checkerboard_half", |world| {
+            world
+                .get_resource::<Checkerboard>()
+                .map(|checkerboard| &checkerboard.half_color.texture)
+        });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(checkerboard_panel);
+
+    schedule.add_systems((
+        checkerboard_resize_system
+            .run_if(resource_changed::<FrameBuffer>)
+            .after(render_scale_system)
+            .before(render_system),
+        checkerboard_camera_system
+            .after(mesh_prepare_system)
+            .after(checkerboard_resize_system)
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Follows the frame buffer, and rebinds it as the reference even when only
+/// the texture was recreated.
+pub fn checkerboard_resize_system(
+    gpu: Res<GpuContext>,
+    frame_buffer: Res<FrameBuffer>,
+    mut checkerboard: ResMut<Checkerboard>,
+) {
+    checkerboard.resize(&gpu, &frame_buffer);
+}
+
+pub fn checkerboard_camera_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    settings: Res<CheckerboardSettings>,
+    stereo: Option<Res<StereoSettings>>,
+    mut checkerboard: ResMut<Checkerboard>,
+) {
+    // The half frame has no room for two eyes
+    let stereo = stereo.is_some_and(|stereo| stereo.mode != StereoMode::Off);
+    checkerboard.active = settings.enabled && !stereo;
+    if !checkerboard.active {
+        // Turning it back on starts over instead of blending in a stale frame
+        checkerboard.view_proj = None;
+        checkerboard.warm = false;
+        return;
+    }
+    checkerboard.frame += 1;
+    checkerboard.write(&gpu, &camera);
+    checkerboard.write_params(&gpu, &settings);
+    checkerboard.warm = true;
+}
+
+/// Draws this frame's half of the pixels, right before the mesh pass draws
+/// all of them.
+pub fn checkerboard_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let checkerboard = world.resource::<Checkerboard>();
+    if !checkerboard.active {
+        return Ok(());
+    }
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let draw_list = world.resource::<DrawList>();
+    let pipelines = world.resource::<MeshPipelines>();
+    let instances = world.resource::<InstanceBuffer>();
+    let materials = world.resource::<Materials>();
+    let meshes = world.resource::<Meshes>();
+    let assets = world.resource::<AssetServer>();
+    let lights = world.resource::<LightBuffer>();
+    let size = frame_buffer.texture.texture.size();
+
+    // What the meshes go over, for the reconstruction to go over it too
+    ctx.encoder.copy_texture_to_texture(
+        frame_buffer.texture.texture.as_image_copy(),
+        checkerboard.background.texture.as_image_copy(),
+        size,
+    );
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_cleared_color_view(&checkerboard.half_color.view, wgpu::Color::TRANSPARENT)
+        .with_cleared_color_view(&checkerboard.half_velocity.view, wgpu::Color::TRANSPARENT)
+        .with_cleared_color_view(&checkerboard.half_surface.view, wgpu::Color::TRANSPARENT)
+        .with_depth(&checkerboard.half_depth.view, 1.0)
+        .build()?;
+
+    render_pass.set_bind_group(1, &instances.bind_group, &[]);
+    render_pass.set_bind_group(3, &lights.bind_group, &[]);
+    for lattice in Lattice::ALL {
+        let [x, y, width, height] = lattice.viewport(size.width, size.height);
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        render_pass.set_bind_group(0, &checkerboard.cameras[lattice as usize].bind_group, &[]);
+        draw_commands(
+            &mut render_pass,
+            draw_list,
+            pipelines,
+            materials,
+            meshes,
+            assets,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Rebuilds the full frame right after the mesh pass, and replaces its
+/// output with the selected view.
+pub fn checkerboard_resolve_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let checkerboard = world.resource::<Checkerboard>();
+    if !checkerboard.active {
+        return Ok(());
+    }
+    let settings = world.resource::<CheckerboardSettings>();
+    let frame_buffer = world.resource::<FrameBuffer>();
+
+    {
+        let mut compute_pass = ctx
+            .encoder
+            .begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some(ctx.label),
+                timestamp_writes: None,
+            });
+        compute_pass.set_pipeline(&checkerboard.pipeline.pipeline);
+        compute_pass.set_bind_group(0, &checkerboard.bind_groups[checkerboard.history()], &[]);
+        let [x, y, z] = checkerboard.workgroup_counts;
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+
+    // The reconstruction still runs for the reference, to keep its history
+    if settings.view != CheckerboardView::Reference {
+        ctx.encoder.copy_texture_to_texture(
+            checkerboard.display.texture.as_image_copy(),
+            frame_buffer.texture.texture.as_image_copy(),
+            frame_buffer.texture.texture.size(),
+        );
+    }
+
+    Ok(())
+}
+
+fn checkerboard_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<CheckerboardSettings>().clone();
+    let stereo = world
+        .get_resource::<StereoSettings>()
+        .is_some_and(|stereo| stereo.mode != StereoMode::Off);
+
+    egui::Window::new(tr("Checkerboard"))
+        .id(egui::Id::new("Checkerboard"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Checkerboard rendering"))
+                .on_hover_text(tr(
+                    "Draws every other pixel, alternating each frame, and fills in the rest from the last frame",
+                ));
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                for view in CheckerboardView::ALL {
+                    ui.radio_value(&mut settings.view, view, view.label());
+                }
+                ui.add_enabled(
+                    settings.view == CheckerboardView::Difference,
+                    egui::Slider::new(&mut settings.gain, 1.0..=64.0)
+                        .logarithmic(true)
+                        .text(tr("Difference gain")),
+                );
+                ui.checkbox(&mut settings.reproject, tr("Reproject the history"))
+                    .on_hover_text(tr("Follows the motion vectors instead of reusing the same pixel"));
+                ui.checkbox(&mut settings.clamp_history, tr("Clamp the history"))
+                    .on_hover_text(tr(
+                        "Keeps the history within the colors of the pixels drawn around it",
+                    ));
+            });
+            if stereo {
+                ui.label(tr("Paused while stereo rendering is on"));
+            }
+            ui.label(tr(
+                "Only meshes are checkerboarded, the full resolution mesh pass still runs as the reference",
+            ));
+            ui.label(tr("Compare the checkerboard and mesh passes in the profiler"));
+        });
+
+    let mut current = world.resource_mut::<CheckerboardSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== SETTINGS ===============================
+/// What the frame buffer shows. The order matches the shader's `VIEW_`
+/// constants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckerboardView {
+    /// The full resolution mesh pass, untouched.
+    Reference,
+    Reconstructed,
+    /// Only the pixels drawn this frame, the rest black.
+    Drawn,
+    /// How far the reconstruction is off the reference, amplified.
+    Difference,
+    /// The reference on the left, the reconstruction on the right.
+    Split,
+}
+impl CheckerboardView {
+    pub const ALL: [CheckerboardView; 5] = [
+        Self::Reference,
+        Self::Reconstructed,
+        Self::Drawn,
+        Self::Difference,
+        Self::Split,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Reference => tr("Full resolution"),
+            Self::Reconstructed => tr("Reconstructed"),
+            Self::Drawn => tr("Drawn this frame"),
+            Self::Difference => tr("Difference"),
+            Self::Split => tr("Split, full resolution on the left"),
+        }
+    }
+}
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct CheckerboardSettings {
+    pub enabled: bool,
+    pub view: CheckerboardView,
+    /// Multiplies the difference view.
+    pub gain: f32,
+    /// Follow the motion vectors into the history.
+    pub reproject: bool,
+    /// Clamp the history to the neighbors drawn this frame, which trades
+    /// ghosting for flicker on thin detail.
+    pub clamp_history: bool,
+}
+impl Default for CheckerboardSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            view: CheckerboardView::Reconstructed,
+            gain: 8.0,
+            reproject: true,
+            clamp_history: true,
+        }
+    }
+}
+
+// =============================== LATTICES ===============================
+/// The rows of the checkerboard, each a grid of every other pixel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lattice {
+    /// Drawn into the top of the half frame.
+    EvenRows,
+    /// Drawn into the bottom.
+    OddRows,
+}
+impl Lattice {
+    pub const ALL: [Lattice; 2] = [Self::EvenRows, Self::OddRows];
+
+    /// The pixel drawn for a half resolution pixel, in full resolution
+    /// pixels from the center of the two by two block it covers. Frames of
+    /// `parity` 0 draw the pixels where `x + y` is even, the others the odd
+    /// ones.
+    pub fn offset(self, parity: u32) -> Vec2 {
+        let (x, y) = match self {
+            Self::EvenRows => (parity, 0),
+            Self::OddRows => (1 - parity, 1),
+        };
+        Vec2::new(x as f32 - 0.5, y as f32 - 0.5)
+    }
+
+    /// Moves the image so the pixel at [`Lattice::offset`] lands where the
+    /// half resolution pixel is sampled.
+    pub fn jitter(self, parity: u32, width: u32, height: u32) -> Mat4 {
+        let offset = self.offset(parity);
+        Mat4::from_translation(Vec3::new(
+            -2.0 * offset.x / width as f32,
+            2.0 * offset.y / height as f32,
+            0.0,
+        ))
+    }
+
+    /// `[x, y, width, height]` in the half frame for a full frame of the
+    /// given size.
+    pub fn viewport(self, width: u32, height: u32) -> [f32; 4] {
+        let y = match self {
+            Self::EvenRows => 0.0,
+            Self::OddRows => height.div_ceil(2) as f32,
+        };
+        [0.0, y, width as f32 / 2.0, height as f32 / 2.0]
+    }
+}
+
+/// Width and height of the half frame, both lattices stacked.
+pub fn half_size(width: u32, height: u32) -> (u32, u32) {
+    (width.div_ceil(2), height.div_ceil(2) * 2)
+}
+
+// =============================== RESOURCES ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CheckerboardParams {
+    pub size: [u32; 2],
+    pub half_height: u32,
+    pub parity: u32,
+    pub view: u32,
+    pub flags: u32,
+    pub gain: f32,
+    pub _padding: u32,
+}
+
+#[derive(Resource)]
+pub struct Checkerboard {
+    /// The mesh pass targets at half size.
+    pub half_color: Texture,
+    half_velocity: Texture,
+    half_surface: Texture,
+    half_depth: Texture,
+    /// The frame buffer before the meshes.
+    background: Texture,
+    /// Alternately read and written, see [`Checkerboard::history`].
+    resolved: [Texture; 2],
+    display: Texture,
+    cameras: [CameraBuffer; 2],
+    /// This frame's and the last frame's view projection, without jitter.
+    view_proj: Option<(Mat4, Mat4)>,
+    frame: u64,
+    /// Drawing this frame.
+    pub active: bool,
+    /// The history holds the last frame's reconstruction.
+    warm: bool,
+    params: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    bind_groups: Vec<wgpu::BindGroup>,
+    pipeline: GPUComputePipeline,
+    workgroup_counts: [u32; 3],
+}
+impl Checkerboard {
+    pub fn new(gpu: &GpuContext, frame_buffer: &FrameBuffer) -> Result<Self> {
+        let device = &gpu.device;
+        let size = frame_buffer.texture.texture.size();
+        let (half_width, half_height) = half_size(size.width, size.height);
+        let half_target =
+            |format, label| Texture::render_target(device, half_width, half_height, format, label);
+        let resolved =
+            |label| Texture::storage(device, size.width, size.height, RESOLVED_FORMAT, label);
+
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: RESOLVED_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let float = wgpu::TextureSampleType::Float { filterable: true };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1, float),
+                texture_entry(2, float),
+                texture_entry(3, wgpu::TextureSampleType::Depth),
+                texture_entry(4, float),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture_entry(6, float),
+                texture_entry(7, float),
+                storage_entry(8),
+                storage_entry(9),
+            ],
+            label: Some("checkerboard_bind_group_layout"),
+        });
+
+        let source = load_shader_source(SHADER_NAME, include_str!("../shaders/checkerboard.wgsl"));
+        let pipeline = GPUComputePipeline::new(
+            device,
+            "checkerboard_pipeline",
+            &source,
+            "cs_main",
+            &[&layout],
+            gpu.pipeline_cache(),
+        )?;
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("checkerboard_params_buffer"),
+            size: std::mem::size_of::<CheckerboardParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("checkerboard_history_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let mut checkerboard = Self {
+            half_color: half_target(wgpu::TextureFormat::Rgba16Float, "checkerboard_half_color"),
+            half_velocity: half_target(VELOCITY_FORMAT, "checkerboard_half_velocity"),
+            half_surface: half_target(SURFACE_FORMAT, "checkerboard_half_surface"),
+            half_depth: Texture::depth_texture(device, half_width, half_height),
+            background: Texture::frame_buffer_texture(
+                device,
+                size.width,
+                size.height,
+                Some("checkerboard_background"),
+                1,
+            ),
+            resolved: [
+                resolved("checkerboard_resolved_a"),
+                resolved("checkerboard_resolved_b"),
+            ],
+            display: resolved("checkerboard_display"),
+            cameras: [CameraBuffer::new(gpu), CameraBuffer::new(gpu)],
+            view_proj: None,
+            frame: 0,
+            active: false,
+            warm: false,
+            params,
+            layout,
+            sampler,
+            bind_groups: Vec::new(),
+            pipeline,
+            workgroup_counts: [0; 3],
+        };
+        checkerboard.resize(gpu, frame_buffer);
+        Ok(checkerboard)
+    }
+
+    /// Resizes the targets if the frame buffer changed size, and rebuilds
+    /// the bind groups either way.
+    pub fn resize(&mut self, gpu: &GpuContext, frame_buffer: &FrameBuffer) {
+        let size = frame_buffer.texture.texture.size();
+        let current = self.background.texture.size();
+        if size.width != current.width || size.height != current.height {
+            let (half_width, half_height) = half_size(size.width, size.height);
+            for texture in [
+                &mut self.half_color,
+                &mut self.half_velocity,
+                &mut self.half_surface,
+                &mut self.half_depth,
+            ] {
+                texture.resize(&gpu.device, &gpu.queue, half_width, half_height);
+            }
+            let [first, second] = &mut self.resolved;
+            for texture in [&mut self.background, first, second, &mut self.display] {
+                texture.resize(&gpu.device, &gpu.queue, size.width, size.height);
+            }
+            self.warm = false;
+        }
+
+        let site = DispatchSite {
+            label: "checkerboard",
+            domain: [size.width, size.height, 1],
+        };
+        self.workgroup_counts = site.workgroup_counts(self.pipeline.workgroup_size);
+        self.bind_groups = (0..2)
+            .map(|history| self.create_bind_group(gpu, frame_buffer, history))
+            .collect();
+    }
+
+    /// Reads the history from `resolved[history]` and writes the other one.
+    fn create_bind_group(
+        &self,
+        gpu: &GpuContext,
+        frame_buffer: &FrameBuffer,
+        history: usize,
+    ) -> wgpu::BindGroup {
+        fn view(binding: u32, texture: &Texture) -> wgpu::BindGroupEntry<'_> {
+            wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            }
+        }
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.as_entire_binding(),
+                },
+                view(1, &self.half_color),
+                view(2, &self.half_velocity),
+                view(3, &self.half_depth),
+                view(4, &self.resolved[history]),
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                view(6, &self.background),
+                view(7, &frame_buffer.texture),
+                view(8, &self.resolved[1 - history]),
+                view(9, &self.display),
+            ],
+            label: Some("checkerboard_bind_group"),
+        })
+    }
+
+    /// Which pixels this frame draws, 0 for those where `x + y` is even.
+    pub fn parity(&self) -> u32 {
+        (self.frame % 2) as u32
+    }
+
+    /// Where the last frame's reconstruction is, the bind group to use.
+    fn history(&self) -> usize {
+        self.parity() as usize
+    }
+
+    /// Uploads both lattices' cameras for a new frame.
+    pub fn write(&mut self, gpu: &GpuContext, camera: &Camera) {
+        let view_proj = camera.view_projection();
+        let previous = self.view_proj.map_or(view_proj, |(current, _)| current);
+        self.upload(gpu, camera.eye, view_proj, previous);
+    }
+
+    /// Like [`CameraBuffer::rewrite`], for both lattices.
+    pub fn rewrite(&mut self, gpu: &GpuContext, camera: &Camera) {
+        let view_proj = camera.view_projection();
+        let previous = self.view_proj.map_or(view_proj, |(_, previous)| previous);
+        self.upload(gpu, camera.eye, view_proj, previous);
+    }
+
+    /// Both frames get the same jitter, so the motion vectors leave it out.
+    fn upload(&mut self, gpu: &GpuContext, eye: Vec3, view_proj: Mat4, previous: Mat4) {
+        self.view_proj = Some((view_proj, previous));
+        let size = self.background.texture.size();
+        let parity = self.parity();
+        for lattice in Lattice::ALL {
+            let jitter = lattice.jitter(parity, size.width, size.height);
+            self.cameras[lattice as usize].write_with_previous(
+                gpu,
+                jitter * view_proj,
+                eye,
+                jitter * previous,
+            );
+        }
+    }
+
+    fn write_params(&self, gpu: &GpuContext, settings: &CheckerboardSettings) {
+        let size = self.background.texture.size();
+        let mut flags = 0;
+        if self.warm {
+            flags |= FLAG_HISTORY;
+        }
+        if settings.reproject {
+            flags |= FLAG_REPROJECT;
+        }
+        if settings.clamp_history {
+            flags |= FLAG_CLAMP;
+        }
+        let params = CheckerboardParams {
+            size: [size.width, size.height],
+            half_height: size.height.div_ceil(2),
+            parity: self.parity(),
+            view: settings.view as u32,
+            flags,
+            gain: settings.gain,
+            _padding: 0,
+        };
+        gpu.queue
+            .write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec4Swizzles;
+
+    use super::*;
+
+    /// The shader's `drawn`.
+    fn drawn(x: u32, y: u32, parity: u32) -> bool {
+        (x + y) % 2 == parity
+    }
+
+    /// The shader's `half_texel`.
+    fn half_texel(x: u32, y: u32, height: u32) -> (u32, u32) {
+        (x / 2, y / 2 + (y % 2) * height.div_ceil(2))
+    }
+
+    #[test]
+    fn lattices_draw_each_pixel_once_every_two_frames() {
+        // Odd on purpose, the last row and column only half exist
+        let (width, height) = (7, 5);
+        let (half_width, half_height) = half_size(width, height);
+        let mut drawn_count = vec![0; (width * height) as usize];
+        for parity in [0, 1] {
+            for lattice in Lattice::ALL {
+                let [_, top, ..] = lattice.viewport(width, height);
+                for row in 0..half_height / 2 {
+                    for column in 0..half_width {
+                        // Sampled at the center of a half resolution pixel,
+                        // two full resolution pixels wide
+                        let center = Vec2::new(column as f32 + 0.5, row as f32 + 0.5) * 2.0;
+                        let pixel = (center + lattice.offset(parity)).floor();
+                        let (x, y) = (pixel.x as u32, pixel.y as u32);
+                        if x >= width || y >= height {
+                            continue;
+                        }
+                        assert!(drawn(x, y, parity), "{lattice:?} drew {x}, {y}");
+                        let texel = (column, row + top as u32);
+                        assert_eq!(half_texel(x, y, height), texel);
+                        drawn_count[(y * width + x) as usize] += 1;
+                    }
+                }
+            }
+        }
+        assert!(
+            drawn_count.iter().all(|&count| count == 1),
+            "{drawn_count:?}"
+        );
+
+        // The jitter brings the pixel at the offset to the sample position
+        let lattice = Lattice::OddRows;
+        let offset = lattice.offset(0);
+        let clip = lattice.jitter(0, width, height) * glam::Vec4::new(0.0, 0.0, 0.5, 1.0);
+        let ndc = clip.xy() / clip.w;
+        let moved = Vec2::new(ndc.x, -ndc.y) * Vec2::new(width as f32, height as f32) / 2.0;
+        assert!((moved + offset).length() < 1e-5);
+    }
+}
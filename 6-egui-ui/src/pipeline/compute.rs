@@ -0,0 +1,173 @@
+use anyhow::Result;
+use pollster::FutureExt;
+use tracing::warn;
+
+use std::time::Instant;
+
+use crate::{
+    shader::{parse_wgsl, workgroup_size},
+    shader_log::{self, EntryKind, ShaderError},
+};
+
+// =============================== PIPELINE ===============================
+pub struct GPUComputePipeline {
+    pub pipeline: wgpu::ComputePipeline,
+    #[allow(unused)]
+    pub pipeline_layout: wgpu::PipelineLayout,
+    pub workgroup_size: [u32; 3],
+}
+
+impl GPUComputePipeline {
+    /// Compiles `source` and reflects the workgroup size of `entry_point`.
+    ///
+    /// The shader is validated with naga first and the pipeline is created inside
+    /// an error scope, so a broken shader returns an error instead of panicking.
+    pub fn new(
+        device: &wgpu::Device,
+        label: &str,
+        source: &str,
+        entry_point: &str,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        cache: Option<&wgpu::PipelineCache>,
+    ) -> Result<Self> {
+        let started = Instant::now();
+        let module = parse_wgsl(label, source)?;
+        let workgroup_size = workgroup_size(&module, entry_point)?;
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some(entry_point),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache,
+        });
+        if let Some(error) = device.pop_error_scope().block_on() {
+            let message = format!("Failed to create compute pipeline '{}': {}", label, error);
+            let error = ShaderError {
+                message: error.to_string(),
+                details: message.clone(),
+                location: None,
+            };
+            shader_log::record(EntryKind::ComputePipeline, label, started, Some(error));
+            anyhow::bail!(message);
+        }
+        shader_log::record(EntryKind::ComputePipeline, label, started, None);
+
+        Ok(Self {
+            pipeline,
+            pipeline_layout,
+            workgroup_size,
+        })
+    }
+}
+
+// =============================== DISPATCH ===============================
+/// A place in the code that dispatches a compute pipeline over a domain of
+/// invocations. Workgroup counts are derived from the reflected workgroup size,
+/// so they stay correct when a hot-reloaded shader changes `@workgroup_size`.
+pub struct DispatchSite {
+    pub label: &'static str,
+    pub domain: [u32; 3],
+}
+
+impl DispatchSite {
+    pub fn workgroup_counts(&self, workgroup_size: [u32; 3]) -> [u32; 3] {
+        [0, 1, 2].map(|i| self.domain[i].div_ceil(workgroup_size[i].max(1)))
+    }
+
+    /// Checks the dispatch against the device limits, returning the workgroup
+    /// counts or an actionable description of what is wrong.
+    pub fn validate(&self, workgroup_size: [u32; 3], limits: &wgpu::Limits) -> Result<[u32; 3]> {
+        let max_size = [
+            limits.max_compute_workgroup_size_x,
+            limits.max_compute_workgroup_size_y,
+            limits.max_compute_workgroup_size_z,
+        ];
+        for (axis, (size, max)) in ["x", "y", "z"].iter().zip(workgroup_size.iter().zip(max_size)) {
+            if *size > max {
+                anyhow::bail!(
+                    "Dispatch '{}': @workgroup_size {} = {} exceeds the device limit of {}",
+                    self.label,
+                    axis,
+                    size,
+                    max
+                );
+            }
+        }
+
+        let invocations: u32 = workgroup_size.iter().product();
+        if invocations > limits.max_compute_invocations_per_workgroup {
+            anyhow::bail!(
+                "Dispatch '{}': @workgroup_size{:?} has {} invocations, the device allows {}; shrink the workgroup",
+                self.label,
+                workgroup_size,
+                invocations,
+                limits.max_compute_invocations_per_workgroup
+            );
+        }
+
+        let counts = self.workgroup_counts(workgroup_size);
+        for (axis, count) in ["x", "y", "z"].iter().zip(counts) {
+            if count > limits.max_compute_workgroups_per_dimension {
+                anyhow::bail!(
+                    "Dispatch '{}': domain {:?} needs {} workgroups along {} but the limit is {}; increase @workgroup_size {}",
+                    self.label,
+                    self.domain,
+                    count,
+                    axis,
+                    limits.max_compute_workgroups_per_dimension,
+                    axis
+                );
+            }
+        }
+        for (axis, (size, extent)) in ["x", "y", "z"]
+            .iter()
+            .zip(workgroup_size.iter().zip(self.domain))
+        {
+            if *size > 1 && extent == 1 {
+                warn!(
+                    "Dispatch '{}': @workgroup_size {} = {} but the domain is 1 wide along {}, {} of every {} invocations are wasted",
+                    self.label,
+                    axis,
+                    size,
+                    axis,
+                    size - 1,
+                    size
+                );
+            }
+        }
+
+        Ok(counts)
+    }
+}
+
+// =============================== READBACK ===============================
+/// Maps `buffer` and copies its contents out, blocking until the GPU is done
+/// with it. Meant for one-off results, not per-frame data.
+pub fn read_buffer<T: bytemuck::Pod>(
+    device: &wgpu::Device,
+    buffer: &wgpu::Buffer,
+) -> Result<Vec<T>> {
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv()??;
+
+    let data = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+    buffer.unmap();
+    Ok(data)
+}
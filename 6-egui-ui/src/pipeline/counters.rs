@@ -0,0 +1,323 @@
+//! GPU counters: compute shaders count things like visible instances or
+//! collisions into a small storage buffer, which is copied into a ring of
+//! readback buffers at the end of the frame. A copy is mapped once the GPU
+//! is done with it, so the numbers show up a frame or two late but the CPU
+//! never waits on the GPU for them.
+//!
+//! Each producer registers a group of counters at setup and binds its slice
+//! of the buffer, where counter `i` is the `i`th `atomic<u32>`. The counters
+//! start every frame at zero.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{ResMut, Resource},
+    world::World,
+};
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+};
+
+use super::{graph::PassContext, render::submit_system, ui::UiPanels};
+
+/// Bytes of counters all groups share.
+const CAPACITY: u64 = 4096;
+/// Readback buffers in flight. A copy is skipped when all of them are still
+/// waiting on the GPU.
+const RING_SIZE: usize = 3;
+
+pub fn setup_gpu_counters(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let counters = GpuCounters::new(gpu);
+
+    world.insert_resource(counters);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(gpu_counters_panel);
+
+    schedule.add_systems(gpu_counters_readback_system.after(submit_system));
+
+    Ok(())
+}
+
+/// Copies this frame's counts into a free readback buffer and zeroes the
+/// counters for the next frame. Runs after every pass that counts; async
+/// compute passes of the next frame are submitted after it too.
+pub fn gpu_counters_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let mut counters = world.resource_mut::<GpuCounters>();
+    let counters = &mut *counters;
+    if counters.layout.groups.is_empty() {
+        return Ok(());
+    }
+    counters.frame += 1;
+    match counters
+        .ring
+        .iter_mut()
+        .find(|slot| slot.state == SlotState::Free)
+    {
+        Some(slot) => {
+            ctx.encoder.copy_buffer_to_buffer(
+                &counters.buffer,
+                0,
+                &slot.buffer,
+                0,
+                counters.layout.used(),
+            );
+            slot.state = SlotState::Copied(counters.frame);
+        }
+        None => counters.skipped += 1,
+    }
+    ctx.encoder.clear_buffer(&counters.buffer, 0, None);
+
+    Ok(())
+}
+
+/// Starts mapping what the pass copied and takes whatever finished mapping
+/// since, without ever waiting on the GPU. The mappings finish as the
+/// [`crate::gpu::PollStrategy`] polls.
+pub fn gpu_counters_readback_system(mut counters: ResMut<GpuCounters>) {
+    let counters = &mut *counters;
+    let size = counters.layout.used();
+    for slot in &mut counters.ring {
+        match slot.state {
+            SlotState::Free => {}
+            SlotState::Copied(frame) => {
+                let mapped = slot.mapped.clone();
+                slot.buffer
+                    .slice(..size)
+                    .map_async(wgpu::MapMode::Read, move |result| {
+                        *mapped.lock().unwrap() = Some(result.is_ok());
+                    });
+                slot.state = SlotState::Mapping(frame);
+            }
+            SlotState::Mapping(frame) => {
+                let Some(ok) = slot.mapped.lock().unwrap().take() else {
+                    continue;
+                };
+                if ok {
+                    // Mapping can finish out of order, older counts are dropped
+                    if counters.latest.as_ref().is_none_or(|(f, _)| *f < frame) {
+                        let data = slot.buffer.slice(..size).get_mapped_range();
+                        counters.latest = Some((frame, bytemuck::cast_slice(&data).to_vec()));
+                    }
+                    slot.buffer.unmap();
+                }
+                slot.state = SlotState::Free;
+            }
+        }
+    }
+}
+
+fn gpu_counters_panel(ctx: &egui::Context, world: &mut World) {
+    let counters = world.resource::<GpuCounters>();
+
+    egui::Window::new(tr("GPU counters"))
+        .id(egui::Id::new("GPU counters"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            let Some(frame) = counters.latest_frame() else {
+                ui.label(tr("Nothing read back yet"));
+                return;
+            };
+            ui.label(trf!("Counted {} frames ago", counters.frame - frame));
+            egui::Grid::new("gpu_counters")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    for group in &counters.layout.groups {
+                        ui.strong(tr(group.label));
+                        ui.end_row();
+                        for (index, name) in group.names.iter().enumerate() {
+                            ui.label(tr(name));
+                            let value = counters.value(group, index);
+                            ui.label(value.map_or("-".to_string(), |value| value.to_string()));
+                            ui.end_row();
+                        }
+                    }
+                });
+            ui.label(trf!(
+                "{} copies skipped while every readback buffer was busy",
+                counters.skipped
+            ));
+        });
+}
+
+// =============================== LAYOUT ===============================
+/// Where a group's counters live in the counter buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CounterSlot {
+    offset: u64,
+    size: u64,
+}
+
+#[derive(Debug)]
+pub struct CounterGroup {
+    pub label: &'static str,
+    pub names: Vec<&'static str>,
+    pub slot: CounterSlot,
+}
+
+/// Hands out slices of the counter buffer, each starting at an offset
+/// storage buffers can be bound at.
+#[derive(Debug)]
+pub struct CounterLayout {
+    alignment: u64,
+    capacity: u64,
+    pub groups: Vec<CounterGroup>,
+}
+impl CounterLayout {
+    pub fn new(alignment: u64, capacity: u64) -> Self {
+        Self {
+            alignment,
+            capacity,
+            groups: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, label: &'static str, names: &[&'static str]) -> Result<CounterSlot> {
+        let offset = self.used().next_multiple_of(self.alignment);
+        let size = (names.len().max(1) * std::mem::size_of::<u32>()) as u64;
+        if offset + size > self.capacity {
+            anyhow::bail!(
+                "GPU counters '{}' don't fit, {} of {} bytes are taken",
+                label,
+                offset,
+                self.capacity
+            );
+        }
+        let slot = CounterSlot { offset, size };
+        self.groups.push(CounterGroup {
+            label,
+            names: names.to_vec(),
+            slot,
+        });
+        Ok(slot)
+    }
+
+    /// Bytes up to the end of the last group, which is all a copy needs.
+    pub fn used(&self) -> u64 {
+        self.groups
+            .last()
+            .map_or(0, |group| group.slot.offset + group.slot.size)
+    }
+}
+
+// =============================== COUNTERS ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SlotState {
+    Free,
+    /// Counts of the given frame were copied in and submitted.
+    Copied(u64),
+    Mapping(u64),
+}
+
+struct ReadbackSlot {
+    buffer: wgpu::Buffer,
+    state: SlotState,
+    /// Set by the map callback, `false` when mapping failed.
+    mapped: Arc<Mutex<Option<bool>>>,
+}
+
+#[derive(Resource)]
+pub struct GpuCounters {
+    buffer: wgpu::Buffer,
+    layout: CounterLayout,
+    ring: Vec<ReadbackSlot>,
+    /// Frames the pass ran for.
+    frame: u64,
+    /// The newest counts read back, as every word up to the last group.
+    latest: Option<(u64, Vec<u32>)>,
+    skipped: u64,
+}
+impl GpuCounters {
+    pub fn new(gpu: &GpuContext) -> Self {
+        let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu_counters"),
+            size: CAPACITY,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let ring = (0..RING_SIZE)
+            .map(|i| ReadbackSlot {
+                buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!("gpu_counters_readback_{}", i)),
+                    size: CAPACITY,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                state: SlotState::Free,
+                mapped: Arc::new(Mutex::new(None)),
+            })
+            .collect();
+        let alignment = gpu.device.limits().min_storage_buffer_offset_alignment as u64;
+
+        Self {
+            buffer,
+            layout: CounterLayout::new(alignment, CAPACITY),
+            ring,
+            frame: 0,
+            latest: None,
+            skipped: 0,
+        }
+    }
+
+    /// Reserves a counter per name, shown under `label`. Call it at setup,
+    /// before the first frame.
+    pub fn register(&mut self, label: &'static str, names: &[&'static str]) -> Result<CounterSlot> {
+        self.layout.register(label, names)
+    }
+
+    /// What a shader binds as `var<storage, read_write> counters: array<atomic<u32>>`.
+    pub fn binding(&self, slot: CounterSlot) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: &self.buffer,
+            offset: slot.offset,
+            size: wgpu::BufferSize::new(slot.size),
+        })
+    }
+
+    pub fn latest_frame(&self) -> Option<u64> {
+        self.latest.as_ref().map(|(frame, _)| *frame)
+    }
+
+    /// The newest count read back for the `index`th counter of `group`.
+    pub fn value(&self, group: &CounterGroup, index: usize) -> Option<u32> {
+        let (_, words) = self.latest.as_ref()?;
+        let word = group.slot.offset as usize / std::mem::size_of::<u32>() + index;
+        words.get(word).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_start_at_bindable_offsets() {
+        let mut layout = CounterLayout::new(256, 1024);
+        let particles = layout.register("particles", &["a", "b"]).unwrap();
+        let cubes = layout.register("cubes", &["c"]).unwrap();
+        assert_eq!(particles, CounterSlot { offset: 0, size: 8 });
+        assert_eq!(
+            cubes,
+            CounterSlot {
+                offset: 256,
+                size: 4
+            }
+        );
+        assert_eq!(layout.used(), 260);
+
+        layout.register("more", &["d"]).unwrap();
+        layout.register("last", &["e", "f"]).unwrap();
+        assert!(layout.register("full", &["g"]).is_err());
+        assert_eq!(layout.groups.len(), 4);
+    }
+}
@@ -0,0 +1,225 @@
+use std::f32::consts::TAU;
+
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+use glam::{Vec3, Vec4};
+
+use crate::{gpu::GpuContext, pass::RenderPassBuilder, vertex::DebugVertex};
+
+use super::{
+    depth::DepthTexture, graph::PassContext, mesh::CameraBuffer, present::FrameBuffer, GPUPipeline,
+    GPUPipelineBuilder,
+};
+
+/// Segments used to approximate circles.
+const CIRCLE_SEGMENTS: usize = 24;
+
+pub fn setup_debug_draw(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let camera = world
+        .get_resource::<CameraBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("CameraBuffer resource not found"))?;
+
+    let pipeline = DebugDrawPipeline::new(gpu, camera)?;
+    let buffer = DebugDrawBuffer::new(gpu, 4096);
+
+    world.insert_resource(pipeline);
+    world.insert_resource(buffer);
+    world.init_resource::<DebugDraw>();
+
+    Ok(())
+}
+
+/// Uploads and draws everything queued on [`DebugDraw`] this frame, then
+/// clears the queue.
+pub fn debug_draw_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let vertices = std::mem::take(&mut world.resource_mut::<DebugDraw>().vertices);
+    if vertices.is_empty() {
+        return Ok(());
+    }
+    world.resource_scope::<DebugDrawBuffer, _>(|world, mut buffer| {
+        let gpu = world.resource::<GpuContext>();
+        buffer.upload(gpu, &vertices);
+    });
+
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let depth = world.resource::<DepthTexture>();
+    let pipeline = world.resource::<DebugDrawPipeline>();
+    let camera = world.resource::<CameraBuffer>();
+    let buffer = world.resource::<DebugDrawBuffer>();
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&frame_buffer.texture.view)
+        .with_depth(&depth.texture.view, 1.0)
+        .load()
+        .build()?;
+
+    render_pass.set_pipeline(&pipeline.pipeline.render_pipeline);
+    render_pass.set_bind_group(0, &camera.bind_group, &[]);
+    render_pass.set_vertex_buffer(0, buffer.buffer.slice(..));
+    render_pass.draw(0..vertices.len() as u32, 0..1);
+
+    Ok(())
+}
+
+// =============================== API ===============================
+/// Immediate-mode line drawing. Anything queued is drawn once, at the end of
+/// the current frame, depth tested against the scene.
+#[derive(Resource, Default)]
+pub struct DebugDraw {
+    pub vertices: Vec<DebugVertex>,
+}
+impl DebugDraw {
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: Vec4) {
+        let color = color.to_array();
+        self.vertices.push(DebugVertex {
+            position: start.to_array(),
+            color,
+        });
+        self.vertices.push(DebugVertex {
+            position: end.to_array(),
+            color,
+        });
+    }
+
+    pub fn arrow(&mut self, start: Vec3, end: Vec3, color: Vec4) {
+        self.line(start, end, color);
+        let direction = end - start;
+        let length = direction.length();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let direction = direction / length;
+        let (right, up) = direction.any_orthonormal_pair();
+        let head = length.min(1.0) * 0.2;
+        for side in [right, -right, up, -up] {
+            self.line(end, end - direction * head + side * head * 0.5, color);
+        }
+    }
+
+    pub fn circle(&mut self, center: Vec3, normal: Vec3, radius: f32, color: Vec4) {
+        let (u, v) = normal.normalize_or(Vec3::Y).any_orthonormal_pair();
+        let point = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * TAU;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+
+    pub fn wire_sphere(&mut self, center: Vec3, radius: f32, color: Vec4) {
+        for normal in [Vec3::X, Vec3::Y, Vec3::Z] {
+            self.circle(center, normal, radius, color);
+        }
+    }
+
+    pub fn wire_box(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        let corner = |i: usize| {
+            Vec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        // Each edge joins two corners that differ in exactly one axis bit
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    }
+
+    /// A cone with its apex at `apex`, opening along `direction`.
+    pub fn cone(&mut self, apex: Vec3, direction: Vec3, length: f32, angle: f32, color: Vec4) {
+        let direction = direction.normalize_or(Vec3::NEG_Z);
+        let base = apex + direction * length;
+        let radius = length * angle.tan();
+        self.circle(base, direction, radius, color);
+        let (u, v) = direction.any_orthonormal_pair();
+        for side in [u, -u, v, -v] {
+            self.line(apex, base + side * radius, color);
+        }
+    }
+}
+
+// =============================== BUFFER ===============================
+#[derive(Resource)]
+pub struct DebugDrawBuffer {
+    pub buffer: wgpu::Buffer,
+    pub capacity: usize,
+}
+impl DebugDrawBuffer {
+    pub fn new(gpu: &GpuContext, capacity: usize) -> Self {
+        Self {
+            buffer: Self::create(gpu, capacity),
+            capacity,
+        }
+    }
+
+    fn create(gpu: &GpuContext, capacity: usize) -> wgpu::Buffer {
+        gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("debug_draw_buffer"),
+            size: (capacity * std::mem::size_of::<DebugVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn upload(&mut self, gpu: &GpuContext, vertices: &[DebugVertex]) {
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.buffer = Self::create(gpu, self.capacity);
+        }
+        gpu.queue
+            .write_buffer(&self.buffer, 0, bytemuck::cast_slice(vertices));
+    }
+}
+
+// =============================== PIPELINE ===============================
+#[derive(Resource)]
+pub struct DebugDrawPipeline {
+    pub pipeline: GPUPipeline,
+}
+impl DebugDrawPipeline {
+    pub fn new(gpu: &GpuContext, camera: &CameraBuffer) -> Result<Self> {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("debug_draw_shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/debug_draw.wgsl").into()),
+            });
+        let pipeline = GPUPipelineBuilder::new(&gpu.device)
+            .label("debug_draw_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&camera.layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .vertex_buffer_layout(DebugVertex::desc())
+            .color_target(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Rgba16Float,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .depth_stencil_state(Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }))
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            })
+            .build()?;
+
+        Ok(Self { pipeline })
+    }
+}
@@ -0,0 +1,512 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use bevy_ecs::{
+    observer::Trigger,
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use tracing::warn;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    assets::AssetServer,
+    gpu::GpuContext,
+    i18n::tr,
+    layout::LayoutCache,
+    pass::RenderPassBuilder,
+    scene::{draw_list_system, DrawCommand, DrawList, MeshId, PipelineId},
+    settings::{RendererSettings, RendererSettingsChanged},
+    shader::load_shader_source,
+    texture::Texture,
+    uniform::{DebugView, Uniforms},
+    vertex::MeshVertex,
+};
+
+use super::{
+    arena::{frame_arena_upload_system, FrameArena},
+    graph::PassContext,
+    inspector::TextureRegistry,
+    mesh::{CameraBuffer, MeshTriangles, Meshes},
+    present::{render_scale_system, FrameBuffer},
+    render::render_system,
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+/// Fragment and lane counts per pixel, summed by additive blending.
+pub const COUNTS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+pub fn setup_debug_views(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let resolve_layout = world.resource_scope::<LayoutCache, _>(|world, mut layouts| {
+        let gpu = world
+            .get_resource::<GpuContext>()
+            .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+        Ok::<_, anyhow::Error>(resolve_bind_group_layout(gpu, &mut layouts))
+    })?;
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let camera = world
+        .get_resource::<CameraBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("CameraBuffer resource not found"))?;
+    let arena = world
+        .get_resource::<FrameArena>()
+        .ok_or_else(|| anyhow::anyhow!("FrameArena resource not found"))?;
+    let frame_buffer = world
+        .get_resource::<FrameBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("FrameBuffer resource not found"))?;
+
+    let size = frame_buffer.texture.texture.size();
+    let counts = Texture::render_target(
+        &gpu.device,
+        size.width,
+        size.height,
+        COUNTS_FORMAT,
+        "debug_view_counts",
+    );
+    let settings = DebugViewSettings {
+        view: world
+            .get_resource::<RendererSettings>()
+            .map_or(DebugView::None, |settings| settings.debug_view),
+        ..Default::default()
+    };
+    let pipelines = DebugViewPipelines::new(gpu, camera, arena, resolve_layout, &settings)?;
+
+    world.insert_resource(DebugViewCounts { texture: counts });
+    world.insert_resource(pipelines);
+    world.insert_resource(settings);
+    world.init_resource::<DebugViewDraws>();
+    world.add_observer(debug_view_renderer_settings_observer);
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("debug_view_counts", |world| {
+            world
+                .get_resource::<DebugViewCounts>()
+                .map(|counts| &counts.texture.texture)
+        });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(debug_view_panel);
+
+    schedule.add_systems((
+        debug_view_settings_system
+            .run_if(resource_changed::<DebugViewSettings>)
+            .before(render_system),
+        debug_view_resize_system
+            .run_if(resource_changed::<FrameBuffer>)
+            .after(render_scale_system)
+            .before(render_system),
+        debug_view_prepare_system
+            .after(draw_list_system)
+            .before(frame_arena_upload_system)
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Hands the selected view to the shaders through [`Uniforms`].
+pub fn debug_view_settings_system(
+    gpu: Res<GpuContext>,
+    settings: Res<DebugViewSettings>,
+    pipelines: Res<DebugViewPipelines>,
+    mut uniforms: ResMut<Uniforms>,
+) {
+    uniforms.update_debug_view(&gpu, settings.view);
+    gpu.queue.write_buffer(
+        &pipelines.params,
+        0,
+        bytemuck::bytes_of(&DebugViewParams::new(&settings)),
+    );
+}
+
+/// The view is picked in [`RendererSettings`], so it is kept between runs.
+pub fn debug_view_renderer_settings_observer(
+    trigger: Trigger<RendererSettingsChanged>,
+    mut settings: ResMut<DebugViewSettings>,
+) {
+    let view = trigger.event().current.debug_view;
+    if settings.view != view {
+        settings.view = view;
+    }
+}
+
+pub fn debug_view_resize_system(
+    gpu: Res<GpuContext>,
+    frame_buffer: Res<FrameBuffer>,
+    mut counts: ResMut<DebugViewCounts>,
+) {
+    let size = frame_buffer.texture.texture.size();
+    let current = counts.texture.texture.size();
+    if size.width == current.width && size.height == current.height {
+        return;
+    }
+    counts
+        .texture
+        .resize(&gpu.device, &gpu.queue, size.width, size.height);
+}
+
+/// Allocates a uniform for every instance of the draw list, the triangles
+/// take up the instance index in the counting pass.
+#[allow(clippy::too_many_arguments)]
+pub fn debug_view_prepare_system(
+    gpu: Res<GpuContext>,
+    assets: Res<AssetServer>,
+    meshes: Res<Meshes>,
+    mut triangles: ResMut<MeshTriangles>,
+    settings: Res<DebugViewSettings>,
+    draw_list: Res<DrawList>,
+    frame_buffer: Res<FrameBuffer>,
+    mut arena: ResMut<FrameArena>,
+    mut draws: ResMut<DebugViewDraws>,
+) {
+    draws.draws.clear();
+    if settings.view == DebugView::None {
+        return;
+    }
+    let size = frame_buffer.texture.texture.size();
+    let resolution = [size.width as f32, size.height as f32, 0.0, 0.0];
+    let (mut pipeline, mut mesh) = (PipelineId::OPAQUE, None);
+    for command in &draw_list.commands {
+        match *command {
+            DrawCommand::SetPipeline(id) => pipeline = id,
+            DrawCommand::SetMaterial(_) => {}
+            DrawCommand::SetMesh(id) => mesh = Some(id),
+            DrawCommand::Draw {
+                first_instance,
+                instance_count,
+            } => {
+                let Some(mesh) = mesh else {
+                    continue;
+                };
+                if let Err(e) = triangles.upload(&gpu, &assets, &meshes, mesh) {
+                    warn!("Not counting {:?}: {}", mesh, e);
+                    continue;
+                }
+                let first = first_instance as usize;
+                for instance in &draw_list.instances[first..first + instance_count as usize] {
+                    let object = CountObject {
+                        model: instance.model,
+                        resolution,
+                    };
+                    draws.draws.push((pipeline, mesh, arena.alloc(&object)));
+                }
+            }
+        }
+    }
+}
+
+/// Counts the fragments of the draw list per pixel, then replaces the frame
+/// buffer with a heatmap of them. Nothing is depth tested, every fragment
+/// the rasterizer produces counts, hidden or not.
+pub fn debug_view_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    if world.resource::<DebugViewSettings>().view == DebugView::None {
+        return Ok(());
+    }
+    let gpu = world.resource::<GpuContext>();
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let counts = world.resource::<DebugViewCounts>();
+    let pipelines = world.resource::<DebugViewPipelines>();
+    let draws = world.resource::<DebugViewDraws>();
+    let camera = world.resource::<CameraBuffer>();
+    let arena = world.resource::<FrameArena>();
+    let triangles = world.resource::<MeshTriangles>();
+    let uniforms = world.resource::<Uniforms>();
+
+    {
+        let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+            .with_label("debug_view_counts")
+            .with_color_view(&counts.texture.view)
+            .build()?;
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        for &(pipeline, mesh, offset) in &draws.draws {
+            let (buffer, count) = triangles.get(mesh)?;
+            let pipeline = if pipeline == PipelineId::OPAQUE {
+                &pipelines.culled
+            } else {
+                &pipelines.double_sided
+            };
+            render_pass.set_pipeline(&pipeline.render_pipeline);
+            render_pass.set_bind_group(1, arena.bind_group(), &[offset]);
+            render_pass.set_vertex_buffer(0, buffer.slice(..));
+            render_pass.draw(0..3, 0..count);
+        }
+    }
+
+    // The counts follow the frame buffer size, so the bind group is made fresh
+    let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &pipelines.resolve_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&counts.texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: uniforms.buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: pipelines.params.as_entire_binding(),
+            },
+        ],
+        label: Some("debug_view_resolve_bind_group"),
+    });
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&frame_buffer.texture.view)
+        .build()?;
+    render_pass.set_pipeline(&pipelines.resolve.render_pipeline);
+    render_pass.set_bind_group(0, &bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+
+    Ok(())
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct DebugViewSettings {
+    pub view: DebugView,
+    /// Fragments per pixel at the white end of the overdraw scale.
+    pub max_overdraw: f32,
+}
+impl Default for DebugViewSettings {
+    fn default() -> Self {
+        Self {
+            view: DebugView::None,
+            max_overdraw: 8.0,
+        }
+    }
+}
+
+/// Red holds the fragments of each pixel, green the quad lanes shaded for
+/// them. Written only while a debug view is selected.
+#[derive(Resource)]
+pub struct DebugViewCounts {
+    pub texture: Texture,
+}
+
+#[derive(Resource, Default)]
+pub struct DebugViewDraws {
+    /// Pipeline, mesh and arena offset of every instance in the draw list.
+    pub draws: Vec<(PipelineId, MeshId, u32)>,
+}
+
+// =============================== PIPELINE ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CountObject {
+    pub model: [[f32; 4]; 4],
+    /// Frame buffer size in pixels, xy.
+    pub resolution: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugViewParams {
+    pub max_overdraw: f32,
+    pub _padding: [f32; 3],
+}
+impl DebugViewParams {
+    pub fn new(settings: &DebugViewSettings) -> Self {
+        Self {
+            max_overdraw: settings.max_overdraw,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+fn resolve_bind_group_layout(
+    gpu: &GpuContext,
+    layouts: &mut LayoutCache,
+) -> Arc<wgpu::BindGroupLayout> {
+    let uniform = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    layouts.get(
+        &gpu.device,
+        &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            uniform(1),
+            uniform(2),
+        ],
+    )
+}
+
+#[derive(Resource)]
+pub struct DebugViewPipelines {
+    /// Counting with back faces culled, like the opaque mesh pipeline.
+    pub culled: GPUPipeline,
+    /// Counting both faces, like the transparent mesh pipeline.
+    pub double_sided: GPUPipeline,
+    pub resolve: GPUPipeline,
+    pub resolve_layout: Arc<wgpu::BindGroupLayout>,
+    pub params: wgpu::Buffer,
+}
+impl DebugViewPipelines {
+    pub fn new(
+        gpu: &GpuContext,
+        camera: &CameraBuffer,
+        arena: &FrameArena,
+        resolve_layout: Arc<wgpu::BindGroupLayout>,
+        settings: &DebugViewSettings,
+    ) -> Result<Self> {
+        let source = load_shader_source("overdraw.wgsl", include_str!("../shaders/overdraw.wgsl"));
+        let count_shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("overdraw_shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let counting = |label, cull_mode| {
+            GPUPipelineBuilder::new(&gpu.device)
+                .label(label)
+                .pipeline_cache(gpu.pipeline_cache())
+                .bind_group_layout(&camera.layout)
+                .bind_group_layout(&arena.layout)
+                .vertex_shader(&count_shader, "vs_main")
+                .fragment_shader(&count_shader, "fs_main")
+                .vertex_buffer_layout(MeshVertex::triangle_desc())
+                .color_target(wgpu::ColorTargetState {
+                    format: COUNTS_FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::One,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent::REPLACE,
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })
+                .default_multisample_state()
+                .primitive_state(wgpu::PrimitiveState {
+                    cull_mode,
+                    ..Default::default()
+                })
+                .build()
+                .map_err(|e| anyhow::anyhow!(e))
+        };
+        let culled = counting("overdraw_culled_pipeline", Some(wgpu::Face::Back))?;
+        let double_sided = counting("overdraw_double_sided_pipeline", None)?;
+
+        let source = load_shader_source(
+            "debug_view.wgsl",
+            include_str!("../shaders/debug_view.wgsl"),
+        );
+        let resolve_shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("debug_view_shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let resolve = GPUPipelineBuilder::new(&gpu.device)
+            .label("debug_view_resolve_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&resolve_layout)
+            .vertex_shader(&resolve_shader, "vs_main")
+            .fragment_shader(&resolve_shader, "fs_main")
+            .color_target(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Rgba16Float,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState::default())
+            .build()?;
+
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("debug_view_params"),
+                contents: bytemuck::bytes_of(&DebugViewParams::new(settings)),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        Ok(Self {
+            culled,
+            double_sided,
+            resolve,
+            resolve_layout,
+            params,
+        })
+    }
+}
+
+// =============================== UI ===============================
+fn debug_view_label(view: DebugView) -> &'static str {
+    match view {
+        DebugView::None => tr("Shaded"),
+        DebugView::Overdraw => tr("Overdraw"),
+        DebugView::QuadOccupancy => tr("Quad occupancy"),
+    }
+}
+
+fn debug_view_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<DebugViewSettings>().clone();
+    let mut view = world.resource::<RendererSettings>().debug_view;
+
+    egui::Window::new(tr("Debug views"))
+        .id(egui::Id::new("Debug views"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            egui::ComboBox::from_label(tr("View"))
+                .selected_text(debug_view_label(view))
+                .show_ui(ui, |ui| {
+                    for option in [
+                        DebugView::None,
+                        DebugView::Overdraw,
+                        DebugView::QuadOccupancy,
+                    ] {
+                        ui.selectable_value(&mut view, option, debug_view_label(option));
+                    }
+                });
+            ui.add(
+                egui::Slider::new(&mut settings.max_overdraw, 1.0..=64.0)
+                    .logarithmic(true)
+                    .text(tr("Overdraw scale")),
+            )
+            .on_hover_text(tr("Fragments per pixel shown as white"));
+            match view {
+                DebugView::None => {}
+                DebugView::Overdraw => {
+                    ui.label(tr(
+                        "Every fragment of the scene meshes counts, hidden ones too. Black, blue, green, yellow, red, then white as the count grows",
+                    ));
+                }
+                DebugView::QuadOccupancy => {
+                    ui.label(tr(
+                        "GPUs shade 2x2 pixel quads. Green quads are fully covered by their triangle, red ones run mostly helper lanes on thin or small triangles",
+                    ));
+                }
+            }
+        });
+
+    let mut current = world.resource_mut::<DebugViewSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+    let mut renderer = world.resource_mut::<RendererSettings>();
+    if renderer.debug_view != view {
+        renderer.debug_view = view;
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use anyhow::Result;
 use bevy_ecs::{
     observer::Trigger,
@@ -8,18 +10,26 @@ use bevy_ecs::{
 };
 
 use crate::{
+    layout::LayoutCache,
+    pass::RenderPassBuilder,
     texture::Texture,
     uniform::{Uniforms, UniformsData},
-    vertex::DepthVertex,
+    vertex::{DepthVertex, VertexBuffers},
     GpuContext,
 };
 
 use super::{
+    graph::PassContext,
+    inspector::TextureRegistry,
     present::{FrameBuffer, PresentBindGroup, PresentBindGroupLayout, PresentPipeline},
     GPUPipeline, GPUPipelineBuilder,
 };
 
 pub fn setup_depth(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let depth_bind_group_layout =
+        world.resource_scope::<LayoutCache, _>(|world, mut layouts| {
+            DepthBindGroupLayout::new(world.resource::<GpuContext>(), &mut layouts)
+        })?;
     let gpu = world
         .get_resource::<GpuContext>()
         .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
@@ -29,7 +39,6 @@ pub fn setup_depth(world: &mut World, schedule: &mut Schedule) -> Result<()> {
 
     let depth_texture = DepthTexture::new(&gpu, gpu.config.width, gpu.config.height)?;
 
-    let depth_bind_group_layout = DepthBindGroupLayout::new(&gpu)?;
     let depth_bind_group = DepthBindGroup::new(
         &gpu,
         &depth_texture,
@@ -39,8 +48,16 @@ pub fn setup_depth(world: &mut World, schedule: &mut Schedule) -> Result<()> {
     let depth_pipeline = DepthPipeline::new(&gpu, &depth_bind_group_layout)?;
     world.insert_resource(depth_bind_group_layout);
     world.insert_resource(depth_bind_group);
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("depth", |world| {
+            world
+                .get_resource::<DepthTexture>()
+                .map(|depth| &depth.texture.texture)
+        });
     world.insert_resource(depth_texture);
     world.insert_resource(depth_pipeline);
+    world.insert_resource(DepthPreview::default());
 
     schedule.add_systems(depth_changed_system.run_if(resource_changed::<DepthTexture>));
 
@@ -62,48 +79,77 @@ pub fn depth_changed_system(
     );
 }
 
+pub fn depth_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    if !world.resource::<DepthPreview>().enabled {
+        return Ok(());
+    }
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let pipeline = world.resource::<DepthPipeline>();
+    let bind_group = world.resource::<DepthBindGroup>();
+    let vertex_buffers = world.resource::<VertexBuffers>();
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&frame_buffer.texture.view)
+        .build()?;
+
+    render_pass.set_pipeline(&pipeline.pipeline.render_pipeline);
+    render_pass.set_bind_group(0, &bind_group.bind_group, &[]);
+    render_pass.set_vertex_buffer(0, vertex_buffers.depth_vertex_buffer.slice(..));
+    vertex_buffers.depth_index_buffer.bind(&mut render_pass);
+    vertex_buffers
+        .depth_index_buffer
+        .draw(&mut render_pass, 0..1);
+
+    Ok(())
+}
+
+/// Whether the depth buffer is drawn over the frame buffer for debugging.
+#[derive(Resource, Default, Clone, PartialEq)]
+pub struct DepthPreview {
+    pub enabled: bool,
+}
+
 // =============================== BIND GROUP ===============================
 #[derive(Resource)]
 pub struct DepthBindGroupLayout {
-    pub layout: wgpu::BindGroupLayout,
+    pub layout: Arc<wgpu::BindGroupLayout>,
 }
 impl DepthBindGroupLayout {
-    pub fn new(gpu: &GpuContext) -> Result<Self> {
-        let depth_layout = gpu
-            .device
-            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Depth,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        // This should match the filterable field of the
-                        // corresponding Texture entry above.
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
+    pub fn new(gpu: &GpuContext, layouts: &mut LayoutCache) -> Result<Self> {
+        let depth_layout = layouts.get(
+            &gpu.device,
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
                     },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    // This should match the filterable field of the
+                    // corresponding Texture entry above.
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
                     },
-                ],
-                label: Some("depth_bind_group_layout"),
-            });
+                    count: None,
+                },
+            ],
+        );
 
         Ok(Self {
             layout: depth_layout,
@@ -189,6 +235,7 @@ impl DepthPipeline {
             });
         let depth_pipeline = GPUPipelineBuilder::new(&gpu.device)
             .label("Depth Pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
             .bind_group_layout(&bind_group_layout.layout)
             .vertex_shader(&depth_shader, "vs_main")
             .fragment_shader(&depth_shader, "fs_main")
@@ -197,8 +244,7 @@ impl DepthPipeline {
             .depth_stencil_state(None)
             .default_multisample_state()
             .default_primitive_state()
-            .build()
-            .map_err(|e| anyhow::anyhow!(e))?;
+            .build()?;
 
         let result = Self {
             shader: depth_shader,
@@ -0,0 +1,665 @@
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{Condition, IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    layout::LayoutCache,
+    pass::RenderPassBuilder,
+    shader::load_shader_source,
+};
+
+use super::{
+    graph::PassContext,
+    present::{render_scale_system, FrameBuffer},
+    render::{render_system, submit_system},
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+/// Distances the panel lists the depth resolution at.
+const PROBE_DISTANCES: [f32; 5] = [1.0, 10.0, 100.0, 1000.0, 10000.0];
+/// Two planes per side, the upper and the lower one.
+const QUERY_COUNT: u32 = 4;
+
+pub fn setup_depth_precision(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let precision = world.resource_scope::<LayoutCache, _>(|world, mut layouts| {
+        let gpu = world
+            .get_resource::<GpuContext>()
+            .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+        DepthPrecision::new(gpu, &mut layouts)
+    })?;
+    world.insert_resource(precision);
+    world.insert_resource(DepthPrecisionSettings::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(depth_precision_panel);
+
+    schedule.add_systems((
+        depth_precision_update_system
+            .run_if(resource_changed::<FrameBuffer>.or(resource_changed::<DepthPrecisionSettings>))
+            .after(render_scale_system)
+            .before(render_system),
+        depth_precision_readback_system.after(submit_system),
+    ));
+
+    Ok(())
+}
+
+/// Creates the depth buffers in the compared formats while the tool is on,
+/// and keeps both cameras in step with the settings.
+pub fn depth_precision_update_system(
+    gpu: Res<GpuContext>,
+    frame_buffer: Res<FrameBuffer>,
+    settings: Res<DepthPrecisionSettings>,
+    mut precision: ResMut<DepthPrecision>,
+) {
+    if !settings.enabled {
+        precision.targets = None;
+        return;
+    }
+    let size = frame_buffer.texture.texture.size();
+    let setups = settings.comparison.setups();
+    let matches = precision.targets.as_ref().is_some_and(|targets| {
+        targets.setups == setups && targets.size == (size.width, size.height)
+    });
+    if !matches {
+        precision.targets = Some(DepthTargets::new(&gpu, setups, size.width, size.height));
+    }
+
+    let aspect = size.width as f32 / size.height.max(1) as f32;
+    for (setup, buffer) in setups.iter().zip(&precision.params) {
+        let params = DepthPrecisionParams::new(&settings, *setup, aspect);
+        gpu.queue
+            .write_buffer(buffer, 0, bytemuck::bytes_of(&params));
+    }
+}
+
+/// Maps the occlusion counts of the last measured frame once the GPU is done
+/// with them, without ever waiting on it.
+pub fn depth_precision_readback_system(mut precision: ResMut<DepthPrecision>) {
+    let queries = &mut precision.queries;
+    match queries.state {
+        QueryState::Idle => {}
+        QueryState::Resolved => {
+            let mapped = queries.mapped.clone();
+            queries
+                .readback_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    *mapped.lock().unwrap() = Some(result.is_ok());
+                });
+            queries.state = QueryState::Mapping;
+        }
+        QueryState::Mapping => {
+            let Some(ok) = queries.mapped.lock().unwrap().take() else {
+                return;
+            };
+            if ok {
+                let samples: Vec<u64> = {
+                    let data = queries.readback_buffer.slice(..).get_mapped_range();
+                    bytemuck::cast_slice(&data).to_vec()
+                };
+                queries.readback_buffer.unmap();
+                for (side, leaked) in queries.leaked.iter_mut().enumerate() {
+                    let (upper, lower) = (samples[side * 2], samples[side * 2 + 1]);
+                    *leaked = (upper > 0).then(|| lower as f64 / upper as f64);
+                }
+            }
+            queries.state = QueryState::Idle;
+        }
+    }
+}
+
+/// Draws the test scene over the frame buffer, one depth setup left of the
+/// divider and the other right of it.
+pub fn depth_precision_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    world.resource_scope::<DepthPrecision, _>(|world, mut precision| {
+        let settings = world.resource::<DepthPrecisionSettings>();
+        if !settings.enabled {
+            return Ok(());
+        }
+        let frame_buffer = world.resource::<FrameBuffer>();
+        let precision = &mut *precision;
+        let Some(targets) = &precision.targets else {
+            return Ok(());
+        };
+
+        let size = frame_buffer.texture.texture.size();
+        let measure = precision.queries.state == QueryState::Idle;
+        for (side, columns) in settings.columns(size.width).into_iter().enumerate() {
+            let setup = targets.setups[side];
+            // Each side starts from its own cleared depth, the color is only
+            // cleared once so the second side doesn't wipe the first
+            let mut clear = RenderPassBuilder::new(ctx.encoder)
+                .with_label("depth_precision_clear")
+                .with_depth(&targets.views[side], setup.clear_depth());
+            if side == 0 {
+                clear = clear.with_color_view(&frame_buffer.texture.view);
+            }
+            clear.build()?;
+
+            let mut builder = RenderPassBuilder::new(ctx.encoder)
+                .with_label(setup.label())
+                .with_color_view(&frame_buffer.texture.view)
+                .with_depth(&targets.views[side], setup.clear_depth())
+                .load();
+            if measure {
+                builder = builder.with_occlusion_queries(&precision.queries.query_set);
+            }
+            let mut render_pass = builder.build()?;
+            if columns.is_empty() {
+                continue;
+            }
+            render_pass.set_scissor_rect(columns.start, 0, columns.len() as u32, size.height);
+            render_pass.set_pipeline(&precision.pipeline(setup).render_pipeline);
+            render_pass.set_bind_group(0, &precision.bind_groups[side], &[]);
+            for layer in 0..2u32 {
+                if measure {
+                    render_pass.begin_occlusion_query(side as u32 * 2 + layer);
+                }
+                render_pass.draw(0..6, layer..layer + 1);
+                if measure {
+                    render_pass.end_occlusion_query();
+                }
+            }
+        }
+
+        if measure {
+            let queries = &mut precision.queries;
+            ctx.encoder.resolve_query_set(
+                &queries.query_set,
+                0..QUERY_COUNT,
+                &queries.resolve_buffer,
+                0,
+            );
+            ctx.encoder.copy_buffer_to_buffer(
+                &queries.resolve_buffer,
+                0,
+                &queries.readback_buffer,
+                0,
+                queries.resolve_buffer.size(),
+            );
+            queries.state = QueryState::Resolved;
+        }
+
+        Ok(())
+    })
+}
+
+fn depth_precision_panel(ctx: &egui::Context, world: &mut World) {
+    let leaked = world.resource::<DepthPrecision>().queries.leaked;
+    let mut settings = world.resource::<DepthPrecisionSettings>().clone();
+
+    egui::Window::new(tr("Depth precision"))
+        .id(egui::Id::new("Depth precision"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Enabled"))
+                .on_hover_text(tr(
+                    "Magenta shows through wherever depth precision runs out",
+                ));
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                egui::ComboBox::from_label(tr("Compare"))
+                    .selected_text(tr(settings.comparison.name()))
+                    .show_ui(ui, |ui| {
+                        for comparison in DepthComparison::ALL {
+                            ui.selectable_value(
+                                &mut settings.comparison,
+                                comparison,
+                                tr(comparison.name()),
+                            );
+                        }
+                    });
+                ui.add(egui::Slider::new(&mut settings.split, 0.0..=1.0).text(tr("Divider")));
+                ui.add(
+                    egui::Slider::new(&mut settings.near, 0.001..=1.0)
+                        .logarithmic(true)
+                        .text(tr("Near plane")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.far, 100.0..=100000.0)
+                        .logarithmic(true)
+                        .text(tr("Far plane")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.gap, 0.001..=1.0)
+                        .logarithmic(true)
+                        .text(tr("Plane gap")),
+                );
+
+                let setups = settings.comparison.setups();
+                ui.separator();
+                ui.label(tr("Smallest resolvable depth difference"));
+                egui::Grid::new("depth_precision_steps")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        ui.label(tr("Distance"));
+                        for setup in setups {
+                            ui.label(tr(setup.name()));
+                        }
+                        ui.end_row();
+                        for distance in PROBE_DISTANCES.into_iter().filter(|d| *d <= settings.far) {
+                            ui.label(format!("{}", distance));
+                            for setup in setups {
+                                let step = setup.resolution(settings.near, settings.far, distance);
+                                ui.label(format!("{:.2e}", step));
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+                ui.label(tr("Lower plane showing through"));
+                egui::Grid::new("depth_precision_leaks")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (setup, leaked) in setups.iter().zip(leaked) {
+                            ui.label(tr(setup.name()));
+                            ui.label(leaked.map_or("-".to_string(), |leaked| {
+                                trf!("{:.3}% of pixels", leaked * 100.0)
+                            }));
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+
+    let mut current = world.resource_mut::<DepthPrecisionSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== SETTINGS ===============================
+/// A depth buffer format together with the direction depth is stored in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DepthSetup {
+    pub format: wgpu::TextureFormat,
+    /// Maps the near plane to 1 and the far plane to 0, which puts the
+    /// precision of floats where the perspective divide removes it.
+    pub reverse: bool,
+}
+impl DepthSetup {
+    const STANDARD_24: Self = Self {
+        format: wgpu::TextureFormat::Depth24Plus,
+        reverse: false,
+    };
+    const STANDARD_32: Self = Self {
+        format: wgpu::TextureFormat::Depth32Float,
+        reverse: false,
+    };
+    const REVERSE_32: Self = Self {
+        format: wgpu::TextureFormat::Depth32Float,
+        reverse: true,
+    };
+    const ALL: [Self; 3] = [Self::STANDARD_24, Self::STANDARD_32, Self::REVERSE_32];
+
+    pub fn name(&self) -> &'static str {
+        match (self.format, self.reverse) {
+            (wgpu::TextureFormat::Depth24Plus, _) => "24-bit, standard Z",
+            (_, false) => "32-bit float, standard Z",
+            (_, true) => "32-bit float, reverse Z",
+        }
+    }
+    fn label(&self) -> &'static str {
+        match (self.format, self.reverse) {
+            (wgpu::TextureFormat::Depth24Plus, _) => "depth_precision_24",
+            (_, false) => "depth_precision_32",
+            (_, true) => "depth_precision_reverse_32",
+        }
+    }
+
+    fn clear_depth(&self) -> f32 {
+        if self.reverse {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    fn compare(&self) -> wgpu::CompareFunction {
+        if self.reverse {
+            wgpu::CompareFunction::Greater
+        } else {
+            wgpu::CompareFunction::Less
+        }
+    }
+
+    fn projection(&self, aspect: f32, near: f32, far: f32) -> Mat4 {
+        let fov_y = 60f32.to_radians();
+        if self.reverse {
+            Mat4::perspective_rh(fov_y, aspect, far, near)
+        } else {
+            Mat4::perspective_rh(fov_y, aspect, near, far)
+        }
+    }
+
+    /// Smallest view distance difference at `distance` that still lands on
+    /// different depth values. `Depth24Plus` is assumed to be 24-bit unorm,
+    /// some backends store it as a float instead.
+    pub fn resolution(&self, near: f32, far: f32, distance: f32) -> f64 {
+        let (near, far, z) = (near as f64, far as f64, distance as f64);
+        let depth = if self.reverse {
+            near * (far - z) / (z * (far - near))
+        } else {
+            far * (z - near) / (z * (far - near))
+        };
+        let step = match self.format {
+            wgpu::TextureFormat::Depth24Plus => 2f64.powi(-24),
+            _ => f32_ulp(depth as f32) as f64,
+        };
+        // How fast depth changes with distance, the same for both directions
+        let slope = near * far / ((far - near) * z * z);
+        step / slope
+    }
+}
+
+/// Distance from `value` to the next larger float.
+fn f32_ulp(value: f32) -> f32 {
+    let value = value.abs().max(f32::MIN_POSITIVE);
+    f32::from_bits(value.to_bits() + 1) - value
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthComparison {
+    ReverseZ,
+    Format,
+}
+impl DepthComparison {
+    pub const ALL: [Self; 2] = [Self::ReverseZ, Self::Format];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ReverseZ => "Standard vs reverse Z",
+            Self::Format => "24-bit vs 32-bit float",
+        }
+    }
+
+    /// Left and right of the divider.
+    pub fn setups(&self) -> [DepthSetup; 2] {
+        match self {
+            Self::ReverseZ => [DepthSetup::STANDARD_32, DepthSetup::REVERSE_32],
+            Self::Format => [DepthSetup::STANDARD_24, DepthSetup::STANDARD_32],
+        }
+    }
+}
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct DepthPrecisionSettings {
+    /// Replaces the scene with the test scene.
+    pub enabled: bool,
+    pub comparison: DepthComparison,
+    /// Position of the divider as a fraction of the screen width.
+    pub split: f32,
+    pub near: f32,
+    pub far: f32,
+    /// Height difference between the two ground planes.
+    pub gap: f32,
+}
+impl Default for DepthPrecisionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            comparison: DepthComparison::ReverseZ,
+            split: 0.5,
+            near: 0.01,
+            far: 10000.0,
+            gap: 0.05,
+        }
+    }
+}
+impl DepthPrecisionSettings {
+    /// Columns covered by the left and the right setup.
+    fn columns(&self, width: u32) -> [Range<u32>; 2] {
+        let split = ((self.split * width as f32) as u32).min(width);
+        [0..split, split..width]
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DepthPrecisionParams {
+    pub view_proj: [[f32; 4]; 4],
+    pub far: f32,
+    pub gap: f32,
+    pub _padding: [f32; 2],
+}
+impl DepthPrecisionParams {
+    /// Looks along the planes from just above them, so the view covers every
+    /// distance from the near to the far plane.
+    pub fn new(settings: &DepthPrecisionSettings, setup: DepthSetup, aspect: f32) -> Self {
+        let eye = Vec3::new(0.0, 1.0, 0.0);
+        let view = Mat4::look_at_rh(eye, Vec3::new(0.0, 0.0, -20.0), Vec3::Y);
+        let proj = setup.projection(aspect, settings.near, settings.far);
+        Self {
+            view_proj: (proj * view).to_cols_array_2d(),
+            far: settings.far,
+            gap: settings.gap,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+// =============================== RESOURCES ===============================
+/// Depth buffers for both sides, only allocated while the tool is on.
+pub struct DepthTargets {
+    setups: [DepthSetup; 2],
+    size: (u32, u32),
+    views: [wgpu::TextureView; 2],
+}
+impl DepthTargets {
+    fn new(gpu: &GpuContext, setups: [DepthSetup; 2], width: u32, height: u32) -> Self {
+        let views = setups.map(|setup| {
+            gpu.device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(setup.label()),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: setup.format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&Default::default())
+        });
+        Self {
+            setups,
+            size: (width, height),
+            views,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum QueryState {
+    Idle,
+    /// Counts were resolved into the readback buffer this frame.
+    Resolved,
+    Mapping,
+}
+
+/// Samples of each plane that passed the depth test, measured whenever the
+/// previous measurement was read back.
+struct LeakQueries {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    state: QueryState,
+    /// Set by the map callback, `false` when mapping failed.
+    mapped: Arc<Mutex<Option<bool>>>,
+    /// Samples of the lower plane that won, relative to the upper plane's.
+    leaked: [Option<f64>; 2],
+}
+impl LeakQueries {
+    fn new(gpu: &GpuContext) -> Self {
+        let size = QUERY_COUNT as u64 * wgpu::QUERY_SIZE as u64;
+        Self {
+            query_set: gpu.device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("depth_precision_queries"),
+                ty: wgpu::QueryType::Occlusion,
+                count: QUERY_COUNT,
+            }),
+            resolve_buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("depth_precision_resolve"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("depth_precision_readback"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            state: QueryState::Idle,
+            mapped: Arc::new(Mutex::new(None)),
+            leaked: [None; 2],
+        }
+    }
+}
+
+/// Renders a scene with a huge depth range twice with different depth
+/// setups, to show where each one starts z-fighting.
+#[derive(Resource)]
+pub struct DepthPrecision {
+    pipelines: Vec<(DepthSetup, GPUPipeline)>,
+    params: [wgpu::Buffer; 2],
+    bind_groups: [wgpu::BindGroup; 2],
+    targets: Option<DepthTargets>,
+    queries: LeakQueries,
+}
+impl DepthPrecision {
+    pub fn new(gpu: &GpuContext, layouts: &mut LayoutCache) -> Result<Self> {
+        let layout = layouts.get(
+            &gpu.device,
+            &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        );
+        let params = [0, 1].map(|_| {
+            gpu.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("depth_precision_params"),
+                    contents: bytemuck::bytes_of(&DepthPrecisionParams::new(
+                        &DepthPrecisionSettings::default(),
+                        DepthSetup::STANDARD_32,
+                        1.0,
+                    )),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                })
+        });
+        let bind_groups = [0, 1].map(|side| {
+            gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params[side].as_entire_binding(),
+                }],
+                label: Some("depth_precision_bind_group"),
+            })
+        });
+
+        let source = load_shader_source(
+            "depth_precision.wgsl",
+            include_str!("../shaders/depth_precision.wgsl"),
+        );
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("depth_precision_shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let mut pipelines = Vec::new();
+        for setup in DepthSetup::ALL {
+            let pipeline = GPUPipelineBuilder::new(&gpu.device)
+                .label(setup.label())
+                .pipeline_cache(gpu.pipeline_cache())
+                .bind_group_layout(&layout)
+                .vertex_shader(&shader, "vs_main")
+                .fragment_shader(&shader, "fs_main")
+                .default_color_target(wgpu::TextureFormat::Rgba16Float)
+                .depth_stencil_state(Some(wgpu::DepthStencilState {
+                    format: setup.format,
+                    depth_write_enabled: true,
+                    depth_compare: setup.compare(),
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }))
+                .default_multisample_state()
+                .primitive_state(wgpu::PrimitiveState {
+                    cull_mode: None,
+                    ..Default::default()
+                })
+                .build()?;
+            pipelines.push((setup, pipeline));
+        }
+
+        Ok(Self {
+            pipelines,
+            params,
+            bind_groups,
+            targets: None,
+            queries: LeakQueries::new(gpu),
+        })
+    }
+
+    fn pipeline(&self, setup: DepthSetup) -> &GPUPipeline {
+        &self
+            .pipelines
+            .iter()
+            .find(|(candidate, _)| *candidate == setup)
+            .expect("a pipeline is built for every depth setup")
+            .1
+    }
+}
+
+// =============================== TESTS ===============================
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverse_z_resolves_far_distances_better() {
+        let (near, far) = (0.01, 10000.0);
+        for distance in [10.0, 100.0, 1000.0] {
+            let standard = DepthSetup::STANDARD_32.resolution(near, far, distance);
+            let reverse = DepthSetup::REVERSE_32.resolution(near, far, distance);
+            assert!(
+                reverse * 100.0 < standard,
+                "at {}: reverse {} vs standard {}",
+                distance,
+                reverse,
+                standard
+            );
+        }
+        // Unorm steps are even in depth, so they grow with the square of the distance
+        let at_10 = DepthSetup::STANDARD_24.resolution(near, far, 10.0);
+        let at_100 = DepthSetup::STANDARD_24.resolution(near, far, 100.0);
+        assert!((at_100 / at_10 - 100.0).abs() < 1e-6);
+    }
+}
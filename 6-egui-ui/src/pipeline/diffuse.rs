@@ -1,72 +1,198 @@
+use std::sync::Arc;
+
 use anyhow::Result;
 use bevy_ecs::{
-    component::Component,
-    prelude::resource_changed,
+    change_detection::DetectChanges,
     schedule::{IntoSystemConfigs, Schedule},
     system::{Res, ResMut, Resource},
     world::World,
 };
 
+use tracing::error;
+use wgpu::util::DeviceExt;
+
 use crate::{
-    texture::{self, Texture},
-    vertex::{DepthVertex, Vertex},
+    assets::{mip_tint, AssetServer, AssetSettings, TextureHandle, TextureSource},
+    layout::LayoutCache,
+    pass::RenderPassBuilder,
+    sampler::{SamplerCache, SamplerKey, SamplerSettings},
+    texture::Texture,
+    vertex::{DepthVertex, Vertex, VertexBuffers},
     GpuContext,
 };
 
-use super::{present::FrameBuffer, GPUPipeline, GPUPipelineBuilder};
+use super::{
+    depth::DepthTexture, graph::PassContext, present::FrameBuffer, render::render_system,
+    GPUPipeline, GPUPipelineBuilder,
+};
 
 pub fn setup_diffuse(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let diffuse_bind_group_layout =
+        world.resource_scope::<LayoutCache, _>(|world, mut layouts| {
+            DiffuseBindGroupLayout::new(world.resource::<GpuContext>(), &mut layouts)
+        })?;
     let gpu = world
         .get_resource::<GpuContext>()
         .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
 
-    let diffuse_bind_group_layout = DiffuseBindGroupLayout::new(&gpu)?;
-    let diffuse_bytes = include_bytes!("../../../assets/stone.png");
-    let diffuse_texture =
-        texture::Texture::from_bytes(&gpu.device, &gpu.queue, diffuse_bytes, "diffuse_texture")?;
-    let diffuse_bind_group =
-        DiffuseBindGroup::new(&gpu, &diffuse_bind_group_layout, &diffuse_texture)?;
+    let settings = world
+        .get_resource::<SamplerSettings>()
+        .ok_or_else(|| anyhow::anyhow!("SamplerSettings resource not found"))?;
+    let key = SamplerKey::material(settings, wgpu::AddressMode::ClampToEdge);
+
     let diffuse_pipeline = DiffusePipeline::new(&gpu, &diffuse_bind_group_layout)?;
 
+    let handle = world.resource_mut::<AssetServer>().add_texture(
+        "diffuse_texture",
+        TextureSource::Asset {
+            name: "stone.png",
+            embedded: include_bytes!("../../../assets/stone.png"),
+        },
+    );
+    world.resource_scope::<SamplerCache, _>(|world, mut samplers| -> Result<()> {
+        world.resource_scope::<AssetServer, _>(|world, mut assets| -> Result<()> {
+            let gpu = world.resource::<GpuContext>();
+            let version = assets.use_texture(gpu, handle)?.version;
+            let tint = gpu
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("diffuse_tint"),
+                    contents: bytemuck::cast_slice(&[1.0f32; 4]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+            let sampler = samplers.get(&gpu.device, key);
+            let diffuse_bind_group = DiffuseBindGroup::new(
+                gpu,
+                &diffuse_bind_group_layout,
+                assets.texture(handle)?,
+                sampler,
+                &tint,
+            )?;
+            world.insert_resource(diffuse_bind_group);
+            world.insert_resource(DiffuseTexture {
+                handle,
+                version,
+                tint,
+            });
+            Ok(())
+        })
+    })?;
     world.insert_resource(diffuse_bind_group_layout);
-    world.insert_resource(diffuse_bind_group);
     world.insert_resource(diffuse_pipeline);
 
+    schedule.add_systems(diffuse_bind_group_system.before(render_system));
+
     Ok(())
 }
 
+/// Keeps the texture resident and rebuilds the bind group when it was loaded
+/// again, a mip streamed in or the filtering quality changed.
+pub fn diffuse_bind_group_system(
+    gpu: Res<GpuContext>,
+    (settings, asset_settings): (Res<SamplerSettings>, Res<AssetSettings>),
+    mut samplers: ResMut<SamplerCache>,
+    mut assets: ResMut<AssetServer>,
+    layout: Res<DiffuseBindGroupLayout>,
+    mut texture: ResMut<DiffuseTexture>,
+    mut bind_group: ResMut<DiffuseBindGroup>,
+) {
+    let (version, tint) = match assets.use_texture(&gpu, texture.handle) {
+        Ok(asset) => (asset.version, mip_tint(&asset_settings, asset)),
+        Err(e) => {
+            error!("Failed to load the diffuse texture: {:?}", e);
+            return;
+        }
+    };
+    if version != texture.version || asset_settings.is_changed() {
+        gpu.queue
+            .write_buffer(&texture.tint, 0, bytemuck::cast_slice(&tint));
+    }
+    if version == texture.version && !settings.is_changed() {
+        return;
+    }
+    texture.version = version;
+    let key = SamplerKey::material(&settings, wgpu::AddressMode::ClampToEdge);
+    let sampler = samplers.get(&gpu.device, key);
+    let recreated = assets
+        .texture(texture.handle)
+        .map_err(anyhow::Error::from)
+        .and_then(|diffuse| DiffuseBindGroup::new(&gpu, &layout, diffuse, sampler, &texture.tint));
+    match recreated {
+        Ok(recreated) => *bind_group = recreated,
+        Err(e) => error!("Failed to recreate the diffuse bind group: {}", e),
+    }
+}
+
+pub fn diffuse_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let depth = world.resource::<DepthTexture>();
+    let pipeline = world.resource::<DiffusePipeline>();
+    let bind_group = world.resource::<DiffuseBindGroup>();
+    let vertex_buffers = world.resource::<VertexBuffers>();
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&frame_buffer.texture.view)
+        .with_depth(&depth.texture.view, 1.0)
+        .build()?;
+
+    render_pass.set_pipeline(&pipeline.pipeline.render_pipeline);
+    render_pass.set_bind_group(0, &bind_group.bind_group, &[]);
+    render_pass.set_vertex_buffer(0, vertex_buffers.vertex_buffer.slice(..));
+    render_pass.draw(0..vertex_buffers.num_vertices, 0..1);
+
+    Ok(())
+}
+
+#[derive(Resource)]
+pub struct DiffuseTexture {
+    pub handle: TextureHandle,
+    /// [`TextureAsset::version`](crate::assets::TextureAsset::version) the bind group was made with.
+    pub version: u32,
+    /// Resident mip debug tint, see [`mip_tint`].
+    pub tint: wgpu::Buffer,
+}
+
 // =============================== BIND GROUP ===============================
 #[derive(Resource)]
 pub struct DiffuseBindGroupLayout {
-    pub layout: wgpu::BindGroupLayout,
+    pub layout: Arc<wgpu::BindGroupLayout>,
 }
 impl DiffuseBindGroupLayout {
-    pub fn new(gpu: &GpuContext) -> Result<Self> {
-        let diffuse_bind_group_layout =
-            gpu.device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Texture {
-                                multisampled: false,
-                                view_dimension: wgpu::TextureViewDimension::D2,
-                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 1,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            // This should match the filterable field of the
-                            // corresponding Texture entry above.
-                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                            count: None,
-                        },
-                    ],
-                    label: Some("diffuse_bind_group_layout"),
-                });
+    pub fn new(gpu: &GpuContext, layouts: &mut LayoutCache) -> Result<Self> {
+        let diffuse_bind_group_layout = layouts.get(
+            &gpu.device,
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    // This should match the filterable field of the
+                    // corresponding Texture entry above.
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        );
 
         Ok(Self {
             layout: diffuse_bind_group_layout,
@@ -83,6 +209,8 @@ impl DiffuseBindGroup {
         gpu: &GpuContext,
         layout: &DiffuseBindGroupLayout,
         texture: &Texture,
+        sampler: &wgpu::Sampler,
+        tint: &wgpu::Buffer,
     ) -> Result<Self> {
         let diffuse_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &layout.layout,
@@ -93,7 +221,11 @@ impl DiffuseBindGroup {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: tint.as_entire_binding(),
                 },
             ],
             label: Some("diffuse_bind_group"),
@@ -120,6 +252,7 @@ impl DiffusePipeline {
             });
         let diffuse_pipeline = GPUPipelineBuilder::new(&gpu.device)
             .label("diffuse_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
             .bind_group_layout(&bind_group_layout.layout)
             .vertex_shader(&shader, "vs_main")
             .fragment_shader(&shader, "fs_main")
@@ -128,8 +261,7 @@ impl DiffusePipeline {
             .default_depth_stencil_state()
             .default_multisample_state()
             .default_primitive_state()
-            .build()
-            .map_err(|e| anyhow::anyhow!(e))?;
+            .build()?;
 
         Ok(Self {
             pipeline: diffuse_pipeline,
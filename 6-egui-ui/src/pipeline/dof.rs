@@ -0,0 +1,260 @@
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{Condition, IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use wgpu::util::DeviceExt;
+
+use crate::{gpu::GpuContext, pass::RenderPassBuilder, scene::Camera, texture::Texture};
+
+use super::{
+    depth::DepthTexture,
+    graph::PassContext,
+    inspector::TextureRegistry,
+    post::PostSettings,
+    present::{render_scale_system, FrameBuffer},
+    render::render_system,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+/// Signed, so the gather can tell the near field from the far field.
+pub const COC_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+
+pub fn setup_depth_of_field(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let frame_buffer = world
+        .get_resource::<FrameBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("FrameBuffer resource not found"))?;
+    let depth = world
+        .get_resource::<DepthTexture>()
+        .ok_or_else(|| anyhow::anyhow!("DepthTexture resource not found"))?;
+
+    let size = frame_buffer.texture.texture.size();
+    let coc = CocBuffer {
+        texture: Texture::render_target(
+            &gpu.device,
+            size.width,
+            size.height,
+            COC_FORMAT,
+            "coc_buffer",
+        ),
+    };
+    let dof = DepthOfField::new(gpu, depth)?;
+
+    world.insert_resource(coc);
+    world.insert_resource(dof);
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("circle_of_confusion", |world| {
+            world
+                .get_resource::<CocBuffer>()
+                .map(|coc| &coc.texture.texture)
+        });
+
+    schedule.add_systems((
+        coc_resize_system
+            .run_if(resource_changed::<FrameBuffer>)
+            .after(render_scale_system)
+            .before(render_system),
+        dof_bind_group_system
+            .run_if(resource_changed::<DepthTexture>)
+            .after(render_scale_system)
+            .before(render_system),
+        dof_params_system
+            .run_if(resource_changed::<PostSettings>.or(resource_changed::<Camera>))
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Keeps the CoC buffer at the frame buffer's size, like the velocity buffer.
+pub fn coc_resize_system(
+    gpu: Res<GpuContext>,
+    frame_buffer: Res<FrameBuffer>,
+    mut coc: ResMut<CocBuffer>,
+) {
+    let size = frame_buffer.texture.texture.size();
+    let current = coc.texture.texture.size();
+    if size.width == current.width && size.height == current.height {
+        return;
+    }
+    coc.texture
+        .resize(&gpu.device, &gpu.queue, size.width, size.height);
+}
+
+/// Picks up the depth texture after it was recreated by a resize.
+pub fn dof_bind_group_system(
+    gpu: Res<GpuContext>,
+    depth: Res<DepthTexture>,
+    mut dof: ResMut<DepthOfField>,
+) {
+    dof.bind_group = DepthOfField::create_bind_group(&gpu, &dof.layout, &depth, &dof.params);
+}
+
+pub fn dof_params_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    settings: Res<PostSettings>,
+    dof: Res<DepthOfField>,
+) {
+    let params = DofParams::new(&settings, &camera);
+    gpu.queue
+        .write_buffer(&dof.params, 0, bytemuck::bytes_of(&params));
+}
+
+/// Writes the circle of confusion of every pixel, read by the bokeh gather
+/// in the present shader.
+pub fn coc_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let settings = world.resource::<PostSettings>();
+    if !settings.depth_of_field {
+        return Ok(());
+    }
+    let coc = world.resource::<CocBuffer>();
+    let dof = world.resource::<DepthOfField>();
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&coc.texture.view)
+        .build()?;
+
+    render_pass.set_pipeline(&dof.pipeline.render_pipeline);
+    render_pass.set_bind_group(0, &dof.bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+
+    Ok(())
+}
+
+// =============================== RESOURCES ===============================
+#[derive(Resource)]
+pub struct CocBuffer {
+    pub texture: Texture,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DofParams {
+    pub focal_distance: f32,
+    pub aperture: f32,
+    pub max_coc: f32,
+    pub near: f32,
+    pub far: f32,
+    pub _padding: [f32; 3],
+}
+impl DofParams {
+    pub fn new(settings: &PostSettings, camera: &Camera) -> Self {
+        Self {
+            focal_distance: settings.focal_distance,
+            aperture: settings.aperture,
+            max_coc: settings.max_coc,
+            near: camera.near,
+            far: camera.far,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct DepthOfField {
+    pub params: wgpu::Buffer,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    pub pipeline: GPUPipeline,
+}
+impl DepthOfField {
+    pub fn new(gpu: &GpuContext, depth: &DepthTexture) -> Result<Self> {
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("dof_params"),
+                contents: bytemuck::bytes_of(&DofParams::new(
+                    &PostSettings::default(),
+                    &Camera::default(),
+                )),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("dof_bind_group_layout"),
+            });
+        let bind_group = Self::create_bind_group(gpu, &layout, depth, &params);
+
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("coc_shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/coc.wgsl").into()),
+            });
+        let pipeline = GPUPipelineBuilder::new(&gpu.device)
+            .label("coc_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .color_target(wgpu::ColorTargetState {
+                format: COC_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .depth_stencil_state(None)
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState::default())
+            .build()?;
+
+        Ok(Self {
+            params,
+            layout,
+            bind_group,
+            pipeline,
+        })
+    }
+
+    fn create_bind_group(
+        gpu: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        depth: &DepthTexture,
+        params: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&depth.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+            label: Some("dof_bind_group"),
+        })
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+use glam::Vec3;
+use tracing::{info, warn};
+
+use crate::i18n::{tr, trf};
+use crate::{gpu::GpuContext, texture::Texture};
+
+use super::{inspector::TextureRegistry, ui::UiPanels};
+
+/// Equirectangular map picked up at startup when present.
+const ENVIRONMENT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/environment.hdr");
+const SKY_WIDTH: u32 = 512;
+const SKY_HEIGHT: u32 = 256;
+
+pub fn setup_environment(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let path = std::env::var("ENVIRONMENT_HDR").unwrap_or_else(|_| ENVIRONMENT_PATH.to_string());
+    let loaded = if Path::new(&path).exists() {
+        match Texture::from_hdr_path(
+            &gpu.device,
+            &gpu.queue,
+            &path,
+            wgpu::TextureFormat::Rgba16Float,
+        ) {
+            Ok(texture) => Some(texture),
+            Err(e) => {
+                warn!("Falling back to the procedural sky: {:?}", e);
+                None
+            }
+        }
+    } else {
+        info!("No environment map at '{}', using the procedural sky", path);
+        None
+    };
+
+    let environment = match loaded {
+        Some(texture) => Environment {
+            texture,
+            source: path,
+        },
+        None => {
+            let texels: Vec<[f32; 4]> = (0..SKY_WIDTH * SKY_HEIGHT)
+                .map(|i| procedural_sky(i % SKY_WIDTH, i / SKY_WIDTH))
+                .collect();
+            let texture = Texture::from_rgba32f(
+                &gpu.device,
+                &gpu.queue,
+                SKY_WIDTH,
+                SKY_HEIGHT,
+                &texels,
+                wgpu::TextureFormat::Rgba16Float,
+                "procedural_sky",
+            )?;
+            Environment {
+                texture,
+                source: "procedural".to_string(),
+            }
+        }
+    };
+
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("environment", |world| {
+            world
+                .get_resource::<Environment>()
+                .map(|env| &env.texture.texture)
+        })
+        .with_source(ENVIRONMENT_PATH);
+    world.insert_resource(environment);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(environment_panel);
+
+    Ok(())
+}
+
+fn environment_panel(ctx: &egui::Context, world: &mut World) {
+    let environment = world.resource::<Environment>();
+    let texture = &environment.texture.texture;
+
+    egui::Window::new(tr("Environment"))
+        .id(egui::Id::new("Environment"))
+        .show(ctx, |ui| {
+            ui.label(trf!("Source: {}", environment.source));
+            ui.label(format!(
+                "{}x{} {:?}",
+                texture.width(),
+                texture.height(),
+                texture.format()
+            ));
+        });
+}
+
+/// Equirectangular HDR environment, linear radiance.
+#[derive(Resource)]
+pub struct Environment {
+    pub texture: Texture,
+    /// File path, or "procedural" for the generated sky.
+    pub source: String,
+}
+
+// =============================== SKY ===============================
+/// Gradient sky with a sun well above 1.0, so the map actually needs HDR.
+fn procedural_sky(x: u32, y: u32) -> [f32; 4] {
+    let phi = (x as f32 + 0.5) / SKY_WIDTH as f32 * std::f32::consts::TAU;
+    let theta = (y as f32 + 0.5) / SKY_HEIGHT as f32 * std::f32::consts::PI;
+    let direction = Vec3::new(
+        theta.sin() * phi.cos(),
+        theta.cos(),
+        theta.sin() * phi.sin(),
+    );
+
+    let zenith = Vec3::new(0.15, 0.35, 0.9);
+    let horizon = Vec3::new(0.8, 0.85, 0.95);
+    let ground = Vec3::new(0.25, 0.22, 0.2);
+    let mut color = if direction.y >= 0.0 {
+        horizon.lerp(zenith, direction.y.powf(0.5))
+    } else {
+        horizon.lerp(ground, (-direction.y * 4.0).min(1.0))
+    };
+
+    let sun = Vec3::new(0.4, 0.6, 0.3).normalize();
+    let sun_dot = direction.dot(sun);
+    if sun_dot > 0.9995 {
+        color += Vec3::splat(50.0);
+    } else {
+        color += Vec3::new(1.0, 0.8, 0.5) * sun_dot.max(0.0).powf(64.0) * 2.0;
+    }
+
+    color.extend(1.0).to_array()
+}
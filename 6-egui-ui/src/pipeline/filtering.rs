@@ -0,0 +1,340 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{Condition, IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    layout::LayoutCache,
+    pass::RenderPassBuilder,
+    sampler::{Anisotropy, SamplerCache, SamplerKey, SamplerSettings},
+    shader::load_shader_source,
+    texture::Texture,
+};
+
+use super::{
+    depth::DepthTexture, graph::PassContext, inspector::TextureRegistry, mesh::CameraBuffer,
+    present::FrameBuffer, ui::UiPanels, GPUPipeline, GPUPipelineBuilder,
+};
+
+const CHECKER_SIZE: u32 = 512;
+const CHECKER_CELLS: u32 = 8;
+
+pub fn setup_filtering_demo(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let demo = world.resource_scope::<LayoutCache, _>(|world, mut layouts| {
+        world.resource_scope::<SamplerCache, _>(|world, mut samplers| {
+            let gpu = world
+                .get_resource::<GpuContext>()
+                .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+            let camera = world
+                .get_resource::<CameraBuffer>()
+                .ok_or_else(|| anyhow::anyhow!("CameraBuffer resource not found"))?;
+            let settings = world
+                .get_resource::<SamplerSettings>()
+                .ok_or_else(|| anyhow::anyhow!("SamplerSettings resource not found"))?;
+
+            let key = SamplerKey::material(settings, wgpu::AddressMode::Repeat);
+            FilteringDemo::new(gpu, &mut layouts, camera, samplers.get(&gpu.device, key))
+        })
+    })?;
+    world.insert_resource(demo);
+    world.insert_resource(FilteringDemoSettings::default());
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("filtering_checker", |world| {
+            world
+                .get_resource::<FilteringDemo>()
+                .map(|demo| &demo.texture.texture)
+        });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(texture_filtering_panel);
+
+    schedule.add_systems((
+        filtering_demo_sampler_system.run_if(resource_changed::<SamplerSettings>),
+        filtering_demo_params_system.run_if(
+            resource_changed::<SamplerSettings>.or(resource_changed::<FilteringDemoSettings>),
+        ),
+    ));
+
+    Ok(())
+}
+
+/// Rebuilds the plane's bind group with the sampler for the current quality.
+pub fn filtering_demo_sampler_system(
+    gpu: Res<GpuContext>,
+    settings: Res<SamplerSettings>,
+    mut samplers: ResMut<SamplerCache>,
+    mut demo: ResMut<FilteringDemo>,
+) {
+    let sampler = samplers.get(
+        &gpu.device,
+        SamplerKey::material(&settings, wgpu::AddressMode::Repeat),
+    );
+    demo.bind_group =
+        FilteringDemo::create_bind_group(&gpu, &demo.layout, &demo.texture, &demo.params, sampler);
+}
+
+pub fn filtering_demo_params_system(
+    gpu: Res<GpuContext>,
+    settings: Res<SamplerSettings>,
+    demo_settings: Res<FilteringDemoSettings>,
+    demo: Res<FilteringDemo>,
+) {
+    let params = FilteringDemoParams::new(&settings, &demo_settings);
+    gpu.queue
+        .write_buffer(&demo.params, 0, bytemuck::bytes_of(&params));
+}
+
+pub fn filtering_demo_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    if !world.resource::<FilteringDemoSettings>().plane {
+        return Ok(());
+    }
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let depth = world.resource::<DepthTexture>();
+    let camera = world.resource::<CameraBuffer>();
+    let demo = world.resource::<FilteringDemo>();
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&frame_buffer.texture.view)
+        .with_depth(&depth.texture.view, 1.0)
+        .load()
+        .build()?;
+
+    render_pass.set_pipeline(&demo.pipeline.render_pipeline);
+    render_pass.set_bind_group(0, &camera.bind_group, &[]);
+    render_pass.set_bind_group(1, &demo.bind_group, &[]);
+    render_pass.draw(0..6, 0..1);
+
+    Ok(())
+}
+
+fn texture_filtering_panel(ctx: &egui::Context, world: &mut World) {
+    let cached = world.resource::<SamplerCache>().cached();
+    let levels = world
+        .resource::<FilteringDemo>()
+        .texture
+        .texture
+        .mip_level_count();
+    let mut sampler = world.resource::<SamplerSettings>().clone();
+    let mut demo = world.resource::<FilteringDemoSettings>().clone();
+
+    egui::Window::new(tr("Texture filtering"))
+        .id(egui::Id::new("Texture filtering"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            egui::ComboBox::from_label(tr("Anisotropy"))
+                .selected_text(sampler.anisotropy.name())
+                .show_ui(ui, |ui| {
+                    for option in Anisotropy::ALL {
+                        ui.selectable_value(&mut sampler.anisotropy, option, option.name());
+                    }
+                });
+            ui.add(egui::Slider::new(&mut sampler.lod_bias, -4.0..=4.0).text(tr("LOD bias")));
+            ui.add(
+                egui::Slider::new(&mut sampler.lod_min_clamp, 0.0..=levels as f32)
+                    .step_by(0.25)
+                    .text(tr("Min LOD")),
+            );
+            ui.add(
+                egui::Slider::new(&mut sampler.lod_max_clamp, 0.0..=32.0)
+                    .step_by(0.25)
+                    .text(tr("Max LOD")),
+            );
+            sampler.lod_max_clamp = sampler.lod_max_clamp.max(sampler.lod_min_clamp);
+            if ui.button(tr("Reset sampler")).clicked() {
+                sampler = SamplerSettings {
+                    anisotropy: sampler.anisotropy,
+                    ..Default::default()
+                };
+            }
+
+            ui.separator();
+            ui.checkbox(&mut demo.plane, tr("Grazing angle demo plane"));
+            ui.checkbox(&mut demo.mip_view, tr("Color by mip level"))
+                .on_hover_text(tr("Red is level 0, then orange, yellow, green..."));
+            ui.label(trf!("Checker texture: {} mip levels", levels));
+            ui.label(trf!("{} samplers cached", cached));
+        });
+
+    // Only touch the resources on edits, samplers are swapped on change
+    let mut settings = world.resource_mut::<SamplerSettings>();
+    if *settings != sampler {
+        *settings = sampler;
+    }
+    let mut demo_settings = world.resource_mut::<FilteringDemoSettings>();
+    if *demo_settings != demo {
+        *demo_settings = demo;
+    }
+}
+
+// =============================== RESOURCES ===============================
+#[derive(Resource, Clone, PartialEq, Default)]
+pub struct FilteringDemoSettings {
+    /// Draws a large checkered plane just above the ground.
+    pub plane: bool,
+    /// Colors the plane by the mip level being sampled.
+    pub mip_view: bool,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FilteringDemoParams {
+    pub lod_bias: f32,
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+    pub mip_view: u32,
+}
+impl FilteringDemoParams {
+    pub fn new(settings: &SamplerSettings, demo: &FilteringDemoSettings) -> Self {
+        Self {
+            lod_bias: settings.lod_bias,
+            lod_min_clamp: settings.lod_min_clamp,
+            lod_max_clamp: settings.lod_max_clamp,
+            mip_view: demo.mip_view as u32,
+        }
+    }
+}
+
+/// Checkered ground plane that makes filtering differences obvious: without
+/// anisotropy the checkers blur out a short distance from the camera.
+#[derive(Resource)]
+pub struct FilteringDemo {
+    pub texture: Texture,
+    pub params: wgpu::Buffer,
+    pub layout: Arc<wgpu::BindGroupLayout>,
+    pub bind_group: wgpu::BindGroup,
+    pub pipeline: GPUPipeline,
+}
+impl FilteringDemo {
+    pub fn new(
+        gpu: &GpuContext,
+        layouts: &mut LayoutCache,
+        camera: &CameraBuffer,
+        sampler: &wgpu::Sampler,
+    ) -> Result<Self> {
+        let cell = CHECKER_SIZE / CHECKER_CELLS;
+        let checker = image::RgbaImage::from_fn(CHECKER_SIZE, CHECKER_SIZE, |x, y| {
+            if (x / cell + y / cell) & 1 == 0 {
+                image::Rgba([230, 230, 230, 255])
+            } else {
+                image::Rgba([40, 40, 40, 255])
+            }
+        });
+        let texture = Texture::from_image(
+            &gpu.device,
+            &gpu.queue,
+            &image::DynamicImage::ImageRgba8(checker),
+            Some("filtering_checker"),
+        )?;
+
+        let layout = layouts.get(
+            &gpu.device,
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        );
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("filtering_demo_params"),
+                contents: bytemuck::bytes_of(&FilteringDemoParams::new(
+                    &SamplerSettings::default(),
+                    &FilteringDemoSettings::default(),
+                )),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let bind_group = Self::create_bind_group(gpu, &layout, &texture, &params, sampler);
+
+        let source = load_shader_source(
+            "filtering_demo.wgsl",
+            include_str!("../shaders/filtering_demo.wgsl"),
+        );
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("filtering_demo_shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let pipeline = GPUPipelineBuilder::new(&gpu.device)
+            .label("filtering_demo_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&camera.layout)
+            .bind_group_layout(&layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .default_color_target(wgpu::TextureFormat::Rgba16Float)
+            .default_depth_stencil_state()
+            .default_multisample_state()
+            .default_primitive_state()
+            .build()?;
+
+        Ok(Self {
+            texture,
+            params,
+            layout,
+            bind_group,
+            pipeline,
+        })
+    }
+
+    fn create_bind_group(
+        gpu: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        texture: &Texture,
+        params: &wgpu::Buffer,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+            label: Some("filtering_demo_bind_group"),
+        })
+    }
+}
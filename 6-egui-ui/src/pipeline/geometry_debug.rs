@@ -0,0 +1,279 @@
+use anyhow::Result;
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    query::{With, Without},
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Commands, Query, Res, ResMut, Resource},
+    world::World,
+};
+use glam::Vec4;
+use tracing::warn;
+
+use crate::{
+    assets::AssetServer,
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pass::RenderPassBuilder,
+    scene::{transform_propagation_system, Aabb, GlobalTransform, MeshId, Renderable},
+    shader::load_shader_source,
+    vertex::MeshVertex,
+};
+
+use super::{
+    arena::{frame_arena_upload_system, FrameArena},
+    debug_draw::DebugDraw,
+    depth::DepthTexture,
+    graph::PassContext,
+    mesh::{CameraBuffer, MeshTriangles, Meshes},
+    present::FrameBuffer,
+    render::render_system,
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+const BOUNDS_COLOR: Vec4 = Vec4::new(0.2, 0.9, 0.9, 1.0);
+/// Vertices drawn per triangle, a normal and a tangent line per corner.
+const LINE_VERTICES_PER_TRIANGLE: u32 = 12;
+
+pub fn setup_geometry_debug(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let camera = world
+        .get_resource::<CameraBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("CameraBuffer resource not found"))?;
+    let arena = world
+        .get_resource::<FrameArena>()
+        .ok_or_else(|| anyhow::anyhow!("FrameArena resource not found"))?;
+
+    let pipeline = GeometryDebugPipeline::new(gpu, camera, arena)?;
+    world.insert_resource(pipeline);
+    world.insert_resource(GeometryDebugSettings::default());
+    world.init_resource::<GeometryDebugDraws>();
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(geometry_debug_panel);
+
+    schedule.add_systems((
+        geometry_debug_attach_system.before(render_system),
+        geometry_debug_system
+            .after(transform_propagation_system)
+            .before(frame_arena_upload_system)
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Gives every renderable a [`GeometryDebug`], so the inspector can toggle
+/// its views.
+pub fn geometry_debug_attach_system(
+    mut commands: Commands,
+    query: Query<Entity, (With<Renderable>, Without<GeometryDebug>)>,
+) {
+    for entity in &query {
+        commands.entity(entity).insert(GeometryDebug::default());
+    }
+}
+
+/// Queues the bounds on [`DebugDraw`] and allocates the per-entity uniforms
+/// of the normal and tangent lines.
+#[allow(clippy::too_many_arguments)]
+pub fn geometry_debug_system(
+    gpu: Res<GpuContext>,
+    assets: Res<AssetServer>,
+    meshes: Res<Meshes>,
+    mut triangles: ResMut<MeshTriangles>,
+    settings: Res<GeometryDebugSettings>,
+    mut arena: ResMut<FrameArena>,
+    mut draws: ResMut<GeometryDebugDraws>,
+    mut debug_draw: ResMut<DebugDraw>,
+    query: Query<(
+        &GlobalTransform,
+        &Renderable,
+        Option<&Aabb>,
+        Option<&GeometryDebug>,
+    )>,
+) {
+    draws.draws.clear();
+    for (global, renderable, aabb, debug) in &query {
+        let debug = debug.copied().unwrap_or_default();
+        if let Some(aabb) = aabb.filter(|_| debug.bounds || settings.all_bounds) {
+            let bounds = aabb.transformed(&global.0);
+            debug_draw.wire_box(bounds.min, bounds.max, BOUNDS_COLOR);
+        }
+
+        let normals = debug.normals || settings.all_normals;
+        if !normals && !debug.tangents {
+            continue;
+        }
+        let uniform = GeometryDebugUniform {
+            model: global.0.to_cols_array_2d(),
+            normal_matrix: global.0.inverse().transpose().to_cols_array_2d(),
+            params: [
+                settings.line_length,
+                normals as u32 as f32,
+                debug.tangents as u32 as f32,
+                0.0,
+            ],
+        };
+        if let Err(e) = triangles.upload(&gpu, &assets, &meshes, renderable.mesh) {
+            warn!("No normals for {:?}: {}", renderable.mesh, e);
+            continue;
+        }
+        draws.draws.push((renderable.mesh, arena.alloc(&uniform)));
+    }
+}
+
+/// Draws the normal and tangent lines of every entity queued by
+/// [`geometry_debug_system`], one instance per triangle of its mesh.
+pub fn geometry_debug_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let draws = world.resource::<GeometryDebugDraws>();
+    if draws.draws.is_empty() {
+        return Ok(());
+    }
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let depth = world.resource::<DepthTexture>();
+    let pipeline = world.resource::<GeometryDebugPipeline>();
+    let camera = world.resource::<CameraBuffer>();
+    let arena = world.resource::<FrameArena>();
+    let triangles = world.resource::<MeshTriangles>();
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&frame_buffer.texture.view)
+        .with_depth(&depth.texture.view, 1.0)
+        .load()
+        .build()?;
+
+    render_pass.set_pipeline(&pipeline.pipeline.render_pipeline);
+    render_pass.set_bind_group(0, &camera.bind_group, &[]);
+    for &(mesh, offset) in &draws.draws {
+        let (buffer, count) = triangles.get(mesh)?;
+        render_pass.set_bind_group(1, arena.bind_group(), &[offset]);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..LINE_VERTICES_PER_TRIANGLE, 0..count);
+    }
+
+    Ok(())
+}
+
+// =============================== COMPONENT ===============================
+/// Debug views of one entity, edited from the inspector.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct GeometryDebug {
+    pub bounds: bool,
+    pub normals: bool,
+    pub tangents: bool,
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct GeometryDebugSettings {
+    /// Overrides the per-entity toggles.
+    pub all_bounds: bool,
+    pub all_normals: bool,
+    /// In world units.
+    pub line_length: f32,
+}
+impl Default for GeometryDebugSettings {
+    fn default() -> Self {
+        Self {
+            all_bounds: false,
+            all_normals: false,
+            line_length: 0.25,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct GeometryDebugDraws {
+    /// Mesh and arena offset of each entity's uniform.
+    pub draws: Vec<(MeshId, u32)>,
+}
+
+// =============================== PIPELINE ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GeometryDebugUniform {
+    pub model: [[f32; 4]; 4],
+    pub normal_matrix: [[f32; 4]; 4],
+    /// Line length, normals shown, tangents shown.
+    pub params: [f32; 4],
+}
+
+#[derive(Resource)]
+pub struct GeometryDebugPipeline {
+    pub pipeline: GPUPipeline,
+}
+impl GeometryDebugPipeline {
+    pub fn new(gpu: &GpuContext, camera: &CameraBuffer, arena: &FrameArena) -> Result<Self> {
+        let source = load_shader_source(
+            "geometry_debug.wgsl",
+            include_str!("../shaders/geometry_debug.wgsl"),
+        );
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("geometry_debug_shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let pipeline = GPUPipelineBuilder::new(&gpu.device)
+            .label("geometry_debug_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&camera.layout)
+            .bind_group_layout(&arena.layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .vertex_buffer_layout(MeshVertex::triangle_desc())
+            .color_target(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Rgba16Float,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .depth_stencil_state(Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }))
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            })
+            .build()?;
+
+        Ok(Self { pipeline })
+    }
+}
+
+// =============================== UI ===============================
+fn geometry_debug_panel(ctx: &egui::Context, world: &mut World) {
+    let draws = world.resource::<GeometryDebugDraws>().draws.len();
+    let mut settings = world.resource::<GeometryDebugSettings>().clone();
+
+    egui::Window::new(tr("Geometry debug"))
+        .id(egui::Id::new("Geometry debug"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.all_bounds, tr("Bounds on every entity"));
+            ui.checkbox(&mut settings.all_normals, tr("Normals on every entity"));
+            ui.add(
+                egui::Slider::new(&mut settings.line_length, 0.01..=2.0)
+                    .logarithmic(true)
+                    .text(tr("Line length")),
+            );
+            ui.label(tr(
+                "Single entities are toggled under Debug view in the entity inspector",
+            ));
+            ui.label(trf!("{} entities with normal or tangent lines", draws));
+        });
+
+    let mut current = world.resource_mut::<GeometryDebugSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
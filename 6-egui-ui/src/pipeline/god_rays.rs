@@ -0,0 +1,410 @@
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{Condition, IntoSystemConfigs, Schedule},
+    system::{Query, Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Vec3, Vec4Swizzles};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::tr,
+    lights::DirectionalLight,
+    pass::RenderPassBuilder,
+    scene::{camera_aspect_system, transform_propagation_system, Camera, GlobalTransform},
+    texture::Texture,
+};
+
+use super::{
+    depth::DepthTexture,
+    graph::PassContext,
+    inspector::TextureRegistry,
+    present::FrameBuffer,
+    render::render_system,
+    scaled::{scaled_depth_resize_system, ResolutionScale, ScaledDepth, Upsampler},
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+pub fn setup_god_rays(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let depth = world
+        .get_resource::<DepthTexture>()
+        .ok_or_else(|| anyhow::anyhow!("DepthTexture resource not found"))?;
+    let scaled = world
+        .get_resource::<ScaledDepth>()
+        .ok_or_else(|| anyhow::anyhow!("ScaledDepth resource not found"))?;
+
+    let settings = GodRaySettings::default();
+    let god_rays = GodRays::new(gpu, &settings, depth, scaled)?;
+
+    world.insert_resource(god_rays);
+    world.insert_resource(settings);
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("god_rays", |world| {
+            world
+                .get_resource::<GodRays>()
+                .map(|god_rays| &god_rays.target.texture)
+        });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(god_rays_panel);
+
+    schedule.add_systems((
+        god_rays_resize_system
+            .run_if(resource_changed::<DepthTexture>.or(resource_changed::<GodRaySettings>))
+            .after(scaled_depth_resize_system)
+            .before(render_system),
+        god_rays_params_system
+            .after(transform_propagation_system)
+            .after(camera_aspect_system)
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Follows the depth texture at the configured resolution scale.
+pub fn god_rays_resize_system(
+    gpu: Res<GpuContext>,
+    depth: Res<DepthTexture>,
+    scaled: Res<ScaledDepth>,
+    settings: Res<GodRaySettings>,
+    mut god_rays: ResMut<GodRays>,
+) {
+    let size = depth.texture.texture.size();
+    let (width, height) = settings.resolution.size(size.width, size.height);
+    let current = god_rays.target.texture.size();
+    if width != current.width || height != current.height {
+        god_rays
+            .target
+            .resize(&gpu.device, &gpu.queue, width, height);
+    }
+    let god_rays = &mut *god_rays;
+    god_rays.bind_group = GodRays::create_bind_group(
+        &gpu,
+        &god_rays.layout,
+        &god_rays.params,
+        scaled.view(settings.resolution, &depth),
+    );
+    god_rays.upsample_bind_group =
+        god_rays
+            .upsampler
+            .bind_group(&gpu, &god_rays.target, &scaled, settings.resolution, &depth);
+}
+
+/// Projects the first directional light onto the screen. Runs every frame,
+/// the light can be moved from the editor.
+pub fn god_rays_params_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    settings: Res<GodRaySettings>,
+    mut god_rays: ResMut<GodRays>,
+    lights: Query<(&GlobalTransform, &DirectionalLight)>,
+) {
+    let light = lights.iter().find(|(_, light)| light.intensity > 0.0);
+    let params = match light {
+        Some((global, light)) => GodRayParams::new(&settings, &camera, global, light),
+        None => GodRayParams::hidden(&settings, &camera),
+    };
+    god_rays.visible = params.strength > 0.0;
+    gpu.queue
+        .write_buffer(&god_rays.params, 0, bytemuck::bytes_of(&params));
+}
+
+/// Renders the scattering into the god rays target and adds it onto the
+/// frame buffer while it's still in linear HDR, ahead of tonemapping.
+pub fn god_rays_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let settings = world.resource::<GodRaySettings>();
+    let god_rays = world.resource::<GodRays>();
+    if !settings.enabled || !god_rays.visible {
+        return Ok(());
+    }
+    let frame_buffer = world.resource::<FrameBuffer>();
+
+    world
+        .resource::<ScaledDepth>()
+        .downsample(ctx.encoder, settings.resolution)?;
+    {
+        let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+            .with_label(ctx.label)
+            .with_cleared_color_view(&god_rays.target.view, wgpu::Color::TRANSPARENT)
+            .build()?;
+
+        render_pass.set_pipeline(&god_rays.pipeline.render_pipeline);
+        render_pass.set_bind_group(0, &god_rays.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+    god_rays.upsampler.draw(
+        ctx.encoder,
+        &frame_buffer.texture.view,
+        &god_rays.upsample_bind_group,
+    )
+}
+
+fn god_rays_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<GodRaySettings>().clone();
+
+    egui::Window::new(tr("God rays"))
+        .id(egui::Id::new("God rays"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Enabled"));
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                ui.add(egui::Slider::new(&mut settings.density, 0.1..=1.0).text(tr("Density")))
+                    .on_hover_text(tr("How far towards the light each pixel gathers"));
+                ui.add(egui::Slider::new(&mut settings.decay, 0.9..=1.0).text(tr("Decay")))
+                    .on_hover_text(tr("Falloff of every further sample"));
+                ui.add(egui::Slider::new(&mut settings.weight, 0.0..=0.2).text(tr("Weight")));
+                ui.add(egui::Slider::new(&mut settings.exposure, 0.0..=4.0).text(tr("Exposure")));
+                ui.add(egui::Slider::new(&mut settings.samples, 8..=128).text(tr("Samples")));
+                ui.horizontal(|ui| {
+                    ui.label(tr("Resolution"));
+                    settings.resolution.combo(ui, "god_rays_resolution");
+                });
+            });
+            ui.label(tr("Shafts come from the first directional light"));
+        });
+
+    let mut current = world.resource_mut::<GodRaySettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct GodRaySettings {
+    pub enabled: bool,
+    /// Fraction of the way to the light the samples span.
+    pub density: f32,
+    /// Falloff applied per sample, further samples count for less.
+    pub decay: f32,
+    /// Contribution of a single sample.
+    pub weight: f32,
+    pub exposure: f32,
+    pub samples: u32,
+    /// Resolution the rays render at before they're upsampled.
+    pub resolution: ResolutionScale,
+}
+impl Default for GodRaySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            density: 0.8,
+            decay: 0.97,
+            weight: 0.05,
+            exposure: 1.0,
+            samples: 64,
+            resolution: ResolutionScale::Half,
+        }
+    }
+}
+
+// =============================== RESOURCES ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GodRayParams {
+    pub light_uv: [f32; 2],
+    pub aspect: f32,
+    /// 0 when the light is behind the camera, or there is none.
+    pub strength: f32,
+    pub color: [f32; 4],
+    pub density: f32,
+    pub decay: f32,
+    pub weight: f32,
+    pub exposure: f32,
+    pub samples: u32,
+    pub _padding: [u32; 3],
+}
+impl GodRayParams {
+    pub fn new(
+        settings: &GodRaySettings,
+        camera: &Camera,
+        global: &GlobalTransform,
+        light: &DirectionalLight,
+    ) -> Self {
+        // A directional light sits infinitely far away against its direction
+        let towards_light = -global
+            .0
+            .transform_vector3(Vec3::NEG_Z)
+            .normalize_or(Vec3::NEG_Z);
+        let clip = camera.view_projection() * towards_light.extend(0.0);
+        let forward = (camera.target - camera.eye).normalize_or(Vec3::NEG_Z);
+        let mut params = Self::hidden(settings, camera);
+        if clip.w > 0.0 {
+            let ndc = clip.xy() / clip.w;
+            params.light_uv = [ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5];
+            params.strength = forward.dot(towards_light).max(0.0);
+            params.color = (light.color * light.intensity).extend(1.0).to_array();
+        }
+        params
+    }
+
+    /// Parameters that draw nothing.
+    pub fn hidden(settings: &GodRaySettings, camera: &Camera) -> Self {
+        Self {
+            light_uv: [0.5, 0.5],
+            aspect: camera.aspect,
+            strength: 0.0,
+            color: [0.0; 4],
+            density: settings.density,
+            decay: settings.decay,
+            weight: settings.weight,
+            exposure: settings.exposure,
+            samples: settings.samples,
+            _padding: [0; 3],
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct GodRays {
+    /// Scattered light at the configured resolution scale.
+    pub target: Texture,
+    pub params: wgpu::Buffer,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    pub pipeline: GPUPipeline,
+    /// Adds the target onto the frame buffer.
+    pub upsampler: Upsampler,
+    pub upsample_bind_group: wgpu::BindGroup,
+    /// Whether the last parameters draw anything at all.
+    pub visible: bool,
+}
+impl GodRays {
+    pub fn new(
+        gpu: &GpuContext,
+        settings: &GodRaySettings,
+        depth: &DepthTexture,
+        scaled: &ScaledDepth,
+    ) -> Result<Self> {
+        let size = depth.texture.texture.size();
+        let (width, height) = settings.resolution.size(size.width, size.height);
+        let target = Texture::render_target(
+            &gpu.device,
+            width,
+            height,
+            wgpu::TextureFormat::Rgba16Float,
+            "god_rays_target",
+        );
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("god_rays_params"),
+                contents: bytemuck::bytes_of(&GodRayParams::hidden(settings, &Camera::default())),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("god_rays_bind_group_layout"),
+            });
+
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("god_rays_shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/god_rays.wgsl").into()),
+            });
+        let pipeline = GPUPipelineBuilder::new(&gpu.device)
+            .label("god_rays_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .default_color_target(wgpu::TextureFormat::Rgba16Float)
+            .depth_stencil_state(None)
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState::default())
+            .build()?;
+        // Adds light, leaving the frame buffer's alpha alone
+        let upsampler = Upsampler::new(
+            gpu,
+            "god_rays_upsample_pipeline",
+            wgpu::TextureFormat::Rgba16Float,
+            Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+        )?;
+        let bind_group = Self::create_bind_group(
+            gpu,
+            &layout,
+            &params,
+            scaled.view(settings.resolution, depth),
+        );
+        let upsample_bind_group =
+            upsampler.bind_group(gpu, &target, scaled, settings.resolution, depth);
+
+        Ok(Self {
+            target,
+            params,
+            layout,
+            bind_group,
+            pipeline,
+            upsampler,
+            upsample_bind_group,
+            visible: false,
+        })
+    }
+
+    /// `depth` is the depth at the target's resolution.
+    pub fn create_bind_group(
+        gpu: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        params: &wgpu::Buffer,
+        depth: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+            label: Some("god_rays_bind_group"),
+        })
+    }
+}
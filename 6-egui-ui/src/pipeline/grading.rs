@@ -0,0 +1,608 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::{Mut, World},
+};
+use tracing::{error, info, warn};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    incremental::{IncrementalTask, IncrementalWork, Step},
+    shader::ShaderWatcher,
+    texture::{f32_to_f16, Texture},
+};
+
+use super::{inspector::TextureRegistry, ui::UiPanels};
+
+/// `.cube` or strip `.png` LUT picked up at startup and reloaded on change.
+const LUT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/grading.cube");
+/// Size of the generated identity LUT.
+const NEUTRAL_SIZE: u32 = 32;
+/// Larger LUTs exist but are pointless at 8 bits per channel.
+const MAX_LUT_SIZE: u32 = 128;
+/// A few hundred microseconds of parsing.
+const CUBE_LINES_PER_STEP: usize = 4096;
+
+pub fn setup_color_grading(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let path = PathBuf::from(std::env::var("GRADING_LUT").unwrap_or_else(|_| LUT_PATH.to_string()));
+    let (lut, source) = load_or_neutral(&path);
+    let settings = ColorGradingSettings::default();
+    let grading = ColorGrading::new(gpu, &lut, source, &settings)?;
+
+    world.insert_resource(grading);
+    world.insert_resource(settings);
+    world
+        .get_resource_or_insert_with(ShaderWatcher::default)
+        .watch(&path);
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("grading_lut", |world| {
+            world
+                .get_resource::<ColorGrading>()
+                .map(|grading| &grading.lut.texture)
+        })
+        .with_source(&path);
+    world.insert_resource(LutPath(path));
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(color_grading_panel);
+
+    schedule.add_systems((
+        lut_reload_system,
+        color_grading_params_system.run_if(resource_changed::<ColorGradingSettings>),
+    ));
+
+    Ok(())
+}
+
+/// Falls back to the identity LUT, so a missing or broken file never stops
+/// the app from starting.
+fn load_or_neutral(path: &Path) -> (Lut, String) {
+    if !path.exists() {
+        info!(
+            "No grading LUT at '{}', using a neutral {}³ LUT",
+            path.display(),
+            NEUTRAL_SIZE
+        );
+        return (Lut::neutral(NEUTRAL_SIZE), "neutral".to_string());
+    }
+    match Lut::load(path) {
+        Ok(lut) => (lut, path.display().to_string()),
+        Err(e) => {
+            warn!("Falling back to a neutral LUT: {:?}", e);
+            (Lut::neutral(NEUTRAL_SIZE), "neutral".to_string())
+        }
+    }
+}
+
+/// Swaps the LUT in whenever the watched file changes, once it's parsed in
+/// the background. A broken file keeps the previous LUT, so a half saved
+/// export doesn't flash the screen.
+pub fn lut_reload_system(
+    path: Res<LutPath>,
+    mut watcher: ResMut<ShaderWatcher>,
+    mut work: ResMut<IncrementalWork>,
+    mut grading: ResMut<ColorGrading>,
+) {
+    if !watcher.take_changed(&path.0) {
+        return;
+    }
+    let is_cube = path
+        .0
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("cube"));
+    let cube = if is_cube {
+        match std::fs::read_to_string(&path.0) {
+            Ok(source) => Some(CubeParser::new(source)),
+            Err(e) => {
+                error!("Keeping previous grading LUT: {:?}", e);
+                grading.last_error = Some(format!("Failed to read '{}': {}", path.0.display(), e));
+                return;
+            }
+        }
+    } else {
+        None
+    };
+    work.spawn(
+        trf!("Grading LUT {}", path.0.display()),
+        LutReload {
+            path: path.0.clone(),
+            cube,
+            result: None,
+        },
+    );
+}
+
+pub fn color_grading_params_system(
+    gpu: Res<GpuContext>,
+    settings: Res<ColorGradingSettings>,
+    grading: Res<ColorGrading>,
+) {
+    grading.write_params(&gpu, &settings);
+}
+
+fn color_grading_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<ColorGradingSettings>().clone();
+    let path = world.resource::<LutPath>().0.clone();
+    let mut neutral = false;
+    let mut export = false;
+    {
+        let grading = world.resource::<ColorGrading>();
+
+        egui::Window::new(tr("Color grading"))
+            .id(egui::Id::new("Color grading"))
+            .default_open(false)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut settings.enabled, tr("Enabled"));
+                ui.add(egui::Slider::new(&mut settings.intensity, 0.0..=1.0).text(tr("Intensity")));
+                ui.label(trf!(
+                    "LUT: {} ({}³)",
+                    grading.source,
+                    grading.lut.texture.width()
+                ));
+                ui.label(trf!("Watching {}", path.display()));
+                if let Some(error) = &grading.last_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                ui.horizontal(|ui| {
+                    neutral = ui.button(tr("Use neutral LUT")).clicked();
+                    export = ui
+                        .button(tr("Export neutral strip"))
+                        .on_hover_text(tr(
+                            "Writes an identity strip PNG to grade in an image editor",
+                        ))
+                        .clicked();
+                });
+            });
+    }
+
+    if neutral {
+        world.resource_scope::<ColorGrading, _>(|world, mut grading| {
+            let gpu = world.resource::<GpuContext>();
+            grading.set_lut(gpu, &Lut::neutral(NEUTRAL_SIZE), "neutral".to_string());
+        });
+    }
+    if export {
+        let strip = path.with_file_name("neutral_lut.png");
+        match Lut::neutral(NEUTRAL_SIZE).save_strip(&strip) {
+            Ok(()) => info!("Wrote neutral LUT strip to {}", strip.display()),
+            Err(e) => error!("Failed to write neutral LUT strip: {:?}", e),
+        }
+    }
+
+    let mut current = world.resource_mut::<ColorGradingSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== LUT ===============================
+/// A 3D color lookup table, red varying fastest, then green, then blue.
+pub struct Lut {
+    pub size: u32,
+    pub texels: Vec<[f32; 4]>,
+}
+impl Lut {
+    /// Maps every color to itself.
+    pub fn neutral(size: u32) -> Self {
+        let max = (size - 1) as f32;
+        let texels = (0..size * size * size)
+            .map(|i| {
+                let (r, g, b) = (i % size, i / size % size, i / (size * size));
+                [r as f32 / max, g as f32 / max, b as f32 / max, 1.0]
+            })
+            .collect();
+        Self { size, texels }
+    }
+
+    /// Loads a `.cube` file or a strip `.png`, picked by extension.
+    pub fn load(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let lut = match extension.as_deref() {
+            Some("cube") => {
+                let source = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read '{}'", path.display()))?;
+                Self::parse_cube(&source)
+            }
+            Some("png") => {
+                let image = image::open(path)
+                    .with_context(|| format!("Failed to decode '{}'", path.display()))?;
+                Self::from_strip(&image.to_rgba8())
+            }
+            _ => bail!("Unsupported LUT format, expected .cube or .png"),
+        };
+        lut.with_context(|| format!("Invalid LUT '{}'", path.display()))
+    }
+
+    /// Parses the Adobe/Resolve `.cube` format. Only 3D LUTs over the
+    /// default 0..1 domain are supported.
+    pub fn parse_cube(source: &str) -> Result<Self> {
+        let mut parser = CubeParser::new(source.to_string());
+        Ok(parser
+            .parse_lines(usize::MAX)?
+            .expect("every line was parsed"))
+    }
+
+    /// Reads a strip of `size` blue slices laid out left to right, each with
+    /// red along x and green along y, so the strip is `size² x size`.
+    pub fn from_strip(image: &image::RgbaImage) -> Result<Self> {
+        let size = image.height();
+        if !(2..=MAX_LUT_SIZE).contains(&size) || image.width() != size * size {
+            bail!(
+                "Strip is {}x{}, expected N²xN with N up to {}",
+                image.width(),
+                image.height(),
+                MAX_LUT_SIZE
+            );
+        }
+        let texels = (0..size * size * size)
+            .map(|i| {
+                let (r, g, b) = (i % size, i / size % size, i / (size * size));
+                let [red, green, blue, _] = image.get_pixel(b * size + r, g).0;
+                [
+                    red as f32 / 255.0,
+                    green as f32 / 255.0,
+                    blue as f32 / 255.0,
+                    1.0,
+                ]
+            })
+            .collect();
+        Ok(Self { size, texels })
+    }
+
+    pub fn save_strip(&self, path: &Path) -> Result<()> {
+        let size = self.size;
+        let strip = image::RgbaImage::from_fn(size * size, size, |x, y| {
+            let (b, r) = (x / size, x % size);
+            let texel = self.texels[(r + y * size + b * size * size) as usize];
+            let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            image::Rgba([channel(texel[0]), channel(texel[1]), channel(texel[2]), 255])
+        });
+        strip.save(path)?;
+        Ok(())
+    }
+}
+
+/// [`Lut::parse_cube`] a slice at a time, large LUTs are millions of lines.
+pub struct CubeParser {
+    source: String,
+    /// Byte offset of the next line.
+    position: usize,
+    line: usize,
+    size: Option<u32>,
+    texels: Vec<[f32; 4]>,
+}
+impl CubeParser {
+    pub fn new(source: String) -> Self {
+        Self {
+            source,
+            position: 0,
+            line: 0,
+            size: None,
+            texels: Vec::new(),
+        }
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.position as f32 / self.source.len().max(1) as f32
+    }
+
+    /// Parses up to `lines` more lines, and returns the LUT after the last.
+    pub fn parse_lines(&mut self, lines: usize) -> Result<Option<Lut>> {
+        for raw in self.source[self.position..]
+            .split_inclusive('\n')
+            .take(lines)
+        {
+            self.position += raw.len();
+            let number = self.line;
+            self.line += 1;
+
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let numbers = |words: &[&str]| -> Result<Vec<f32>> {
+                words
+                    .iter()
+                    .map(|word| {
+                        word.parse::<f32>()
+                            .with_context(|| format!("line {}: bad number '{}'", number + 1, word))
+                    })
+                    .collect()
+            };
+            match words[0] {
+                "TITLE" => {}
+                "LUT_3D_SIZE" => {
+                    let value: u32 = words
+                        .get(1)
+                        .and_then(|word| word.parse().ok())
+                        .with_context(|| format!("line {}: bad LUT_3D_SIZE", number + 1))?;
+                    if !(2..=MAX_LUT_SIZE).contains(&value) {
+                        bail!("line {}: LUT size {} out of range", number + 1, value);
+                    }
+                    self.size = Some(value);
+                    self.texels.reserve((value * value * value) as usize);
+                }
+                "LUT_1D_SIZE" => bail!("1D LUTs are not supported"),
+                keyword @ ("DOMAIN_MIN" | "DOMAIN_MAX") => {
+                    let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    if numbers(&words[1..])?.iter().any(|value| *value != expected) {
+                        bail!("line {}: only the 0..1 domain is supported", number + 1);
+                    }
+                }
+                _ => {
+                    let values = numbers(&words)?;
+                    let [r, g, b] = values[..] else {
+                        bail!("line {}: expected three values", number + 1);
+                    };
+                    self.texels.push([r, g, b, 1.0]);
+                }
+            }
+        }
+        if self.position < self.source.len() {
+            return Ok(None);
+        }
+
+        let size = self.size.context("missing LUT_3D_SIZE")?;
+        let expected = (size * size * size) as usize;
+        if self.texels.len() != expected {
+            bail!("{} entries, expected {}", self.texels.len(), expected);
+        }
+        Ok(Some(Lut {
+            size,
+            texels: std::mem::take(&mut self.texels),
+        }))
+    }
+}
+
+/// Reloads the LUT over as many frames as it takes, a `.cube` file a slice
+/// of lines per step and a strip in one.
+struct LutReload {
+    path: PathBuf,
+    cube: Option<CubeParser>,
+    result: Option<Result<Lut>>,
+}
+impl IncrementalTask for LutReload {
+    fn step(&mut self) -> Step {
+        let result = match &mut self.cube {
+            Some(parser) => match parser.parse_lines(CUBE_LINES_PER_STEP) {
+                Ok(None) => return Step::Pending(parser.progress()),
+                Ok(Some(lut)) => Ok(lut),
+                Err(e) => Err(e.context(format!("Invalid LUT '{}'", self.path.display()))),
+            },
+            None => Lut::load(&self.path),
+        };
+        self.result = Some(result);
+        Step::Done
+    }
+
+    fn finish(self: Box<Self>, world: &mut World) {
+        let Some(result) = self.result else {
+            return;
+        };
+        world.resource_scope(|world, mut grading: Mut<ColorGrading>| match result {
+            Ok(lut) => {
+                info!(
+                    "Reloaded {}³ grading LUT from {}",
+                    lut.size,
+                    self.path.display()
+                );
+                let gpu = world.resource::<GpuContext>();
+                grading.set_lut(gpu, &lut, self.path.display().to_string());
+            }
+            Err(e) => {
+                error!("Keeping previous grading LUT: {:?}", e);
+                grading.last_error = Some(format!("{:?}", e));
+            }
+        });
+    }
+}
+
+// =============================== RESOURCES ===============================
+#[derive(Resource)]
+pub struct LutPath(pub PathBuf);
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct ColorGradingSettings {
+    pub enabled: bool,
+    /// Blend between the ungraded (0) and the fully graded (1) image.
+    pub intensity: f32,
+}
+impl Default for ColorGradingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            intensity: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ColorGradingParams {
+    pub intensity: f32,
+    pub _padding: [f32; 3],
+}
+impl ColorGradingParams {
+    pub fn new(settings: &ColorGradingSettings) -> Self {
+        Self {
+            intensity: if settings.enabled {
+                settings.intensity
+            } else {
+                0.0
+            },
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+/// Group 1 of the present pipeline: the LUT applied to the final display
+/// color, right before it's written to the surface.
+#[derive(Resource)]
+pub struct ColorGrading {
+    pub lut: Texture,
+    /// Clamps, so colors at 0 and 1 don't bleed into the opposite edge.
+    pub sampler: wgpu::Sampler,
+    pub params: wgpu::Buffer,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    /// File path, or "neutral" for the identity LUT.
+    pub source: String,
+    pub last_error: Option<String>,
+}
+impl ColorGrading {
+    pub fn new(
+        gpu: &GpuContext,
+        lut: &Lut,
+        source: String,
+        settings: &ColorGradingSettings,
+    ) -> Result<Self> {
+        let texture = Self::create_lut_texture(gpu, lut)?;
+        let sampler = gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("grading_lut_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("grading_params"),
+                contents: bytemuck::bytes_of(&ColorGradingParams::new(settings)),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D3,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("grading_bind_group_layout"),
+            });
+        let bind_group = Self::create_bind_group(gpu, &layout, &texture, &sampler, &params);
+
+        Ok(Self {
+            lut: texture,
+            sampler,
+            params,
+            layout,
+            bind_group,
+            source,
+            last_error: None,
+        })
+    }
+
+    /// Replaces the LUT, which may have a different size than the old one.
+    pub fn set_lut(&mut self, gpu: &GpuContext, lut: &Lut, source: String) {
+        match Self::create_lut_texture(gpu, lut) {
+            Ok(texture) => {
+                self.bind_group = Self::create_bind_group(
+                    gpu,
+                    &self.layout,
+                    &texture,
+                    &self.sampler,
+                    &self.params,
+                );
+                self.lut = texture;
+                self.source = source;
+                self.last_error = None;
+            }
+            Err(e) => self.last_error = Some(format!("{:?}", e)),
+        }
+    }
+
+    pub fn write_params(&self, gpu: &GpuContext, settings: &ColorGradingSettings) {
+        let params = ColorGradingParams::new(settings);
+        gpu.queue
+            .write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+    }
+
+    fn create_lut_texture(gpu: &GpuContext, lut: &Lut) -> Result<Texture> {
+        let texture = Texture::volume(
+            &gpu.device,
+            [lut.size; 3],
+            wgpu::TextureFormat::Rgba16Float,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            "grading_lut",
+        );
+        let halves: Vec<u16> = lut
+            .texels
+            .iter()
+            .flatten()
+            .map(|&v| f32_to_f16(v))
+            .collect();
+        texture.write_slices(&gpu.queue, 0, bytemuck::cast_slice(&halves))?;
+        Ok(texture)
+    }
+
+    fn create_bind_group(
+        gpu: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        lut: &Texture,
+        sampler: &wgpu::Sampler,
+        params: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&lut.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+            label: Some("grading_bind_group"),
+        })
+    }
+}
@@ -0,0 +1,590 @@
+use std::fmt::Write;
+
+use anyhow::{Context, Result};
+use bevy_ecs::{system::Resource, world::World};
+use pollster::FutureExt;
+use tracing::{error, info, info_span, warn};
+use tracing_tracy::client::Client;
+
+use crate::{gpu::GpuContext, profiler::GpuTimer};
+
+/// Everything a pass needs to record its commands for the current frame.
+pub struct PassContext<'a> {
+    pub label: &'static str,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    pub surface_view: &'a wgpu::TextureView,
+    /// For views of the surface in another format than its own.
+    pub surface_texture: &'a wgpu::Texture,
+}
+
+pub type PassFn = Box<dyn FnMut(&mut World, &mut PassContext) -> Result<()> + Send + Sync>;
+
+/// Where a pass records its commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PassQueue {
+    Graphics,
+    /// Compute work that doesn't depend on anything recorded earlier in the
+    /// frame. wgpu exposes a single queue, so with [`AsyncComputeSettings`]
+    /// enabled these passes are recorded into their own command buffer and
+    /// submitted ahead of the graphics work instead, which lets the driver
+    /// start on them while the CPU is still recording the rest of the frame.
+    AsyncCompute,
+}
+
+pub struct GraphPass {
+    pub label: &'static str,
+    pub queue: PassQueue,
+    /// What the pass declared it touches, one entry per usage scope.
+    pub scopes: Vec<Vec<ResourceUse>>,
+    run: PassFn,
+}
+
+/// Toggles the separate async compute submission, off runs every pass in
+/// graph order on the graphics encoder.
+#[derive(Resource, Clone, Copy, Default, PartialEq)]
+pub struct AsyncComputeSettings {
+    pub enabled: bool,
+}
+
+/// With validation on, the graph logs its execution order, what every pass
+/// touches and the hazards found in it, and records each pass in its own
+/// wgpu error scope. A validation error then fails the frame with the pass
+/// and the hazards behind it instead of reaching the device's error handler.
+#[derive(Resource, Clone, Copy, Default, PartialEq)]
+pub struct GraphValidationSettings {
+    pub enabled: bool,
+}
+
+// =============================== RESOURCE ACCESS ===============================
+/// How a pass uses a resource. wgpu allows a resource in several read uses
+/// at once within one pass, but a writable use only on its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Sample,
+    /// As a storage, vertex, index or indirect buffer.
+    Read,
+    /// As a storage buffer or texture written by the shader.
+    Write,
+    CopySrc,
+    CopyDst,
+    ColorAttachment,
+    DepthAttachment,
+}
+impl Access {
+    pub fn writes(self) -> bool {
+        matches!(
+            self,
+            Self::Write | Self::CopyDst | Self::ColorAttachment | Self::DepthAttachment
+        )
+    }
+
+    /// Can't share a usage scope with any other use of the same resource.
+    fn exclusive(self) -> bool {
+        matches!(
+            self,
+            Self::Write | Self::ColorAttachment | Self::DepthAttachment
+        )
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Sample => "samples",
+            Self::Read => "reads",
+            Self::Write => "writes",
+            Self::CopySrc => "copies from",
+            Self::CopyDst => "copies into",
+            Self::ColorAttachment => "renders into",
+            Self::DepthAttachment => "renders depth into",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResourceUse {
+    pub resource: &'static str,
+    pub access: Access,
+}
+
+pub fn sample(resource: &'static str) -> ResourceUse {
+    ResourceUse {
+        resource,
+        access: Access::Sample,
+    }
+}
+
+pub fn read(resource: &'static str) -> ResourceUse {
+    ResourceUse {
+        resource,
+        access: Access::Read,
+    }
+}
+
+pub fn write(resource: &'static str) -> ResourceUse {
+    ResourceUse {
+        resource,
+        access: Access::Write,
+    }
+}
+
+pub fn copy_src(resource: &'static str) -> ResourceUse {
+    ResourceUse {
+        resource,
+        access: Access::CopySrc,
+    }
+}
+
+pub fn copy_dst(resource: &'static str) -> ResourceUse {
+    ResourceUse {
+        resource,
+        access: Access::CopyDst,
+    }
+}
+
+pub fn color(resource: &'static str) -> ResourceUse {
+    ResourceUse {
+        resource,
+        access: Access::ColorAttachment,
+    }
+}
+
+pub fn depth(resource: &'static str) -> ResourceUse {
+    ResourceUse {
+        resource,
+        access: Access::DepthAttachment,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// Valid, but the pass may not see what the graph order suggests.
+    Warning,
+    /// wgpu rejects the pass.
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Hazard {
+    pub pass: &'static str,
+    pub resource: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// What [`RenderGraph::validate`] found.
+#[derive(Clone, Debug)]
+pub struct GraphReport {
+    pub async_compute: bool,
+    /// Labels in the order the passes are submitted.
+    pub order: Vec<(&'static str, PassQueue)>,
+    pub hazards: Vec<Hazard>,
+}
+impl GraphReport {
+    pub fn errors(&self) -> usize {
+        self.hazards
+            .iter()
+            .filter(|hazard| hazard.severity == Severity::Error)
+            .count()
+    }
+}
+
+fn describe_uses(scopes: &[Vec<ResourceUse>]) -> String {
+    let mut text = String::new();
+    for (i, scope) in scopes.iter().enumerate() {
+        if i > 0 {
+            text.push_str(" | ");
+        }
+        for (j, resource_use) in scope.iter().enumerate() {
+            if j > 0 {
+                text.push_str(", ");
+            }
+            let _ = write!(
+                text,
+                "{} {}",
+                resource_use.access.describe(),
+                resource_use.resource
+            );
+        }
+    }
+    text
+}
+
+// =============================== RENDER GRAPH ===============================
+/// An ordered list of passes recorded once per frame by the render system.
+///
+/// Every pass is wrapped in a tracing span, a Tracy zone and a GPU debug group
+/// named after its label, so profiling output always matches the graph. With
+/// [`GpuZoneSettings::tracy`](crate::profiler::GpuZoneSettings::tracy) on,
+/// Tracy also gets a GPU zone per pass from the [`GpuTimer`] queries.
+#[derive(Resource, Default)]
+pub struct RenderGraph {
+    passes: Vec<GraphPass>,
+    /// The last report logged by the validation mode.
+    report: Option<GraphReport>,
+}
+
+impl RenderGraph {
+    pub fn add_pass(
+        &mut self,
+        label: &'static str,
+        run: impl FnMut(&mut World, &mut PassContext) -> Result<()> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.passes.push(GraphPass {
+            label,
+            queue: PassQueue::Graphics,
+            scopes: Vec::new(),
+            run: Box::new(run),
+        });
+        self
+    }
+
+    /// Adds a compute pass that may be submitted ahead of the graphics work,
+    /// see [`PassQueue::AsyncCompute`]. It then sees the results of the
+    /// previous frame's graphics passes rather than this frame's.
+    pub fn add_async_compute_pass(
+        &mut self,
+        label: &'static str,
+        run: impl FnMut(&mut World, &mut PassContext) -> Result<()> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.passes.push(GraphPass {
+            label,
+            queue: PassQueue::AsyncCompute,
+            scopes: Vec::new(),
+            run: Box::new(run),
+        });
+        self
+    }
+
+    /// Declares what the pass added last touches in one usage scope, that
+    /// is one render or compute pass, or the copies recorded outside of
+    /// them. Call it again for every further scope of the pass. Resources
+    /// are named freely, the names only have to agree between passes.
+    pub fn uses(&mut self, uses: &[ResourceUse]) -> &mut Self {
+        self.passes
+            .last_mut()
+            .expect("uses() describes the pass added before it")
+            .scopes
+            .push(uses.to_vec());
+        self
+    }
+
+    pub fn report(&self) -> Option<&GraphReport> {
+        self.report.as_ref()
+    }
+
+    /// The passes in the order their commands reach the queue.
+    pub fn execution_order(&self, async_compute: bool) -> Vec<&GraphPass> {
+        let is_async = |pass: &&GraphPass| async_compute && pass.queue == PassQueue::AsyncCompute;
+        let compute = self.passes.iter().filter(is_async);
+        let graphics = self.passes.iter().filter(|pass| !is_async(pass));
+        compute.chain(graphics).collect()
+    }
+
+    /// Checks the declared uses for what wgpu would reject within a pass,
+    /// and for async compute passes that see a resource at another point in
+    /// the frame than their place in the graph suggests.
+    pub fn validate(&self, async_compute: bool) -> GraphReport {
+        let mut hazards = Vec::new();
+        for pass in &self.passes {
+            for scope in &pass.scopes {
+                for (i, first) in scope.iter().enumerate() {
+                    let Some(second) = scope[i + 1..].iter().find(|other| {
+                        other.resource == first.resource
+                            && (first.access.exclusive() || other.access.exclusive())
+                    }) else {
+                        continue;
+                    };
+                    let depth_hint = [first.access, second.access].contains(&Access::Sample)
+                        && [first.access, second.access].contains(&Access::DepthAttachment);
+                    let mut message = format!(
+                        "'{}' {} '{}' and also {} it in the same pass, but a resource \
+                         written in a pass can't be used any other way in it",
+                        pass.label,
+                        first.access.describe(),
+                        first.resource,
+                        second.access.describe()
+                    );
+                    if depth_hint {
+                        message.push_str(", sample a copy of the depth instead");
+                    }
+                    hazards.push(Hazard {
+                        pass: pass.label,
+                        resource: first.resource,
+                        severity: Severity::Error,
+                        message,
+                    });
+                }
+            }
+        }
+
+        if async_compute {
+            let touches = |pass: &GraphPass, resource: &str, writes: bool| {
+                pass.scopes.iter().flatten().any(|resource_use| {
+                    resource_use.resource == resource && (!writes || resource_use.access.writes())
+                })
+            };
+            for (index, pass) in self.passes.iter().enumerate() {
+                if pass.queue != PassQueue::AsyncCompute {
+                    continue;
+                }
+                let earlier: Vec<_> = self.passes[..index]
+                    .iter()
+                    .filter(|earlier| earlier.queue == PassQueue::Graphics)
+                    .collect();
+                for resource_use in pass.scopes.iter().flatten() {
+                    let resource = resource_use.resource;
+                    let conflict = if resource_use.access.writes() {
+                        earlier
+                            .iter()
+                            .find(|earlier| touches(earlier, resource, false))
+                            .map(|earlier| {
+                                format!(
+                                    "'{}' is submitted ahead of the graphics work, so '{}' \
+                                     placed before it already sees what it {} '{}' this frame",
+                                    pass.label,
+                                    earlier.label,
+                                    resource_use.access.describe(),
+                                    resource
+                                )
+                            })
+                    } else {
+                        earlier
+                            .iter()
+                            .rev()
+                            .find(|earlier| touches(earlier, resource, true))
+                            .map(|earlier| {
+                                format!(
+                                    "'{}' is submitted ahead of the graphics work, so it {} \
+                                     '{}' as '{}' left it the frame before",
+                                    pass.label,
+                                    resource_use.access.describe(),
+                                    resource,
+                                    earlier.label
+                                )
+                            })
+                    };
+                    if let Some(message) = conflict {
+                        hazards.push(Hazard {
+                            pass: pass.label,
+                            resource,
+                            severity: Severity::Warning,
+                            message,
+                        });
+                    }
+                }
+            }
+        }
+
+        GraphReport {
+            async_compute,
+            order: self
+                .execution_order(async_compute)
+                .iter()
+                .map(|pass| (pass.label, pass.queue))
+                .collect(),
+            hazards,
+        }
+    }
+
+    fn log_report(&self, report: &GraphReport) {
+        let order: Vec<_> = report
+            .order
+            .iter()
+            .map(|(label, queue)| match queue {
+                PassQueue::AsyncCompute if report.async_compute => format!("{} (compute)", label),
+                _ => label.to_string(),
+            })
+            .collect();
+        info!("Render graph order: {}", order.join(" -> "));
+        for pass in self.execution_order(report.async_compute) {
+            if pass.scopes.is_empty() {
+                info!("  {}: nothing declared", pass.label);
+            } else {
+                info!("  {}: {}", pass.label, describe_uses(&pass.scopes));
+            }
+        }
+        for hazard in &report.hazards {
+            match hazard.severity {
+                Severity::Warning => warn!(
+                    pass = hazard.pass,
+                    resource = hazard.resource,
+                    "Render graph hazard: {}",
+                    hazard.message
+                ),
+                Severity::Error => error!(
+                    pass = hazard.pass,
+                    resource = hazard.resource,
+                    "Render graph hazard: {}",
+                    hazard.message
+                ),
+            }
+        }
+    }
+
+    /// Records every pass into its own command buffer, in graph order. Async
+    /// compute passes are tagged [`PassQueue::AsyncCompute`] only when
+    /// `async_compute` is set, otherwise they are submitted with the graphics
+    /// work. `validate` turns on the validation mode, see
+    /// [`GraphValidationSettings`].
+    pub fn record(
+        &mut self,
+        world: &mut World,
+        surface_texture: &wgpu::Texture,
+        surface_view: &wgpu::TextureView,
+        async_compute: bool,
+        validate: bool,
+    ) -> Result<Vec<RecordedPass>> {
+        let _graph_span = info_span!("render_graph").entered();
+
+        if !validate {
+            self.report = None;
+        } else if self
+            .report
+            .as_ref()
+            .is_none_or(|report| report.async_compute != async_compute)
+        {
+            let report = self.validate(async_compute);
+            self.log_report(&report);
+            self.report = Some(report);
+        }
+
+        let mut recorded = Vec::with_capacity(self.passes.len());
+        for pass in &mut self.passes {
+            let queue = match pass.queue {
+                PassQueue::AsyncCompute if async_compute => PassQueue::AsyncCompute,
+                _ => PassQueue::Graphics,
+            };
+            let commands = if validate {
+                world
+                    .resource::<GpuContext>()
+                    .device
+                    .push_error_scope(wgpu::ErrorFilter::Validation);
+                let commands = Self::record_pass(world, pass, surface_texture, surface_view);
+                let device = &world.resource::<GpuContext>().device;
+                if let Some(error) = device.pop_error_scope().block_on() {
+                    let report = self.report.as_ref().expect("validated above");
+                    return Err(validation_failure(pass, report, error));
+                }
+                commands?
+            } else {
+                Self::record_pass(world, pass, surface_texture, surface_view)?
+            };
+            recorded.push(RecordedPass { queue, commands });
+        }
+
+        Ok(recorded)
+    }
+
+    fn record_pass(
+        world: &mut World,
+        pass: &mut GraphPass,
+        surface_texture: &wgpu::Texture,
+        surface_view: &wgpu::TextureView,
+    ) -> Result<wgpu::CommandBuffer> {
+        let _span = info_span!("render_pass", pass = pass.label).entered();
+        let _zone = Client::running().map(|client| {
+            client.span_alloc(Some(pass.label), "RenderGraph::record", file!(), line!(), 0)
+        });
+
+        let mut encoder = world
+            .resource::<GpuContext>()
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some(pass.label),
+            });
+        encoder.push_debug_group(pass.label);
+        let timed = world
+            .get_resource_mut::<GpuTimer>()
+            .and_then(|mut timer| timer.begin_pass(&mut encoder, pass.label));
+        let mut ctx = PassContext {
+            label: pass.label,
+            encoder: &mut encoder,
+            surface_view,
+            surface_texture,
+        };
+        let result = (pass.run)(world, &mut ctx);
+        if let Some(mut timer) = world.get_resource_mut::<GpuTimer>() {
+            timer.end_pass(&mut encoder, timed);
+        }
+        encoder.pop_debug_group();
+
+        result.with_context(|| format!("Render pass '{}' failed", pass.label))?;
+        Ok(encoder.finish())
+    }
+}
+
+/// A wgpu validation error, put in terms of the graph: the pass it came from,
+/// what the pass declared and the hazards found in it.
+fn validation_failure(pass: &GraphPass, report: &GraphReport, error: wgpu::Error) -> anyhow::Error {
+    let mut message = format!(
+        "Render pass '{}' failed wgpu validation: {}",
+        pass.label, error
+    );
+    if pass.scopes.is_empty() {
+        message.push_str("\nThe pass declares no resource uses");
+    } else {
+        let _ = write!(message, "\nThe pass {}", describe_uses(&pass.scopes));
+    }
+    for hazard in report
+        .hazards
+        .iter()
+        .filter(|hazard| hazard.pass == pass.label)
+    {
+        let _ = write!(message, "\nHazard: {}", hazard.message);
+    }
+    anyhow::anyhow!(message)
+}
+
+/// Commands of one pass, ready to submit.
+pub struct RecordedPass {
+    pub queue: PassQueue,
+    pub commands: wgpu::CommandBuffer,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::render::render_graph;
+
+    fn nothing(_: &mut World, _: &mut PassContext) -> Result<()> {
+        Ok(())
+    }
+
+    #[test]
+    fn hazards_are_found_within_passes_and_across_queues() {
+        let mut graph = RenderGraph::default();
+        graph
+            .add_pass("opaque", nothing)
+            .uses(&[color("frame"), depth("depth")])
+            .add_pass("fog", nothing)
+            .uses(&[sample("depth"), color("frame"), depth("depth")])
+            .add_async_compute_pass("simulate", nothing)
+            .uses(&[sample("depth"), write("particles")])
+            .add_pass("draw", nothing)
+            .uses(&[read("particles"), color("frame"), depth("depth")]);
+
+        let report = graph.validate(false);
+        assert_eq!(report.errors(), 1);
+        assert_eq!(report.hazards.len(), 1);
+        assert_eq!(report.hazards[0].pass, "fog");
+        assert_eq!(report.hazards[0].resource, "depth");
+        assert_eq!(report.order[2], ("simulate", PassQueue::AsyncCompute));
+
+        // Submitted first, the simulation sees last frame's depth
+        let report = graph.validate(true);
+        assert_eq!(report.order[0], ("simulate", PassQueue::AsyncCompute));
+        let warning = report
+            .hazards
+            .iter()
+            .find(|hazard| hazard.severity == Severity::Warning)
+            .unwrap();
+        assert_eq!(warning.pass, "simulate");
+        assert!(warning.message.contains("'fog'"));
+
+        // The renderer's own graph declares nothing wgpu would reject
+        let graph = render_graph();
+        for async_compute in [false, true] {
+            let report = graph.validate(async_compute);
+            assert_eq!(report.errors(), 0, "{:?}", report.hazards);
+        }
+    }
+}
@@ -0,0 +1,669 @@
+use anyhow::{Context, Result};
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::Vec3;
+use tracing::error;
+
+use crate::i18n::{tr, trf};
+use crate::{gpu::GpuContext, shader::load_shader_source};
+
+use super::{
+    compute::{read_buffer, DispatchSite, GPUComputePipeline},
+    graph::PassContext,
+    post::{PostSettings, WhiteBalance},
+    present::FrameBuffer,
+    render::{render_system, submit_system},
+    ui::{EguiState, UiPanels},
+};
+
+const SHADER_NAME: &str = "histogram.wgsl";
+pub const BINS: usize = 256;
+const DEFAULT_PHOTO: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/stone.png");
+/// Gains are clamped so a nearly single colored image doesn't blow up.
+const MAX_GAIN: f32 = 4.0;
+const PLOT_SIZE: egui::Vec2 = egui::vec2(256.0, 96.0);
+
+pub fn setup_histogram(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let frame_buffer = world
+        .get_resource::<FrameBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("FrameBuffer resource not found"))?;
+
+    let pipelines = HistogramPipelines::new(gpu)?;
+    let scene = HistogramBins::new(gpu, &pipelines, &frame_buffer.texture.texture, "scene");
+    let photo = Photo::load(gpu, &pipelines, DEFAULT_PHOTO)?;
+
+    let (photo_id, balanced_id) = world.resource_scope::<EguiState, _>(|world, mut ui| {
+        let device = &world.resource::<GpuContext>().device;
+        (
+            ui.renderer
+                .register_native_texture(device, &photo.view, wgpu::FilterMode::Linear),
+            ui.renderer.register_native_texture(
+                device,
+                &photo.balanced_view,
+                wgpu::FilterMode::Linear,
+            ),
+        )
+    });
+
+    world.insert_resource(HistogramState {
+        scene: None,
+        scene_pending: false,
+        photo: Some(photo.histogram.clone()),
+        path_input: DEFAULT_PHOTO.to_string(),
+        photo_id,
+        balanced_id,
+        panel_open: false,
+        error: None,
+    });
+    world.insert_resource(HistogramSettings::default());
+    world.insert_resource(HistogramScene { bins: scene });
+    world.insert_resource(photo);
+    world.insert_resource(pipelines);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(histogram_panel);
+
+    schedule.add_systems((
+        histogram_bind_group_system
+            .run_if(resource_changed::<FrameBuffer>)
+            .before(render_system),
+        histogram_photo_system
+            .run_if(resource_changed::<HistogramSettings>)
+            .before(render_system),
+        histogram_readback_system.after(submit_system),
+    ));
+
+    Ok(())
+}
+
+/// Follows the frame buffer across resizes.
+pub fn histogram_bind_group_system(
+    gpu: Res<GpuContext>,
+    pipelines: Res<HistogramPipelines>,
+    frame_buffer: Res<FrameBuffer>,
+    mut scene: ResMut<HistogramScene>,
+) {
+    scene.bins = HistogramBins::new(&gpu, &pipelines, &frame_buffer.texture.texture, "scene");
+}
+
+/// Loads, measures and balances a photo when another one was picked.
+pub fn histogram_photo_system(
+    gpu: Res<GpuContext>,
+    pipelines: Res<HistogramPipelines>,
+    settings: Res<HistogramSettings>,
+    mut photo: ResMut<Photo>,
+    mut state: ResMut<HistogramState>,
+    mut egui: ResMut<EguiState>,
+) {
+    if photo.path == settings.photo_path {
+        return;
+    }
+    match Photo::load(&gpu, &pipelines, &settings.photo_path) {
+        Ok(loaded) => {
+            *photo = loaded;
+            egui.renderer.update_native_texture(
+                &gpu.device,
+                &photo.view,
+                wgpu::FilterMode::Linear,
+                state.photo_id,
+            );
+            egui.renderer.update_native_texture(
+                &gpu.device,
+                &photo.balanced_view,
+                wgpu::FilterMode::Linear,
+                state.balanced_id,
+            );
+            state.photo = Some(photo.histogram.clone());
+            state.error = None;
+        }
+        Err(e) => {
+            error!("Failed to load {}: {:?}", settings.photo_path, e);
+            state.error = Some(format!("{:#}", e));
+        }
+    }
+}
+
+/// Counts the frame buffer before it's presented, while the white balance
+/// needs it or the panel shows it.
+pub fn histogram_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let settings = world.resource::<HistogramSettings>();
+    let state = world.resource::<HistogramState>();
+    let wanted = world.resource::<PostSettings>().white_balance
+        || (state.panel_open && settings.source == HistogramSource::Scene);
+    if !wanted {
+        return Ok(());
+    }
+
+    let gpu = world.resource::<GpuContext>();
+    let pipelines = world.resource::<HistogramPipelines>();
+    world
+        .resource::<HistogramScene>()
+        .bins
+        .encode(gpu, pipelines, ctx.encoder)?;
+    world.resource_mut::<HistogramState>().scene_pending = true;
+    Ok(())
+}
+
+/// Reads back what the pass counted and updates the white balance gains.
+/// Blocks on the GPU, so it only does work on frames the pass ran.
+pub fn histogram_readback_system(
+    gpu: Res<GpuContext>,
+    scene: Res<HistogramScene>,
+    mut state: ResMut<HistogramState>,
+    mut white_balance: ResMut<WhiteBalance>,
+) {
+    if !state.scene_pending {
+        return;
+    }
+    state.scene_pending = false;
+    match scene.bins.read(&gpu.device) {
+        Ok(histogram) => {
+            white_balance.gains = histogram.gray_world_gains();
+            state.scene = Some(histogram);
+        }
+        Err(e) => error!("Failed to read back the scene histogram: {:?}", e),
+    }
+}
+
+fn histogram_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<HistogramSettings>().clone();
+    let gains = world.resource::<WhiteBalance>().gains;
+    let photo_gains = world.resource::<Photo>().histogram.gray_world_gains();
+    let mut state = world.resource_mut::<HistogramState>();
+
+    let response = egui::Window::new(tr("Histogram"))
+        .id(egui::Id::new("Histogram"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut settings.source, HistogramSource::Scene, tr("Scene"));
+                ui.radio_value(&mut settings.source, HistogramSource::Photo, tr("Photo"));
+                ui.checkbox(&mut settings.log_scale, tr("Log scale"));
+            });
+
+            match settings.source {
+                HistogramSource::Scene => {
+                    if let Some(histogram) = &state.scene {
+                        histogram_plot(ui, histogram, settings.log_scale);
+                    }
+                    gains_label(ui, histogram_gains(state.scene.as_ref()).unwrap_or(gains));
+                    ui.label(tr("Apply it with Auto white balance in Post effects"));
+                }
+                HistogramSource::Photo => {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut state.path_input);
+                        if ui.button(tr("Load")).clicked() {
+                            settings.photo_path = state.path_input.clone();
+                        }
+                    });
+                    if let Some(error) = &state.error {
+                        ui.colored_label(egui::Color32::RED, error);
+                    }
+                    ui.horizontal(|ui| {
+                        let size = egui::vec2(PLOT_SIZE.x / 2.0 - 4.0, PLOT_SIZE.x / 2.0 - 4.0);
+                        ui.image((state.photo_id, size));
+                        ui.image((state.balanced_id, size));
+                    });
+                    if let Some(histogram) = &state.photo {
+                        histogram_plot(ui, histogram, settings.log_scale);
+                    }
+                    gains_label(ui, photo_gains);
+                }
+            }
+        });
+    // Collapsed windows return no inner response, the scene isn't counted then
+    state.panel_open = response.is_some_and(|response| response.inner.is_some());
+
+    let mut current = world.resource_mut::<HistogramSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+fn histogram_gains(histogram: Option<&Histogram>) -> Option<Vec3> {
+    histogram.map(Histogram::gray_world_gains)
+}
+
+fn gains_label(ui: &mut egui::Ui, gains: Vec3) {
+    ui.label(trf!(
+        "Gray world gains: R {:.2}, G {:.2}, B {:.2}",
+        gains.x,
+        gains.y,
+        gains.z
+    ));
+}
+
+/// The three channels as lines over a dark background.
+fn histogram_plot(ui: &mut egui::Ui, histogram: &Histogram, log_scale: bool) {
+    let (rect, _) = ui.allocate_exact_size(PLOT_SIZE, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(180));
+
+    let scale = |count: u32| {
+        if log_scale {
+            (count as f32 + 1.0).ln()
+        } else {
+            count as f32
+        }
+    };
+    let max = histogram
+        .counts
+        .iter()
+        .flatten()
+        .map(|&count| scale(count))
+        .fold(1.0, f32::max);
+    let colors = [
+        egui::Color32::from_rgb(255, 80, 80),
+        egui::Color32::from_rgb(80, 255, 80),
+        egui::Color32::from_rgb(80, 140, 255),
+    ];
+    for (counts, color) in histogram.counts.iter().zip(colors) {
+        let points = counts
+            .iter()
+            .enumerate()
+            .map(|(bin, &count)| {
+                egui::pos2(
+                    rect.left() + (bin as f32 + 0.5) / BINS as f32 * rect.width(),
+                    rect.bottom() - scale(count) / max * rect.height(),
+                )
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+    }
+}
+
+// =============================== RESOURCES ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistogramSource {
+    /// The frame buffer, as it goes into the present pass.
+    Scene,
+    Photo,
+}
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct HistogramSettings {
+    pub source: HistogramSource,
+    pub log_scale: bool,
+    pub photo_path: String,
+}
+impl Default for HistogramSettings {
+    fn default() -> Self {
+        Self {
+            source: HistogramSource::Scene,
+            log_scale: false,
+            photo_path: DEFAULT_PHOTO.to_string(),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct HistogramState {
+    pub scene: Option<Histogram>,
+    /// Set by the pass, the bins are read back after the frame is submitted.
+    scene_pending: bool,
+    pub photo: Option<Histogram>,
+    /// Path being typed, only loaded once confirmed.
+    path_input: String,
+    photo_id: egui::TextureId,
+    balanced_id: egui::TextureId,
+    panel_open: bool,
+    error: Option<String>,
+}
+
+/// Counts per bin of the red, green and blue channels.
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    pub counts: [Vec<u32>; 3],
+}
+impl Histogram {
+    fn from_bins(bins: &[u32]) -> Self {
+        Self {
+            counts: [0, 1, 2].map(|channel| bins[channel * BINS..(channel + 1) * BINS].to_vec()),
+        }
+    }
+
+    /// Average of every channel in linear space, with each pixel at the
+    /// center of its bin.
+    pub fn linear_means(&self) -> Vec3 {
+        let means = self.counts.each_ref().map(|counts| {
+            let total: u64 = counts.iter().map(|&count| count as u64).sum();
+            let sum: f64 = counts
+                .iter()
+                .enumerate()
+                .map(|(bin, &count)| {
+                    count as f64 * srgb_to_linear((bin as f32 + 0.5) / BINS as f32) as f64
+                })
+                .sum();
+            (sum / total.max(1) as f64) as f32
+        });
+        Vec3::from_array(means)
+    }
+
+    /// Gains that turn the average color gray, assuming the scene averages
+    /// out to gray. Keeps the overall brightness.
+    pub fn gray_world_gains(&self) -> Vec3 {
+        let means = self.linear_means();
+        let gray = (means.x + means.y + means.z) / 3.0;
+        (Vec3::splat(gray) / means.max(Vec3::splat(1e-4)))
+            .clamp(Vec3::splat(1.0 / MAX_GAIN), Vec3::splat(MAX_GAIN))
+    }
+}
+
+fn srgb_to_linear(srgb: f32) -> f32 {
+    if srgb <= 0.04045 {
+        srgb / 12.92
+    } else {
+        ((srgb + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[derive(Resource)]
+pub struct HistogramScene {
+    pub bins: HistogramBins,
+}
+
+// =============================== PIPELINES ===============================
+#[derive(Resource)]
+pub struct HistogramPipelines {
+    /// Group 0: the image and the bins.
+    pub source_layout: wgpu::BindGroupLayout,
+    /// Group 1 of the balance pass: the gains and the output.
+    pub balance_layout: wgpu::BindGroupLayout,
+    pub histogram: GPUComputePipeline,
+    pub balance: GPUComputePipeline,
+}
+impl HistogramPipelines {
+    pub fn new(gpu: &GpuContext) -> Result<Self> {
+        let source_layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("histogram_source_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let balance_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("histogram_balance_layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::StorageTexture {
+                                access: wgpu::StorageTextureAccess::WriteOnly,
+                                format: wgpu::TextureFormat::Rgba8Unorm,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+
+        let source = load_shader_source(SHADER_NAME, include_str!("../shaders/histogram.wgsl"));
+        let histogram = GPUComputePipeline::new(
+            &gpu.device,
+            "histogram_pipeline",
+            &source,
+            "cs_histogram",
+            &[&source_layout],
+            gpu.pipeline_cache(),
+        )?;
+        let balance = GPUComputePipeline::new(
+            &gpu.device,
+            "histogram_balance_pipeline",
+            &source,
+            "cs_balance",
+            &[&source_layout, &balance_layout],
+            gpu.pipeline_cache(),
+        )?;
+
+        Ok(Self {
+            source_layout,
+            balance_layout,
+            histogram,
+            balance,
+        })
+    }
+}
+
+/// Bins for one image, with the bind group reading it.
+pub struct HistogramBins {
+    pub bins: wgpu::Buffer,
+    pub readback: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    /// Size of the image being counted.
+    pub size: [u32; 2],
+}
+impl HistogramBins {
+    pub fn new(
+        gpu: &GpuContext,
+        pipelines: &HistogramPipelines,
+        source: &wgpu::Texture,
+        label: &str,
+    ) -> Self {
+        let size = (3 * BINS * std::mem::size_of::<u32>()) as u64;
+        let bins = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("histogram_{}_bins", label)),
+            size,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("histogram_{}_readback", label)),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let view = source.create_view(&Default::default());
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("histogram_{}_bind_group", label)),
+            layout: &pipelines.source_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: bins.as_entire_binding(),
+                },
+            ],
+        });
+
+        Self {
+            bins,
+            readback,
+            bind_group,
+            size: [source.width(), source.height()],
+        }
+    }
+
+    /// Clears the bins, counts the image and queues the copy to read back.
+    pub fn encode(
+        &self,
+        gpu: &GpuContext,
+        pipelines: &HistogramPipelines,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Result<()> {
+        let [x, y, z] = DispatchSite {
+            label: "histogram",
+            domain: [self.size[0], self.size[1], 1],
+        }
+        .validate(pipelines.histogram.workgroup_size, &gpu.device.limits())?;
+
+        encoder.clear_buffer(&self.bins, 0, None);
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("histogram"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&pipelines.histogram.pipeline);
+            compute_pass.set_bind_group(0, &self.bind_group, &[]);
+            compute_pass.dispatch_workgroups(x, y, z);
+        }
+        encoder.copy_buffer_to_buffer(&self.bins, 0, &self.readback, 0, self.bins.size());
+        Ok(())
+    }
+
+    /// Waits for the GPU, only call it after the encoded work was submitted.
+    pub fn read(&self, device: &wgpu::Device) -> Result<Histogram> {
+        let bins = read_buffer::<u32>(device, &self.readback)?;
+        Ok(Histogram::from_bins(&bins))
+    }
+}
+
+// =============================== PHOTO ===============================
+/// A photo loaded from disk with its histogram and a white balanced copy.
+#[derive(Resource)]
+pub struct Photo {
+    pub path: String,
+    /// Stored as is, without sRGB decoding, like the values in the frame
+    /// buffer.
+    pub view: wgpu::TextureView,
+    pub balanced_view: wgpu::TextureView,
+    pub histogram: Histogram,
+}
+impl Photo {
+    pub fn load(gpu: &GpuContext, pipelines: &HistogramPipelines, path: &str) -> Result<Self> {
+        let image = image::open(path)
+            .with_context(|| format!("Failed to open {}", path))?
+            .to_rgba8();
+        let (width, height) = image.dimensions();
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let create = |label, usage| {
+            gpu.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | usage,
+                view_formats: &[],
+            })
+        };
+        let texture = create("histogram_photo", wgpu::TextureUsages::COPY_DST);
+        let balanced = create(
+            "histogram_photo_balanced",
+            wgpu::TextureUsages::STORAGE_BINDING,
+        );
+        gpu.queue.write_texture(
+            texture.as_image_copy(),
+            &image,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        // Counted on its own submission, the gains have to be known before
+        // the balance pass runs
+        let bins = HistogramBins::new(gpu, pipelines, &texture, "photo");
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("histogram_photo_encoder"),
+            });
+        bins.encode(gpu, pipelines, &mut encoder)?;
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+        let histogram = bins.read(&gpu.device)?;
+
+        let gains = histogram.gray_world_gains().extend(1.0).to_array();
+        let gains_buffer = wgpu::util::DeviceExt::create_buffer_init(
+            &*gpu.device,
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("histogram_photo_gains"),
+                contents: bytemuck::cast_slice(&gains),
+                usage: wgpu::BufferUsages::UNIFORM,
+            },
+        );
+        let balanced_view = balanced.create_view(&Default::default());
+        let balance_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("histogram_photo_balance_bind_group"),
+            layout: &pipelines.balance_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: gains_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&balanced_view),
+                },
+            ],
+        });
+        let [x, y, z] = DispatchSite {
+            label: "histogram_balance",
+            domain: [width, height, 1],
+        }
+        .validate(pipelines.balance.workgroup_size, &gpu.device.limits())?;
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("histogram_balance_encoder"),
+            });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("histogram_balance"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&pipelines.balance.pipeline);
+            compute_pass.set_bind_group(0, &bins.bind_group, &[]);
+            compute_pass.set_bind_group(1, &balance_bind_group, &[]);
+            compute_pass.dispatch_workgroups(x, y, z);
+        }
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(Self {
+            path: path.to_string(),
+            view: texture.create_view(&Default::default()),
+            balanced_view,
+            histogram,
+        })
+    }
+}
@@ -0,0 +1,616 @@
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Vec2, Vec4};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    layout::LayoutCache,
+    pass::RenderPassBuilder,
+    shader::load_shader_source,
+    texture::Texture,
+    vertex::HudInstance,
+};
+
+use super::{
+    graph::PassContext, present::FrameBuffer, render::render_system, ui::UiPanels, GPUPipeline,
+    GPUPipelineBuilder,
+};
+
+/// Size of the generated nine-slice frame texture.
+const FRAME_SIZE: u32 = 32;
+/// Width of the frame texture's border, the part that is never stretched.
+const FRAME_BORDER: u32 = 8;
+
+pub fn setup_hud(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let renderer = world.resource_scope::<LayoutCache, _>(|world, mut layouts| {
+        let gpu = world
+            .get_resource::<GpuContext>()
+            .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+        HudRenderer::new(gpu, &mut layouts)
+    })?;
+    let frame = renderer.frame;
+    world.insert_resource(renderer);
+    world.init_resource::<HudQuads>();
+    world.insert_resource(HudDemoSettings {
+        frame,
+        ..Default::default()
+    });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(hud_panel);
+
+    schedule.add_systems(hud_demo_system.before(render_system));
+
+    Ok(())
+}
+
+/// Queues a small example HUD in the bottom left corner.
+pub fn hud_demo_system(
+    gpu: Res<GpuContext>,
+    settings: Res<HudDemoSettings>,
+    mut quads: ResMut<HudQuads>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    let s = settings.scale;
+    let size = Vec2::new(320.0, 150.0) * s;
+    let min = Vec2::new(24.0, gpu.config.height as f32 - 24.0) - Vec2::new(0.0, size.y);
+    let white = Vec4::splat(settings.brightness).with_w(1.0);
+
+    quads.push(
+        HudQuad::new(min, size)
+            .with_texture(settings.frame)
+            .with_nine_slice(
+                FRAME_BORDER as f32 * s,
+                FRAME_BORDER as f32 / FRAME_SIZE as f32,
+            )
+            .with_color(white),
+    );
+
+    // Health and energy bars, a border around a rounded track
+    let bars = [
+        (0.72, Vec4::new(0.9, 0.15, 0.1, 1.0)),
+        (0.4, Vec4::new(0.2, 0.55, 1.0, 1.0)),
+    ];
+    for (row, (fill, color)) in bars.into_iter().enumerate() {
+        let track_min = min + Vec2::new(20.0, 22.0 + row as f32 * 34.0) * s;
+        let track_size = Vec2::new(280.0, 22.0) * s;
+        quads.push(
+            HudQuad::new(track_min, track_size)
+                .with_color(Vec4::new(0.05, 0.05, 0.07, 0.8))
+                .with_corner_radius(settings.corner_radius * s)
+                .with_border(settings.border_width * s, Vec4::new(0.8, 0.8, 0.85, 1.0)),
+        );
+        let inset = (settings.border_width + 2.0) * s;
+        let fill_size = (track_size - Vec2::splat(inset * 2.0)) * Vec2::new(fill, 1.0);
+        quads.push(
+            HudQuad::new(track_min + Vec2::splat(inset), fill_size)
+                .with_color(color * settings.brightness)
+                .with_corner_radius((settings.corner_radius - inset).max(0.0)),
+        );
+    }
+
+    // A row of round slots, a corner radius of half the size makes circles
+    for slot in 0..4 {
+        let slot_size = Vec2::splat(30.0 * s);
+        let slot_min = min + Vec2::new(20.0 + slot as f32 * 40.0, 96.0) * s;
+        let color = if slot == 0 {
+            Vec4::new(1.0, 0.8, 0.2, 1.0) * settings.brightness
+        } else {
+            Vec4::new(0.15, 0.15, 0.2, 0.9)
+        };
+        quads.push(
+            HudQuad::new(slot_min, slot_size)
+                .with_color(color.with_w(0.9))
+                .with_corner_radius(slot_size.x * 0.5)
+                .with_border(settings.border_width * s, Vec4::new(0.8, 0.8, 0.85, 1.0)),
+        );
+    }
+}
+
+/// Draws everything queued on [`HudQuads`] this frame over the scene, then
+/// clears the queue. Consecutive quads with the same texture share a draw.
+pub fn hud_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let quads = std::mem::take(&mut world.resource_mut::<HudQuads>().quads);
+    world.resource_scope::<HudRenderer, _>(|world, mut renderer| {
+        renderer.stats = HudStats::default();
+        if quads.is_empty() {
+            return Ok(());
+        }
+        let gpu = world.resource::<GpuContext>();
+        let instances: Vec<HudInstance> = quads.iter().map(HudQuad::instance).collect();
+        renderer.upload(gpu, &instances);
+
+        let mut batches: Vec<(HudMaterial, HudTexture, std::ops::Range<u32>)> = Vec::new();
+        for (i, quad) in quads.iter().enumerate() {
+            let i = i as u32;
+            match batches.last_mut() {
+                Some((material, texture, range))
+                    if *material == quad.material && *texture == quad.texture =>
+                {
+                    range.end = i + 1
+                }
+                _ => batches.push((quad.material, quad.texture, i..i + 1)),
+            }
+        }
+        renderer.stats = HudStats {
+            quads: quads.len(),
+            batches: batches.len(),
+        };
+
+        let frame_buffer = world.resource::<FrameBuffer>();
+        let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+            .with_label(ctx.label)
+            .with_color_view(&frame_buffer.texture.view)
+            .load()
+            .build()?;
+
+        render_pass.set_bind_group(0, &renderer.params_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, renderer.instances.slice(..));
+        for (material, texture, range) in batches {
+            let pipeline = match material {
+                HudMaterial::Shape => &renderer.pipeline,
+                HudMaterial::DistanceField => &renderer.distance_field_pipeline,
+            };
+            render_pass.set_pipeline(&pipeline.render_pipeline);
+            render_pass.set_bind_group(1, &renderer.textures[texture.0], &[]);
+            render_pass.draw(0..6, range);
+        }
+
+        Ok(())
+    })
+}
+
+fn hud_panel(ctx: &egui::Context, world: &mut World) {
+    let stats = world.resource::<HudRenderer>().stats;
+    let mut settings = world.resource::<HudDemoSettings>().clone();
+
+    egui::Window::new(tr("HUD quads"))
+        .id(egui::Id::new("HUD quads"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Show demo HUD"));
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                ui.add(egui::Slider::new(&mut settings.scale, 0.5..=3.0).text(tr("Scale")));
+                ui.add(
+                    egui::Slider::new(&mut settings.corner_radius, 0.0..=20.0)
+                        .text(tr("Corner radius")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.border_width, 0.0..=6.0)
+                        .text(tr("Border width")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.brightness, 0.1..=8.0)
+                        .logarithmic(true)
+                        .text(tr("Brightness")),
+                )
+                .on_hover_text(tr(
+                    "Above 1 the HUD is HDR and gets tone mapped like the scene",
+                ));
+            });
+            ui.label(trf!(
+                "{} quads in {} draw calls",
+                stats.quads,
+                stats.batches
+            ));
+        });
+
+    let mut current = world.resource_mut::<HudDemoSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== API ===============================
+/// Texture registered with [`HudRenderer::add_texture`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HudTexture(usize);
+impl HudTexture {
+    /// A single white pixel, for untextured quads.
+    pub const WHITE: Self = Self(0);
+}
+
+/// How a quad's texture is turned into color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HudMaterial {
+    /// The texture times the color, inside a rounded box with a border.
+    Shape,
+    /// The texture's alpha is a signed distance field, as made for text.
+    /// Reuses the border as an outline and can cast a drop shadow.
+    DistanceField,
+}
+
+/// A screen space rectangle, positioned in surface pixels from the top left.
+#[derive(Clone, Copy, Debug)]
+pub struct HudQuad {
+    pub min: Vec2,
+    pub size: Vec2,
+    /// Multiplies the texture, values above 1 stay HDR.
+    pub color: Vec4,
+    pub corner_radius: f32,
+    pub border_width: f32,
+    pub border_color: Vec4,
+    pub texture: HudTexture,
+    /// Region of the texture, as min and max UV.
+    pub uv_rect: [f32; 4],
+    /// Screen pixels and fraction of the region kept unstretched at every
+    /// edge, zero stretches the whole texture.
+    pub slice: (f32, f32),
+    pub material: HudMaterial,
+    /// Offset in pixels and opacity of the distance field drop shadow.
+    pub shadow: (Vec2, f32),
+}
+impl HudQuad {
+    pub fn new(min: Vec2, size: Vec2) -> Self {
+        Self {
+            min,
+            size,
+            color: Vec4::ONE,
+            corner_radius: 0.0,
+            border_width: 0.0,
+            border_color: Vec4::ZERO,
+            texture: HudTexture::WHITE,
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            slice: (0.0, 0.0),
+            material: HudMaterial::Shape,
+            shadow: (Vec2::ZERO, 0.0),
+        }
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Clamped to half the shorter side, which turns squares into circles.
+    pub fn with_corner_radius(mut self, radius: f32) -> Self {
+        self.corner_radius = radius;
+        self
+    }
+
+    /// Drawn inside the edge, following the rounded corners.
+    pub fn with_border(mut self, width: f32, color: Vec4) -> Self {
+        self.border_width = width;
+        self.border_color = color;
+        self
+    }
+
+    pub fn with_texture(mut self, texture: HudTexture) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    /// Only `uv_rect` of the texture, e.g. one entry of an atlas.
+    pub fn with_uv_rect(mut self, uv_rect: [f32; 4]) -> Self {
+        self.uv_rect = uv_rect;
+        self
+    }
+
+    /// Keeps `source` of the texture region at every edge `border` pixels
+    /// wide on screen, and stretches only the middle.
+    pub fn with_nine_slice(mut self, border: f32, source: f32) -> Self {
+        self.slice = (border, source);
+        self
+    }
+
+    /// Reads the texture as a distance field, see [`HudMaterial::DistanceField`].
+    pub fn with_distance_field(mut self) -> Self {
+        self.material = HudMaterial::DistanceField;
+        self
+    }
+
+    /// A black copy of a distance field shape, `offset` pixels away.
+    pub fn with_shadow(mut self, offset: Vec2, opacity: f32) -> Self {
+        self.shadow = (offset, opacity);
+        self
+    }
+
+    fn instance(&self) -> HudInstance {
+        let shape = match self.material {
+            HudMaterial::Shape => [
+                self.corner_radius,
+                self.border_width,
+                self.slice.0,
+                self.slice.1,
+            ],
+            HudMaterial::DistanceField => [
+                self.border_width,
+                self.shadow.0.x,
+                self.shadow.0.y,
+                self.shadow.1,
+            ],
+        };
+        HudInstance {
+            rect: [self.min.x, self.min.y, self.size.x, self.size.y],
+            uv_rect: self.uv_rect,
+            color: self.color.to_array(),
+            border_color: self.border_color.to_array(),
+            shape,
+        }
+    }
+}
+
+/// Immediate-mode HUD drawing. Quads are drawn in the order they are pushed,
+/// once, at the end of the current frame.
+#[derive(Resource, Default)]
+pub struct HudQuads {
+    pub quads: Vec<HudQuad>,
+}
+impl HudQuads {
+    pub fn push(&mut self, quad: HudQuad) {
+        self.quads.push(quad);
+    }
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct HudDemoSettings {
+    pub enabled: bool,
+    pub scale: f32,
+    pub corner_radius: f32,
+    pub border_width: f32,
+    pub brightness: f32,
+    /// The generated nine-slice frame.
+    pub frame: HudTexture,
+}
+impl Default for HudDemoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scale: 1.0,
+            corner_radius: 8.0,
+            border_width: 2.0,
+            brightness: 1.0,
+            frame: HudTexture::WHITE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct HudStats {
+    pub quads: usize,
+    pub batches: usize,
+}
+
+// =============================== RENDERER ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct HudParams {
+    pub screen: [f32; 2],
+    pub _padding: [f32; 2],
+}
+
+#[derive(Resource)]
+pub struct HudRenderer {
+    pub pipeline: GPUPipeline,
+    pub distance_field_pipeline: GPUPipeline,
+    params: wgpu::Buffer,
+    params_bind_group: wgpu::BindGroup,
+    texture_layout: std::sync::Arc<wgpu::BindGroupLayout>,
+    sampler: wgpu::Sampler,
+    /// Bind group per [`HudTexture`].
+    textures: Vec<wgpu::BindGroup>,
+    /// Kept alive for the bind groups of the generated textures.
+    owned: Vec<Texture>,
+    /// The generated nine-slice frame.
+    pub frame: HudTexture,
+    instances: wgpu::Buffer,
+    capacity: usize,
+    /// Counts of the last drawn frame.
+    pub stats: HudStats,
+}
+impl HudRenderer {
+    pub fn new(gpu: &GpuContext, layouts: &mut LayoutCache) -> Result<Self> {
+        let params_layout = layouts.get(
+            &gpu.device,
+            &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        );
+        let texture_layout = layouts.get(
+            &gpu.device,
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        );
+
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("hud_params"),
+                contents: bytemuck::bytes_of(&HudParams {
+                    screen: [gpu.config.width as f32, gpu.config.height as f32],
+                    _padding: [0.0; 2],
+                }),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let params_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &params_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.as_entire_binding(),
+            }],
+            label: Some("hud_params_bind_group"),
+        });
+
+        let source = load_shader_source("hud.wgsl", include_str!("../shaders/hud.wgsl"));
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("hud_shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let build = |label: &str, fragment: &str| {
+            GPUPipelineBuilder::new(&gpu.device)
+                .label(label)
+                .pipeline_cache(gpu.pipeline_cache())
+                .bind_group_layout(&params_layout)
+                .bind_group_layout(&texture_layout)
+                .vertex_shader(&shader, "vs_main")
+                .fragment_shader(&shader, fragment)
+                .vertex_buffer_layout(HudInstance::desc())
+                .color_target(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })
+                .default_multisample_state()
+                .primitive_state(wgpu::PrimitiveState {
+                    cull_mode: None,
+                    ..Default::default()
+                })
+                .build()
+                .map_err(|e| anyhow::anyhow!(e))
+        };
+        let pipeline = build("hud_pipeline", "fs_main")?;
+        let distance_field_pipeline = build("hud_distance_field_pipeline", "fs_distance_field")?;
+
+        let capacity = 256;
+        let mut renderer = Self {
+            pipeline,
+            distance_field_pipeline,
+            params,
+            params_bind_group,
+            texture_layout,
+            sampler: gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("hud_sampler"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }),
+            textures: Vec::new(),
+            owned: Vec::new(),
+            frame: HudTexture::WHITE,
+            instances: Self::create_instances(gpu, capacity),
+            capacity,
+            stats: HudStats::default(),
+        };
+
+        let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+        renderer.add_generated(gpu, white, "hud_white")?;
+        renderer.frame = renderer.add_generated(gpu, frame_image(), "hud_frame")?;
+
+        Ok(renderer)
+    }
+
+    /// Makes `view` usable with [`HudQuad::with_texture`].
+    pub fn add_texture(&mut self, gpu: &GpuContext, view: &wgpu::TextureView) -> HudTexture {
+        let bind_group = self.create_bind_group(gpu, view);
+        self.textures.push(bind_group);
+        HudTexture(self.textures.len() - 1)
+    }
+
+    /// Points `texture` at a new view, e.g. after a texture was resized.
+    pub fn replace_texture(
+        &mut self,
+        gpu: &GpuContext,
+        texture: HudTexture,
+        view: &wgpu::TextureView,
+    ) {
+        self.textures[texture.0] = self.create_bind_group(gpu, view);
+    }
+
+    fn create_bind_group(&self, gpu: &GpuContext, view: &wgpu::TextureView) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+            label: Some("hud_texture_bind_group"),
+        })
+    }
+
+    fn add_generated(
+        &mut self,
+        gpu: &GpuContext,
+        image: image::RgbaImage,
+        label: &str,
+    ) -> Result<HudTexture> {
+        let texture = Texture::from_image(
+            &gpu.device,
+            &gpu.queue,
+            &image::DynamicImage::ImageRgba8(image),
+            Some(label),
+        )?;
+        let handle = self.add_texture(gpu, &texture.view);
+        self.owned.push(texture);
+        Ok(handle)
+    }
+
+    fn create_instances(gpu: &GpuContext, capacity: usize) -> wgpu::Buffer {
+        gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("hud_instances"),
+            size: (capacity * std::mem::size_of::<HudInstance>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn upload(&mut self, gpu: &GpuContext, instances: &[HudInstance]) {
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.instances = Self::create_instances(gpu, self.capacity);
+        }
+        gpu.queue
+            .write_buffer(&self.instances, 0, bytemuck::cast_slice(instances));
+        gpu.queue.write_buffer(
+            &self.params,
+            0,
+            bytemuck::bytes_of(&HudParams {
+                screen: [gpu.config.width as f32, gpu.config.height as f32],
+                _padding: [0.0; 2],
+            }),
+        );
+    }
+}
+
+/// A beveled frame around a dark translucent middle, light from the top left.
+fn frame_image() -> image::RgbaImage {
+    let last = FRAME_SIZE - 1;
+    image::RgbaImage::from_fn(FRAME_SIZE, FRAME_SIZE, |x, y| {
+        let edge = x.min(y).min(last - x).min(last - y);
+        if edge >= FRAME_BORDER {
+            return image::Rgba([20, 24, 32, 200]);
+        }
+        let lit = x.min(y) == edge;
+        let shade = if lit { 200 } else { 110 } - edge as u8 * 8;
+        image::Rgba([shade, shade, shade.saturating_add(20), 255])
+    })
+}
@@ -0,0 +1,542 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+
+use crate::i18n::{tr, trf};
+use crate::{
+    external_editor::open_in_editor, gpu::GpuContext, pass::RenderPassBuilder, texture::Texture,
+};
+
+use super::{
+    graph::PassContext,
+    ui::{EguiState, UiPanels},
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+const PREVIEW_SIZE: u32 = 512;
+
+pub fn setup_texture_inspector(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let preview = Texture::frame_buffer_texture(
+        &gpu.device,
+        PREVIEW_SIZE,
+        PREVIEW_SIZE,
+        Some("inspector_preview"),
+        1,
+    );
+    let blit = InspectorBlit::new(gpu)?;
+
+    let texture_id = world.resource_scope::<EguiState, _>(|world, mut ui| {
+        ui.renderer.register_native_texture(
+            &world.resource::<GpuContext>().device,
+            &preview.view,
+            wgpu::FilterMode::Nearest,
+        )
+    });
+
+    world.insert_resource(blit);
+    world.insert_resource(TextureInspector::new(preview, texture_id));
+    world.init_resource::<TextureRegistry>();
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(texture_inspector_panel);
+
+    Ok(())
+}
+
+/// Blits the selected texture into the preview target shown by the panel.
+pub fn texture_inspector_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let inspector = world.resource::<TextureInspector>();
+    let Some(selected) = inspector.selected.filter(|_| inspector.visible) else {
+        return Ok(());
+    };
+    let Some(texture) = world.resource::<TextureRegistry>().get(selected, world) else {
+        return Ok(());
+    };
+    let Ok(kind) = PreviewKind::of(texture) else {
+        return Ok(());
+    };
+    let gpu = world.resource::<GpuContext>();
+    let blit = world.resource::<InspectorBlit>();
+
+    let mip = inspector.mip.min(texture.mip_level_count() - 1);
+    let layers = texture
+        .size()
+        .mip_level_size(mip, texture.dimension())
+        .depth_or_array_layers;
+    let layer = inspector.layer.min(layers - 1);
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("inspector_source_view"),
+        dimension: Some(kind.view_dimension()),
+        aspect: if kind == PreviewKind::Depth {
+            wgpu::TextureAspect::DepthOnly
+        } else {
+            wgpu::TextureAspect::All
+        },
+        base_mip_level: mip,
+        mip_level_count: Some(1),
+        base_array_layer: if kind == PreviewKind::Volume {
+            0
+        } else {
+            layer
+        },
+        array_layer_count: Some(1),
+        ..Default::default()
+    });
+    let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &blit.layouts[kind as usize],
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: blit.uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: kind.binding(),
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+        ],
+        label: Some("inspector_bind_group"),
+    });
+
+    let [width, height] = inspector.viewport;
+    let uniform = InspectorUniform {
+        channels: inspector
+            .channels
+            .map(|shown| if shown { 1.0 } else { 0.0 }),
+        range: inspector.range,
+        exposure: inspector.exposure,
+        slice: layer,
+        viewport: [width as f32, height as f32],
+        _padding: [0.0; 2],
+    };
+    gpu.queue
+        .write_buffer(&blit.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&inspector.preview.view)
+        .build()?;
+
+    render_pass.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
+    render_pass.set_pipeline(&blit.pipelines[kind as usize].render_pipeline);
+    render_pass.set_bind_group(0, &bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+
+    Ok(())
+}
+
+fn texture_inspector_panel(ctx: &egui::Context, world: &mut World) {
+    world.resource_scope::<TextureInspector, _>(|world, mut inspector| {
+        let registry = world.resource::<TextureRegistry>();
+        let mut open = None;
+
+        let response = egui::Window::new(tr("Texture inspector"))
+            .id(egui::Id::new("Texture inspector"))
+            .default_open(false)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("inspector_textures")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for header in ["Name", "Format", "Size", "Mips", "Usage"] {
+                                    ui.strong(tr(header));
+                                }
+                                ui.end_row();
+
+                                for (index, name) in registry.names().enumerate() {
+                                    let Some(texture) = registry.get(index, world) else {
+                                        continue;
+                                    };
+                                    let selected = inspector.selected == Some(index);
+                                    let response = ui.selectable_label(selected, name);
+                                    if response.clicked() && !selected {
+                                        inspector.select(index, texture);
+                                    }
+                                    if let Some(source) = registry.source(index) {
+                                        response.context_menu(|ui| {
+                                            if ui.button(tr("Open source in editor")).clicked() {
+                                                open = Some(source.to_path_buf());
+                                                ui.close_menu();
+                                            }
+                                        });
+                                    }
+                                    ui.label(format!("{:?}", texture.format()));
+                                    ui.label(size_label(texture));
+                                    ui.label(texture.mip_level_count().to_string());
+                                    ui.label(usage_label(texture.usage()));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                let Some(texture) = inspector
+                    .selected
+                    .and_then(|index| registry.get(index, world))
+                else {
+                    ui.label(tr("Select a texture to preview it"));
+                    return;
+                };
+                ui.separator();
+                if let Err(reason) = PreviewKind::of(texture) {
+                    ui.label(trf!("No preview: {}", tr(&reason.to_string())));
+                    return;
+                }
+
+                let inspector = &mut *inspector;
+                let mips = texture.mip_level_count();
+                if mips > 1 {
+                    ui.add(egui::Slider::new(&mut inspector.mip, 0..=mips - 1).text(tr("mip")));
+                }
+                let mip = inspector.mip.min(mips - 1);
+                let layers = texture
+                    .size()
+                    .mip_level_size(mip, texture.dimension())
+                    .depth_or_array_layers;
+                if layers > 1 {
+                    let label = match texture.dimension() {
+                        wgpu::TextureDimension::D3 => "slice",
+                        _ => "layer",
+                    };
+                    ui.add(egui::Slider::new(&mut inspector.layer, 0..=layers - 1).text(label));
+                }
+                ui.horizontal(|ui| {
+                    for (shown, name) in inspector.channels.iter_mut().zip(["R", "G", "B", "A"]) {
+                        ui.checkbox(shown, name);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label(tr("range"));
+                    ui.add(egui::DragValue::new(&mut inspector.range[0]).speed(0.001));
+                    ui.add(egui::DragValue::new(&mut inspector.range[1]).speed(0.001));
+                });
+                ui.add(
+                    egui::Slider::new(&mut inspector.exposure, -10.0..=10.0)
+                        .text(tr("exposure (EV)")),
+                );
+
+                let size = texture.size().mip_level_size(mip, texture.dimension());
+                inspector.viewport = fit_preview(size.width, size.height);
+                let [width, height] = inspector.viewport;
+                let uv = egui::Rect::from_min_max(
+                    egui::pos2(0.0, 0.0),
+                    egui::pos2(
+                        width as f32 / PREVIEW_SIZE as f32,
+                        height as f32 / PREVIEW_SIZE as f32,
+                    ),
+                );
+                let scale = 256.0 / width.max(height) as f32;
+                ui.add(
+                    egui::Image::new((
+                        inspector.texture_id,
+                        egui::vec2(width as f32 * scale, height as f32 * scale),
+                    ))
+                    .uv(uv),
+                );
+            });
+
+        inspector.visible = response.is_some_and(|response| response.inner.is_some());
+        if let Some(source) = open {
+            open_in_editor(world, &source, None);
+        }
+    });
+}
+
+fn size_label(texture: &wgpu::Texture) -> String {
+    match (texture.dimension(), texture.depth_or_array_layers()) {
+        (wgpu::TextureDimension::D3, depth) => {
+            format!("{}x{}x{}", texture.width(), texture.height(), depth)
+        }
+        (_, 1) => format!("{}x{}", texture.width(), texture.height()),
+        (_, layers) => format!("{}x{} [{}]", texture.width(), texture.height(), layers),
+    }
+}
+
+fn usage_label(usage: wgpu::TextureUsages) -> String {
+    usage
+        .iter_names()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+/// Largest extent with the texture's aspect ratio that fits the preview target.
+fn fit_preview(width: u32, height: u32) -> [u32; 2] {
+    let scale = PREVIEW_SIZE as f32 / width.max(height) as f32;
+    [
+        ((width as f32 * scale) as u32).clamp(1, PREVIEW_SIZE),
+        ((height as f32 * scale) as u32).clamp(1, PREVIEW_SIZE),
+    ]
+}
+
+// =============================== REGISTRY ===============================
+pub type TextureGetter = Box<dyn Fn(&World) -> Option<&wgpu::Texture> + Send + Sync>;
+
+struct TextureEntry {
+    name: &'static str,
+    getter: TextureGetter,
+    /// File the texture is loaded or generated from.
+    source: Option<PathBuf>,
+}
+
+/// Textures listed by the inspector. Entries look their texture up every
+/// frame, so textures recreated on resize stay current.
+#[derive(Resource, Default)]
+pub struct TextureRegistry {
+    entries: Vec<TextureEntry>,
+}
+impl TextureRegistry {
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        getter: impl Fn(&World) -> Option<&wgpu::Texture> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.entries.push(TextureEntry {
+            name,
+            getter: Box::new(getter),
+            source: None,
+        });
+        self
+    }
+
+    /// Sets the file behind the texture registered last, which the inspector
+    /// offers to open in the editor.
+    pub fn with_source(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        if let Some(entry) = self.entries.last_mut() {
+            entry.source = Some(path.into());
+        }
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.entries.iter().map(|entry| entry.name)
+    }
+
+    pub fn get<'w>(&self, index: usize, world: &'w World) -> Option<&'w wgpu::Texture> {
+        self.entries
+            .get(index)
+            .and_then(|entry| (entry.getter)(world))
+    }
+
+    pub fn source(&self, index: usize) -> Option<&Path> {
+        self.entries.get(index)?.source.as_deref()
+    }
+}
+
+// =============================== INSPECTOR ===============================
+#[derive(Resource)]
+pub struct TextureInspector {
+    pub selected: Option<usize>,
+    pub mip: u32,
+    /// Array layer, or depth slice for 3D textures.
+    pub layer: u32,
+    pub channels: [bool; 4],
+    pub range: [f32; 2],
+    pub exposure: f32,
+    /// Whether the window was expanded this frame; the blit is skipped otherwise.
+    pub visible: bool,
+    pub viewport: [u32; 2],
+    pub preview: Texture,
+    pub texture_id: egui::TextureId,
+}
+impl TextureInspector {
+    pub fn new(preview: Texture, texture_id: egui::TextureId) -> Self {
+        Self {
+            selected: None,
+            mip: 0,
+            layer: 0,
+            channels: [true, true, true, false],
+            range: [0.0, 1.0],
+            exposure: 0.0,
+            visible: false,
+            viewport: [PREVIEW_SIZE; 2],
+            preview,
+            texture_id,
+        }
+    }
+
+    fn select(&mut self, index: usize, texture: &wgpu::Texture) {
+        self.selected = Some(index);
+        self.mip = 0;
+        self.layer = 0;
+        self.range = [0.0, 1.0];
+        self.exposure = 0.0;
+        // Depth and single channel formats read best as grayscale
+        self.channels = match texture.format().components() {
+            1 => [true, false, false, false],
+            _ => [true, true, true, false],
+        };
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InspectorUniform {
+    pub channels: [f32; 4],
+    pub range: [f32; 2],
+    pub exposure: f32,
+    pub slice: u32,
+    pub viewport: [f32; 2],
+    pub _padding: [f32; 2],
+}
+
+/// Why a texture can't be previewed. The messages are translation keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum PreviewError {
+    #[error("missing TEXTURE_BINDING usage")]
+    NotBindable,
+    #[error("multisampled textures are not supported")]
+    Multisampled,
+    #[error("1D textures are not supported")]
+    OneDimensional,
+    #[error("integer formats are not supported")]
+    Integer,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreviewKind {
+    Float,
+    Depth,
+    Volume,
+}
+impl PreviewKind {
+    pub fn of(texture: &wgpu::Texture) -> Result<Self, PreviewError> {
+        if !texture
+            .usage()
+            .contains(wgpu::TextureUsages::TEXTURE_BINDING)
+        {
+            return Err(PreviewError::NotBindable);
+        }
+        if texture.sample_count() > 1 {
+            return Err(PreviewError::Multisampled);
+        }
+        let format = texture.format();
+        if format.has_depth_aspect() {
+            return Ok(Self::Depth);
+        }
+        match (format.sample_type(None, None), texture.dimension()) {
+            (_, wgpu::TextureDimension::D1) => Err(PreviewError::OneDimensional),
+            (Some(wgpu::TextureSampleType::Float { .. }), wgpu::TextureDimension::D2) => {
+                Ok(Self::Float)
+            }
+            (Some(wgpu::TextureSampleType::Float { .. }), wgpu::TextureDimension::D3) => {
+                Ok(Self::Volume)
+            }
+            _ => Err(PreviewError::Integer),
+        }
+    }
+
+    fn view_dimension(self) -> wgpu::TextureViewDimension {
+        match self {
+            Self::Float | Self::Depth => wgpu::TextureViewDimension::D2,
+            Self::Volume => wgpu::TextureViewDimension::D3,
+        }
+    }
+
+    /// Matches the source bindings in inspector.wgsl.
+    fn binding(self) -> u32 {
+        self as u32 + 1
+    }
+
+    fn sample_type(self) -> wgpu::TextureSampleType {
+        match self {
+            Self::Float | Self::Volume => wgpu::TextureSampleType::Float { filterable: false },
+            Self::Depth => wgpu::TextureSampleType::Depth,
+        }
+    }
+
+    fn entry_point(self) -> &'static str {
+        match self {
+            Self::Float => "fs_float",
+            Self::Depth => "fs_depth",
+            Self::Volume => "fs_volume",
+        }
+    }
+}
+
+// =============================== PIPELINES ===============================
+/// One bind group layout and pipeline per [`PreviewKind`].
+#[derive(Resource)]
+pub struct InspectorBlit {
+    pub uniform_buffer: wgpu::Buffer,
+    pub layouts: Vec<wgpu::BindGroupLayout>,
+    pub pipelines: Vec<GPUPipeline>,
+}
+impl InspectorBlit {
+    pub fn new(gpu: &GpuContext) -> Result<Self> {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("inspector_shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/inspector.wgsl").into()),
+            });
+        let uniform_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("inspector_uniform_buffer"),
+            size: std::mem::size_of::<InspectorUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let kinds = [PreviewKind::Float, PreviewKind::Depth, PreviewKind::Volume];
+        let layouts: Vec<_> = kinds
+            .iter()
+            .map(|kind| {
+                gpu.device
+                    .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        entries: &[
+                            wgpu::BindGroupLayoutEntry {
+                                binding: 0,
+                                visibility: wgpu::ShaderStages::FRAGMENT,
+                                ty: wgpu::BindingType::Buffer {
+                                    ty: wgpu::BufferBindingType::Uniform,
+                                    has_dynamic_offset: false,
+                                    min_binding_size: None,
+                                },
+                                count: None,
+                            },
+                            wgpu::BindGroupLayoutEntry {
+                                binding: kind.binding(),
+                                visibility: wgpu::ShaderStages::FRAGMENT,
+                                ty: wgpu::BindingType::Texture {
+                                    multisampled: false,
+                                    view_dimension: kind.view_dimension(),
+                                    sample_type: kind.sample_type(),
+                                },
+                                count: None,
+                            },
+                        ],
+                        label: Some("inspector_bind_group_layout"),
+                    })
+            })
+            .collect();
+        let pipelines = kinds
+            .iter()
+            .zip(&layouts)
+            .map(|(kind, layout)| {
+                GPUPipelineBuilder::new(&gpu.device)
+                    .label("inspector_pipeline")
+                    .pipeline_cache(gpu.pipeline_cache())
+                    .bind_group_layout(layout)
+                    .vertex_shader(&shader, "vs_main")
+                    .fragment_shader(&shader, kind.entry_point())
+                    .default_color_target(wgpu::TextureFormat::Rgba16Float)
+                    .depth_stencil_state(None)
+                    .default_multisample_state()
+                    .primitive_state(wgpu::PrimitiveState::default())
+                    .build()
+                    .map_err(|e| anyhow::anyhow!(e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            uniform_buffer,
+            layouts,
+            pipelines,
+        })
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::Schedule,
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+
+use crate::i18n::tr;
+use crate::{gpu::GpuContext, texture::Texture, time::TimeContext};
+
+use super::{
+    inspector::TextureRegistry,
+    ui::{EguiState, UiPanels},
+};
+
+const LAYER_SIZE: u32 = 128;
+const CYCLE_SECONDS: f32 = 1.0;
+
+pub fn setup_layer_demo(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let patterns: [fn(u32, u32) -> [u8; 4]; 4] = [checker, stripes, radial, dots];
+    let texture = Texture::array(
+        &gpu.device,
+        LAYER_SIZE,
+        LAYER_SIZE,
+        patterns.len() as u32,
+        wgpu::TextureFormat::Rgba8Unorm,
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        "layer_demo_texture",
+    );
+    for (layer, pattern) in patterns.iter().enumerate() {
+        let data: Vec<u8> = (0..LAYER_SIZE * LAYER_SIZE)
+            .flat_map(|i| pattern(i % LAYER_SIZE, i / LAYER_SIZE))
+            .collect();
+        texture.write_layer(&gpu.queue, layer as u32, &data)?;
+    }
+
+    let texture_ids = world.resource_scope::<EguiState, _>(|world, mut ui| {
+        let device = &world.resource::<GpuContext>().device;
+        (0..texture.layers())
+            .map(|layer| {
+                ui.renderer.register_native_texture(
+                    device,
+                    &texture.layer_view(layer),
+                    wgpu::FilterMode::Nearest,
+                )
+            })
+            .collect()
+    });
+
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("layer_demo", |world| {
+            world
+                .get_resource::<LayerDemo>()
+                .map(|demo| &demo.texture.texture)
+        });
+    world.insert_resource(LayerDemo {
+        texture,
+        texture_ids,
+        current: 0,
+        elapsed: 0.0,
+        cycling: true,
+    });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(layer_demo_panel);
+
+    schedule.add_systems(layer_cycle_system);
+
+    Ok(())
+}
+
+pub fn layer_cycle_system(time: Res<TimeContext>, mut demo: ResMut<LayerDemo>) {
+    if !demo.cycling {
+        return;
+    }
+    demo.elapsed += time.delta;
+    if demo.elapsed >= CYCLE_SECONDS {
+        demo.elapsed -= CYCLE_SECONDS;
+        demo.current = (demo.current + 1) % demo.texture.layers();
+    }
+}
+
+fn layer_demo_panel(ctx: &egui::Context, world: &mut World) {
+    let mut demo = world.resource_mut::<LayerDemo>();
+    let layers = demo.texture.layers();
+
+    egui::Window::new(tr("Texture array"))
+        .id(egui::Id::new("Texture array"))
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut demo.cycling, tr("Cycle"));
+                ui.add(egui::Slider::new(&mut demo.current, 0..=layers - 1).text(tr("layer")));
+            });
+            let texture_id = demo.texture_ids[demo.current as usize];
+            ui.image((texture_id, egui::vec2(256.0, 256.0)));
+        });
+}
+
+#[derive(Resource)]
+pub struct LayerDemo {
+    pub texture: Texture,
+    /// One egui texture per layer view.
+    pub texture_ids: Vec<egui::TextureId>,
+    pub current: u32,
+    pub elapsed: f32,
+    pub cycling: bool,
+}
+
+// =============================== PATTERNS ===============================
+fn checker(x: u32, y: u32) -> [u8; 4] {
+    if (x / 16 + y / 16).is_multiple_of(2) {
+        [230, 230, 230, 255]
+    } else {
+        [40, 40, 40, 255]
+    }
+}
+
+fn stripes(x: u32, y: u32) -> [u8; 4] {
+    let band = ((x + y) / 12) % 3;
+    [[220, 60, 60, 255], [60, 200, 90, 255], [60, 90, 220, 255]][band as usize]
+}
+
+fn radial(x: u32, y: u32) -> [u8; 4] {
+    let center = LAYER_SIZE as f32 / 2.0;
+    let distance = ((x as f32 - center).powi(2) + (y as f32 - center).powi(2)).sqrt() / center;
+    let v = ((1.0 - distance.min(1.0)) * 255.0) as u8;
+    [v, v / 2, 255 - v, 255]
+}
+
+fn dots(x: u32, y: u32) -> [u8; 4] {
+    let (cx, cy) = ((x % 32) as i32 - 16, (y % 32) as i32 - 16);
+    if cx * cx + cy * cy < 64 {
+        [250, 210, 60, 255]
+    } else {
+        [30, 30, 60, 255]
+    }
+}
@@ -0,0 +1,410 @@
+//! Conway's game of life on the GPU, one fragment pass per generation on a
+//! [`PingPongTarget`]: every pass reads the last generation and writes the
+//! next, then the sides swap.
+
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pass::RenderPassBuilder,
+    rng::Rng,
+    shader::load_shader_source,
+};
+
+use super::{
+    graph::PassContext,
+    inspector::TextureRegistry,
+    ping_pong::PingPongTarget,
+    render::render_system,
+    ui::{EguiState, UiPanels},
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+pub fn setup_life(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let seed = world
+        .get_resource_mut::<Rng>()
+        .ok_or_else(|| anyhow::anyhow!("Rng resource not found"))?
+        .next_u32();
+    let settings = LifeSettings::default();
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let board = LifeBoard::new(&gpu.device, gpu.pipeline_cache(), settings.size, seed)?;
+    let preview = world.resource_scope::<EguiState, _>(|world, mut ui| {
+        ui.renderer.register_native_texture(
+            &world.resource::<GpuContext>().device,
+            board.target.read_view(),
+            wgpu::FilterMode::Nearest,
+        )
+    });
+
+    world.insert_resource(board);
+    world.insert_resource(LifePreview {
+        texture_id: preview,
+    });
+    world.insert_resource(settings);
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("life", |world| {
+            world
+                .get_resource::<LifeBoard>()
+                .map(|board| board.target.read_texture())
+        });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(life_panel);
+
+    schedule.add_systems(life_resize_system.before(render_system));
+
+    Ok(())
+}
+
+/// Starts over on a board of the new size.
+pub fn life_resize_system(
+    gpu: Res<GpuContext>,
+    settings: Res<LifeSettings>,
+    mut board: ResMut<LifeBoard>,
+) {
+    if board
+        .target
+        .resize(&gpu.device, settings.size, settings.size)
+    {
+        board.reseed = true;
+    }
+}
+
+/// Seeds the board when asked to, then runs this frame's generations.
+pub fn life_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let settings = world.resource::<LifeSettings>().clone();
+    let stepped = world.resource_scope::<LifeBoard, _>(|world, mut board| {
+        let gpu = world.resource::<GpuContext>();
+        let steps = if settings.running {
+            settings.steps_per_frame
+        } else {
+            board.step_once as u32
+        };
+        board.step_once = false;
+        board.run(&gpu.queue, ctx.encoder, ctx.label, steps)
+    })?;
+
+    if stepped {
+        world.resource_scope::<EguiState, _>(|world, mut ui| {
+            let board = world.resource::<LifeBoard>();
+            ui.renderer.update_native_texture(
+                &world.resource::<GpuContext>().device,
+                board.target.read_view(),
+                wgpu::FilterMode::Nearest,
+                world.resource::<LifePreview>().texture_id,
+            );
+        });
+    }
+    Ok(())
+}
+
+fn life_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<LifeSettings>().clone();
+    let texture_id = world.resource::<LifePreview>().texture_id;
+    let mut board = world.resource_mut::<LifeBoard>();
+
+    egui::Window::new(tr("Game of life"))
+        .id(egui::Id::new("Game of life"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut settings.running, tr("Running"));
+                if ui.button(tr("Step")).clicked() {
+                    board.step_once = true;
+                }
+                if ui.button(tr("Reseed")).clicked() {
+                    board.seed = board.seed.wrapping_add(1);
+                    board.reseed = true;
+                }
+            });
+            ui.add(
+                egui::Slider::new(&mut settings.steps_per_frame, 1..=16)
+                    .text(tr("Generations per frame")),
+            );
+            egui::ComboBox::from_label(tr("Board size"))
+                .selected_text(format!("{0}x{0}", settings.size))
+                .show_ui(ui, |ui| {
+                    for size in [64, 128, 256, 512, 1024] {
+                        ui.selectable_value(&mut settings.size, size, format!("{0}x{0}", size));
+                    }
+                });
+            ui.label(trf!("Generation {}", board.generation));
+            ui.image((texture_id, egui::vec2(256.0, 256.0)));
+        });
+
+    if settings != *world.resource::<LifeSettings>() {
+        world.insert_resource(settings);
+    }
+}
+
+// =============================== BOARD ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LifeParams {
+    pub size: [u32; 2],
+    pub seed: u32,
+    pub _padding: u32,
+}
+
+#[derive(Resource)]
+pub struct LifeBoard {
+    pub target: PingPongTarget,
+    seed_pipeline: GPUPipeline,
+    step_pipeline: GPUPipeline,
+    params: wgpu::Buffer,
+    params_bind_group: wgpu::BindGroup,
+    pub seed: u32,
+    /// Seeds the board again before the next generation.
+    pub reseed: bool,
+    pub step_once: bool,
+    pub generation: u64,
+}
+impl LifeBoard {
+    pub fn new(
+        device: &wgpu::Device,
+        cache: Option<&wgpu::PipelineCache>,
+        size: u32,
+        seed: u32,
+    ) -> Result<Self> {
+        let target = PingPongTarget::new(device, "life", size, size, FORMAT);
+        let params_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("life_params_layout"),
+        });
+        let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("life_params"),
+            contents: bytemuck::bytes_of(&LifeParams {
+                size: [size; 2],
+                seed,
+                _padding: 0,
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let params_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &params_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.as_entire_binding(),
+            }],
+            label: Some("life_params_bind_group"),
+        });
+
+        let source = load_shader_source("life.wgsl", include_str!("../shaders/life.wgsl"));
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("life_shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let build = |label, entry_point| {
+            GPUPipelineBuilder::new(device)
+                .label(label)
+                .pipeline_cache(cache)
+                .bind_group_layout(target.read_layout())
+                .bind_group_layout(&params_layout)
+                .vertex_shader(&shader, "vs_main")
+                .fragment_shader(&shader, entry_point)
+                .default_color_target(FORMAT)
+                .depth_stencil_state(None)
+                .default_multisample_state()
+                .primitive_state(wgpu::PrimitiveState::default())
+                .build()
+                .map_err(|e| anyhow::anyhow!(e))
+        };
+        let seed_pipeline = build("life_seed_pipeline", "fs_seed")?;
+        let step_pipeline = build("life_step_pipeline", "fs_step")?;
+
+        Ok(Self {
+            target,
+            seed_pipeline,
+            step_pipeline,
+            params,
+            params_bind_group,
+            seed,
+            reseed: true,
+            step_once: false,
+            generation: 0,
+        })
+    }
+
+    /// Records a seeding pass if one is due and `steps` generations after
+    /// it, each followed by a swap. Returns whether the read side changed.
+    pub fn run(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        steps: u32,
+    ) -> Result<bool> {
+        let reseed = std::mem::take(&mut self.reseed);
+        if reseed {
+            let (width, height) = self.target.size();
+            let params = LifeParams {
+                size: [width, height],
+                seed: self.seed,
+                _padding: 0,
+            };
+            queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+            self.draw(encoder, label, true)?;
+            self.generation = 0;
+        }
+        for _ in 0..steps {
+            self.draw(encoder, label, false)?;
+            self.generation += 1;
+        }
+        Ok(reseed || steps > 0)
+    }
+
+    fn draw(&mut self, encoder: &mut wgpu::CommandEncoder, label: &str, seed: bool) -> Result<()> {
+        let pipeline = if seed {
+            &self.seed_pipeline
+        } else {
+            &self.step_pipeline
+        };
+        let mut render_pass = RenderPassBuilder::new(encoder)
+            .with_label(label)
+            .with_color_view(self.target.write_view())
+            .build()?;
+        render_pass.set_pipeline(&pipeline.render_pipeline);
+        render_pass.set_bind_group(0, self.target.read_bind_group(), &[]);
+        render_pass.set_bind_group(1, &self.params_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        drop(render_pass);
+        self.target.swap();
+        Ok(())
+    }
+}
+
+#[derive(Resource)]
+pub struct LifePreview {
+    pub texture_id: egui::TextureId,
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct LifeSettings {
+    pub running: bool,
+    pub steps_per_frame: u32,
+    /// Cells per side.
+    pub size: u32,
+}
+impl Default for LifeSettings {
+    fn default() -> Self {
+        Self {
+            running: false,
+            steps_per_frame: 1,
+            size: 256,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader_test::{gpu_or_skip, TestGpu};
+
+    const SIZE: u32 = 64;
+
+    /// Alive cells of the read side, row by row.
+    fn read_board(gpu: &TestGpu, board: &LifeBoard) -> Vec<bool> {
+        let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (SIZE * SIZE * 4) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
+        encoder.copy_texture_to_buffer(
+            board.target.read_texture().as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(SIZE * 4),
+                    rows_per_image: None,
+                },
+            },
+            board.target.read_texture().size(),
+        );
+        gpu.queue.submit(Some(encoder.finish()));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        gpu.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().unwrap().unwrap();
+        let cells = buffer.slice(..).get_mapped_range();
+        cells.chunks_exact(4).map(|texel| texel[1] > 127).collect()
+    }
+
+    fn step_on_cpu(cells: &[bool]) -> Vec<bool> {
+        let size = SIZE as i32;
+        let alive = |x: i32, y: i32| cells[((y + size) % size * size + (x + size) % size) as usize];
+        (0..size * size)
+            .map(|i| {
+                let (x, y) = (i % size, i / size);
+                let neighbours = (-1..=1)
+                    .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                    .filter(|&(dx, dy)| (dx, dy) != (0, 0) && alive(x + dx, y + dy))
+                    .count();
+                neighbours == 3 || (neighbours == 2 && alive(x, y))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn generations_match_the_cpu_through_swaps() -> Result<()> {
+        let Some(gpu) = gpu_or_skip() else {
+            return Ok(());
+        };
+        let mut board = LifeBoard::new(&gpu.device, None, SIZE, 7)?;
+        let run = |board: &mut LifeBoard, steps| -> Result<()> {
+            let mut encoder = gpu.device.create_command_encoder(&Default::default());
+            board.run(&gpu.queue, &mut encoder, "life_test", steps)?;
+            gpu.queue.submit(Some(encoder.finish()));
+            Ok(())
+        };
+
+        run(&mut board, 0)?;
+        let mut expected = read_board(&gpu, &board);
+        let alive = expected.iter().filter(|&&cell| cell).count();
+        assert!(alive > 0 && alive < expected.len() / 2);
+
+        // An odd and an even number of swaps, the read side has to follow
+        for steps in [3, 2] {
+            run(&mut board, steps)?;
+            for _ in 0..steps {
+                expected = step_on_cpu(&expected);
+            }
+            assert_eq!(read_board(&gpu, &board), expected);
+        }
+        assert_eq!(board.generation, 5);
+
+        // Resizing loses the board, it has to be seeded again
+        assert!(board.target.resize(&gpu.device, SIZE / 2, SIZE / 2));
+        assert!(!board.target.resize(&gpu.device, SIZE / 2, SIZE / 2));
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::Result;
+use bevy_ecs::{
+    observer::Trigger,
+    schedule::Schedule,
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use tracing::warn;
+
+use crate::{
+    gpu::{GpuContext, SurfaceChanged},
+    pass::RenderPassBuilder,
+    scene_file::SceneLoading,
+    shader::load_shader_source,
+};
+
+use super::{graph::PassContext, GPUPipeline, GPUPipelineBuilder};
+
+/// Seconds a load runs before the screen starts fading in, and how long the
+/// fade takes.
+const FADE_DELAY: f32 = 0.1;
+const FADE_TIME: f32 = 0.2;
+
+pub fn setup_loading_screen(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let screen = LoadingScreen::new(gpu)?;
+    world.insert_resource(screen);
+    world.add_observer(loading_surface_changed_observer);
+
+    Ok(())
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LoadingParams {
+    pub resolution: [f32; 2],
+    pub progress: f32,
+    pub time: f32,
+    pub srgb_surface: f32,
+    pub opacity: f32,
+    pub _padding: [f32; 2],
+}
+
+/// A progress bar over the old scene, which keeps running underneath while
+/// the new one loads in the background. Drawn on the surface after
+/// presenting, so post effects leave it alone.
+#[derive(Resource)]
+pub struct LoadingScreen {
+    pub layout: wgpu::BindGroupLayout,
+    pub pipeline: GPUPipeline,
+    pub params: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+impl LoadingScreen {
+    pub fn new(gpu: &GpuContext) -> Result<Self> {
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("loading_layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let pipeline = Self::pipeline(gpu, &layout)?;
+        // Written every frame the screen is drawn
+        let params = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("loading_params"),
+            size: std::mem::size_of::<LoadingParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("loading_bind_group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.as_entire_binding(),
+            }],
+        });
+
+        Ok(Self {
+            layout,
+            pipeline,
+            params,
+            bind_group,
+        })
+    }
+
+    /// Renders to the surface, so it is built for its current format.
+    fn pipeline(gpu: &GpuContext, layout: &wgpu::BindGroupLayout) -> Result<GPUPipeline> {
+        let source = load_shader_source("loading.wgsl", include_str!("../shaders/loading.wgsl"));
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("loading_shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        GPUPipelineBuilder::new(&gpu.device)
+            .label("loading_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .color_target(wgpu::ColorTargetState {
+                format: gpu.config.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState::default())
+            .build()
+            .map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+pub fn loading_surface_changed_observer(
+    trigger: Trigger<SurfaceChanged>,
+    gpu: Res<GpuContext>,
+    mut screen: ResMut<LoadingScreen>,
+) {
+    if !trigger.event().format_changed() {
+        return;
+    }
+    match LoadingScreen::pipeline(&gpu, &screen.layout) {
+        Ok(pipeline) => screen.pipeline = pipeline,
+        Err(e) => warn!(
+            "Loading screen pipeline for {:?} failed: {:?}",
+            gpu.config.format, e
+        ),
+    }
+}
+
+/// Draws nothing unless a scene is loading.
+pub fn loading_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let Some((progress, elapsed)) = world
+        .get_resource::<SceneLoading>()
+        .and_then(SceneLoading::progress)
+    else {
+        return Ok(());
+    };
+    let gpu = world.resource::<GpuContext>();
+    let screen = world.resource::<LoadingScreen>();
+    let params = LoadingParams {
+        resolution: [gpu.config.width as f32, gpu.config.height as f32],
+        progress,
+        time: elapsed,
+        srgb_surface: if gpu.config.format.is_srgb() {
+            1.0
+        } else {
+            0.0
+        },
+        opacity: ((elapsed - FADE_DELAY) / FADE_TIME).clamp(0.0, 1.0),
+        _padding: [0.0; 2],
+    };
+    gpu.queue
+        .write_buffer(&screen.params, 0, bytemuck::bytes_of(&params));
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(ctx.surface_view)
+        .load()
+        .build()?;
+    render_pass.set_pipeline(&screen.pipeline.render_pipeline);
+    render_pass.set_bind_group(0, &screen.bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+
+    Ok(())
+}
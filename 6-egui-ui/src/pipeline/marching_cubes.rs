@@ -0,0 +1,505 @@
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, Resource},
+    world::World,
+};
+use glam::Vec3;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pass::RenderPassBuilder,
+    rng::Rng,
+    shader::load_shader_source,
+    time::TimeContext,
+};
+
+use super::{
+    compute::{DispatchSite, GPUComputePipeline},
+    counters::{CounterSlot, GpuCounters},
+    depth::DepthTexture,
+    graph::PassContext,
+    mesh::CameraBuffer,
+    present::FrameBuffer,
+    render::render_system,
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+const RESOLUTION: u32 = 40;
+const CELL_SIZE: f32 = 0.2;
+const FIELD_CENTER: Vec3 = Vec3::new(0.0, 4.0, -12.0);
+/// Triangles the vertex buffer can hold; cells past this are dropped.
+const TRIANGLE_CAPACITY: u32 = 65536;
+
+pub fn setup_marching_cubes(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let seed = world
+        .get_resource_mut::<Rng>()
+        .ok_or_else(|| anyhow::anyhow!("Rng resource not found"))?
+        .next_u32();
+    let counter_slot = world
+        .get_resource_mut::<GpuCounters>()
+        .ok_or_else(|| anyhow::anyhow!("GpuCounters resource not found"))?
+        .register("Marching cubes", &["Triangles", "Dropped triangles"])?;
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let camera = world
+        .get_resource::<CameraBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("CameraBuffer resource not found"))?;
+
+    let counters = world.resource::<GpuCounters>();
+
+    let buffers = MarchingCubesBuffers::new(gpu, TRIANGLE_CAPACITY, counter_slot);
+    let bind_group = MarchingCubesBindGroup::new(gpu, &buffers, counters);
+    let pipelines = MarchingCubesPipelines::new(gpu, &bind_group, camera)?;
+
+    world.insert_resource(buffers);
+    world.insert_resource(bind_group);
+    world.insert_resource(pipelines);
+    world.insert_resource(MarchingCubesSettings {
+        seed,
+        ..Default::default()
+    });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(marching_cubes_panel);
+
+    schedule.add_systems(marching_cubes_uniform_system.before(render_system));
+
+    Ok(())
+}
+
+pub fn marching_cubes_uniform_system(
+    gpu: Res<GpuContext>,
+    time: Res<TimeContext>,
+    settings: Res<MarchingCubesSettings>,
+    buffers: Res<MarchingCubesBuffers>,
+) {
+    let extent = RESOLUTION as f32 * CELL_SIZE;
+    let uniform = FieldUniform {
+        origin: (FIELD_CENTER - Vec3::splat(extent * 0.5))
+            .extend(CELL_SIZE)
+            .to_array(),
+        params: [
+            time.total * settings.speed,
+            settings.iso,
+            settings.radius,
+            settings.roughness,
+        ],
+        resolution: RESOLUTION,
+        capacity: buffers.capacity,
+        seed: settings.seed,
+        _padding: 0,
+    };
+    gpu.queue
+        .write_buffer(&buffers.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+}
+
+/// Polygonizes the field into the vertex buffer and writes the draw arguments.
+pub fn marching_cubes_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    if !world.resource::<MarchingCubesSettings>().enabled {
+        return Ok(());
+    }
+    let buffers = world.resource::<MarchingCubesBuffers>();
+    let bind_group = world.resource::<MarchingCubesBindGroup>();
+    let pipelines = world.resource::<MarchingCubesPipelines>();
+
+    ctx.encoder.clear_buffer(&buffers.counter, 0, None);
+    let mut compute_pass = ctx
+        .encoder
+        .begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(ctx.label),
+            timestamp_writes: None,
+        });
+    compute_pass.set_bind_group(0, &bind_group.bind_group, &[]);
+    compute_pass.set_pipeline(&pipelines.polygonize.pipeline);
+    let [x, y, z] = pipelines.workgroup_counts;
+    compute_pass.dispatch_workgroups(x, y, z);
+    compute_pass.set_pipeline(&pipelines.finalize.pipeline);
+    compute_pass.dispatch_workgroups(1, 1, 1);
+
+    Ok(())
+}
+
+/// Draws however many triangles the compute pass emitted, without a readback.
+pub fn marching_cubes_draw_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    if !world.resource::<MarchingCubesSettings>().enabled {
+        return Ok(());
+    }
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let depth = world.resource::<DepthTexture>();
+    let camera = world.resource::<CameraBuffer>();
+    let buffers = world.resource::<MarchingCubesBuffers>();
+    let pipelines = world.resource::<MarchingCubesPipelines>();
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&frame_buffer.texture.view)
+        .with_depth(&depth.texture.view, 1.0)
+        .load()
+        .build()?;
+
+    render_pass.set_pipeline(&pipelines.draw.render_pipeline);
+    render_pass.set_bind_group(0, &camera.bind_group, &[]);
+    render_pass.set_vertex_buffer(0, buffers.vertices.slice(..));
+    render_pass.draw_indirect(&buffers.draw_args, 0);
+
+    Ok(())
+}
+
+fn marching_cubes_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource_mut::<MarchingCubesSettings>();
+
+    egui::Window::new(tr("Marching cubes"))
+        .id(egui::Id::new("Marching cubes"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Enabled"));
+            ui.add(egui::Slider::new(&mut settings.iso, -0.9..=3.0).text(tr("iso level")));
+            ui.add(egui::Slider::new(&mut settings.radius, 0.2..=1.5).text(tr("blob radius")));
+            ui.add(egui::Slider::new(&mut settings.speed, 0.0..=4.0).text(tr("speed")));
+            ui.add(egui::Slider::new(&mut settings.roughness, 0.0..=1.0).text(tr("roughness")));
+            ui.add(egui::DragValue::new(&mut settings.seed).prefix(tr("noise seed: ")));
+            ui.label(trf!(
+                "{}³ cells, room for {} triangles",
+                RESOLUTION,
+                TRIANGLE_CAPACITY
+            ));
+        });
+}
+
+// =============================== RESOURCES ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct MarchingCubesSettings {
+    pub enabled: bool,
+    pub iso: f32,
+    pub radius: f32,
+    pub speed: f32,
+    /// Amplitude of the simplex noise added to the metaballs.
+    pub roughness: f32,
+    pub seed: u32,
+}
+impl Default for MarchingCubesSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            iso: 0.0,
+            radius: 0.7,
+            speed: 1.0,
+            roughness: 0.3,
+            seed: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FieldUniform {
+    pub origin: [f32; 4],
+    pub params: [f32; 4],
+    pub resolution: u32,
+    pub capacity: u32,
+    pub seed: u32,
+    pub _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FieldVertex {
+    pub position: [f32; 4],
+    pub normal: [f32; 4],
+}
+impl FieldVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct MarchingCubesBuffers {
+    pub uniform_buffer: wgpu::Buffer,
+    pub triangle_table: wgpu::Buffer,
+    /// Written by the compute pass, read as a vertex buffer.
+    pub vertices: wgpu::Buffer,
+    pub counter: wgpu::Buffer,
+    pub draw_args: wgpu::Buffer,
+    pub capacity: u32,
+    /// Triangles emitted and how many of them didn't fit.
+    pub counters: CounterSlot,
+}
+impl MarchingCubesBuffers {
+    pub fn new(gpu: &GpuContext, capacity: u32, counters: CounterSlot) -> Self {
+        let uniform_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("marching_cubes_uniform_buffer"),
+            size: std::mem::size_of::<FieldUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let triangle_table = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("marching_cubes_triangle_table"),
+                contents: bytemuck::cast_slice(&triangle_table()),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let vertices = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("marching_cubes_vertices"),
+            size: (capacity as usize * 3 * std::mem::size_of::<FieldVertex>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+        let counter = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("marching_cubes_counter"),
+            size: 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let draw_args = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("marching_cubes_draw_args"),
+                contents: wgpu::util::DrawIndirectArgs {
+                    vertex_count: 0,
+                    instance_count: 1,
+                    first_vertex: 0,
+                    first_instance: 0,
+                }
+                .as_bytes(),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+            });
+
+        Self {
+            uniform_buffer,
+            triangle_table,
+            vertices,
+            counter,
+            draw_args,
+            capacity,
+            counters,
+        }
+    }
+}
+
+// =============================== BIND GROUP ===============================
+#[derive(Resource)]
+pub struct MarchingCubesBindGroup {
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+impl MarchingCubesBindGroup {
+    pub fn new(gpu: &GpuContext, buffers: &MarchingCubesBuffers, counters: &GpuCounters) -> Self {
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = |read_only| wgpu::BufferBindingType::Storage { read_only };
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    entry(0, wgpu::BufferBindingType::Uniform),
+                    entry(1, storage(true)),
+                    entry(2, storage(false)),
+                    entry(3, storage(false)),
+                    entry(4, storage(false)),
+                    entry(5, storage(false)),
+                ],
+                label: Some("marching_cubes_bind_group_layout"),
+            });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[
+                &buffers.uniform_buffer,
+                &buffers.triangle_table,
+                &buffers.vertices,
+                &buffers.counter,
+                &buffers.draw_args,
+            ]
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .chain([wgpu::BindGroupEntry {
+                binding: 5,
+                resource: counters.binding(buffers.counters),
+            }])
+            .collect::<Vec<_>>(),
+            label: Some("marching_cubes_bind_group"),
+        });
+
+        Self { layout, bind_group }
+    }
+}
+
+// =============================== PIPELINE ===============================
+#[derive(Resource)]
+pub struct MarchingCubesPipelines {
+    pub polygonize: GPUComputePipeline,
+    pub finalize: GPUComputePipeline,
+    pub workgroup_counts: [u32; 3],
+    pub draw: GPUPipeline,
+}
+impl MarchingCubesPipelines {
+    pub fn new(
+        gpu: &GpuContext,
+        bind_group: &MarchingCubesBindGroup,
+        camera: &CameraBuffer,
+    ) -> Result<Self> {
+        let source = load_shader_source(
+            "marching_cubes.wgsl",
+            include_str!("../shaders/marching_cubes.wgsl"),
+        );
+        let compute = |label, entry_point| {
+            GPUComputePipeline::new(
+                &gpu.device,
+                label,
+                &source,
+                entry_point,
+                &[&bind_group.layout],
+                gpu.pipeline_cache(),
+            )
+        };
+        let polygonize = compute("marching_cubes_polygonize", "cs_main")?;
+        let finalize = compute("marching_cubes_finalize", "cs_finalize")?;
+        let workgroup_counts = DispatchSite {
+            label: "marching_cubes",
+            domain: [RESOLUTION; 3],
+        }
+        .validate(polygonize.workgroup_size, &gpu.device.limits())?;
+
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("marching_cubes_draw_shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/marching_cubes_draw.wgsl").into(),
+                ),
+            });
+        let draw = GPUPipelineBuilder::new(&gpu.device)
+            .label("marching_cubes_draw_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&camera.layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .vertex_buffer_layout(FieldVertex::desc())
+            .default_color_target(wgpu::TextureFormat::Rgba16Float)
+            .default_depth_stencil_state()
+            .default_multisample_state()
+            // Loop winding is not tied to the field gradient, so nothing is culled
+            .primitive_state(wgpu::PrimitiveState::default())
+            .build()?;
+
+        Ok(Self {
+            polygonize,
+            finalize,
+            workgroup_counts,
+            draw,
+        })
+    }
+}
+
+// =============================== TABLES ===============================
+/// Corners of each cell edge. Corner `c` sits at `(c & 1, (c >> 1) & 1, (c >> 2) & 1)`.
+const EDGES: [[usize; 2]; 12] = [
+    [0, 1],
+    [0, 2],
+    [0, 4],
+    [1, 3],
+    [1, 5],
+    [2, 3],
+    [2, 6],
+    [3, 7],
+    [4, 5],
+    [4, 6],
+    [5, 7],
+    [6, 7],
+];
+/// Corners of each cell face, counter-clockwise seen from outside the cell.
+const FACES: [[usize; 4]; 6] = [
+    [4, 6, 2, 0],
+    [1, 3, 7, 5],
+    [1, 5, 4, 0],
+    [2, 6, 7, 3],
+    [2, 3, 1, 0],
+    [4, 5, 7, 6],
+];
+
+/// Builds the 256-case triangle table instead of embedding the classic listing.
+///
+/// On every face, each run of inside corners is cut off by a segment between
+/// the edge where the run starts and the edge where it ends. Walking the faces
+/// counter-clockwise, a crossed edge ends one face's segment and starts the
+/// neighbour's, so the segments link up into closed loops that are then fanned
+/// into triangles. Ambiguous faces are always split the same way, which keeps
+/// neighbouring cells crack-free.
+fn triangle_table() -> Vec<i32> {
+    let edge_index = |a: usize, b: usize| {
+        EDGES
+            .iter()
+            .position(|edge| *edge == [a.min(b), a.max(b)])
+            .expect("corners share an edge")
+    };
+
+    let mut table = vec![-1; 256 * 16];
+    for case in 0..256usize {
+        let inside = |corner: usize| case >> corner & 1 == 1;
+
+        // Segment from the edge leaving an inside run to the edge entering it
+        let mut next = [None; 12];
+        for face in FACES {
+            for k in 0..4 {
+                let (a, b) = (face[k], face[(k + 1) % 4]);
+                if !inside(a) || inside(b) {
+                    continue;
+                }
+                let mut start = k;
+                while inside(face[(start + 3) % 4]) {
+                    start = (start + 3) % 4;
+                }
+                next[edge_index(a, b)] = Some(edge_index(face[(start + 3) % 4], face[start]));
+            }
+        }
+
+        let mut triangles = Vec::new();
+        let mut visited = [false; 12];
+        for first in 0..12 {
+            if visited[first] || next[first].is_none() {
+                continue;
+            }
+            let mut polygon = vec![first];
+            visited[first] = true;
+            let mut edge = next[first].expect("checked above");
+            while edge != first {
+                polygon.push(edge);
+                visited[edge] = true;
+                edge = next[edge].expect("segments form closed loops");
+            }
+            for i in 1..polygon.len().saturating_sub(1) {
+                triangles.extend([polygon[0], polygon[i], polygon[i + 1]]);
+            }
+        }
+
+        for (slot, edge) in table[case * 16..].iter_mut().zip(triangles) {
+            *slot = edge as i32;
+        }
+    }
+    table
+}
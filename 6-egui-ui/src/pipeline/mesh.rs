@@ -0,0 +1,857 @@
+pub mod gltf;
+pub mod model;
+pub mod obj;
+
+use std::ops::Range;
+
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Query, Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Mat4, Vec3};
+use tracing::warn;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    assets::{AssetServer, MeshHandle},
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    lights::LightBuffer,
+    pass::RenderPassBuilder,
+    scene::{
+        draw_list_system, transform_propagation_system, Camera, DrawCommand, DrawList,
+        MaterialDesc, MaterialId, MaterialTable, MeshId, PipelineId, Renderable,
+    },
+    shader::{load_shader_source, parse_wgsl, preprocess},
+    time::TimeHistory,
+    vertex::{cube_vertices, quad_vertices, weld, IndexBuffer, MeshVertex},
+};
+
+use self::model::{model_material_system, model_spawn_system, ModelCache};
+use super::{
+    depth::DepthTexture,
+    graph::PassContext,
+    present::FrameBuffer,
+    render::render_system,
+    ssr::{SurfaceBuffer, SURFACE_FORMAT},
+    stereo::{StereoCameras, StereoMode, StereoSettings},
+    ui::UiPanels,
+    velocity::{VelocityBuffer, VELOCITY_FORMAT},
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+const INITIAL_INSTANCES: u64 = 1024;
+
+pub fn setup_mesh(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let camera_buffer = CameraBuffer::new(gpu);
+    let instances = InstanceBuffer::new(gpu, INITIAL_INSTANCES);
+    let table = world
+        .get_resource::<MaterialTable>()
+        .ok_or_else(|| anyhow::anyhow!("MaterialTable resource not found"))?;
+    let materials = Materials::new(gpu, table);
+    let meshes = Meshes::new(&mut world.resource_mut::<AssetServer>());
+    let gpu = world.resource::<GpuContext>();
+    let lights = world
+        .get_resource::<LightBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("LightBuffer resource not found"))?;
+    let settings = MeshShaderSettings::default();
+    let pipelines = MeshPipelines::new(
+        gpu,
+        &camera_buffer,
+        &instances,
+        &materials,
+        lights,
+        &settings,
+    )?;
+
+    world.insert_resource(camera_buffer);
+    world.insert_resource(instances);
+    world.insert_resource(materials);
+    world.insert_resource(meshes);
+    world.init_resource::<MeshTriangles>();
+    world.init_resource::<ModelCache>();
+    world.insert_resource(pipelines);
+    world.insert_resource(settings);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(mesh_shader_panel);
+
+    schedule.add_systems((
+        mesh_permutation_system
+            .run_if(resource_changed::<MeshShaderSettings>)
+            .before(render_system),
+        mesh_prepare_system
+            .after(draw_list_system)
+            .before(render_system),
+        material_upload_system
+            .run_if(resource_changed::<MaterialTable>)
+            .before(render_system),
+        mesh_residency_system
+            .after(draw_list_system)
+            .before(render_system),
+        model_spawn_system.before(transform_propagation_system),
+        model_material_system
+            .after(model_spawn_system)
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Uploads the camera and the instance data of the draw list.
+pub fn mesh_prepare_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    draw_list: Res<DrawList>,
+    mut camera_buffer: ResMut<CameraBuffer>,
+    mut instances: ResMut<InstanceBuffer>,
+) {
+    camera_buffer.write(&gpu, camera.view_projection(), camera.eye);
+    instances.write(&gpu, &draw_list.instances);
+}
+
+/// Rebuilds the pipelines with the selected permutation, falling back to the
+/// default one when it doesn't compile.
+pub fn mesh_permutation_system(
+    gpu: Res<GpuContext>,
+    settings: Res<MeshShaderSettings>,
+    camera: Res<CameraBuffer>,
+    instances: Res<InstanceBuffer>,
+    materials: Res<Materials>,
+    lights: Res<LightBuffer>,
+    mut pipelines: ResMut<MeshPipelines>,
+) {
+    if pipelines.settings == *settings && pipelines.fallback_reason.is_none() {
+        return;
+    }
+    let build = |settings: &MeshShaderSettings| {
+        MeshPipelines::new(&gpu, &camera, &instances, &materials, &lights, settings)
+    };
+    *pipelines = match build(&settings) {
+        Ok(built) => built,
+        Err(e) => {
+            warn!("Mesh shader permutation {:?} failed: {:?}", *settings, e);
+            match build(&MeshShaderSettings::default()) {
+                Ok(built) => MeshPipelines {
+                    fallback_reason: Some(format!("{:#}", e)),
+                    ..built
+                },
+                Err(e) => {
+                    warn!("Default mesh shader failed: {:?}", e);
+                    return;
+                }
+            }
+        }
+    };
+}
+
+fn mesh_shader_panel(ctx: &egui::Context, world: &mut World) {
+    let f16_supported = world
+        .resource::<GpuContext>()
+        .device
+        .features()
+        .contains(wgpu::Features::SHADER_F16);
+    let pipelines = world.resource::<MeshPipelines>();
+    let (active, fallback_reason) = (
+        pipelines.settings.clone(),
+        pipelines.fallback_reason.clone(),
+    );
+    let history = world.resource::<TimeHistory>();
+    let frame_ms = history.average_frame_time() * 1000.0;
+    let mut settings = world.resource_mut::<MeshShaderSettings>();
+
+    egui::Window::new(tr("Mesh shading"))
+        .id(egui::Id::new("Mesh shading"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.add_enabled_ui(f16_supported, |ui| {
+                ui.checkbox(&mut settings.half_lighting, tr("f16 lighting"))
+                    .on_disabled_hover_text(tr("The device doesn't support SHADER_F16"));
+            });
+            ui.label(trf!(
+                "Active permutation: {}",
+                if active.half_lighting {
+                    tr("f16 lighting")
+                } else {
+                    tr("f32 lighting")
+                }
+            ));
+            if let Some(reason) = fallback_reason {
+                ui.label(trf!("Fell back to f32 lighting: {}", reason));
+            }
+            ui.label(trf!("Average frame time: {:.3} ms", frame_ms));
+        });
+}
+
+/// Keeps the meshes of every renderable resident, shadow casters outside the
+/// view included. Meshes nothing refers to anymore can be evicted.
+pub fn mesh_residency_system(
+    gpu: Res<GpuContext>,
+    meshes: Res<Meshes>,
+    mut assets: ResMut<AssetServer>,
+    renderables: Query<&Renderable>,
+) {
+    let mut used = vec![false; meshes.handles.len()];
+    for renderable in renderables.iter() {
+        if let Some(used) = used.get_mut(renderable.mesh.0 as usize) {
+            *used = true;
+        }
+    }
+    for (handle, _) in meshes.handles.iter().zip(used).filter(|(_, used)| *used) {
+        if let Err(e) = assets.use_mesh(&gpu, *handle) {
+            warn!("Failed to upload a mesh: {:?}", e);
+        }
+    }
+}
+
+/// Re-uploads material constants after they were edited at runtime, or
+/// rebuilds the buffers when a scene reload changed how many there are.
+pub fn material_upload_system(
+    gpu: Res<GpuContext>,
+    table: Res<MaterialTable>,
+    mut materials: ResMut<Materials>,
+) {
+    if materials.materials.len() != table.materials.len() {
+        materials.rebuild(&gpu, &table);
+        return;
+    }
+    for (desc, material) in table.materials.iter().zip(&materials.materials) {
+        let uniform = MaterialUniform::new(desc);
+        gpu.queue
+            .write_buffer(&material.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+}
+
+pub fn mesh_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let depth = world.resource::<DepthTexture>();
+    let draw_list = world.resource::<DrawList>();
+    let pipelines = world.resource::<MeshPipelines>();
+    let camera_buffer = world.resource::<CameraBuffer>();
+    let instances = world.resource::<InstanceBuffer>();
+    let materials = world.resource::<Materials>();
+    let meshes = world.resource::<Meshes>();
+    let assets = world.resource::<AssetServer>();
+    let lights = world.resource::<LightBuffer>();
+    let velocity = world.resource::<VelocityBuffer>();
+    let surface = world.resource::<SurfaceBuffer>();
+    let stereo = world
+        .get_resource::<StereoSettings>()
+        .map_or(StereoMode::Off, |settings| settings.mode);
+    let size = frame_buffer.texture.texture.size();
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&frame_buffer.texture.view)
+        .with_cleared_color_view(&velocity.texture.view, wgpu::Color::TRANSPARENT)
+        .with_cleared_color_view(&surface.texture.view, wgpu::Color::TRANSPARENT)
+        .with_depth(&depth.texture.view, 1.0)
+        .load()
+        .build()?;
+
+    render_pass.set_bind_group(1, &instances.bind_group, &[]);
+    render_pass.set_bind_group(3, &lights.bind_group, &[]);
+    let draw = |render_pass: &mut wgpu::RenderPass| {
+        draw_commands(render_pass, draw_list, pipelines, materials, meshes, assets)
+    };
+    match world.get_resource::<StereoCameras>() {
+        Some(cameras) if stereo != StereoMode::Off => {
+            // The whole list once per eye, each into its half
+            for (eye, x) in stereo.halves(size.width as f32) {
+                render_pass.set_viewport(
+                    x,
+                    0.0,
+                    size.width as f32 / 2.0,
+                    size.height as f32,
+                    0.0,
+                    1.0,
+                );
+                render_pass.set_bind_group(0, &cameras.eye(eye).bind_group, &[]);
+                draw(&mut render_pass)?;
+            }
+        }
+        _ => {
+            render_pass.set_bind_group(0, &camera_buffer.bind_group, &[]);
+            draw(&mut render_pass)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replays the draw list into a pass with the camera already bound.
+pub(super) fn draw_commands(
+    render_pass: &mut wgpu::RenderPass,
+    draw_list: &DrawList,
+    pipelines: &MeshPipelines,
+    materials: &Materials,
+    meshes: &Meshes,
+    assets: &AssetServer,
+) -> Result<()> {
+    let mut mesh = None;
+    for command in &draw_list.commands {
+        match *command {
+            DrawCommand::SetPipeline(id) => {
+                render_pass.set_pipeline(&pipelines.get(id)?.render_pipeline);
+            }
+            DrawCommand::SetMaterial(id) => {
+                render_pass.set_bind_group(2, &materials.get(id)?.bind_group, &[]);
+            }
+            DrawCommand::SetMesh(id) => {
+                let current = meshes.get(assets, id)?;
+                current.bind(render_pass);
+                mesh = Some(current);
+            }
+            DrawCommand::Draw {
+                first_instance,
+                instance_count,
+            } => {
+                let mesh = mesh.ok_or_else(|| anyhow::anyhow!("Draw before any mesh was set"))?;
+                mesh.draw(render_pass, first_instance..first_instance + instance_count);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// =============================== CAMERA ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view_proj: [[f32; 4]; 4],
+    pub eye: [f32; 4],
+    /// Last frame's `view_proj`, for motion vectors. Shaders that don't need
+    /// it can leave it out of their struct.
+    pub prev_view_proj: [[f32; 4]; 4],
+}
+
+#[derive(Resource)]
+pub struct CameraBuffer {
+    pub buffer: wgpu::Buffer,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    /// What was uploaded last, `None` before the first upload.
+    pub uploaded: Option<CameraUniform>,
+}
+impl CameraBuffer {
+    pub fn new(gpu: &GpuContext) -> Self {
+        let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("camera_buffer"),
+            size: std::mem::size_of::<CameraUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("camera_bind_group_layout"),
+            });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("camera_bind_group"),
+        });
+
+        Self {
+            buffer,
+            layout,
+            bind_group,
+            uploaded: None,
+        }
+    }
+
+    /// Uploads the camera of a new frame, the last upload becomes its
+    /// previous one.
+    pub fn write(&mut self, gpu: &GpuContext, view_proj: Mat4, eye: Vec3) {
+        let previous = self
+            .uploaded
+            .map_or(view_proj.to_cols_array_2d(), |uploaded| uploaded.view_proj);
+        self.upload(gpu, view_proj, eye, previous);
+    }
+
+    /// Replaces this frame's camera, e.g. after the late latch moved it.
+    /// Stays relative to the same previous frame.
+    pub fn rewrite(&mut self, gpu: &GpuContext, view_proj: Mat4, eye: Vec3) {
+        let previous = self
+            .uploaded
+            .map_or(view_proj.to_cols_array_2d(), |uploaded| {
+                uploaded.prev_view_proj
+            });
+        self.upload(gpu, view_proj, eye, previous);
+    }
+
+    /// Uploads a camera whose previous frame isn't simply the last upload,
+    /// like a jittered one, whose motion vectors shouldn't include the jitter.
+    pub fn write_with_previous(
+        &mut self,
+        gpu: &GpuContext,
+        view_proj: Mat4,
+        eye: Vec3,
+        previous: Mat4,
+    ) {
+        self.upload(gpu, view_proj, eye, previous.to_cols_array_2d());
+    }
+
+    fn upload(&mut self, gpu: &GpuContext, view_proj: Mat4, eye: Vec3, previous: [[f32; 4]; 4]) {
+        let data = CameraUniform {
+            view_proj: view_proj.to_cols_array_2d(),
+            eye: eye.extend(1.0).to_array(),
+            prev_view_proj: previous,
+        };
+        gpu.queue
+            .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&data));
+        self.uploaded = Some(data);
+    }
+}
+
+// =============================== OBJECTS ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ObjectUniform {
+    pub model: [[f32; 4]; 4],
+    pub prev_model: [[f32; 4]; 4],
+}
+impl ObjectUniform {
+    /// An object that didn't move since the last frame.
+    pub fn new(model: &Mat4) -> Self {
+        Self::with_previous(model, model)
+    }
+
+    pub fn with_previous(model: &Mat4, previous: &Mat4) -> Self {
+        Self {
+            model: model.to_cols_array_2d(),
+            prev_model: previous.to_cols_array_2d(),
+        }
+    }
+}
+
+/// Storage buffer of every visible object's data, indexed by the instance
+/// index so that a single draw can cover any number of objects.
+#[derive(Resource)]
+pub struct InstanceBuffer {
+    pub buffer: wgpu::Buffer,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    pub capacity: u64,
+}
+impl InstanceBuffer {
+    pub fn new(gpu: &GpuContext, capacity: u64) -> Self {
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("instance_bind_group_layout"),
+            });
+        let (buffer, bind_group) = Self::create(gpu, &layout, capacity);
+
+        Self {
+            buffer,
+            layout,
+            bind_group,
+            capacity,
+        }
+    }
+
+    fn create(
+        gpu: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        capacity: u64,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("instance_buffer"),
+            size: capacity * std::mem::size_of::<ObjectUniform>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("instance_bind_group"),
+        });
+        (buffer, bind_group)
+    }
+
+    /// Uploads `instances`, growing the buffer if they don't fit.
+    pub fn write(&mut self, gpu: &GpuContext, instances: &[ObjectUniform]) {
+        let len = instances.len() as u64;
+        if len > self.capacity {
+            self.capacity = len.next_power_of_two();
+            (self.buffer, self.bind_group) = Self::create(gpu, &self.layout, self.capacity);
+        }
+        if !instances.is_empty() {
+            gpu.queue
+                .write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
+        }
+    }
+}
+
+// =============================== MATERIALS ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub base_color: [f32; 4],
+    pub roughness: f32,
+    pub reflectivity: f32,
+    pub _padding: [f32; 2],
+}
+impl MaterialUniform {
+    pub fn new(desc: &MaterialDesc) -> Self {
+        Self {
+            base_color: desc.base_color.to_array(),
+            roughness: desc.roughness,
+            reflectivity: desc.reflectivity,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+pub struct GpuMaterial {
+    pub buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+#[derive(Resource)]
+pub struct Materials {
+    pub layout: wgpu::BindGroupLayout,
+    pub materials: Vec<GpuMaterial>,
+}
+impl Materials {
+    pub fn new(gpu: &GpuContext, table: &MaterialTable) -> Self {
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("material_bind_group_layout"),
+            });
+        let materials = Self::create(gpu, &layout, table);
+
+        Self { layout, materials }
+    }
+
+    /// Keeps the layout, so pipelines don't have to be rebuilt.
+    pub fn rebuild(&mut self, gpu: &GpuContext, table: &MaterialTable) {
+        self.materials = Self::create(gpu, &self.layout, table);
+    }
+
+    fn create(
+        gpu: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        table: &MaterialTable,
+    ) -> Vec<GpuMaterial> {
+        table
+            .materials
+            .iter()
+            .map(|desc| {
+                let buffer = gpu
+                    .device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("material_buffer"),
+                        contents: bytemuck::bytes_of(&MaterialUniform::new(desc)),
+                        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    });
+                let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                    label: Some("material_bind_group"),
+                });
+                GpuMaterial { buffer, bind_group }
+            })
+            .collect()
+    }
+
+    pub fn get(&self, id: MaterialId) -> Result<&GpuMaterial> {
+        self.materials
+            .get(id.0 as usize)
+            .ok_or_else(|| anyhow::anyhow!("Unknown material {:?}", id))
+    }
+}
+
+// =============================== MESHES ===============================
+pub struct GpuMesh {
+    pub vertex_buffer: wgpu::Buffer,
+    /// `None` for a plain triangle list.
+    pub index_buffer: Option<IndexBuffer>,
+    /// Indices drawn, or vertices without an index buffer.
+    pub count: u32,
+}
+impl GpuMesh {
+    /// Binds the vertex buffer to slot 0, and the index buffer if there is one.
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        if let Some(index_buffer) = &self.index_buffer {
+            index_buffer.bind(render_pass);
+        }
+    }
+
+    /// Draws the whole mesh after [`GpuMesh::bind`], indexed or not.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, instances: Range<u32>) {
+        match &self.index_buffer {
+            Some(index_buffer) => index_buffer.draw(render_pass, instances),
+            None => render_pass.draw(0..self.count, instances),
+        }
+    }
+}
+
+#[derive(Resource)]
+/// Asset server handles of the meshes, indexed by [`MeshId`].
+pub struct Meshes {
+    pub handles: Vec<MeshHandle>,
+}
+impl Meshes {
+    pub fn new(assets: &mut AssetServer) -> Self {
+        // Registered in the order of the built-in `MeshId` constants
+        let (vertices, indices) = weld(&cube_vertices());
+        let cube = assets.add_mesh("cube", vertices, Some(indices));
+        let (vertices, indices) = weld(&quad_vertices());
+        let quad = assets.add_mesh("quad", vertices, Some(indices));
+        Self {
+            handles: vec![cube, quad],
+        }
+    }
+
+    /// Registers a mesh after the built-in ones, e.g. from a loaded model.
+    pub fn add(
+        &mut self,
+        assets: &mut AssetServer,
+        label: &str,
+        vertices: Vec<MeshVertex>,
+        indices: Option<Vec<u32>>,
+    ) -> MeshId {
+        self.handles.push(assets.add_mesh(label, vertices, indices));
+        MeshId(self.handles.len() as u32 - 1)
+    }
+
+    pub fn handle(&self, id: MeshId) -> Result<MeshHandle> {
+        self.handles
+            .get(id.0 as usize)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Unknown mesh {:?}", id))
+    }
+
+    /// Resident for every mesh a renderable uses, see [`mesh_residency_system`].
+    pub fn get<'a>(&self, assets: &'a AssetServer, id: MeshId) -> Result<&'a GpuMesh> {
+        Ok(assets.mesh(self.handle(id)?)?)
+    }
+}
+
+/// Meshes as plain triangle lists, for the debug passes that read a whole
+/// triangle per instance, which an index buffer can't feed. Uploaded on
+/// first use and kept, indexed by [`MeshId`].
+#[derive(Resource, Default)]
+pub struct MeshTriangles {
+    buffers: Vec<Option<(wgpu::Buffer, u32)>>,
+}
+impl MeshTriangles {
+    pub fn upload(
+        &mut self,
+        gpu: &GpuContext,
+        assets: &AssetServer,
+        meshes: &Meshes,
+        id: MeshId,
+    ) -> Result<()> {
+        let index = id.0 as usize;
+        if self.buffers.get(index).is_some_and(Option::is_some) {
+            return Ok(());
+        }
+        let vertices = assets.mesh_triangles(meshes.handle(id)?)?;
+        let buffer = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("mesh_triangles"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        if self.buffers.len() <= index {
+            self.buffers.resize_with(index + 1, || None);
+        }
+        self.buffers[index] = Some((buffer, vertices.len() as u32 / 3));
+        Ok(())
+    }
+
+    /// The buffer and its triangle count, after [`MeshTriangles::upload`].
+    pub fn get(&self, id: MeshId) -> Result<(&wgpu::Buffer, u32)> {
+        self.buffers
+            .get(id.0 as usize)
+            .and_then(Option::as_ref)
+            .map(|(buffer, count)| (buffer, *count))
+            .ok_or_else(|| anyhow::anyhow!("No triangle list uploaded for {:?}", id))
+    }
+}
+
+// =============================== PIPELINE ===============================
+/// Shader permutation the mesh pipelines are built from.
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct MeshShaderSettings {
+    /// Accumulates lighting in f16, needs `Features::SHADER_F16`.
+    pub half_lighting: bool,
+}
+impl MeshShaderSettings {
+    pub fn defines(&self) -> Vec<&'static str> {
+        let mut defines = Vec::new();
+        if self.half_lighting {
+            defines.push("HALF_LIGHTING");
+        }
+        defines
+    }
+}
+
+/// Render pipelines addressed by [`PipelineId`]: an opaque variant that writes
+/// depth, and an alpha-blended variant that only tests against it.
+#[derive(Resource)]
+pub struct MeshPipelines {
+    pub pipelines: Vec<GPUPipeline>,
+    /// Permutation the pipelines were built from.
+    pub settings: MeshShaderSettings,
+    /// Why the requested permutation was replaced by the default one.
+    pub fallback_reason: Option<String>,
+}
+impl MeshPipelines {
+    pub fn new(
+        gpu: &GpuContext,
+        camera: &CameraBuffer,
+        instances: &InstanceBuffer,
+        materials: &Materials,
+        lights: &LightBuffer,
+        settings: &MeshShaderSettings,
+    ) -> Result<Self> {
+        if settings.half_lighting && !gpu.device.features().contains(wgpu::Features::SHADER_F16) {
+            anyhow::bail!("SHADER_F16 is not supported by this device");
+        }
+        let source = preprocess(
+            "mesh.wgsl",
+            &load_shader_source("mesh.wgsl", include_str!("../shaders/mesh.wgsl")),
+            &settings.defines(),
+        )?;
+        // Validate first, a permutation naga can't handle must not reach wgpu
+        parse_wgsl("mesh.wgsl", &source)?;
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("mesh_shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let opaque = GPUPipelineBuilder::new(&gpu.device)
+            .label("mesh_opaque_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&camera.layout)
+            .bind_group_layout(&instances.layout)
+            .bind_group_layout(&materials.layout)
+            .bind_group_layout(&lights.layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .vertex_buffer_layout(MeshVertex::desc())
+            .default_color_target(wgpu::TextureFormat::Rgba16Float)
+            .color_target(wgpu::ColorTargetState {
+                format: VELOCITY_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .color_target(wgpu::ColorTargetState {
+                format: SURFACE_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .default_depth_stencil_state()
+            .default_multisample_state()
+            .default_primitive_state()
+            .build()?;
+
+        let transparent = GPUPipelineBuilder::new(&gpu.device)
+            .label("mesh_transparent_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&camera.layout)
+            .bind_group_layout(&instances.layout)
+            .bind_group_layout(&materials.layout)
+            .bind_group_layout(&lights.layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .vertex_buffer_layout(MeshVertex::desc())
+            .color_target(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Rgba16Float,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            // Blended surfaces keep the velocity and surface of what's behind them
+            .color_target(wgpu::ColorTargetState {
+                format: VELOCITY_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::empty(),
+            })
+            .color_target(wgpu::ColorTargetState {
+                format: SURFACE_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::empty(),
+            })
+            .depth_stencil_state(Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }))
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            })
+            .build()?;
+
+        // Indexed by `PipelineId::OPAQUE` and `PipelineId::TRANSPARENT`
+        Ok(Self {
+            pipelines: vec![opaque, transparent],
+            settings: settings.clone(),
+            fallback_reason: None,
+        })
+    }
+
+    pub fn get(&self, id: PipelineId) -> Result<&GPUPipeline> {
+        self.pipelines
+            .get(id.0 as usize)
+            .ok_or_else(|| anyhow::anyhow!("Unknown pipeline {:?}", id))
+    }
+}
@@ -0,0 +1,157 @@
+//! glTF models, from `.gltf` files with their buffers next to them or
+//! embedded, and from `.glb` files. Every triangle primitive becomes a mesh
+//! with indices, and texture coordinates when it has them, and every glTF
+//! material a [`MaterialDesc`] without its textures. The node hierarchy of
+//! the default scene is flattened into parts, one per primitive drawn.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use glam::{Mat4, Vec4};
+use tracing::warn;
+
+use crate::scene::MaterialDesc;
+
+use super::model::{Model, ModelMaterial, ModelMesh, ModelPart};
+
+pub fn load(path: &Path) -> Result<Model> {
+    let ::gltf::Gltf { document, blob } =
+        ::gltf::Gltf::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let buffers = ::gltf::import_buffers(&document, path.parent(), blob)
+        .with_context(|| format!("Failed to read the buffers of {}", path.display()))?;
+    from_document(&document, &buffers)
+}
+
+fn from_document(document: &::gltf::Document, buffers: &[::gltf::buffer::Data]) -> Result<Model> {
+    let mut materials: Vec<ModelMaterial> = document
+        .materials()
+        .map(|gltf_material| material(gltf_material).into())
+        .collect();
+    // Primitives without a material share a white one at the end
+    let mut default_material = None;
+
+    let mut meshes = Vec::new();
+    // Model meshes of each glTF mesh, one per primitive
+    let mut primitives = Vec::new();
+    for mesh in document.meshes() {
+        let mut indices = Vec::new();
+        for primitive in mesh.primitives() {
+            if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+                warn!(
+                    "Skipping a {:?} primitive of mesh {}, only triangles are supported",
+                    primitive.mode(),
+                    mesh.index()
+                );
+                continue;
+            }
+            let material = match primitive.material().index() {
+                Some(index) => index,
+                None => *default_material.get_or_insert_with(|| {
+                    materials.push(MaterialDesc::opaque(Vec4::ONE).into());
+                    materials.len() - 1
+                }),
+            };
+            let label = match mesh.name() {
+                Some(name) => format!("{} {}", name, primitive.index()),
+                None => format!("mesh {} {}", mesh.index(), primitive.index()),
+            };
+            indices.push(meshes.len());
+            meshes.push(read_primitive(&primitive, buffers, label, material)?);
+        }
+        primitives.push(indices);
+    }
+
+    let mut parts = Vec::new();
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .context("The model has no scene")?;
+    let mut pending: Vec<_> = scene.nodes().map(|node| (node, Mat4::IDENTITY)).collect();
+    while let Some((node, parent)) = pending.pop() {
+        let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+        if let Some(mesh) = node.mesh() {
+            let name = node.name().unwrap_or("Part");
+            for &mesh in &primitives[mesh.index()] {
+                parts.push(ModelPart {
+                    name: name.to_string(),
+                    mesh,
+                    transform,
+                });
+            }
+        }
+        pending.extend(node.children().map(|child| (child, transform)));
+    }
+
+    Ok(Model {
+        meshes,
+        materials,
+        parts,
+    })
+}
+
+fn material(material: ::gltf::Material) -> MaterialDesc {
+    let pbr = material.pbr_metallic_roughness();
+    let base_color = Vec4::from_array(pbr.base_color_factor());
+    let desc = match material.alpha_mode() {
+        ::gltf::material::AlphaMode::Blend => MaterialDesc::transparent(base_color),
+        _ => MaterialDesc::opaque(base_color),
+    };
+    // Only metals reflect in this renderer, dielectrics stay matte
+    desc.with_reflections(pbr.roughness_factor(), pbr.metallic_factor())
+}
+
+fn read_primitive(
+    primitive: &::gltf::Primitive,
+    buffers: &[::gltf::buffer::Data],
+    label: String,
+    material: usize,
+) -> Result<ModelMesh> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .with_context(|| format!("{} has no positions", label))?
+        .collect();
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    let normals = reader.read_normals().map(|normals| normals.collect());
+    let tex_coords = reader
+        .read_tex_coords(0)
+        .map(|tex_coords| tex_coords.into_f32().collect());
+    ModelMesh::new(label, positions, normals, tex_coords, indices, material)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+
+    use super::{
+        super::model::{smooth_normals, MODEL_DIR},
+        *,
+    };
+
+    #[test]
+    fn gem_model_loads_with_its_hierarchy() {
+        let model = load(&Path::new(MODEL_DIR).join("models/gem.gltf")).unwrap();
+        assert_eq!(model.meshes.len(), 2);
+        assert_eq!(model.materials.len(), 2);
+        let pedestal = &model.meshes[0];
+        // A hexagonal prism, flat shaded
+        assert_eq!(pedestal.vertices.len(), 36);
+        assert_eq!(pedestal.indices.len(), 60);
+        assert!((pedestal.bounds.max.y - 0.6).abs() < 1e-5);
+
+        // The gem sits on the pedestal as a child node
+        let gem = model.parts.iter().find(|part| part.name == "Gem").unwrap();
+        assert_eq!(model.meshes[gem.mesh].material, 1);
+        let (scale, rotation, translation) = gem.transform.to_scale_rotation_translation();
+        assert!(translation.abs_diff_eq(Vec3::new(0.0, 1.5, 0.0), 1e-5));
+        assert!(scale.abs_diff_eq(Vec3::new(1.0, 1.4, 1.0), 1e-5));
+        assert!(rotation.angle_between(Quat::from_rotation_y(std::f32::consts::FRAC_PI_4)) < 1e-4);
+
+        // Missing normals face out of a counter-clockwise triangle
+        let normals = smooth_normals(&[[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]], &[0, 1, 2]);
+        assert_eq!(normals, vec![[0.0, 0.0, 1.0]; 3]);
+    }
+}
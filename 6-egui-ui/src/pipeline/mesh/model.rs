@@ -0,0 +1,420 @@
+//! Models loaded from files, in whichever format the extension says: glTF
+//! through [`super::gltf`], Wavefront OBJ through [`super::obj`]. Either way
+//! a model is indexed meshes, [`MaterialDesc`]s and the parts drawing them,
+//! each with its transform relative to the model.
+//!
+//! In a scene a model is an entity with a [`ModelSource`]. The first time
+//! [`model_spawn_system`] sees it, the model is loaded, its meshes are
+//! registered with [`Meshes`] and its materials appended to the
+//! [`MaterialTable`], and every part is spawned as a child of the entity.
+//! [`model_material_system`] then gives every material it appended a bind
+//! group in the layout of the diffuse pass, with its diffuse texture or white.
+//! Anything beyond that, the base color, roughness and reflectivity is
+//! ignored.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use bevy_ecs::{
+    change_detection::DetectChanges,
+    component::Component,
+    entity::Entity,
+    query::Added,
+    system::{Commands, Query, Res, ResMut, Resource},
+};
+use glam::{Mat4, Vec3};
+use tracing::{error, info, warn};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    assets::{mip_tint, AssetServer, AssetSettings, TextureHandle, TextureSource},
+    gpu::GpuContext,
+    pipeline::diffuse::{DiffuseBindGroup, DiffuseBindGroupLayout},
+    raycast::MeshColliders,
+    sampler::{SamplerCache, SamplerKey, SamplerSettings},
+    scene::{
+        Aabb, GlobalTransform, MaterialDesc, MaterialId, MaterialTable, MeshId, Name, Parent,
+        Renderable, SceneEntity, Transform, Visibility,
+    },
+    texture::Texture,
+    vertex::{unweld, MeshVertex},
+};
+
+use super::{gltf, obj, Meshes};
+
+/// Where relative model paths are looked up.
+pub const MODEL_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets");
+
+// =============================== MODEL ===============================
+pub struct ModelMesh {
+    pub label: String,
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+    /// One per vertex, with the origin at the top left of the texture.
+    pub tex_coords: Option<Vec<[f32; 2]>>,
+    /// Index into [`Model::materials`].
+    pub material: usize,
+    pub bounds: Aabb,
+}
+impl ModelMesh {
+    /// Checks the indices and fills in smooth normals when there are none.
+    pub fn new(
+        label: String,
+        positions: Vec<[f32; 3]>,
+        normals: Option<Vec<[f32; 3]>>,
+        tex_coords: Option<Vec<[f32; 2]>>,
+        indices: Vec<u32>,
+        material: usize,
+    ) -> Result<Self> {
+        if let Some(index) = indices
+            .iter()
+            .find(|&&index| index as usize >= positions.len())
+        {
+            anyhow::bail!(
+                "{} has index {} past its {} vertices",
+                label,
+                index,
+                positions.len()
+            );
+        }
+        if let Some(tex_coords) = tex_coords
+            .as_ref()
+            .filter(|tex_coords| tex_coords.len() != positions.len())
+        {
+            anyhow::bail!(
+                "{} has {} texture coordinates for {} vertices",
+                label,
+                tex_coords.len(),
+                positions.len()
+            );
+        }
+        let normals = normals.unwrap_or_else(|| smooth_normals(&positions, &indices));
+
+        let bounds = positions.iter().fold(
+            Aabb {
+                min: Vec3::splat(f32::MAX),
+                max: Vec3::splat(f32::MIN),
+            },
+            |bounds, &position| Aabb {
+                min: bounds.min.min(Vec3::from(position)),
+                max: bounds.max.max(Vec3::from(position)),
+            },
+        );
+        let vertices = positions
+            .into_iter()
+            .zip(normals)
+            .map(|(position, normal)| MeshVertex { position, normal })
+            .collect();
+        Ok(Self {
+            label,
+            vertices,
+            indices,
+            tex_coords,
+            material,
+            bounds,
+        })
+    }
+}
+
+/// A mesh drawn somewhere in the model.
+pub struct ModelPart {
+    pub name: String,
+    /// Index into [`Model::meshes`].
+    pub mesh: usize,
+    /// Relative to the model.
+    pub transform: Mat4,
+}
+
+pub struct ModelMaterial {
+    pub desc: MaterialDesc,
+    /// Multiplied with the base color.
+    pub diffuse_map: Option<PathBuf>,
+}
+impl From<MaterialDesc> for ModelMaterial {
+    fn from(desc: MaterialDesc) -> Self {
+        Self {
+            desc,
+            diffuse_map: None,
+        }
+    }
+}
+
+pub struct Model {
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<ModelMaterial>,
+    pub parts: Vec<ModelPart>,
+}
+impl Model {
+    pub fn load(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            "gltf" | "glb" => gltf::load(path),
+            "obj" => obj::load(path),
+            _ => anyhow::bail!("{} is not a glTF or OBJ model", path.display()),
+        }
+    }
+}
+
+/// Area weighted normals of the triangles around each vertex, for meshes
+/// that come without any.
+pub(super) fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(positions[triangle[i] as usize]));
+        let normal = (b - a).cross(c - a);
+        for &index in triangle {
+            normals[index as usize] += normal;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.normalize_or(Vec3::Y).to_array())
+        .collect()
+}
+
+// =============================== SPAWNING ===============================
+/// Spawns the model at `path` as children of the entity, relative to
+/// [`MODEL_DIR`] unless absolute.
+#[derive(Component, Clone, Debug)]
+pub struct ModelSource {
+    pub path: PathBuf,
+}
+
+/// Spawned for a [`ModelSource`], not saved with the scene.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ModelPartEntity;
+
+struct LoadedModel {
+    model: Model,
+    meshes: Vec<MeshId>,
+    /// The diffuse map of every material, registered with the [`AssetServer`].
+    textures: Vec<Option<TextureHandle>>,
+}
+
+/// A bind group of a material in the [`MaterialTable`] that a model appended.
+struct MaterialBinding {
+    /// `None` binds white.
+    texture: Option<TextureHandle>,
+    /// [`TextureAsset::version`](crate::assets::TextureAsset::version) the bind group was made with.
+    version: u32,
+    /// The resident mip debug tint and the bind group, once they were made.
+    gpu: Option<(wgpu::Buffer, DiffuseBindGroup)>,
+}
+
+/// Models loaded so far, so a scene reload doesn't register their meshes
+/// again. Loads that failed are tried again the next time.
+#[derive(Resource, Default)]
+pub struct ModelCache {
+    models: HashMap<PathBuf, LoadedModel>,
+    bindings: HashMap<MaterialId, MaterialBinding>,
+    /// Bound for materials without a diffuse map.
+    white: Option<Texture>,
+}
+impl ModelCache {
+    /// Made by [`model_material_system`], `None` for materials that no
+    /// model appended.
+    pub fn bind_group(&self, material: MaterialId) -> Option<&wgpu::BindGroup> {
+        let (_, bind_group) = self.bindings.get(&material)?.gpu.as_ref()?;
+        Some(&bind_group.bind_group)
+    }
+}
+
+pub fn model_spawn_system(
+    mut commands: Commands,
+    mut cache: ResMut<ModelCache>,
+    mut meshes: ResMut<Meshes>,
+    mut assets: ResMut<AssetServer>,
+    mut table: ResMut<MaterialTable>,
+    mut colliders: Option<ResMut<MeshColliders>>,
+    sources: Query<(Entity, &ModelSource), Added<ModelSource>>,
+) {
+    for (entity, source) in sources.iter() {
+        let path = Path::new(MODEL_DIR).join(&source.path);
+        if !cache.models.contains_key(&path) {
+            let model = match Model::load(&path) {
+                Ok(model) => model,
+                Err(e) => {
+                    warn!("Failed to load a model: {:?}", e);
+                    continue;
+                }
+            };
+            let ids = model
+                .meshes
+                .iter()
+                .map(|mesh| {
+                    let id = meshes.add(
+                        &mut assets,
+                        &mesh.label,
+                        mesh.vertices.clone(),
+                        Some(mesh.indices.clone()),
+                    );
+                    if let Some(colliders) = colliders.as_mut() {
+                        let triangles = unweld(&mesh.vertices, &mesh.indices);
+                        colliders.insert(id, MeshColliders::from_vertices(&triangles));
+                    }
+                    id
+                })
+                .collect();
+            let textures = model
+                .materials
+                .iter()
+                .map(|material| {
+                    let path = material.diffuse_map.as_ref()?;
+                    let label = path.file_name().unwrap_or_default().to_string_lossy();
+                    Some(assets.add_texture(&label, TextureSource::File(path.clone())))
+                })
+                .collect();
+            info!(
+                "Loaded {} with {} meshes in {} parts",
+                path.display(),
+                model.meshes.len(),
+                model.parts.len()
+            );
+            cache.models.insert(
+                path.clone(),
+                LoadedModel {
+                    model,
+                    meshes: ids,
+                    textures,
+                },
+            );
+        }
+        let ModelCache {
+            models, bindings, ..
+        } = &mut *cache;
+        let loaded = &models[&path];
+
+        // Every instance gets its own copy of the materials, so editing one
+        // doesn't change the others
+        let first_material = table.materials.len();
+        table
+            .materials
+            .extend(loaded.model.materials.iter().map(|material| material.desc));
+        for (index, &texture) in loaded.textures.iter().enumerate() {
+            bindings.insert(
+                MaterialId((first_material + index) as u32),
+                MaterialBinding {
+                    texture,
+                    version: 0,
+                    gpu: None,
+                },
+            );
+        }
+        for part in &loaded.model.parts {
+            let mesh = &loaded.model.meshes[part.mesh];
+            let (scale, rotation, translation) = part.transform.to_scale_rotation_translation();
+            commands.spawn((
+                SceneEntity,
+                ModelPartEntity,
+                Name::new(part.name.clone()),
+                Transform::from_translation(translation)
+                    .with_rotation(rotation.normalize())
+                    .with_scale(scale),
+                GlobalTransform::default(),
+                Parent(entity),
+                mesh.bounds,
+                Visibility::default(),
+                Renderable {
+                    material: MaterialId((first_material + mesh.material) as u32),
+                    mesh: loaded.meshes[part.mesh],
+                },
+            ));
+        }
+    }
+}
+
+/// Keeps the diffuse maps of model materials resident and builds their bind
+/// groups, again when a texture was loaded again, a mip streamed in or the
+/// filtering quality changed.
+pub fn model_material_system(
+    gpu: Res<GpuContext>,
+    (settings, asset_settings): (Res<SamplerSettings>, Res<AssetSettings>),
+    mut samplers: ResMut<SamplerCache>,
+    mut assets: ResMut<AssetServer>,
+    layout: Res<DiffuseBindGroupLayout>,
+    table: Res<MaterialTable>,
+    mut cache: ResMut<ModelCache>,
+) {
+    let ModelCache {
+        bindings, white, ..
+    } = &mut *cache;
+    // A loaded scene replaces the table, and the models append theirs again
+    let appended = table.scene_materials..table.materials.len();
+    bindings.retain(|id, _| appended.contains(&(id.0 as usize)));
+    if bindings.is_empty() {
+        return;
+    }
+    if white.is_none() {
+        let pixel = image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4]));
+        match Texture::from_image(
+            &gpu.device,
+            &gpu.queue,
+            &image::DynamicImage::ImageRgba8(pixel),
+            Some("model_white"),
+        ) {
+            Ok(texture) => *white = Some(texture),
+            Err(e) => {
+                error!("Failed to create the white model texture: {:?}", e);
+                return;
+            }
+        }
+    }
+    let Some(white) = white.as_ref() else {
+        return;
+    };
+
+    let key = SamplerKey::material(&settings, wgpu::AddressMode::Repeat);
+    let sampler = samplers.get(&gpu.device, key);
+    for binding in bindings.values_mut() {
+        let (version, tint) = match binding.texture {
+            Some(handle) => match assets.use_texture(&gpu, handle) {
+                Ok(asset) => (asset.version, mip_tint(&asset_settings, asset)),
+                Err(e) => {
+                    error!("Failed to load a model texture: {:?}", e);
+                    continue;
+                }
+            },
+            None => (0, [1.0; 4]),
+        };
+        if let Some((tint_buffer, _)) = &binding.gpu {
+            if version != binding.version || asset_settings.is_changed() {
+                gpu.queue
+                    .write_buffer(tint_buffer, 0, bytemuck::cast_slice(&tint));
+            }
+            if version == binding.version && !settings.is_changed() {
+                continue;
+            }
+        }
+        let tint_buffer = match binding.gpu.take() {
+            Some((tint_buffer, _)) => tint_buffer,
+            None => gpu
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("model_material_tint"),
+                    contents: bytemuck::cast_slice(&tint),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                }),
+        };
+        let texture = match binding.texture {
+            Some(handle) => assets.texture(handle).map_err(anyhow::Error::from),
+            None => Ok(white),
+        };
+        let created = texture.and_then(|texture| {
+            DiffuseBindGroup::new(&gpu, &layout, texture, sampler, &tint_buffer)
+        });
+        match created {
+            Ok(bind_group) => {
+                binding.version = version;
+                binding.gpu = Some((tint_buffer, bind_group));
+            }
+            Err(e) => error!("Failed to create a model material bind group: {}", e),
+        }
+    }
+}
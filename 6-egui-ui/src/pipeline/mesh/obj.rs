@@ -0,0 +1,433 @@
+//! Wavefront OBJ models with their MTL material libraries. Every object or
+//! group becomes a part, split further wherever `usemtl` switches the
+//! material, and polygons are triangulated as fans. Smoothing groups, lines
+//! and points are skipped, and faces without normals get smooth ones. Texture
+//! coordinates are flipped to start at the top like wgpu's.
+//!
+//! From the MTL side `Kd`, `d` or `Tr`, `Ns`, `Ks`, `illum` and `map_Kd` are
+//! read, the texture relative to the MTL file. Only materials with `illum` 3
+//! and up reflect, with `Ks` as their reflectivity, like metals in glTF.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use glam::{Mat4, Vec3, Vec4};
+use tracing::warn;
+
+use crate::scene::MaterialDesc;
+
+use super::model::{Model, ModelMaterial, ModelMesh, ModelPart};
+
+/// Reads the `.obj` at `path`, with its material libraries next to it.
+pub fn load(path: &Path) -> Result<Model> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    parse(&source, |library| {
+        let path = dir.join(library);
+        let source = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        parse_mtl(&source, path.parent().unwrap_or(dir))
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    })
+    .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+// =============================== OBJ ===============================
+/// Position, texture coordinate and normal index of a triangle corner.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// Faces of one object or group drawn with one material.
+struct Group {
+    name: String,
+    material: Option<usize>,
+    corners: Vec<Corner>,
+}
+
+/// Everything a face can refer to.
+#[derive(Default)]
+struct Attributes {
+    positions: Vec<[f32; 3]>,
+    tex_coords: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+}
+
+/// `read_library` resolves `mtllib` names to the materials they define.
+fn parse(
+    source: &str,
+    mut read_library: impl FnMut(&str) -> Result<Vec<(String, ModelMaterial)>>,
+) -> Result<Model> {
+    let mut attributes = Attributes::default();
+    let mut materials = Vec::new();
+    let mut material_names = HashMap::new();
+    let mut groups = vec![Group {
+        name: "Part".to_string(),
+        material: None,
+        corners: Vec::new(),
+    }];
+
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let rest: Vec<&str> = tokens.collect();
+        let context = || format!("Line {}: '{}'", number + 1, line.trim());
+        match keyword {
+            "v" => attributes
+                .positions
+                .push(parse_vec3(&rest).with_context(context)?),
+            "vt" => attributes
+                .tex_coords
+                .push(parse_tex_coord(&rest).with_context(context)?),
+            "vn" => attributes
+                .normals
+                .push(parse_vec3(&rest).with_context(context)?),
+            "f" => {
+                if rest.len() < 3 {
+                    anyhow::bail!("{}: a face needs at least 3 corners", context());
+                }
+                let corners = rest
+                    .iter()
+                    .map(|corner| parse_corner(corner, &attributes))
+                    .collect::<Result<Vec<_>>>()
+                    .with_context(context)?;
+                let group = groups.last_mut().expect("there is always a group");
+                for i in 1..corners.len() - 1 {
+                    group
+                        .corners
+                        .extend([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "o" | "g" => {
+                let name = if rest.is_empty() {
+                    "Part".to_string()
+                } else {
+                    rest.join(" ")
+                };
+                let material = groups.last().and_then(|group| group.material);
+                start_group(&mut groups, name, material);
+            }
+            "usemtl" => {
+                let name = rest.join(" ");
+                let material = material_names.get(&name).copied();
+                if material.is_none() {
+                    warn!("Material '{}' isn't defined, using white", name);
+                }
+                let group_name = groups.last().map(|group| group.name.clone());
+                start_group(&mut groups, group_name.unwrap_or_default(), material);
+            }
+            "mtllib" => {
+                for library in &rest {
+                    match read_library(library) {
+                        Ok(library) => {
+                            for (name, material) in library {
+                                material_names.insert(name, materials.len());
+                                materials.push(material);
+                            }
+                        }
+                        Err(e) => warn!("Failed to load a material library: {:?}", e),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Faces without a material share a white one at the end
+    let mut default_material = None;
+    let mut meshes = Vec::new();
+    let mut parts = Vec::new();
+    for group in groups {
+        if group.corners.is_empty() {
+            continue;
+        }
+        let material = match group.material {
+            Some(material) => material,
+            None => *default_material.get_or_insert_with(|| {
+                materials.push(MaterialDesc::opaque(Vec4::ONE).into());
+                materials.len() - 1
+            }),
+        };
+        parts.push(ModelPart {
+            name: group.name.clone(),
+            mesh: meshes.len(),
+            transform: Mat4::IDENTITY,
+        });
+        meshes.push(build_mesh(group, &attributes, material)?);
+    }
+
+    Ok(Model {
+        meshes,
+        materials,
+        parts,
+    })
+}
+
+/// Continues in a new group, or in the last one if nothing was drawn in it.
+fn start_group(groups: &mut Vec<Group>, name: String, material: Option<usize>) {
+    match groups.last_mut() {
+        Some(group) if group.corners.is_empty() => {
+            group.name = name;
+            group.material = material;
+        }
+        _ => groups.push(Group {
+            name,
+            material,
+            corners: Vec::new(),
+        }),
+    }
+}
+
+fn build_mesh(group: Group, attributes: &Attributes, material: usize) -> Result<ModelMesh> {
+    // Corners sharing all their attributes share a vertex
+    let mut vertices = HashMap::new();
+    let mut positions = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut normals = Vec::new();
+    let indices = group
+        .corners
+        .iter()
+        .map(|&corner @ (position, tex_coord, normal)| {
+            *vertices.entry(corner).or_insert_with(|| {
+                positions.push(attributes.positions[position]);
+                tex_coords.push(tex_coord.map(|tex_coord| attributes.tex_coords[tex_coord]));
+                normals.push(normal.map(|normal| attributes.normals[normal]));
+                positions.len() as u32 - 1
+            })
+        })
+        .collect();
+    // Any corner without a normal and the whole group is smoothed, any
+    // without a texture coordinate and the group has none
+    let normals = normals.into_iter().collect::<Option<Vec<_>>>();
+    let tex_coords = tex_coords.into_iter().collect::<Option<Vec<_>>>();
+    ModelMesh::new(
+        group.name, positions, normals, tex_coords, indices, material,
+    )
+}
+
+fn parse_vec3(values: &[&str]) -> Result<[f32; 3]> {
+    match values {
+        [x, y, z, ..] => Ok([x.parse()?, y.parse()?, z.parse()?]),
+        _ => anyhow::bail!("Expected 3 values"),
+    }
+}
+
+/// `u v`, with an optional `w` that is ignored, flipped so `v` goes down.
+fn parse_tex_coord(values: &[&str]) -> Result<[f32; 2]> {
+    match values {
+        [u, v, ..] => Ok([u.parse()?, 1.0 - v.parse::<f32>()?]),
+        [u] => Ok([u.parse()?, 1.0]),
+        _ => anyhow::bail!("Expected 2 values"),
+    }
+}
+
+/// A `v`, `v/vt`, `v//vn` or `v/vt/vn` face corner, as 0 based indices.
+fn parse_corner(corner: &str, attributes: &Attributes) -> Result<Corner> {
+    let mut indices = corner.split('/');
+    let position = resolve_index(
+        indices.next().unwrap_or_default(),
+        attributes.positions.len(),
+    )?;
+    let mut optional = |count| match indices.next() {
+        Some(index) if !index.is_empty() => resolve_index(index, count).map(Some),
+        _ => Ok(None),
+    };
+    let tex_coord = optional(attributes.tex_coords.len())?;
+    let normal = optional(attributes.normals.len())?;
+    Ok((position, tex_coord, normal))
+}
+
+/// 1 based, or counting back from the last one defined when negative.
+fn resolve_index(index: &str, count: usize) -> Result<usize> {
+    let index: i64 = index
+        .parse()
+        .with_context(|| format!("Bad index '{}'", index))?;
+    let resolved = match index {
+        1.. => index - 1,
+        ..=-1 => count as i64 + index,
+        0 => anyhow::bail!("Indices start at 1"),
+    };
+    if !(0..count as i64).contains(&resolved) {
+        anyhow::bail!("Index {} is past the {} defined so far", index, count);
+    }
+    Ok(resolved as usize)
+}
+
+// =============================== MTL ===============================
+struct MtlMaterial {
+    diffuse: Vec3,
+    alpha: f32,
+    specular: Vec3,
+    shininess: Option<f32>,
+    illum: u32,
+    diffuse_map: Option<PathBuf>,
+}
+impl MtlMaterial {
+    fn material(self) -> ModelMaterial {
+        ModelMaterial {
+            desc: self.desc(),
+            diffuse_map: self.diffuse_map,
+        }
+    }
+
+    fn desc(&self) -> MaterialDesc {
+        let base_color = self.diffuse.extend(self.alpha);
+        let desc = if self.alpha < 1.0 {
+            MaterialDesc::transparent(base_color)
+        } else {
+            MaterialDesc::opaque(base_color)
+        };
+        // The usual mapping of a Phong exponent to a microfacet roughness
+        let roughness = self
+            .shininess
+            .map_or(desc.roughness, |shininess| (2.0 / (shininess + 2.0)).sqrt());
+        let reflectivity = if self.illum >= 3 {
+            self.specular.max_element()
+        } else {
+            0.0
+        };
+        desc.with_reflections(roughness, reflectivity)
+    }
+}
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            diffuse: Vec3::ONE,
+            alpha: 1.0,
+            specular: Vec3::ZERO,
+            shininess: None,
+            illum: 2,
+            diffuse_map: None,
+        }
+    }
+}
+
+/// Texture names are relative to `dir`.
+fn parse_mtl(source: &str, dir: &Path) -> Result<Vec<(String, ModelMaterial)>> {
+    let mut materials: Vec<(String, MtlMaterial)> = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let rest: Vec<&str> = tokens.collect();
+        let context = || format!("Line {}: '{}'", number + 1, line.trim());
+        if keyword == "newmtl" {
+            materials.push((rest.join(" "), MtlMaterial::default()));
+            continue;
+        }
+        let Some((_, material)) = materials.last_mut() else {
+            continue;
+        };
+        match keyword {
+            "Kd" => material.diffuse = Vec3::from(parse_vec3(&rest).with_context(context)?),
+            "Ks" => material.specular = Vec3::from(parse_vec3(&rest).with_context(context)?),
+            "d" => material.alpha = parse_scalar(&rest).with_context(context)?,
+            "Tr" => material.alpha = 1.0 - parse_scalar(&rest).with_context(context)?,
+            "Ns" => material.shininess = Some(parse_scalar(&rest).with_context(context)?),
+            "illum" => material.illum = parse_scalar(&rest).with_context(context)? as u32,
+            // Options like `-s` come first, the file name last
+            "map_Kd" => {
+                let name = rest
+                    .last()
+                    .with_context(|| format!("{}: expected a file", context()))?;
+                material.diffuse_map = Some(dir.join(name));
+            }
+            _ => {}
+        }
+    }
+    Ok(materials
+        .into_iter()
+        .map(|(name, material)| (name, material.material()))
+        .collect())
+}
+
+fn parse_scalar(values: &[&str]) -> Result<f32> {
+    Ok(values.first().context("Expected a value")?.parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::model::MODEL_DIR, *};
+    use crate::scene::BlendMode;
+
+    #[test]
+    fn objects_split_by_material_with_their_mtl() {
+        let source = "
+            mtllib glass.mtl
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vn 0 0 1
+            o Window
+            usemtl Glass
+            f 1//1 2//1 3//1 4//1 # a quad, fanned into two triangles
+            usemtl Frame
+            f -4 -3 -2
+        ";
+        let model = parse(source, |library| {
+            assert_eq!(library, "glass.mtl");
+            parse_mtl(
+                "newmtl Glass\nKd 0.5 0.8 1.0\nd 0.25\nNs 48\n",
+                Path::new(""),
+            )
+        })
+        .unwrap();
+
+        // The glass and, for the undefined frame material, white
+        assert_eq!(model.materials.len(), 2);
+        let glass = model.materials[0].desc;
+        assert_eq!(glass.base_color, Vec4::new(0.5, 0.8, 1.0, 0.25));
+        assert_eq!(glass.blend, BlendMode::Transparent);
+        assert!((glass.roughness - 0.2).abs() < 1e-6);
+        assert_eq!(model.materials[1].desc.base_color, Vec4::ONE);
+
+        assert_eq!(model.parts.len(), 2);
+        assert!(model.parts.iter().all(|part| part.name == "Window"));
+        let window = &model.meshes[0];
+        assert_eq!(window.vertices.len(), 4);
+        assert_eq!(window.indices, vec![0, 1, 2, 0, 2, 3]);
+        // Relative indices, and normals filled in when missing
+        let frame = &model.meshes[1];
+        assert_eq!(frame.material, 1);
+        assert_eq!(frame.vertices[0].position, [0.0, 0.0, 0.0]);
+        assert_eq!(frame.vertices[2].normal, [0.0, 0.0, 1.0]);
+
+        assert!(parse("v 0 0 0\nf 1 2 3\n", |_| Ok(Vec::new())).is_err());
+
+        let table = load(&Path::new(MODEL_DIR).join("models/table.obj")).unwrap();
+        assert_eq!(table.parts.len(), 2);
+        assert!(table.materials[1].desc.reflectivity > 0.8);
+    }
+
+    #[test]
+    fn diffuse_maps_load_relative_to_their_mtl() {
+        let model = load(&Path::new(MODEL_DIR).join("models/crate.obj")).unwrap();
+        let map = model.materials[0].diffuse_map.as_ref().unwrap();
+        assert_eq!(map, &Path::new(MODEL_DIR).join("models/../stone.png"));
+        assert!(image::open(map).is_ok());
+
+        // Every face has its own corners, with the texture upside down
+        let crate_mesh = &model.meshes[0];
+        assert_eq!(crate_mesh.vertices.len(), 24);
+        let tex_coords = crate_mesh.tex_coords.as_ref().unwrap();
+        assert_eq!(
+            tex_coords[..4],
+            [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]
+        );
+
+        // Without `vt` on every corner the mesh has none
+        let model = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf 1/1 2 3\n", |_| {
+            Ok(Vec::new())
+        })
+        .unwrap();
+        assert!(model.meshes[0].tex_coords.is_none());
+    }
+}
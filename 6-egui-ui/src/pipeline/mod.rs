@@ -1,12 +1,58 @@
-use std::num::NonZero;
+use std::{num::NonZero, time::Instant};
 
 use wgpu::PrimitiveState;
 
+use crate::shader_log::{self, EntryKind};
+
+pub mod annotations;
+pub mod ao;
+pub mod arena;
+pub mod binding_benchmark;
+pub mod cascades;
+pub mod checkerboard;
+pub mod compute;
+pub mod counters;
+pub mod debug_draw;
+pub mod debug_view;
 pub mod depth;
+pub mod depth_precision;
 pub mod diffuse;
+pub mod dof;
+pub mod environment;
+pub mod filtering;
+pub mod geometry_debug;
+pub mod god_rays;
+pub mod grading;
+pub mod graph;
+pub mod histogram;
+pub mod hud;
+pub mod inspector;
+pub mod layers;
+pub mod life;
+pub mod loading;
+pub mod marching_cubes;
+pub mod mesh;
+pub mod particles;
+pub mod paths;
+pub mod ping_pong;
+pub mod plot;
+pub mod post;
 pub mod present;
+pub mod procedural;
+pub mod quality;
+pub mod reduction;
 pub mod render;
+pub mod scaled;
+pub mod shadow;
+pub mod ssr;
+pub mod stereo;
+pub mod subgroups;
+pub mod text;
 pub mod ui;
+pub mod validation;
+pub mod velocity;
+pub mod visibility;
+pub mod volume;
 
 pub struct GPUPipeline {
     pub render_pipeline: wgpu::RenderPipeline,
@@ -25,6 +71,12 @@ impl GPUPipeline {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineError {
+    #[error("Vertex shader is required")]
+    MissingVertexShader,
+}
+
 // Define the GPUPipelineBuilder struct
 pub struct GPUPipelineBuilder<'a> {
     device: &'a wgpu::Device,
@@ -38,6 +90,8 @@ pub struct GPUPipelineBuilder<'a> {
     depth_stencil_state: Option<wgpu::DepthStencilState>,
     multisample_state: Option<wgpu::MultisampleState>,
     multiview: Option<NonZero<u32>>,
+    cache: Option<&'a wgpu::PipelineCache>,
+    push_constant_ranges: Vec<wgpu::PushConstantRange>,
 }
 
 impl<'a> GPUPipelineBuilder<'a> {
@@ -54,6 +108,8 @@ impl<'a> GPUPipelineBuilder<'a> {
             depth_stencil_state: None,
             multisample_state: None,
             multiview: None,
+            cache: None,
+            push_constant_ranges: vec![],
         }
     }
 
@@ -61,6 +117,11 @@ impl<'a> GPUPipelineBuilder<'a> {
         self.label = Some(label);
         self
     }
+    /// Driver pipeline cache to compile against; `None` compiles without one.
+    pub fn pipeline_cache(mut self, cache: Option<&'a wgpu::PipelineCache>) -> Self {
+        self.cache = cache;
+        self
+    }
     pub fn bind_group_layout(mut self, layout: &'a wgpu::BindGroupLayout) -> Self {
         self.bind_group_layouts.push(layout);
         self
@@ -97,6 +158,11 @@ impl<'a> GPUPipelineBuilder<'a> {
         self.multiview = Some(multiview);
         self
     }
+    /// Needs [`wgpu::Features::PUSH_CONSTANTS`].
+    pub fn push_constant_range(mut self, range: wgpu::PushConstantRange) -> Self {
+        self.push_constant_ranges.push(range);
+        self
+    }
 
     // Utilities
     pub fn default_color_target(mut self, format: wgpu::TextureFormat) -> Self {
@@ -148,18 +214,18 @@ impl<'a> GPUPipelineBuilder<'a> {
         self
     }
 
-    pub fn build(self) -> Result<GPUPipeline, &'static str> {
-        if self.vertex_shader.is_none() {
-            return Err("Vertex shader is required");
-        }
-        let vertex_shader = self.vertex_shader.expect("Vertex shader is required");
+    pub fn build(self) -> Result<GPUPipeline, PipelineError> {
+        let vertex_shader = self
+            .vertex_shader
+            .ok_or(PipelineError::MissingVertexShader)?;
+        let started = Instant::now();
 
         let layout = self
             .device
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: self.label,
                 bind_group_layouts: &self.bind_group_layouts,
-                push_constant_ranges: &[],
+                push_constant_ranges: &self.push_constant_ranges,
             });
 
         let vertex_state = wgpu::VertexState {
@@ -193,9 +259,15 @@ impl<'a> GPUPipelineBuilder<'a> {
                     .multisample_state
                     .unwrap_or(wgpu::MultisampleState::default()),
                 multiview: self.multiview,
-                cache: None,
+                cache: self.cache,
             });
 
+        shader_log::record(
+            EntryKind::RenderPipeline,
+            self.label.unwrap_or("unlabeled"),
+            started,
+            None,
+        );
         Ok(GPUPipeline::new(layout, render_pipeline))
     }
 }
@@ -0,0 +1,491 @@
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::Vec3;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pass::RenderPassBuilder,
+    rng::Rng,
+    scene::{camera_aspect_system, Camera},
+    shader::load_shader_source,
+    time::TimeContext,
+};
+
+use super::{
+    compute::{DispatchSite, GPUComputePipeline},
+    counters::{CounterSlot, GpuCounters},
+    depth::DepthTexture,
+    graph::PassContext,
+    present::FrameBuffer,
+    render::render_system,
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+const PARTICLE_COUNT: u32 = 16384;
+const EMITTER_POSITION: Vec3 = Vec3::new(0.0, 10.0, 0.0);
+/// Longer frames are simulated as this long so particles cannot tunnel.
+const MAX_DELTA: f32 = 1.0 / 30.0;
+
+pub fn setup_particles(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let counter_slot = world
+        .get_resource_mut::<GpuCounters>()
+        .ok_or_else(|| anyhow::anyhow!("GpuCounters resource not found"))?
+        .register("Particles", &["Respawned", "Collisions"])?;
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let depth = world
+        .get_resource::<DepthTexture>()
+        .ok_or_else(|| anyhow::anyhow!("DepthTexture resource not found"))?;
+    let counters = world.resource::<GpuCounters>();
+
+    let buffers = ParticleBuffers::new(gpu, PARTICLE_COUNT, counter_slot);
+    let bind_group_layouts = ParticleBindGroupLayouts::new(gpu);
+    let bind_groups = ParticleBindGroups::new(gpu, &bind_group_layouts, &buffers, depth, counters);
+    let pipelines = ParticlePipelines::new(gpu, &bind_group_layouts, &buffers)?;
+
+    world.insert_resource(buffers);
+    world.insert_resource(bind_group_layouts);
+    world.insert_resource(bind_groups);
+    world.insert_resource(pipelines);
+    world.insert_resource(ParticleSettings::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(particles_panel);
+
+    schedule.add_systems((
+        particle_bind_group_system.run_if(resource_changed::<DepthTexture>),
+        particle_uniform_system
+            .after(camera_aspect_system)
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+pub fn particle_bind_group_system(
+    gpu: Res<GpuContext>,
+    layouts: Res<ParticleBindGroupLayouts>,
+    buffers: Res<ParticleBuffers>,
+    depth: Res<DepthTexture>,
+    counters: Res<GpuCounters>,
+    mut bind_groups: ResMut<ParticleBindGroups>,
+) {
+    *bind_groups = ParticleBindGroups::new(&gpu, &layouts, &buffers, &depth, &counters);
+}
+
+pub fn particle_uniform_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    time: Res<TimeContext>,
+    settings: Res<ParticleSettings>,
+    buffers: Res<ParticleBuffers>,
+    mut rng: ResMut<Rng>,
+) {
+    let (view, projection) = (camera.view(), camera.projection());
+    let uniform = ParticleUniform {
+        view: view.to_cols_array_2d(),
+        proj: projection.to_cols_array_2d(),
+        inv_proj: projection.inverse().to_cols_array_2d(),
+        inv_view: view.inverse().to_cols_array_2d(),
+        emitter: EMITTER_POSITION.extend(settings.spread).to_array(),
+        motion: [
+            if settings.paused {
+                0.0
+            } else {
+                time.delta.min(MAX_DELTA)
+            },
+            time.total,
+            settings.gravity,
+            settings.restitution,
+        ],
+        shape: [
+            settings.lifetime,
+            settings.speed,
+            settings.size,
+            settings.thickness,
+        ],
+        count: buffers.count,
+        collide: settings.collide as u32,
+        seed: rng.next_u32(),
+        _padding: 0,
+    };
+    gpu.queue
+        .write_buffer(&buffers.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+}
+
+/// Integrates the particles and bounces them off the depth buffer written by
+/// the mesh pass, so it has to run after it.
+pub fn particle_simulate_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    if !world.resource::<ParticleSettings>().enabled {
+        return Ok(());
+    }
+    let bind_groups = world.resource::<ParticleBindGroups>();
+    let pipelines = world.resource::<ParticlePipelines>();
+
+    let mut compute_pass = ctx
+        .encoder
+        .begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(ctx.label),
+            timestamp_writes: None,
+        });
+    compute_pass.set_pipeline(&pipelines.simulate.pipeline);
+    compute_pass.set_bind_group(0, &bind_groups.simulate, &[]);
+    let [x, y, z] = pipelines.workgroup_counts;
+    compute_pass.dispatch_workgroups(x, y, z);
+
+    Ok(())
+}
+
+/// Additive billboards, depth tested against the scene without writing depth.
+pub fn particle_draw_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    if !world.resource::<ParticleSettings>().enabled {
+        return Ok(());
+    }
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let depth = world.resource::<DepthTexture>();
+    let buffers = world.resource::<ParticleBuffers>();
+    let bind_groups = world.resource::<ParticleBindGroups>();
+    let pipelines = world.resource::<ParticlePipelines>();
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&frame_buffer.texture.view)
+        .with_depth(&depth.texture.view, 1.0)
+        .load()
+        .build()?;
+
+    render_pass.set_pipeline(&pipelines.draw.render_pipeline);
+    render_pass.set_bind_group(0, &bind_groups.draw, &[]);
+    render_pass.draw(0..6, 0..buffers.count);
+
+    Ok(())
+}
+
+fn particles_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource_mut::<ParticleSettings>();
+
+    egui::Window::new(tr("Particles"))
+        .id(egui::Id::new("Particles"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut settings.enabled, tr("Enabled"));
+                ui.checkbox(&mut settings.paused, tr("Paused"));
+            });
+            ui.checkbox(&mut settings.collide, tr("Collide with depth buffer"));
+            ui.add_enabled_ui(settings.collide, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.restitution, 0.0..=1.0).text(tr("restitution")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.thickness, 0.05..=5.0).text(tr("thickness")),
+                );
+            });
+            ui.add(egui::Slider::new(&mut settings.gravity, 0.0..=30.0).text(tr("gravity")));
+            ui.add(egui::Slider::new(&mut settings.speed, 0.0..=30.0).text(tr("speed")));
+            ui.add(egui::Slider::new(&mut settings.spread, 0.0..=4.0).text(tr("spread")));
+            ui.add(egui::Slider::new(&mut settings.lifetime, 0.5..=10.0).text(tr("lifetime")));
+            ui.add(egui::Slider::new(&mut settings.size, 0.01..=0.5).text(tr("size")));
+            ui.label(trf!("{} particles", PARTICLE_COUNT));
+        });
+}
+
+// =============================== RESOURCES ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct ParticleSettings {
+    pub enabled: bool,
+    pub paused: bool,
+    pub collide: bool,
+    pub gravity: f32,
+    pub restitution: f32,
+    /// How far behind a depth sample still counts as a hit. Anything deeper is
+    /// treated as passing behind the surface.
+    pub thickness: f32,
+    pub speed: f32,
+    pub spread: f32,
+    pub lifetime: f32,
+    pub size: f32,
+}
+impl Default for ParticleSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            paused: false,
+            collide: true,
+            gravity: 9.81,
+            restitution: 0.5,
+            thickness: 1.0,
+            speed: 12.0,
+            spread: 1.2,
+            lifetime: 4.0,
+            size: 0.08,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Particle {
+    /// xyz position, w age.
+    pub position: [f32; 4],
+    /// xyz velocity, w lifetime.
+    pub velocity: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ParticleUniform {
+    pub view: [[f32; 4]; 4],
+    pub proj: [[f32; 4]; 4],
+    pub inv_proj: [[f32; 4]; 4],
+    pub inv_view: [[f32; 4]; 4],
+    pub emitter: [f32; 4],
+    pub motion: [f32; 4],
+    pub shape: [f32; 4],
+    pub count: u32,
+    pub collide: u32,
+    /// Drawn every frame, respawned particles hash it with their index.
+    pub seed: u32,
+    pub _padding: u32,
+}
+
+#[derive(Resource)]
+pub struct ParticleBuffers {
+    pub particles: wgpu::Buffer,
+    pub uniform_buffer: wgpu::Buffer,
+    pub count: u32,
+    /// Respawns and depth buffer bounces, counted by the simulation.
+    pub counters: CounterSlot,
+}
+impl ParticleBuffers {
+    pub fn new(gpu: &GpuContext, count: u32, counters: CounterSlot) -> Self {
+        // Zeroed particles have no lifetime left and respawn on the first step
+        let particles = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particle_buffer"),
+            size: (count as usize * std::mem::size_of::<Particle>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let uniform_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("particle_uniform_buffer"),
+            size: std::mem::size_of::<ParticleUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            particles,
+            uniform_buffer,
+            count,
+            counters,
+        }
+    }
+
+    pub fn dispatch_site(&self) -> DispatchSite {
+        DispatchSite {
+            label: "particles",
+            domain: [self.count, 1, 1],
+        }
+    }
+}
+
+// =============================== BIND GROUP ===============================
+#[derive(Resource)]
+pub struct ParticleBindGroupLayouts {
+    pub simulate: wgpu::BindGroupLayout,
+    pub draw: wgpu::BindGroupLayout,
+}
+impl ParticleBindGroupLayouts {
+    pub fn new(gpu: &GpuContext) -> Self {
+        let uniform = |visibility| wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let storage = |visibility, read_only| wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let simulate = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    uniform(wgpu::ShaderStages::COMPUTE),
+                    storage(wgpu::ShaderStages::COMPUTE, false),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        ..storage(wgpu::ShaderStages::COMPUTE, false)
+                    },
+                ],
+                label: Some("particle_simulate_bind_group_layout"),
+            });
+        let draw = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    uniform(wgpu::ShaderStages::VERTEX),
+                    storage(wgpu::ShaderStages::VERTEX, true),
+                ],
+                label: Some("particle_draw_bind_group_layout"),
+            });
+
+        Self { simulate, draw }
+    }
+}
+
+#[derive(Resource)]
+pub struct ParticleBindGroups {
+    pub simulate: wgpu::BindGroup,
+    pub draw: wgpu::BindGroup,
+}
+impl ParticleBindGroups {
+    pub fn new(
+        gpu: &GpuContext,
+        layouts: &ParticleBindGroupLayouts,
+        buffers: &ParticleBuffers,
+        depth: &DepthTexture,
+        counters: &GpuCounters,
+    ) -> Self {
+        let simulate = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layouts.simulate,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffers.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.particles.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&depth.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: counters.binding(buffers.counters),
+                },
+            ],
+            label: Some("particle_simulate_bind_group"),
+        });
+        let draw = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layouts.draw,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffers.uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.particles.as_entire_binding(),
+                },
+            ],
+            label: Some("particle_draw_bind_group"),
+        });
+
+        Self { simulate, draw }
+    }
+}
+
+// =============================== PIPELINE ===============================
+#[derive(Resource)]
+pub struct ParticlePipelines {
+    pub simulate: GPUComputePipeline,
+    pub workgroup_counts: [u32; 3],
+    pub draw: GPUPipeline,
+}
+impl ParticlePipelines {
+    pub fn new(
+        gpu: &GpuContext,
+        layouts: &ParticleBindGroupLayouts,
+        buffers: &ParticleBuffers,
+    ) -> Result<Self> {
+        let source =
+            load_shader_source("particles.wgsl", include_str!("../shaders/particles.wgsl"));
+        let simulate = GPUComputePipeline::new(
+            &gpu.device,
+            "particle_simulate_pipeline",
+            &source,
+            "cs_main",
+            &[&layouts.simulate],
+            gpu.pipeline_cache(),
+        )?;
+        let workgroup_counts = buffers
+            .dispatch_site()
+            .validate(simulate.workgroup_size, &gpu.device.limits())?;
+
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("particle_draw_shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/particles_draw.wgsl").into(),
+                ),
+            });
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let draw = GPUPipelineBuilder::new(&gpu.device)
+            .label("particle_draw_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&layouts.draw)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .color_target(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Rgba16Float,
+                blend: Some(wgpu::BlendState {
+                    color: additive,
+                    alpha: additive,
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .depth_stencil_state(Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }))
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState::default())
+            .build()?;
+
+        Ok(Self {
+            simulate,
+            workgroup_counts,
+            draw,
+        })
+    }
+}
@@ -0,0 +1,613 @@
+use std::f32::consts::TAU;
+
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Vec2, Vec4};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    layout::LayoutCache,
+    pass::RenderPassBuilder,
+    shader::load_shader_source,
+    vertex::PathVertex,
+};
+
+use super::{
+    graph::PassContext, present::FrameBuffer, render::render_system, ui::UiPanels, GPUPipeline,
+    GPUPipelineBuilder,
+};
+
+/// Curves are split until the polyline is at most this far off, in pixels.
+const DEFAULT_TOLERANCE: f32 = 0.25;
+/// Upper bound on the segments of a single curve.
+const MAX_CURVE_SEGMENTS: u32 = 256;
+
+pub fn setup_paths(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let renderer = world.resource_scope::<LayoutCache, _>(|world, mut layouts| {
+        let gpu = world
+            .get_resource::<GpuContext>()
+            .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+        PathRenderer::new(gpu, &mut layouts)
+    })?;
+    world.insert_resource(renderer);
+    world.init_resource::<VectorPaths>();
+    world.insert_resource(PathDemoSettings::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(paths_panel);
+
+    schedule.add_systems(path_demo_system.before(render_system));
+
+    Ok(())
+}
+
+/// Queues a few shapes in the top right corner: filled and stroked cubic
+/// and quadratic curves, and a concave polygon.
+pub fn path_demo_system(
+    gpu: Res<GpuContext>,
+    settings: Res<PathDemoSettings>,
+    mut paths: ResMut<VectorPaths>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    paths.tolerance = settings.tolerance;
+    let origin = Vec2::new(gpu.config.width as f32 - 360.0, 40.0);
+    let width = settings.stroke_width;
+    let outline = Vec4::new(0.9, 0.9, 0.95, 1.0);
+
+    // A heart out of four cubics
+    let heart = Path::new()
+        .move_to(origin + Vec2::new(60.0, 40.0))
+        .cubic_to(
+            origin + Vec2::new(60.0, 0.0),
+            origin + Vec2::new(0.0, 0.0),
+            origin + Vec2::new(0.0, 40.0),
+        )
+        .cubic_to(
+            origin + Vec2::new(0.0, 80.0),
+            origin + Vec2::new(60.0, 100.0),
+            origin + Vec2::new(60.0, 120.0),
+        )
+        .cubic_to(
+            origin + Vec2::new(60.0, 100.0),
+            origin + Vec2::new(120.0, 80.0),
+            origin + Vec2::new(120.0, 40.0),
+        )
+        .cubic_to(
+            origin + Vec2::new(120.0, 0.0),
+            origin + Vec2::new(60.0, 0.0),
+            origin + Vec2::new(60.0, 40.0),
+        )
+        .close();
+    paths.fill(&heart, Vec4::new(0.85, 0.1, 0.2, 1.0));
+    paths.stroke(&heart, width, outline);
+
+    // A five pointed star, concave
+    let center = origin + Vec2::new(230.0, 60.0);
+    let mut star = Path::new();
+    for i in 0..10 {
+        let angle = i as f32 / 10.0 * TAU - TAU / 4.0;
+        let radius = if i % 2 == 0 { 60.0 } else { 25.0 };
+        let point = center + Vec2::new(angle.cos(), angle.sin()) * radius;
+        star = if i == 0 {
+            star.move_to(point)
+        } else {
+            star.line_to(point)
+        };
+    }
+    let star = star.close();
+    paths.fill(&star, Vec4::new(1.0, 0.8, 0.2, 1.0));
+    paths.stroke(&star, width, outline);
+
+    // An open wave of quadratics
+    let mut wave = Path::new().move_to(origin + Vec2::new(0.0, 170.0));
+    for i in 0..6 {
+        let x = i as f32 * 55.0;
+        let y = if i % 2 == 0 { 130.0 } else { 210.0 };
+        wave = wave.quad_to(
+            origin + Vec2::new(x + 27.5, y),
+            origin + Vec2::new(x + 55.0, 170.0),
+        );
+    }
+    paths.stroke(&wave, width * 2.0, Vec4::new(0.3, 0.7, 1.0, 1.0));
+}
+
+/// Draws everything queued on [`VectorPaths`] this frame over the scene, in
+/// one draw, then clears the queue.
+pub fn paths_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let (vertices, stats) = {
+        let mut paths = world.resource_mut::<VectorPaths>();
+        (
+            std::mem::take(&mut paths.vertices),
+            std::mem::take(&mut paths.stats),
+        )
+    };
+    world.resource_scope::<PathRenderer, _>(|world, mut renderer| {
+        renderer.stats = stats;
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        let gpu = world.resource::<GpuContext>();
+        renderer.upload(gpu, &vertices);
+
+        let frame_buffer = world.resource::<FrameBuffer>();
+        let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+            .with_label(ctx.label)
+            .with_color_view(&frame_buffer.texture.view)
+            .load()
+            .build()?;
+
+        render_pass.set_pipeline(&renderer.pipeline.render_pipeline);
+        render_pass.set_bind_group(0, &renderer.params_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, renderer.vertices.slice(..));
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+
+        Ok(())
+    })
+}
+
+fn paths_panel(ctx: &egui::Context, world: &mut World) {
+    let stats = world.resource::<PathRenderer>().stats;
+    let mut settings = world.resource::<PathDemoSettings>().clone();
+
+    egui::Window::new(tr("Vector paths"))
+        .id(egui::Id::new("Vector paths"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Show demo paths"));
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.stroke_width, 0.25..=12.0)
+                        .text(tr("Stroke width")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.tolerance, 0.05..=10.0)
+                        .logarithmic(true)
+                        .text(tr("Curve tolerance")),
+                )
+                .on_hover_text(tr(
+                    "Largest distance in pixels between a curve and its segments",
+                ));
+            });
+            ui.label(trf!(
+                "{} segments, {} fill triangles",
+                stats.segments,
+                stats.triangles
+            ));
+        });
+
+    let mut current = world.resource_mut::<PathDemoSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== PATH ===============================
+#[derive(Clone, Copy, Debug)]
+enum PathCommand {
+    MoveTo(Vec2),
+    LineTo(Vec2),
+    QuadTo(Vec2, Vec2),
+    CubicTo(Vec2, Vec2, Vec2),
+    Close,
+}
+
+/// Outline made of lines and quadratic and cubic Bezier curves, in surface
+/// pixels. Every `move_to` starts a new subpath.
+#[derive(Clone, Debug, Default)]
+pub struct Path {
+    commands: Vec<PathCommand>,
+}
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn move_to(mut self, point: Vec2) -> Self {
+        self.commands.push(PathCommand::MoveTo(point));
+        self
+    }
+
+    pub fn line_to(mut self, point: Vec2) -> Self {
+        self.commands.push(PathCommand::LineTo(point));
+        self
+    }
+
+    pub fn quad_to(mut self, control: Vec2, point: Vec2) -> Self {
+        self.commands.push(PathCommand::QuadTo(control, point));
+        self
+    }
+
+    pub fn cubic_to(mut self, first: Vec2, second: Vec2, point: Vec2) -> Self {
+        self.commands
+            .push(PathCommand::CubicTo(first, second, point));
+        self
+    }
+
+    /// Joins the current subpath back to its start.
+    pub fn close(mut self) -> Self {
+        self.commands.push(PathCommand::Close);
+        self
+    }
+
+    /// Subpaths as polylines, with whether each one was closed.
+    pub fn flatten(&self, tolerance: f32) -> Vec<(Vec<Vec2>, bool)> {
+        let mut subpaths = Vec::new();
+        let mut current: Vec<Vec2> = Vec::new();
+        let tolerance = tolerance.max(0.01);
+        for command in &self.commands {
+            let last = current.last().copied().unwrap_or(Vec2::ZERO);
+            match *command {
+                PathCommand::MoveTo(point) => {
+                    if current.len() > 1 {
+                        subpaths.push((std::mem::take(&mut current), false));
+                    }
+                    current.clear();
+                    current.push(point);
+                }
+                PathCommand::LineTo(point) => current.push(point),
+                // Uniform steps stay within tolerance once the deviation of
+                // the control points divided by the squared count is below it
+                PathCommand::QuadTo(control, point) => {
+                    let deviation = (last - 2.0 * control + point).length();
+                    let count = curve_segments(deviation / (8.0 * tolerance));
+                    current.extend((1..=count).map(|i| {
+                        let t = i as f32 / count as f32;
+                        let u = 1.0 - t;
+                        last * u * u + control * 2.0 * u * t + point * t * t
+                    }));
+                }
+                PathCommand::CubicTo(first, second, point) => {
+                    let deviation = (last - 2.0 * first + second)
+                        .length()
+                        .max((first - 2.0 * second + point).length());
+                    let count = curve_segments(deviation * 3.0 / (4.0 * tolerance));
+                    current.extend((1..=count).map(|i| {
+                        let t = i as f32 / count as f32;
+                        let u = 1.0 - t;
+                        last * u * u * u
+                            + first * 3.0 * u * u * t
+                            + second * 3.0 * u * t * t
+                            + point * t * t * t
+                    }));
+                }
+                PathCommand::Close => {
+                    if current.len() > 1 {
+                        let start = current[0];
+                        if current.last() == Some(&start) {
+                            current.pop();
+                        }
+                        subpaths.push((std::mem::take(&mut current), true));
+                        // Drawing on continues from the start, as in SVG
+                        current.push(start);
+                    }
+                }
+            }
+        }
+        if current.len() > 1 {
+            subpaths.push((current, false));
+        }
+        subpaths
+    }
+}
+
+fn curve_segments(squared: f32) -> u32 {
+    (squared.sqrt().ceil() as u32).clamp(1, MAX_CURVE_SEGMENTS)
+}
+
+/// Splits a simple polygon into triangles by clipping ears, as indices into
+/// `points`. Self-intersecting outlines come out partially filled.
+pub fn triangulate(points: &[Vec2]) -> Vec<[usize; 3]> {
+    let signed_area: f32 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum();
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    // Ears are found on a counter-clockwise outline
+    if signed_area < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let (a, b, c) = (
+                points[remaining[(i + count - 1) % count]],
+                points[remaining[i]],
+                points[remaining[(i + 1) % count]],
+            );
+            // Convex corner with no other vertex inside
+            (b - a).perp_dot(c - b) > 0.0
+                && remaining.iter().all(|&j| {
+                    let p = points[j];
+                    p == a || p == b || p == c || !in_triangle(p, a, b, c)
+                })
+        });
+        // Degenerate outlines have no ears left, drop a corner and go on
+        let i = ear.unwrap_or(0);
+        if ear.is_some() {
+            triangles.push([
+                remaining[(i + count - 1) % count],
+                remaining[i],
+                remaining[(i + 1) % count],
+            ]);
+        }
+        remaining.remove(i);
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    (b - a).perp_dot(p - a) >= 0.0
+        && (c - b).perp_dot(p - b) >= 0.0
+        && (a - c).perp_dot(p - c) >= 0.0
+}
+
+// =============================== API ===============================
+#[derive(Clone, Copy, Default)]
+pub struct PathStats {
+    pub segments: usize,
+    pub triangles: usize,
+}
+
+/// Immediate-mode vector drawing. Paths are tessellated when queued and drawn
+/// in order, once, at the end of the current frame.
+#[derive(Resource)]
+pub struct VectorPaths {
+    pub vertices: Vec<PathVertex>,
+    /// See [`DEFAULT_TOLERANCE`].
+    pub tolerance: f32,
+    pub stats: PathStats,
+}
+impl Default for VectorPaths {
+    fn default() -> Self {
+        Self {
+            vertices: Vec::new(),
+            tolerance: DEFAULT_TOLERANCE,
+            stats: PathStats::default(),
+        }
+    }
+}
+impl VectorPaths {
+    /// Round joins and caps, `width` in pixels.
+    pub fn stroke(&mut self, path: &Path, width: f32, color: Vec4) {
+        for (points, closed) in path.flatten(self.tolerance) {
+            self.polyline(&points, closed, width, color);
+        }
+    }
+
+    /// Each subpath is filled on its own, they don't cut holes.
+    pub fn fill(&mut self, path: &Path, color: Vec4) {
+        for (points, _) in path.flatten(self.tolerance) {
+            if points.len() < 3 {
+                continue;
+            }
+            let triangles = triangulate(&points);
+            self.stats.triangles += triangles.len();
+            self.vertices
+                .extend(triangles.iter().flatten().map(|&i| PathVertex {
+                    position: points[i].to_array(),
+                    color: color.to_array(),
+                    segment: [0.0; 4],
+                    half_width: 0.0,
+                }));
+            // A hairline around the edge stands in for anti-aliasing
+            self.polyline(&points, true, 1.0, color);
+        }
+    }
+
+    /// Strokes straight segments between `points`, the cheapest way to draw
+    /// long series of samples.
+    pub fn polyline(&mut self, points: &[Vec2], closed: bool, width: f32, color: Vec4) {
+        if points.len() < 2 {
+            return;
+        }
+        let closing = closed.then(|| (points[points.len() - 1], points[0]));
+        let segments = points
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .chain(closing);
+        for (start, end) in segments {
+            self.segment(start, end, width, color);
+        }
+    }
+
+    fn segment(&mut self, start: Vec2, end: Vec2, width: f32, color: Vec4) {
+        let half_width = width * 0.5;
+        // One pixel past the stroke for the anti-aliased edge
+        let reach = half_width + 1.0;
+        let direction = (end - start).normalize_or(Vec2::X) * reach;
+        let normal = direction.perp();
+        let corners = [
+            start - direction - normal,
+            end + direction - normal,
+            end + direction + normal,
+            start - direction + normal,
+        ];
+        let vertex = |position: Vec2| PathVertex {
+            position: position.to_array(),
+            color: color.to_array(),
+            segment: [start.x, start.y, end.x, end.y],
+            half_width,
+        };
+        self.vertices
+            .extend([0, 1, 2, 0, 2, 3].map(|i| vertex(corners[i])));
+        self.stats.segments += 1;
+    }
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct PathDemoSettings {
+    pub enabled: bool,
+    pub stroke_width: f32,
+    pub tolerance: f32,
+}
+impl Default for PathDemoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stroke_width: 2.0,
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+}
+
+// =============================== RENDERER ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PathParams {
+    pub screen: [f32; 2],
+    pub _padding: [f32; 2],
+}
+
+#[derive(Resource)]
+pub struct PathRenderer {
+    pub pipeline: GPUPipeline,
+    params: wgpu::Buffer,
+    params_bind_group: wgpu::BindGroup,
+    vertices: wgpu::Buffer,
+    capacity: usize,
+    /// Counts of the last drawn frame.
+    pub stats: PathStats,
+}
+impl PathRenderer {
+    pub fn new(gpu: &GpuContext, layouts: &mut LayoutCache) -> Result<Self> {
+        let layout = layouts.get(
+            &gpu.device,
+            &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        );
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("path_params"),
+                contents: bytemuck::bytes_of(&PathParams {
+                    screen: [gpu.config.width as f32, gpu.config.height as f32],
+                    _padding: [0.0; 2],
+                }),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let params_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.as_entire_binding(),
+            }],
+            label: Some("path_params_bind_group"),
+        });
+
+        let source = load_shader_source("paths.wgsl", include_str!("../shaders/paths.wgsl"));
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("path_shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let pipeline = GPUPipelineBuilder::new(&gpu.device)
+            .label("path_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .vertex_buffer_layout(PathVertex::desc())
+            .color_target(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Rgba16Float,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            })
+            .build()?;
+
+        let capacity = 4096;
+        Ok(Self {
+            pipeline,
+            params,
+            params_bind_group,
+            vertices: Self::create_vertices(gpu, capacity),
+            capacity,
+            stats: PathStats::default(),
+        })
+    }
+
+    fn create_vertices(gpu: &GpuContext, capacity: usize) -> wgpu::Buffer {
+        gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("path_vertices"),
+            size: (capacity * std::mem::size_of::<PathVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn upload(&mut self, gpu: &GpuContext, vertices: &[PathVertex]) {
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.vertices = Self::create_vertices(gpu, self.capacity);
+        }
+        gpu.queue
+            .write_buffer(&self.vertices, 0, bytemuck::cast_slice(vertices));
+        gpu.queue.write_buffer(
+            &self.params,
+            0,
+            bytemuck::bytes_of(&PathParams {
+                screen: [gpu.config.width as f32, gpu.config.height as f32],
+                _padding: [0.0; 2],
+            }),
+        );
+    }
+}
+
+// =============================== TESTS ===============================
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concave_polygon_triangulates_to_its_area() {
+        // An L shape, three unit squares, given clockwise
+        let points = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 2.0),
+            Vec2::new(2.0, 2.0),
+            Vec2::new(2.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 0.0),
+        ];
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), points.len() - 2);
+        let area: f32 = triangles
+            .iter()
+            .map(|&[a, b, c]| {
+                (points[b] - points[a])
+                    .perp_dot(points[c] - points[a])
+                    .abs()
+                    * 0.5
+            })
+            .sum();
+        assert!((area - 3.0).abs() < 1e-5);
+    }
+}
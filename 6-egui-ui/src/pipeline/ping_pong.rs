@@ -0,0 +1,192 @@
+//! Two textures taking turns, for passes that build on their own last
+//! result: temporal history, cellular automata, blurs run a few times over.
+//! A pass reads one side and renders to the other, then
+//! [`PingPongTarget::swap`] makes what was just written the side to read.
+//!
+//! The read side is only handed out as a bind group and the write side only
+//! as a render target view, so a pass can't end up sampling the texture it
+//! draws to. wgpu would reject that too, but only once the pass is recorded.
+
+/// A pair of same sized textures, one read and one written at any time.
+pub struct PingPongTarget {
+    label: String,
+    format: wgpu::TextureFormat,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    sides: [Side; 2],
+    /// Index of the side being read.
+    read: usize,
+}
+
+struct Side {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    /// Reads this side, see [`PingPongTarget::read_layout`].
+    bind_group: wgpu::BindGroup,
+}
+
+impl PingPongTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let sample_type = format
+            .sample_type(None, Some(device.features()))
+            .unwrap_or(wgpu::TextureSampleType::Float { filterable: false });
+        let filterable = sample_type == wgpu::TextureSampleType::Float { filterable: true };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(if filterable {
+                        wgpu::SamplerBindingType::Filtering
+                    } else {
+                        wgpu::SamplerBindingType::NonFiltering
+                    }),
+                    count: None,
+                },
+            ],
+            label: Some(&format!("{}_layout", label)),
+        });
+        let filter = if filterable {
+            wgpu::FilterMode::Linear
+        } else {
+            wgpu::FilterMode::Nearest
+        };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&format!("{}_sampler", label)),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+
+        let sides = Self::create_sides(device, label, format, &layout, &sampler, width, height);
+        Self {
+            label: label.to_string(),
+            format,
+            layout,
+            sampler,
+            sides,
+            read: 0,
+        }
+    }
+
+    fn create_sides(
+        device: &wgpu::Device,
+        label: &str,
+        format: wgpu::TextureFormat,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        width: u32,
+        height: u32,
+    ) -> [Side; 2] {
+        ["a", "b"].map(|name| {
+            let label = format!("{}_{}", label, name);
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(&label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                // Copies out for readback and the texture inspector
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&Default::default());
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+                label: Some(&format!("{}_bind_group", label)),
+            });
+            Side {
+                texture,
+                view,
+                bind_group,
+            }
+        })
+    }
+
+    /// What was written becomes what is read.
+    pub fn swap(&mut self) {
+        self.read = 1 - self.read;
+    }
+
+    /// Recreates both sides when the size changed, losing what they held.
+    /// Returns whether it did, e.g. to start a simulation over.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) -> bool {
+        if self.size() == (width, height) {
+            return false;
+        }
+        self.sides = Self::create_sides(
+            device,
+            &self.label,
+            self.format,
+            &self.layout,
+            &self.sampler,
+            width,
+            height,
+        );
+        true
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        let size = self.sides[0].texture.size();
+        (size.width, size.height)
+    }
+
+    /// Layout of [`read_bind_group`](Self::read_bind_group): the texture at
+    /// binding 0, a sampler at binding 1, filtering when the format allows.
+    pub fn read_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn read_bind_group(&self) -> &wgpu::BindGroup {
+        &self.sides[self.read].bind_group
+    }
+
+    /// The read side, for showing or copying the latest result.
+    pub fn read_texture(&self) -> &wgpu::Texture {
+        &self.sides[self.read].texture
+    }
+
+    pub fn read_view(&self) -> &wgpu::TextureView {
+        &self.sides[self.read].view
+    }
+
+    /// Color attachment of the side being written.
+    pub fn write_view(&self) -> &wgpu::TextureView {
+        &self.sides[1 - self.read].view
+    }
+}
@@ -0,0 +1,463 @@
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Vec2, Vec4};
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    rng::Rng,
+    time::{TimeContext, TimeHistory},
+};
+
+use super::{
+    paths::{Path, VectorPaths},
+    render::render_system,
+    text::{text_layout_system, DebugText},
+    ui::UiPanels,
+};
+
+/// Room for the tick labels left of and below the plot area, in pixels.
+const LABEL_MARGIN: Vec2 = Vec2::new(52.0, 20.0);
+const LABEL_SIZE: f32 = 12.0;
+const TITLE_SIZE: f32 = 14.0;
+/// Roughly how many ticks auto-scaled axes aim for.
+const TARGET_TICKS: usize = 5;
+
+pub fn setup_plot(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    world.insert_resource(PlotDemoSettings::default());
+    world.init_resource::<PlotDemoStats>();
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(plot_panel);
+
+    schedule.add_systems(
+        plot_demo_system
+            .before(text_layout_system)
+            .before(render_system),
+    );
+
+    Ok(())
+}
+
+/// Plots the frame times and a made up, animated spectrum along the bottom
+/// left of the surface.
+pub fn plot_demo_system(
+    gpu: Res<GpuContext>,
+    time: Res<TimeContext>,
+    history: Res<TimeHistory>,
+    settings: Res<PlotDemoSettings>,
+    mut stats: ResMut<PlotDemoStats>,
+    mut paths: ResMut<VectorPaths>,
+    mut text: ResMut<DebugText>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    let size = Vec2::new(420.0, 180.0);
+    let bottom = gpu.config.height as f32 - size.y - 16.0;
+
+    let frame_times = Plot::new(Vec2::new(16.0, bottom), size)
+        .title(tr("Frame time (ms)"))
+        .line_width(settings.line_width)
+        .line_series(
+            tr("Frame"),
+            Vec4::new(0.3, 0.9, 0.4, 1.0),
+            history
+                .frame_times
+                .iter()
+                .enumerate()
+                .map(|(i, &seconds)| Vec2::new(i as f32, seconds * 1000.0)),
+        );
+
+    // A few harmonics that drift over time on top of a noise floor
+    let mut rng = Rng::new(time.total.to_bits() as u64);
+    let bins = settings.spectrum_bins;
+    let fundamental = 440.0 + (time.total * 0.5).sin() * 110.0;
+    let spectrum = (0..bins).map(|i| {
+        let frequency = i as f32 / bins as f32 * 22050.0;
+        let noise = rng.next_u32() as f32 / u32::MAX as f32;
+        let peaks: f32 = (1..=6)
+            .map(|harmonic| {
+                let offset = (frequency - fundamental * harmonic as f32) / 40.0;
+                60.0 / harmonic as f32 * (-offset * offset).exp()
+            })
+            .sum();
+        Vec2::new(frequency, -90.0 + noise * 12.0 + peaks)
+    });
+    let spectrum = Plot::new(Vec2::new(16.0 + size.x + 16.0, bottom), size)
+        .title(tr("Spectrum (dB)"))
+        .line_width(settings.line_width)
+        .x_range(0.0, 22050.0)
+        .y_range(-90.0, 0.0)
+        .line_series(tr("Signal"), Vec4::new(0.3, 0.7, 1.0, 1.0), spectrum);
+
+    stats.points = frame_times.point_count() + spectrum.point_count();
+    stats.drawn = frame_times.draw(&mut paths, &mut text) + spectrum.draw(&mut paths, &mut text);
+}
+
+// =============================== API ===============================
+pub struct Series {
+    pub name: String,
+    pub color: Vec4,
+    /// Sorted by `x`.
+    pub points: Vec<Vec2>,
+}
+
+/// A line chart drawn through [`VectorPaths`] and [`DebugText`] instead of
+/// egui, built and drawn again every frame. Axes without a fixed range fit
+/// all series, rounded out to the nearest tick.
+pub struct Plot {
+    min: Vec2,
+    size: Vec2,
+    title: Option<String>,
+    x_range: Option<(f32, f32)>,
+    y_range: Option<(f32, f32)>,
+    line_width: f32,
+    series: Vec<Series>,
+}
+impl Plot {
+    /// `min` is the top left corner in surface pixels, `size` includes the
+    /// labels.
+    pub fn new(min: Vec2, size: Vec2) -> Self {
+        Self {
+            min,
+            size,
+            title: None,
+            x_range: None,
+            y_range: None,
+            line_width: 1.5,
+            series: Vec::new(),
+        }
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn x_range(mut self, min: f32, max: f32) -> Self {
+        self.x_range = Some((min, max));
+        self
+    }
+
+    pub fn y_range(mut self, min: f32, max: f32) -> Self {
+        self.y_range = Some((min, max));
+        self
+    }
+
+    pub fn line_width(mut self, width: f32) -> Self {
+        self.line_width = width;
+        self
+    }
+
+    pub fn line_series(
+        mut self,
+        name: impl Into<String>,
+        color: Vec4,
+        points: impl IntoIterator<Item = Vec2>,
+    ) -> Self {
+        self.series.push(Series {
+            name: name.into(),
+            color,
+            points: points.into_iter().collect(),
+        });
+        self
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.series.iter().map(|series| series.points.len()).sum()
+    }
+
+    /// Queues the plot for this frame, returns how many points were left
+    /// after decimation.
+    pub fn draw(&self, paths: &mut VectorPaths, text: &mut DebugText) -> usize {
+        let header = if self.title.is_some() {
+            TITLE_SIZE + 6.0
+        } else {
+            0.0
+        };
+        let area_min = self.min + Vec2::new(LABEL_MARGIN.x, header);
+        let area_size =
+            (self.size - Vec2::new(LABEL_MARGIN.x, header + LABEL_MARGIN.y)).max(Vec2::ONE);
+        let area_max = area_min + area_size;
+
+        let (x_min, x_max, x_step) = self.axis(self.x_range, |point| point.x);
+        let (y_min, y_max, y_step) = self.axis(self.y_range, |point| point.y);
+        let to_screen = |point: Vec2| {
+            let t = Vec2::new(
+                (point.x - x_min) / (x_max - x_min),
+                (point.y - y_min) / (y_max - y_min),
+            );
+            let screen = area_min + Vec2::new(t.x, 1.0 - t.y) * area_size;
+            screen.clamp(area_min, area_max)
+        };
+
+        let background = Path::new()
+            .move_to(area_min)
+            .line_to(Vec2::new(area_max.x, area_min.y))
+            .line_to(area_max)
+            .line_to(Vec2::new(area_min.x, area_max.y))
+            .close();
+        paths.fill(&background, Vec4::new(0.05, 0.05, 0.07, 0.8));
+
+        let grid = Vec4::new(0.5, 0.5, 0.55, 0.25);
+        let label = Vec4::new(0.75, 0.75, 0.8, 1.0);
+        for x in ticks(x_min, x_max, x_step) {
+            let screen = to_screen(Vec2::new(x, y_min)).x;
+            paths.polyline(
+                &[Vec2::new(screen, area_min.y), Vec2::new(screen, area_max.y)],
+                false,
+                1.0,
+                grid,
+            );
+            text.text(
+                Vec2::new(screen - 8.0, area_max.y + 4.0),
+                LABEL_SIZE,
+                label,
+                format_tick(x, x_step),
+            );
+        }
+        for y in ticks(y_min, y_max, y_step) {
+            let screen = to_screen(Vec2::new(x_min, y)).y;
+            paths.polyline(
+                &[Vec2::new(area_min.x, screen), Vec2::new(area_max.x, screen)],
+                false,
+                1.0,
+                grid,
+            );
+            text.text(
+                Vec2::new(self.min.x, screen - LABEL_SIZE * 0.5),
+                LABEL_SIZE,
+                label,
+                format_tick(y, y_step),
+            );
+        }
+        let axis = Vec4::new(0.8, 0.8, 0.85, 1.0);
+        paths.polyline(
+            &[area_min, Vec2::new(area_min.x, area_max.y), area_max],
+            false,
+            1.0,
+            axis,
+        );
+        if let Some(title) = &self.title {
+            text.text(
+                self.min + Vec2::new(LABEL_MARGIN.x, 0.0),
+                TITLE_SIZE,
+                axis,
+                title.as_str(),
+            );
+        }
+
+        let mut drawn = 0;
+        for (i, series) in self.series.iter().enumerate() {
+            let screen: Vec<_> = series
+                .points
+                .iter()
+                .map(|&point| to_screen(point))
+                .collect();
+            let points = decimate(&screen, area_min.x);
+            drawn += points.len();
+            paths.polyline(&points, false, self.line_width, series.color);
+
+            // Legend in the top right corner of the plot area
+            let entry = Vec2::new(
+                area_max.x - 110.0,
+                area_min.y + 6.0 + i as f32 * (LABEL_SIZE + 4.0),
+            );
+            paths.polyline(
+                &[
+                    entry + Vec2::new(0.0, LABEL_SIZE * 0.5),
+                    entry + Vec2::new(16.0, LABEL_SIZE * 0.5),
+                ],
+                false,
+                2.0,
+                series.color,
+            );
+            text.text(
+                entry + Vec2::new(22.0, 0.0),
+                LABEL_SIZE,
+                label,
+                series.name.as_str(),
+            );
+        }
+        drawn
+    }
+
+    /// Range and tick step of one axis, the fixed range if there is one.
+    fn axis(&self, fixed: Option<(f32, f32)>, value: impl Fn(&Vec2) -> f32) -> (f32, f32, f32) {
+        if let Some((min, max)) = fixed {
+            let (min, max) = widen(min, max);
+            return (min, max, tick_step(min, max, TARGET_TICKS));
+        }
+        let (min, max) = self
+            .series
+            .iter()
+            .flat_map(|series| series.points.iter().map(&value))
+            .filter(|value| value.is_finite())
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+                (min.min(value), max.max(value))
+            });
+        if min > max {
+            return (0.0, 1.0, tick_step(0.0, 1.0, TARGET_TICKS));
+        }
+        let (min, max) = widen(min, max);
+        let step = tick_step(min, max, TARGET_TICKS);
+        (
+            (min / step).floor() * step,
+            (max / step).ceil() * step,
+            step,
+        )
+    }
+}
+
+/// Keeps a range from collapsing when all values are the same.
+fn widen(min: f32, max: f32) -> (f32, f32) {
+    if max - min > f32::EPSILON * max.abs().max(1.0) {
+        (min, max)
+    } else {
+        let pad = (min.abs() * 0.1).max(0.5);
+        (min - pad, max + pad)
+    }
+}
+
+/// The 1, 2 or 5 times a power of ten that splits `min..max` into about
+/// `target` intervals.
+pub fn tick_step(min: f32, max: f32, target: usize) -> f32 {
+    let raw = (max - min) / target.max(1) as f32;
+    let magnitude = 10f32.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|&step| step >= raw)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Multiples of `step` inside `min..=max`.
+fn ticks(min: f32, max: f32, step: f32) -> impl Iterator<Item = f32> {
+    let first = (min / step).ceil() as i64;
+    let last = (max / step + 1e-4).floor() as i64;
+    (first..=last).map(move |i| i as f32 * step)
+}
+
+/// Just enough decimals to tell neighbouring ticks apart.
+fn format_tick(value: f32, step: f32) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+/// Reduces a polyline in screen space to the first, lowest, highest and last
+/// point of every pixel column, so spikes survive even with far more points
+/// than pixels.
+pub fn decimate(points: &[Vec2], left: f32) -> Vec<Vec2> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    while start < points.len() {
+        let column = (points[start].x - left).floor();
+        let end = points[start..]
+            .iter()
+            .position(|point| (point.x - left).floor() != column)
+            .map_or(points.len(), |count| start + count);
+        let (mut low, mut high) = (start, start);
+        for i in start..end {
+            if points[i].y < points[low].y {
+                low = i;
+            }
+            if points[i].y > points[high].y {
+                high = i;
+            }
+        }
+        let mut keep = [start, low, high, end - 1];
+        keep.sort_unstable();
+        let mut previous = None;
+        for i in keep {
+            if previous != Some(i) {
+                out.push(points[i]);
+                previous = Some(i);
+            }
+        }
+        start = end;
+    }
+    out
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct PlotDemoSettings {
+    pub enabled: bool,
+    pub spectrum_bins: u32,
+    pub line_width: f32,
+}
+impl Default for PlotDemoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            spectrum_bins: 4096,
+            line_width: 1.5,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct PlotDemoStats {
+    pub points: usize,
+    pub drawn: usize,
+}
+
+// =============================== UI ===============================
+fn plot_panel(ctx: &egui::Context, world: &mut World) {
+    let (points, drawn) = {
+        let stats = world.resource::<PlotDemoStats>();
+        (stats.points, stats.drawn)
+    };
+    let mut settings = world.resource::<PlotDemoSettings>().clone();
+
+    egui::Window::new(tr("Plots"))
+        .id(egui::Id::new("Plots"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Show GPU plots"));
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.spectrum_bins, 16..=65536)
+                        .logarithmic(true)
+                        .text(tr("Spectrum bins")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.line_width, 0.5..=6.0).text(tr("Line width")),
+                );
+            });
+            ui.label(trf!("{} points, {} drawn after decimation", points, drawn));
+        });
+
+    let mut current = world.resource_mut::<PlotDemoSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_are_round_and_decimation_keeps_spikes() {
+        assert_eq!(tick_step(0.0, 10.0, 5), 2.0);
+        assert_eq!(tick_step(0.0, 0.9, 5), 0.2);
+        assert_eq!(tick_step(-90.0, 0.0, 5), 20.0);
+        assert_eq!(format_tick(0.4, 0.2), "0.4");
+
+        // A thousand points squeezed into ten columns with one spike
+        let points: Vec<_> = (0..1000)
+            .map(|i| Vec2::new(i as f32 / 100.0, if i == 537 { 50.0 } else { 1.0 }))
+            .collect();
+        let decimated = decimate(&points, 0.0);
+        assert!(decimated.len() <= 40);
+        assert!(decimated.iter().any(|point| point.y == 50.0));
+        assert_eq!(decimated.first(), points.first());
+        assert_eq!(decimated.last(), points.last());
+    }
+}
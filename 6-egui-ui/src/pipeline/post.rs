@@ -0,0 +1,407 @@
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{Condition, IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::Vec3;
+use wgpu::util::DeviceExt;
+
+use crate::i18n::{tr, trf};
+use crate::{gpu::GpuContext, time::TimeContext};
+
+use super::{
+    dof::{coc_resize_system, CocBuffer},
+    render::render_system,
+    ui::UiPanels,
+    velocity::{velocity_resize_system, VelocityBuffer},
+};
+
+pub fn setup_post_effects(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let velocity = world
+        .get_resource::<VelocityBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("VelocityBuffer resource not found"))?;
+    let coc = world
+        .get_resource::<CocBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("CocBuffer resource not found"))?;
+
+    let settings = PostSettings::default();
+    let effects = PostEffects::new(gpu, &settings, velocity, coc);
+    world.insert_resource(effects);
+    world.insert_resource(settings);
+    world.insert_resource(WhiteBalance::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(post_effects_panel);
+
+    schedule.add_systems((
+        post_params_system,
+        post_bind_group_system
+            .run_if(resource_changed::<VelocityBuffer>.or(resource_changed::<CocBuffer>))
+            .after(velocity_resize_system)
+            .after(coc_resize_system)
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Uploads the effect parameters every frame, film grain needs the time.
+pub fn post_params_system(
+    gpu: Res<GpuContext>,
+    time: Res<TimeContext>,
+    settings: Res<PostSettings>,
+    white_balance: Res<WhiteBalance>,
+    effects: Res<PostEffects>,
+) {
+    let params = PostParams::new(&settings, time.total, white_balance.gains);
+    gpu.queue
+        .write_buffer(&effects.params, 0, bytemuck::bytes_of(&params));
+}
+
+/// Picks up the velocity and CoC buffers after they were resized.
+pub fn post_bind_group_system(
+    gpu: Res<GpuContext>,
+    velocity: Res<VelocityBuffer>,
+    coc: Res<CocBuffer>,
+    mut effects: ResMut<PostEffects>,
+) {
+    effects.bind_group =
+        PostEffects::create_bind_group(&gpu, &effects.layout, &effects.params, &velocity, &coc);
+}
+
+fn post_effects_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<PostSettings>().clone();
+
+    egui::Window::new(tr("Post effects"))
+        .id(egui::Id::new("Post effects"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.vignette, tr("Vignette"));
+            ui.add_enabled_ui(settings.vignette, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.vignette_intensity, 0.0..=1.0)
+                        .text(tr("Intensity")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.vignette_radius, 0.1..=1.5).text(tr("Radius")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.vignette_softness, 0.01..=1.0)
+                        .text(tr("Softness")),
+                );
+            });
+
+            ui.separator();
+            ui.checkbox(
+                &mut settings.chromatic_aberration,
+                tr("Chromatic aberration"),
+            );
+            ui.add_enabled_ui(settings.chromatic_aberration, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.aberration_strength, 0.0..=0.05)
+                        .text(tr("Strength")),
+                );
+            });
+
+            ui.separator();
+            ui.checkbox(&mut settings.film_grain, tr("Film grain"));
+            ui.add_enabled_ui(settings.film_grain, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.grain_intensity, 0.0..=0.5)
+                        .text(tr("Intensity")),
+                );
+                ui.add(egui::Slider::new(&mut settings.grain_size, 1.0..=4.0).text(tr("Size")));
+            });
+
+            ui.separator();
+            ui.checkbox(&mut settings.motion_blur, tr("Motion blur"));
+            ui.add_enabled_ui(settings.motion_blur, |ui| {
+                ui.add(egui::Slider::new(&mut settings.shutter, 0.0..=1.0).text(tr("Shutter")))
+                    .on_hover_text(tr("Fraction of the frame the shutter stays open"));
+                ui.add(
+                    egui::Slider::new(&mut settings.motion_blur_samples, 2..=32)
+                        .text(tr("Samples")),
+                );
+            });
+
+            ui.separator();
+            ui.checkbox(&mut settings.depth_of_field, tr("Depth of field"));
+            ui.add_enabled_ui(settings.depth_of_field, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.focal_distance, 0.5..=100.0)
+                        .logarithmic(true)
+                        .text(tr("Focal distance")),
+                );
+                ui.add(egui::Slider::new(&mut settings.aperture, 0.0..=32.0).text(tr("Aperture")))
+                    .on_hover_text(tr("Blur radius in pixels far behind the focal plane"));
+                ui.add(egui::Slider::new(&mut settings.max_coc, 1.0..=32.0).text(tr("Max radius")));
+                ui.add(egui::Slider::new(&mut settings.bokeh_samples, 8..=64).text(tr("Samples")));
+                ui.checkbox(&mut settings.coc_debug, tr("Show circle of confusion"))
+                    .on_hover_text(tr("Red in front of the focal plane, blue behind it"));
+            });
+
+            ui.separator();
+            ui.checkbox(&mut settings.white_balance, tr("Auto white balance"))
+                .on_hover_text(tr(
+                    "Gray world gains from the histogram of the previous frame",
+                ));
+
+            ui.separator();
+            let defines = settings.defines();
+            ui.label(trf!(
+                "Permutation: {}",
+                if defines.is_empty() {
+                    tr("none").to_string()
+                } else {
+                    defines.join(" ")
+                }
+            ));
+        });
+
+    // Toggles rebuild the present pipeline, so only write back real edits
+    let mut current = world.resource_mut::<PostSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== SETTINGS ===============================
+/// Screen space effects applied by the present shader. The toggles select
+/// the shader permutation, disabled effects cost nothing; the rest are
+/// uniforms.
+#[derive(Resource, Clone, PartialEq)]
+pub struct PostSettings {
+    pub vignette: bool,
+    /// How dark the corners get.
+    pub vignette_intensity: f32,
+    /// Distance from the center where darkening starts, 1 is the corner.
+    pub vignette_radius: f32,
+    pub vignette_softness: f32,
+    pub chromatic_aberration: bool,
+    /// Red and blue offset at the edge of the screen, in UV units.
+    pub aberration_strength: f32,
+    pub film_grain: bool,
+    pub grain_intensity: f32,
+    /// Grain cell size in pixels.
+    pub grain_size: f32,
+    pub motion_blur: bool,
+    /// Fraction of the frame's motion that gets smeared, 0.5 being the
+    /// classic 180° shutter.
+    pub shutter: f32,
+    pub motion_blur_samples: u32,
+    pub depth_of_field: bool,
+    /// Distance from the camera that's perfectly sharp.
+    pub focal_distance: f32,
+    /// Blur radius in pixels of something infinitely far away.
+    pub aperture: f32,
+    /// Largest blur radius in pixels, bounds the gather.
+    pub max_coc: f32,
+    pub bokeh_samples: u32,
+    /// Shows the CoC buffer instead of the image.
+    pub coc_debug: bool,
+    /// Multiplies the image by the [`WhiteBalance`] gains.
+    pub white_balance: bool,
+}
+impl Default for PostSettings {
+    fn default() -> Self {
+        Self {
+            vignette: false,
+            vignette_intensity: 0.5,
+            vignette_radius: 0.75,
+            vignette_softness: 0.45,
+            chromatic_aberration: false,
+            aberration_strength: 0.005,
+            film_grain: false,
+            grain_intensity: 0.08,
+            grain_size: 1.5,
+            motion_blur: false,
+            shutter: 0.5,
+            motion_blur_samples: 8,
+            depth_of_field: false,
+            focal_distance: 30.0,
+            aperture: 12.0,
+            max_coc: 12.0,
+            bokeh_samples: 32,
+            coc_debug: false,
+            white_balance: false,
+        }
+    }
+}
+impl PostSettings {
+    pub fn defines(&self) -> Vec<&'static str> {
+        let mut defines = Vec::new();
+        if self.vignette {
+            defines.push("VIGNETTE");
+        }
+        if self.chromatic_aberration {
+            defines.push("CHROMATIC_ABERRATION");
+        }
+        if self.film_grain {
+            defines.push("FILM_GRAIN");
+        }
+        if self.motion_blur {
+            defines.push("MOTION_BLUR");
+        }
+        if self.depth_of_field {
+            defines.push("DEPTH_OF_FIELD");
+            if self.coc_debug {
+                defines.push("COC_DEBUG");
+            }
+        }
+        if self.white_balance {
+            defines.push("WHITE_BALANCE");
+        }
+        defines
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PostParams {
+    pub vignette_intensity: f32,
+    pub vignette_radius: f32,
+    pub vignette_softness: f32,
+    pub aberration_strength: f32,
+    pub grain_intensity: f32,
+    pub grain_size: f32,
+    pub time: f32,
+    pub shutter: f32,
+    pub motion_blur_samples: u32,
+    pub max_coc: f32,
+    pub bokeh_samples: u32,
+    pub _padding: f32,
+    pub white_balance: [f32; 4],
+}
+impl PostParams {
+    pub fn new(settings: &PostSettings, time: f32, white_balance: Vec3) -> Self {
+        Self {
+            vignette_intensity: settings.vignette_intensity,
+            vignette_radius: settings.vignette_radius,
+            vignette_softness: settings.vignette_softness,
+            aberration_strength: settings.aberration_strength,
+            grain_intensity: settings.grain_intensity,
+            grain_size: settings.grain_size,
+            time,
+            shutter: settings.shutter,
+            motion_blur_samples: settings.motion_blur_samples,
+            max_coc: settings.max_coc,
+            bokeh_samples: settings.bokeh_samples,
+            _padding: 0.0,
+            white_balance: white_balance.extend(1.0).to_array(),
+        }
+    }
+}
+
+/// Per channel gains of the white balance, in linear space. Kept up to date
+/// by the histogram pass while the effect is on.
+#[derive(Resource)]
+pub struct WhiteBalance {
+    pub gains: Vec3,
+}
+impl Default for WhiteBalance {
+    fn default() -> Self {
+        Self { gains: Vec3::ONE }
+    }
+}
+
+/// Group 2 of the present pipeline: the parameters, the velocity buffer and
+/// the circle of confusion.
+#[derive(Resource)]
+pub struct PostEffects {
+    pub params: wgpu::Buffer,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+impl PostEffects {
+    pub fn new(
+        gpu: &GpuContext,
+        settings: &PostSettings,
+        velocity: &VelocityBuffer,
+        coc: &CocBuffer,
+    ) -> Self {
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("post_params"),
+                contents: bytemuck::bytes_of(&PostParams::new(settings, 0.0, Vec3::ONE)),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Read with textureLoad, so no sampler
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("post_bind_group_layout"),
+            });
+        let bind_group = Self::create_bind_group(gpu, &layout, &params, velocity, coc);
+
+        Self {
+            params,
+            layout,
+            bind_group,
+        }
+    }
+
+    fn create_bind_group(
+        gpu: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        params: &wgpu::Buffer,
+        velocity: &VelocityBuffer,
+        coc: &CocBuffer,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&velocity.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&coc.texture.view),
+                },
+            ],
+            label: Some("post_bind_group"),
+        })
+    }
+}
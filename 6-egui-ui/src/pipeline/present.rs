@@ -1,22 +1,44 @@
+use std::sync::Arc;
+
 use anyhow::Result;
 use bevy_ecs::{
     component::Component,
+    observer::Trigger,
     prelude::resource_changed,
-    schedule::{IntoSystemConfigs, Schedule},
-    system::{Res, ResMut, Resource},
+    schedule::{Condition, IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource, SystemParam},
     world::World,
 };
 
+use tracing::warn;
+
 use crate::{
+    gpu::SurfaceChanged,
+    i18n::{tr, trf},
+    layout::LayoutCache,
+    pass::RenderPassBuilder,
+    shader::{parse_wgsl, preprocess},
     texture::{self, Texture},
     uniform::Uniforms,
     vertex::{DepthVertex, Vertex},
     GpuContext,
 };
 
-use super::{GPUPipeline, GPUPipelineBuilder};
+use super::{
+    depth::DepthTexture,
+    grading::ColorGrading,
+    graph::PassContext,
+    inspector::TextureRegistry,
+    post::{PostEffects, PostSettings},
+    render::{render_system, AcquireSettings, AcquireStats},
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
 
 pub fn setup_present(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let bind_group_layout = world.resource_scope::<LayoutCache, _>(|world, mut layouts| {
+        PresentBindGroupLayout::new(world.resource::<GpuContext>(), &mut layouts)
+    })?;
     let gpu = world
         .get_resource::<GpuContext>()
         .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
@@ -26,17 +48,47 @@ pub fn setup_present(world: &mut World, schedule: &mut Schedule) -> Result<()> {
     let uniform = world
         .get_resource::<Uniforms>()
         .ok_or_else(|| anyhow::anyhow!("Uniform resource not found"))?;
+    let settings = world
+        .get_resource::<PresentSettings>()
+        .ok_or_else(|| anyhow::anyhow!("PresentSettings resource not found"))?;
+    let grading = world
+        .get_resource::<ColorGrading>()
+        .ok_or_else(|| anyhow::anyhow!("ColorGrading resource not found"))?;
+    let post = world
+        .get_resource::<PostEffects>()
+        .ok_or_else(|| anyhow::anyhow!("PostEffects resource not found"))?;
+    let post_settings = world
+        .get_resource::<PostSettings>()
+        .ok_or_else(|| anyhow::anyhow!("PostSettings resource not found"))?;
 
-    let bind_group_layout = PresentBindGroupLayout::new(&gpu)?;
-    let bind_group =
-        PresentBindGroup::new(&gpu, &bind_group_layout, &frame_buffer.texture, uniform)?;
-    let pipeline = PresentPipeline::new(&gpu, &bind_group_layout)?;
+    let samplers = PresentSamplers::new(gpu);
+    let bind_group = PresentBindGroup::new(
+        &gpu,
+        &bind_group_layout,
+        &frame_buffer.texture,
+        uniform,
+        samplers.get(settings.scaling),
+    )?;
+    let pipeline = PresentPipeline::new(&gpu, &bind_group_layout, grading, post, post_settings)?;
 
+    world.insert_resource(samplers);
     world.insert_resource(bind_group_layout);
     world.insert_resource(bind_group);
     world.insert_resource(pipeline);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(present_panel);
+    world.add_observer(present_surface_changed_observer);
 
-    schedule.add_systems(frame_buffer_changed_system.run_if(resource_changed::<FrameBuffer>));
+    schedule.add_systems((
+        render_scale_system.run_if(resource_changed::<PresentSettings>),
+        frame_buffer_changed_system
+            .after(render_scale_system)
+            .run_if(resource_changed::<FrameBuffer>.or(resource_changed::<PresentSettings>)),
+        present_permutation_system
+            .run_if(resource_changed::<PostSettings>)
+            .before(render_system),
+    ));
 
     Ok(())
 }
@@ -45,18 +97,51 @@ pub fn setup_frame_buffer(world: &mut World, schedule: &mut Schedule) -> Result<
         .get_resource::<GpuContext>()
         .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
 
-    let texture =
-        Texture::frame_buffer_texture(&gpu.device, gpu.config.width, gpu.config.height, None, 1);
+    let settings = PresentSettings::default();
+    let (width, height) = settings.internal_size(gpu.config.width, gpu.config.height);
+    let texture = Texture::frame_buffer_texture(&gpu.device, width, height, None, 1);
     let frame_buffer = FrameBuffer { texture };
 
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("frame_buffer", |world| {
+            world
+                .get_resource::<FrameBuffer>()
+                .map(|fb| &fb.texture.texture)
+        });
     world.insert_resource(frame_buffer);
+    world.insert_resource(settings);
 
     Ok(())
 }
 
+/// Resizes everything that renders at the internal resolution when the render
+/// scale changes. Window resizes go through `window_event_system` instead.
+pub fn render_scale_system(
+    gpu: Res<GpuContext>,
+    settings: Res<PresentSettings>,
+    mut frame_buffer: ResMut<FrameBuffer>,
+    mut depth_texture: ResMut<DepthTexture>,
+    mut uniforms: ResMut<Uniforms>,
+) {
+    let (width, height) = settings.internal_size(gpu.config.width, gpu.config.height);
+    let size = frame_buffer.texture.texture.size();
+    if size.width == width && size.height == height {
+        return;
+    }
+
+    frame_buffer
+        .texture
+        .resize(&gpu.device, &gpu.queue, width, height);
+    depth_texture.resize(&gpu.device, width, height);
+    uniforms.update_resolution(&gpu, [width as f32, height as f32]);
+}
+
 pub fn frame_buffer_changed_system(
-    frame_buffer: ResMut<FrameBuffer>,
+    frame_buffer: Res<FrameBuffer>,
     gpu: Res<GpuContext>,
+    settings: Res<PresentSettings>,
+    samplers: Res<PresentSamplers>,
     mut present_bind_group: ResMut<PresentBindGroup>,
     present_bind_group_layout: Res<PresentBindGroupLayout>,
     uniforms: Res<Uniforms>,
@@ -66,7 +151,163 @@ pub fn frame_buffer_changed_system(
         &present_bind_group_layout,
         &frame_buffer.texture,
         &uniforms,
+        samplers.get(settings.scaling),
+    );
+}
+
+/// Rebuilds the present pipeline when post effects are toggled. A failed
+/// build keeps the previous permutation.
+pub fn present_permutation_system(
+    gpu: Res<GpuContext>,
+    layout: Res<PresentBindGroupLayout>,
+    grading: Res<ColorGrading>,
+    post: Res<PostEffects>,
+    settings: Res<PostSettings>,
+    mut pipeline: ResMut<PresentPipeline>,
+) {
+    if pipeline.defines == settings.defines() {
+        return;
+    }
+    match PresentPipeline::new(&gpu, &layout, &grading, &post, &settings) {
+        Ok(built) => *pipeline = built,
+        Err(e) => warn!(
+            "Present permutation {:?} failed: {:?}",
+            settings.defines(),
+            e
+        ),
+    }
+}
+
+/// The pipeline renders straight to the surface, so it follows its format.
+pub fn present_surface_changed_observer(
+    trigger: Trigger<SurfaceChanged>,
+    gpu: Res<GpuContext>,
+    layout: Res<PresentBindGroupLayout>,
+    grading: Res<ColorGrading>,
+    post: Res<PostEffects>,
+    settings: Res<PostSettings>,
+    mut pipeline: ResMut<PresentPipeline>,
+) {
+    if !trigger.event().format_changed() {
+        return;
+    }
+    match PresentPipeline::new(&gpu, &layout, &grading, &post, &settings) {
+        Ok(built) => *pipeline = built,
+        Err(e) => warn!(
+            "Present pipeline for {:?} failed: {:?}",
+            gpu.config.format, e
+        ),
+    }
+}
+
+pub fn present_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let gpu = world.resource::<GpuContext>();
+    let settings = world.resource::<PresentSettings>();
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let pipeline = world.resource::<PresentPipeline>();
+    let bind_group = world.resource::<PresentBindGroup>();
+    let grading = world.resource::<ColorGrading>();
+    let post = world.resource::<PostEffects>();
+
+    let size = frame_buffer.texture.texture.size();
+    let [x, y, width, height] = settings.viewport(
+        (gpu.config.width, gpu.config.height),
+        (size.width, size.height),
     );
+
+    // Everything outside the viewport keeps the clear color, which gives
+    // integer scaling its black borders
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(ctx.surface_view)
+        .build()?;
+
+    render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+    render_pass.set_pipeline(&pipeline.pipeline.render_pipeline);
+    render_pass.set_bind_group(0, &bind_group.bind_group, &[]);
+    render_pass.set_bind_group(1, &grading.bind_group, &[]);
+    render_pass.set_bind_group(2, &post.bind_group, &[]);
+    render_pass.draw(0..6, 0..1);
+
+    Ok(())
+}
+
+fn present_panel(ctx: &egui::Context, world: &mut World) {
+    let gpu = world.resource::<GpuContext>();
+    let (window_width, window_height) = (gpu.config.width, gpu.config.height);
+    let size = world.resource::<FrameBuffer>().texture.texture.size();
+    let stats = world.resource::<AcquireStats>();
+    let (acquired, skipped, timeouts, reconfigures, last_ms, slowest_ms) = (
+        stats.acquired,
+        stats.skipped,
+        stats.timeouts,
+        stats.reconfigures,
+        stats.last_ms,
+        stats.slowest_ms,
+    );
+    let mut acquire = world.resource::<AcquireSettings>().clone();
+    let mut settings = world.resource_mut::<PresentSettings>();
+    let mut edited = settings.clone();
+
+    egui::Window::new(tr("Present"))
+        .id(egui::Id::new("Present"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.add(
+                egui::Slider::new(&mut edited.render_scale, 0.125..=1.0).text(tr("Render scale")),
+            );
+            egui::ComboBox::from_label(tr("Scaling"))
+                .selected_text(tr(edited.scaling.name()))
+                .show_ui(ui, |ui| {
+                    for scaling in [
+                        PresentScaling::Linear,
+                        PresentScaling::Nearest,
+                        PresentScaling::Integer,
+                    ] {
+                        ui.selectable_value(&mut edited.scaling, scaling, tr(scaling.name()));
+                    }
+                });
+            ui.label(trf!(
+                "Internal {}x{}, window {}x{}",
+                size.width,
+                size.height,
+                window_width,
+                window_height
+            ));
+
+            ui.separator();
+            ui.label(tr("Surface acquire"));
+            ui.add(
+                egui::Slider::new(&mut acquire.timeout_ms, 16.0..=1000.0)
+                    .logarithmic(true)
+                    .text(tr("Timeout (ms)")),
+            );
+            ui.add(
+                egui::Slider::new(&mut acquire.reconfigure_after, 1..=10)
+                    .text(tr("Reconfigure after timeouts")),
+            );
+            ui.label(trf!(
+                "{} acquired, {} skipped, {} timeouts, {} reconfigures",
+                acquired,
+                skipped,
+                timeouts,
+                reconfigures
+            ));
+            ui.label(trf!(
+                "Last acquire {:.2} ms, slowest {:.2} ms",
+                last_ms,
+                slowest_ms
+            ));
+        });
+
+    // Only touch the resource on edits, the bind group is rebuilt on change
+    if edited != *settings {
+        *settings = edited;
+    }
+    let mut current = world.resource_mut::<AcquireSettings>();
+    if *current != acquire {
+        *current = acquire;
+    }
 }
 
 // =============================== FRAME BUFFER ===============================
@@ -75,46 +316,162 @@ pub struct FrameBuffer {
     pub texture: Texture,
 }
 
+// =============================== SETTINGS ===============================
+/// How the frame buffer is fitted to the window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresentScaling {
+    /// Bilinear filtering, stretched to the window.
+    Linear,
+    /// Crisp pixels, stretched to the window.
+    Nearest,
+    /// The largest whole multiple of the internal resolution that fits,
+    /// centered with black borders.
+    Integer,
+}
+impl PresentScaling {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Nearest => "Nearest",
+            Self::Integer => "Integer",
+        }
+    }
+}
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct PresentSettings {
+    pub scaling: PresentScaling,
+    /// Internal resolution as a fraction of the window size.
+    pub render_scale: f32,
+}
+impl Default for PresentSettings {
+    fn default() -> Self {
+        Self {
+            scaling: PresentScaling::Linear,
+            render_scale: 1.0,
+        }
+    }
+}
+impl PresentSettings {
+    /// Size of the frame buffer for a window of the given size. Rounds down
+    /// so integer scaling gets at least a 2x multiple at half scale.
+    pub fn internal_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale = |extent: u32| ((extent as f32 * self.render_scale) as u32).max(1);
+        (scale(width), scale(height))
+    }
+
+    /// Where the frame buffer lands on the surface, as `[x, y, width, height]`.
+    pub fn viewport(&self, surface: (u32, u32), internal: (u32, u32)) -> [f32; 4] {
+        let (surface_width, surface_height) = surface;
+        match self.scaling {
+            PresentScaling::Linear | PresentScaling::Nearest => {
+                [0.0, 0.0, surface_width as f32, surface_height as f32]
+            }
+            PresentScaling::Integer => {
+                let factor = (surface_width / internal.0.max(1))
+                    .min(surface_height / internal.1.max(1))
+                    .max(1);
+                let (width, height) = (internal.0 * factor, internal.1 * factor);
+                [
+                    (surface_width.saturating_sub(width) / 2) as f32,
+                    (surface_height.saturating_sub(height) / 2) as f32,
+                    width as f32,
+                    height as f32,
+                ]
+            }
+        }
+    }
+}
+
+/// The area of the window the scene is presented to, for mapping cursor
+/// positions back into the frame buffer.
+#[derive(SystemParam)]
+pub struct PresentViewport<'w> {
+    gpu: Res<'w, GpuContext>,
+    settings: Res<'w, PresentSettings>,
+    frame_buffer: Res<'w, FrameBuffer>,
+}
+impl PresentViewport<'_> {
+    pub fn rect(&self) -> [f32; 4] {
+        let size = self.frame_buffer.texture.texture.size();
+        self.settings.viewport(
+            (self.gpu.config.width, self.gpu.config.height),
+            (size.width, size.height),
+        )
+    }
+}
+
+#[derive(Resource)]
+pub struct PresentSamplers {
+    pub linear: wgpu::Sampler,
+    pub nearest: wgpu::Sampler,
+}
+impl PresentSamplers {
+    pub fn new(gpu: &GpuContext) -> Self {
+        let sampler = |label, filter| {
+            gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some(label),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            })
+        };
+        Self {
+            linear: sampler("present_linear_sampler", wgpu::FilterMode::Linear),
+            nearest: sampler("present_nearest_sampler", wgpu::FilterMode::Nearest),
+        }
+    }
+
+    pub fn get(&self, scaling: PresentScaling) -> &wgpu::Sampler {
+        match scaling {
+            PresentScaling::Linear => &self.linear,
+            PresentScaling::Nearest | PresentScaling::Integer => &self.nearest,
+        }
+    }
+}
+
 // =============================== BIND GROUP ===============================
 #[derive(Resource)]
 pub struct PresentBindGroupLayout {
-    pub layout: wgpu::BindGroupLayout,
+    pub layout: Arc<wgpu::BindGroupLayout>,
 }
 impl PresentBindGroupLayout {
-    pub fn new(gpu: &GpuContext) -> Result<Self> {
-        let diffuse_bind_group_layout =
-            gpu.device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Texture {
-                                multisampled: false,
-                                view_dimension: wgpu::TextureViewDimension::D2,
-                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 1,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 2,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        },
-                    ],
-                    label: Some("diffuse_bind_group_layout"),
-                });
+    pub fn new(gpu: &GpuContext, layouts: &mut LayoutCache) -> Result<Self> {
+        let diffuse_bind_group_layout = layouts.get(
+            &gpu.device,
+            &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        );
 
         Ok(Self {
             layout: diffuse_bind_group_layout,
@@ -132,6 +489,7 @@ impl PresentBindGroup {
         layout: &PresentBindGroupLayout,
         texture: &Texture,
         uniforms_buffer: &Uniforms,
+        sampler: &wgpu::Sampler,
     ) -> Result<Self> {
         let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &layout.layout,
@@ -142,7 +500,7 @@ impl PresentBindGroup {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    resource: wgpu::BindingResource::Sampler(sampler),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
@@ -164,6 +522,7 @@ impl PresentBindGroup {
         layout: &PresentBindGroupLayout,
         texture: &Texture,
         uniforms_buffer: &Uniforms,
+        sampler: &wgpu::Sampler,
     ) {
         self.bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &layout.layout,
@@ -174,7 +533,7 @@ impl PresentBindGroup {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    resource: wgpu::BindingResource::Sampler(sampler),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
@@ -194,27 +553,44 @@ impl PresentBindGroup {
 #[derive(Resource)]
 pub struct PresentPipeline {
     pub pipeline: GPUPipeline,
+    /// Post effects compiled into the pipeline.
+    pub defines: Vec<&'static str>,
 }
 impl PresentPipeline {
-    pub fn new(gpu: &GpuContext, bind_group_layout: &PresentBindGroupLayout) -> Result<Self> {
+    pub fn new(
+        gpu: &GpuContext,
+        bind_group_layout: &PresentBindGroupLayout,
+        grading: &ColorGrading,
+        post: &PostEffects,
+        post_settings: &PostSettings,
+    ) -> Result<Self> {
+        let defines = post_settings.defines();
+        let source = preprocess(
+            "present.wgsl",
+            include_str!("../shaders/present.wgsl"),
+            &defines,
+        )?;
+        parse_wgsl("present.wgsl", &source)?;
         let shader = gpu
             .device
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some("present_shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/present.wgsl").into()),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
             });
         let pipeline = GPUPipelineBuilder::new(&gpu.device)
             .label("present_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
             .bind_group_layout(&bind_group_layout.layout)
+            .bind_group_layout(&grading.layout)
+            .bind_group_layout(&post.layout)
             .vertex_shader(&shader, "vs_main")
             .fragment_shader(&shader, "fs_main")
             .default_color_target(gpu.config.format)
             .depth_stencil_state(None)
             .default_multisample_state()
             .default_primitive_state()
-            .build()
-            .map_err(|e| anyhow::anyhow!(e))?;
+            .build()?;
 
-        Ok(Self { pipeline })
+        Ok(Self { pipeline, defines })
     }
 }
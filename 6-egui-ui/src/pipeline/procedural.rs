@@ -0,0 +1,348 @@
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::Schedule,
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use tracing::{error, info};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::tr,
+    rng::Rng,
+    shader::{load_shader_source, shader_path, ShaderWatcher},
+    time::TimeContext,
+};
+
+use super::{
+    compute::{DispatchSite, GPUComputePipeline},
+    graph::PassContext,
+    inspector::TextureRegistry,
+    ui::{EguiState, UiPanels},
+};
+
+const SHADER_NAME: &str = "procedural.wgsl";
+/// Included by the shader, edits to it reload the pipeline too.
+const NOISE_SHADER_NAME: &str = "noise.wgsl";
+const TEXTURE_SIZE: u32 = 256;
+
+pub fn setup_procedural(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let seed = world
+        .get_resource_mut::<Rng>()
+        .ok_or_else(|| anyhow::anyhow!("Rng resource not found"))?
+        .next_u32();
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let texture = ProceduralTexture::new(gpu, TEXTURE_SIZE, TEXTURE_SIZE, seed);
+    let bind_group_layout = ProceduralBindGroupLayout::new(gpu);
+    let bind_group = ProceduralBindGroup::new(gpu, &bind_group_layout, &texture);
+    let pipeline = ProceduralPipeline::new(gpu, &bind_group_layout, &texture)?;
+
+    let texture_id = world.resource_scope::<EguiState, _>(|world, mut ui| {
+        ui.renderer.register_native_texture(
+            &world.resource::<GpuContext>().device,
+            &texture.view,
+            wgpu::FilterMode::Linear,
+        )
+    });
+
+    world.insert_resource(ProceduralPreview { texture_id });
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("procedural", |world| {
+            world
+                .get_resource::<ProceduralTexture>()
+                .map(|p| &p.texture)
+        })
+        .with_source(shader_path(SHADER_NAME));
+    world.insert_resource(texture);
+    world.insert_resource(bind_group_layout);
+    world.insert_resource(bind_group);
+    world.insert_resource(pipeline);
+    let mut watcher = world.get_resource_or_insert_with(ShaderWatcher::default);
+    watcher.watch(shader_path(SHADER_NAME));
+    watcher.watch(shader_path(NOISE_SHADER_NAME));
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(procedural_panel);
+
+    schedule.add_systems(procedural_reload_system);
+
+    Ok(())
+}
+
+pub fn procedural_reload_system(
+    gpu: Res<GpuContext>,
+    mut watcher: ResMut<ShaderWatcher>,
+    layout: Res<ProceduralBindGroupLayout>,
+    texture: Res<ProceduralTexture>,
+    mut pipeline: ResMut<ProceduralPipeline>,
+) {
+    // Both have to be taken, a short circuit would leave the other pending
+    let changed = watcher.take_changed(&shader_path(SHADER_NAME))
+        | watcher.take_changed(&shader_path(NOISE_SHADER_NAME));
+    if !changed {
+        return;
+    }
+    match pipeline.reload(&gpu, &layout, &texture) {
+        Ok(()) => {
+            pipeline.last_error = None;
+            info!(
+                "Reloaded {} with @workgroup_size{:?}, dispatching {:?} workgroups",
+                SHADER_NAME, pipeline.pipeline.workgroup_size, pipeline.workgroup_counts
+            );
+        }
+        Err(e) => {
+            error!("Keeping previous {} pipeline: {:?}", SHADER_NAME, e);
+            pipeline.last_error = Some(format!("{:?}", e));
+        }
+    }
+}
+
+pub fn procedural_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let gpu = world.resource::<GpuContext>();
+    let time = world.resource::<TimeContext>();
+    let texture = world.resource::<ProceduralTexture>();
+    let bind_group = world.resource::<ProceduralBindGroup>();
+    let pipeline = world.resource::<ProceduralPipeline>();
+
+    let params = ProceduralParams {
+        time: time.total,
+        seed: texture.seed,
+        size: [texture.width, texture.height],
+    };
+    gpu.queue
+        .write_buffer(&bind_group.params_buffer, 0, bytemuck::bytes_of(&params));
+
+    let mut compute_pass = ctx
+        .encoder
+        .begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(ctx.label),
+            timestamp_writes: None,
+        });
+    compute_pass.set_pipeline(&pipeline.pipeline.pipeline);
+    compute_pass.set_bind_group(0, &bind_group.bind_group, &[]);
+    let [x, y, z] = pipeline.workgroup_counts;
+    compute_pass.dispatch_workgroups(x, y, z);
+
+    Ok(())
+}
+
+fn procedural_panel(ctx: &egui::Context, world: &mut World) {
+    let preview = world.resource::<ProceduralPreview>();
+    let pipeline = world.resource::<ProceduralPipeline>();
+
+    egui::Window::new(tr("Compute"))
+        .id(egui::Id::new("Compute"))
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{}: @workgroup_size{:?}, {:?} workgroups",
+                SHADER_NAME, pipeline.pipeline.workgroup_size, pipeline.workgroup_counts
+            ));
+            if let Some(e) = &pipeline.last_error {
+                ui.colored_label(egui::Color32::RED, e);
+            }
+            ui.image((preview.texture_id, egui::vec2(256.0, 256.0)));
+        });
+}
+
+// =============================== TEXTURE ===============================
+#[derive(Resource)]
+pub struct ProceduralTexture {
+    #[allow(unused)]
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub width: u32,
+    pub height: u32,
+    /// Seed of the noise the texture is generated from.
+    pub seed: u32,
+}
+impl ProceduralTexture {
+    pub fn new(gpu: &GpuContext, width: u32, height: u32, seed: u32) -> Self {
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("procedural_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+
+        Self {
+            texture,
+            view,
+            width,
+            height,
+            seed,
+        }
+    }
+
+    pub fn dispatch_site(&self) -> DispatchSite {
+        DispatchSite {
+            label: "procedural",
+            domain: [self.width, self.height, 1],
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct ProceduralPreview {
+    pub texture_id: egui::TextureId,
+}
+
+// =============================== BIND GROUP ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ProceduralParams {
+    pub time: f32,
+    pub seed: u32,
+    pub size: [u32; 2],
+}
+
+#[derive(Resource)]
+pub struct ProceduralBindGroupLayout {
+    pub layout: wgpu::BindGroupLayout,
+}
+impl ProceduralBindGroupLayout {
+    pub fn new(gpu: &GpuContext) -> Self {
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: wgpu::TextureFormat::Rgba8Unorm,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("procedural_bind_group_layout"),
+            });
+
+        Self { layout }
+    }
+}
+
+#[derive(Resource)]
+pub struct ProceduralBindGroup {
+    pub bind_group: wgpu::BindGroup,
+    pub params_buffer: wgpu::Buffer,
+}
+impl ProceduralBindGroup {
+    pub fn new(
+        gpu: &GpuContext,
+        layout: &ProceduralBindGroupLayout,
+        texture: &ProceduralTexture,
+    ) -> Self {
+        let params_buffer = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("procedural_params_buffer"),
+                contents: bytemuck::bytes_of(&ProceduralParams {
+                    time: 0.0,
+                    seed: texture.seed,
+                    size: [texture.width, texture.height],
+                }),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("procedural_bind_group"),
+        });
+
+        Self {
+            bind_group,
+            params_buffer,
+        }
+    }
+}
+
+// =============================== PIPELINE ===============================
+#[derive(Resource)]
+pub struct ProceduralPipeline {
+    pub pipeline: GPUComputePipeline,
+    pub workgroup_counts: [u32; 3],
+    pub last_error: Option<String>,
+}
+impl ProceduralPipeline {
+    pub fn new(
+        gpu: &GpuContext,
+        layout: &ProceduralBindGroupLayout,
+        texture: &ProceduralTexture,
+    ) -> Result<Self> {
+        let (pipeline, workgroup_counts) = Self::build(gpu, layout, texture)?;
+        Ok(Self {
+            pipeline,
+            workgroup_counts,
+            last_error: None,
+        })
+    }
+
+    /// Recompiles the shader and revalidates the dispatch. On failure the
+    /// currently running pipeline is left untouched.
+    pub fn reload(
+        &mut self,
+        gpu: &GpuContext,
+        layout: &ProceduralBindGroupLayout,
+        texture: &ProceduralTexture,
+    ) -> Result<()> {
+        let (pipeline, workgroup_counts) = Self::build(gpu, layout, texture)?;
+        self.pipeline = pipeline;
+        self.workgroup_counts = workgroup_counts;
+        Ok(())
+    }
+
+    fn build(
+        gpu: &GpuContext,
+        layout: &ProceduralBindGroupLayout,
+        texture: &ProceduralTexture,
+    ) -> Result<(GPUComputePipeline, [u32; 3])> {
+        let source = load_shader_source(SHADER_NAME, include_str!("../shaders/procedural.wgsl"));
+        let pipeline = GPUComputePipeline::new(
+            &gpu.device,
+            "procedural_pipeline",
+            &source,
+            "cs_main",
+            &[&layout.layout],
+            gpu.pipeline_cache(),
+        )?;
+        let workgroup_counts = texture
+            .dispatch_site()
+            .validate(pipeline.workgroup_size, &gpu.device.limits())?;
+        Ok((pipeline, workgroup_counts))
+    }
+}
@@ -0,0 +1,477 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use bevy_ecs::{
+    observer::Trigger,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Commands, Res, Resource},
+    world::World,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::i18n::{tr, trf};
+use crate::{
+    profiler::TraceCapture,
+    settings::{RendererSettings, RendererSettingsChanged},
+    time::TimeContext,
+};
+
+use super::{
+    ao::AoSettings, cascades::CascadeSettings, god_rays::GodRaySettings, present::PresentSettings,
+    render::render_system, scaled::ResolutionScale, shadow::ShadowAtlas, ssr::SsrSettings,
+    ui::UiPanels, volume::VolumeSettings,
+};
+
+/// Read at startup and written from the quality panel, in the working directory.
+pub const QUALITY_CONFIG: &str = "quality.json";
+
+/// Has to run after every pipeline whose settings a profile touches.
+pub fn setup_quality(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let config = match QualityConfig::load(Path::new(QUALITY_CONFIG)) {
+        Ok(Some(config)) => config,
+        Ok(None) => QualityConfig::default(),
+        Err(e) => {
+            warn!("Ignoring {}: {:?}", QUALITY_CONFIG, e);
+            QualityConfig::default()
+        }
+    };
+    let preset = world
+        .get_resource::<RendererSettings>()
+        .map_or(QualityPreset::High, |settings| settings.quality);
+    config.profile(preset).apply(world);
+
+    world.insert_resource(config);
+    world.insert_resource(QualityBenchmark::default());
+    world.add_observer(quality_renderer_settings_observer);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(quality_panel);
+
+    // Before rendering, so a capture started here already covers this frame
+    schedule.add_systems(quality_benchmark_system.before(render_system));
+
+    Ok(())
+}
+
+/// Applies the preset picked in [`RendererSettings`].
+pub fn quality_renderer_settings_observer(
+    trigger: Trigger<RendererSettingsChanged>,
+    config: Res<QualityConfig>,
+    mut commands: Commands,
+) {
+    let event = trigger.event();
+    if event.previous.quality == event.current.quality {
+        return;
+    }
+    let profile = config.profile(event.current.quality).clone();
+    commands.queue(move |world: &mut World| profile.apply(world));
+}
+
+/// Steps the benchmark through its presets: applies one, lets it settle for
+/// a few frames, then captures it with the profiler.
+pub fn quality_benchmark_system(world: &mut World) {
+    let delta = world.resource::<TimeContext>().delta as f64;
+    let Some(capture) = world.get_resource::<TraceCapture>().cloned() else {
+        return;
+    };
+    let mut benchmark = world.resource_mut::<QualityBenchmark>();
+    let frames = benchmark.frames;
+    let Some(run) = &mut benchmark.run else {
+        return;
+    };
+
+    if run.warmup_left > 0 {
+        run.warmup_left -= 1;
+        if run.warmup_left == 0 {
+            let mut capture = capture;
+            capture.start(frames);
+            run.frame_seconds = 0.0;
+        }
+        return;
+    }
+    run.frame_seconds += delta;
+    if capture.frames_left() > 0 {
+        return;
+    }
+
+    let result = QualityResult {
+        preset: run.current,
+        gpu_ms: capture.average_gpu_frame_ms(),
+        frame_ms: run.frame_seconds * 1000.0 / frames as f64,
+    };
+    info!(
+        "Quality benchmark {}: GPU {}, frame {:.2} ms",
+        result.preset.name(),
+        result
+            .gpu_ms
+            .map_or("-".to_string(), |ms| format!("{:.2} ms", ms)),
+        result.frame_ms
+    );
+    benchmark.results.push(result);
+
+    let run = benchmark.run.as_mut().expect("benchmark is running");
+    let next = match run.remaining.pop() {
+        Some(next) => {
+            run.current = next;
+            run.warmup_left = QualityBenchmark::WARMUP_FRAMES;
+            next
+        }
+        None => {
+            let restore = run.restore;
+            benchmark.run = None;
+            restore
+        }
+    };
+    let profile = world.resource::<QualityConfig>().profile(next).clone();
+    profile.apply(world);
+}
+
+fn quality_panel(ctx: &egui::Context, world: &mut World) {
+    let mut config = world.resource::<QualityConfig>().clone();
+    let mut benchmark = world.resource::<QualityBenchmark>().clone();
+    let mut preset = world.resource::<RendererSettings>().quality;
+    let capturing = world
+        .get_resource::<TraceCapture>()
+        .is_some_and(|capture| capture.is_capturing());
+    let mut apply = None;
+    let mut start = false;
+
+    egui::Window::new(tr("Quality"))
+        .id(egui::Id::new("Quality"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.add_enabled_ui(benchmark.run.is_none(), |ui| {
+                ui.horizontal(|ui| {
+                    for option in QualityPreset::ALL {
+                        ui.selectable_value(&mut preset, option, tr(option.name()));
+                    }
+                });
+                if ui.button(tr("Reapply")).clicked() {
+                    apply = Some(preset);
+                }
+            });
+            ui.label(tr("Panels can still tweak single settings after a preset"));
+            if ui.button(trf!("Save to {}", QUALITY_CONFIG)).clicked() {
+                config.last_save = Some(
+                    config
+                        .save(Path::new(QUALITY_CONFIG))
+                        .map_err(|e| format!("{:?}", e)),
+                );
+            }
+            match &config.last_save {
+                Some(Ok(())) => {
+                    ui.label(tr("Saved"));
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(egui::Color32::RED, trf!("Save failed: {}", e));
+                }
+                None => {}
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(tr("Frames per preset"));
+                ui.add(egui::DragValue::new(&mut benchmark.frames).range(10..=600));
+            });
+            match &benchmark.run {
+                Some(run) => {
+                    ui.label(trf!("Benchmarking {}...", tr(run.current.name())));
+                }
+                None => {
+                    start = ui
+                        .add_enabled(!capturing, egui::Button::new(tr("Benchmark all presets")))
+                        .clicked();
+                }
+            }
+            if benchmark.results.is_empty() {
+                return;
+            }
+            egui::Grid::new("quality_results")
+                .num_columns(3)
+                .show(ui, |ui| {
+                    ui.label(tr("Preset"));
+                    ui.label(tr("GPU"));
+                    ui.label(tr("Frame"));
+                    ui.end_row();
+                    for result in &benchmark.results {
+                        ui.label(tr(result.preset.name()));
+                        ui.label(
+                            result
+                                .gpu_ms
+                                .map_or("-".to_string(), |ms| format!("{:.2} ms", ms)),
+                        );
+                        ui.label(trf!("{:.2} ms", result.frame_ms));
+                        ui.end_row();
+                    }
+                });
+        });
+
+    if start {
+        let mut remaining = QualityPreset::ALL.to_vec();
+        remaining.reverse();
+        let first = remaining.pop().expect("there are presets");
+        benchmark.results.clear();
+        benchmark.run = Some(BenchmarkRun {
+            current: first,
+            remaining,
+            restore: preset,
+            warmup_left: QualityBenchmark::WARMUP_FRAMES,
+            frame_seconds: 0.0,
+        });
+        apply = Some(first);
+    }
+    if let Some(preset) = apply {
+        config.profile(preset).clone().apply(world);
+    }
+
+    let mut current = world.resource_mut::<QualityConfig>();
+    if *current != config {
+        *current = config;
+    }
+    *world.resource_mut::<QualityBenchmark>() = benchmark;
+    let mut settings = world.resource_mut::<RendererSettings>();
+    if settings.quality != preset {
+        settings.quality = preset;
+    }
+}
+
+// =============================== PRESETS ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+impl QualityPreset {
+    pub const ALL: [Self; 4] = [Self::Low, Self::Medium, Self::High, Self::Ultra];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Low => "Low",
+            Self::Medium => "Medium",
+            Self::High => "High",
+            Self::Ultra => "Ultra",
+        }
+    }
+}
+
+/// The pass toggles and parameters a preset sets. Everything else is left
+/// to the individual panels.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QualityProfile {
+    /// Internal resolution as a fraction of the window size.
+    pub render_scale: f32,
+    /// Shrinks every spot light shadow tile, 1 keeps them at full size.
+    pub shadow_tile_divisor: u32,
+    pub cascades: usize,
+    pub ao: bool,
+    pub ao_resolution: ResolutionScale,
+    pub ssr: bool,
+    pub ssr_steps: u32,
+    pub god_rays: bool,
+    pub god_rays_resolution: ResolutionScale,
+    pub volume_steps: u32,
+    pub volume_resolution: ResolutionScale,
+}
+impl QualityProfile {
+    pub fn preset(preset: QualityPreset) -> Self {
+        match preset {
+            QualityPreset::Low => Self {
+                render_scale: 0.75,
+                shadow_tile_divisor: 4,
+                cascades: 2,
+                ao: false,
+                ao_resolution: ResolutionScale::Quarter,
+                ssr: false,
+                ssr_steps: 32,
+                god_rays: false,
+                god_rays_resolution: ResolutionScale::Quarter,
+                volume_steps: 32,
+                volume_resolution: ResolutionScale::Quarter,
+            },
+            QualityPreset::Medium => Self {
+                render_scale: 1.0,
+                shadow_tile_divisor: 2,
+                cascades: 3,
+                ao: true,
+                ao_resolution: ResolutionScale::Half,
+                ssr: true,
+                ssr_steps: 32,
+                god_rays: true,
+                god_rays_resolution: ResolutionScale::Quarter,
+                volume_steps: 48,
+                volume_resolution: ResolutionScale::Half,
+            },
+            // What the panels start out with
+            QualityPreset::High => Self {
+                render_scale: 1.0,
+                shadow_tile_divisor: 1,
+                cascades: 4,
+                ao: true,
+                ao_resolution: ResolutionScale::Half,
+                ssr: true,
+                ssr_steps: 64,
+                god_rays: true,
+                god_rays_resolution: ResolutionScale::Half,
+                volume_steps: 64,
+                volume_resolution: ResolutionScale::Full,
+            },
+            QualityPreset::Ultra => Self {
+                render_scale: 1.0,
+                shadow_tile_divisor: 1,
+                cascades: 4,
+                ao: true,
+                ao_resolution: ResolutionScale::Full,
+                ssr: true,
+                ssr_steps: 128,
+                god_rays: true,
+                god_rays_resolution: ResolutionScale::Full,
+                volume_steps: 128,
+                volume_resolution: ResolutionScale::Full,
+            },
+        }
+    }
+
+    /// Writes the profile into the settings resources, only touching the ones
+    /// that actually change so their resize systems don't run for nothing.
+    pub fn apply(&self, world: &mut World) {
+        update::<PresentSettings>(world, |settings| {
+            settings.render_scale = self.render_scale;
+        });
+        update::<CascadeSettings>(world, |settings| {
+            settings.count = self.cascades;
+        });
+        update::<AoSettings>(world, |settings| {
+            settings.enabled = self.ao;
+            settings.resolution = self.ao_resolution;
+        });
+        update::<SsrSettings>(world, |settings| {
+            settings.enabled = self.ssr;
+            settings.max_steps = self.ssr_steps;
+        });
+        update::<GodRaySettings>(world, |settings| {
+            settings.enabled = self.god_rays;
+            settings.resolution = self.god_rays_resolution;
+        });
+        update::<VolumeSettings>(world, |settings| {
+            settings.steps = self.volume_steps;
+            settings.resolution = self.volume_resolution;
+        });
+        if let Some(mut atlas) = world.get_resource_mut::<ShadowAtlas>() {
+            atlas.tile_divisor = self.shadow_tile_divisor;
+        }
+    }
+}
+
+/// Edits a copy of the settings and only writes it back when it differs.
+pub fn update<T: Resource + Clone + PartialEq>(world: &mut World, f: impl FnOnce(&mut T)) {
+    let Some(current) = world.get_resource::<T>() else {
+        return;
+    };
+    let mut settings = current.clone();
+    f(&mut settings);
+    if *current != settings {
+        world.insert_resource(settings);
+    }
+}
+
+// =============================== CONFIG ===============================
+/// What each preset means, so the profiles can be tuned in the file without
+/// a rebuild. Missing entries fall back to the built in profiles. Which one
+/// is selected is a [`RendererSettings`] field.
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityConfig {
+    pub low: QualityProfile,
+    pub medium: QualityProfile,
+    pub high: QualityProfile,
+    pub ultra: QualityProfile,
+    #[serde(skip)]
+    pub last_save: Option<Result<(), String>>,
+}
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            low: QualityProfile::preset(QualityPreset::Low),
+            medium: QualityProfile::preset(QualityPreset::Medium),
+            high: QualityProfile::preset(QualityPreset::High),
+            ultra: QualityProfile::preset(QualityPreset::Ultra),
+            last_save: None,
+        }
+    }
+}
+impl QualityConfig {
+    pub fn profile(&self, preset: QualityPreset) -> &QualityProfile {
+        match preset {
+            QualityPreset::Low => &self.low,
+            QualityPreset::Medium => &self.medium,
+            QualityPreset::High => &self.high,
+            QualityPreset::Ultra => &self.ultra,
+        }
+    }
+
+    /// `None` when there is no config file yet.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(config))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        info!("Saved quality config to {}", path.display());
+        Ok(())
+    }
+}
+
+// =============================== BENCHMARK ===============================
+#[derive(Clone, Debug)]
+pub struct QualityResult {
+    pub preset: QualityPreset,
+    /// `None` without timestamp queries.
+    pub gpu_ms: Option<f64>,
+    /// Wall clock, includes waiting on the GPU for the capture's timings.
+    pub frame_ms: f64,
+}
+
+#[derive(Clone, Debug)]
+struct BenchmarkRun {
+    current: QualityPreset,
+    /// Presets still to run, the next one last.
+    remaining: Vec<QualityPreset>,
+    /// Preset to go back to when done.
+    restore: QualityPreset,
+    warmup_left: u32,
+    frame_seconds: f64,
+}
+
+/// Runs every preset through a profiler capture in turn, for numbers to
+/// compare them by.
+#[derive(Resource, Clone, Debug)]
+pub struct QualityBenchmark {
+    pub frames: u32,
+    pub results: Vec<QualityResult>,
+    run: Option<BenchmarkRun>,
+}
+impl QualityBenchmark {
+    /// Frames to let resized targets and the frame pacing settle.
+    const WARMUP_FRAMES: u32 = 30;
+}
+impl Default for QualityBenchmark {
+    fn default() -> Self {
+        Self {
+            frames: 120,
+            results: Vec::new(),
+            run: None,
+        }
+    }
+}
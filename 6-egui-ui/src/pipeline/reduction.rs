@@ -0,0 +1,397 @@
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::Schedule,
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use tracing::{info, info_span, warn};
+use wgpu::util::DeviceExt;
+
+use crate::i18n::{tr, trf};
+use crate::{gpu::GpuContext, shader::load_shader_source};
+
+use super::{
+    compute::{read_buffer, DispatchSite, GPUComputePipeline},
+    ui::UiPanels,
+};
+
+/// Elements summed by every variant.
+const ELEMENTS: u32 = 1 << 22;
+/// Runs per variant; the reported time is their average.
+const ITERATIONS: u32 = 10;
+
+pub fn setup_reduction(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let benchmark = ReductionBenchmark::new(gpu)?;
+
+    world.insert_resource(benchmark);
+    world.insert_resource(ReductionResults::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(reduction_panel);
+
+    schedule.add_systems(reduction_benchmark_system);
+
+    Ok(())
+}
+
+/// Runs the benchmark on its own submission when the panel asks for it.
+pub fn reduction_benchmark_system(
+    gpu: Res<GpuContext>,
+    benchmark: Res<ReductionBenchmark>,
+    mut results: ResMut<ReductionResults>,
+) {
+    if !results.requested {
+        return;
+    }
+    results.requested = false;
+
+    match benchmark.run(&gpu) {
+        Ok(runs) => {
+            for run in &runs {
+                info!(
+                    "Reduction '{}': {:?} ms, sum {} ({})",
+                    run.name,
+                    run.average_ms,
+                    run.sum,
+                    if run.correct { "correct" } else { "WRONG" }
+                );
+            }
+            results.runs = runs;
+        }
+        Err(e) => warn!("Reduction benchmark failed: {}", e),
+    }
+}
+
+fn reduction_panel(ctx: &egui::Context, world: &mut World) {
+    let benchmark = world.resource::<ReductionBenchmark>();
+    let (timestamps, variants) = (
+        benchmark.timestamps.is_some(),
+        benchmark
+            .variants
+            .iter()
+            .map(|variant| variant.name)
+            .collect::<Vec<_>>(),
+    );
+    let mut results = world.resource_mut::<ReductionResults>();
+
+    egui::Window::new(tr("Reduction benchmark"))
+        .id(egui::Id::new("Reduction benchmark"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.label(trf!(
+                "Sums {} M elements, {} runs per variant",
+                ELEMENTS >> 20,
+                ITERATIONS
+            ));
+            ui.label(trf!("Variants: {}", variants.join(", ")));
+            if !timestamps {
+                ui.label(tr(
+                    "Timestamp queries unsupported, only results are checked",
+                ));
+            }
+            if ui.button(tr("Run")).clicked() {
+                results.requested = true;
+            }
+            egui::Grid::new("reduction_results")
+                .striped(true)
+                .show(ui, |ui| {
+                    for run in &results.runs {
+                        ui.label(run.name);
+                        ui.label(
+                            run.average_ms
+                                .map_or("-".to_string(), |ms| format!("{:.3} ms", ms)),
+                        );
+                        ui.label(tr(if run.correct { "correct" } else { "wrong sum" }));
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+// =============================== RESOURCES ===============================
+pub struct ReductionRun {
+    pub name: &'static str,
+    /// `None` when the device has no timestamp queries.
+    pub average_ms: Option<f64>,
+    pub sum: u32,
+    pub correct: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct ReductionResults {
+    pub requested: bool,
+    pub runs: Vec<ReductionRun>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ReductionParams {
+    pub count: u32,
+    pub _padding: [u32; 3],
+}
+
+pub struct ReductionVariant {
+    pub name: &'static str,
+    pub pipeline: GPUComputePipeline,
+    pub workgroup_counts: [u32; 3],
+}
+
+struct Timestamps {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    period: f32,
+}
+
+// =============================== BENCHMARK ===============================
+/// Sums a large buffer with a naive global atomic per element, a workgroup
+/// shared memory tree, and subgroup operations when the device has them.
+#[derive(Resource)]
+pub struct ReductionBenchmark {
+    pub variants: Vec<ReductionVariant>,
+    bind_group: wgpu::BindGroup,
+    total: wgpu::Buffer,
+    sums_readback: wgpu::Buffer,
+    timestamps: Option<Timestamps>,
+    expected: u64,
+}
+impl ReductionBenchmark {
+    pub fn new(gpu: &GpuContext) -> Result<Self> {
+        let data: Vec<u32> = (0..ELEMENTS)
+            .map(|i| i.wrapping_mul(2654435761) >> 28)
+            .collect();
+        let expected = data.iter().map(|v| *v as u64).sum();
+
+        let input = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("reduction_input"),
+                contents: bytemuck::cast_slice(&data),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("reduction_params"),
+                contents: bytemuck::bytes_of(&ReductionParams {
+                    count: ELEMENTS,
+                    _padding: [0; 3],
+                }),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let total = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("reduction_total"),
+            size: 4,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    entry(0, wgpu::BufferBindingType::Storage { read_only: true }),
+                    entry(1, wgpu::BufferBindingType::Storage { read_only: false }),
+                    entry(2, wgpu::BufferBindingType::Uniform),
+                ],
+                label: Some("reduction_bind_group_layout"),
+            });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: input.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: total.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+            label: Some("reduction_bind_group"),
+        });
+
+        let source =
+            load_shader_source("reduction.wgsl", include_str!("../shaders/reduction.wgsl"));
+        let mut shaders = vec![
+            ("global atomics", source.clone(), "cs_global"),
+            ("shared memory", source, "cs_shared"),
+        ];
+        // Subgroup builtins fail validation on devices without the feature
+        if gpu.device.features().contains(wgpu::Features::SUBGROUP) {
+            shaders.push((
+                "subgroups",
+                load_shader_source(
+                    "reduction_subgroup.wgsl",
+                    include_str!("../shaders/reduction_subgroup.wgsl"),
+                ),
+                "cs_subgroup",
+            ));
+        }
+        let variants = shaders
+            .into_iter()
+            .map(|(name, source, entry_point)| {
+                let pipeline = GPUComputePipeline::new(
+                    &gpu.device,
+                    &format!("reduction_{}", entry_point),
+                    &source,
+                    entry_point,
+                    &[&layout],
+                    gpu.pipeline_cache(),
+                )?;
+                let workgroup_counts = DispatchSite {
+                    label: "reduction",
+                    domain: [ELEMENTS, 1, 1],
+                }
+                .validate(pipeline.workgroup_size, &gpu.device.limits())?;
+                Ok(ReductionVariant {
+                    name,
+                    pipeline,
+                    workgroup_counts,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let sums_readback = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("reduction_sums_readback"),
+            size: variants.len() as u64 * 4,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let timestamps = gpu
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let count = variants.len() as u32 * ITERATIONS * 2;
+                let size = count as u64 * wgpu::QUERY_SIZE as u64;
+                Timestamps {
+                    query_set: gpu.device.create_query_set(&wgpu::QuerySetDescriptor {
+                        label: Some("reduction_query_set"),
+                        ty: wgpu::QueryType::Timestamp,
+                        count,
+                    }),
+                    resolve_buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("reduction_resolve_buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    }),
+                    readback_buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("reduction_timestamp_readback"),
+                        size,
+                        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }),
+                    period: gpu.queue.get_timestamp_period(),
+                }
+            });
+
+        Ok(Self {
+            variants,
+            bind_group,
+            total,
+            sums_readback,
+            timestamps,
+            expected,
+        })
+    }
+
+    /// Records every variant `ITERATIONS` times, each in its own timed compute
+    /// pass, and blocks until the sums and timestamps are back.
+    pub fn run(&self, gpu: &GpuContext) -> Result<Vec<ReductionRun>> {
+        let _span = info_span!("reduction_benchmark").entered();
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("reduction_encoder"),
+            });
+        for (v, variant) in self.variants.iter().enumerate() {
+            for iteration in 0..ITERATIONS {
+                encoder.clear_buffer(&self.total, 0, None);
+                let first = (v as u32 * ITERATIONS + iteration) * 2;
+                let timestamp_writes =
+                    self.timestamps
+                        .as_ref()
+                        .map(|t| wgpu::ComputePassTimestampWrites {
+                            query_set: &t.query_set,
+                            beginning_of_pass_write_index: Some(first),
+                            end_of_pass_write_index: Some(first + 1),
+                        });
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some(variant.name),
+                    timestamp_writes,
+                });
+                compute_pass.set_pipeline(&variant.pipeline.pipeline);
+                compute_pass.set_bind_group(0, &self.bind_group, &[]);
+                let [x, y, z] = variant.workgroup_counts;
+                compute_pass.dispatch_workgroups(x, y, z);
+            }
+            encoder.copy_buffer_to_buffer(&self.total, 0, &self.sums_readback, v as u64 * 4, 4);
+        }
+        if let Some(t) = &self.timestamps {
+            let count = self.variants.len() as u32 * ITERATIONS * 2;
+            encoder.resolve_query_set(&t.query_set, 0..count, &t.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                &t.resolve_buffer,
+                0,
+                &t.readback_buffer,
+                0,
+                t.resolve_buffer.size(),
+            );
+        }
+        gpu.queue.submit(Some(encoder.finish()));
+
+        let sums: Vec<u32> = read_buffer(&gpu.device, &self.sums_readback)?;
+        let ticks: Option<Vec<u64>> = self
+            .timestamps
+            .as_ref()
+            .map(|t| read_buffer(&gpu.device, &t.readback_buffer))
+            .transpose()?;
+
+        Ok(self
+            .variants
+            .iter()
+            .enumerate()
+            .map(|(v, variant)| {
+                let average_ms = ticks
+                    .as_ref()
+                    .zip(self.timestamps.as_ref())
+                    .map(|(ticks, t)| {
+                        let range = v * ITERATIONS as usize * 2..(v + 1) * ITERATIONS as usize * 2;
+                        let total: u64 = ticks[range]
+                            .chunks_exact(2)
+                            .map(|pair| pair[1].saturating_sub(pair[0]))
+                            .sum();
+                        total as f64 * t.period as f64 / ITERATIONS as f64 / 1e6
+                    });
+                ReductionRun {
+                    name: variant.name,
+                    average_ms,
+                    sum: sums[v],
+                    correct: sums[v] as u64 == self.expected,
+                }
+            })
+            .collect())
+    }
+}
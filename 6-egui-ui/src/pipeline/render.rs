@@ -1,154 +1,480 @@
-use anyhow::Result;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
 use bevy_ecs::{
-    schedule::Schedule,
-    system::{Res, ResMut},
-    world::World,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::{Mut, World},
 };
-use egui_wgpu::ScreenDescriptor;
-use tracing::{error, info};
-use tracing_tracy::client::frame_name;
+use playground_app::FrameCapture;
+use tracing::{error, info_span, warn};
 
 use crate::{
     gpu::GpuContext,
-    pass::RenderPassBuilder,
-    time::TimeContext,
-    vertex::{self, VertexBuffers},
+    profiler::{GpuTimer, GpuZoneSettings, SubmissionTimeline, TraceCapture},
 };
 
 use super::{
-    depth::{DepthBindGroup, DepthPipeline, DepthTexture},
-    diffuse::{DiffuseBindGroup, DiffusePipeline},
-    present::{FrameBuffer, PresentBindGroup, PresentPipeline},
-    ui::{EguiRenderer, EguiState},
+    ao::ao_pass,
+    cascades::cascade_shadow_pass,
+    checkerboard::{checkerboard_pass, checkerboard_resolve_pass},
+    counters::gpu_counters_pass,
+    debug_draw::debug_draw_pass,
+    debug_view::debug_view_pass,
+    depth::depth_pass,
+    depth_precision::depth_precision_pass,
+    diffuse::diffuse_pass,
+    dof::coc_pass,
+    filtering::filtering_demo_pass,
+    geometry_debug::geometry_debug_pass,
+    god_rays::god_rays_pass,
+    graph::{
+        color, copy_dst, copy_src, depth, read, sample, write, AsyncComputeSettings,
+        GraphValidationSettings, PassQueue, RecordedPass, RenderGraph,
+    },
+    histogram::histogram_pass,
+    hud::hud_pass,
+    inspector::texture_inspector_pass,
+    life::life_pass,
+    loading::loading_pass,
+    marching_cubes::{marching_cubes_draw_pass, marching_cubes_pass},
+    mesh::mesh_pass,
+    particles::{particle_draw_pass, particle_simulate_pass},
+    paths::paths_pass,
+    present::present_pass,
+    procedural::procedural_pass,
+    shadow::spot_shadow_pass,
+    ssr::ssr_pass,
+    ui::ui_pass,
+    validation::validation_pass,
+    visibility::visibility_pass,
+    volume::volume_pass,
 };
 
-pub fn setup_rendering(_world: &mut World, schedule: &mut Schedule) -> Result<()> {
-    schedule.add_systems(render_system);
+pub fn setup_rendering(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    world.insert_resource(render_graph());
+    world.insert_resource(AsyncComputeSettings::default());
+    world.insert_resource(GraphValidationSettings::default());
+    world.insert_resource(FrameTarget::default());
+    world.insert_resource(FrameCommands::default());
+    world.insert_resource(AcquireSettings::default());
+    world.insert_resource(AcquireStats::default());
+    let surface = world.resource::<GpuContext>().surface.clone();
+    world.insert_resource(
+        SurfaceAcquirer::spawn(surface).context("Failed to start the surface acquire thread")?,
+    );
+
+    schedule.add_systems((acquire_system, render_system, submit_system).chain());
     Ok(())
 }
 
-pub fn render_system(
-    time: Res<TimeContext>,
-    gpu: Res<GpuContext>,
-    depth: Res<DepthTexture>,
-    diffuse_bind_group: Res<DiffuseBindGroup>,
-    diffuse_pipeline: Res<DiffusePipeline>,
-    depth_bind_group: Res<DepthBindGroup>,
-    depth_pipeline: Res<DepthPipeline>,
-    present_bind_group: Res<PresentBindGroup>,
-    present_pipeline: Res<PresentPipeline>,
-    vertex_buffers: Res<VertexBuffers>,
-    frame_buffer: Res<FrameBuffer>,
-    mut ui: ResMut<EguiState>,
-) {
-    let mut f = || -> Result<()> {
-        let output = gpu.surface.get_current_texture()?;
-        let view = output.texture.create_view(&Default::default());
-
-        // Update the vertex buffer with new data
-        let new_vertices = vertex::rotated_vertices(time.total);
-        gpu.queue.write_buffer(
-            &vertex_buffers.vertex_buffer,
-            0,
-            bytemuck::cast_slice(&new_vertices),
-        );
-
-        let mut encoder = gpu
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("render_encoder"),
-            });
+/// Every pass of a frame and the resources it touches, in recording order.
+pub fn render_graph() -> RenderGraph {
+    let mut graph = RenderGraph::default();
+    graph
+        .add_pass("procedural", procedural_pass)
+        .uses(&[write("procedural")])
+        .add_pass("life", life_pass)
+        .uses(&[write("life")])
+        .add_async_compute_pass("marching_cubes", marching_cubes_pass)
+        .uses(&[write("marching_cubes_vertices"), write("gpu_counters")])
+        .add_pass("spot_shadows", spot_shadow_pass)
+        .uses(&[depth("shadow_atlas")])
+        .add_pass("cascade_shadows", cascade_shadow_pass)
+        .uses(&[depth("shadow_cascades")])
+        .add_pass("diffuse", diffuse_pass)
+        .uses(&[color("frame"), depth("depth")])
+        .add_pass("filtering_demo", filtering_demo_pass)
+        .uses(&[color("frame"), depth("depth")])
+        .add_pass("checkerboard", checkerboard_pass)
+        .uses(&[copy_src("frame"), copy_dst("checkerboard_background")])
+        .uses(&[
+            color("checkerboard_half"),
+            depth("checkerboard_half_depth"),
+            sample("shadow_atlas"),
+            sample("shadow_cascades"),
+        ])
+        .add_pass("mesh", mesh_pass)
+        .uses(&[
+            color("frame"),
+            color("velocity"),
+            color("surface"),
+            depth("depth"),
+            sample("shadow_atlas"),
+            sample("shadow_cascades"),
+        ])
+        .add_pass("checkerboard_resolve", checkerboard_resolve_pass)
+        .uses(&[
+            sample("checkerboard_half"),
+            sample("checkerboard_half_depth"),
+            sample("checkerboard_history"),
+            sample("checkerboard_background"),
+            sample("frame"),
+            write("checkerboard_resolved"),
+            write("checkerboard_display"),
+        ])
+        .uses(&[copy_src("checkerboard_display"), copy_dst("frame")])
+        .add_pass("ao", ao_pass)
+        .uses(&[sample("depth"), depth("scaled_depth")])
+        .uses(&[sample("scaled_depth"), sample("surface"), color("ao")])
+        .uses(&[
+            sample("ao"),
+            sample("scaled_depth"),
+            sample("depth"),
+            color("frame"),
+        ])
+        .add_pass("ssr", ssr_pass)
+        .uses(&[copy_src("frame"), copy_dst("ssr_scene")])
+        .uses(&[
+            sample("depth"),
+            sample("surface"),
+            sample("ssr_scene"),
+            color("frame"),
+        ])
+        .add_pass("visibility", visibility_pass)
+        .uses(&[color("visibility_ids"), depth("visibility_depth")])
+        .uses(&[sample("visibility_ids"), color("visibility_shaded")])
+        .add_pass("marching_cubes_draw", marching_cubes_draw_pass)
+        .uses(&[
+            read("marching_cubes_vertices"),
+            color("frame"),
+            depth("depth"),
+        ])
+        .add_async_compute_pass("particle_simulate", particle_simulate_pass)
+        .uses(&[write("particles"), sample("depth"), write("gpu_counters")])
+        .add_pass("particle_draw", particle_draw_pass)
+        .uses(&[read("particles"), color("frame"), depth("depth")])
+        .add_pass("volume", volume_pass)
+        .uses(&[sample("depth"), color("frame")])
+        .uses(&[sample("depth"), depth("scaled_depth")])
+        .uses(&[sample("scaled_depth"), color("volume")])
+        .uses(&[
+            sample("volume"),
+            sample("scaled_depth"),
+            sample("depth"),
+            color("frame"),
+        ])
+        .add_pass("god_rays", god_rays_pass)
+        .uses(&[sample("depth"), depth("scaled_depth")])
+        .uses(&[sample("scaled_depth"), color("god_rays")])
+        .uses(&[
+            sample("god_rays"),
+            sample("scaled_depth"),
+            sample("depth"),
+            color("frame"),
+        ])
+        .add_pass("validation", validation_pass)
+        .uses(&[sample("frame"), write("validation_mask")])
+        .uses(&[sample("validation_mask"), color("frame")])
+        .add_pass("debug_view", debug_view_pass)
+        .uses(&[color("debug_view_counts")])
+        .uses(&[sample("debug_view_counts"), color("frame")])
+        .add_pass("debug_draw", debug_draw_pass)
+        .uses(&[color("frame"), depth("depth")])
+        .add_pass("geometry_debug", geometry_debug_pass)
+        .uses(&[color("frame"), depth("depth")])
+        .add_pass("depth", depth_pass)
+        .uses(&[sample("depth"), color("frame")])
+        .add_pass("depth_precision", depth_precision_pass)
+        .uses(&[color("frame"), depth("depth_precision")])
+        .add_pass("texture_inspector", texture_inspector_pass)
+        .uses(&[color("inspector_preview")])
+        .add_pass("coc", coc_pass)
+        .uses(&[sample("depth"), color("coc")])
+        .add_pass("histogram", histogram_pass)
+        .uses(&[sample("frame"), write("histogram")])
+        .add_pass("paths", paths_pass)
+        .uses(&[color("frame")])
+        .add_pass("hud", hud_pass)
+        .uses(&[color("frame")])
+        .add_pass("present", present_pass)
+        .uses(&[
+            sample("frame"),
+            sample("velocity"),
+            sample("coc"),
+            color("swapchain"),
+        ])
+        .add_pass("loading", loading_pass)
+        .uses(&[color("swapchain")])
+        .add_pass("ui", ui_pass)
+        .uses(&[color("swapchain")])
+        .add_pass("gpu_counters", gpu_counters_pass)
+        .uses(&[copy_src("gpu_counters")])
+        .uses(&[copy_dst("gpu_counters")]);
+    graph
+}
+
+// =============================== FRAME ===============================
+/// The surface texture the frame renders to. Empty when no frame is drawn,
+/// e.g. while minimized or after the surface was lost.
+#[derive(Resource, Default)]
+pub struct FrameTarget {
+    pub acquired: Option<AcquiredFrame>,
+}
+
+pub struct AcquiredFrame {
+    pub output: wgpu::SurfaceTexture,
+    pub view: wgpu::TextureView,
+    pub start: Instant,
+}
 
-        // DRAWING DIFFUSE
-        {
-            let _guard = tracing_tracy::client::Client::running()
-                .expect("client must be running")
-                .non_continuous_frame(frame_name!("diffuse"));
-            let mut render_pass = RenderPassBuilder::new(&mut encoder)
-                .with_label("diffuse_render_pass")
-                .with_color_view(&frame_buffer.texture.view)
-                .with_depth(&depth.texture.view, 1.0)
-                .build()?;
-
-            render_pass.set_pipeline(&diffuse_pipeline.pipeline.render_pipeline);
-            render_pass.set_bind_group(0, &diffuse_bind_group.bind_group, &[]);
-            render_pass.set_vertex_buffer(0, vertex_buffers.vertex_buffer.slice(..));
-            render_pass.draw(0..vertex_buffers.num_vertices, 0..1);
+/// Command buffers recorded for the frame, waiting for [`submit_system`].
+#[derive(Resource, Default)]
+pub struct FrameCommands {
+    pub passes: Vec<RecordedPass>,
+}
+
+// =============================== ACQUIRE POLICY ===============================
+/// How long the frame may wait on the compositor for a surface texture.
+#[derive(Resource, Clone, PartialEq)]
+pub struct AcquireSettings {
+    /// The frame is skipped once the acquire took longer than this, so the
+    /// rest of the schedule keeps up with a stuck compositor. See
+    /// [`SurfaceAcquirer`].
+    pub timeout_ms: f32,
+    /// Timeouts reported by wgpu in a row before the surface is configured
+    /// again. wgpu itself gives up after about a second.
+    pub reconfigure_after: u32,
+}
+impl Default for AcquireSettings {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 250.0,
+            reconfigure_after: 3,
         }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct AcquireStats {
+    pub acquired: u64,
+    /// Frames not rendered because the acquire timed out.
+    pub skipped: u64,
+    /// Frames skipped because no texture came within the timeout.
+    pub timeouts: u64,
+    pub reconfigures: u64,
+    /// Timeouts since the last successful acquire.
+    pub failures_in_row: u32,
+    pub last_ms: f32,
+    /// Longest successful acquire so far, a slow compositor shows up here.
+    pub slowest_ms: f32,
+}
+
+type Acquired = Result<wgpu::SurfaceTexture, wgpu::SurfaceError>;
+
+/// Acquires surface textures on a thread of its own. wgpu blocks until the
+/// compositor hands one out and can't be told to give up sooner, so the
+/// frame waits on a channel instead. Every request brings the channel to
+/// answer on; once the frame stopped waiting it's gone, and the late texture
+/// is dropped, which hands it back without presenting.
+#[derive(Resource)]
+pub struct SurfaceAcquirer {
+    requests: mpsc::Sender<mpsc::SyncSender<Acquired>>,
+    /// Set while the thread is inside an acquire.
+    busy: Arc<AtomicBool>,
+}
+impl SurfaceAcquirer {
+    pub fn spawn(surface: Arc<wgpu::Surface<'static>>) -> std::io::Result<Self> {
+        let (requests, receiver) = mpsc::channel::<mpsc::SyncSender<Acquired>>();
+        let busy = Arc::new(AtomicBool::new(false));
+        let thread_busy = busy.clone();
+        std::thread::Builder::new()
+            .name("surface-acquire".to_string())
+            .spawn(move || {
+                for reply in receiver {
+                    let acquired = surface.get_current_texture();
+                    thread_busy.store(false, Ordering::Release);
+                    let _ = reply.send(acquired);
+                }
+            })?;
+        Ok(Self { requests, busy })
+    }
 
-        // DRAWING DEPTH
-        {
-            let _guard = tracing_tracy::client::Client::running()
-                .expect("client must be running")
-                .non_continuous_frame(frame_name!("depth"));
-            let mut render_pass = RenderPassBuilder::new(&mut encoder)
-                .with_label("depth_render_pass")
-                .with_color_view(&frame_buffer.texture.view)
-                .build()?;
-
-            render_pass.set_pipeline(&depth_pipeline.pipeline.render_pipeline);
-            render_pass.set_bind_group(0, &depth_bind_group.bind_group, &[]);
-            render_pass.set_vertex_buffer(0, vertex_buffers.depth_vertex_buffer.slice(..));
-            render_pass.draw(0..vertex_buffers.num_depth_vertices, 0..1);
+    /// `None` when nothing came within `timeout`, or the previous acquire is
+    /// still stuck. Requests don't queue up behind a stuck one.
+    fn acquire(&self, timeout: Duration) -> Option<Acquired> {
+        if self.busy.swap(true, Ordering::AcqRel) {
+            return None;
         }
+        let (reply, answer) = mpsc::sync_channel(1);
+        if self.requests.send(reply).is_err() {
+            self.busy.store(false, Ordering::Release);
+            return None;
+        }
+        answer.recv_timeout(timeout).ok()
+    }
+}
 
-        // UI
-        let _guard = tracing_tracy::client::Client::running()
-            .expect("client must be running")
-            .non_continuous_frame(frame_name!("ui"));
-        ui.renderer.begin_frame(&gpu.window);
-        ui.run_app();
-        let frame_buffer_size = frame_buffer.texture.texture.size();
-        let screen_descriptor = ScreenDescriptor {
-            size_in_pixels: [frame_buffer_size.width, frame_buffer_size.height],
-            pixels_per_point: gpu.window.scale_factor() as f32,
-        };
-        ui.renderer.end_frame_and_draw(
-            &gpu.device,
-            &gpu.queue,
-            &mut encoder,
-            &gpu.window,
-            &frame_buffer.texture.view,
-            screen_descriptor,
-        );
-
-        drop(_guard);
-
-        // PRESENT
-        {
-            let _guard = tracing_tracy::client::Client::running()
-                .expect("client must be running")
-                .non_continuous_frame(frame_name!("present"));
-            let mut render_pass = RenderPassBuilder::new(&mut encoder)
-                .with_label("present_render_pass")
-                .with_color_view(&view)
-                .build()?;
-
-            render_pass.set_pipeline(&present_pipeline.pipeline.render_pipeline);
-            render_pass.set_bind_group(0, &present_bind_group.bind_group, &[]);
-            render_pass.draw(0..6, 0..1);
+// =============================== STAGES ===============================
+/// Acquires the surface texture and starts the frame's GPU timings. See
+/// [`AcquireSettings`] for what happens when the compositor is slow.
+pub fn acquire_system(
+    gpu: Res<GpuContext>,
+    (capture, zones): (Option<Res<TraceCapture>>, Res<GpuZoneSettings>),
+    (timer, mut timeline): (Option<ResMut<GpuTimer>>, ResMut<SubmissionTimeline>),
+    (settings, mut stats): (Res<AcquireSettings>, ResMut<AcquireStats>),
+    (acquirer, mut target): (Res<SurfaceAcquirer>, ResMut<FrameTarget>),
+) {
+    target.acquired = None;
+    if gpu.is_minimized() {
+        return;
+    }
+    let started = Instant::now();
+    let acquired = acquirer.acquire(Duration::from_secs_f32(settings.timeout_ms / 1000.0));
+    stats.last_ms = started.elapsed().as_secs_f32() * 1000.0;
+    // Not reconfigured, that would wait for the acquire still in flight
+    let Some(acquired) = acquired else {
+        stats.skipped += 1;
+        stats.timeouts += 1;
+        return;
+    };
+    let output = match acquired {
+        Ok(output) => output,
+        Err(wgpu::SurfaceError::Timeout) => {
+            stats.skipped += 1;
+            stats.failures_in_row += 1;
+            if stats.failures_in_row >= settings.reconfigure_after.max(1) {
+                warn!(
+                    "Surface acquire timed out {} frames in a row, reconfiguring",
+                    stats.failures_in_row
+                );
+                stats.failures_in_row = 0;
+                stats.reconfigures += 1;
+                gpu.reconfigure();
+            }
+            return;
+        }
+        // Happens around display mode switches, the next frame gets a
+        // fresh surface
+        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+            stats.reconfigures += 1;
+            gpu.reconfigure();
+            return;
         }
+        Err(e) => {
+            error!("Error during rendering: {:?}", e);
+            return;
+        }
+    };
+    stats.acquired += 1;
+    stats.failures_in_row = 0;
+    stats.slowest_ms = stats.slowest_ms.max(stats.last_ms);
+    let view = output.texture.create_view(&Default::default());
 
-        let _encoder_guard = tracing_tracy::client::Client::running()
-            .expect("client must be running")
-            .non_continuous_frame(frame_name!("encode"));
-        gpu.queue.submit(std::iter::once(encoder.finish()));
-        drop(_encoder_guard);
+    let capturing = capture.is_some_and(|capture| capture.is_capturing());
+    if let Some(mut timer) = timer {
+        timer.begin_frame(capturing || zones.tracy);
+    }
+    timeline.begin_frame();
 
-        let _present_guard = tracing_tracy::client::Client::running()
-            .expect("client must be running")
-            .non_continuous_frame(frame_name!("presenting"));
-        output.present();
-        drop(_present_guard);
+    target.acquired = Some(AcquiredFrame {
+        output,
+        view,
+        start: Instant::now(),
+    });
+}
 
-        tracing_tracy::client::Client::running()
-            .expect("client must be running")
-            .frame_mark();
+/// Records the render graph into [`FrameCommands`]. A failing pass drops the
+/// whole frame.
+pub fn render_system(world: &mut World) {
+    world.resource_scope(|world, mut target: Mut<FrameTarget>| {
+        let Some(frame) = &target.acquired else {
+            return;
+        };
+        let async_compute = world.resource::<AsyncComputeSettings>().enabled;
+        let validate = world.resource::<GraphValidationSettings>().enabled;
+        let recorded = world.resource_scope(|world, mut graph: Mut<RenderGraph>| {
+            graph.record(
+                world,
+                &frame.output.texture,
+                &frame.view,
+                async_compute,
+                validate,
+            )
+        });
+        let mut passes = match recorded {
+            Ok(passes) => passes,
+            Err(e) => {
+                error!("Error during rendering: {:?}", e);
+                target.acquired = None;
+                return;
+            }
+        };
 
-        Ok(())
+        if let Some(timer) = world.get_resource::<GpuTimer>() {
+            let mut encoder = world
+                .resource::<GpuContext>()
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("timestamp_resolve"),
+                });
+            timer.resolve(&mut encoder);
+            passes.push(RecordedPass {
+                queue: PassQueue::Graphics,
+                commands: encoder.finish(),
+            });
+        }
+        world.resource_mut::<FrameCommands>().passes = passes;
+    });
+}
+
+/// Submits the recorded command buffers and presents the frame.
+pub fn submit_system(world: &mut World) {
+    let Some(frame) = world.resource_mut::<FrameTarget>().acquired.take() else {
+        return;
     };
+    let passes = std::mem::take(&mut world.resource_mut::<FrameCommands>().passes);
 
-    if let Err(e) = f() {
-        error!("Error during rendering: {:?}", e);
+    {
+        let _span = info_span!("submit").entered();
+        // The compute work goes first. wgpu runs both on its one queue in
+        // submission order, so what's measurable here is the fence of
+        // each part rather than true overlap
+        let (compute, graphics): (Vec<_>, Vec<_>) = passes
+            .into_iter()
+            .partition(|pass| pass.queue == PassQueue::AsyncCompute);
+        world.resource_scope(|world, mut timeline: Mut<SubmissionTimeline>| {
+            let queue = &world.resource::<GpuContext>().queue;
+            if !compute.is_empty() {
+                timeline.submit(
+                    queue,
+                    PassQueue::AsyncCompute,
+                    compute.into_iter().map(|pass| pass.commands),
+                );
+            }
+            timeline.submit(
+                queue,
+                PassQueue::Graphics,
+                graphics.into_iter().map(|pass| pass.commands),
+            );
+        });
+    }
+    world.resource::<GpuContext>().poll_after_submit();
+    if world.contains_resource::<GpuTimer>() {
+        world.resource_scope(|world, mut timer: Mut<GpuTimer>| {
+            let timings = timer.read_back(&world.resource::<GpuContext>().device);
+            if let Some(capture) = world.get_resource::<TraceCapture>() {
+                capture.record_gpu(frame.start, &timings);
+            }
+        });
+    }
+    if world
+        .get_resource::<FrameCapture>()
+        .is_some_and(|c| c.is_due())
+    {
+        world.resource_scope(|world, mut capture: Mut<FrameCapture>| {
+            let gpu = world.resource::<GpuContext>();
+            capture.capture(&gpu.device, &gpu.queue, &frame.output.texture);
+        });
+    }
+    {
+        let _span = info_span!("presenting").entered();
+        frame.output.present();
     }
 }
@@ -0,0 +1,412 @@
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use wgpu::util::DeviceExt;
+
+use crate::{gpu::GpuContext, i18n::tr, pass::RenderPassBuilder, scene::Camera, texture::Texture};
+
+use super::{
+    depth::DepthTexture, inspector::TextureRegistry, present::render_scale_system,
+    render::render_system, GPUPipeline, GPUPipelineBuilder,
+};
+
+pub fn setup_scaled_depth(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let depth = world
+        .get_resource::<DepthTexture>()
+        .ok_or_else(|| anyhow::anyhow!("DepthTexture resource not found"))?;
+
+    let scaled = ScaledDepth::new(gpu, depth)?;
+    world.insert_resource(scaled);
+    let mut registry = world.get_resource_or_insert_with(TextureRegistry::default);
+    registry.register("depth_half", |world| {
+        world
+            .get_resource::<ScaledDepth>()
+            .map(|scaled| &scaled.half.texture)
+    });
+    registry.register("depth_quarter", |world| {
+        world
+            .get_resource::<ScaledDepth>()
+            .map(|scaled| &scaled.quarter.texture)
+    });
+
+    schedule.add_systems((
+        scaled_depth_resize_system
+            .run_if(resource_changed::<DepthTexture>)
+            .after(render_scale_system)
+            .before(render_system),
+        scaled_depth_camera_system
+            .run_if(resource_changed::<Camera>)
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Follows the full resolution depth texture. Passes that bind the scaled
+/// targets have to run after this.
+pub fn scaled_depth_resize_system(
+    gpu: Res<GpuContext>,
+    depth: Res<DepthTexture>,
+    mut scaled: ResMut<ScaledDepth>,
+) {
+    let size = depth.texture.texture.size();
+    let (width, height) = ResolutionScale::Half.size(size.width, size.height);
+    scaled.half = Texture::depth_texture(&gpu.device, width, height);
+    let (width, height) = ResolutionScale::Quarter.size(size.width, size.height);
+    scaled.quarter = Texture::depth_texture(&gpu.device, width, height);
+    scaled.half_bind_group =
+        ScaledDepth::source_bind_group(&gpu, &scaled.layout, &depth.texture.view);
+    scaled.quarter_bind_group =
+        ScaledDepth::source_bind_group(&gpu, &scaled.layout, &scaled.half.view);
+}
+
+pub fn scaled_depth_camera_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    scaled: Res<ScaledDepth>,
+) {
+    gpu.queue.write_buffer(
+        &scaled.camera,
+        0,
+        bytemuck::bytes_of(&UpsampleCamera::new(&camera)),
+    );
+}
+
+// =============================== SCALE ===============================
+/// Resolution an effect renders at, relative to the frame buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ResolutionScale {
+    Full,
+    Half,
+    Quarter,
+}
+impl ResolutionScale {
+    pub const ALL: [Self; 3] = [Self::Full, Self::Half, Self::Quarter];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Full => "Full",
+            Self::Half => "Half",
+            Self::Quarter => "Quarter",
+        }
+    }
+
+    pub fn divisor(&self) -> u32 {
+        match self {
+            Self::Full => 1,
+            Self::Half => 2,
+            Self::Quarter => 4,
+        }
+    }
+
+    /// Rounded up, so every full resolution pixel has a texel to read.
+    pub fn size(&self, width: u32, height: u32) -> (u32, u32) {
+        let divisor = self.divisor();
+        (width.div_ceil(divisor), height.div_ceil(divisor))
+    }
+
+    pub fn combo(&mut self, ui: &mut egui::Ui, id: &str) -> bool {
+        let mut changed = false;
+        egui::ComboBox::from_id_salt(id)
+            .selected_text(tr(self.name()))
+            .show_ui(ui, |ui| {
+                for scale in Self::ALL {
+                    changed |= ui.selectable_value(self, scale, tr(scale.name())).changed();
+                }
+            });
+        changed
+    }
+}
+
+// =============================== DEPTH ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UpsampleCamera {
+    pub near: f32,
+    pub far: f32,
+    pub _padding: [f32; 2],
+}
+impl UpsampleCamera {
+    pub fn new(camera: &Camera) -> Self {
+        Self {
+            near: camera.near,
+            far: camera.far,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// The depth buffer at half and quarter resolution, for effects rendered at
+/// those scales to test against and to upsample with.
+///
+/// Not a graph pass of its own: geometry drawn after the mesh pass also
+/// writes depth, so every effect calls [`ScaledDepth::downsample`] right
+/// before it needs the result.
+#[derive(Resource)]
+pub struct ScaledDepth {
+    pub half: Texture,
+    pub quarter: Texture,
+    /// Near and far planes for the upsample's depth weights.
+    pub camera: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    /// Reads the full resolution depth.
+    half_bind_group: wgpu::BindGroup,
+    /// Reads the half resolution depth.
+    quarter_bind_group: wgpu::BindGroup,
+    pipeline: GPUPipeline,
+}
+impl ScaledDepth {
+    pub fn new(gpu: &GpuContext, depth: &DepthTexture) -> Result<Self> {
+        let size = depth.texture.texture.size();
+        let (width, height) = ResolutionScale::Half.size(size.width, size.height);
+        let half = Texture::depth_texture(&gpu.device, width, height);
+        let (width, height) = ResolutionScale::Quarter.size(size.width, size.height);
+        let quarter = Texture::depth_texture(&gpu.device, width, height);
+        let camera = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("upsample_camera"),
+                contents: bytemuck::bytes_of(&UpsampleCamera::new(&Camera::default())),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                }],
+                label: Some("depth_downsample_bind_group_layout"),
+            });
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("depth_downsample_shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/depth_downsample.wgsl").into(),
+                ),
+            });
+        let pipeline = GPUPipelineBuilder::new(&gpu.device)
+            .label("depth_downsample_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .depth_stencil_state(Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }))
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState::default())
+            .build()?;
+
+        let half_bind_group = Self::source_bind_group(gpu, &layout, &depth.texture.view);
+        let quarter_bind_group = Self::source_bind_group(gpu, &layout, &half.view);
+
+        Ok(Self {
+            half,
+            quarter,
+            camera,
+            layout,
+            half_bind_group,
+            quarter_bind_group,
+            pipeline,
+        })
+    }
+
+    fn source_bind_group(
+        gpu: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            }],
+            label: Some("depth_downsample_bind_group"),
+        })
+    }
+
+    /// Depth to render an effect at `scale` against.
+    pub fn view<'a>(
+        &'a self,
+        scale: ResolutionScale,
+        depth: &'a DepthTexture,
+    ) -> &'a wgpu::TextureView {
+        match scale {
+            ResolutionScale::Full => &depth.texture.view,
+            ResolutionScale::Half => &self.half.view,
+            ResolutionScale::Quarter => &self.quarter.view,
+        }
+    }
+
+    /// Brings the targets `scale` needs up to date with the depth buffer,
+    /// a quarter goes through half.
+    pub fn downsample(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        scale: ResolutionScale,
+    ) -> Result<()> {
+        let steps = match scale {
+            ResolutionScale::Full => return Ok(()),
+            ResolutionScale::Half => 1,
+            ResolutionScale::Quarter => 2,
+        };
+        let targets = [
+            (&self.half, &self.half_bind_group),
+            (&self.quarter, &self.quarter_bind_group),
+        ];
+        for (target, bind_group) in targets.into_iter().take(steps) {
+            let mut render_pass = RenderPassBuilder::new(encoder)
+                .with_label("depth_downsample")
+                .with_depth(&target.view, 1.0)
+                .build()?;
+            render_pass.set_pipeline(&self.pipeline.render_pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        Ok(())
+    }
+}
+
+// =============================== UPSAMPLE ===============================
+/// Draws a reduced resolution effect onto a full resolution target with a
+/// depth aware bilinear filter. Every effect owns one, built with the blend
+/// state it composites with.
+pub struct Upsampler {
+    pub layout: wgpu::BindGroupLayout,
+    pub pipeline: GPUPipeline,
+}
+impl Upsampler {
+    pub fn new(
+        gpu: &GpuContext,
+        label: &str,
+        format: wgpu::TextureFormat,
+        blend: Option<wgpu::BlendState>,
+    ) -> Result<Self> {
+        let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type,
+            },
+            count: None,
+        };
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    texture(0, wgpu::TextureSampleType::Float { filterable: false }),
+                    texture(1, wgpu::TextureSampleType::Depth),
+                    texture(2, wgpu::TextureSampleType::Depth),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("upsample_bind_group_layout"),
+            });
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("bilateral_upsample_shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/bilateral_upsample.wgsl").into(),
+                ),
+            });
+        let pipeline = GPUPipelineBuilder::new(&gpu.device)
+            .label(label)
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .color_target(wgpu::ColorTargetState {
+                format,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .depth_stencil_state(None)
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState::default())
+            .build()?;
+
+        Ok(Self { layout, pipeline })
+    }
+
+    /// `source` has to be the size of the `scale` depth target.
+    pub fn bind_group(
+        &self,
+        gpu: &GpuContext,
+        source: &Texture,
+        scaled: &ScaledDepth,
+        scale: ResolutionScale,
+        depth: &DepthTexture,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&source.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(scaled.view(scale, depth)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&depth.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: scaled.camera.as_entire_binding(),
+                },
+            ],
+            label: Some("upsample_bind_group"),
+        })
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        bind_group: &wgpu::BindGroup,
+    ) -> Result<()> {
+        let mut render_pass = RenderPassBuilder::new(encoder)
+            .with_label("bilateral_upsample")
+            .with_color_view(target)
+            .load()
+            .build()?;
+        render_pass.set_pipeline(&self.pipeline.render_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use bevy_ecs::{
+    entity::Entity,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Query, Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+
+use crate::{
+    assets::AssetServer,
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    lights::{light_gathering_system, SpotLight},
+    pass::RenderPassBuilder,
+    scene::{
+        transform_propagation_system, BlendMode, Camera, GlobalTransform, MaterialTable, MeshId,
+        Renderable,
+    },
+    texture::Texture,
+    vertex::MeshVertex,
+};
+
+use super::{
+    arena::{frame_arena_upload_system, FrameArena},
+    graph::PassContext,
+    inspector::TextureRegistry,
+    mesh::{Meshes, ObjectUniform},
+    render::render_system,
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+pub const ATLAS_SIZE: u32 = 2048;
+pub const MAX_SHADOWED_SPOTS: usize = 16;
+const SHADOW_NEAR: f32 = 0.1;
+
+pub fn setup_shadows(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let arena = world
+        .get_resource::<FrameArena>()
+        .ok_or_else(|| anyhow::anyhow!("FrameArena resource not found"))?;
+
+    let atlas = ShadowAtlas::new(gpu);
+    let pipeline = ShadowPipeline::new(gpu, &atlas, arena)?;
+
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("shadow_atlas", |world| {
+            world
+                .get_resource::<ShadowAtlas>()
+                .map(|atlas| &atlas.texture.texture)
+        });
+    world.insert_resource(atlas);
+    world.insert_resource(ShadowCasters::default());
+    world.insert_resource(pipeline);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(shadow_panel);
+
+    schedule.add_systems(
+        (
+            shadow_allocation_system.before(light_gathering_system),
+            shadow_caster_system.before(frame_arena_upload_system),
+        )
+            .after(transform_propagation_system)
+            .before(render_system),
+    );
+
+    Ok(())
+}
+
+/// Renders every shadowed spot light into its own tile of the atlas.
+pub fn spot_shadow_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let atlas = world.resource::<ShadowAtlas>();
+    let casters = world.resource::<ShadowCasters>();
+    let pipeline = world.resource::<ShadowPipeline>();
+    let meshes = world.resource::<Meshes>();
+    let assets = world.resource::<AssetServer>();
+    let arena = world.resource::<FrameArena>();
+
+    // Clears the whole atlas, so tiles of lights that lost their shadow are reset too
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_depth(&atlas.texture.view, 1.0)
+        .build()?;
+
+    render_pass.set_pipeline(&pipeline.pipeline.render_pipeline);
+    let mut slots: Vec<&ShadowSlot> = atlas.slots.values().collect();
+    slots.sort_by_key(|slot| slot.index);
+    for slot in slots {
+        let [x, y, size] = slot.rect;
+        render_pass.set_viewport(x as f32, y as f32, size as f32, size as f32, 0.0, 1.0);
+        render_pass.set_scissor_rect(x, y, size, size);
+        render_pass.set_bind_group(
+            0,
+            &atlas.views_bind_group,
+            &[slot.index * atlas.view_stride],
+        );
+        draw_shadow_casters(&mut render_pass, casters, meshes, assets, arena)?;
+    }
+
+    Ok(())
+}
+
+/// Draws every caster with the shadow pipeline and view already bound.
+pub fn draw_shadow_casters(
+    render_pass: &mut wgpu::RenderPass,
+    casters: &ShadowCasters,
+    meshes: &Meshes,
+    assets: &AssetServer,
+    arena: &FrameArena,
+) -> Result<()> {
+    let mut current_mesh = None;
+    for (mesh_id, offset) in &casters.draws {
+        let mesh = meshes.get(assets, *mesh_id)?;
+        if current_mesh != Some(*mesh_id) {
+            mesh.bind(render_pass);
+            current_mesh = Some(*mesh_id);
+        }
+        render_pass.set_bind_group(1, arena.bind_group(), &[*offset]);
+        mesh.draw(render_pass, 0..1);
+    }
+    Ok(())
+}
+
+fn shadow_panel(ctx: &egui::Context, world: &mut World) {
+    let atlas = world.resource::<ShadowAtlas>();
+    let casters = world.resource::<ShadowCasters>();
+    let mut slots: Vec<&ShadowSlot> = atlas.slots.values().collect();
+    slots.sort_by_key(|slot| slot.index);
+
+    egui::Window::new(tr("Shadows"))
+        .id(egui::Id::new("Shadows"))
+        .show(ctx, |ui| {
+            ui.label(trf!(
+                "Atlas: {0}x{0}, {1} casters",
+                ATLAS_SIZE,
+                casters.draws.len()
+            ));
+            for slot in slots {
+                let [x, y, size] = slot.rect;
+                ui.label(trf!(
+                    "Spot {}: {}px tile at ({}, {})",
+                    slot.index,
+                    size,
+                    x,
+                    y
+                ));
+            }
+            if atlas.unallocated > 0 {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    trf!("{} spot lights without a tile", atlas.unallocated),
+                );
+            }
+        });
+}
+
+// =============================== ALLOCATION ===============================
+/// Quadtree allocator handing out power-of-two square tiles. Allocating from
+/// largest to smallest packs the atlas without gaps.
+pub struct AtlasAllocator {
+    size: u32,
+    free: Vec<[u32; 3]>,
+}
+impl AtlasAllocator {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            free: vec![[0, 0, size]],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.free.clear();
+        self.free.push([0, 0, self.size]);
+    }
+
+    /// Returns `[x, y, size]` of a free tile, splitting the smallest free
+    /// tile that fits.
+    pub fn allocate(&mut self, size: u32) -> Option<[u32; 3]> {
+        let (index, _) = self
+            .free
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| tile[2] >= size)
+            .min_by_key(|(_, tile)| tile[2])?;
+        let [x, y, mut tile] = self.free.remove(index);
+        while tile > size {
+            tile /= 2;
+            self.free.push([x + tile, y, tile]);
+            self.free.push([x, y + tile, tile]);
+            self.free.push([x + tile, y + tile, tile]);
+        }
+        Some([x, y, size])
+    }
+}
+
+/// Tile size for the spot light ranked `rank` by importance.
+fn tile_size(rank: usize) -> u32 {
+    match rank {
+        0 => ATLAS_SIZE / 2,
+        1..=4 => ATLAS_SIZE / 4,
+        _ => ATLAS_SIZE / 8,
+    }
+}
+
+pub struct ShadowSlot {
+    /// Index into the shadow view buffer.
+    pub index: u32,
+    /// `[x, y, size]` in texels.
+    pub rect: [u32; 3],
+    pub view_proj: Mat4,
+}
+impl ShadowSlot {
+    /// Offset and scale that map the light's [0, 1] uv space into the atlas.
+    pub fn atlas_transform(&self) -> Vec4 {
+        let [x, y, size] = self.rect;
+        let texel = 1.0 / ATLAS_SIZE as f32;
+        Vec4::new(x as f32 * texel, y as f32 * texel, size as f32 * texel, 1.0)
+    }
+}
+
+fn spot_view_projection(global: &GlobalTransform, light: &SpotLight) -> Mat4 {
+    let position = global.0.w_axis.xyz();
+    let direction = global
+        .0
+        .transform_vector3(Vec3::NEG_Z)
+        .normalize_or(Vec3::NEG_Z);
+    let up = if direction.y.abs() > 0.99 {
+        Vec3::X
+    } else {
+        Vec3::Y
+    };
+    let view = Mat4::look_to_rh(position, direction, up);
+    let projection = Mat4::perspective_rh(light.outer_angle * 2.0, 1.0, SHADOW_NEAR, light.range);
+    projection * view
+}
+
+// =============================== ATLAS ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowViewUniform {
+    pub view_proj: [[f32; 4]; 4],
+}
+
+#[derive(Resource)]
+pub struct ShadowAtlas {
+    pub texture: Texture,
+    pub allocator: AtlasAllocator,
+    pub slots: HashMap<Entity, ShadowSlot>,
+    pub unallocated: usize,
+    /// Shrinks every tile by this factor, 1 keeps them at full size.
+    pub tile_divisor: u32,
+    pub views_buffer: wgpu::Buffer,
+    pub views_layout: wgpu::BindGroupLayout,
+    pub views_bind_group: wgpu::BindGroup,
+    pub view_stride: u32,
+}
+impl ShadowAtlas {
+    pub fn new(gpu: &GpuContext) -> Self {
+        let texture = Texture::shadow_texture(&gpu.device, ATLAS_SIZE, ATLAS_SIZE, "shadow_atlas");
+
+        let alignment = gpu.device.limits().min_uniform_buffer_offset_alignment;
+        let view_stride =
+            (std::mem::size_of::<ShadowViewUniform>() as u32).next_multiple_of(alignment);
+        let views_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shadow_views_buffer"),
+            size: view_stride as u64 * MAX_SHADOWED_SPOTS as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let views_layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<ShadowViewUniform>() as u64,
+                        ),
+                    },
+                    count: None,
+                }],
+                label: Some("shadow_views_bind_group_layout"),
+            });
+        let views_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &views_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &views_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<ShadowViewUniform>() as u64),
+                }),
+            }],
+            label: Some("shadow_views_bind_group"),
+        });
+
+        Self {
+            texture,
+            allocator: AtlasAllocator::new(ATLAS_SIZE),
+            slots: HashMap::new(),
+            unallocated: 0,
+            tile_divisor: 1,
+            views_buffer,
+            views_layout,
+            views_bind_group,
+            view_stride,
+        }
+    }
+}
+
+/// Opaque renderables drawn into the shadow maps. These are not frustum
+/// culled against the camera, so off-screen objects still cast shadows.
+#[derive(Resource, Default)]
+pub struct ShadowCasters {
+    /// Mesh and frame arena offset of each caster, grouped by mesh.
+    pub draws: Vec<(MeshId, u32)>,
+}
+
+// =============================== SYSTEMS ===============================
+/// Ranks shadow casting spot lights by importance and hands out atlas tiles,
+/// larger ones to the lights that matter most on screen.
+pub fn shadow_allocation_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    mut atlas: ResMut<ShadowAtlas>,
+    spots: Query<(Entity, &GlobalTransform, &SpotLight)>,
+) {
+    let mut ranked: Vec<(f32, Entity, &GlobalTransform, &SpotLight)> = spots
+        .iter()
+        .filter(|(_, _, light)| light.cast_shadows && light.intensity > 0.0)
+        .map(|(entity, global, light)| {
+            let distance = global.0.w_axis.xyz().distance(camera.eye).max(1.0);
+            (
+                light.intensity * light.range / distance,
+                entity,
+                global,
+                light,
+            )
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let atlas = &mut *atlas;
+    atlas.allocator.reset();
+    atlas.slots.clear();
+    let mut views = vec![0u8; atlas.view_stride as usize * MAX_SHADOWED_SPOTS];
+    for (rank, (_, entity, global, light)) in ranked.iter().enumerate() {
+        if atlas.slots.len() == MAX_SHADOWED_SPOTS {
+            break;
+        }
+        let Some(rect) = atlas
+            .allocator
+            .allocate(tile_size(rank) / atlas.tile_divisor.max(1))
+        else {
+            break;
+        };
+        let slot = ShadowSlot {
+            index: atlas.slots.len() as u32,
+            rect,
+            view_proj: spot_view_projection(global, light),
+        };
+        let offset = slot.index as usize * atlas.view_stride as usize;
+        let uniform = ShadowViewUniform {
+            view_proj: slot.view_proj.to_cols_array_2d(),
+        };
+        views[offset..offset + std::mem::size_of::<ShadowViewUniform>()]
+            .copy_from_slice(bytemuck::bytes_of(&uniform));
+        atlas.slots.insert(*entity, slot);
+    }
+    atlas.unallocated = ranked.len() - atlas.slots.len();
+    gpu.queue.write_buffer(&atlas.views_buffer, 0, &views);
+}
+
+pub fn shadow_caster_system(
+    materials: Res<MaterialTable>,
+    mut arena: ResMut<FrameArena>,
+    mut casters: ResMut<ShadowCasters>,
+    query: Query<(&GlobalTransform, &Renderable)>,
+) {
+    let mut items: Vec<(MeshId, Mat4)> = query
+        .iter()
+        .filter(|(_, renderable)| materials.blend(renderable.material) == BlendMode::Opaque)
+        .map(|(global, renderable)| (renderable.mesh, global.0))
+        .collect();
+    items.sort_by_key(|(mesh, _)| *mesh);
+
+    casters.draws = items
+        .iter()
+        .map(|(mesh, model)| (*mesh, arena.alloc(&ObjectUniform::new(model))))
+        .collect();
+}
+
+// =============================== PIPELINE ===============================
+#[derive(Resource)]
+pub struct ShadowPipeline {
+    pub pipeline: GPUPipeline,
+}
+impl ShadowPipeline {
+    pub fn new(gpu: &GpuContext, atlas: &ShadowAtlas, arena: &FrameArena) -> Result<Self> {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("shadow_shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/shadow.wgsl").into()),
+            });
+        let pipeline = GPUPipelineBuilder::new(&gpu.device)
+            .label("shadow_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&atlas.views_layout)
+            .bind_group_layout(&arena.layout)
+            .vertex_shader(&shader, "vs_main")
+            .vertex_buffer_layout(MeshVertex::desc())
+            .depth_stencil_state(Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                // Slope-scaled bias against shadow acne
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }))
+            .default_multisample_state()
+            .default_primitive_state()
+            .build()?;
+
+        Ok(Self { pipeline })
+    }
+}
@@ -0,0 +1,424 @@
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{Condition, IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use wgpu::util::DeviceExt;
+
+use crate::i18n::tr;
+use crate::{gpu::GpuContext, pass::RenderPassBuilder, scene::Camera, texture::Texture};
+
+use super::{
+    depth::DepthTexture,
+    environment::Environment,
+    graph::PassContext,
+    inspector::TextureRegistry,
+    present::{render_scale_system, FrameBuffer},
+    render::render_system,
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+/// Octahedral normal in rg, roughness in b and reflectivity in a. Written by
+/// the mesh pass next to the velocity buffer.
+pub const SURFACE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+pub fn setup_reflections(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let frame_buffer = world
+        .get_resource::<FrameBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("FrameBuffer resource not found"))?;
+    let depth = world
+        .get_resource::<DepthTexture>()
+        .ok_or_else(|| anyhow::anyhow!("DepthTexture resource not found"))?;
+    let environment = world
+        .get_resource::<Environment>()
+        .ok_or_else(|| anyhow::anyhow!("Environment resource not found"))?;
+
+    let size = frame_buffer.texture.texture.size();
+    let surface = SurfaceBuffer {
+        texture: Texture::render_target(
+            &gpu.device,
+            size.width,
+            size.height,
+            SURFACE_FORMAT,
+            "surface_buffer",
+        ),
+    };
+    let settings = SsrSettings::default();
+    let reflections = Reflections::new(gpu, &settings, depth, &surface, environment, size)?;
+
+    world.insert_resource(surface);
+    world.insert_resource(reflections);
+    world.insert_resource(settings);
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("surface", |world| {
+            world
+                .get_resource::<SurfaceBuffer>()
+                .map(|surface| &surface.texture.texture)
+        });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(reflections_panel);
+
+    schedule.add_systems((
+        ssr_resize_system
+            .run_if(resource_changed::<FrameBuffer>.or(resource_changed::<DepthTexture>))
+            .after(render_scale_system)
+            .before(render_system),
+        ssr_params_system
+            .run_if(resource_changed::<SsrSettings>.or(resource_changed::<Camera>))
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Follows the frame buffer like the velocity buffer, and picks up the depth
+/// texture after it was recreated.
+pub fn ssr_resize_system(
+    gpu: Res<GpuContext>,
+    frame_buffer: Res<FrameBuffer>,
+    depth: Res<DepthTexture>,
+    environment: Res<Environment>,
+    mut surface: ResMut<SurfaceBuffer>,
+    mut reflections: ResMut<Reflections>,
+) {
+    let size = frame_buffer.texture.texture.size();
+    let current = surface.texture.texture.size();
+    if size.width != current.width || size.height != current.height {
+        surface
+            .texture
+            .resize(&gpu.device, &gpu.queue, size.width, size.height);
+        reflections
+            .scene
+            .resize(&gpu.device, &gpu.queue, size.width, size.height);
+    }
+    reflections.bind_group = Reflections::create_bind_group(
+        &gpu,
+        &reflections.layout,
+        &depth,
+        &surface,
+        &reflections.scene,
+        &environment,
+        &reflections.params,
+    );
+}
+
+pub fn ssr_params_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    settings: Res<SsrSettings>,
+    reflections: Res<Reflections>,
+) {
+    let params = SsrParams::new(&settings, &camera);
+    gpu.queue
+        .write_buffer(&reflections.params, 0, bytemuck::bytes_of(&params));
+}
+
+/// Blends reflections over the lit frame buffer. The frame buffer is copied
+/// first, the pass can't sample what it draws into.
+pub fn ssr_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let settings = world.resource::<SsrSettings>();
+    if !settings.enabled {
+        return Ok(());
+    }
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let reflections = world.resource::<Reflections>();
+
+    ctx.encoder.copy_texture_to_texture(
+        frame_buffer.texture.texture.as_image_copy(),
+        reflections.scene.texture.as_image_copy(),
+        frame_buffer.texture.texture.size(),
+    );
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label(ctx.label)
+        .with_color_view(&frame_buffer.texture.view)
+        .load()
+        .build()?;
+
+    render_pass.set_pipeline(&reflections.pipeline.render_pipeline);
+    render_pass.set_bind_group(0, &reflections.bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+
+    Ok(())
+}
+
+fn reflections_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<SsrSettings>().clone();
+
+    egui::Window::new(tr("Reflections"))
+        .id(egui::Id::new("Reflections"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Screen space reflections"));
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                ui.add(egui::Slider::new(&mut settings.max_steps, 8..=256).text(tr("Steps")));
+                ui.add(
+                    egui::Slider::new(&mut settings.max_distance, 1.0..=100.0)
+                        .logarithmic(true)
+                        .text(tr("Max distance")),
+                );
+                ui.add(
+                    egui::Slider::new(&mut settings.thickness, 0.01..=2.0).text(tr("Thickness")),
+                )
+                .on_hover_text(tr("How far behind the depth buffer a ray still hits"));
+                ui.add(
+                    egui::Slider::new(&mut settings.max_roughness, 0.05..=1.0)
+                        .text(tr("Max roughness")),
+                )
+                .on_hover_text(tr("Rougher surfaces only reflect the environment"));
+                ui.add(egui::Slider::new(&mut settings.edge_fade, 0.0..=0.5).text(tr("Edge fade")));
+                ui.add(egui::Slider::new(&mut settings.intensity, 0.0..=2.0).text(tr("Intensity")));
+            });
+            ui.label(tr(
+                "Reflectivity and roughness are set per material in the inspector",
+            ));
+        });
+
+    let mut current = world.resource_mut::<SsrSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct SsrSettings {
+    pub enabled: bool,
+    pub max_steps: u32,
+    /// World space length of a reflected ray.
+    pub max_distance: f32,
+    pub thickness: f32,
+    /// Above this roughness rays aren't traced, the environment map is used.
+    pub max_roughness: f32,
+    /// Fraction of the screen over which hits fade out towards its edges.
+    pub edge_fade: f32,
+    pub intensity: f32,
+}
+impl Default for SsrSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_steps: 64,
+            max_distance: 20.0,
+            thickness: 0.5,
+            max_roughness: 0.6,
+            edge_fade: 0.1,
+            intensity: 1.0,
+        }
+    }
+}
+
+// =============================== RESOURCES ===============================
+/// Per pixel normal and material response of the opaque geometry, what the
+/// reflection and AO passes need beyond depth.
+#[derive(Resource)]
+pub struct SurfaceBuffer {
+    pub texture: Texture,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SsrParams {
+    pub view_proj: [[f32; 4]; 4],
+    pub inv_view_proj: [[f32; 4]; 4],
+    pub eye: [f32; 4],
+    pub max_steps: u32,
+    pub max_distance: f32,
+    pub thickness: f32,
+    pub max_roughness: f32,
+    pub edge_fade: f32,
+    pub intensity: f32,
+    pub near: f32,
+    pub far: f32,
+}
+impl SsrParams {
+    pub fn new(settings: &SsrSettings, camera: &Camera) -> Self {
+        let view_proj = camera.view_projection();
+        Self {
+            view_proj: view_proj.to_cols_array_2d(),
+            inv_view_proj: view_proj.inverse().to_cols_array_2d(),
+            eye: camera.eye.extend(1.0).to_array(),
+            max_steps: settings.max_steps,
+            max_distance: settings.max_distance,
+            thickness: settings.thickness,
+            max_roughness: settings.max_roughness,
+            edge_fade: settings.edge_fade,
+            intensity: settings.intensity,
+            near: camera.near,
+            far: camera.far,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Reflections {
+    /// Copy of the lit frame buffer the rays sample.
+    pub scene: Texture,
+    pub params: wgpu::Buffer,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    pub pipeline: GPUPipeline,
+}
+impl Reflections {
+    pub fn new(
+        gpu: &GpuContext,
+        settings: &SsrSettings,
+        depth: &DepthTexture,
+        surface: &SurfaceBuffer,
+        environment: &Environment,
+        size: wgpu::Extent3d,
+    ) -> Result<Self> {
+        let scene = Texture::frame_buffer_texture(
+            &gpu.device,
+            size.width,
+            size.height,
+            Some("ssr_scene"),
+            1,
+        );
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("ssr_params"),
+                contents: bytemuck::bytes_of(&SsrParams::new(settings, &Camera::default())),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let sampler = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type,
+            },
+            count: None,
+        };
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    texture(0, wgpu::TextureSampleType::Depth),
+                    texture(1, wgpu::TextureSampleType::Float { filterable: false }),
+                    texture(2, wgpu::TextureSampleType::Float { filterable: true }),
+                    texture(3, wgpu::TextureSampleType::Float { filterable: true }),
+                    sampler(4),
+                    sampler(5),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("ssr_bind_group_layout"),
+            });
+        let bind_group =
+            Self::create_bind_group(gpu, &layout, depth, surface, &scene, environment, &params);
+
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("ssr_shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/ssr.wgsl").into()),
+            });
+        let pipeline = GPUPipelineBuilder::new(&gpu.device)
+            .label("ssr_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .color_target(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Rgba16Float,
+                // Weighted by the fresnel term in alpha, leaving the frame
+                // buffer's own alpha alone
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::SrcAlpha,
+                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::Zero,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .depth_stencil_state(None)
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState::default())
+            .build()?;
+
+        Ok(Self {
+            scene,
+            params,
+            layout,
+            bind_group,
+            pipeline,
+        })
+    }
+
+    fn create_bind_group(
+        gpu: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        depth: &DepthTexture,
+        surface: &SurfaceBuffer,
+        scene: &Texture,
+        environment: &Environment,
+        params: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&depth.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&surface.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&scene.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&environment.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&scene.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&environment.texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+            label: Some("ssr_bind_group"),
+        })
+    }
+}
@@ -0,0 +1,233 @@
+//! Side-by-side stereo: the mesh pass draws the scene once per eye into the
+//! left and right half of the frame buffer, each eye with its own camera
+//! uniform. Groundwork for XR, where a headset wants one image per eye.
+//! Passes with a camera of their own, like particles, the volume or debug
+//! draws, still draw once across the whole frame.
+
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Mat4, Vec3};
+
+use crate::{gpu::GpuContext, i18n::tr, scene::Camera};
+
+use super::{
+    mesh::{mesh_prepare_system, CameraBuffer},
+    render::render_system,
+    ui::UiPanels,
+};
+
+pub fn setup_stereo(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    // wgpu pools bind group layouts with the same entries, so the mesh
+    // pipelines take these like the main camera's
+    let cameras = StereoCameras {
+        eyes: [CameraBuffer::new(gpu), CameraBuffer::new(gpu)],
+    };
+
+    world.insert_resource(cameras);
+    world.insert_resource(StereoSettings::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(stereo_panel);
+
+    schedule.add_systems(
+        stereo_camera_system
+            .after(mesh_prepare_system)
+            .before(render_system),
+    );
+
+    Ok(())
+}
+
+pub fn stereo_camera_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    settings: Res<StereoSettings>,
+    mut cameras: ResMut<StereoCameras>,
+) {
+    if settings.mode == StereoMode::Off {
+        // Turning stereo back on shouldn't smear from wherever it was left
+        for eye in &mut cameras.eyes {
+            eye.uploaded = None;
+        }
+        return;
+    }
+    cameras.write(&gpu, &camera, &settings);
+}
+
+fn stereo_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<StereoSettings>().clone();
+
+    egui::Window::new(tr("Stereo"))
+        .id(egui::Id::new("Stereo"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            for mode in StereoMode::ALL {
+                ui.radio_value(&mut settings.mode, mode, mode.label());
+            }
+            ui.add_enabled_ui(settings.mode != StereoMode::Off, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.separation, 0.0..=2.0)
+                        .text(tr("Eye separation")),
+                )
+                .on_hover_text(tr("In scene units, more separation makes depth stand out"));
+            });
+            ui.label(tr("Both eyes converge on the orbit target"));
+            ui.label(tr(
+                "Only meshes are drawn per eye, particles, volumes and debug draws stay mono",
+            ));
+        });
+
+    let mut current = world.resource_mut::<StereoSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StereoMode {
+    Off,
+    /// Left eye on the left, for headsets and parallel viewing.
+    SideBySide,
+    /// Left eye on the right, to view by crossing the eyes.
+    CrossEyed,
+}
+impl StereoMode {
+    pub const ALL: [StereoMode; 3] = [Self::Off, Self::SideBySide, Self::CrossEyed];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => tr("Off"),
+            Self::SideBySide => tr("Side by side"),
+            Self::CrossEyed => tr("Cross-eyed"),
+        }
+    }
+
+    /// Each eye with the left edge of its half in a frame `width` wide.
+    pub fn halves(self, width: f32) -> [(Eye, f32); 2] {
+        match self {
+            Self::CrossEyed => [(Eye::Right, 0.0), (Eye::Left, width / 2.0)],
+            _ => [(Eye::Left, 0.0), (Eye::Right, width / 2.0)],
+        }
+    }
+}
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct StereoSettings {
+    pub mode: StereoMode,
+    /// Distance between the eyes, in scene units.
+    pub separation: f32,
+}
+impl Default for StereoSettings {
+    fn default() -> Self {
+        Self {
+            mode: StereoMode::Off,
+            separation: 0.3,
+        }
+    }
+}
+
+// =============================== CAMERAS ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+impl Eye {
+    /// Which way along the camera's right axis the eye sits.
+    fn sign(self) -> f32 {
+        match self {
+            Self::Left => -1.0,
+            Self::Right => 1.0,
+        }
+    }
+}
+
+/// View projection and position of one eye, drawing into half the frame.
+/// The eyes look parallel and their frusta are shifted towards each other,
+/// so the orbit target lands on the same spot in both halves. Anything in
+/// front of it comes out of the screen, anything behind recedes.
+pub fn eye_view_projection(camera: &Camera, eye: Eye, separation: f32) -> (Mat4, Vec3) {
+    let forward = (camera.target - camera.eye).normalize_or(Vec3::NEG_Z);
+    let right = forward.cross(camera.up).normalize_or(Vec3::X);
+    let offset = right * eye.sign() * separation * 0.5;
+    let view = Mat4::look_at_rh(camera.eye + offset, camera.target + offset, camera.up);
+    let projection =
+        Mat4::perspective_rh(camera.fov_y, camera.aspect * 0.5, camera.near, camera.far);
+    let convergence = camera.eye.distance(camera.target).max(camera.near);
+    // Shifts clip space x by a multiple of w, which moves the target back
+    // to the center after the eye stepped aside
+    let shift = eye.sign() * projection.x_axis.x * separation * 0.5 / convergence;
+    (
+        Mat4::from_translation(Vec3::new(shift, 0.0, 0.0)) * projection * view,
+        camera.eye + offset,
+    )
+}
+
+/// A camera uniform per eye, bound in place of the main camera's.
+#[derive(Resource)]
+pub struct StereoCameras {
+    eyes: [CameraBuffer; 2],
+}
+impl StereoCameras {
+    pub fn eye(&self, eye: Eye) -> &CameraBuffer {
+        &self.eyes[eye as usize]
+    }
+
+    pub fn write(&mut self, gpu: &GpuContext, camera: &Camera, settings: &StereoSettings) {
+        for eye in [Eye::Left, Eye::Right] {
+            let (view_proj, position) = eye_view_projection(camera, eye, settings.separation);
+            self.eyes[eye as usize].write(gpu, view_proj, position);
+        }
+    }
+
+    /// Like [`CameraBuffer::rewrite`], for both eyes.
+    pub fn rewrite(&mut self, gpu: &GpuContext, camera: &Camera, settings: &StereoSettings) {
+        for eye in [Eye::Left, Eye::Right] {
+            let (view_proj, position) = eye_view_projection(camera, eye, settings.separation);
+            self.eyes[eye as usize].rewrite(gpu, view_proj, position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec4Swizzles;
+
+    use super::*;
+
+    fn ndc(view_proj: Mat4, point: Vec3) -> Vec3 {
+        let clip = view_proj * point.extend(1.0);
+        clip.xyz() / clip.w
+    }
+
+    #[test]
+    fn eyes_converge_on_the_target() {
+        let camera = Camera::default();
+        let (left, left_eye) = eye_view_projection(&camera, Eye::Left, 0.5);
+        let (right, right_eye) = eye_view_projection(&camera, Eye::Right, 0.5);
+        assert!((left_eye.distance(right_eye) - 0.5).abs() < 1e-5);
+
+        // No parallax at the target, the same spot in both halves
+        let target_left = ndc(left, camera.target);
+        let target_right = ndc(right, camera.target);
+        assert!(target_left.x.abs() < 1e-5 && target_right.x.abs() < 1e-5);
+        let center = ndc(camera.view_projection(), camera.target);
+        assert!((target_left.z - center.z).abs() < 1e-3);
+
+        // Further away the left eye sees things further left than the right
+        // one does, nearer it's the other way around
+        let forward = (camera.target - camera.eye).normalize();
+        let far = camera.target + forward * 10.0;
+        assert!(ndc(left, far).x < ndc(right, far).x);
+        let near = camera.target - forward * 10.0;
+        assert!(ndc(left, near).x > ndc(right, near).x);
+    }
+}
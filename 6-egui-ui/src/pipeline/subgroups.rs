@@ -0,0 +1,209 @@
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+use tracing::{info, warn};
+use wgpu::util::DeviceExt;
+
+use crate::{gpu::GpuContext, shader::load_shader_source};
+
+use super::compute::{read_buffer, DispatchSite, GPUComputePipeline};
+
+const ELEMENTS: u32 = 1 << 16;
+
+/// Runs the subgroup demo once at startup and keeps the outcome for the
+/// diagnostics panel.
+pub fn setup_subgroup_demo(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let path = if gpu.device.features().contains(wgpu::Features::SUBGROUP) {
+        SubgroupPath::Subgroups
+    } else {
+        SubgroupPath::SharedMemory
+    };
+    let result = run_subgroup_demo(gpu, path);
+    match &result {
+        Ok(result) => info!(
+            "Subgroup demo ({}): {} odd, maximum {} ({})",
+            path.name(),
+            result.odd,
+            result.maximum,
+            if result.correct { "correct" } else { "WRONG" }
+        ),
+        Err(e) => warn!("Subgroup demo ({}) failed: {}", path.name(), e),
+    }
+
+    world.insert_resource(SubgroupDemo {
+        path,
+        result: result.map_err(|e| e.to_string()),
+    });
+
+    Ok(())
+}
+
+// =============================== RESOURCES ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubgroupPath {
+    /// `subgroupBallot` and `subgroupShuffleXor`.
+    Subgroups,
+    /// Workgroup atomics and a shared memory tree.
+    SharedMemory,
+}
+impl SubgroupPath {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Subgroups => "subgroup ballot/shuffle",
+            Self::SharedMemory => "shared memory fallback",
+        }
+    }
+}
+
+pub struct SubgroupDemoResult {
+    pub odd: u32,
+    pub maximum: u32,
+    pub correct: bool,
+}
+
+/// Counts the odd values of a buffer and finds its maximum, on whichever path
+/// the device supports.
+#[derive(Resource)]
+pub struct SubgroupDemo {
+    pub path: SubgroupPath,
+    pub result: Result<SubgroupDemoResult, String>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SubgroupDemoParams {
+    pub count: u32,
+    pub _padding: [u32; 3],
+}
+
+// =============================== DEMO ===============================
+fn run_subgroup_demo(gpu: &GpuContext, path: SubgroupPath) -> Result<SubgroupDemoResult> {
+    let data: Vec<u32> = (0..ELEMENTS)
+        .map(|i| i.wrapping_mul(2654435761) >> 12)
+        .collect();
+    let expected_odd = data.iter().filter(|v| *v & 1 == 1).count() as u32;
+    let expected_max = data.iter().copied().max().unwrap_or(0);
+
+    let input = gpu
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("subgroup_demo_input"),
+            contents: bytemuck::cast_slice(&data),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+    let params = gpu
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("subgroup_demo_params"),
+            contents: bytemuck::bytes_of(&SubgroupDemoParams {
+                count: ELEMENTS,
+                _padding: [0; 3],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+    let output = gpu
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("subgroup_demo_output"),
+            contents: bytemuck::cast_slice(&[0u32; 2]),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+    let readback = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("subgroup_demo_readback"),
+        size: 8,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+    let layout = gpu
+        .device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                entry(0, wgpu::BufferBindingType::Storage { read_only: true }),
+                entry(1, wgpu::BufferBindingType::Storage { read_only: false }),
+                entry(2, wgpu::BufferBindingType::Uniform),
+            ],
+            label: Some("subgroup_demo_bind_group_layout"),
+        });
+    let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: input.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: output.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: params.as_entire_binding(),
+            },
+        ],
+        label: Some("subgroup_demo_bind_group"),
+    });
+
+    // Subgroup builtins fail validation on devices without the feature, so the
+    // paths live in separate shaders
+    let source = match path {
+        SubgroupPath::Subgroups => load_shader_source(
+            "subgroup_demo.wgsl",
+            include_str!("../shaders/subgroup_demo.wgsl"),
+        ),
+        SubgroupPath::SharedMemory => load_shader_source(
+            "subgroup_demo_fallback.wgsl",
+            include_str!("../shaders/subgroup_demo_fallback.wgsl"),
+        ),
+    };
+    let pipeline = GPUComputePipeline::new(
+        &gpu.device,
+        "subgroup_demo",
+        &source,
+        "cs_main",
+        &[&layout],
+        gpu.pipeline_cache(),
+    )?;
+    let [x, y, z] = DispatchSite {
+        label: "subgroup_demo",
+        domain: [ELEMENTS, 1, 1],
+    }
+    .validate(pipeline.workgroup_size, &gpu.device.limits())?;
+
+    let mut encoder = gpu
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("subgroup_demo_encoder"),
+        });
+    {
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("subgroup_demo"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&pipeline.pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+    encoder.copy_buffer_to_buffer(&output, 0, &readback, 0, 8);
+    gpu.queue.submit(Some(encoder.finish()));
+
+    let result: Vec<u32> = read_buffer(&gpu.device, &readback)?;
+    Ok(SubgroupDemoResult {
+        odd: result[0],
+        maximum: result[1],
+        correct: result[0] == expected_odd && result[1] == expected_max,
+    })
+}
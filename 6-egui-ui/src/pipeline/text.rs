@@ -0,0 +1,729 @@
+use std::collections::HashMap;
+
+use ab_glyph::{Font, FontArc, GlyphId, PxScale, ScaleFont};
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Vec2, Vec4};
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+};
+
+use super::{
+    hud::{HudQuad, HudQuads, HudRenderer, HudTexture},
+    render::render_system,
+    ui::UiPanels,
+};
+
+/// egui's bundled fonts, tried in order for every character.
+const FONT_CHAIN: [&str; 4] = [
+    "Ubuntu-Light",
+    "Hack",
+    "NotoEmoji-Regular",
+    "emoji-icon-font",
+];
+const INITIAL_ATLAS_SIZE: u32 = 256;
+/// Beyond this the atlas is emptied and refilled instead of grown.
+const MAX_ATLAS_SIZE: u32 = 2048;
+/// Empty texels around every glyph, so linear filtering doesn't bleed.
+const GLYPH_PADDING: u32 = 1;
+/// Sizes are rounded to whole pixels, this bounds how many variants of a
+/// glyph can end up in the atlas.
+const SIZE_RANGE: (f32, f32) = (6.0, 128.0);
+/// Distance fields are made once per glyph at this size and scaled.
+const SDF_SIZE: u32 = 48;
+/// Pixels at [`SDF_SIZE`] the distance field reaches past the outline, which
+/// also bounds outlines and shadows.
+const SDF_SPREAD: u32 = 8;
+
+pub fn setup_text(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let fonts = load_fonts()?;
+    let atlas = world.resource_scope::<HudRenderer, _>(|world, mut hud| {
+        let gpu = world
+            .get_resource::<GpuContext>()
+            .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+        Ok::<_, anyhow::Error>(GlyphAtlas::new(gpu, &mut hud, fonts))
+    })?;
+    world.insert_resource(atlas);
+    world.init_resource::<DebugText>();
+    world.insert_resource(TextDemoSettings::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(text_panel);
+
+    schedule.add_systems((
+        text_demo_system.before(text_layout_system),
+        text_layout_system.before(render_system),
+    ));
+
+    Ok(())
+}
+
+fn load_fonts() -> Result<Vec<FontArc>> {
+    let definitions = egui::FontDefinitions::default();
+    FONT_CHAIN
+        .iter()
+        .filter_map(|name| definitions.font_data.get(*name))
+        .map(|data| {
+            FontArc::try_from_vec(data.font.to_vec())
+                .map_err(|e| anyhow::anyhow!("Failed to parse font: {}", e))
+        })
+        .collect::<Result<Vec<_>>>()
+        .and_then(|fonts| {
+            if fonts.is_empty() {
+                anyhow::bail!("None of the bundled fonts were found");
+            }
+            Ok(fonts)
+        })
+}
+
+pub fn text_demo_system(settings: Res<TextDemoSettings>, mut text: ResMut<DebugText>) {
+    if !settings.enabled {
+        return;
+    }
+    let position = Vec2::new(24.0, 24.0);
+    if settings.distance_field {
+        text.styled(
+            position,
+            settings.size,
+            Vec4::ONE,
+            TextStyle::DistanceField(settings.effects),
+            settings.text.clone(),
+        );
+    } else {
+        text.text(position, settings.size, Vec4::ONE, settings.text.clone());
+    }
+}
+
+/// Lays out everything queued on [`DebugText`], rasterizing glyphs the atlas
+/// doesn't have yet, and queues one HUD quad per glyph.
+pub fn text_layout_system(
+    gpu: Res<GpuContext>,
+    mut text: ResMut<DebugText>,
+    mut atlas: ResMut<GlyphAtlas>,
+    mut hud: ResMut<HudRenderer>,
+    mut quads: ResMut<HudQuads>,
+) {
+    atlas.begin_frame();
+    let items = std::mem::take(&mut text.items);
+    // Glyphs are only turned into quads once all of them are in, the atlas
+    // may have grown in between and moved every UV
+    let mut placed = Vec::new();
+    for item in &items {
+        for glyph in atlas.shape(&item.text, item.size, item.style) {
+            placed.push((glyph, item));
+        }
+    }
+    atlas.upload(&gpu, &mut hud);
+
+    for (placed, item) in placed {
+        let page = atlas.page(placed.distance_field);
+        let Some(Some(glyph)) = page.glyphs.get(&placed.key) else {
+            continue;
+        };
+        let size = Vec2::new(glyph.rect[2] as f32, glyph.rect[3] as f32);
+        let mut quad = HudQuad::new(
+            item.position + placed.pen + glyph.offset * placed.scale,
+            size * placed.scale,
+        )
+        .with_texture(page.texture)
+        .with_uv_rect(page.uv_rect(glyph.rect))
+        .with_color(item.color);
+        if let TextStyle::DistanceField(effects) = item.style {
+            quad = quad
+                .with_distance_field()
+                .with_border(effects.outline_width, effects.outline_color)
+                .with_shadow(effects.shadow_offset, effects.shadow_opacity);
+        }
+        quads.push(quad);
+    }
+}
+
+fn text_panel(ctx: &egui::Context, world: &mut World) {
+    let atlas = world.resource::<GlyphAtlas>();
+    let pages = [
+        ("Bitmap atlas", atlas.bitmap.summary()),
+        ("Distance field atlas", atlas.distance_field.summary()),
+    ];
+    let mut settings = world.resource::<TextDemoSettings>().clone();
+
+    egui::Window::new(tr("Text"))
+        .id(egui::Id::new("Text"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Show demo text"));
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.size, SIZE_RANGE.0..=SIZE_RANGE.1)
+                        .text(tr("Size")),
+                );
+                ui.text_edit_multiline(&mut settings.text);
+                ui.checkbox(&mut settings.distance_field, tr("Distance field"))
+                    .on_hover_text(tr(
+                        "Scales one field per glyph instead of rasterizing every size",
+                    ));
+                ui.add_enabled_ui(settings.distance_field, |ui| {
+                    let effects = &mut settings.effects;
+                    ui.add(
+                        egui::Slider::new(&mut effects.outline_width, 0.0..=4.0)
+                            .text(tr("Outline width")),
+                    );
+                    ui.horizontal(|ui| {
+                        let mut color = effects.outline_color.to_array();
+                        ui.color_edit_button_rgba_unmultiplied(&mut color);
+                        effects.outline_color = Vec4::from_array(color);
+                        ui.label(tr("Outline color"));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut effects.shadow_offset.x).speed(0.1));
+                        ui.add(egui::DragValue::new(&mut effects.shadow_offset.y).speed(0.1));
+                        ui.label(tr("Shadow offset"));
+                    });
+                    ui.add(
+                        egui::Slider::new(&mut effects.shadow_opacity, 0.0..=1.0)
+                            .text(tr("Shadow opacity")),
+                    );
+                });
+            });
+            for (name, (stats, size, cached)) in pages {
+                ui.separator();
+                ui.strong(tr(name));
+                ui.label(trf!(
+                    "{} glyphs cached in a {}x{} atlas",
+                    cached,
+                    size,
+                    size
+                ));
+                ui.label(trf!(
+                    "{} hits, {} misses this frame",
+                    stats.hits,
+                    stats.misses
+                ));
+                ui.label(trf!("{} grows, {} evictions", stats.grows, stats.evictions));
+            }
+        });
+
+    let mut current = world.resource_mut::<TextDemoSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== API ===============================
+/// How the glyphs of a [`TextItem`] are made.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextStyle {
+    /// Rasterized at the exact size, the sharpest option for small text.
+    Bitmap,
+    /// Scaled from one distance field per glyph, crisp at any size.
+    DistanceField(TextEffects),
+}
+
+/// Outline and drop shadow of distance field text, in screen pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextEffects {
+    pub outline_width: f32,
+    pub outline_color: Vec4,
+    pub shadow_offset: Vec2,
+    pub shadow_opacity: f32,
+}
+impl Default for TextEffects {
+    fn default() -> Self {
+        Self {
+            outline_width: 1.5,
+            outline_color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            shadow_offset: Vec2::new(2.0, 2.0),
+            shadow_opacity: 0.6,
+        }
+    }
+}
+
+pub struct TextItem {
+    /// Top left of the first line, in surface pixels.
+    pub position: Vec2,
+    pub size: f32,
+    pub color: Vec4,
+    pub style: TextStyle,
+    pub text: String,
+}
+
+/// Immediate-mode text drawing on top of [`HudQuads`]. Anything queued is
+/// drawn once, at the end of the current frame.
+#[derive(Resource, Default)]
+pub struct DebugText {
+    pub items: Vec<TextItem>,
+}
+impl DebugText {
+    pub fn text(&mut self, position: Vec2, size: f32, color: Vec4, text: impl Into<String>) {
+        self.styled(position, size, color, TextStyle::Bitmap, text);
+    }
+
+    pub fn styled(
+        &mut self,
+        position: Vec2,
+        size: f32,
+        color: Vec4,
+        style: TextStyle,
+        text: impl Into<String>,
+    ) {
+        self.items.push(TextItem {
+            position,
+            size,
+            color,
+            style,
+            text: text.into(),
+        });
+    }
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct TextDemoSettings {
+    pub enabled: bool,
+    pub size: f32,
+    pub text: String,
+    pub distance_field: bool,
+    pub effects: TextEffects,
+}
+impl Default for TextDemoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: 24.0,
+            text: "AVATAR Typewriter, kerning\nGrüße, ½ × π ≈ 1.57 ✔".to_string(),
+            distance_field: false,
+            effects: TextEffects::default(),
+        }
+    }
+}
+
+// =============================== ATLAS ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    /// Index into the font chain.
+    font: usize,
+    glyph: GlyphId,
+    /// Size the glyph was rasterized at, always [`SDF_SIZE`] for fields.
+    size: u32,
+}
+
+/// A glyph of a shaped string, relative to the string's top left.
+struct PlacedGlyph {
+    key: GlyphKey,
+    distance_field: bool,
+    pen: Vec2,
+    /// From the rasterized size to the requested one.
+    scale: f32,
+}
+
+struct AtlasGlyph {
+    /// Left, top, width and height in atlas texels.
+    rect: [u32; 4],
+    /// From the pen position on the baseline to the top left of the bitmap.
+    offset: Vec2,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct AtlasStats {
+    /// Glyph lookups this frame that were already rasterized.
+    pub hits: usize,
+    pub misses: usize,
+    pub grows: usize,
+    /// Times the atlas was full at its largest size and emptied.
+    pub evictions: usize,
+}
+
+/// Packs glyphs into rows, each as tall as the tallest glyph in it.
+struct ShelfPacker {
+    cursor: (u32, u32),
+    shelf_height: u32,
+}
+impl ShelfPacker {
+    fn new() -> Self {
+        Self {
+            cursor: (GLYPH_PADDING, GLYPH_PADDING),
+            shelf_height: 0,
+        }
+    }
+
+    fn place(&mut self, size: u32, width: u32, height: u32) -> Option<(u32, u32)> {
+        if self.cursor.0 + width + GLYPH_PADDING > size {
+            self.cursor = (
+                GLYPH_PADDING,
+                self.cursor.1 + self.shelf_height + GLYPH_PADDING,
+            );
+            self.shelf_height = 0;
+        }
+        if self.cursor.0 + width + GLYPH_PADDING > size
+            || self.cursor.1 + height + GLYPH_PADDING > size
+        {
+            return None;
+        }
+        let position = self.cursor;
+        self.cursor.0 += width + GLYPH_PADDING;
+        self.shelf_height = self.shelf_height.max(height);
+        Some(position)
+    }
+}
+
+/// One texture of glyphs that doubles in size when it runs out of space. A
+/// CPU copy is kept to refill the larger texture.
+pub struct AtlasPage {
+    label: &'static str,
+    /// `None` for glyphs without a bitmap, e.g. spaces.
+    glyphs: HashMap<GlyphKey, Option<AtlasGlyph>>,
+    image: image::RgbaImage,
+    size: u32,
+    packer: ShelfPacker,
+    texture: HudTexture,
+    gpu_texture: wgpu::Texture,
+    /// Regions written since the last upload, in texels.
+    dirty: Vec<[u32; 4]>,
+    /// The texture was recreated and needs all of the CPU copy.
+    resized: bool,
+    /// The atlas filled up at its largest size and is emptied next frame.
+    full: bool,
+    pub stats: AtlasStats,
+}
+impl AtlasPage {
+    fn new(gpu: &GpuContext, hud: &mut HudRenderer, label: &'static str) -> Self {
+        let gpu_texture = Self::create_texture(gpu, label, INITIAL_ATLAS_SIZE);
+        let texture = hud.add_texture(gpu, &gpu_texture.create_view(&Default::default()));
+        Self {
+            label,
+            glyphs: HashMap::new(),
+            image: image::RgbaImage::new(INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE),
+            size: INITIAL_ATLAS_SIZE,
+            packer: ShelfPacker::new(),
+            texture,
+            gpu_texture,
+            dirty: Vec::new(),
+            resized: true,
+            full: false,
+            stats: AtlasStats::default(),
+        }
+    }
+
+    fn create_texture(gpu: &GpuContext, label: &str, size: u32) -> wgpu::Texture {
+        gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // Coverage and distances are linear, stored as alpha over white
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        })
+    }
+
+    /// Stats, atlas size and cached glyphs.
+    pub fn summary(&self) -> (AtlasStats, u32, usize) {
+        (self.stats, self.size, self.glyphs.len())
+    }
+
+    fn uv_rect(&self, rect: [u32; 4]) -> [f32; 4] {
+        let [x, y, width, height] = rect.map(|v| v as f32 / self.size as f32);
+        [x, y, x + width, y + height]
+    }
+
+    fn begin_frame(&mut self) {
+        self.stats.hits = 0;
+        self.stats.misses = 0;
+        if self.full {
+            self.glyphs.clear();
+            self.image.fill(0);
+            self.packer = ShelfPacker::new();
+            self.resized = true;
+            self.full = false;
+            self.stats.evictions += 1;
+        }
+    }
+
+    fn insert(&mut self, key: GlyphKey, bitmap: &image::RgbaImage, offset: Vec2) {
+        let (width, height) = bitmap.dimensions();
+        let position = loop {
+            if let Some(position) = self.packer.place(self.size, width, height) {
+                break Some(position);
+            }
+            if self.size >= MAX_ATLAS_SIZE {
+                break None;
+            }
+            self.grow();
+        };
+        let Some((x, y)) = position else {
+            // Dropped for this frame, the atlas starts over on the next one
+            self.full = true;
+            return;
+        };
+
+        image::imageops::replace(&mut self.image, bitmap, x as i64, y as i64);
+        self.dirty.push([x, y, width, height]);
+        self.glyphs.insert(
+            key,
+            Some(AtlasGlyph {
+                rect: [x, y, width, height],
+                offset,
+            }),
+        );
+    }
+
+    /// Doubles the atlas. Glyphs keep their texel positions, only their UVs
+    /// change.
+    fn grow(&mut self) {
+        self.size *= 2;
+        let mut image = image::RgbaImage::new(self.size, self.size);
+        image::imageops::replace(&mut image, &self.image, 0, 0);
+        self.image = image;
+        self.resized = true;
+        self.stats.grows += 1;
+    }
+
+    fn upload(&mut self, gpu: &GpuContext, hud: &mut HudRenderer) {
+        if self.resized {
+            if self.gpu_texture.width() != self.size {
+                self.gpu_texture = Self::create_texture(gpu, self.label, self.size);
+                let view = self.gpu_texture.create_view(&Default::default());
+                hud.replace_texture(gpu, self.texture, &view);
+            }
+            self.write(gpu, [0, 0, self.size, self.size]);
+            self.resized = false;
+            self.dirty.clear();
+        }
+        for rect in std::mem::take(&mut self.dirty) {
+            self.write(gpu, rect);
+        }
+    }
+
+    fn write(&self, gpu: &GpuContext, [x, y, width, height]: [u32; 4]) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let region = image::imageops::crop_imm(&self.image, x, y, width, height).to_image();
+        gpu.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.gpu_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &region,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+/// Glyphs rasterized on first use, as bitmaps at each requested size or as
+/// one distance field per glyph.
+#[derive(Resource)]
+pub struct GlyphAtlas {
+    fonts: Vec<FontArc>,
+    pub bitmap: AtlasPage,
+    pub distance_field: AtlasPage,
+}
+impl GlyphAtlas {
+    pub fn new(gpu: &GpuContext, hud: &mut HudRenderer, fonts: Vec<FontArc>) -> Self {
+        Self {
+            fonts,
+            bitmap: AtlasPage::new(gpu, hud, "glyph_atlas"),
+            distance_field: AtlasPage::new(gpu, hud, "glyph_distance_field_atlas"),
+        }
+    }
+
+    fn page(&self, distance_field: bool) -> &AtlasPage {
+        if distance_field {
+            &self.distance_field
+        } else {
+            &self.bitmap
+        }
+    }
+
+    fn begin_frame(&mut self) {
+        self.bitmap.begin_frame();
+        self.distance_field.begin_frame();
+    }
+
+    fn upload(&mut self, gpu: &GpuContext, hud: &mut HudRenderer) {
+        self.bitmap.upload(gpu, hud);
+        self.distance_field.upload(gpu, hud);
+    }
+
+    /// Positions of the glyphs of `text` relative to its top left, one line
+    /// per `\n`. Characters missing from a font fall back to the next one in
+    /// the chain, and pairs from the same font are kerned.
+    fn shape(&mut self, text: &str, size: f32, style: TextStyle) -> Vec<PlacedGlyph> {
+        let distance_field = matches!(style, TextStyle::DistanceField(_));
+        let size = size.clamp(SIZE_RANGE.0, SIZE_RANGE.1);
+        // Bitmaps snap to whole pixels to stay sharp, fields scale freely
+        let size = if distance_field { size } else { size.round() };
+        let raster_size = if distance_field {
+            SDF_SIZE
+        } else {
+            size as u32
+        };
+        let scale = PxScale::from(size);
+        let primary = self.fonts[0].as_scaled(scale);
+        let line_height = primary.height() + primary.line_gap();
+        let tab = primary.h_advance(primary.glyph_id(' ')) * 4.0;
+
+        let mut placed = Vec::new();
+        let mut pen = Vec2::new(0.0, primary.ascent());
+        let mut previous: Option<(usize, GlyphId)> = None;
+        for c in text.chars() {
+            match c {
+                '\n' => {
+                    pen = Vec2::new(0.0, pen.y + line_height);
+                    previous = None;
+                    continue;
+                }
+                '\t' => {
+                    pen.x = ((pen.x / tab).floor() + 1.0) * tab;
+                    previous = None;
+                    continue;
+                }
+                c if c.is_control() => continue,
+                _ => {}
+            }
+            let font = self
+                .fonts
+                .iter()
+                .position(|font| font.glyph_id(c).0 != 0)
+                .unwrap_or(0);
+            let scaled = self.fonts[font].clone().into_scaled(scale);
+            let glyph = scaled.glyph_id(c);
+            if let Some((previous_font, previous_glyph)) = previous {
+                if previous_font == font {
+                    pen.x += scaled.kern(previous_glyph, glyph);
+                }
+            }
+
+            let key = GlyphKey {
+                font,
+                glyph,
+                size: raster_size,
+            };
+            self.rasterize(key, distance_field);
+            placed.push(PlacedGlyph {
+                key,
+                distance_field,
+                pen: if distance_field { pen } else { pen.round() },
+                scale: size / raster_size as f32,
+            });
+            pen.x += scaled.h_advance(glyph);
+            previous = Some((font, glyph));
+        }
+        placed
+    }
+
+    fn rasterize(&mut self, key: GlyphKey, distance_field: bool) {
+        let page = if distance_field {
+            &mut self.distance_field
+        } else {
+            &mut self.bitmap
+        };
+        if page.glyphs.contains_key(&key) {
+            page.stats.hits += 1;
+            return;
+        }
+        page.stats.misses += 1;
+
+        let glyph = key
+            .glyph
+            .with_scale_and_position(key.size as f32, ab_glyph::point(0.0, 0.0));
+        let Some(outlined) = self.fonts[key.font].outline_glyph(glyph) else {
+            page.glyphs.insert(key, None);
+            return;
+        };
+        let bounds = outlined.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        let mut coverage = vec![0.0; (width * height) as usize];
+        outlined.draw(|x, y, c| coverage[(y * width + x) as usize] = c);
+
+        let offset = Vec2::new(bounds.min.x, bounds.min.y);
+        if distance_field {
+            let bitmap = distance_field_image(&coverage, width, height);
+            page.insert(key, &bitmap, offset - SDF_SPREAD as f32);
+        } else {
+            let bitmap = image::RgbaImage::from_fn(width, height, |x, y| {
+                let alpha = coverage[(y * width + x) as usize].clamp(0.0, 1.0) * 255.0;
+                image::Rgba([255, 255, 255, alpha as u8])
+            });
+            page.insert(key, &bitmap, offset);
+        }
+    }
+}
+
+/// Signed distance to the glyph's outline, [`SDF_SPREAD`] pixels past it on
+/// every side, as alpha `0.5 + distance / (2 * spread)`. A brute force search
+/// of the neighborhood, fine for the few glyphs missing in a frame.
+fn distance_field_image(coverage: &[f32], width: u32, height: u32) -> image::RgbaImage {
+    let spread = SDF_SPREAD as i32;
+    let inside = |x: i32, y: i32| {
+        x >= 0
+            && y >= 0
+            && x < width as i32
+            && y < height as i32
+            && coverage[(y * width as i32 + x) as usize] >= 0.5
+    };
+    image::RgbaImage::from_fn(width + 2 * SDF_SPREAD, height + 2 * SDF_SPREAD, |x, y| {
+        let (x, y) = (x as i32 - spread, y as i32 - spread);
+        let here = inside(x, y);
+        // Nothing within reach counts as exactly the spread away
+        let mut nearest = (spread as f32 + 0.5).powi(2);
+        for dy in -spread..=spread {
+            for dx in -spread..=spread {
+                if inside(x + dx, y + dy) != here {
+                    nearest = nearest.min((dx * dx + dy * dy) as f32);
+                }
+            }
+        }
+        // Texel centers are half a texel from the edge between them
+        let distance = nearest.sqrt() - 0.5;
+        let signed = if here { distance } else { -distance };
+        let value = (0.5 + signed / (2.0 * SDF_SPREAD as f32)).clamp(0.0, 1.0);
+        image::Rgba([255, 255, 255, (value * 255.0).round() as u8])
+    })
+}
+
+// =============================== TESTS ===============================
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_field_is_signed_around_the_outline() {
+        // A 10x10 square
+        let coverage = vec![1.0; 100];
+        let field = distance_field_image(&coverage, 10, 10);
+        let alpha = |x: u32, y: u32| field.get_pixel(x, y)[3];
+        let left = SDF_SPREAD;
+        let row = SDF_SPREAD + 5;
+
+        // The texels either side of the left edge straddle the middle
+        assert!((128..=140).contains(&alpha(left, row)));
+        assert!((115..128).contains(&alpha(left - 1, row)));
+        // Deeper inside is further from the edge, the image corner is as far
+        // outside as the spread reaches
+        assert!(alpha(left + 4, row) > alpha(left + 1, row));
+        assert_eq!(alpha(0, 0), 0);
+    }
+}
@@ -9,20 +9,16 @@ use wgpu::TextureFormat;
 
 use crate::gpu::GpuContext;
 
-use super::present::FrameBuffer;
+use super::{graph::PassContext, present::FrameBuffer};
 
 pub fn setup_ui(world: &mut World, schedule: &mut Schedule) -> Result<()> {
     let gpu = world
         .get_resource::<GpuContext>()
         .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
 
-    let pipeline = EguiRenderer::new(
-        &gpu.device,
-        TextureFormat::Rgba16Float,
-        None,
-        1,
-        &gpu.window,
-    );
+    // Drawn straight onto the surface after present, so the UI stays sharp
+    // whatever the internal resolution is
+    let pipeline = EguiRenderer::new(&gpu.device, gpu.config.format, None, 1, &gpu.window);
     let app = egui_demo_lib::DemoWindows::default();
     let ui = EguiState {
         renderer: pipeline,
@@ -30,6 +26,7 @@ pub fn setup_ui(world: &mut World, schedule: &mut Schedule) -> Result<()> {
     };
 
     world.insert_resource(ui);
+    world.init_resource::<UiPanels>();
 
     schedule.add_systems(frame_buffer_changed_system.run_if(resource_changed::<FrameBuffer>));
 
@@ -45,6 +42,50 @@ pub fn frame_buffer_changed_system(
     let new_scale = gpu.window.scale_factor();
 }
 
+pub fn ui_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    world.resource_scope::<EguiState, _>(|world, mut ui| {
+        let gpu = world.resource::<GpuContext>();
+        ui.renderer.begin_frame(&gpu.window);
+        ui.run_app();
+
+        let context = ui.renderer.context().clone();
+        world.resource_scope::<UiPanels, _>(|world, mut panels| {
+            for panel in panels.panels.iter_mut() {
+                panel(&context, world);
+            }
+        });
+
+        let gpu = world.resource::<GpuContext>();
+        // Set up as a view format of the surface when the two differ
+        let view;
+        let target = if ui.renderer.output_format() == gpu.config.format {
+            ctx.surface_view
+        } else {
+            view = ctx
+                .surface_texture
+                .create_view(&wgpu::TextureViewDescriptor {
+                    format: Some(ui.renderer.output_format()),
+                    ..Default::default()
+                });
+            &view
+        };
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [gpu.config.width, gpu.config.height],
+            pixels_per_point: gpu.window.scale_factor() as f32,
+        };
+        ui.renderer.end_frame_and_draw(
+            &gpu.device,
+            &gpu.queue,
+            ctx.encoder,
+            &gpu.window,
+            target,
+            screen_descriptor,
+        );
+    });
+
+    Ok(())
+}
+
 // =============================== UI RESOURCE ===============================
 #[derive(Resource)]
 pub struct EguiState {
@@ -59,18 +100,40 @@ impl EguiState {
     }
 }
 
+// =============================== UI PANELS ===============================
+pub type UiPanelFn = Box<dyn FnMut(&egui::Context, &mut World) + Send + Sync>;
+
+/// Windows contributed by other modules, drawn every frame after the demo app.
+#[derive(Resource, Default)]
+pub struct UiPanels {
+    panels: Vec<UiPanelFn>,
+}
+impl UiPanels {
+    pub fn add_panel(
+        &mut self,
+        panel: impl FnMut(&egui::Context, &mut World) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.panels.push(Box::new(panel));
+        self
+    }
+}
+
 // =============================== RENDERER ===============================
 use egui::Context;
 use egui_wgpu::wgpu::{CommandEncoder, Device, Queue, StoreOp, TextureView};
 use egui_wgpu::{wgpu, Renderer, ScreenDescriptor};
 use egui_winit::{EventResponse, State};
+use playground_app::TextInputEvent;
 use winit::event::WindowEvent;
 use winit::window::Window;
 
 pub struct EguiRenderer {
     state: State,
     renderer: Renderer,
+    output_color_format: TextureFormat,
     frame_started: bool,
+    /// An `ImeEvent::Enabled` was sent without its `Disabled` yet.
+    ime_enabled: bool,
 }
 
 impl EguiRenderer {
@@ -106,14 +169,89 @@ impl EguiRenderer {
         EguiRenderer {
             state: egui_state,
             renderer: egui_renderer,
+            output_color_format,
             frame_started: false,
+            ime_enabled: false,
         }
     }
 
+    /// Format the renderer was built for, fixed for its lifetime.
+    pub fn output_format(&self) -> TextureFormat {
+        self.output_color_format
+    }
+
     pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) -> EventResponse {
+        // IME goes through handle_text_input, egui-winit ignores it on Linux
+        if let WindowEvent::Ime(_) = event {
+            return EventResponse {
+                repaint: true,
+                consumed: self.context().wants_keyboard_input(),
+            };
+        }
         self.state.on_window_event(window, event)
     }
 
+    /// Forwards a composition from the harness, bracketed by the enable and
+    /// disable events egui's text edits need to place it at the cursor.
+    pub fn handle_text_input(&mut self, event: &TextInputEvent) {
+        let events = &mut self.state.egui_input_mut().events;
+        if !self.ime_enabled {
+            events.push(egui::Event::Ime(egui::ImeEvent::Enabled));
+            self.ime_enabled = true;
+        }
+        match event {
+            TextInputEvent::Preedit(text) => {
+                events.push(egui::Event::Ime(egui::ImeEvent::Preedit(text.clone())));
+                return;
+            }
+            TextInputEvent::Commit(text) => {
+                events.push(egui::Event::Ime(egui::ImeEvent::Commit(text.clone())));
+            }
+            TextInputEvent::Cancel => {
+                events.push(egui::Event::Ime(egui::ImeEvent::Preedit(String::new())));
+            }
+        }
+        events.push(egui::Event::Ime(egui::ImeEvent::Disabled));
+        self.ime_enabled = false;
+    }
+
+    pub fn register_native_texture(
+        &mut self,
+        device: &Device,
+        view: &TextureView,
+        filter: wgpu::FilterMode,
+    ) -> egui::TextureId {
+        self.renderer.register_native_texture(device, view, filter)
+    }
+
+    /// Points an id from [`EguiRenderer::register_native_texture`] at a new
+    /// view, e.g. after the texture was recreated with a different size.
+    pub fn update_native_texture(
+        &mut self,
+        device: &Device,
+        view: &TextureView,
+        filter: wgpu::FilterMode,
+        id: egui::TextureId,
+    ) {
+        self.renderer
+            .update_egui_texture_from_wgpu_texture(device, view, filter, id);
+    }
+
+    /// Queues a key press and release for the next frame, for input that
+    /// doesn't come from the window.
+    pub fn press_key(&mut self, key: egui::Key, modifiers: egui::Modifiers) {
+        let events = &mut self.state.egui_input_mut().events;
+        for pressed in [true, false] {
+            events.push(egui::Event::Key {
+                key,
+                physical_key: None,
+                pressed,
+                repeat: false,
+                modifiers,
+            });
+        }
+    }
+
     pub fn ppp(&mut self, v: f32) {
         self.context().set_pixels_per_point(v);
     }
@@ -0,0 +1,531 @@
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use tracing::{error, warn};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pass::RenderPassBuilder,
+    shader::load_shader_source,
+};
+
+use super::{
+    compute::{read_buffer, DispatchSite, GPUComputePipeline},
+    graph::PassContext,
+    inspector::TextureRegistry,
+    present::FrameBuffer,
+    render::{render_system, submit_system},
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+const SHADER_NAME: &str = "validation.wgsl";
+/// Class of every pixel, see `validation.wgsl`.
+const MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+/// NaN, Inf, negative and out of range.
+const CLASSES: usize = 4;
+
+pub fn setup_validation(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let frame_buffer = world
+        .get_resource::<FrameBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("FrameBuffer resource not found"))?;
+
+    let settings = ValidationSettings::default();
+    let pipelines = ValidationPipelines::new(gpu, &settings)?;
+    let targets = ValidationTargets::new(gpu, &pipelines, &frame_buffer.texture.view, {
+        let size = frame_buffer.texture.texture.size();
+        [size.width, size.height]
+    });
+
+    world.insert_resource(pipelines);
+    world.insert_resource(targets);
+    world.insert_resource(settings);
+    world.init_resource::<ValidationState>();
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("validation_mask", |world| {
+            world
+                .get_resource::<ValidationTargets>()
+                .map(|targets| &targets.mask)
+        });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(validation_panel);
+
+    schedule.add_systems((
+        validation_targets_system
+            .run_if(resource_changed::<FrameBuffer>)
+            .before(render_system),
+        validation_params_system
+            .run_if(resource_changed::<ValidationSettings>)
+            .before(render_system),
+        validation_readback_system.after(submit_system),
+    ));
+
+    Ok(())
+}
+
+/// Follows the frame buffer across resizes.
+pub fn validation_targets_system(
+    gpu: Res<GpuContext>,
+    pipelines: Res<ValidationPipelines>,
+    frame_buffer: Res<FrameBuffer>,
+    mut targets: ResMut<ValidationTargets>,
+) {
+    let size = frame_buffer.texture.texture.size();
+    *targets = ValidationTargets::new(
+        &gpu,
+        &pipelines,
+        &frame_buffer.texture.view,
+        [size.width, size.height],
+    );
+}
+
+pub fn validation_params_system(
+    gpu: Res<GpuContext>,
+    settings: Res<ValidationSettings>,
+    pipelines: Res<ValidationPipelines>,
+) {
+    gpu.queue.write_buffer(
+        &pipelines.params,
+        0,
+        bytemuck::bytes_of(&ValidationParams::new(&settings)),
+    );
+}
+
+/// Classifies every pixel of the frame buffer and paints the bad ones over
+/// it, before any debug view or overlay lands on top.
+pub fn validation_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let settings = world.resource::<ValidationSettings>();
+    if !settings.enabled {
+        return Ok(());
+    }
+    let count = settings.count_pixels;
+    let gpu = world.resource::<GpuContext>();
+    let pipelines = world.resource::<ValidationPipelines>();
+    let targets = world.resource::<ValidationTargets>();
+    let frame_buffer = world.resource::<FrameBuffer>();
+
+    let [x, y, z] = DispatchSite {
+        label: "validation",
+        domain: [targets.size[0], targets.size[1], 1],
+    }
+    .validate(pipelines.classify.workgroup_size, &gpu.device.limits())?;
+
+    ctx.encoder.clear_buffer(&targets.counters, 0, None);
+    {
+        let mut compute_pass = ctx
+            .encoder
+            .begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("validation_classify"),
+                timestamp_writes: None,
+            });
+        compute_pass.set_pipeline(&pipelines.classify.pipeline);
+        compute_pass.set_bind_group(0, &targets.classify_bind_group, &[]);
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+    if count {
+        ctx.encoder.copy_buffer_to_buffer(
+            &targets.counters,
+            0,
+            &targets.readback,
+            0,
+            targets.counters.size(),
+        );
+    }
+    {
+        let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+            .with_label(ctx.label)
+            .with_color_view(&frame_buffer.texture.view)
+            .load()
+            .build()?;
+        render_pass.set_pipeline(&pipelines.overlay.render_pipeline);
+        render_pass.set_bind_group(0, &targets.overlay_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    world.resource_mut::<ValidationState>().pending = count;
+    Ok(())
+}
+
+/// Reads back the counters of the frame just submitted. Blocks on the GPU,
+/// so it only does work on frames that asked for it.
+pub fn validation_readback_system(
+    gpu: Res<GpuContext>,
+    targets: Res<ValidationTargets>,
+    mut state: ResMut<ValidationState>,
+) {
+    if !state.pending {
+        return;
+    }
+    state.pending = false;
+    let counts = match read_buffer::<u32>(&gpu.device, &targets.readback) {
+        Ok(counts) => ValidationCounts::from_slice(&counts),
+        Err(e) => {
+            error!("Failed to read back the validation counters: {:?}", e);
+            return;
+        }
+    };
+    // Once per streak of bad frames, the overlay shows where
+    let was_clean = state.counts.is_none_or(|counts| counts.total() == 0);
+    if counts.total() > 0 {
+        state.bad_frames += 1;
+        if was_clean {
+            warn!(
+                "Frame buffer has {} NaN, {} Inf, {} negative and {} out of range pixels",
+                counts.nan, counts.inf, counts.negative, counts.out_of_range
+            );
+        }
+    }
+    state.counts = Some(counts);
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct ValidationSettings {
+    pub enabled: bool,
+    /// Reads the counters back every frame, stalling on the GPU.
+    pub count_pixels: bool,
+    /// Channels above it are flagged as out of range.
+    pub max_value: f32,
+}
+impl Default for ValidationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            count_pixels: false,
+            max_value: 16.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ValidationCounts {
+    pub nan: u32,
+    pub inf: u32,
+    pub negative: u32,
+    pub out_of_range: u32,
+}
+impl ValidationCounts {
+    fn from_slice(counts: &[u32]) -> Self {
+        Self {
+            nan: counts[0],
+            inf: counts[1],
+            negative: counts[2],
+            out_of_range: counts[3],
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.nan + self.inf + self.negative + self.out_of_range
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ValidationState {
+    /// Of the last frame read back.
+    pub counts: Option<ValidationCounts>,
+    /// Frames read back with any flagged pixel.
+    pub bad_frames: u64,
+    /// Set by the pass, the counters are read back after the frame is submitted.
+    pending: bool,
+}
+
+// =============================== PIPELINES ===============================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ValidationParams {
+    pub max_value: f32,
+    pub _padding: [f32; 3],
+}
+impl ValidationParams {
+    pub fn new(settings: &ValidationSettings) -> Self {
+        Self {
+            max_value: settings.max_value,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct ValidationPipelines {
+    /// The frame buffer, the mask, the counters and the parameters.
+    pub classify_layout: wgpu::BindGroupLayout,
+    /// The mask, read by the overlay.
+    pub overlay_layout: wgpu::BindGroupLayout,
+    pub classify: GPUComputePipeline,
+    pub overlay: GPUPipeline,
+    pub params: wgpu::Buffer,
+}
+impl ValidationPipelines {
+    pub fn new(gpu: &GpuContext, settings: &ValidationSettings) -> Result<Self> {
+        let classify_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("validation_classify_layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Texture {
+                                multisampled: false,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::StorageTexture {
+                                access: wgpu::StorageTextureAccess::WriteOnly,
+                                format: MASK_FORMAT,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        let overlay_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("validation_overlay_layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Uint,
+                        },
+                        count: None,
+                    }],
+                });
+
+        let source = load_shader_source(SHADER_NAME, include_str!("../shaders/validation.wgsl"));
+        let classify = GPUComputePipeline::new(
+            &gpu.device,
+            "validation_classify_pipeline",
+            &source,
+            "cs_classify",
+            &[&classify_layout],
+            gpu.pipeline_cache(),
+        )?;
+
+        let source = load_shader_source(
+            "validation_overlay.wgsl",
+            include_str!("../shaders/validation_overlay.wgsl"),
+        );
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("validation_overlay_shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let overlay = GPUPipelineBuilder::new(&gpu.device)
+            .label("validation_overlay_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&overlay_layout)
+            .vertex_shader(&shader, "vs_main")
+            .fragment_shader(&shader, "fs_main")
+            .color_target(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Rgba16Float,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState::default())
+            .build()?;
+
+        let params = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("validation_params"),
+                contents: bytemuck::bytes_of(&ValidationParams::new(settings)),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        Ok(Self {
+            classify_layout,
+            overlay_layout,
+            classify,
+            overlay,
+            params,
+        })
+    }
+}
+
+/// The mask and counters for a frame buffer of one size, with the bind
+/// groups reading it.
+#[derive(Resource)]
+pub struct ValidationTargets {
+    pub mask: wgpu::Texture,
+    pub counters: wgpu::Buffer,
+    pub readback: wgpu::Buffer,
+    pub classify_bind_group: wgpu::BindGroup,
+    pub overlay_bind_group: wgpu::BindGroup,
+    pub size: [u32; 2],
+}
+impl ValidationTargets {
+    pub fn new(
+        gpu: &GpuContext,
+        pipelines: &ValidationPipelines,
+        frame_buffer: &wgpu::TextureView,
+        size: [u32; 2],
+    ) -> Self {
+        let mask = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("validation_mask"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: MASK_FORMAT,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let mask_view = mask.create_view(&Default::default());
+        let counters_size = (CLASSES * std::mem::size_of::<u32>()) as u64;
+        let counters = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("validation_counters"),
+            size: counters_size,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("validation_readback"),
+            size: counters_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let classify_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("validation_classify_bind_group"),
+            layout: &pipelines.classify_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(frame_buffer),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&mask_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: counters.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: pipelines.params.as_entire_binding(),
+                },
+            ],
+        });
+        let overlay_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("validation_overlay_bind_group"),
+            layout: &pipelines.overlay_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&mask_view),
+            }],
+        });
+
+        Self {
+            mask,
+            counters,
+            readback,
+            classify_bind_group,
+            overlay_bind_group,
+            size,
+        }
+    }
+}
+
+// =============================== UI ===============================
+fn validation_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<ValidationSettings>().clone();
+    let (counts, bad_frames) = {
+        let state = world.resource::<ValidationState>();
+        (state.counts, state.bad_frames)
+    };
+
+    egui::Window::new(tr("Frame validation"))
+        .id(egui::Id::new("Frame validation"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Flag invalid pixels"));
+            ui.add_enabled_ui(settings.enabled, |ui| {
+                ui.add(
+                    egui::Slider::new(&mut settings.max_value, 1.0..=65504.0)
+                        .logarithmic(true)
+                        .text(tr("Out of range above")),
+                );
+                ui.checkbox(&mut settings.count_pixels, tr("Count bad pixels"))
+                    .on_hover_text(tr(
+                        "Reads the counts back every frame, which waits for the GPU",
+                    ));
+            });
+            ui.colored_label(egui::Color32::from_rgb(255, 0, 255), tr("NaN: magenta"));
+            ui.colored_label(
+                egui::Color32::from_rgb(255, 0, 255),
+                tr("Inf: magenta and black stripes"),
+            );
+            ui.colored_label(
+                egui::Color32::from_rgb(0, 255, 255),
+                tr("Negative: cyan stripes"),
+            );
+            ui.colored_label(
+                egui::Color32::from_rgb(255, 255, 0),
+                tr("Out of range: yellow stripes"),
+            );
+            if let Some(counts) = counts.filter(|_| settings.enabled && settings.count_pixels) {
+                ui.separator();
+                ui.label(trf!(
+                    "NaN {}, Inf {}, negative {}, out of range {}",
+                    counts.nan,
+                    counts.inf,
+                    counts.negative,
+                    counts.out_of_range
+                ));
+                ui.label(trf!("{} frames with bad pixels", bad_frames));
+            }
+        });
+
+    let mut current = world.resource_mut::<ValidationSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+
+use crate::{gpu::GpuContext, texture::Texture};
+
+use super::{
+    inspector::TextureRegistry,
+    present::{render_scale_system, FrameBuffer},
+    render::render_system,
+};
+
+/// Screen space motion in UV units per frame, enough range and precision for
+/// anything short of teleports.
+pub const VELOCITY_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+pub fn setup_velocity(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let frame_buffer = world
+        .get_resource::<FrameBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("FrameBuffer resource not found"))?;
+
+    let size = frame_buffer.texture.texture.size();
+    let texture = Texture::render_target(
+        &gpu.device,
+        size.width,
+        size.height,
+        VELOCITY_FORMAT,
+        "velocity_buffer",
+    );
+    world.insert_resource(VelocityBuffer { texture });
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("velocity", |world| {
+            world
+                .get_resource::<VelocityBuffer>()
+                .map(|velocity| &velocity.texture.texture)
+        });
+
+    schedule.add_systems(
+        velocity_resize_system
+            .run_if(resource_changed::<FrameBuffer>)
+            .after(render_scale_system)
+            .before(render_system),
+    );
+
+    Ok(())
+}
+
+/// Follows the frame buffer, whether it changed with the window or the
+/// render scale. Has to run after both resizes, the mesh pass fails on
+/// attachments of different sizes.
+pub fn velocity_resize_system(
+    gpu: Res<GpuContext>,
+    frame_buffer: Res<FrameBuffer>,
+    mut velocity: ResMut<VelocityBuffer>,
+) {
+    let size = frame_buffer.texture.texture.size();
+    let current = velocity.texture.texture.size();
+    if size.width == current.width && size.height == current.height {
+        return;
+    }
+    velocity
+        .texture
+        .resize(&gpu.device, &gpu.queue, size.width, size.height);
+}
+
+/// Per pixel motion since the previous frame, written by the mesh pass as a
+/// second color target. Anything not drawn by it stays at zero.
+#[derive(Resource)]
+pub struct VelocityBuffer {
+    pub texture: Texture,
+}
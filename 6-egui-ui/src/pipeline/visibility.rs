@@ -0,0 +1,541 @@
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Vec3, Vec4};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pass::RenderPassBuilder,
+    scene::{draw_list_system, Camera, DrawList, MaterialTable},
+    texture::Texture,
+    vertex::{cube_vertices, quad_vertices, MeshVertex},
+};
+
+use super::{
+    graph::PassContext, inspector::TextureRegistry, mesh::CameraBuffer, present::FrameBuffer,
+    render::render_system, ui::UiPanels, GPUPipeline, GPUPipelineBuilder,
+};
+
+const INITIAL_INSTANCES: u64 = 1024;
+/// Bits of a visibility ID that hold the triangle; the rest is the instance.
+const TRIANGLE_BITS: u32 = 12;
+
+pub fn setup_visibility(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let camera = world
+        .get_resource::<CameraBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("CameraBuffer resource not found"))?;
+
+    let geometry = VisibilityGeometry::new(gpu, INITIAL_INSTANCES)?;
+    let pipelines = VisibilityPipelines::new(gpu, camera, &geometry)?;
+    let targets = VisibilityTargets::new(
+        gpu,
+        &pipelines.shade_layout,
+        gpu.config.width,
+        gpu.config.height,
+    );
+
+    world.insert_resource(geometry);
+    world.insert_resource(targets);
+    world.insert_resource(pipelines);
+    world.insert_resource(VisibilitySettings::default());
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("visibility_shaded", |world| {
+            world
+                .get_resource::<VisibilityTargets>()
+                .map(|targets| &targets.shaded.texture)
+        });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(visibility_panel);
+
+    schedule.add_systems((
+        visibility_instance_system
+            .after(draw_list_system)
+            .before(render_system),
+        visibility_target_system.before(render_system),
+    ));
+
+    Ok(())
+}
+
+/// Uploads the opaque draws of the draw list as instances.
+pub fn visibility_instance_system(
+    gpu: Res<GpuContext>,
+    settings: Res<VisibilitySettings>,
+    draw_list: Res<DrawList>,
+    materials: Res<MaterialTable>,
+    mut geometry: ResMut<VisibilityGeometry>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    // Whatever doesn't fit in the instance bits of an ID is left out
+    let items = draw_list
+        .opaque
+        .iter()
+        .take(((1 << (32 - TRIANGLE_BITS)) - 1) as usize);
+    let instances: Vec<VisibilityInstance> = items
+        .clone()
+        .map(|item| {
+            let (first_vertex, _) = geometry.mesh(item.key.mesh.0 as usize);
+            let color = materials
+                .materials
+                .get(item.key.material.0 as usize)
+                .map_or(Vec4::ONE, |material| material.base_color);
+            VisibilityInstance {
+                model: item.model.to_cols_array_2d(),
+                color: color.to_array(),
+                first_vertex,
+                _padding: [0; 3],
+            }
+        })
+        .collect();
+    geometry.instance_meshes = items.map(|item| item.key.mesh.0 as usize).collect();
+    geometry.write_instances(&gpu, &instances);
+}
+
+/// Keeps the targets at the size of the frame buffer and uploads the shading
+/// uniform.
+pub fn visibility_target_system(
+    gpu: Res<GpuContext>,
+    settings: Res<VisibilitySettings>,
+    camera: Res<Camera>,
+    frame_buffer: Res<FrameBuffer>,
+    pipelines: Res<VisibilityPipelines>,
+    mut targets: ResMut<VisibilityTargets>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    let size = frame_buffer.texture.texture.size();
+    if size.width != targets.width || size.height != targets.height {
+        *targets = VisibilityTargets::new(&gpu, &pipelines.shade_layout, size.width, size.height);
+    }
+
+    let uniform = ShadeUniform {
+        inv_view_proj: camera.view_projection().inverse().to_cols_array_2d(),
+        eye: camera.eye.extend(1.0).to_array(),
+        light: Vec3::new(0.4, 1.0, 0.3).normalize().extend(0.0).to_array(),
+        mode: settings.mode as u32,
+        _padding: [0; 3],
+    };
+    gpu.queue
+        .write_buffer(&targets.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+}
+
+/// Rasterizes triangle IDs, then resolves them into colors in a full-screen
+/// pass that fetches the triangle's attributes itself.
+pub fn visibility_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    if !world.resource::<VisibilitySettings>().enabled {
+        return Ok(());
+    }
+    let camera = world.resource::<CameraBuffer>();
+    let geometry = world.resource::<VisibilityGeometry>();
+    let targets = world.resource::<VisibilityTargets>();
+    let pipelines = world.resource::<VisibilityPipelines>();
+
+    {
+        let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+            .with_label(ctx.label)
+            .with_color_view(&targets.ids_view)
+            .with_depth(&targets.depth.view, 1.0)
+            .build()?;
+
+        render_pass.set_pipeline(&pipelines.rasterize.render_pipeline);
+        render_pass.set_bind_group(0, &camera.bind_group, &[]);
+        render_pass.set_bind_group(1, &geometry.bind_group, &[]);
+        for (instance, mesh) in geometry.instance_meshes.iter().enumerate() {
+            let (_, vertex_count) = geometry.mesh(*mesh);
+            render_pass.draw(0..vertex_count, instance as u32..instance as u32 + 1);
+        }
+    }
+
+    let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+        .with_label("visibility_shade")
+        .with_color_view(&targets.shaded.view)
+        .build()?;
+
+    render_pass.set_pipeline(&pipelines.shade.render_pipeline);
+    render_pass.set_bind_group(0, &targets.bind_group, &[]);
+    render_pass.set_bind_group(1, &geometry.bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+
+    Ok(())
+}
+
+fn visibility_panel(ctx: &egui::Context, world: &mut World) {
+    let instances = world.resource::<VisibilityGeometry>().instance_meshes.len();
+    let mut settings = world.resource_mut::<VisibilitySettings>();
+
+    egui::Window::new(tr("Visibility buffer"))
+        .id(egui::Id::new("Visibility buffer"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Enabled"));
+            egui::ComboBox::from_label(tr("Output"))
+                .selected_text(format!("{:?}", settings.mode))
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        VisibilityMode::Shaded,
+                        VisibilityMode::Triangles,
+                        VisibilityMode::Instances,
+                    ] {
+                        ui.selectable_value(&mut settings.mode, mode, format!("{:?}", mode));
+                    }
+                });
+            ui.label(trf!("{} opaque instances", instances));
+            ui.label(tr(
+                "View the result as visibility_shaded in the texture inspector.",
+            ));
+        });
+}
+
+// =============================== RESOURCES ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VisibilityMode {
+    Shaded = 0,
+    Triangles = 1,
+    Instances = 2,
+}
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct VisibilitySettings {
+    pub enabled: bool,
+    pub mode: VisibilityMode,
+}
+impl Default for VisibilitySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: VisibilityMode::Shaded,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GeometryVertex {
+    pub position: [f32; 4],
+    pub normal: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VisibilityInstance {
+    pub model: [[f32; 4]; 4],
+    pub color: [f32; 4],
+    pub first_vertex: u32,
+    pub _padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadeUniform {
+    pub inv_view_proj: [[f32; 4]; 4],
+    pub eye: [f32; 4],
+    pub light: [f32; 4],
+    pub mode: u32,
+    pub _padding: [u32; 3],
+}
+
+// =============================== GEOMETRY ===============================
+/// Every built-in mesh in one storage buffer, plus the instances drawn this
+/// frame. Both passes read vertices from here rather than vertex buffers.
+#[derive(Resource)]
+pub struct VisibilityGeometry {
+    pub vertices: wgpu::Buffer,
+    pub instances: wgpu::Buffer,
+    pub capacity: u64,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    /// First vertex and vertex count of each mesh, indexed by `MeshId`.
+    pub meshes: Vec<(u32, u32)>,
+    /// Mesh of every uploaded instance.
+    pub instance_meshes: Vec<usize>,
+}
+impl VisibilityGeometry {
+    pub fn new(gpu: &GpuContext, capacity: u64) -> Result<Self> {
+        // Same order as the built-in `MeshId` constants
+        let mut vertices = Vec::new();
+        let mut meshes = Vec::new();
+        for mesh in [cube_vertices(), quad_vertices()] {
+            meshes.push((vertices.len() as u32, mesh.len() as u32));
+            vertices.extend(mesh.iter().map(|v: &MeshVertex| GeometryVertex {
+                position: [v.position[0], v.position[1], v.position[2], 1.0],
+                normal: [v.normal[0], v.normal[1], v.normal[2], 0.0],
+            }));
+        }
+        if meshes
+            .iter()
+            .any(|(_, count)| count / 3 >= 1 << TRIANGLE_BITS)
+        {
+            anyhow::bail!("Mesh has too many triangles for a visibility ID");
+        }
+
+        let vertices = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("visibility_vertices"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let instances = Self::create_instances(gpu, capacity);
+        let entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[entry(0), entry(1)],
+                label: Some("visibility_geometry_bind_group_layout"),
+            });
+        let bind_group = Self::create_bind_group(gpu, &layout, &vertices, &instances);
+
+        Ok(Self {
+            vertices,
+            instances,
+            capacity,
+            layout,
+            bind_group,
+            meshes,
+            instance_meshes: Vec::new(),
+        })
+    }
+
+    fn create_instances(gpu: &GpuContext, capacity: u64) -> wgpu::Buffer {
+        gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("visibility_instances"),
+            size: capacity * std::mem::size_of::<VisibilityInstance>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        gpu: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        vertices: &wgpu::Buffer,
+        instances: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: vertices.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: instances.as_entire_binding(),
+                },
+            ],
+            label: Some("visibility_geometry_bind_group"),
+        })
+    }
+
+    /// First vertex and vertex count of `mesh`, empty if it is unknown.
+    pub fn mesh(&self, mesh: usize) -> (u32, u32) {
+        self.meshes.get(mesh).copied().unwrap_or((0, 0))
+    }
+
+    /// Uploads `instances`, growing the buffer if they don't fit.
+    pub fn write_instances(&mut self, gpu: &GpuContext, instances: &[VisibilityInstance]) {
+        let len = instances.len() as u64;
+        if len > self.capacity {
+            self.capacity = len.next_power_of_two();
+            self.instances = Self::create_instances(gpu, self.capacity);
+            self.bind_group =
+                Self::create_bind_group(gpu, &self.layout, &self.vertices, &self.instances);
+        }
+        if !instances.is_empty() {
+            gpu.queue
+                .write_buffer(&self.instances, 0, bytemuck::cast_slice(instances));
+        }
+    }
+}
+
+// =============================== TARGETS ===============================
+#[derive(Resource)]
+pub struct VisibilityTargets {
+    #[allow(unused)]
+    pub ids: wgpu::Texture,
+    pub ids_view: wgpu::TextureView,
+    pub depth: Texture,
+    pub shaded: Texture,
+    pub uniform_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub width: u32,
+    pub height: u32,
+}
+impl VisibilityTargets {
+    pub fn new(gpu: &GpuContext, layout: &wgpu::BindGroupLayout, width: u32, height: u32) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let ids = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("visibility_ids"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Uint,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let ids_view = ids.create_view(&Default::default());
+        let depth = Texture::depth_texture(&gpu.device, width, height);
+        let shaded =
+            Texture::frame_buffer_texture(&gpu.device, width, height, Some("visibility_shaded"), 1);
+
+        let uniform_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("visibility_shade_uniform_buffer"),
+            size: std::mem::size_of::<ShadeUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&ids_view),
+                },
+            ],
+            label: Some("visibility_shade_bind_group"),
+        });
+
+        Self {
+            ids,
+            ids_view,
+            depth,
+            shaded,
+            uniform_buffer,
+            bind_group,
+            width,
+            height,
+        }
+    }
+
+    /// Shading inputs: the uniform and the ID target.
+    pub fn layout(gpu: &GpuContext) -> wgpu::BindGroupLayout {
+        gpu.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Uint,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("visibility_shade_bind_group_layout"),
+            })
+    }
+}
+
+// =============================== PIPELINE ===============================
+#[derive(Resource)]
+pub struct VisibilityPipelines {
+    pub rasterize: GPUPipeline,
+    pub shade_layout: wgpu::BindGroupLayout,
+    pub shade: GPUPipeline,
+}
+impl VisibilityPipelines {
+    pub fn new(
+        gpu: &GpuContext,
+        camera: &CameraBuffer,
+        geometry: &VisibilityGeometry,
+    ) -> Result<Self> {
+        let rasterize_shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("visibility_shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/visibility.wgsl").into()),
+            });
+        let rasterize = GPUPipelineBuilder::new(&gpu.device)
+            .label("visibility_rasterize_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&camera.layout)
+            .bind_group_layout(&geometry.layout)
+            .vertex_shader(&rasterize_shader, "vs_main")
+            .fragment_shader(&rasterize_shader, "fs_main")
+            // Integer targets can't blend
+            .color_target(wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::R32Uint,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .default_depth_stencil_state()
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            })
+            .build()?;
+
+        let shade_shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("visibility_shade_shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/visibility_shade.wgsl").into(),
+                ),
+            });
+        let shade_layout = VisibilityTargets::layout(gpu);
+        let shade = GPUPipelineBuilder::new(&gpu.device)
+            .label("visibility_shade_pipeline")
+            .pipeline_cache(gpu.pipeline_cache())
+            .bind_group_layout(&shade_layout)
+            .bind_group_layout(&geometry.layout)
+            .vertex_shader(&shade_shader, "vs_main")
+            .fragment_shader(&shade_shader, "fs_main")
+            .default_color_target(wgpu::TextureFormat::Rgba16Float)
+            .default_multisample_state()
+            .primitive_state(wgpu::PrimitiveState::default())
+            .build()?;
+
+        Ok(Self {
+            rasterize,
+            shade_layout,
+            shade,
+        })
+    }
+}
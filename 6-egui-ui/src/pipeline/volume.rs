@@ -0,0 +1,448 @@
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{Condition, IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use glam::Vec3;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::tr,
+    noise,
+    pass::RenderPassBuilder,
+    rng::Rng,
+    scene::{camera_aspect_system, Camera},
+    texture::Texture,
+    time::TimeContext,
+};
+
+use super::{
+    depth::DepthTexture,
+    graph::PassContext,
+    inspector::TextureRegistry,
+    present::FrameBuffer,
+    render::render_system,
+    scaled::{scaled_depth_resize_system, ResolutionScale, ScaledDepth, Upsampler},
+    ui::UiPanels,
+    GPUPipeline, GPUPipelineBuilder,
+};
+
+const NOISE_SIZE: u32 = 64;
+const NOISE_PERIOD: u32 = 8;
+const BOX_CENTER: Vec3 = Vec3::new(0.0, 7.0, 0.0);
+const BOX_HALF_EXTENTS: Vec3 = Vec3::new(12.0, 3.0, 12.0);
+
+pub fn setup_volume(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let seed = world
+        .get_resource_mut::<Rng>()
+        .ok_or_else(|| anyhow::anyhow!("Rng resource not found"))?
+        .next_u32();
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let depth = world
+        .get_resource::<DepthTexture>()
+        .ok_or_else(|| anyhow::anyhow!("DepthTexture resource not found"))?;
+    let scaled = world
+        .get_resource::<ScaledDepth>()
+        .ok_or_else(|| anyhow::anyhow!("ScaledDepth resource not found"))?;
+
+    let noise = Texture::volume_from_fn(
+        &gpu.device,
+        &gpu.queue,
+        [NOISE_SIZE; 3],
+        wgpu::TextureFormat::R8Unorm,
+        "volume_noise",
+        |x, y, z| {
+            let p = Vec3::new(x as f32, y as f32, z as f32) / NOISE_SIZE as f32;
+            (noise::fbm_value(p * NOISE_PERIOD as f32, 4, NOISE_PERIOD, seed) * 255.0) as u8
+        },
+    )?;
+    let settings = VolumeSettings::default();
+    let bind_group_layout = VolumeBindGroupLayout::new(gpu);
+    let volume_depth = scaled.view(settings.resolution, depth);
+    let bind_group = VolumeBindGroup::new(gpu, &bind_group_layout, &noise, volume_depth);
+    let pipeline = VolumePipeline::new(gpu, &bind_group_layout)?;
+    let target = VolumeTarget::new(gpu, &pipeline, depth, scaled, settings.resolution);
+
+    world
+        .get_resource_or_insert_with(TextureRegistry::default)
+        .register("volume_noise", |world| {
+            world
+                .get_resource::<VolumeNoise>()
+                .map(|noise| &noise.texture.texture)
+        });
+    world.insert_resource(VolumeNoise { texture: noise });
+    world.insert_resource(bind_group_layout);
+    world.insert_resource(bind_group);
+    world.insert_resource(pipeline);
+    world.insert_resource(target);
+    world.insert_resource(settings);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(volume_panel);
+
+    schedule.add_systems((
+        volume_bind_group_system
+            .run_if(resource_changed::<DepthTexture>.or(resource_changed::<VolumeSettings>))
+            .after(scaled_depth_resize_system)
+            .before(render_system),
+        volume_target_system
+            .run_if(resource_changed::<DepthTexture>.or(resource_changed::<VolumeSettings>))
+            .after(scaled_depth_resize_system)
+            .before(render_system),
+        volume_uniform_system
+            .after(camera_aspect_system)
+            .before(render_system),
+    ));
+
+    Ok(())
+}
+
+pub fn volume_bind_group_system(
+    gpu: Res<GpuContext>,
+    layout: Res<VolumeBindGroupLayout>,
+    noise: Res<VolumeNoise>,
+    depth: Res<DepthTexture>,
+    scaled: Res<ScaledDepth>,
+    settings: Res<VolumeSettings>,
+    mut bind_group: ResMut<VolumeBindGroup>,
+) {
+    let volume_depth = scaled.view(settings.resolution, &depth);
+    *bind_group = VolumeBindGroup::new(&gpu, &layout, &noise.texture, volume_depth);
+}
+
+/// Follows the depth texture at the configured resolution scale.
+pub fn volume_target_system(
+    gpu: Res<GpuContext>,
+    depth: Res<DepthTexture>,
+    scaled: Res<ScaledDepth>,
+    settings: Res<VolumeSettings>,
+    pipeline: Res<VolumePipeline>,
+    mut target: ResMut<VolumeTarget>,
+) {
+    *target = VolumeTarget::new(&gpu, &pipeline, &depth, &scaled, settings.resolution);
+}
+
+pub fn volume_uniform_system(
+    gpu: Res<GpuContext>,
+    camera: Res<Camera>,
+    time: Res<TimeContext>,
+    settings: Res<VolumeSettings>,
+    bind_group: Res<VolumeBindGroup>,
+) {
+    let uniform = VolumeUniform {
+        inv_view_proj: camera.view_projection().inverse().to_cols_array_2d(),
+        eye: camera.eye.extend(1.0).to_array(),
+        box_min: (BOX_CENTER - BOX_HALF_EXTENTS).extend(1.0).to_array(),
+        box_max: (BOX_CENTER + BOX_HALF_EXTENTS).extend(1.0).to_array(),
+        params: [
+            settings.density,
+            settings.steps as f32,
+            settings.coverage,
+            time.total,
+        ],
+    };
+    gpu.queue
+        .write_buffer(&bind_group.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+}
+
+/// Raymarches the noise volume over the frame buffer, stopping at the scene
+/// depth. At a reduced resolution it marches into the volume target instead
+/// and upsamples that onto the frame buffer.
+pub fn volume_pass(world: &mut World, ctx: &mut PassContext) -> Result<()> {
+    let settings = world.resource::<VolumeSettings>();
+    if !settings.enabled {
+        return Ok(());
+    }
+    let scale = settings.resolution;
+    let frame_buffer = world.resource::<FrameBuffer>();
+    let pipeline = world.resource::<VolumePipeline>();
+    let bind_group = world.resource::<VolumeBindGroup>();
+
+    if scale == ResolutionScale::Full {
+        let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+            .with_label(ctx.label)
+            .with_color_view(&frame_buffer.texture.view)
+            .load()
+            .build()?;
+
+        render_pass.set_pipeline(&pipeline.pipeline.render_pipeline);
+        render_pass.set_bind_group(0, &bind_group.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        return Ok(());
+    }
+
+    let target = world.resource::<VolumeTarget>();
+    world
+        .resource::<ScaledDepth>()
+        .downsample(ctx.encoder, scale)?;
+    {
+        let mut render_pass = RenderPassBuilder::new(ctx.encoder)
+            .with_label(ctx.label)
+            .with_cleared_color_view(&target.texture.view, wgpu::Color::TRANSPARENT)
+            .build()?;
+
+        render_pass.set_pipeline(&pipeline.offscreen.render_pipeline);
+        render_pass.set_bind_group(0, &bind_group.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+    pipeline.upsampler.draw(
+        ctx.encoder,
+        &frame_buffer.texture.view,
+        &target.upsample_bind_group,
+    )
+}
+
+fn volume_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<VolumeSettings>().clone();
+
+    egui::Window::new(tr("Volume"))
+        .id(egui::Id::new("Volume"))
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.enabled, tr("Enabled"));
+            ui.add(egui::Slider::new(&mut settings.density, 0.0..=10.0).text(tr("density")));
+            ui.add(egui::Slider::new(&mut settings.coverage, 0.0..=1.0).text(tr("coverage")));
+            ui.add(egui::Slider::new(&mut settings.steps, 8..=256).text(tr("steps")));
+            ui.horizontal(|ui| {
+                ui.label(tr("resolution"));
+                settings.resolution.combo(ui, "volume_resolution");
+            });
+        });
+
+    let mut current = world.resource_mut::<VolumeSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+// =============================== RESOURCES ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct VolumeSettings {
+    pub enabled: bool,
+    pub density: f32,
+    pub coverage: f32,
+    pub steps: u32,
+    /// Resolution the raymarch runs at before it's upsampled.
+    pub resolution: ResolutionScale,
+}
+impl Default for VolumeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            density: 2.0,
+            coverage: 0.45,
+            steps: 64,
+            resolution: ResolutionScale::Full,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct VolumeNoise {
+    pub texture: Texture,
+}
+
+/// Premultiplied volume at the configured resolution scale, unused at full
+/// resolution where the march draws straight onto the frame buffer.
+#[derive(Resource)]
+pub struct VolumeTarget {
+    pub texture: Texture,
+    pub upsample_bind_group: wgpu::BindGroup,
+}
+impl VolumeTarget {
+    pub fn new(
+        gpu: &GpuContext,
+        pipeline: &VolumePipeline,
+        depth: &DepthTexture,
+        scaled: &ScaledDepth,
+        scale: ResolutionScale,
+    ) -> Self {
+        let size = depth.texture.texture.size();
+        let (width, height) = scale.size(size.width, size.height);
+        let texture = Texture::render_target(
+            &gpu.device,
+            width,
+            height,
+            wgpu::TextureFormat::Rgba16Float,
+            "volume_target",
+        );
+        let upsample_bind_group = pipeline
+            .upsampler
+            .bind_group(gpu, &texture, scaled, scale, depth);
+        Self {
+            texture,
+            upsample_bind_group,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct VolumeUniform {
+    pub inv_view_proj: [[f32; 4]; 4],
+    pub eye: [f32; 4],
+    pub box_min: [f32; 4],
+    pub box_max: [f32; 4],
+    pub params: [f32; 4],
+}
+
+// =============================== BIND GROUP ===============================
+#[derive(Resource)]
+pub struct VolumeBindGroupLayout {
+    pub layout: wgpu::BindGroupLayout,
+}
+impl VolumeBindGroupLayout {
+    pub fn new(gpu: &GpuContext) -> Self {
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D3,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("volume_bind_group_layout"),
+            });
+
+        Self { layout }
+    }
+}
+
+#[derive(Resource)]
+pub struct VolumeBindGroup {
+    pub bind_group: wgpu::BindGroup,
+    pub uniform_buffer: wgpu::Buffer,
+}
+impl VolumeBindGroup {
+    pub fn new(
+        gpu: &GpuContext,
+        layout: &VolumeBindGroupLayout,
+        noise: &Texture,
+        depth: &wgpu::TextureView,
+    ) -> Self {
+        let uniform_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("volume_uniform_buffer"),
+            size: std::mem::size_of::<VolumeUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &layout.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&noise.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&noise.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+            ],
+            label: Some("volume_bind_group"),
+        });
+
+        Self {
+            bind_group,
+            uniform_buffer,
+        }
+    }
+}
+
+// =============================== PIPELINE ===============================
+#[derive(Resource)]
+pub struct VolumePipeline {
+    /// Blends straight onto the frame buffer.
+    pub pipeline: GPUPipeline,
+    /// Writes into the volume target for upsampling.
+    pub offscreen: GPUPipeline,
+    pub upsampler: Upsampler,
+}
+impl VolumePipeline {
+    pub fn new(gpu: &GpuContext, layout: &VolumeBindGroupLayout) -> Result<Self> {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("volume_shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/volume.wgsl").into()),
+            });
+        let build = |label, blend| {
+            GPUPipelineBuilder::new(&gpu.device)
+                .label(label)
+                .pipeline_cache(gpu.pipeline_cache())
+                .bind_group_layout(&layout.layout)
+                .vertex_shader(&shader, "vs_main")
+                .fragment_shader(&shader, "fs_main")
+                .color_target(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })
+                .depth_stencil_state(None)
+                .default_multisample_state()
+                .primitive_state(wgpu::PrimitiveState::default())
+                .build()
+                .map_err(|e| anyhow::anyhow!(e))
+        };
+        let pipeline = build(
+            "volume_pipeline",
+            Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+        )?;
+        let offscreen = build("volume_offscreen_pipeline", None)?;
+        let upsampler = Upsampler::new(
+            gpu,
+            "volume_upsample_pipeline",
+            wgpu::TextureFormat::Rgba16Float,
+            Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+        )?;
+
+        Ok(Self {
+            pipeline,
+            offscreen,
+            upsampler,
+        })
+    }
+}
@@ -0,0 +1,1261 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use bevy_ecs::{
+    observer::Trigger,
+    schedule::Schedule,
+    system::{Res, Resource},
+    world::{Mut, World},
+};
+use playground_app::{FrameEnd, FrameStart};
+use serde_json::json;
+use tracing::{
+    field::{Field, Visit},
+    info, span, warn, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+use tracing_tracy::client::{Client, GpuContext as TracyGpuContext, GpuContextType, GpuSpan};
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pipeline::{
+        graph::{
+            AsyncComputeSettings, GraphReport, GraphValidationSettings, PassQueue, RenderGraph,
+            Severity,
+        },
+        ui::UiPanels,
+    },
+};
+
+pub fn setup_profiler(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    match GpuTimer::new(gpu) {
+        Some(timer) => world.insert_resource(timer),
+        None => info!("Timestamp queries not supported, GPU timings will not be captured"),
+    }
+    world.insert_resource(SubmissionTimeline::default());
+    world.insert_resource(GpuZoneSettings::default());
+    world.add_observer(profiler_frame_start_observer);
+    world.add_observer(profiler_frame_end_observer);
+
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(profiler_panel);
+
+    Ok(())
+}
+
+pub fn profiler_frame_start_observer(
+    _trigger: Trigger<FrameStart>,
+    timings: Option<Res<SystemTimings>>,
+) {
+    if let Some(timings) = timings {
+        timings.begin_frame();
+    }
+}
+
+/// Marks the frame boundary for Tracy and counts the frame towards a capture.
+pub fn profiler_frame_end_observer(
+    _trigger: Trigger<FrameEnd>,
+    capture: Option<Res<TraceCapture>>,
+    timings: Option<Res<SystemTimings>>,
+) {
+    Client::running()
+        .expect("client must be running")
+        .frame_mark();
+    if let Some(capture) = capture {
+        capture.end_frame();
+    }
+    if let Some(timings) = timings {
+        timings.end_frame();
+    }
+}
+
+fn profiler_panel(ctx: &egui::Context, world: &mut World) {
+    let has_gpu_timer = world.contains_resource::<GpuTimer>();
+    let mut async_compute = *world.resource::<AsyncComputeSettings>();
+    let mut validation = *world.resource::<GraphValidationSettings>();
+    let mut zones = *world.resource::<GpuZoneSettings>();
+    let report = world.resource::<RenderGraph>().report().cloned();
+    world.resource_scope(|world, timeline: Mut<SubmissionTimeline>| {
+        let Some(mut capture) = world.get_resource_mut::<TraceCapture>() else {
+            return;
+        };
+        profiler_window(
+            ctx,
+            &mut capture,
+            has_gpu_timer.then_some(&mut zones),
+            &timeline,
+            &mut async_compute,
+            &mut validation,
+            report.as_ref(),
+        );
+    });
+
+    let mut current = world.resource_mut::<AsyncComputeSettings>();
+    if *current != async_compute {
+        *current = async_compute;
+    }
+    let mut current = world.resource_mut::<GraphValidationSettings>();
+    if *current != validation {
+        *current = validation;
+    }
+    let mut current = world.resource_mut::<GpuZoneSettings>();
+    if *current != zones {
+        *current = zones;
+    }
+
+    if let Some(mut timings) = world.get_resource_mut::<SystemTimings>() {
+        let mut sort = timings.sort;
+        systems_window(ctx, &timings, &mut sort);
+        if sort != timings.sort {
+            timings.sort = sort;
+        }
+    }
+}
+
+fn systems_window(ctx: &egui::Context, timings: &SystemTimings, sort: &mut SystemSort) {
+    egui::Window::new(tr("System timings"))
+        .id(egui::Id::new("System timings"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            let mut recording = timings.is_recording();
+            if ui
+                .checkbox(&mut recording, tr("Record system timings"))
+                .changed()
+            {
+                timings.set_recording(recording);
+            }
+
+            let state = timings.state.lock().unwrap();
+            let Some(last) = state.frames.back() else {
+                ui.label(tr("No frames recorded"));
+                return;
+            };
+            let busy: Duration = last.spans.iter().map(SystemSpan::duration).sum();
+            ui.label(trf!(
+                "Frame {:.2} ms, {:.2} ms in {} systems",
+                last.duration().as_secs_f64() * 1e3,
+                busy.as_secs_f64() * 1e3,
+                last.spans.len()
+            ));
+            systems_flame(ui, last);
+
+            ui.separator();
+            let stats = state.stats(*sort);
+            let frames = state.frames.len();
+            drop(state);
+            egui::ScrollArea::vertical()
+                .max_height(320.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("system_timings_grid")
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (column, name) in [
+                                (SystemSort::Name, tr("System")),
+                                (SystemSort::Last, tr("Last ms")),
+                                (SystemSort::Average, tr("Average ms")),
+                                (SystemSort::Max, tr("Max ms")),
+                            ] {
+                                let text = if *sort == column {
+                                    format!("{} \u{25BC}", name)
+                                } else {
+                                    name.to_string()
+                                };
+                                if ui.selectable_label(*sort == column, text).clicked() {
+                                    *sort = column;
+                                }
+                            }
+                            ui.end_row();
+                            for stat in &stats {
+                                ui.label(short_system_name(&stat.name))
+                                    .on_hover_text(&*stat.name);
+                                ui.monospace(format!("{:.3}", stat.last_ms));
+                                ui.monospace(format!("{:.3}", stat.average_ms));
+                                ui.monospace(format!("{:.3}", stat.max_ms));
+                                ui.end_row();
+                            }
+                        });
+                });
+            ui.label(trf!("Over the last {} frames", frames));
+        });
+}
+
+/// The last frame from its start to its end, one lane per thread that ran
+/// systems, every system a bar from when it started to when it finished.
+fn systems_flame(ui: &mut egui::Ui, frame: &FrameSystems) {
+    const LANE_HEIGHT: f32 = 14.0;
+    let mut threads: Vec<u64> = Vec::new();
+    for span in &frame.spans {
+        if !threads.contains(&span.thread) {
+            threads.push(span.thread);
+        }
+    }
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(
+            ui.available_width().max(320.0),
+            LANE_HEIGHT * threads.len().max(1) as f32,
+        ),
+        egui::Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(180));
+
+    let span = frame.duration().as_secs_f32().max(1e-6);
+    let x = |instant: Instant| {
+        rect.left()
+            + instant.saturating_duration_since(frame.start).as_secs_f32() / span * rect.width()
+    };
+    let pointer = response.hover_pos();
+    let mut hovered = None;
+    for system in &frame.spans {
+        let lane = threads
+            .iter()
+            .position(|&t| t == system.thread)
+            .unwrap_or(0);
+        let top = rect.top() + lane as f32 * LANE_HEIGHT;
+        let bar = egui::Rect::from_x_y_ranges(
+            x(system.start)..=x(system.end).max(x(system.start) + 1.0),
+            top + 1.0..=top + LANE_HEIGHT - 1.0,
+        );
+        painter.rect_filled(bar, 1.0, system_color(&system.name));
+        if bar.width() > 40.0 {
+            painter.text(
+                bar.left_center() + egui::vec2(2.0, 0.0),
+                egui::Align2::LEFT_CENTER,
+                short_system_name(&system.name),
+                egui::FontId::monospace(8.0),
+                egui::Color32::BLACK,
+            );
+        }
+        if pointer.is_some_and(|pointer| bar.contains(pointer)) {
+            hovered = Some(system);
+        }
+    }
+    if let Some(system) = hovered {
+        response.on_hover_text_at_pointer(format!(
+            "{}\n{:.3} ms",
+            system.name,
+            system.duration().as_secs_f64() * 1e3
+        ));
+    }
+}
+
+/// A stable color per system, so a system is easy to follow across frames.
+fn system_color(name: &str) -> egui::Color32 {
+    let hash = name.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+    egui::ecolor::Hsva::new((hash % 360) as f32 / 360.0, 0.45, 0.85, 1.0).into()
+}
+
+/// `egui_ui::pipeline::render::render_system` as `render_system`, closures
+/// keep the function they are in.
+fn short_system_name(name: &str) -> &str {
+    let mut segments = name.rsplitn(3, "::");
+    let last = segments.next().unwrap_or(name);
+    if !last.starts_with('{') {
+        return last;
+    }
+    match (segments.next(), segments.next()) {
+        (Some(parent), Some(_)) => &name[name.len() - last.len() - parent.len() - 2..],
+        _ => name,
+    }
+}
+
+fn profiler_window(
+    ctx: &egui::Context,
+    capture: &mut TraceCapture,
+    // `None` without a GPU timer
+    zones: Option<&mut GpuZoneSettings>,
+    timeline: &SubmissionTimeline,
+    async_compute: &mut AsyncComputeSettings,
+    validation: &mut GraphValidationSettings,
+    report: Option<&GraphReport>,
+) {
+    egui::Window::new(tr("Profiler"))
+        .id(egui::Id::new("Profiler"))
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(tr("Frames to capture"));
+                ui.add(egui::DragValue::new(&mut capture.frames_to_capture).range(1..=600));
+            });
+            match zones {
+                Some(zones) => {
+                    ui.checkbox(&mut zones.tracy, tr("GPU zones in Tracy"))
+                        .on_hover_text(tr(
+                            "Times every pass on the GPU, which waits for the GPU at the end of each frame",
+                        ));
+                }
+                None => {
+                    ui.label(tr("GPU timestamps unavailable on this adapter"));
+                }
+            }
+
+            let frames_left = capture.frames_left();
+            if frames_left > 0 {
+                ui.label(trf!("Capturing... {} frames left", frames_left));
+            } else if ui.button(tr("Start capture")).clicked() {
+                let frames = capture.frames_to_capture;
+                capture.start(frames);
+            }
+
+            let (events, frames) = capture.summary();
+            if frames_left == 0 && events > 0 {
+                ui.label(trf!("Captured {} events over {} frames", events, frames));
+                if ui.button(tr("Export chrome trace")).clicked() {
+                    capture.last_export = Some(capture.export());
+                }
+            }
+            match &capture.last_export {
+                Some(Ok(path)) => {
+                    ui.label(trf!("Saved to {}", path.display()));
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(egui::Color32::RED, trf!("Export failed: {}", e));
+                }
+                None => {}
+            }
+
+            ui.separator();
+            ui.checkbox(&mut async_compute.enabled, tr("Async compute submission"));
+            timeline_plot(ui, timeline);
+
+            ui.separator();
+            ui.checkbox(&mut validation.enabled, tr("Validate render graph"))
+                .on_hover_text(tr(
+                    "Logs the pass order and resource uses, and explains wgpu validation errors by pass",
+                ));
+            if let Some(report) = report {
+                let errors = report.errors();
+                ui.label(trf!(
+                    "{} passes, {} errors, {} warnings",
+                    report.order.len(),
+                    errors,
+                    report.hazards.len() - errors
+                ));
+                for hazard in &report.hazards {
+                    let color = match hazard.severity {
+                        Severity::Warning => egui::Color32::YELLOW,
+                        Severity::Error => egui::Color32::RED,
+                    };
+                    ui.colored_label(color, &hazard.message);
+                }
+            }
+        });
+}
+
+/// One lane per queue, every submission drawn from when it was submitted to
+/// when its fence was seen signaled.
+fn timeline_plot(ui: &mut egui::Ui, timeline: &SubmissionTimeline) {
+    const LANE_HEIGHT: f32 = 18.0;
+    let lanes = [
+        (
+            PassQueue::AsyncCompute,
+            "compute",
+            egui::Color32::from_rgb(230, 160, 60),
+        ),
+        (
+            PassQueue::Graphics,
+            "graphics",
+            egui::Color32::from_rgb(90, 160, 230),
+        ),
+    ];
+    let (rect, _) = ui.allocate_exact_size(
+        egui::vec2(
+            ui.available_width().max(320.0),
+            LANE_HEIGHT * lanes.len() as f32,
+        ),
+        egui::Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_black_alpha(180));
+
+    let (Some(first), Some(now)) = (
+        timeline.submissions.front().map(|s| s.submitted),
+        timeline.submissions.back().map(|s| s.submitted),
+    ) else {
+        return;
+    };
+    let end = timeline
+        .submissions
+        .iter()
+        .filter_map(Submission::done)
+        .fold(now, Instant::max);
+    let span = end.saturating_duration_since(first).as_secs_f32().max(1e-6);
+    let x = |instant: Instant| {
+        rect.left() + instant.saturating_duration_since(first).as_secs_f32() / span * rect.width()
+    };
+
+    for (row, (queue, name, color)) in lanes.iter().enumerate() {
+        let top = rect.top() + row as f32 * LANE_HEIGHT;
+        painter.text(
+            egui::pos2(rect.left() + 2.0, top + 1.0),
+            egui::Align2::LEFT_TOP,
+            name,
+            egui::FontId::monospace(9.0),
+            egui::Color32::GRAY,
+        );
+        for submission in timeline.submissions.iter().filter(|s| s.queue == *queue) {
+            // Still in flight, drawn up to the newest submission
+            let done = submission.done();
+            let bar = egui::Rect::from_x_y_ranges(
+                x(submission.submitted)..=x(done.unwrap_or(end)).max(x(submission.submitted) + 2.0),
+                top + 5.0..=top + LANE_HEIGHT - 2.0,
+            );
+            let fill = if done.is_some() {
+                *color
+            } else {
+                color.gamma_multiply(0.4)
+            };
+            painter.rect_filled(bar, 1.0, fill);
+            painter.text(
+                bar.left_center(),
+                egui::Align2::LEFT_CENTER,
+                format!("#{}", submission.index),
+                egui::FontId::monospace(8.0),
+                egui::Color32::BLACK,
+            );
+        }
+    }
+    ui.label(trf!(
+        "Last {} frames, {:.2} ms",
+        SubmissionTimeline::FRAMES,
+        span * 1e3
+    ));
+}
+
+// =============================== SUBMISSIONS ===============================
+pub struct Submission {
+    /// Counts every submission made through the timeline.
+    pub index: u64,
+    pub frame: u64,
+    pub queue: PassQueue,
+    pub submitted: Instant,
+    /// Set from the work done callback, so it's when a device poll noticed
+    /// the fence rather than when the GPU signaled it.
+    done: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Submission {
+    pub fn done(&self) -> Option<Instant> {
+        *self.done.lock().unwrap()
+    }
+}
+
+/// The render system's queue submissions over the last few frames.
+#[derive(Resource, Default)]
+pub struct SubmissionTimeline {
+    next_index: u64,
+    frame: u64,
+    submissions: VecDeque<Submission>,
+}
+
+impl SubmissionTimeline {
+    pub const FRAMES: u64 = 8;
+
+    /// Drops frames that scrolled out. Fences are picked up as the
+    /// [`crate::gpu::PollStrategy`] polls.
+    pub fn begin_frame(&mut self) {
+        self.frame += 1;
+        while self
+            .submissions
+            .front()
+            .is_some_and(|submission| submission.frame + Self::FRAMES <= self.frame)
+        {
+            self.submissions.pop_front();
+        }
+    }
+
+    /// Submits `command_buffers` together and watches for their fence.
+    pub fn submit(
+        &mut self,
+        queue: &wgpu::Queue,
+        lane: PassQueue,
+        command_buffers: impl IntoIterator<Item = wgpu::CommandBuffer>,
+    ) -> wgpu::SubmissionIndex {
+        let index = queue.submit(command_buffers);
+        let done = Arc::new(Mutex::new(None));
+        let signal = done.clone();
+        queue.on_submitted_work_done(move || {
+            *signal.lock().unwrap() = Some(Instant::now());
+        });
+        self.submissions.push_back(Submission {
+            index: self.next_index,
+            frame: self.frame,
+            queue: lane,
+            submitted: Instant::now(),
+            done,
+        });
+        self.next_index += 1;
+        index
+    }
+}
+
+// =============================== TRACE CAPTURE ===============================
+struct TraceEvent {
+    name: String,
+    tid: u64,
+    start_us: f64,
+    duration_us: f64,
+}
+
+#[derive(Default)]
+struct CaptureState {
+    origin: Option<Instant>,
+    frames_left: u32,
+    frames_captured: u32,
+    events: Vec<TraceEvent>,
+    /// First to last timestamp of every captured frame.
+    gpu_frames_ms: Vec<f64>,
+}
+
+impl CaptureState {
+    fn micros_since_origin(&self, instant: Instant) -> f64 {
+        self.origin
+            .map(|origin| instant.saturating_duration_since(origin).as_secs_f64() * 1e6)
+            .unwrap_or(0.0)
+    }
+}
+
+/// Records CPU spans and GPU pass timings for a range of frames so they can be
+/// exported as a chrome://tracing (or Perfetto) compatible JSON file.
+#[derive(Resource, Clone)]
+pub struct TraceCapture {
+    capturing: Arc<AtomicBool>,
+    state: Arc<Mutex<CaptureState>>,
+    pub frames_to_capture: u32,
+    pub last_export: Option<Result<PathBuf, String>>,
+}
+
+impl Default for TraceCapture {
+    fn default() -> Self {
+        Self {
+            capturing: Arc::new(AtomicBool::new(false)),
+            state: Arc::new(Mutex::new(CaptureState::default())),
+            frames_to_capture: 60,
+            last_export: None,
+        }
+    }
+}
+
+impl TraceCapture {
+    pub fn layer(&self) -> CaptureLayer {
+        CaptureLayer {
+            capture: self.clone(),
+        }
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capturing.load(Ordering::Relaxed)
+    }
+
+    pub fn start(&mut self, frames: u32) {
+        let mut state = self.state.lock().unwrap();
+        *state = CaptureState {
+            origin: Some(Instant::now()),
+            frames_left: frames.max(1),
+            ..Default::default()
+        };
+        self.last_export = None;
+        self.capturing.store(true, Ordering::Relaxed);
+    }
+
+    pub fn frames_left(&self) -> u32 {
+        self.state.lock().unwrap().frames_left
+    }
+
+    /// Returns the number of captured events and frames.
+    pub fn summary(&self) -> (usize, u32) {
+        let state = self.state.lock().unwrap();
+        (state.events.len(), state.frames_captured)
+    }
+
+    /// Mean GPU time of the captured frames, if timestamps were recorded.
+    pub fn average_gpu_frame_ms(&self) -> Option<f64> {
+        let state = self.state.lock().unwrap();
+        if state.gpu_frames_ms.is_empty() {
+            return None;
+        }
+        Some(state.gpu_frames_ms.iter().sum::<f64>() / state.gpu_frames_ms.len() as f64)
+    }
+
+    /// Marks the end of a frame, stopping the capture once enough frames were recorded.
+    pub fn end_frame(&self) {
+        if !self.is_capturing() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.frames_captured += 1;
+        state.frames_left = state.frames_left.saturating_sub(1);
+        if state.frames_left == 0 {
+            self.capturing.store(false, Ordering::Relaxed);
+            info!(
+                "Trace capture finished: {} events over {} frames",
+                state.events.len(),
+                state.frames_captured
+            );
+        }
+    }
+
+    /// Records GPU pass timings, aligning the first timestamp with `frame_start`.
+    pub fn record_gpu(&self, frame_start: Instant, timings: &[GpuPassTiming]) {
+        if !self.is_capturing() {
+            return;
+        }
+        let Some(first) = timings.iter().map(|t| t.start_ns).reduce(f64::min) else {
+            return;
+        };
+        let last = timings.iter().map(|t| t.end_ns).fold(first, f64::max);
+        let mut state = self.state.lock().unwrap();
+        state.gpu_frames_ms.push((last - first) / 1e6);
+        let base_us = state.micros_since_origin(frame_start);
+        for timing in timings {
+            state.events.push(TraceEvent {
+                name: timing.label.to_string(),
+                tid: GPU_TID,
+                start_us: base_us + (timing.start_ns - first) / 1e3,
+                duration_us: (timing.end_ns - timing.start_ns).max(0.0) / 1e3,
+            });
+        }
+    }
+
+    fn record_cpu(&self, name: String, start: Instant, end: Instant) {
+        let mut state = self.state.lock().unwrap();
+        let start_us = state.micros_since_origin(start);
+        let event = TraceEvent {
+            name,
+            tid: current_tid(),
+            start_us,
+            duration_us: end.duration_since(start).as_secs_f64() * 1e6,
+        };
+        state.events.push(event);
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let state = self.state.lock().unwrap();
+        let mut events = vec![
+            json!({ "name": "process_name", "ph": "M", "pid": 0, "args": { "name": "wgpu-playground" } }),
+            json!({ "name": "thread_name", "ph": "M", "pid": 0, "tid": GPU_TID, "args": { "name": "GPU" } }),
+        ];
+        events.extend(state.events.iter().map(|event| {
+            json!({
+                "name": event.name,
+                "cat": if event.tid == GPU_TID { "gpu" } else { "cpu" },
+                "ph": "X",
+                "pid": 0,
+                "tid": event.tid,
+                "ts": event.start_us,
+                "dur": event.duration_us,
+            })
+        }));
+        json!({ "traceEvents": events, "displayTimeUnit": "ms" })
+    }
+
+    /// Writes the captured range to `trace-<unix time>.json` in the working directory.
+    pub fn export(&self) -> Result<PathBuf, String> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let path = PathBuf::from(format!("trace-{}.json", timestamp));
+        let contents = serde_json::to_string(&self.to_json()).map_err(|e| e.to_string())?;
+        std::fs::write(&path, contents).map_err(|e| e.to_string())?;
+        info!("Exported chrome trace to {}", path.display());
+        Ok(path)
+    }
+}
+
+const GPU_TID: u64 = 0;
+
+fn current_tid() -> u64 {
+    static NEXT_TID: AtomicU64 = AtomicU64::new(GPU_TID + 1);
+    thread_local! {
+        static TID: u64 = NEXT_TID.fetch_add(1, Ordering::Relaxed);
+    }
+    TID.with(|tid| *tid)
+}
+
+// =============================== TRACING LAYER ===============================
+/// Span name used in the trace, taken from a `pass` field when present.
+struct SpanName(String);
+struct SpanStart(Instant);
+
+struct PassFieldVisitor(Option<String>);
+
+impl Visit for PassFieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "pass" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "pass" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+/// Tracing layer feeding span timings into a [`TraceCapture`] while it is active.
+pub struct CaptureLayer {
+    capture: TraceCapture,
+}
+
+impl<S> Layer<S> for CaptureLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = PassFieldVisitor(None);
+        attrs.record(&mut visitor);
+        let name = visitor
+            .0
+            .unwrap_or_else(|| attrs.metadata().name().to_string());
+        span.extensions_mut().insert(SpanName(name));
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if !self.capture.is_capturing() {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().replace(SpanStart(Instant::now()));
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(SpanStart(start)) = extensions.remove::<SpanStart>() else {
+            return;
+        };
+        if !self.capture.is_capturing() {
+            return;
+        }
+        let name = extensions
+            .get_mut::<SpanName>()
+            .map(|name| name.0.clone())
+            .unwrap_or_default();
+        self.capture.record_cpu(name, start, Instant::now());
+    }
+}
+
+// =============================== SYSTEM TIMINGS ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SystemSort {
+    Name,
+    Last,
+    Average,
+    Max,
+}
+
+pub struct SystemSpan {
+    pub name: Arc<str>,
+    pub thread: u64,
+    pub start: Instant,
+    pub end: Instant,
+}
+
+impl SystemSpan {
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_duration_since(self.start)
+    }
+}
+
+pub struct FrameSystems {
+    pub start: Instant,
+    pub end: Instant,
+    pub spans: Vec<SystemSpan>,
+}
+
+impl FrameSystems {
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_duration_since(self.start)
+    }
+}
+
+/// One system over the recorded frames, frames it didn't run in count as 0.
+pub struct SystemStats {
+    pub name: Arc<str>,
+    pub last_ms: f64,
+    pub average_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Default)]
+struct SystemTimingState {
+    frame_start: Option<Instant>,
+    current: Vec<SystemSpan>,
+    frames: VecDeque<FrameSystems>,
+}
+
+impl SystemTimingState {
+    fn stats(&self, sort: SystemSort) -> Vec<SystemStats> {
+        let mut systems: HashMap<Arc<str>, SystemStats> = HashMap::new();
+        let last_frame = self.frames.len().saturating_sub(1);
+        for (index, frame) in self.frames.iter().enumerate() {
+            // A system can run more than once a frame, its runs add up
+            let mut frame_ms: HashMap<&Arc<str>, f64> = HashMap::new();
+            for span in &frame.spans {
+                *frame_ms.entry(&span.name).or_default() += span.duration().as_secs_f64() * 1e3;
+            }
+            for (name, ms) in frame_ms {
+                let stats = systems.entry(name.clone()).or_insert_with(|| SystemStats {
+                    name: name.clone(),
+                    last_ms: 0.0,
+                    average_ms: 0.0,
+                    max_ms: 0.0,
+                });
+                stats.average_ms += ms / self.frames.len() as f64;
+                stats.max_ms = stats.max_ms.max(ms);
+                if index == last_frame {
+                    stats.last_ms = ms;
+                }
+            }
+        }
+        let mut stats: Vec<SystemStats> = systems.into_values().collect();
+        match sort {
+            SystemSort::Name => {
+                stats.sort_by(|a, b| short_system_name(&a.name).cmp(short_system_name(&b.name)))
+            }
+            SystemSort::Last => stats.sort_by(|a, b| b.last_ms.total_cmp(&a.last_ms)),
+            SystemSort::Average => stats.sort_by(|a, b| b.average_ms.total_cmp(&a.average_ms)),
+            SystemSort::Max => stats.sort_by(|a, b| b.max_ms.total_cmp(&a.max_ms)),
+        }
+        stats
+    }
+}
+
+/// CPU time of every ECS system over the last frames, taken from the spans
+/// bevy_ecs opens around each system run.
+#[derive(Resource, Clone)]
+pub struct SystemTimings {
+    recording: Arc<AtomicBool>,
+    state: Arc<Mutex<SystemTimingState>>,
+    pub sort: SystemSort,
+}
+
+impl Default for SystemTimings {
+    fn default() -> Self {
+        Self {
+            recording: Arc::new(AtomicBool::new(true)),
+            state: Arc::new(Mutex::new(SystemTimingState::default())),
+            sort: SystemSort::Average,
+        }
+    }
+}
+
+impl SystemTimings {
+    /// Frames kept for the averages.
+    pub const FRAMES: usize = 120;
+
+    pub fn layer(&self) -> SystemTimingLayer {
+        SystemTimingLayer {
+            timings: self.clone(),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    pub fn set_recording(&self, recording: bool) {
+        self.recording.store(recording, Ordering::Relaxed);
+    }
+
+    pub fn begin_frame(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.frame_start = Some(Instant::now());
+        state.current.clear();
+    }
+
+    /// Files the systems that ran since [`Self::begin_frame`] as a frame.
+    pub fn end_frame(&self) {
+        let mut state = self.state.lock().unwrap();
+        let Some(start) = state.frame_start.take() else {
+            return;
+        };
+        if !self.is_recording() {
+            return;
+        }
+        let spans = std::mem::take(&mut state.current);
+        if state.frames.len() == Self::FRAMES {
+            state.frames.pop_front();
+        }
+        state.frames.push_back(FrameSystems {
+            start,
+            end: Instant::now(),
+            spans,
+        });
+    }
+
+    fn record(&self, name: Arc<str>, start: Instant, end: Instant) {
+        let mut state = self.state.lock().unwrap();
+        // Systems run outside a frame, like at startup, aren't kept
+        if state.frame_start.is_none() {
+            return;
+        }
+        state.current.push(SystemSpan {
+            name,
+            thread: current_tid(),
+            start,
+            end,
+        });
+    }
+}
+
+struct SystemName(Arc<str>);
+struct SystemStart(Instant);
+
+struct NameFieldVisitor(Option<String>);
+
+impl Visit for NameFieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "name" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+/// Tracing layer timing the `system` spans into [`SystemTimings`].
+pub struct SystemTimingLayer {
+    timings: SystemTimings,
+}
+
+impl<S> Layer<S> for SystemTimingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != "system" {
+            return;
+        }
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = NameFieldVisitor(None);
+        attrs.record(&mut visitor);
+        if let Some(name) = visitor.0 {
+            span.extensions_mut().insert(SystemName(name.into()));
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if !self.timings.is_recording() {
+            return;
+        }
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if extensions.get_mut::<SystemName>().is_some() {
+            extensions.replace(SystemStart(Instant::now()));
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(SystemStart(start)) = extensions.remove::<SystemStart>() else {
+            return;
+        };
+        let Some(SystemName(name)) = extensions.get_mut::<SystemName>() else {
+            return;
+        };
+        self.timings.record(name.clone(), start, Instant::now());
+    }
+}
+
+// =============================== GPU TIMER ===============================
+pub struct GpuPassTiming {
+    pub label: &'static str,
+    pub start_ns: f64,
+    pub end_ns: f64,
+}
+
+/// Whether frames are timed on the GPU outside of trace captures too.
+#[derive(Resource, Clone, Copy, Default, PartialEq)]
+pub struct GpuZoneSettings {
+    /// Sends every pass to Tracy as a GPU zone.
+    pub tracy: bool,
+}
+
+/// Timestamp queries written around every render graph pass, and the Tracy
+/// GPU zones opened along with them.
+#[derive(Resource)]
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    capacity: u32,
+    period: f32,
+    labels: Vec<&'static str>,
+    /// One per label, `None` when Tracy isn't running or is out of zones.
+    spans: Vec<Option<GpuSpan>>,
+    tracy: Option<TracyGpuContext>,
+    active: bool,
+}
+
+impl GpuTimer {
+    const MAX_PASSES: u32 = 64;
+
+    pub fn new(gpu: &GpuContext) -> Option<Self> {
+        let required =
+            wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS;
+        if !gpu.device.features().contains(required) {
+            return None;
+        }
+
+        let capacity = Self::MAX_PASSES * 2;
+        let query_set = gpu.device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("gpu_timer_query_set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: capacity,
+        });
+        let size = capacity as u64 * wgpu::QUERY_SIZE as u64;
+        let resolve_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu_timer_resolve_buffer"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu_timer_readback_buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut timer = Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            capacity,
+            period: gpu.queue.get_timestamp_period(),
+            labels: Vec::new(),
+            spans: Vec::new(),
+            tracy: None,
+            active: false,
+        };
+        timer.tracy = Client::running().and_then(|client| timer.tracy_context(gpu, client));
+        Some(timer)
+    }
+
+    /// Tracy lines the GPU clock up with its own from a timestamp taken now.
+    fn tracy_context(&self, gpu: &GpuContext, client: Client) -> Option<TracyGpuContext> {
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("gpu_timer_calibration"),
+            });
+        encoder.write_timestamp(&self.query_set, 0);
+        self.copy_to_readback(&mut encoder, 1);
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+        let now = *self.read_ticks(&gpu.device, 1)?.first()?;
+
+        let ty = match gpu.adapter_info.backend {
+            wgpu::Backend::Vulkan => GpuContextType::Vulkan,
+            wgpu::Backend::Dx12 => GpuContextType::Direct3D12,
+            wgpu::Backend::Gl => GpuContextType::OpenGL,
+            _ => GpuContextType::Invalid,
+        };
+        client
+            .new_gpu_context(Some("wgpu"), ty, now as i64, self.period)
+            .map_err(|e| warn!("Failed to create the Tracy GPU context: {}", e))
+            .ok()
+    }
+
+    /// Unfinished zones of the last frame are dropped, which moves them out
+    /// of the way in Tracy.
+    pub fn begin_frame(&mut self, active: bool) {
+        self.labels.clear();
+        self.spans.clear();
+        self.active = active;
+    }
+
+    /// Returns the query the pass ends on, `None` when it isn't timed, e.g.
+    /// past the capacity.
+    pub fn begin_pass(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        label: &'static str,
+    ) -> Option<u32> {
+        let index = self.labels.len() as u32 * 2;
+        if !self.active || index + 1 >= self.capacity {
+            return None;
+        }
+        encoder.write_timestamp(&self.query_set, index);
+        self.labels.push(label);
+        // Right next to the timestamp, Tracy takes the CPU time from here
+        let span = self.tracy.as_ref().and_then(|tracy| {
+            tracy
+                .span_alloc(label, "RenderGraph::record", file!(), line!())
+                .ok()
+        });
+        self.spans.push(span);
+        Some(index + 1)
+    }
+
+    /// Ends a pass [`Self::begin_pass`] started timing, so a pass that wasn't
+    /// timed can't overwrite the end of another one.
+    pub fn end_pass(&mut self, encoder: &mut wgpu::CommandEncoder, pass: Option<u32>) {
+        if let Some(index) = pass {
+            encoder.write_timestamp(&self.query_set, index);
+            if let Some(span) = self
+                .spans
+                .get_mut(index as usize / 2)
+                .and_then(Option::as_mut)
+            {
+                span.end_zone();
+            }
+        }
+    }
+
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        if !self.active || self.labels.is_empty() {
+            return;
+        }
+        self.copy_to_readback(encoder, self.labels.len() as u32 * 2);
+    }
+
+    fn copy_to_readback(&self, encoder: &mut wgpu::CommandEncoder, count: u32) {
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            count as u64 * wgpu::QUERY_SIZE as u64,
+        );
+    }
+
+    /// Blocks until the frame's timestamps are available, and hands them to
+    /// Tracy too. Only call after submitting.
+    pub fn read_back(&mut self, device: &wgpu::Device) -> Vec<GpuPassTiming> {
+        if !self.active || self.labels.is_empty() {
+            return Vec::new();
+        }
+        let Some(ticks) = self.read_ticks(device, self.labels.len() as u32 * 2) else {
+            return Vec::new();
+        };
+        for (span, ticks) in self.spans.drain(..).zip(ticks.chunks_exact(2)) {
+            if let Some(mut span) = span {
+                span.end_zone();
+                span.upload_timestamp(ticks[0] as i64, ticks[1] as i64);
+            }
+        }
+        self.labels
+            .iter()
+            .enumerate()
+            .map(|(i, label)| GpuPassTiming {
+                label,
+                start_ns: ticks[i * 2] as f64 * self.period as f64,
+                end_ns: ticks[i * 2 + 1] as f64 * self.period as f64,
+            })
+            .collect()
+    }
+
+    /// The first `count` timestamps copied to the readback buffer, `None`
+    /// when it couldn't be mapped.
+    fn read_ticks(&self, device: &wgpu::Device, count: u32) -> Option<Vec<u64>> {
+        let size = count as u64 * wgpu::QUERY_SIZE as u64;
+        let slice = self.readback_buffer.slice(..size);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        // A lost device fails the map, the frame then goes without timings
+        match receiver.try_recv() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                warn!("Failed to map the GPU timestamps: {}", e);
+                return None;
+            }
+            Err(_) => {
+                warn!("The GPU timestamps weren't mapped after waiting");
+                return None;
+            }
+        }
+        let ticks = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        self.readback_buffer.unmap();
+        Some(ticks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::info_span;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    fn run(name: &str, busy: Duration) {
+        let _span = info_span!("system", name = name).entered();
+        let start = Instant::now();
+        while start.elapsed() < busy {}
+    }
+
+    #[test]
+    fn system_spans_are_timed_per_frame() {
+        let timings = SystemTimings::default();
+        let subscriber = tracing_subscriber::registry().with(timings.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            // Before the first frame, like setup
+            run("egui_ui::setup_system", Duration::ZERO);
+            for _ in 0..2 {
+                timings.begin_frame();
+                run(
+                    "egui_ui::pipeline::render::render_system",
+                    Duration::from_millis(4),
+                );
+                run("egui_ui::input::input_system", Duration::ZERO);
+                run("egui_ui::input::input_system", Duration::ZERO);
+                let _other = info_span!("pass", pass = "shadows").entered();
+                timings.end_frame();
+            }
+        });
+
+        let state = timings.state.lock().unwrap();
+        assert_eq!(state.frames.len(), 2);
+        assert_eq!(state.frames[1].spans.len(), 3);
+        let stats = state.stats(SystemSort::Average);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(short_system_name(&stats[0].name), "render_system");
+        assert!(stats[0].average_ms >= 4.0 && stats[0].last_ms >= 4.0);
+        let by_name = state.stats(SystemSort::Name);
+        assert_eq!(short_system_name(&by_name[0].name), "input_system");
+        assert_eq!(
+            short_system_name("egui_ui::main::setup::{{closure}}"),
+            "setup::{{closure}}"
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use anyhow::Result;
+use bevy_ecs::{
+    entity::Entity,
+    schedule::Schedule,
+    system::{Query, Res, Resource, SystemParam},
+    world::World,
+};
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{
+    scene::{Aabb, Camera, GlobalTransform, MeshId, Renderable, Visibility},
+    vertex::{cube_vertices, quad_vertices, MeshVertex},
+};
+
+pub fn setup_raycast(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    world.insert_resource(MeshColliders::default());
+    Ok(())
+}
+
+// =============================== RAY ===============================
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+impl Ray {
+    /// Ray through a cursor position given in physical pixels.
+    pub fn from_screen(camera: &Camera, cursor: Vec2, size: Vec2) -> Self {
+        let ndc = Vec2::new(cursor.x / size.x * 2.0 - 1.0, 1.0 - cursor.y / size.y * 2.0);
+        let inverse = camera.view_projection().inverse();
+        let near = inverse.project_point3(ndc.extend(0.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+        Self {
+            origin: near,
+            direction: (far - near).normalize(),
+        }
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// The same ray in another space. The direction is left unnormalized so
+    /// distances along it still match the original ray.
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        Self {
+            origin: matrix.transform_point3(self.origin),
+            direction: matrix.transform_vector3(self.direction),
+        }
+    }
+
+    /// Slab test; distance to the entry point, or zero when starting inside.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let inverse = self.direction.recip();
+        let t0 = (aabb.min - self.origin) * inverse;
+        let t1 = (aabb.max - self.origin) * inverse;
+        let near = t0.min(t1).max_element();
+        let far = t0.max(t1).min_element();
+        (far >= near.max(0.0)).then_some(near.max(0.0))
+    }
+
+    pub fn intersect_plane(&self, point: Vec3, normal: Vec3) -> Option<f32> {
+        let denom = normal.dot(self.direction);
+        if denom.abs() <= 1e-6 {
+            return None;
+        }
+        let t = normal.dot(point - self.origin) / denom;
+        (t >= 0.0).then_some(t)
+    }
+
+    /// Möller–Trumbore, hitting both faces.
+    pub fn intersect_triangle(&self, [a, b, c]: &[Vec3; 3]) -> Option<f32> {
+        let edge1 = *b - *a;
+        let edge2 = *c - *a;
+        let p = self.direction.cross(edge2);
+        let det = edge1.dot(p);
+        if det.abs() <= 1e-8 {
+            return None;
+        }
+        let inv_det = det.recip();
+        let offset = self.origin - *a;
+        let u = offset.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = offset.cross(edge1);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inv_det;
+        (t >= 0.0).then_some(t)
+    }
+
+    /// Parameters of the closest points on the ray and on the line through
+    /// `point` along the unit `direction`. `None` when they are parallel.
+    pub fn closest_to_line(&self, point: Vec3, direction: Vec3) -> Option<(f32, f32)> {
+        let offset = self.origin - point;
+        let b = self.direction.dot(direction);
+        let d = self.direction.dot(offset);
+        let e = direction.dot(offset);
+        let denom = 1.0 - b * b;
+        if denom <= 1e-6 {
+            return None;
+        }
+        let ray_t = ((b * e - d) / denom).max(0.0);
+        let line_t = (e - b * d) / denom;
+        Some((ray_t, line_t))
+    }
+}
+
+// =============================== COLLIDERS ===============================
+/// CPU copies of the mesh triangles, indexed by [`MeshId`].
+#[derive(Resource)]
+pub struct MeshColliders {
+    triangles: Vec<Vec<[Vec3; 3]>>,
+}
+impl Default for MeshColliders {
+    fn default() -> Self {
+        // Same order as `Meshes::new`
+        Self {
+            triangles: vec![
+                Self::from_vertices(&cube_vertices()),
+                Self::from_vertices(&quad_vertices()),
+            ],
+        }
+    }
+}
+impl MeshColliders {
+    pub fn from_vertices(vertices: &[MeshVertex]) -> Vec<[Vec3; 3]> {
+        vertices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]].map(|v| Vec3::from(v.position)))
+            .collect()
+    }
+
+    /// Adds the triangles of a mesh registered after the built-in ones.
+    pub fn insert(&mut self, mesh: MeshId, triangles: Vec<[Vec3; 3]>) {
+        let index = mesh.0 as usize;
+        if self.triangles.len() <= index {
+            self.triangles.resize_with(index + 1, Vec::new);
+        }
+        self.triangles[index] = triangles;
+    }
+
+    pub fn get(&self, mesh: MeshId) -> Option<&[[Vec3; 3]]> {
+        self.triangles.get(mesh.0 as usize).map(Vec::as_slice)
+    }
+}
+
+// =============================== QUERIES ===============================
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    pub entity: Entity,
+    /// Along the ray, in world units when the ray direction is normalized.
+    pub distance: f32,
+    pub point: Vec3,
+    pub normal: Vec3,
+}
+
+/// Closest-hit queries against visible entities. Bounding boxes reject
+/// candidates; renderables are then tested against their mesh triangles,
+/// anything else against its box.
+#[derive(SystemParam)]
+pub struct Raycast<'w, 's> {
+    colliders: Res<'w, MeshColliders>,
+    query: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static GlobalTransform,
+            &'static Aabb,
+            &'static Visibility,
+            Option<&'static Renderable>,
+        ),
+    >,
+}
+impl Raycast<'_, '_> {
+    pub fn cast(&self, ray: &Ray) -> Option<RayHit> {
+        self.cast_filtered(ray, |_| true)
+    }
+
+    pub fn cast_filtered(&self, ray: &Ray, filter: impl Fn(Entity) -> bool) -> Option<RayHit> {
+        let mut candidates: Vec<_> = self
+            .query
+            .iter()
+            .filter(|(entity, _, _, visibility, _)| visibility.visible && filter(*entity))
+            .filter_map(|(entity, global, aabb, _, renderable)| {
+                let t = ray.intersect_aabb(&aabb.transformed(&global.0))?;
+                Some((t, entity, global, aabb, renderable))
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut closest: Option<RayHit> = None;
+        for (entry, entity, global, aabb, renderable) in candidates {
+            if closest.is_some_and(|hit| hit.distance < entry) {
+                break;
+            }
+            let hit = match renderable.and_then(|r| self.colliders.get(r.mesh)) {
+                Some(triangles) => Self::hit_triangles(ray, entity, &global.0, triangles),
+                None => Self::hit_box(ray, entity, &global.0, aabb),
+            };
+            if let Some(hit) = hit {
+                if closest.is_none_or(|closest| hit.distance < closest.distance) {
+                    closest = Some(hit);
+                }
+            }
+        }
+        closest
+    }
+
+    fn hit_triangles(
+        ray: &Ray,
+        entity: Entity,
+        matrix: &Mat4,
+        triangles: &[[Vec3; 3]],
+    ) -> Option<RayHit> {
+        let local = ray.transformed(&matrix.inverse());
+        let (distance, [a, b, c]) = triangles
+            .iter()
+            .filter_map(|triangle| Some((local.intersect_triangle(triangle)?, triangle)))
+            .min_by(|a, b| a.0.total_cmp(&b.0))?;
+        let normal = (*b - *a).cross(*c - *a);
+        Some(RayHit {
+            entity,
+            distance,
+            point: ray.at(distance),
+            normal: Self::world_normal(matrix, normal, ray),
+        })
+    }
+
+    fn hit_box(ray: &Ray, entity: Entity, matrix: &Mat4, aabb: &Aabb) -> Option<RayHit> {
+        let local = ray.transformed(&matrix.inverse());
+        let distance = local.intersect_aabb(aabb)?;
+        // The face whose slab was entered last
+        let offset =
+            (local.at(distance) - aabb.center()) / aabb.half_extents().max(Vec3::splat(1e-6));
+        let abs = offset.abs();
+        let normal = if abs.x >= abs.y && abs.x >= abs.z {
+            Vec3::X * offset.x.signum()
+        } else if abs.y >= abs.z {
+            Vec3::Y * offset.y.signum()
+        } else {
+            Vec3::Z * offset.z.signum()
+        };
+        Some(RayHit {
+            entity,
+            distance,
+            point: ray.at(distance),
+            normal: Self::world_normal(matrix, normal, ray),
+        })
+    }
+
+    /// Local normals map through the inverse transpose; flipped to face the ray.
+    fn world_normal(matrix: &Mat4, normal: Vec3, ray: &Ray) -> Vec3 {
+        let normal = matrix
+            .inverse()
+            .transpose()
+            .transform_vector3(normal)
+            .normalize_or_zero();
+        if normal.dot(ray.direction) > 0.0 {
+            -normal
+        } else {
+            normal
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+use tracing::info;
+
+/// Environment variable read when `--seed` isn't passed.
+pub const SEED_ENV: &str = "PLAYGROUND_SEED";
+
+pub fn setup_rng(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    let seed = choose_seed(std::env::args().skip(1), std::env::var(SEED_ENV).ok())?;
+    info!("Random seed: {}", seed);
+    world.insert_resource(Rng::new(seed));
+    Ok(())
+}
+
+fn parse_seed(value: &str) -> Result<u64> {
+    value
+        .parse()
+        .with_context(|| format!("Invalid seed '{}'", value))
+}
+
+/// `--seed` wins over [`SEED_ENV`], which is only parsed when no seed was
+/// passed, so a broken variable doesn't get in the way of an explicit seed.
+fn choose_seed(args: impl IntoIterator<Item = String>, env: Option<String>) -> Result<u64> {
+    let seed = match seed_from_args(args)? {
+        Some(seed) => Some(seed),
+        None => env.as_deref().map(parse_seed).transpose()?,
+    };
+    Ok(seed.unwrap_or(Rng::DEFAULT_SEED))
+}
+
+/// Accepts both `--seed 42` and `--seed=42`.
+fn seed_from_args(args: impl IntoIterator<Item = String>) -> Result<Option<u64>> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--seed" {
+            let value = args.next().context("--seed needs a value")?;
+            return parse_seed(&value).map(Some);
+        }
+        if let Some(value) = arg.strip_prefix("--seed=") {
+            return parse_seed(value).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// SplitMix64 generator shared by everything that needs randomness, so a run
+/// can be replayed from its seed. Bevy picks any order for conflicting
+/// systems, so systems drawing every frame have to be ordered against each
+/// other for the numbers to land in the same place every run.
+#[derive(Resource, Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+impl Rng {
+    pub const DEFAULT_SEED: u64 = 0x5eed;
+
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_take_precedence_over_the_env() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let env = |value: &str| Some(value.to_string());
+
+        assert_eq!(choose_seed(args(&["--seed", "7"]), env("9")).unwrap(), 7);
+        assert_eq!(
+            choose_seed(args(&["--seed=7"]), env("not a seed")).unwrap(),
+            7
+        );
+        assert_eq!(choose_seed(args(&[]), env("9")).unwrap(), 9);
+        assert_eq!(choose_seed(args(&[]), None).unwrap(), Rng::DEFAULT_SEED);
+        assert!(choose_seed(args(&[]), env("not a seed")).is_err());
+        assert!(choose_seed(args(&["--seed"]), env("9")).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+
+pub fn setup_samplers(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    world.insert_resource(SamplerCache::default());
+    world.insert_resource(SamplerSettings::default());
+    Ok(())
+}
+
+// =============================== SETTINGS ===============================
+/// Maximum number of anisotropic samples. Only takes effect with linear
+/// filtering on all three filters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Anisotropy {
+    X1 = 1,
+    X4 = 4,
+    X8 = 8,
+    X16 = 16,
+}
+impl Anisotropy {
+    pub const ALL: [Anisotropy; 4] = [Self::X1, Self::X4, Self::X8, Self::X16];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::X1 => "1x (off)",
+            Self::X4 => "4x",
+            Self::X8 => "8x",
+            Self::X16 => "16x",
+        }
+    }
+}
+
+/// Global texture filtering quality, applied to every material sampler.
+#[derive(Resource, Clone, PartialEq)]
+pub struct SamplerSettings {
+    pub anisotropy: Anisotropy,
+    /// Added to the mip level picked by the hardware. WebGPU samplers have no
+    /// bias, so shaders that honor it pass it to `textureSampleBias`.
+    pub lod_bias: f32,
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+}
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            anisotropy: Anisotropy::X16,
+            lod_bias: 0.0,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+        }
+    }
+}
+
+// =============================== CACHE ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerKey {
+    pub address_mode: wgpu::AddressMode,
+    pub filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    pub anisotropy: Anisotropy,
+    /// `f32::to_bits` of the LOD clamps, so the key stays hashable.
+    pub lod_clamp: [u32; 2],
+}
+impl SamplerKey {
+    /// Trilinear filtering with the globally selected anisotropy and LOD range.
+    pub fn material(settings: &SamplerSettings, address_mode: wgpu::AddressMode) -> Self {
+        // Quarter levels, so dragging a slider doesn't create a sampler per frame
+        let quantize = |lod: f32| (lod.max(0.0) * 4.0).round() / 4.0;
+        let min = quantize(settings.lod_min_clamp);
+        let max = quantize(settings.lod_max_clamp).max(min);
+        Self {
+            address_mode,
+            filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy: settings.anisotropy,
+            lod_clamp: [min.to_bits(), max.to_bits()],
+        }
+    }
+
+    fn descriptor(&self) -> wgpu::SamplerDescriptor<'static> {
+        // wgpu rejects anisotropy unless every filter is linear
+        let linear = self.filter == wgpu::FilterMode::Linear
+            && self.mipmap_filter == wgpu::FilterMode::Linear;
+        wgpu::SamplerDescriptor {
+            label: Some("cached_sampler"),
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            mag_filter: self.filter,
+            min_filter: self.filter,
+            mipmap_filter: self.mipmap_filter,
+            lod_min_clamp: f32::from_bits(self.lod_clamp[0]),
+            lod_max_clamp: f32::from_bits(self.lod_clamp[1]),
+            anisotropy_clamp: if linear { self.anisotropy as u16 } else { 1 },
+            ..Default::default()
+        }
+    }
+}
+
+/// Shares samplers between bind groups, so changing the filtering quality
+/// only creates each distinct sampler once.
+#[derive(Resource, Default)]
+pub struct SamplerCache {
+    samplers: HashMap<SamplerKey, wgpu::Sampler>,
+}
+impl SamplerCache {
+    pub fn get(&mut self, device: &wgpu::Device, key: SamplerKey) -> &wgpu::Sampler {
+        self.samplers
+            .entry(key)
+            .or_insert_with(|| device.create_sampler(&key.descriptor()))
+    }
+
+    pub fn cached(&self) -> usize {
+        self.samplers.len()
+    }
+}
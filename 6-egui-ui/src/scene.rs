@@ -0,0 +1,663 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Commands, Query, Res, ResMut, Resource},
+    world::World,
+};
+use glam::{Mat4, Quat, Vec3, Vec4, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
+use tracing::info_span;
+
+use crate::{
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    jobs::{par_for_each, par_sort_by_key, JobSystem},
+    pipeline::{
+        arena::FrameArena,
+        depth::DepthPreview,
+        mesh::ObjectUniform,
+        render::{render_system, submit_system},
+        ui::UiPanels,
+    },
+    scene_file::initial_scene,
+    time::TimeContext,
+};
+
+pub fn setup_scene(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let camera = Camera {
+        aspect: gpu.config.width as f32 / gpu.config.height.max(1) as f32,
+        ..Default::default()
+    };
+    world.insert_resource(camera);
+    world.insert_resource(SceneStats::default());
+    world.insert_resource(DrawList {
+        merge_instances: true,
+        ..Default::default()
+    });
+    world.insert_resource(MaterialTable::default());
+
+    initial_scene().spawn(world);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(scene_panel);
+
+    schedule.add_systems(
+        (
+            camera_aspect_system,
+            spin_system,
+            transform_propagation_system,
+            frustum_culling_system,
+            draw_list_system,
+        )
+            .chain()
+            .before(render_system),
+    );
+    schedule.add_systems(previous_transform_system.after(submit_system));
+
+    Ok(())
+}
+
+fn scene_panel(ctx: &egui::Context, world: &mut World) {
+    let stats = world.resource::<SceneStats>();
+    let (entities, visible) = (stats.entities, stats.visible);
+    let draw_list = world.resource::<DrawList>();
+    let (draw_stats, mut merge_instances) = (draw_list.stats, draw_list.merge_instances);
+    let arena_stats = world.resource::<FrameArena>().stats;
+    let threads = world
+        .get_resource::<JobSystem>()
+        .map_or(1, |jobs| jobs.threads);
+    let mut preview = world.resource_mut::<DepthPreview>();
+
+    egui::Window::new(tr("Scene"))
+        .id(egui::Id::new("Scene"))
+        .show(ctx, |ui| {
+            ui.label(trf!("Job threads: {}", threads));
+            ui.label(trf!("Visible: {} / {}", visible, entities));
+            ui.label(trf!(
+                "Draws: {} ({} opaque, {} transparent)",
+                draw_stats.draws,
+                draw_stats.opaque,
+                draw_stats.transparent
+            ));
+            ui.label(trf!(
+                "Draw calls: {} ({} merged into instanced draws)",
+                draw_stats.draw_calls,
+                draw_stats.merged
+            ));
+            ui.label(trf!(
+                "State changes: {} pipeline, {} material, {} mesh",
+                draw_stats.pipeline_changes,
+                draw_stats.material_changes,
+                draw_stats.mesh_changes
+            ));
+            ui.label(trf!(
+                "Frame arena: {:.1} / {} KiB in {} allocations",
+                arena_stats.bytes as f32 / 1024.0,
+                arena_stats.capacity / 1024,
+                arena_stats.allocations
+            ));
+            ui.checkbox(&mut merge_instances, tr("Merge instanced draws"));
+            ui.checkbox(&mut preview.enabled, tr("Depth preview"));
+        });
+
+    let mut draw_list = world.resource_mut::<DrawList>();
+    if draw_list.merge_instances != merge_instances {
+        draw_list.merge_instances = merge_instances;
+    }
+}
+
+// =============================== COMPONENTS ===============================
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+impl Transform {
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+    pub fn with_rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+/// World-space matrix computed by [`transform_propagation_system`].
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct GlobalTransform(pub Mat4);
+
+/// World matrix of the last rendered frame, for motion vectors. Entities
+/// without one haven't been rendered yet and count as not moving.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct PreviousGlobalTransform(pub Mat4);
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Parent(pub Entity);
+
+/// Display name, shown by the entity inspector.
+#[derive(Component, Clone, Debug)]
+pub struct Name(pub String);
+impl Name {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+}
+
+/// Spawned from the scene file, and despawned when it is reloaded.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct SceneEntity;
+
+/// Local-space bounding box.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+impl Aabb {
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+    pub fn transformed(&self, matrix: &Mat4) -> Aabb {
+        let center = matrix.transform_point3(self.center());
+        let half = self.half_extents();
+        let extents = matrix.x_axis.xyz().abs() * half.x
+            + matrix.y_axis.xyz().abs() * half.y
+            + matrix.z_axis.xyz().abs() * half.z;
+        Aabb {
+            min: center - extents,
+            max: center + extents,
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Visibility {
+    pub visible: bool,
+}
+impl Default for Visibility {
+    fn default() -> Self {
+        Self { visible: true }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PipelineId(pub u32);
+impl PipelineId {
+    pub const OPAQUE: Self = Self(0);
+    pub const TRANSPARENT: Self = Self(1);
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaterialId(pub u32);
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MeshId(pub u32);
+impl MeshId {
+    pub const CUBE: Self = Self(0);
+    pub const QUAD: Self = Self(1);
+}
+
+/// Marks an entity as drawable by the mesh pass. The pipeline is picked from
+/// the blend mode of the material.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Renderable {
+    pub material: MaterialId,
+    pub mesh: MeshId,
+}
+
+// =============================== MATERIALS ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlendMode {
+    Opaque,
+    Transparent,
+}
+impl BlendMode {
+    pub fn pipeline(&self) -> PipelineId {
+        match self {
+            BlendMode::Opaque => PipelineId::OPAQUE,
+            BlendMode::Transparent => PipelineId::TRANSPARENT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MaterialDesc {
+    pub base_color: Vec4,
+    pub blend: BlendMode,
+    /// 0 is a mirror, rough surfaces only reflect the environment.
+    pub roughness: f32,
+    /// Reflectance at normal incidence, 0 for no reflections at all.
+    pub reflectivity: f32,
+}
+impl MaterialDesc {
+    pub fn opaque(base_color: Vec4) -> Self {
+        Self {
+            base_color,
+            blend: BlendMode::Opaque,
+            roughness: 0.5,
+            reflectivity: 0.0,
+        }
+    }
+    pub fn transparent(base_color: Vec4) -> Self {
+        Self {
+            base_color,
+            blend: BlendMode::Transparent,
+            roughness: 0.5,
+            reflectivity: 0.0,
+        }
+    }
+    pub fn with_reflections(mut self, roughness: f32, reflectivity: f32) -> Self {
+        self.roughness = roughness;
+        self.reflectivity = reflectivity;
+        self
+    }
+}
+
+/// CPU-side material descriptions, uploaded by the mesh pipeline at startup.
+#[derive(Resource, Default)]
+pub struct MaterialTable {
+    pub materials: Vec<MaterialDesc>,
+    /// How many came from the scene file, the rest were appended by loaded
+    /// models.
+    pub scene_materials: usize,
+}
+impl MaterialTable {
+    pub fn blend(&self, id: MaterialId) -> BlendMode {
+        self.materials
+            .get(id.0 as usize)
+            .map_or(BlendMode::Opaque, |material| material.blend)
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Spin {
+    pub axis: Vec3,
+    pub speed: f32,
+}
+
+// =============================== CAMERA ===============================
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct Camera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    pub fov_y: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+}
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            eye: Vec3::new(0.0, 12.0, 28.0),
+            target: Vec3::ZERO,
+            up: Vec3::Y,
+            fov_y: 45f32.to_radians(),
+            aspect: 16.0 / 9.0,
+            near: 0.1,
+            far: 200.0,
+        }
+    }
+}
+impl Camera {
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.eye, self.target, self.up)
+    }
+    pub fn projection(&self) -> Mat4 {
+        Mat4::perspective_rh(self.fov_y, self.aspect, self.near, self.far)
+    }
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection() * self.view()
+    }
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_projection(&self.view_projection())
+    }
+}
+
+/// Six inward-facing planes extracted from a view-projection matrix.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    pub planes: [Vec4; 6],
+}
+impl Frustum {
+    pub fn from_view_projection(m: &Mat4) -> Self {
+        let rows = [m.row(0), m.row(1), m.row(2), m.row(3)];
+        let planes = [
+            rows[3] + rows[0],
+            rows[3] - rows[0],
+            rows[3] + rows[1],
+            rows[3] - rows[1],
+            // wgpu clip space depth is [0, 1]
+            rows[2],
+            rows[3] - rows[2],
+        ]
+        .map(|plane| plane / plane.xyz().length());
+        Self { planes }
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        let center = aabb.center();
+        let half = aabb.half_extents();
+        self.planes.iter().all(|plane| {
+            let radius = half.dot(plane.xyz().abs());
+            plane.xyz().dot(center) + plane.w >= -radius
+        })
+    }
+}
+
+// =============================== SYSTEMS ===============================
+pub fn camera_aspect_system(gpu: Res<GpuContext>, mut camera: ResMut<Camera>) {
+    let aspect = gpu.config.width as f32 / gpu.config.height.max(1) as f32;
+    if camera.aspect != aspect {
+        camera.aspect = aspect;
+    }
+}
+
+pub fn spin_system(time: Res<TimeContext>, mut query: Query<(&mut Transform, &Spin)>) {
+    for (mut transform, spin) in query.iter_mut() {
+        transform.rotation =
+            Quat::from_axis_angle(spin.axis, spin.speed * time.delta) * transform.rotation;
+    }
+}
+
+/// Computes world matrices on the job pool. Every entity walks its own parent
+/// chain, which keeps the work embarrassingly parallel for shallow hierarchies.
+pub fn transform_propagation_system(
+    mut query: Query<(Entity, &Transform, Option<&Parent>, &mut GlobalTransform)>,
+) {
+    let locals: HashMap<Entity, (Mat4, Option<Entity>)> = query
+        .iter()
+        .map(|(entity, transform, parent, _)| (entity, (transform.matrix(), parent.map(|p| p.0))))
+        .collect();
+
+    let mut globals: Vec<(Entity, Mat4)> = locals.keys().map(|e| (*e, Mat4::IDENTITY)).collect();
+    par_for_each("transform_propagation", &mut globals, |(entity, global)| {
+        let mut current = Some(*entity);
+        let mut matrix = Mat4::IDENTITY;
+        while let Some((local, parent)) = current.and_then(|e| locals.get(&e)) {
+            matrix = *local * matrix;
+            current = *parent;
+        }
+        *global = matrix;
+    });
+
+    for (entity, matrix) in globals {
+        if let Ok((_, _, _, mut global)) = query.get_mut(entity) {
+            global.0 = matrix;
+        }
+    }
+}
+
+/// Remembers this frame's world matrices once it has been rendered.
+pub fn previous_transform_system(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &GlobalTransform,
+        Option<&mut PreviousGlobalTransform>,
+    )>,
+) {
+    for (entity, global, previous) in query.iter_mut() {
+        match previous {
+            Some(mut previous) => previous.0 = global.0,
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(PreviousGlobalTransform(global.0));
+            }
+        }
+    }
+}
+
+pub fn frustum_culling_system(
+    camera: Res<Camera>,
+    mut stats: ResMut<SceneStats>,
+    mut query: Query<(&GlobalTransform, &Aabb, &mut Visibility)>,
+) {
+    let frustum = camera.frustum();
+    let mut items: Vec<_> = query.iter_mut().collect();
+    par_for_each(
+        "frustum_culling",
+        &mut items,
+        |(global, aabb, visibility)| {
+            let visible = frustum.intersects_aabb(&aabb.transformed(&global.0));
+            if visibility.visible != visible {
+                visibility.visible = visible;
+            }
+        },
+    );
+
+    stats.entities = items.len();
+    stats.visible = items.iter().filter(|(_, _, v)| v.visible).count();
+}
+
+pub fn draw_list_system(
+    camera: Res<Camera>,
+    materials: Res<MaterialTable>,
+    mut draw_list: ResMut<DrawList>,
+    query: Query<(
+        &GlobalTransform,
+        Option<&PreviousGlobalTransform>,
+        &Renderable,
+        &Visibility,
+    )>,
+) {
+    let view = camera.view();
+    let draw_list = &mut *draw_list;
+    draw_list.opaque.clear();
+    draw_list.transparent.clear();
+    for (global, previous, renderable, visibility) in query.iter() {
+        if !visibility.visible {
+            continue;
+        }
+        let blend = materials.blend(renderable.material);
+        let view_depth = -(view * global.0.w_axis).z;
+        let item = DrawItem {
+            key: DrawKey::new(blend.pipeline(), renderable, view_depth),
+            model: global.0,
+            previous: previous.map_or(global.0, |previous| previous.0),
+        };
+        match blend {
+            BlendMode::Opaque => draw_list.opaque.push(item),
+            BlendMode::Transparent => draw_list.transparent.push(item),
+        }
+    }
+
+    // Opaque draws are batched by state and drawn front-to-back within a batch
+    // to make the most of early depth testing; transparent draws have to be
+    // blended back-to-front, whatever that costs in state changes.
+    par_sort_by_key("sort_opaque", &mut draw_list.opaque, |item| item.key);
+    par_sort_by_key("sort_transparent", &mut draw_list.transparent, |item| {
+        std::cmp::Reverse(item.key.depth)
+    });
+    draw_list.build_commands();
+}
+
+// =============================== DRAW LIST ===============================
+/// Sort key of a draw. Fields are compared in declaration order, so draws are
+/// grouped by the most expensive state first and ordered front-to-back last.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DrawKey {
+    pub pipeline: PipelineId,
+    pub material: MaterialId,
+    pub mesh: MeshId,
+    pub depth: u32,
+}
+impl DrawKey {
+    pub fn new(pipeline: PipelineId, renderable: &Renderable, view_depth: f32) -> Self {
+        Self {
+            pipeline,
+            material: renderable.material,
+            mesh: renderable.mesh,
+            // The bit pattern of a non-negative float sorts like the float itself
+            depth: view_depth.max(0.0).to_bits(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DrawItem {
+    pub key: DrawKey,
+    pub model: Mat4,
+    /// Model matrix of the previous frame.
+    pub previous: Mat4,
+}
+
+/// What the mesh pass has to record, with redundant state changes removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawCommand {
+    SetPipeline(PipelineId),
+    SetMaterial(MaterialId),
+    SetMesh(MeshId),
+    /// Draws `instance_count` instances starting at `first_instance` in
+    /// [`DrawList::instances`].
+    Draw {
+        first_instance: u32,
+        instance_count: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DrawStats {
+    pub draws: usize,
+    pub opaque: usize,
+    pub transparent: usize,
+    pub pipeline_changes: usize,
+    pub material_changes: usize,
+    pub mesh_changes: usize,
+    pub draw_calls: usize,
+    /// Draws folded into the instanced draw before them.
+    pub merged: usize,
+}
+
+/// Visible renderables for the current frame, split into render queues.
+#[derive(Resource, Default)]
+pub struct DrawList {
+    pub opaque: Vec<DrawItem>,
+    pub transparent: Vec<DrawItem>,
+    /// Both queues in submission order.
+    pub items: Vec<DrawItem>,
+    /// Per-instance data of `items`, in the same order.
+    pub instances: Vec<ObjectUniform>,
+    pub commands: Vec<DrawCommand>,
+    pub stats: DrawStats,
+    /// Whether consecutive draws of the same state become one instanced draw.
+    pub merge_instances: bool,
+}
+impl DrawList {
+    /// Concatenates the sorted queues and turns them into commands, emitting a
+    /// state change only when the corresponding part of the key differs from
+    /// the previous draw.
+    pub fn build_commands(&mut self) {
+        let _span = info_span!(
+            "build_draw_commands",
+            len = self.opaque.len() + self.transparent.len()
+        )
+        .entered();
+        self.items.clear();
+        self.items.extend_from_slice(&self.opaque);
+        self.items.extend_from_slice(&self.transparent);
+        self.instances.clear();
+        self.commands.clear();
+        let mut stats = DrawStats {
+            opaque: self.opaque.len(),
+            transparent: self.transparent.len(),
+            ..Default::default()
+        };
+        let mut previous: Option<DrawKey> = None;
+        for (instance, item) in self.items.iter().enumerate() {
+            let key = item.key;
+            if previous.map(|p| p.pipeline) != Some(key.pipeline) {
+                self.commands.push(DrawCommand::SetPipeline(key.pipeline));
+                stats.pipeline_changes += 1;
+            }
+            if previous.map(|p| p.material) != Some(key.material) {
+                self.commands.push(DrawCommand::SetMaterial(key.material));
+                stats.material_changes += 1;
+            }
+            if previous.map(|p| p.mesh) != Some(key.mesh) {
+                self.commands.push(DrawCommand::SetMesh(key.mesh));
+                stats.mesh_changes += 1;
+            }
+            self.instances
+                .push(ObjectUniform::with_previous(&item.model, &item.previous));
+            self.commands.push(DrawCommand::Draw {
+                first_instance: instance as u32,
+                instance_count: 1,
+            });
+            stats.draws += 1;
+            previous = Some(key);
+        }
+        if self.merge_instances {
+            stats.merged = self.merge_instanced_draws();
+        }
+        stats.draw_calls = stats.draws - stats.merged;
+        self.stats = stats;
+    }
+
+    /// Merges runs of draws with no state change in between, which share
+    /// pipeline, material and mesh and have adjacent instances, into single
+    /// instanced draws. Returns how many draws were folded away.
+    fn merge_instanced_draws(&mut self) -> usize {
+        let _span = info_span!("merge_instanced_draws").entered();
+        let mut merged = 0;
+        let mut commands = Vec::with_capacity(self.commands.len());
+        for command in self.commands.drain(..) {
+            if let (
+                Some(DrawCommand::Draw {
+                    first_instance,
+                    instance_count,
+                }),
+                DrawCommand::Draw {
+                    first_instance: next,
+                    instance_count: count,
+                },
+            ) = (commands.last_mut(), command)
+            {
+                if *first_instance + *instance_count == next {
+                    *instance_count += count;
+                    merged += 1;
+                    continue;
+                }
+            }
+            commands.push(command);
+        }
+        self.commands = commands;
+        merged
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SceneStats {
+    pub entities: usize,
+    pub visible: usize,
+}
@@ -0,0 +1,820 @@
+//! The scene as a file, and reloading it without restarting. A reload
+//! despawns every [`SceneEntity`] and spawns the file again into the same
+//! world, so the GPU context, pipelines and settings all survive it.
+//!
+//! Reloads read and parse the file on the job pool while the old scene keeps
+//! running under a progress bar, see [`SceneLoading`]. The startup scene
+//! loads before the first frame, with nothing to show meanwhile.
+
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::{Instant, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use bevy_ecs::{
+    entity::Entity,
+    query::{With, Without},
+    schedule::{IntoSystemConfigs, Schedule},
+    system::Resource,
+    world::{Mut, World},
+};
+use glam::{Quat, Vec3, Vec4};
+use playground_app::Input;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, info_span};
+use winit::keyboard::KeyCode;
+
+use crate::{
+    assets::AssetServer,
+    editor::forget_entities,
+    gpu::GpuContext,
+    i18n::{tr, trf},
+    pipeline::{
+        mesh::{
+            material_upload_system,
+            model::{ModelPartEntity, ModelSource},
+            Meshes,
+        },
+        ui::UiPanels,
+    },
+    scene::{
+        camera_aspect_system, Aabb, BlendMode, GlobalTransform, MaterialDesc, MaterialId,
+        MaterialTable, MeshId, Name, Parent, Renderable, SceneEntity, Spin, Transform, Visibility,
+    },
+    time::TimeContext,
+};
+
+/// Loaded at startup and watched for changes, in the working directory. The
+/// demo scene stands in while it doesn't exist.
+pub const SCENE_FILE: &str = "scene.json";
+
+/// Has to run after the scene, mesh and editor setups.
+pub fn setup_scene_file(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let path = PathBuf::from(SCENE_FILE);
+    world.insert_resource(SceneFile {
+        modified: modified(&path),
+        path,
+        watch: true,
+        reload: false,
+        error: None,
+        last_save: None,
+        elapsed: 0.0,
+    });
+    world.init_resource::<SceneLoading>();
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(scene_file_panel);
+
+    schedule.add_systems(
+        scene_reload_system
+            .before(camera_aspect_system)
+            .before(material_upload_system),
+    );
+
+    Ok(())
+}
+
+/// The startup scene: the scene file if it loads, the demo scene otherwise.
+pub fn initial_scene() -> SceneDesc {
+    match SceneDesc::load(Path::new(SCENE_FILE)) {
+        Ok(Some(scene)) => scene,
+        Ok(None) => SceneDesc::demo(),
+        Err(e) => {
+            error!("Using the demo scene instead of {}: {:?}", SCENE_FILE, e);
+            SceneDesc::demo()
+        }
+    }
+}
+
+// =============================== DESCRIPTION ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MeshKind {
+    Cube,
+    Quad,
+}
+impl MeshKind {
+    pub fn from_id(id: MeshId) -> Option<Self> {
+        match id {
+            MeshId::CUBE => Some(Self::Cube),
+            MeshId::QUAD => Some(Self::Quad),
+            _ => None,
+        }
+    }
+
+    pub fn id(self) -> MeshId {
+        match self {
+            Self::Cube => MeshId::CUBE,
+            Self::Quad => MeshId::QUAD,
+        }
+    }
+
+    pub fn bounds(self) -> Aabb {
+        match self {
+            Self::Cube => Aabb {
+                min: Vec3::splat(-0.5),
+                max: Vec3::splat(0.5),
+            },
+            Self::Quad => Aabb {
+                min: Vec3::new(-0.5, -0.5, 0.0),
+                max: Vec3::new(0.5, 0.5, 0.0),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct MaterialEntry {
+    pub base_color: [f32; 4],
+    pub blend: BlendMode,
+    pub roughness: f32,
+    pub reflectivity: f32,
+}
+impl From<&MaterialDesc> for MaterialEntry {
+    fn from(desc: &MaterialDesc) -> Self {
+        Self {
+            base_color: desc.base_color.to_array(),
+            blend: desc.blend,
+            roughness: desc.roughness,
+            reflectivity: desc.reflectivity,
+        }
+    }
+}
+impl From<&MaterialEntry> for MaterialDesc {
+    fn from(entry: &MaterialEntry) -> Self {
+        Self {
+            base_color: Vec4::from_array(entry.base_color),
+            blend: entry.blend,
+            roughness: entry.roughness,
+            reflectivity: entry.reflectivity,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SpinEntry {
+    pub axis: [f32; 3],
+    pub speed: f32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RenderableEntry {
+    pub mesh: MeshKind,
+    /// Index into [`SceneDesc::materials`].
+    pub material: usize,
+}
+
+/// Missing fields take their defaults, so hand-written entries stay short.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntityEntry {
+    pub name: String,
+    /// Index of an earlier entry in [`SceneDesc::entities`].
+    pub parent: Option<usize>,
+    pub translation: [f32; 3],
+    /// Quaternion, `[x, y, z, w]`.
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    pub spin: Option<SpinEntry>,
+    pub renderable: Option<RenderableEntry>,
+    /// glTF or OBJ model spawned under the entity, relative to `assets/`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<PathBuf>,
+}
+impl Default for EntityEntry {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            parent: None,
+            translation: [0.0; 3],
+            rotation: Quat::IDENTITY.to_array(),
+            scale: [1.0; 3],
+            spin: None,
+            renderable: None,
+            model: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SceneDesc {
+    pub materials: Vec<MaterialEntry>,
+    pub entities: Vec<EntityEntry>,
+}
+impl SceneDesc {
+    /// `None` when there is no scene file.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&contents, path).map(Some)
+    }
+
+    /// Parses and validates the contents of the scene file at `path`.
+    pub fn parse(contents: &[u8], path: &Path) -> Result<Self> {
+        let scene: Self = serde_json::from_slice(contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        scene
+            .validate()
+            .with_context(|| format!("Invalid scene in {}", path.display()))?;
+        Ok(scene)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        info!("Saved scene to {}", path.display());
+        Ok(())
+    }
+
+    /// Checked before anything is despawned, so a broken file leaves the
+    /// current scene alone.
+    pub fn validate(&self) -> Result<()> {
+        for (index, entity) in self.entities.iter().enumerate() {
+            if let Some(parent) = entity.parent.filter(|&parent| parent >= index) {
+                anyhow::bail!(
+                    "Entity {} ({:?}) has parent {}, parents have to come first",
+                    index,
+                    entity.name,
+                    parent
+                );
+            }
+            if let Some(renderable) = entity
+                .renderable
+                .filter(|renderable| renderable.material >= self.materials.len())
+            {
+                anyhow::bail!(
+                    "Entity {} ({:?}) uses material {} of {}",
+                    index,
+                    entity.name,
+                    renderable.material,
+                    self.materials.len()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The current scene entities, including edits made at runtime.
+    /// Parts of loaded models are left out, the model is saved instead.
+    pub fn capture(world: &mut World) -> Self {
+        let mut query = world.query_filtered::<(
+            Entity,
+            Option<&Name>,
+            &Transform,
+            Option<&Parent>,
+            Option<&Spin>,
+            Option<&Renderable>,
+        ), (With<SceneEntity>, Without<ModelPartEntity>)>();
+        let mut pending: Vec<_> = query.iter(world).collect();
+        pending.sort_by_key(|(entity, ..)| *entity);
+
+        // Parents have to be written before their children
+        let mut indices = HashMap::new();
+        let mut entities = Vec::with_capacity(pending.len());
+        while !pending.is_empty() {
+            let before = pending.len();
+            pending.retain(|&(entity, name, transform, parent, spin, renderable)| {
+                let parent = match parent {
+                    Some(Parent(parent)) if pending_parent(&indices, *parent, world) => {
+                        return true
+                    }
+                    Some(Parent(parent)) => indices.get(parent).copied(),
+                    None => None,
+                };
+                indices.insert(entity, entities.len());
+                entities.push(EntityEntry {
+                    name: name.map(|name| name.0.clone()).unwrap_or_default(),
+                    parent,
+                    translation: transform.translation.to_array(),
+                    rotation: transform.rotation.to_array(),
+                    scale: transform.scale.to_array(),
+                    spin: spin.map(|spin| SpinEntry {
+                        axis: spin.axis.to_array(),
+                        speed: spin.speed,
+                    }),
+                    renderable: renderable.and_then(|renderable| {
+                        Some(RenderableEntry {
+                            mesh: MeshKind::from_id(renderable.mesh)?,
+                            material: renderable.material.0 as usize,
+                        })
+                    }),
+                    model: world
+                        .get::<ModelSource>(entity)
+                        .map(|model| model.path.clone()),
+                });
+                false
+            });
+            // Only a parent cycle can stall, which the hierarchy doesn't allow
+            if pending.len() == before {
+                break;
+            }
+        }
+
+        // The materials models appended come back when they are loaded
+        // again, unless an entity of the scene was switched to one of them
+        let table = world.resource::<MaterialTable>();
+        let used = entities
+            .iter()
+            .filter_map(|entry| Some(entry.renderable?.material + 1))
+            .max()
+            .unwrap_or(0);
+        let materials = table.materials[..table.scene_materials.max(used)]
+            .iter()
+            .map(MaterialEntry::from)
+            .collect();
+
+        Self {
+            materials,
+            entities,
+        }
+    }
+
+    /// The scene the playground ships with, a grid of spinning cubes with a
+    /// glTF and an OBJ model behind it.
+    pub fn demo() -> Self {
+        let mut scene = Self::default();
+        let opaque = [
+            Vec4::new(0.9, 0.3, 0.2, 1.0),
+            Vec4::new(0.2, 0.7, 0.3, 1.0),
+            Vec4::new(0.2, 0.4, 0.9, 1.0),
+            Vec4::new(0.9, 0.8, 0.3, 1.0),
+        ]
+        .map(|base_color| scene.add_material(MaterialDesc::opaque(base_color)));
+        let ground = scene.add_material(
+            MaterialDesc::opaque(Vec4::new(0.6, 0.6, 0.6, 1.0)).with_reflections(0.1, 0.3),
+        );
+        let glass = [
+            Vec4::new(0.4, 0.8, 1.0, 0.35),
+            Vec4::new(1.0, 0.4, 0.8, 0.35),
+        ]
+        .map(|base_color| scene.add_material(MaterialDesc::transparent(base_color)));
+
+        scene.entities.push(EntityEntry {
+            name: "Gem".to_string(),
+            translation: [0.0, -0.5, -22.0],
+            scale: [2.0; 3],
+            model: Some(PathBuf::from("models/gem.gltf")),
+            ..Default::default()
+        });
+        scene.entities.push(EntityEntry {
+            name: "Table".to_string(),
+            translation: [6.0, -0.5, -20.0],
+            rotation: Quat::from_rotation_y(-0.5).to_array(),
+            scale: [1.5; 3],
+            model: Some(PathBuf::from("models/table.obj")),
+            ..Default::default()
+        });
+        scene.entities.push(EntityEntry {
+            name: "Ground".to_string(),
+            translation: [0.0, -0.6, 0.0],
+            scale: [48.0, 0.2, 48.0],
+            renderable: Some(RenderableEntry {
+                mesh: MeshKind::Cube,
+                material: ground,
+            }),
+            ..Default::default()
+        });
+
+        let extent: i32 = 6;
+        for x in -extent..=extent {
+            for z in -extent..=extent {
+                let pivot = scene.entities.len();
+                scene.entities.push(EntityEntry {
+                    name: format!("Pivot {} {}", x, z),
+                    translation: [x as f32 * 3.0, 0.0, z as f32 * 3.0],
+                    spin: Some(SpinEntry {
+                        axis: Vec3::Y.to_array(),
+                        speed: 0.2 + ((x + z).rem_euclid(5)) as f32 * 0.1,
+                    }),
+                    ..Default::default()
+                });
+
+                let material = (x + extent).rem_euclid(4) as usize;
+                scene.entities.push(EntityEntry {
+                    name: "Cube".to_string(),
+                    parent: Some(pivot),
+                    scale: [0.8; 3],
+                    renderable: Some(RenderableEntry {
+                        mesh: MeshKind::Cube,
+                        material: opaque[material],
+                    }),
+                    ..Default::default()
+                });
+                scene.entities.push(EntityEntry {
+                    name: "Satellite".to_string(),
+                    parent: Some(pivot),
+                    translation: [1.0, 0.5, 0.0],
+                    scale: [0.3; 3],
+                    spin: Some(SpinEntry {
+                        axis: Vec3::X.to_array(),
+                        speed: 1.0,
+                    }),
+                    renderable: Some(RenderableEntry {
+                        mesh: MeshKind::Cube,
+                        material: opaque[(material + 1) % 4],
+                    }),
+                    ..Default::default()
+                });
+                if (x + z).rem_euclid(3) == 0 {
+                    scene.entities.push(EntityEntry {
+                        name: "Glass".to_string(),
+                        parent: Some(pivot),
+                        translation: [0.0, 1.4, 0.0],
+                        scale: [1.6; 3],
+                        renderable: Some(RenderableEntry {
+                            mesh: MeshKind::Quad,
+                            material: glass[(x.rem_euclid(2)) as usize],
+                        }),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        scene
+    }
+
+    fn add_material(&mut self, desc: MaterialDesc) -> usize {
+        self.materials.push(MaterialEntry::from(&desc));
+        self.materials.len() - 1
+    }
+
+    /// Replaces the material table and spawns every entity, tagged with
+    /// [`SceneEntity`]. Expects a validated scene.
+    pub fn spawn(&self, world: &mut World) {
+        let mut table = world.resource_mut::<MaterialTable>();
+        table.materials = self.materials.iter().map(MaterialDesc::from).collect();
+        table.scene_materials = self.materials.len();
+
+        let mut spawned = Vec::with_capacity(self.entities.len());
+        for entry in &self.entities {
+            let transform = Transform::from_translation(Vec3::from_array(entry.translation))
+                .with_rotation(Quat::from_array(entry.rotation).normalize())
+                .with_scale(Vec3::from_array(entry.scale));
+            let mut entity = world.spawn((
+                SceneEntity,
+                Name::new(entry.name.clone()),
+                transform,
+                GlobalTransform::default(),
+            ));
+            if let Some(parent) = entry.parent {
+                entity.insert(Parent(spawned[parent]));
+            }
+            if let Some(spin) = entry.spin {
+                entity.insert(Spin {
+                    axis: Vec3::from_array(spin.axis).normalize_or(Vec3::Y),
+                    speed: spin.speed,
+                });
+            }
+            if let Some(renderable) = entry.renderable {
+                entity.insert((
+                    renderable.mesh.bounds(),
+                    Visibility::default(),
+                    Renderable {
+                        material: MaterialId(renderable.material as u32),
+                        mesh: renderable.mesh.id(),
+                    },
+                ));
+            }
+            if let Some(path) = &entry.model {
+                entity.insert(ModelSource { path: path.clone() });
+            }
+            spawned.push(entity.id());
+        }
+    }
+}
+
+fn pending_parent(indices: &HashMap<Entity, usize>, parent: Entity, world: &World) -> bool {
+    !indices.contains_key(&parent) && world.get::<SceneEntity>(parent).is_some()
+}
+
+// =============================== RELOAD ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct SceneFile {
+    pub path: PathBuf,
+    /// Reload whenever the file is modified.
+    pub watch: bool,
+    /// Reload at the start of the next frame.
+    pub reload: bool,
+    /// Why the last reload failed, the previous scene stays in that case.
+    pub error: Option<String>,
+    pub last_save: Option<Result<(), String>>,
+    modified: Option<SystemTime>,
+    elapsed: f32,
+}
+impl SceneFile {
+    /// Seconds between modification checks.
+    const POLL_INTERVAL: f32 = 0.5;
+
+    /// Returns true once per modification while watching.
+    fn poll(&mut self, delta: f32) -> bool {
+        self.elapsed += delta;
+        if !self.watch || self.elapsed < Self::POLL_INTERVAL {
+            return false;
+        }
+        self.elapsed = 0.0;
+        let modified = modified(&self.path);
+        let changed = modified.is_some() && modified != self.modified;
+        self.modified = modified;
+        if changed {
+            info!("Scene file changed: {}", self.path.display());
+        }
+        changed
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Starts a load when asked to reload or on F5, and swaps the loaded scene
+/// in once it's done.
+pub fn scene_reload_system(world: &mut World) {
+    let delta = world.resource::<TimeContext>().delta;
+    let pressed = world.resource::<Input>().keys.just_pressed(KeyCode::F5);
+    let mut file = world.resource_mut::<SceneFile>();
+    let changed = file.poll(delta);
+    if changed || file.reload || pressed {
+        file.reload = false;
+        let path = file.path.clone();
+        world.resource_mut::<SceneLoading>().start(path);
+    }
+
+    let Some(loaded) = world.resource_mut::<SceneLoading>().finish() else {
+        return;
+    };
+    let result = loaded.and_then(|scene| {
+        let scene = scene.unwrap_or_else(SceneDesc::demo);
+        make_resident(world, &scene)?;
+        reload_scene(world, &scene);
+        Ok(())
+    });
+    let error = result.err().map(|e| {
+        error!("Failed to reload the scene: {:?}", e);
+        format!("{:#}", e)
+    });
+    world.resource_mut::<SceneFile>().error = error;
+}
+
+/// Uploads the meshes `scene` uses that aren't resident yet, so the first
+/// frame of the new scene draws all of it.
+fn make_resident(world: &mut World, scene: &SceneDesc) -> Result<()> {
+    let mut kinds: Vec<MeshKind> = Vec::new();
+    for renderable in scene.entities.iter().filter_map(|entry| entry.renderable) {
+        if !kinds.contains(&renderable.mesh) {
+            kinds.push(renderable.mesh);
+        }
+    }
+    world.resource_scope(|world, mut assets: Mut<AssetServer>| {
+        let gpu = world.resource::<GpuContext>();
+        let meshes = world.resource::<Meshes>();
+        for kind in kinds {
+            let handle = meshes
+                .handles
+                .get(kind.id().0 as usize)
+                .with_context(|| format!("No mesh for {:?}", kind))?;
+            assets.use_mesh(gpu, *handle)?;
+        }
+        Ok(())
+    })
+}
+
+// =============================== LOADING ===============================
+/// Share of the progress bar reading the file takes, parsing the rest.
+const READ_SHARE: f32 = 0.5;
+const READ_CHUNK: usize = 64 * 1024;
+
+type LoadResult = Result<Option<SceneDesc>>;
+
+/// A scene file being read, parsed and validated on the job pool. The world
+/// only changes when it's done, in a single system run, so no frame shows
+/// half of a scene or one with missing meshes.
+#[derive(Resource, Default)]
+pub struct SceneLoading {
+    pending: Option<PendingScene>,
+}
+
+struct PendingScene {
+    /// Of the whole load, as `f32` bits.
+    progress: Arc<AtomicU32>,
+    result: Arc<Mutex<Option<LoadResult>>>,
+    started: Instant,
+}
+
+impl SceneLoading {
+    /// Loads `path` on the job pool. A load that is still running is
+    /// abandoned, its result is dropped.
+    pub fn start(&mut self, path: PathBuf) {
+        let progress = Arc::new(AtomicU32::new(0.0f32.to_bits()));
+        let result = Arc::new(Mutex::new(None));
+        let (job_progress, job_result) = (progress.clone(), result.clone());
+        rayon::spawn(move || {
+            let _span = info_span!("load_scene", path = %path.display()).entered();
+            let loaded = read_scene(&path, &job_progress);
+            *job_result.lock().expect("scene load poisoned") = Some(loaded);
+        });
+        self.pending = Some(PendingScene {
+            progress,
+            result,
+            started: Instant::now(),
+        });
+    }
+
+    /// Progress of the running load and seconds since it started.
+    pub fn progress(&self) -> Option<(f32, f32)> {
+        self.pending.as_ref().map(|pending| {
+            (
+                f32::from_bits(pending.progress.load(Ordering::Relaxed)),
+                pending.started.elapsed().as_secs_f32(),
+            )
+        })
+    }
+
+    /// The scene once the running load is done, `None` in the result when
+    /// there is no file.
+    pub fn finish(&mut self) -> Option<LoadResult> {
+        let loaded = self
+            .pending
+            .as_ref()?
+            .result
+            .lock()
+            .expect("scene load poisoned")
+            .take()?;
+        let pending = self.pending.take().expect("checked above");
+        info!(
+            "Loaded the scene in {:.1} ms",
+            pending.started.elapsed().as_secs_f64() * 1000.0
+        );
+        Some(loaded)
+    }
+}
+
+fn read_scene(path: &Path, progress: &AtomicU32) -> LoadResult {
+    let set_progress = |value: f32| progress.store(value.to_bits(), Ordering::Relaxed);
+    if !path.exists() {
+        set_progress(1.0);
+        return Ok(None);
+    }
+    let mut file =
+        std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let size = file.metadata().map_or(0, |metadata| metadata.len()) as usize;
+    let mut contents = Vec::with_capacity(size);
+    let mut chunk = vec![0; READ_CHUNK];
+    loop {
+        let read = file
+            .read(&mut chunk)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        contents.extend_from_slice(&chunk[..read]);
+        set_progress(READ_SHARE * (contents.len() as f32 / size.max(1) as f32).min(1.0));
+    }
+    let scene = SceneDesc::parse(&contents, path)?;
+    set_progress(1.0);
+    Ok(Some(scene))
+}
+
+/// Swaps the scene entities for `scene`. Editor state pointing at the old
+/// entities goes with them, the material buffers are rebuilt by
+/// [`material_upload_system`] once the table changed.
+pub fn reload_scene(world: &mut World, scene: &SceneDesc) {
+    let _span = info_span!("reload_scene", entities = scene.entities.len()).entered();
+    let old: Vec<Entity> = world
+        .query_filtered::<Entity, With<SceneEntity>>()
+        .iter(world)
+        .collect();
+    for &entity in &old {
+        world.despawn(entity);
+    }
+
+    forget_entities(world, &old);
+
+    scene.spawn(world);
+    info!(
+        "Reloaded the scene: {} entities replaced by {}, {} materials",
+        old.len(),
+        scene.entities.len(),
+        scene.materials.len()
+    );
+}
+
+// =============================== PANEL ===============================
+fn scene_file_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<SceneFile>().clone();
+    let loading = world.resource::<SceneLoading>().progress();
+    let mut save = false;
+
+    egui::Window::new(tr("Scene file"))
+        .id(egui::Id::new("Scene file"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.label(trf!("File: {}", settings.path.display()));
+            ui.checkbox(&mut settings.watch, tr("Reload when the file changes"));
+            ui.horizontal(|ui| {
+                if ui.button(tr("Reload (F5)")).clicked() {
+                    settings.reload = true;
+                }
+                save = ui.button(tr("Save current scene")).clicked();
+            });
+            ui.label(tr("Without a file, reloading restores the demo scene"));
+            if let Some((progress, _)) = loading {
+                ui.add(egui::ProgressBar::new(progress).text(tr("Loading...")));
+            }
+            if let Some(error) = &settings.error {
+                ui.colored_label(egui::Color32::RED, trf!("Reload failed: {}", error));
+            }
+            match &settings.last_save {
+                Some(Ok(())) => {
+                    ui.label(tr("Saved"));
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(egui::Color32::RED, trf!("Save failed: {}", e));
+                }
+                None => {}
+            }
+        });
+
+    if save {
+        settings.last_save = Some(
+            SceneDesc::capture(world)
+                .save(&settings.path)
+                .map_err(|e| format!("{:?}", e)),
+        );
+        // Our own write isn't a change to reload
+        settings.modified = modified(&settings.path);
+    }
+
+    let mut current = world.resource_mut::<SceneFile>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demo_scene_round_trips_and_bad_references_are_rejected() {
+        let demo = SceneDesc::demo();
+        demo.validate().unwrap();
+        let json = serde_json::to_string(&demo).unwrap();
+        let parsed: SceneDesc = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.entities.len(), demo.entities.len());
+        assert_eq!(parsed.materials.len(), demo.materials.len());
+
+        // Omitted fields fall back to an identity transform
+        let short: SceneDesc =
+            serde_json::from_str(r#"{"materials": [], "entities": [{"name": "Empty"}]}"#).unwrap();
+        assert_eq!(short.entities[0].scale, [1.0; 3]);
+        short.validate().unwrap();
+
+        let mut forward_parent = demo.clone();
+        forward_parent.entities[1].parent = Some(1);
+        assert!(forward_parent.validate().is_err());
+        let mut missing_material = demo;
+        missing_material.entities[0].renderable = Some(RenderableEntry {
+            mesh: MeshKind::Cube,
+            material: 99,
+        });
+        assert!(missing_material.validate().is_err());
+    }
+
+    #[test]
+    fn scenes_load_on_the_job_pool() {
+        let wait = |loading: &mut SceneLoading| loop {
+            if let Some(loaded) = loading.finish() {
+                break loaded;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        };
+        let path = std::env::temp_dir().join(format!("scene_load_{}.json", std::process::id()));
+        SceneDesc::demo().save(&path).unwrap();
+
+        let mut loading = SceneLoading::default();
+        assert!(loading.progress().is_none());
+        loading.start(path.clone());
+        assert!(loading.progress().is_some());
+        let scene = wait(&mut loading).unwrap().unwrap();
+        assert_eq!(scene.entities.len(), SceneDesc::demo().entities.len());
+        assert!(loading.progress().is_none());
+
+        // Broken and missing files only come back as results
+        std::fs::write(&path, "{ \"entities\": [").unwrap();
+        loading.start(path.clone());
+        assert!(wait(&mut loading).is_err());
+        std::fs::remove_file(&path).unwrap();
+        loading.start(path);
+        assert!(wait(&mut loading).unwrap().is_none());
+    }
+}
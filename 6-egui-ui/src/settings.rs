@@ -0,0 +1,295 @@
+//! Renderer-wide settings in one resource, saved to a file whenever they
+//! change. Subsystems don't poll it, they observe [`RendererSettingsChanged`]
+//! and react to the fields they care about, so changing vsync reconfigures
+//! the surface and changing the quality preset applies its profile.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use bevy_ecs::{
+    event::Event,
+    observer::Trigger,
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Commands, Res, ResMut, Resource},
+    world::World,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    gpu::{GpuContext, PollStrategy},
+    i18n::{tr, trf},
+    pipeline::{quality::QualityPreset, render::render_system, ui::UiPanels},
+    uniform::DebugView,
+};
+
+/// Loaded at startup and written on every change, in the working directory.
+pub const SETTINGS_FILE: &str = "settings.json";
+
+/// Has to run right after the GPU is set up, the pipelines read their
+/// starting values from it.
+pub fn setup_renderer_settings(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let path = PathBuf::from(SETTINGS_FILE);
+    let settings = match RendererSettings::load(&path) {
+        Ok(Some(settings)) => settings,
+        Ok(None) => RendererSettings::default(),
+        Err(e) => {
+            warn!("Ignoring {}: {:?}", path.display(), e);
+            RendererSettings::default()
+        }
+    };
+    // Nothing depends on the surface yet, so no change is triggered
+    let mut gpu = world
+        .get_resource_mut::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    gpu.set_present(settings.vsync, settings.max_frame_latency);
+    gpu.set_pipeline_cache(settings.pipeline_cache);
+    gpu.set_poll_strategy(settings.poll_strategy);
+
+    world.insert_resource(AppliedRendererSettings {
+        settings: settings.clone(),
+        path,
+        last_save: None,
+    });
+    world.insert_resource(settings);
+    world.add_observer(renderer_settings_gpu_observer);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(renderer_settings_panel);
+
+    schedule.add_systems(
+        renderer_settings_system
+            .run_if(resource_changed::<RendererSettings>)
+            .before(render_system),
+    );
+
+    Ok(())
+}
+
+// =============================== SETTINGS ===============================
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RendererSettings {
+    pub vsync: bool,
+    /// Frames the CPU may queue ahead of the GPU, more smooths out spikes at
+    /// the cost of input latency.
+    pub max_frame_latency: u32,
+    /// Keep compiled pipelines on disk between runs.
+    pub pipeline_cache: bool,
+    /// Who polls the device for readbacks and fences.
+    pub poll_strategy: PollStrategy,
+    /// Profile applied from [`crate::pipeline::quality::QualityConfig`].
+    pub quality: QualityPreset,
+    pub debug_view: DebugView,
+    /// Move the camera again right before submitting, see
+    /// [`crate::input::latch`].
+    pub late_latch: bool,
+}
+impl Default for RendererSettings {
+    fn default() -> Self {
+        Self {
+            vsync: true,
+            max_frame_latency: 2,
+            pipeline_cache: true,
+            poll_strategy: PollStrategy::PerFrame,
+            quality: QualityPreset::High,
+            debug_view: DebugView::None,
+            late_latch: false,
+        }
+    }
+}
+impl RendererSettings {
+    /// `None` when there is no settings file yet.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let settings = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(settings))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Triggered once per frame the settings changed in, with what they were.
+#[derive(Event, Clone, Debug)]
+pub struct RendererSettingsChanged {
+    pub previous: RendererSettings,
+    pub current: RendererSettings,
+}
+impl RendererSettingsChanged {
+    pub fn present_changed(&self) -> bool {
+        self.previous.vsync != self.current.vsync
+            || self.previous.max_frame_latency != self.current.max_frame_latency
+    }
+}
+
+/// What the subsystems were last told, to tell a real change from a panel
+/// writing back the same values.
+#[derive(Resource)]
+pub struct AppliedRendererSettings {
+    settings: RendererSettings,
+    path: PathBuf,
+    last_save: Option<Result<(), String>>,
+}
+
+pub fn renderer_settings_system(
+    settings: Res<RendererSettings>,
+    mut applied: ResMut<AppliedRendererSettings>,
+    mut commands: Commands,
+) {
+    if applied.settings == *settings {
+        return;
+    }
+    let previous = std::mem::replace(&mut applied.settings, settings.clone());
+    commands.trigger(RendererSettingsChanged {
+        previous,
+        current: settings.clone(),
+    });
+
+    let result = settings.save(&applied.path);
+    match &result {
+        Ok(()) => info!("Saved renderer settings to {}", applied.path.display()),
+        Err(e) => error!("Failed to save renderer settings: {:?}", e),
+    }
+    applied.last_save = Some(result.map_err(|e| format!("{:#}", e)));
+}
+
+/// Reconfigures the surface, switches the pipeline cache and the poll
+/// strategy.
+pub fn renderer_settings_gpu_observer(
+    trigger: Trigger<RendererSettingsChanged>,
+    mut gpu: ResMut<GpuContext>,
+    mut commands: Commands,
+) {
+    let event = trigger.event();
+    if event.present_changed() {
+        let current = &event.current;
+        if let Some(changed) = gpu.set_present(current.vsync, current.max_frame_latency) {
+            commands.trigger(changed);
+        }
+    }
+    if event.previous.pipeline_cache != event.current.pipeline_cache {
+        gpu.set_pipeline_cache(event.current.pipeline_cache);
+    }
+    if event.previous.poll_strategy != event.current.poll_strategy {
+        gpu.set_poll_strategy(event.current.poll_strategy);
+    }
+}
+
+fn renderer_settings_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<RendererSettings>().clone();
+    let applied = world.resource::<AppliedRendererSettings>();
+    let present_mode = world.resource::<GpuContext>().config.present_mode;
+
+    egui::Window::new(tr("Renderer"))
+        .id(egui::Id::new("Renderer"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut settings.vsync, tr("Vsync"));
+            ui.label(trf!("Present mode: {}", format!("{:?}", present_mode)));
+            ui.horizontal(|ui| {
+                ui.label(tr("Frames in flight"));
+                ui.add(egui::DragValue::new(&mut settings.max_frame_latency).range(1..=3));
+            });
+            ui.checkbox(&mut settings.pipeline_cache, tr("Pipeline cache"))
+                .on_hover_text(tr("Turning it off deletes the cached pipelines"));
+            egui::ComboBox::from_label(tr("Device polling"))
+                .selected_text(poll_strategy_name(settings.poll_strategy))
+                .show_ui(ui, |ui| {
+                    for strategy in [
+                        PollStrategy::PerFrame,
+                        PollStrategy::Thread,
+                        PollStrategy::WaitOnSubmit,
+                    ] {
+                        ui.selectable_value(
+                            &mut settings.poll_strategy,
+                            strategy,
+                            poll_strategy_name(strategy),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text(tr(
+                    "Readbacks and GPU timings arrive when the device is polled",
+                ));
+            ui.label(tr(
+                "The quality preset, debug view and late latching are set in their panels and saved here too",
+            ));
+
+            ui.separator();
+            ui.label(trf!("File: {}", applied.path.display()));
+            if let Some(Err(e)) = &applied.last_save {
+                ui.colored_label(egui::Color32::RED, trf!("Save failed: {}", e));
+            }
+        });
+
+    let mut current = world.resource_mut::<RendererSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+}
+
+fn poll_strategy_name(strategy: PollStrategy) -> &'static str {
+    match strategy {
+        PollStrategy::PerFrame => tr("Every frame"),
+        PollStrategy::Thread => tr("Polling thread"),
+        PollStrategy::WaitOnSubmit => tr("Wait on submit"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::{change_detection::DetectChangesMut, system::ResMut};
+
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Changes(Vec<RendererSettingsChanged>);
+
+    #[test]
+    fn changes_are_triggered_once_and_saved() {
+        let path = std::env::temp_dir().join(format!("settings_{}.json", std::process::id()));
+        let mut world = World::new();
+        world.init_resource::<Changes>();
+        world.insert_resource(RendererSettings::default());
+        world.insert_resource(AppliedRendererSettings {
+            settings: RendererSettings::default(),
+            path: path.clone(),
+            last_save: None,
+        });
+        world.add_observer(
+            |trigger: Trigger<RendererSettingsChanged>, mut changes: ResMut<Changes>| {
+                changes.0.push(trigger.event().clone());
+            },
+        );
+        let mut schedule = Schedule::default();
+        schedule.add_systems(renderer_settings_system.run_if(resource_changed::<RendererSettings>));
+
+        // Written back unchanged, like a panel does every frame
+        schedule.run(&mut world);
+        world.resource_mut::<RendererSettings>().set_changed();
+        schedule.run(&mut world);
+        assert!(world.resource::<Changes>().0.is_empty());
+        assert!(!path.exists());
+
+        world.resource_mut::<RendererSettings>().vsync = false;
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        let changes = &world.resource::<Changes>().0;
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].present_changed());
+        assert!(changes[0].previous.vsync && !changes[0].current.vsync);
+
+        let saved = RendererSettings::load(&path).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved, *world.resource::<RendererSettings>());
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use bevy_ecs::{
+    schedule::Schedule,
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use tracing::{info, warn};
+
+use crate::{
+    shader_log::{self, EntryKind, ShaderError},
+    time::TimeContext,
+};
+
+pub fn setup_shaders(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    world.insert_resource(ShaderWatcher::default());
+    schedule.add_systems(shader_watch_system);
+    Ok(())
+}
+
+/// Location of a shader inside this example's source tree.
+pub fn shader_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src")
+        .join("shaders")
+        .join(name)
+}
+
+/// Shaders that others can pull in with `#include "name"`.
+const INCLUDES: &[(&str, &str)] = &[("noise.wgsl", include_str!("shaders/noise.wgsl"))];
+
+/// Reads a shader from disk, falling back to the embedded copy when the source
+/// tree is not available (e.g. when running a copied binary).
+pub fn load_shader_source(name: &str, embedded: &'static str) -> String {
+    let mut source = resolve_includes(&read_shader(name, embedded));
+    // At the very end, so it doesn't shift any line of the file itself
+    source.push_str(&format!("{}\"{}\"\n", SOURCE_MARKER, name));
+    source
+}
+
+fn read_shader(name: &str, embedded: &'static str) -> String {
+    std::fs::read_to_string(shader_path(name)).unwrap_or_else(|_| embedded.to_string())
+}
+
+/// Comment naming the file the lines after it come from, so diagnostics can be
+/// mapped back to it. See [`crate::shader_log::map_location`].
+pub const SOURCE_MARKER: &str = "// #source ";
+
+/// Blanks `#include "name"` lines and appends each included file once at the
+/// end, after a [`SOURCE_MARKER`] with the line it was included from. WGSL
+/// doesn't care about declaration order, and this way naga errors still point
+/// at the right line of the including file. Unknown includes are left in place
+/// for naga to report.
+pub fn resolve_includes(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut included = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let include = line.trim().strip_prefix("#include ").and_then(|name| {
+            INCLUDES
+                .iter()
+                .find(|(include, _)| *include == name.trim().trim_matches('"'))
+        });
+        match include {
+            Some(include) if !included.iter().any(|(i, _)| i == include) => {
+                included.push((*include, number + 1))
+            }
+            Some(_) => {}
+            None => output.push_str(line),
+        }
+        output.push('\n');
+    }
+    for ((name, embedded), from) in included {
+        output.push_str(&format!("{}\"{}\" {}\n", SOURCE_MARKER, name, from));
+        output.push_str(&read_shader(name, embedded));
+        output.push('\n');
+    }
+    output
+}
+
+/// Parses and validates WGSL with naga so errors can be reported instead of
+/// hitting wgpu's panicking validation handler. The outcome goes to the
+/// [shader log](crate::shader_log).
+pub fn parse_wgsl(name: &str, source: &str) -> Result<naga::Module> {
+    let started = Instant::now();
+    let fail = |message: String, details: String, location: Option<naga::SourceLocation>| {
+        let location = location
+            .map(|l| shader_log::map_location(name, source, l.line_number, l.line_position));
+        let error = ShaderError {
+            message,
+            details: details.clone(),
+            location,
+        };
+        shader_log::record(EntryKind::Shader, name, started, Some(error));
+        anyhow::anyhow!(details)
+    };
+    let module = naga::front::wgsl::parse_str(source).map_err(|e| {
+        fail(
+            e.message().to_string(),
+            e.emit_to_string_with_path(source, name),
+            e.location(source),
+        )
+    })?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| {
+        fail(
+            e.as_inner().to_string(),
+            e.emit_to_string_with_path(source, name),
+            e.location(source),
+        )
+    })
+    .with_context(|| format!("Shader '{}' failed validation", name))?;
+    shader_log::record(EntryKind::Shader, name, started, None);
+    Ok(module)
+}
+
+/// Reflects the `@workgroup_size` of a compute entry point.
+pub fn workgroup_size(module: &naga::Module, entry_point: &str) -> Result<[u32; 3]> {
+    module
+        .entry_points
+        .iter()
+        .find(|ep| ep.name == entry_point && ep.stage == naga::ShaderStage::Compute)
+        .map(|ep| ep.workgroup_size)
+        .ok_or_else(|| anyhow::anyhow!("Compute entry point '{}' not found", entry_point))
+}
+
+// =============================== PERMUTATIONS ===============================
+/// Selects a shader permutation by resolving `#ifdef NAME`, `#else` and
+/// `#endif` lines against `defines`. Dropped lines are kept as empty lines so
+/// naga errors still point at the right line of the file.
+pub fn preprocess(name: &str, source: &str, defines: &[&str]) -> Result<String> {
+    // Whether each enclosing block is emitted
+    let mut stack: Vec<bool> = Vec::new();
+    let mut output = String::with_capacity(source.len());
+    for (number, line) in source.lines().enumerate() {
+        let directive = line.trim();
+        let active = stack.iter().all(|active| *active);
+        if let Some(define) = directive.strip_prefix("#ifdef ") {
+            stack.push(defines.contains(&define.trim()));
+        } else if directive == "#else" {
+            let top = stack
+                .last_mut()
+                .with_context(|| format!("{}:{}: #else without #ifdef", name, number + 1))?;
+            *top = !*top;
+        } else if directive == "#endif" {
+            stack
+                .pop()
+                .with_context(|| format!("{}:{}: #endif without #ifdef", name, number + 1))?;
+        } else if active {
+            output.push_str(line);
+        }
+        output.push('\n');
+    }
+    if !stack.is_empty() {
+        anyhow::bail!("{}: {} unterminated #ifdef", name, stack.len());
+    }
+    Ok(output)
+}
+
+// =============================== WATCHER ===============================
+/// Polls the modification time of registered shader files.
+#[derive(Resource)]
+pub struct ShaderWatcher {
+    files: HashMap<PathBuf, Option<SystemTime>>,
+    changed: HashSet<PathBuf>,
+    interval: f32,
+    elapsed: f32,
+}
+
+impl Default for ShaderWatcher {
+    fn default() -> Self {
+        Self {
+            files: HashMap::new(),
+            changed: HashSet::new(),
+            interval: 0.5,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl ShaderWatcher {
+    pub fn watch(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        let modified = Self::modified(&path);
+        self.files.insert(path, modified);
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.files.keys().map(PathBuf::as_path)
+    }
+
+    /// Returns true once per modification of `path`.
+    pub fn take_changed(&mut self, path: &Path) -> bool {
+        self.changed.remove(path)
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    fn poll(&mut self) {
+        for (path, last_modified) in self.files.iter_mut() {
+            let modified = Self::modified(path);
+            if modified.is_some() && modified != *last_modified {
+                info!("Shader changed: {}", path.display());
+                *last_modified = modified;
+                self.changed.insert(path.clone());
+            } else if modified.is_none() && last_modified.is_some() {
+                warn!("Watched shader disappeared: {}", path.display());
+                *last_modified = None;
+            }
+        }
+    }
+}
+
+pub fn shader_watch_system(mut watcher: ResMut<ShaderWatcher>, time: Res<TimeContext>) {
+    watcher.elapsed += time.delta;
+    if watcher.elapsed >= watcher.interval {
+        watcher.elapsed = 0.0;
+        watcher.poll();
+    }
+}
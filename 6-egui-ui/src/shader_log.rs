@@ -0,0 +1,258 @@
+//! Log of shader compilations and pipeline creations. Errors keep the
+//! location naga reported, mapped back through the include chain, so the
+//! panel can open the offending line in the editor.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+
+use crate::{
+    external_editor::{open_in_editor, EditorConfig},
+    i18n::{tr, trf},
+    pipeline::ui::UiPanels,
+    shader::{shader_path, SOURCE_MARKER},
+};
+
+/// Oldest entries are dropped past this, hot reloading adds a few per save.
+const MAX_ENTRIES: usize = 256;
+
+static LOG: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+
+pub fn setup_shader_log(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    world.insert_resource(ShaderLogSettings::default());
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(shader_log_panel);
+
+    Ok(())
+}
+
+// =============================== LOG ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    Shader,
+    RenderPipeline,
+    ComputePipeline,
+}
+impl EntryKind {
+    fn name(self) -> &'static str {
+        match self {
+            EntryKind::Shader => tr("Shader"),
+            EntryKind::RenderPipeline => tr("Render pipeline"),
+            EntryKind::ComputePipeline => tr("Compute pipeline"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub kind: EntryKind,
+    pub label: String,
+    pub created: Instant,
+    /// How long validating the shader or creating the pipeline took.
+    pub duration: Duration,
+    pub error: Option<ShaderError>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ShaderError {
+    /// The headline, e.g. "expected ';', found 'let'".
+    pub message: String,
+    /// The full report with the annotated source snippet.
+    pub details: String,
+    pub location: Option<SourceLocation>,
+}
+
+/// A position in a file of the shaders directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    /// 1-based, like the editors count.
+    pub line: u32,
+    pub column: u32,
+    /// File and line of the `#include` that pulled `file` in.
+    pub included_from: Option<(String, u32)>,
+}
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// Adds an entry for work that started at `started`.
+pub fn record(kind: EntryKind, label: &str, started: Instant, error: Option<ShaderError>) {
+    let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    if log.len() == MAX_ENTRIES {
+        log.pop_front();
+    }
+    log.push_back(LogEntry {
+        kind,
+        label: label.to_string(),
+        created: Instant::now(),
+        duration: started.elapsed(),
+        error,
+    });
+}
+
+/// Maps a 1-based line and column of a resolved shader `name` back to the
+/// file it came from, using the [`SOURCE_MARKER`] comments left by
+/// [`crate::shader::resolve_includes`] and
+/// [`crate::shader::load_shader_source`]. `#ifdef` permutations keep every
+/// line, so preprocessed sources map the same way.
+pub fn map_location(name: &str, source: &str, line: u32, column: u32) -> SourceLocation {
+    let mut root = name.to_string();
+    // Last include marker above the line, with its own line and where the
+    // include was
+    let mut include = None;
+    for (index, text) in source.lines().enumerate() {
+        let Some((file, from)) = text
+            .strip_prefix(SOURCE_MARKER)
+            .and_then(|marker| marker.strip_prefix('"'))
+            .and_then(|marker| marker.split_once('"'))
+        else {
+            continue;
+        };
+        let marker_line = index as u32 + 1;
+        match from.trim().parse::<u32>().ok() {
+            None => root = file.to_string(),
+            Some(from) if marker_line < line => {
+                include = Some((file.to_string(), marker_line, from))
+            }
+            Some(_) => {}
+        }
+    }
+    match include {
+        Some((file, marker_line, from)) => SourceLocation {
+            file,
+            line: line - marker_line,
+            column,
+            included_from: Some((root, from)),
+        },
+        None => SourceLocation {
+            file: root,
+            line,
+            column,
+            included_from: None,
+        },
+    }
+}
+
+// =============================== UI ===============================
+#[derive(Resource, Clone, Default, PartialEq)]
+pub struct ShaderLogSettings {
+    pub errors_only: bool,
+}
+
+fn shader_log_panel(ctx: &egui::Context, world: &mut World) {
+    let mut settings = world.resource::<ShaderLogSettings>().clone();
+    let editor_error = world.resource::<EditorConfig>().last_error.clone();
+    let mut open = None;
+    let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    let errors = log.iter().filter(|entry| entry.error.is_some()).count();
+
+    egui::Window::new(tr("Shader log"))
+        .id(egui::Id::new("Shader log"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(trf!("{} entries, {} errors", log.len(), errors));
+                ui.checkbox(&mut settings.errors_only, tr("Errors only"));
+                if ui.button(tr("Clear")).clicked() {
+                    log.clear();
+                }
+            });
+            if let Some(error) = &editor_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            ui.separator();
+
+            egui::ScrollArea::vertical()
+                .max_height(400.0)
+                .show(ui, |ui| {
+                    // Newest first, the last save is what matters
+                    for (index, entry) in log.iter().enumerate().rev() {
+                        if settings.errors_only && entry.error.is_none() {
+                            continue;
+                        }
+                        ui.push_id(index, |ui| entry_ui(ui, entry, &mut open));
+                    }
+                });
+        });
+    drop(log);
+
+    let mut current = world.resource_mut::<ShaderLogSettings>();
+    if *current != settings {
+        *current = settings;
+    }
+    if let Some(location) = open {
+        let position = (location.line, location.column);
+        open_in_editor(world, &shader_path(&location.file), Some(position));
+    }
+}
+
+fn entry_ui(ui: &mut egui::Ui, entry: &LogEntry, open: &mut Option<SourceLocation>) {
+    ui.horizontal(|ui| {
+        let (icon, color) = match entry.error {
+            Some(_) => ("✖", egui::Color32::RED),
+            None => ("✔", egui::Color32::GREEN),
+        };
+        ui.colored_label(color, icon);
+        ui.label(entry.kind.name());
+        ui.monospace(&entry.label);
+        ui.weak(trf!(
+            "{:.1} ms, {}s ago",
+            entry.duration.as_secs_f64() * 1000.0,
+            entry.created.elapsed().as_secs()
+        ));
+    });
+    let Some(error) = &entry.error else {
+        return;
+    };
+    ui.colored_label(egui::Color32::LIGHT_RED, &error.message);
+    if let Some(location) = &error.location {
+        ui.horizontal(|ui| {
+            let link = ui
+                .link(location.to_string())
+                .on_hover_text(tr("Open in editor"));
+            if link.clicked() {
+                *open = Some(location.clone());
+            }
+            if let Some((file, line)) = &location.included_from {
+                ui.weak(trf!("included from {}:{}", file, line));
+            }
+        });
+    }
+    egui::CollapsingHeader::new(tr("Details"))
+        .id_salt("details")
+        .show(ui, |ui| ui.monospace(&error.details));
+    ui.separator();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::resolve_includes;
+
+    #[test]
+    fn locations_map_through_includes() {
+        let mut source = resolve_includes("fn a() {}\n#include \"noise.wgsl\"\nfn b() {}\n");
+        source.push_str(&format!("{}\"root.wgsl\"\n", SOURCE_MARKER));
+
+        let in_root = map_location("label", &source, 3, 4);
+        assert_eq!(in_root.file, "root.wgsl");
+        assert_eq!((in_root.line, in_root.column), (3, 4));
+        assert_eq!(in_root.included_from, None);
+
+        // The marker follows the three lines of the root file
+        let in_include = map_location("label", &source, 6, 1);
+        assert_eq!(in_include.file, "noise.wgsl");
+        assert_eq!(in_include.line, 2);
+        assert_eq!(in_include.included_from, Some(("root.wgsl".to_string(), 2)));
+    }
+}
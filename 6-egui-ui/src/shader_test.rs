@@ -0,0 +1,393 @@
+//! Unit tests for WGSL functions. The functions under test are copied out of
+//! a shader file into a generated compute kernel that evaluates an expression
+//! for every element of an input buffer and writes the results back, so a
+//! test only deals in plain Rust values.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use bytemuck::Pod;
+use pollster::FutureExt;
+use wgpu::util::DeviceExt;
+
+use crate::shader::{parse_wgsl, resolve_includes, shader_path};
+
+/// Types that can be passed through the storage buffers, with their WGSL
+/// spelling. There's no `vec3`, its storage layout is padded to 16 bytes.
+pub trait WgslType: Pod {
+    const WGSL: &'static str;
+}
+impl WgslType for f32 {
+    const WGSL: &'static str = "f32";
+}
+impl WgslType for u32 {
+    const WGSL: &'static str = "u32";
+}
+impl WgslType for [f32; 2] {
+    const WGSL: &'static str = "vec2<f32>";
+}
+impl WgslType for [f32; 4] {
+    const WGSL: &'static str = "vec4<f32>";
+}
+impl WgslType for [u32; 2] {
+    const WGSL: &'static str = "vec2<u32>";
+}
+impl WgslType for [u32; 4] {
+    const WGSL: &'static str = "vec4<u32>";
+}
+
+const WORKGROUP_SIZE: u32 = 64;
+
+// =============================== DEVICE ===============================
+/// Set to fail GPU tests instead of skipping them when there's no adapter,
+/// on machines that are supposed to have one.
+pub const REQUIRE_GPU_ENV: &str = "PLAYGROUND_REQUIRE_GPU";
+
+/// The device for a GPU test, `None` when the test should return early
+/// because there's no adapter. Every GPU test goes through this, so skips
+/// look the same everywhere.
+pub fn gpu_or_skip() -> Option<TestGpu> {
+    let gpu = TestGpu::new();
+    if gpu.is_none() {
+        let test = std::thread::current()
+            .name()
+            .unwrap_or("GPU test")
+            .to_string();
+        assert!(
+            std::env::var_os(REQUIRE_GPU_ENV).is_none(),
+            "No adapter available for {}, but {} is set",
+            test,
+            REQUIRE_GPU_ENV
+        );
+        // Straight to stderr, the test harness swallows eprintln! output of
+        // tests that pass
+        let _ = writeln!(std::io::stderr(), "No adapter available, skipping {}", test);
+    }
+    gpu
+}
+
+pub struct TestGpu {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+impl TestGpu {
+    /// `None` when the machine has no adapter at all, see [`gpu_or_skip`].
+    pub fn new() -> Option<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions::default())
+            .block_on()?;
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("shader_test_device"),
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::downlevel_defaults(),
+                    memory_hints: wgpu::MemoryHints::default(),
+                },
+                None,
+            )
+            .block_on()
+            .ok()?;
+        Some(Self { device, queue })
+    }
+}
+
+// =============================== SHADER TEST ===============================
+pub struct ShaderTest {
+    name: String,
+    source: String,
+    items: Vec<String>,
+}
+impl ShaderTest {
+    /// Tests functions of `name` in the shaders directory.
+    pub fn new(name: &str) -> Result<Self> {
+        let source = std::fs::read_to_string(shader_path(name))
+            .with_context(|| format!("Failed to read shader '{}'", name))?;
+        let source = resolve_includes(&source);
+        Ok(Self {
+            name: name.to_string(),
+            source,
+            items: Vec::new(),
+        })
+    }
+
+    /// Copies `fn name` into the kernel. Functions it calls have to be added
+    /// too, before or after it.
+    pub fn function(mut self, name: &str) -> Result<Self> {
+        let function = extract_function(&self.source, name)
+            .with_context(|| format!("Function '{}' not found in '{}'", name, self.name))?;
+        self.items.push(function.to_string());
+        Ok(self)
+    }
+
+    /// Copies the whole file, for files without bindings or entry points such
+    /// as includes.
+    pub fn whole(mut self) -> Self {
+        self.items.push(self.source.clone());
+        self
+    }
+
+    /// Adds WGSL the functions depend on, e.g. a constant or a stand-in for a
+    /// binding they read.
+    pub fn prelude(mut self, wgsl: &str) -> Self {
+        self.items.push(wgsl.to_string());
+        self
+    }
+
+    /// Evaluates `expression` once per input, with the input bound as
+    /// `input`, and returns the results in order.
+    pub fn run<I: WgslType, O: WgslType>(
+        &self,
+        gpu: &TestGpu,
+        expression: &str,
+        inputs: &[I],
+    ) -> Result<Vec<O>> {
+        let kernel = self.kernel(expression, I::WGSL, O::WGSL);
+        let label = format!("{}_test", self.name);
+        parse_wgsl(&label, &kernel)?;
+
+        let device = &gpu.device;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&label),
+            source: wgpu::ShaderSource::Wgsl(kernel.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&label),
+            layout: None,
+            module: &module,
+            entry_point: Some("test_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let input_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("shader_test_inputs"),
+            contents: bytemuck::cast_slice(inputs),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let output_size = std::mem::size_of_val(inputs) as u64 / std::mem::size_of::<I>() as u64
+            * std::mem::size_of::<O>() as u64;
+        let output_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shader_test_outputs"),
+            size: output_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shader_test_readback"),
+            size: output_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("shader_test_bind_group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: input_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: output_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("shader_test_encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("shader_test_pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups((inputs.len() as u32).div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&output_buffer, 0, &readback, 0, output_size);
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let outputs = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        Ok(outputs)
+    }
+
+    fn kernel(&self, expression: &str, input: &str, output: &str) -> String {
+        format!(
+            "{items}\n\n\
+             @group(0) @binding(0)\n\
+             var<storage, read> test_inputs: array<{input}>;\n\
+             @group(0) @binding(1)\n\
+             var<storage, read_write> test_outputs: array<{output}>;\n\n\
+             @compute @workgroup_size({WORKGROUP_SIZE})\n\
+             fn test_main(@builtin(global_invocation_id) id: vec3<u32>) {{\n\
+             \x20   if id.x >= arrayLength(&test_outputs) {{\n\
+             \x20       return;\n\
+             \x20   }}\n\
+             \x20   let input = test_inputs[id.x];\n\
+             \x20   test_outputs[id.x] = {expression};\n\
+             }}\n",
+            items = self.items.join("\n\n"),
+        )
+    }
+}
+
+/// Finds `fn name(` at the start of a line and returns it up to its matching
+/// closing brace.
+fn extract_function<'a>(source: &'a str, name: &str) -> Option<&'a str> {
+    let signature = format!("fn {}(", name);
+    let start = source
+        .match_indices(&signature)
+        .map(|(index, _)| index)
+        .find(|&index| index == 0 || source[..index].ends_with('\n'))?;
+    let mut depth = 0;
+    for (offset, c) in source[start..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&source[start..=start + offset]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// =============================== TESTS ===============================
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linear_to_srgb(linear: f32) -> f32 {
+        if linear < 0.0031308 {
+            linear * 12.92
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        }
+    }
+
+    #[test]
+    fn srgb_conversion_matches_reference() -> Result<()> {
+        let Some(gpu) = gpu_or_skip() else {
+            return Ok(());
+        };
+        let test = ShaderTest::new("present.wgsl")?
+            .function("linear_to_srgb")?
+            .function("srgb_to_linear")?;
+        let inputs: Vec<f32> = (0..=256).map(|i| i as f32 / 256.0).collect();
+
+        let encoded: Vec<f32> = test.run(&gpu, "linear_to_srgb(vec3<f32>(input)).x", &inputs)?;
+        for (linear, srgb) in inputs.iter().zip(&encoded) {
+            let expected = linear_to_srgb(*linear);
+            assert!(
+                (srgb - expected).abs() < 1e-4,
+                "linear_to_srgb({}) = {}, expected {}",
+                linear,
+                srgb,
+                expected
+            );
+        }
+
+        let round_trip: Vec<f32> = test.run(
+            &gpu,
+            "srgb_to_linear(linear_to_srgb(vec3<f32>(input))).x",
+            &inputs,
+        )?;
+        for (linear, back) in inputs.iter().zip(&round_trip) {
+            assert!(
+                (linear - back).abs() < 1e-4,
+                "{} came back as {}",
+                linear,
+                back
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn dither_hash_is_uniform() -> Result<()> {
+        let Some(gpu) = gpu_or_skip() else {
+            return Ok(());
+        };
+        let test = ShaderTest::new("present.wgsl")?.function("hash")?;
+        let inputs: Vec<[u32; 4]> = (0..4096).map(|i| [i % 64, i / 64, 7, 0]).collect();
+        let values: Vec<f32> = test.run(&gpu, "hash(input.xyz)", &inputs)?;
+
+        assert!(values.iter().all(|v| (0.0..=1.0).contains(v)));
+        let mut buckets = [0u32; 8];
+        for v in &values {
+            buckets[((v * 8.0) as usize).min(7)] += 1;
+        }
+        let expected = values.len() as u32 / 8;
+        for (i, count) in buckets.iter().enumerate() {
+            assert!(
+                count.abs_diff(expected) < expected / 5,
+                "bucket {} has {} values, expected about {}",
+                i,
+                count,
+                expected
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn particle_hash_is_deterministic_and_spread() -> Result<()> {
+        let Some(gpu) = gpu_or_skip() else {
+            return Ok(());
+        };
+        let test = ShaderTest::new("particles.wgsl")?.function("hash")?;
+        let inputs: Vec<u32> = (0..1024).collect();
+        let first: Vec<u32> = test.run(&gpu, "hash(input)", &inputs)?;
+        let second: Vec<u32> = test.run(&gpu, "hash(input)", &inputs)?;
+        assert_eq!(first, second);
+
+        let mut unique = first.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), first.len(), "hash has collisions");
+        // Neighbouring seeds should flip about half the bits
+        let flipped: u32 = first.windows(2).map(|w| (w[0] ^ w[1]).count_ones()).sum();
+        let average = flipped as f32 / (first.len() - 1) as f32;
+        assert!((12.0..20.0).contains(&average), "{} bits flip", average);
+        Ok(())
+    }
+
+    #[test]
+    fn volume_box_intersection() -> Result<()> {
+        let Some(gpu) = gpu_or_skip() else {
+            return Ok(());
+        };
+        // Stands in for the uniform, only the box is read
+        let test = ShaderTest::new("volume.wgsl")?
+            .prelude(
+                "struct Volume { box_min: vec4<f32>, box_max: vec4<f32> }\n\
+                 var<private> volume: Volume = \
+                 Volume(vec4<f32>(-1.0, -1.0, -1.0, 0.0), vec4<f32>(1.0, 1.0, 1.0, 0.0));",
+            )
+            .function("intersect_box")?;
+        // Rays along +z starting 5 units in front of the box, offset in x
+        let inputs: Vec<f32> = vec![0.0, 0.5, -0.99, 1.5, -3.0];
+        let hits: Vec<[f32; 2]> = test.run(
+            &gpu,
+            "intersect_box(vec3<f32>(input, 0.0, -5.0), vec3<f32>(0.0, 0.0, 1.0))",
+            &inputs,
+        )?;
+        for (x, [entry, exit]) in inputs.iter().zip(&hits) {
+            if x.abs() < 1.0 {
+                assert!((entry - 4.0).abs() < 1e-5 && (exit - 6.0).abs() < 1e-5);
+            } else {
+                assert!(entry > exit, "ray at x = {} should miss", x);
+            }
+        }
+        Ok(())
+    }
+}
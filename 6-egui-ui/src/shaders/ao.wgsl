@@ -0,0 +1,174 @@
+// Ambient occlusion from the depth buffer and the surface normals. Every
+// technique is a permutation of this file with the same inputs and output,
+// so they can be swapped or run side by side.
+
+struct AoParams {
+    proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    // View space radius of the occlusion
+    radius: f32,
+    // Exponent applied to the visibility
+    intensity: f32,
+    // SSAO kernel size
+    samples: u32,
+    // GTAO directions and steps per side
+    slices: u32,
+    steps: u32,
+    // Divider of the comparison view in UV, negative when not comparing
+    split: f32,
+}
+
+// At the resolution the AO renders at
+@group(0) @binding(0)
+var t_depth: texture_depth_2d;
+// Octahedral normal in xy
+@group(0) @binding(1)
+var t_surface: texture_2d<f32>;
+@group(0) @binding(2)
+var<uniform> ao: AoParams;
+
+const PI: f32 = 3.14159265;
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+fn octahedral_decode(encoded: vec2<f32>) -> vec3<f32> {
+    var n = vec3<f32>(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    let t = max(-n.z, 0.0);
+    n.x += select(t, -t, n.x >= 0.0);
+    n.y += select(t, -t, n.y >= 0.0);
+    return normalize(n);
+}
+
+fn view_position(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let position = ao.inv_proj * ndc;
+    return position.xyz / position.w;
+}
+
+fn view_position_at(uv: vec2<f32>, size: vec2<f32>) -> vec3<f32> {
+    let pixel = vec2<i32>(clamp(uv, vec2<f32>(0.0), vec2<f32>(0.9999)) * size);
+    return view_position(uv, textureLoad(t_depth, pixel, 0));
+}
+
+// Ordered noise repeating every 4x4 pixels, which the composite's 4x4 blur
+// averages out exactly
+fn bayer(pixel: vec2<i32>) -> f32 {
+    var pattern = array<f32, 16>(0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+    let cell = pixel & vec2<i32>(3);
+    return (pattern[cell.y * 4 + cell.x] + 0.5) / 16.0;
+}
+
+#ifdef GTAO
+// Ground truth AO (Jimenez et al. 2016): finds the horizon on both sides of a
+// few screen space slices and integrates the cosine weighted visibility
+// between them analytically.
+fn visibility(uv: vec2<f32>, origin: vec3<f32>, normal: vec3<f32>, noise: f32, size: vec2<f32>) -> f32 {
+    let view_dir = normalize(-origin);
+    // Radius in pixels at this depth
+    let radius_pixels = ao.radius * ao.proj[1][1] * 0.5 * size.y / -origin.z;
+    if radius_pixels < 1.0 {
+        return 1.0;
+    }
+    let step_pixels = radius_pixels / f32(ao.steps);
+
+    var total = 0.0;
+    for (var slice = 0u; slice < ao.slices; slice++) {
+        let phi = (f32(slice) + noise) * PI / f32(ao.slices);
+        let direction = vec3<f32>(cos(phi), sin(phi), 0.0);
+        // Screen space y points down
+        let screen_step = vec2<f32>(direction.x, -direction.y) * step_pixels / size;
+
+        let ortho = direction - dot(direction, view_dir) * view_dir;
+        let axis = normalize(cross(ortho, view_dir));
+        let projected_normal = normal - axis * dot(normal, axis);
+        let projected_length = length(projected_normal);
+        let cos_n = clamp(dot(projected_normal, view_dir) / max(projected_length, 0.0001), -1.0, 1.0);
+        let n = sign(dot(ortho, projected_normal)) * acos(cos_n);
+
+        var horizon_cos = vec2<f32>(-1.0);
+        for (var step = 0u; step < ao.steps; step++) {
+            let offset = screen_step * (f32(step) + fract(noise * 7.0) + 1.0);
+            for (var side = 0; side < 2; side++) {
+                let sample_uv = uv + select(-offset, offset, side == 1);
+                if any(sample_uv < vec2<f32>(0.0)) || any(sample_uv > vec2<f32>(1.0)) {
+                    continue;
+                }
+                let delta = view_position_at(sample_uv, size) - origin;
+                let distance = length(delta);
+                // Fades out occluders towards the radius instead of cutting them off
+                let falloff = clamp(2.0 - 2.0 * distance / ao.radius, 0.0, 1.0);
+                let sample_cos = mix(-1.0, dot(delta / distance, view_dir), falloff);
+                horizon_cos[side] = max(horizon_cos[side], sample_cos);
+            }
+        }
+
+        let h0 = n + clamp(-acos(horizon_cos.x) - n, -PI * 0.5, PI * 0.5);
+        let h1 = n + clamp(acos(horizon_cos.y) - n, -PI * 0.5, PI * 0.5);
+        let arc0 = (cos_n + 2.0 * h0 * sin(n) - cos(2.0 * h0 - n)) * 0.25;
+        let arc1 = (cos_n + 2.0 * h1 * sin(n) - cos(2.0 * h1 - n)) * 0.25;
+        total += projected_length * (arc0 + arc1);
+    }
+    return clamp(total / f32(ao.slices), 0.0, 1.0);
+}
+#else
+// Classic normal oriented hemisphere SSAO: counts kernel samples that end up
+// behind the depth buffer.
+fn visibility(uv: vec2<f32>, origin: vec3<f32>, normal: vec3<f32>, noise: f32, size: vec2<f32>) -> f32 {
+    // Random rotation of the kernel around the normal
+    let angle = noise * 2.0 * PI;
+    var seed = vec3<f32>(cos(angle), sin(angle), 0.0);
+    if abs(dot(seed, normal)) > 0.99 {
+        seed = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let tangent = normalize(seed - normal * dot(seed, normal));
+    let bitangent = cross(normal, tangent);
+
+    var occlusion = 0.0;
+    for (var i = 0u; i < ao.samples; i++) {
+        // Spiral over the hemisphere, denser close to the surface
+        let t = (f32(i) + 0.5) / f32(ao.samples);
+        let cos_theta = 1.0 - t;
+        let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+        let phi = f32(i) * GOLDEN_ANGLE;
+        let scale = mix(0.1, 1.0, t * t);
+        let kernel = vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta) * scale;
+        let sample = origin + (tangent * kernel.x + bitangent * kernel.y + normal * kernel.z) * ao.radius;
+
+        let clip = ao.proj * vec4<f32>(sample, 1.0);
+        let sample_uv = clip.xy / clip.w * vec2<f32>(0.5, -0.5) + 0.5;
+        if any(sample_uv < vec2<f32>(0.0)) || any(sample_uv > vec2<f32>(1.0)) {
+            continue;
+        }
+        let scene = view_position_at(sample_uv, size);
+        // Ignores occluders far in front, they're not in the radius
+        let range = smoothstep(0.0, 1.0, ao.radius / abs(origin.z - scene.z));
+        occlusion += select(0.0, range, scene.z >= sample.z + 0.025);
+    }
+    return 1.0 - occlusion / f32(ao.samples);
+}
+#endif
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy);
+    let depth = textureLoad(t_depth, pixel, 0);
+    if depth >= 1.0 {
+        return vec4<f32>(1.0);
+    }
+    let size = vec2<f32>(textureDimensions(t_depth));
+    let uv = position.xy / size;
+    let origin = view_position(uv, depth);
+    // The surface buffer stays at full resolution when the AO doesn't
+    let surface_pixel = vec2<i32>(uv * vec2<f32>(textureDimensions(t_surface)));
+    let world_normal = octahedral_decode(textureLoad(t_surface, surface_pixel, 0).xy);
+    let normal = normalize((ao.view * vec4<f32>(world_normal, 0.0)).xyz);
+
+    let unoccluded = visibility(uv, origin, normal, bayer(pixel), size);
+    return vec4<f32>(pow(unoccluded, ao.intensity));
+}
@@ -0,0 +1,65 @@
+// Applies the AO buffer to the frame buffer, either multiplied in or shown on
+// its own. Blurs over the 4x4 tile of the AO noise, and when the buffer is at
+// a reduced resolution upsamples it in the same taps, weighting each by how
+// close its depth is to the full resolution depth.
+
+struct AoParams {
+    proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    radius: f32,
+    intensity: f32,
+    samples: u32,
+    slices: u32,
+    steps: u32,
+    // Divider of the comparison view in UV, negative when not comparing
+    split: f32,
+}
+
+@group(0) @binding(0)
+var t_ao: texture_2d<f32>;
+// Depth the AO buffer was rendered against
+@group(0) @binding(1)
+var t_ao_depth: texture_depth_2d;
+@group(0) @binding(2)
+var t_depth: texture_depth_2d;
+@group(0) @binding(3)
+var<uniform> ao: AoParams;
+
+// Relative depth difference at which a tap's weight drops to 1/e
+const DEPTH_SIGMA: f32 = 0.05;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+fn linear_depth(depth: f32) -> f32 {
+    let view = ao.inv_proj * vec4<f32>(0.0, 0.0, depth, 1.0);
+    return -view.z / view.w;
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let full_size = vec2<f32>(textureDimensions(t_depth));
+    if ao.split >= 0.0 && abs(position.x - ao.split * full_size.x) < 1.0 {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let size = vec2<i32>(textureDimensions(t_ao));
+    let depth = linear_depth(textureLoad(t_depth, vec2<i32>(position.xy), 0));
+    let pixel = vec2<i32>(position.xy / full_size * vec2<f32>(size));
+    var sum = 0.0;
+    var total = 0.0;
+    for (var y = -1; y <= 2; y++) {
+        for (var x = -1; x <= 2; x++) {
+            let tap = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let delta = abs(linear_depth(textureLoad(t_ao_depth, tap, 0)) - depth) / depth;
+            let weight = exp(-delta / DEPTH_SIGMA) + 0.0001;
+            sum += textureLoad(t_ao, tap, 0).r * weight;
+            total += weight;
+        }
+    }
+    return vec4<f32>(vec3<f32>(sum / total), 1.0);
+}
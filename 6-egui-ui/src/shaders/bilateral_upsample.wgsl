@@ -0,0 +1,67 @@
+// Upsamples a reduced resolution effect to the full resolution target. The
+// four texels of the bilinear footprint are weighted by how close their depth
+// is to the full resolution depth, so the effect doesn't bleed across edges.
+
+struct Camera {
+    near: f32,
+    far: f32,
+}
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+// Depth the source was rendered against
+@group(0) @binding(1)
+var t_source_depth: texture_depth_2d;
+@group(0) @binding(2)
+var t_depth: texture_depth_2d;
+@group(0) @binding(3)
+var<uniform> camera: Camera;
+
+// Relative depth difference at which a texel's weight drops to 1/e
+const DEPTH_SIGMA: f32 = 0.05;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+fn linear_depth(depth: f32) -> f32 {
+    return camera.near * camera.far / (camera.far - depth * (camera.far - camera.near));
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let full_size = vec2<f32>(textureDimensions(t_depth));
+    let low_size = vec2<i32>(textureDimensions(t_source));
+    let depth = linear_depth(textureLoad(t_depth, vec2<i32>(position.xy), 0));
+
+    let coord = position.xy / full_size * vec2<f32>(low_size) - 0.5;
+    let base = vec2<i32>(floor(coord));
+    let f = fract(coord);
+
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    // Fallback when every texel is across an edge
+    var nearest = vec4<f32>(0.0);
+    var nearest_delta = 1e30;
+    for (var y = 0; y < 2; y++) {
+        for (var x = 0; x < 2; x++) {
+            let tap = clamp(base + vec2<i32>(x, y), vec2<i32>(0), low_size - 1);
+            let sample = textureLoad(t_source, tap, 0);
+            let delta = abs(linear_depth(textureLoad(t_source_depth, tap, 0)) - depth) / depth;
+            let bilinear = select(1.0 - f.x, f.x, x == 1) * select(1.0 - f.y, f.y, y == 1);
+            let weight = (bilinear + 0.001) * exp(-delta / DEPTH_SIGMA);
+            sum += sample * weight;
+            total += weight;
+            if delta < nearest_delta {
+                nearest_delta = delta;
+                nearest = sample;
+            }
+        }
+    }
+    if total < 0.0001 {
+        return nearest;
+    }
+    return sum / total;
+}
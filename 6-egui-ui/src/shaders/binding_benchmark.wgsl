@@ -0,0 +1,50 @@
+// The objects of the binding benchmark, each drawn on its own. The uniform
+// variant binds its object at a dynamic offset before every draw, the
+// storage variant binds them all once and picks its object by instance.
+// Push constants are in `binding_benchmark_push.wgsl`, which only compiles
+// on devices with the feature.
+
+struct Object {
+    transform: mat4x4<f32>,
+    color: vec4<f32>,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> object: Object;
+@group(0) @binding(1)
+var<storage, read> objects: array<Object>;
+
+fn transform_vertex(data: Object, vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = data.transform * vec4<f32>(vertex.position, 1.0);
+    // A light from the upper left, enough to tell the faces apart
+    let normal = normalize((data.transform * vec4<f32>(vertex.normal, 0.0)).xyz);
+    let light = 0.4 + 0.6 * max(dot(normal, normalize(vec3<f32>(-0.4, 0.6, -0.7))), 0.0);
+    out.color = vec4<f32>(data.color.rgb * light, 1.0);
+    return out;
+}
+
+@vertex
+fn vs_uniform(vertex: VertexInput) -> VertexOutput {
+    return transform_vertex(object, vertex);
+}
+
+@vertex
+fn vs_storage(vertex: VertexInput, @builtin(instance_index) instance: u32) -> VertexOutput {
+    return transform_vertex(objects[instance], vertex);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,34 @@
+// The push constant variant of `binding_benchmark.wgsl`, which it mirrors:
+// every draw pushes its object right before it.
+
+struct Object {
+    transform: mat4x4<f32>,
+    color: vec4<f32>,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+var<push_constant> object: Object;
+
+@vertex
+fn vs_push(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = object.transform * vec4<f32>(vertex.position, 1.0);
+    let normal = normalize((object.transform * vec4<f32>(vertex.normal, 0.0)).xyz);
+    let light = 0.4 + 0.6 * max(dot(normal, normalize(vec3<f32>(-0.4, 0.6, -0.7))), 0.0);
+    out.color = vec4<f32>(object.color.rgb * light, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,139 @@
+// Rebuilds the full frame from the half drawn this frame and the last
+// reconstruction. Pixels with (x + y) % 2 == parity were drawn, even rows
+// into the top of the half frame and odd rows into the bottom. The others
+// come from the history, reprojected along the velocity of their nearest
+// neighbor and clamped to what the neighbors show.
+
+struct Params {
+    size: vec2<u32>,
+    half_height: u32,
+    parity: u32,
+    view: u32,
+    flags: u32,
+    gain: f32,
+    _padding: u32,
+}
+
+const FLAG_HISTORY: u32 = 1u;
+const FLAG_REPROJECT: u32 = 2u;
+const FLAG_CLAMP: u32 = 4u;
+
+const VIEW_RECONSTRUCTED: u32 = 1u;
+const VIEW_DRAWN: u32 = 2u;
+const VIEW_DIFFERENCE: u32 = 3u;
+const VIEW_SPLIT: u32 = 4u;
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var half_color: texture_2d<f32>;
+@group(0) @binding(2) var half_velocity: texture_2d<f32>;
+@group(0) @binding(3) var half_depth: texture_depth_2d;
+@group(0) @binding(4) var history: texture_2d<f32>;
+@group(0) @binding(5) var history_sampler: sampler;
+// What was in the frame buffer before the meshes, and after the full
+// resolution mesh pass
+@group(0) @binding(6) var background: texture_2d<f32>;
+@group(0) @binding(7) var reference: texture_2d<f32>;
+@group(0) @binding(8) var resolved: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(9) var display: texture_storage_2d<rgba16float, write>;
+
+fn drawn(pixel: vec2<i32>) -> bool {
+    return u32(pixel.x + pixel.y) % 2u == params.parity;
+}
+
+fn half_texel(pixel: vec2<i32>) -> vec2<i32> {
+    return vec2<i32>(pixel.x / 2, pixel.y / 2 + (pixel.y % 2) * i32(params.half_height));
+}
+
+fn fill(pixel: vec2<i32>, size: vec2<i32>) -> vec4<f32> {
+    var offsets = array<vec2<i32>, 4>(
+        vec2<i32>(-1, 0),
+        vec2<i32>(1, 0),
+        vec2<i32>(0, -1),
+        vec2<i32>(0, 1),
+    );
+    var low = vec4<f32>(1e9);
+    var high = vec4<f32>(-1e9);
+    var sum = vec4<f32>(0.0);
+    var nearest = 2.0;
+    var velocity = vec2<f32>(0.0);
+    for (var i = 0; i < 4; i++) {
+        // Mirrored at the edges, clamping would land on a pixel not drawn
+        var neighbor = pixel + offsets[i];
+        if any(neighbor < vec2<i32>(0)) || any(neighbor >= size) {
+            neighbor = pixel - offsets[i];
+        }
+        let texel = half_texel(neighbor);
+        let color = textureLoad(half_color, texel, 0);
+        low = min(low, color);
+        high = max(high, color);
+        sum += color;
+        let depth = textureLoad(half_depth, texel, 0);
+        if depth < nearest {
+            nearest = depth;
+            velocity = textureLoad(half_velocity, texel, 0).xy;
+        }
+    }
+    let spatial = sum * 0.25;
+    if (params.flags & FLAG_HISTORY) == 0u {
+        return spatial;
+    }
+
+    if (params.flags & FLAG_REPROJECT) == 0u {
+        velocity = vec2<f32>(0.0);
+    }
+    let uv = (vec2<f32>(pixel) + 0.5) / vec2<f32>(size);
+    let previous = uv - velocity;
+    if any(previous < vec2<f32>(0.0)) || any(previous > vec2<f32>(1.0)) {
+        return spatial;
+    }
+    let color = textureSampleLevel(history, history_sampler, previous, 0.0);
+    if (params.flags & FLAG_CLAMP) != 0u {
+        return clamp(color, low, high);
+    }
+    return color;
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(params.size);
+    let pixel = vec2<i32>(id.xy);
+    if any(pixel >= size) {
+        return;
+    }
+
+    let is_drawn = drawn(pixel);
+    var color: vec4<f32>;
+    if is_drawn {
+        color = textureLoad(half_color, half_texel(pixel), 0);
+    } else {
+        color = fill(pixel, size);
+    }
+    textureStore(resolved, pixel, color);
+
+    // The meshes are premultiplied over a transparent clear
+    let under = textureLoad(background, pixel, 0);
+    let composite = color + under * (1.0 - color.a);
+    let truth = textureLoad(reference, pixel, 0);
+    var shown = truth;
+    switch params.view {
+        case VIEW_RECONSTRUCTED: {
+            shown = composite;
+        }
+        case VIEW_DRAWN: {
+            shown = select(vec4<f32>(0.0, 0.0, 0.0, 1.0), composite, is_drawn);
+        }
+        case VIEW_DIFFERENCE: {
+            shown = vec4<f32>(abs(composite.rgb - truth.rgb) * params.gain, 1.0);
+        }
+        case VIEW_SPLIT: {
+            let center = size.x / 2;
+            if pixel.x == center {
+                shown = vec4<f32>(1.0);
+            } else if pixel.x > center {
+                shown = composite;
+            }
+        }
+        default: {}
+    }
+    textureStore(display, pixel, shown);
+}
@@ -0,0 +1,34 @@
+// Signed circle of confusion from the depth buffer, in pixels of the frame
+// buffer. Negative in front of the focal plane, positive behind it.
+
+struct DofParams {
+    focal_distance: f32,
+    // Blur radius in pixels of something infinitely far away
+    aperture: f32,
+    max_coc: f32,
+    near: f32,
+    far: f32,
+}
+
+@group(0) @binding(0)
+var t_depth: texture_depth_2d;
+@group(0) @binding(1)
+var<uniform> dof: DofParams;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// Inverse of the [0, 1] depth range of a right handed perspective projection
+fn linear_depth(depth: f32) -> f32 {
+    return dof.near * dof.far / (dof.far - depth * (dof.far - dof.near));
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let depth = linear_depth(textureLoad(t_depth, vec2<u32>(position.xy), 0));
+    let coc = dof.aperture * (depth - dof.focal_distance) / depth;
+    return vec4<f32>(clamp(coc, -dof.max_coc, dof.max_coc), 0.0, 0.0, 1.0);
+}
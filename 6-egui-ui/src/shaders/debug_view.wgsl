@@ -0,0 +1,58 @@
+struct Uniforms {
+    resolution: vec2<f32>,
+    srgb_surface: f32,
+    debug_view: u32,
+}
+
+struct Params {
+    // Fragments per pixel at the top of the overdraw scale
+    max_overdraw: f32,
+}
+
+@group(0) @binding(0)
+var t_counts: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> uniforms: Uniforms;
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle covering the screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Black, blue, green, yellow, red, white
+fn heat(t: f32) -> vec3<f32> {
+    let stops = array<vec3<f32>, 6>(
+        vec3<f32>(0.0, 0.0, 0.0),
+        vec3<f32>(0.0, 0.2, 1.0),
+        vec3<f32>(0.0, 0.9, 0.2),
+        vec3<f32>(1.0, 0.9, 0.0),
+        vec3<f32>(1.0, 0.1, 0.0),
+        vec3<f32>(1.0, 1.0, 1.0),
+    );
+    let x = clamp(t, 0.0, 1.0) * 5.0;
+    let i = min(u32(x), 4u);
+    return mix(stops[i], stops[i + 1u], x - f32(i));
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let counts = textureLoad(t_counts, vec2<u32>(position.xy), 0);
+    if counts.r < 0.5 {
+        return vec4<f32>(0.02, 0.02, 0.02, 1.0);
+    }
+    // Overdraw
+    if uniforms.debug_view == 1u {
+        return vec4<f32>(heat(counts.r / params.max_overdraw), 1.0);
+    }
+    // Quad occupancy: green when every lane shades a covered pixel, red when
+    // three out of four are helpers. Brighter where more quads were shaded.
+    let occupancy = counts.r / counts.g;
+    let waste = clamp((1.0 - occupancy) / 0.75, 0.0, 1.0);
+    let color = mix(vec3<f32>(0.1, 0.9, 0.2), vec3<f32>(1.0, 0.1, 0.05), waste);
+    let load = 0.4 + 0.6 * clamp(counts.g / (4.0 * params.max_overdraw), 0.0, 1.0);
+    return vec4<f32>(color * load, 1.0);
+}
@@ -21,6 +21,7 @@ fn vs_main(
 struct Uniforms {
     resolution: vec2<f32>,
     srgb_surface: f32,
+    debug_view: u32,
 }
 ;
 
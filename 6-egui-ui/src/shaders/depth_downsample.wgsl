@@ -0,0 +1,22 @@
+// Halves a depth buffer, keeping the closest of every 2x2 block so thin
+// foreground geometry doesn't vanish from the smaller target.
+
+@group(0) @binding(0)
+var t_source: texture_depth_2d;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @builtin(frag_depth) f32 {
+    let last = vec2<i32>(textureDimensions(t_source)) - 1;
+    let base = vec2<i32>(position.xy) * 2;
+    let a = textureLoad(t_source, min(base, last), 0);
+    let b = textureLoad(t_source, min(base + vec2<i32>(1, 0), last), 0);
+    let c = textureLoad(t_source, min(base + vec2<i32>(0, 1), last), 0);
+    let d = textureLoad(t_source, min(base + vec2<i32>(1, 1), last), 0);
+    return min(min(a, b), min(c, d));
+}
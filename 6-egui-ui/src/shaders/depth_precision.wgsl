@@ -0,0 +1,66 @@
+// Two ground planes a small gap apart, stretching from the camera out to the
+// far plane. The upper one should always win the depth test, wherever the
+// magenta lower one shows through depth precision ran out.
+
+struct Params {
+    view_proj: mat4x4<f32>,
+    far: f32,
+    gap: f32,
+    _padding: vec2<f32>,
+}
+;
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world: vec3<f32>,
+    @location(1) @interpolate(flat) layer: u32,
+}
+;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) layer: u32,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, 0.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, -1.0),
+    );
+    let corner = corners[vertex_index] * params.far;
+    let world = vec3<f32>(corner.x, -f32(layer) * params.gap, corner.y);
+
+    var out: VertexOutput;
+    out.clip_position = params.view_proj * vec4<f32>(world, 1.0);
+    out.world = world;
+    out.layer = layer;
+    return out;
+}
+
+// One hue per power of ten of distance, so the panel's table can be matched
+// against where the artifacts start
+fn decade_color(decade: f32) -> vec3<f32> {
+    let hue = fract(decade * 0.21 + 0.55);
+    let rgb = clamp(abs(fract(hue + vec3<f32>(0.0, 2.0 / 3.0, 1.0 / 3.0)) * 6.0 - 3.0) - 1.0, vec3<f32>(0.0), vec3<f32>(1.0));
+    return mix(vec3<f32>(0.5), rgb, 0.5);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if in.layer == 1u {
+        return vec4<f32>(1.0, 0.0, 1.0, 1.0);
+    }
+
+    let distance = max(length(in.world.xz), 1e-3);
+    let decade = floor(log2(distance) / log2(10.0));
+    // Checkers a tenth of the current decade wide
+    let cell = floor(in.world.xz / pow(10.0, decade - 1.0));
+    let checker = select(0.6, 1.0, (i32(cell.x) + i32(cell.y)) % 2 == 0);
+    return vec4<f32>(decade_color(decade) * checker, 1.0);
+}
@@ -0,0 +1,95 @@
+// A large textured ground plane, mostly seen at grazing angles, to compare
+// texture filtering settings.
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var t_checker: texture_2d<f32>;
+@group(1) @binding(1)
+var s_checker: sampler;
+
+struct Params {
+    lod_bias: f32,
+    lod_min_clamp: f32,
+    lod_max_clamp: f32,
+    // 1 colors pixels by mip level instead of the texture
+    mip_view: u32,
+}
+
+@group(1) @binding(2)
+var<uniform> params: Params;
+
+const HALF_EXTENT: f32 = 200.0;
+const HEIGHT: f32 = -0.45;
+// World units covered by one repeat of the texture
+const TILE: f32 = 2.0;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    // Counter-clockwise when seen from above
+    let corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let xz = corners[vertex_index] * HALF_EXTENT;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(xz.x, HEIGHT, xz.y, 1.0);
+    out.uv = xz / TILE;
+    return out;
+}
+
+// One color per mip level, repeating after eight
+fn level_color(level: u32) -> vec3<f32> {
+    let colors = array<vec3<f32>, 8>(
+        vec3<f32>(1.0, 0.2, 0.2),
+        vec3<f32>(1.0, 0.6, 0.1),
+        vec3<f32>(1.0, 1.0, 0.2),
+        vec3<f32>(0.3, 1.0, 0.3),
+        vec3<f32>(0.2, 0.9, 0.9),
+        vec3<f32>(0.2, 0.4, 1.0),
+        vec3<f32>(0.7, 0.3, 1.0),
+        vec3<f32>(1.0, 0.4, 0.8),
+    );
+    return colors[level % 8u];
+}
+
+// The level the hardware picks without anisotropy: log2 of the longest
+// screen-space texel footprint. Anisotropic filtering samples finer levels
+// along the short axis, so this overestimates where it kicks in.
+fn mip_level(uv: vec2<f32>) -> f32 {
+    let texels = uv * vec2<f32>(textureDimensions(t_checker));
+    let footprint = max(dot(dpdx(texels), dpdx(texels)), dot(dpdy(texels), dpdy(texels)));
+    let levels = f32(textureNumLevels(t_checker) - 1u);
+    let lod = 0.5 * log2(max(footprint, 1e-8)) + params.lod_bias;
+    return clamp(lod, max(params.lod_min_clamp, 0.0), min(params.lod_max_clamp, levels));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleBias(t_checker, s_checker, in.uv, params.lod_bias).rgb;
+    if params.mip_view == 0u {
+        return vec4<f32>(color, 1.0);
+    }
+
+    // Blend between neighbouring level colors like trilinear filtering does,
+    // keeping some of the texture visible for orientation
+    let lod = mip_level(in.uv);
+    let level = u32(floor(lod));
+    let tint = mix(level_color(level), level_color(level + 1u), fract(lod));
+    return vec4<f32>(tint * (0.5 + 0.5 * color), 1.0);
+}
@@ -0,0 +1,72 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+}
+
+struct Object {
+    model: mat4x4<f32>,
+    normal_matrix: mat4x4<f32>,
+    // x: line length, y: normals shown, z: tangents shown
+    params: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var<uniform> object: Object;
+
+// One instance per triangle
+struct TriangleInput {
+    @location(0) position0: vec3<f32>,
+    @location(1) normal0: vec3<f32>,
+    @location(2) position1: vec3<f32>,
+    @location(3) normal1: vec3<f32>,
+    @location(4) position2: vec3<f32>,
+    @location(5) normal2: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+// Vertices 0..6 are the normal lines of the three corners, 6..12 their
+// tangent lines, two vertices per line
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, triangle: TriangleInput) -> VertexOutput {
+    var positions = array<vec3<f32>, 3>(triangle.position0, triangle.position1, triangle.position2);
+    var normals = array<vec3<f32>, 3>(triangle.normal0, triangle.normal1, triangle.normal2);
+    let corner = (index / 2u) % 3u;
+    let is_tangent = index >= 6u;
+
+    var out: VertexOutput;
+    let enabled = select(object.params.y, object.params.z, is_tangent) > 0.5;
+    if !enabled {
+        // Past the far plane, the whole line gets clipped
+        out.clip_position = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+        out.color = vec4<f32>(0.0);
+        return out;
+    }
+
+    let position = (object.model * vec4<f32>(positions[corner], 1.0)).xyz;
+    let normal = normalize((object.normal_matrix * vec4<f32>(normals[corner], 0.0)).xyz);
+    // Meshes have no texture coordinates, the edge to the next corner made
+    // orthogonal to the normal stands in for the tangent
+    let edge = (object.model * vec4<f32>(positions[(corner + 1u) % 3u] - positions[corner], 0.0)).xyz;
+    let tangent = normalize(edge - normal * dot(edge, normal));
+
+    var direction = normal;
+    out.color = vec4<f32>(normal * 0.5 + 0.5, 1.0);
+    if is_tangent {
+        direction = tangent;
+        out.color = vec4<f32>(1.0, 0.35, 0.1, 1.0);
+    }
+    let tip = f32(index % 2u);
+    out.clip_position = camera.view_proj * vec4<f32>(position + direction * object.params.x * tip, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,67 @@
+// Screen space light scattering: blurs the unoccluded sky around the light
+// radially towards its position on screen, so occluders cut shafts into the
+// glow. Written additively, to be upsampled onto the frame buffer.
+
+struct GodRayParams {
+    // Light position in UV, may lie off screen
+    light_uv: vec2<f32>,
+    aspect: f32,
+    // Fades the effect out as the light turns away from the camera
+    strength: f32,
+    // Light color scaled by its intensity
+    color: vec4<f32>,
+    // Fraction of the way to the light the samples span
+    density: f32,
+    decay: f32,
+    weight: f32,
+    exposure: f32,
+    samples: u32,
+}
+
+// At the resolution the rays render at
+@group(0) @binding(0)
+var t_depth: texture_depth_2d;
+@group(0) @binding(1)
+var<uniform> rays: GodRayParams;
+
+// Screen space radius of the glow around the light the rays are cut from
+const GLOW_RADIUS: f32 = 0.35;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// Per pixel offset that breaks the sample banding into noise the upsample
+// smooths out
+fn interleaved_gradient_noise(pixel: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+fn glow(uv: vec2<f32>) -> f32 {
+    let offset = (uv - rays.light_uv) * vec2<f32>(rays.aspect, 1.0);
+    let falloff = max(1.0 - length(offset) / GLOW_RADIUS, 0.0);
+    return falloff * falloff;
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_depth));
+    let last = vec2<i32>(size) - 1;
+    let uv = position.xy / size;
+    let step = (uv - rays.light_uv) * rays.density / f32(rays.samples);
+
+    var coord = uv - step * interleaved_gradient_noise(position.xy);
+    var decay = 1.0;
+    var sum = 0.0;
+    for (var i = 0u; i < rays.samples; i++) {
+        let tap = clamp(vec2<i32>(coord * size), vec2<i32>(0), last);
+        let sky = select(0.0, 1.0, textureLoad(t_depth, tap, 0) >= 1.0);
+        sum += sky * glow(coord) * decay * rays.weight;
+        decay *= rays.decay;
+        coord -= step;
+    }
+    let light = rays.color.rgb * sum * rays.exposure * rays.strength;
+    return vec4<f32>(light, 0.0);
+}
@@ -0,0 +1,73 @@
+// RGB histogram of an image and a gray world white balance using it. Every
+// workgroup counts into shared memory first and merges into the global bins
+// once, so the global atomics see one add per bin and workgroup instead of
+// one per pixel.
+
+struct Balance {
+    // Linear per channel gains, w unused
+    gains: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+// Red, green and blue bins back to back
+@group(0) @binding(1)
+var<storage, read_write> bins: array<atomic<u32>, 768>;
+@group(1) @binding(0)
+var<uniform> balance: Balance;
+@group(1) @binding(1)
+var output: texture_storage_2d<rgba8unorm, write>;
+
+const BINS: u32 = 256u;
+// Matches @workgroup_size
+const INVOCATIONS: u32 = 256u;
+
+var<workgroup> local_bins: array<atomic<u32>, 768>;
+
+@compute @workgroup_size(16, 16, 1)
+fn cs_histogram(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    for (var i = index; i < 3u * BINS; i += INVOCATIONS) {
+        atomicStore(&local_bins[i], 0u);
+    }
+    workgroupBarrier();
+
+    // No early return, every invocation has to reach the barriers
+    if all(id.xy < textureDimensions(source)) {
+        let color = clamp(textureLoad(source, id.xy, 0).rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+        let bin = min(vec3<u32>(color * f32(BINS)), vec3<u32>(BINS - 1u));
+        atomicAdd(&local_bins[bin.r], 1u);
+        atomicAdd(&local_bins[BINS + bin.g], 1u);
+        atomicAdd(&local_bins[2u * BINS + bin.b], 1u);
+    }
+    workgroupBarrier();
+
+    for (var i = index; i < 3u * BINS; i += INVOCATIONS) {
+        let count = atomicLoad(&local_bins[i]);
+        if count > 0u {
+            atomicAdd(&bins[i], count);
+        }
+    }
+}
+
+fn linear_to_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let a = 0.055;
+    return mix(linear * 12.92, pow(linear, vec3<f32>(1.0 / 2.4)) * (1.0 + a) - vec3<f32>(a), step(vec3<f32>(0.0031308), linear));
+}
+fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
+    let a = 0.055;
+    return mix(srgb / 12.92, pow((srgb + vec3<f32>(a)) / (1.0 + a), vec3<f32>(2.4)), step(vec3<f32>(0.04045), srgb));
+}
+
+// Applies the gains in linear space, like the present shader does for the scene
+@compute @workgroup_size(16, 16, 1)
+fn cs_balance(@builtin(global_invocation_id) id: vec3<u32>) {
+    if any(id.xy >= textureDimensions(source)) {
+        return;
+    }
+    let color = textureLoad(source, id.xy, 0);
+    let balanced = linear_to_srgb(srgb_to_linear(color.rgb) * balance.gains.rgb);
+    textureStore(output, id.xy, vec4<f32>(clamp(balanced, vec3<f32>(0.0), vec3<f32>(1.0)), color.a));
+}
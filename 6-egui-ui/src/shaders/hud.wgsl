@@ -0,0 +1,134 @@
+// Screen space quads for HUD elements. Rounded corners and borders come from
+// a rounded box SDF, textures can be stretched or nine-sliced.
+
+struct Params {
+    screen: vec2<f32>,
+    _padding: vec2<f32>,
+}
+;
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+@group(1) @binding(0)
+var hud_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var hud_sampler: sampler;
+
+struct InstanceInput {
+    @location(0) rect: vec4<f32>,
+    @location(1) uv_rect: vec4<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) border_color: vec4<f32>,
+    // Corner radius, border width, nine-slice border in pixels and as a
+    // fraction of the texture region
+    @location(4) shape: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // Pixels from the top left corner of the quad
+    @location(0) local: vec2<f32>,
+    @location(1) @interpolate(flat) size: vec2<f32>,
+    @location(2) @interpolate(flat) uv_rect: vec4<f32>,
+    @location(3) @interpolate(flat) color: vec4<f32>,
+    @location(4) @interpolate(flat) border_color: vec4<f32>,
+    @location(5) @interpolate(flat) shape: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, instance: InstanceInput) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+    let local = corners[vertex_index] * instance.rect.zw;
+    let position = (instance.rect.xy + local) / params.screen;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(position.x * 2.0 - 1.0, 1.0 - position.y * 2.0, 0.0, 1.0);
+    out.local = local;
+    out.size = instance.rect.zw;
+    out.uv_rect = instance.uv_rect;
+    out.color = instance.color;
+    out.border_color = instance.border_color;
+    out.shape = instance.shape;
+    return out;
+}
+
+// Maps a pixel along one axis to 0..1 across the texture region. The
+// borders keep their size, only the middle stretches.
+fn slice_axis(p: f32, size: f32, border: f32, source: f32) -> f32 {
+    if border <= 0.0 {
+        return p / size;
+    }
+    let b = min(border, size * 0.5);
+    if p < b {
+        return p / b * source;
+    }
+    if p > size - b {
+        return 1.0 - (size - p) / b * source;
+    }
+    return mix(source, 1.0 - source, (p - b) / max(size - 2.0 * b, 1e-3));
+}
+
+fn rounded_box(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + radius;
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let half_size = in.size * 0.5;
+    let radius = min(in.shape.x, min(half_size.x, half_size.y));
+    let distance = rounded_box(in.local - half_size, half_size, radius);
+    let aa = max(fwidth(distance), 1e-3);
+
+    let slice = vec2<f32>(
+        slice_axis(in.local.x, in.size.x, in.shape.z, in.shape.w),
+        slice_axis(in.local.y, in.size.y, in.shape.z, in.shape.w),
+    );
+    let uv = mix(in.uv_rect.xy, in.uv_rect.zw, slice);
+    // The slices aren't continuous, so derivatives would pick wild mips at
+    // the seams
+    let fill = textureSampleLevel(hud_texture, hud_sampler, uv, 0.0) * in.color;
+
+    let border = select(0.0, clamp(0.5 + (distance + in.shape.y) / aa, 0.0, 1.0), in.shape.y > 0.0);
+    let color = mix(fill, in.border_color, border);
+    let coverage = clamp(0.5 - distance / aa, 0.0, 1.0);
+    return vec4<f32>(color.rgb, color.a * coverage);
+}
+
+// Straight alpha `top` over `bottom`
+fn over(top: vec4<f32>, bottom: vec4<f32>) -> vec4<f32> {
+    let alpha = top.a + bottom.a * (1.0 - top.a);
+    let rgb = (top.rgb * top.a + bottom.rgb * bottom.a * (1.0 - top.a)) / max(alpha, 1e-4);
+    return vec4<f32>(rgb, alpha);
+}
+
+// Alpha holds 0.5 plus the distance to the edge, positive inside. The shape
+// is outline width, shadow offset in pixels and shadow opacity.
+@fragment
+fn fs_distance_field(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv_per_pixel = (in.uv_rect.zw - in.uv_rect.xy) / in.size;
+    let uv = in.uv_rect.xy + in.local * uv_per_pixel;
+    let field = textureSampleLevel(hud_texture, hud_sampler, uv, 0.0).a;
+    let shadow_uv = clamp(uv - in.shape.yz * uv_per_pixel, in.uv_rect.xy, in.uv_rect.zw);
+    let shadow_field = textureSampleLevel(hud_texture, hud_sampler, shadow_uv, 0.0).a;
+
+    // How much the field changes over one screen pixel, so edges stay a
+    // pixel wide at any size
+    let per_pixel = max(fwidth(field), 1e-4);
+    let outline = in.shape.x * per_pixel;
+    let fill = clamp((field - 0.5) / per_pixel + 0.5, 0.0, 1.0);
+    let outlined = clamp((field - 0.5 + outline) / per_pixel + 0.5, 0.0, 1.0);
+    let shadow = clamp((shadow_field - 0.5 + outline) / per_pixel + 0.5, 0.0, 1.0) * in.shape.w;
+
+    var color = vec4<f32>(0.0, 0.0, 0.0, shadow);
+    color = over(vec4<f32>(in.border_color.rgb, in.border_color.a * outlined), color);
+    return over(vec4<f32>(in.color.rgb, in.color.a * fill), color);
+}
@@ -0,0 +1,62 @@
+struct Inspector {
+    // 1.0 for every channel shown; a single channel is shown as grayscale
+    channels: vec4<f32>,
+    range: vec2<f32>,
+    exposure: f32,
+    slice: u32,
+    viewport: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> inspector: Inspector;
+// Only one of these is bound, depending on the inspected texture
+@group(0) @binding(1)
+var source_2d: texture_2d<f32>;
+@group(0) @binding(2)
+var source_depth: texture_depth_2d;
+@group(0) @binding(3)
+var source_3d: texture_3d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+fn texel_at(position: vec2<f32>, size: vec2<u32>) -> vec2<u32> {
+    let uv = position / inspector.viewport;
+    return min(vec2<u32>(uv * vec2<f32>(size)), size - 1u);
+}
+
+fn display(value: vec4<f32>) -> vec4<f32> {
+    let exposed = vec4<f32>(value.rgb * exp2(inspector.exposure), value.a);
+    let extent = max(inspector.range.y - inspector.range.x, 1e-6);
+    let remapped = clamp((exposed - inspector.range.x) / extent, vec4<f32>(0.0), vec4<f32>(1.0));
+
+    if dot(inspector.channels, vec4<f32>(1.0)) == 1.0 {
+        let gray = dot(remapped, inspector.channels);
+        return vec4<f32>(gray, gray, gray, 1.0);
+    }
+    return vec4<f32>(remapped.rgb * inspector.channels.rgb, 1.0);
+}
+
+@fragment
+fn fs_float(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let texel = texel_at(position.xy, textureDimensions(source_2d));
+    return display(textureLoad(source_2d, texel, 0));
+}
+
+@fragment
+fn fs_depth(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let texel = texel_at(position.xy, textureDimensions(source_depth));
+    let depth = textureLoad(source_depth, texel, 0);
+    return display(vec4<f32>(depth, depth, depth, 1.0));
+}
+
+@fragment
+fn fs_volume(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = textureDimensions(source_3d);
+    let texel = texel_at(position.xy, size.xy);
+    let slice = min(inspector.slice, size.z - 1u);
+    return display(textureLoad(source_3d, vec3<u32>(texel, slice), 0));
+}
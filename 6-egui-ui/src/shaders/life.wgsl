@@ -0,0 +1,53 @@
+struct Params {
+    size: vec2<u32>,
+    seed: u32,
+    _padding: u32,
+}
+;
+
+// The board as of the last generation
+@group(0) @binding(0)
+var board: texture_2d<f32>;
+@group(1) @binding(0)
+var<uniform> params: Params;
+
+#include "noise.wgsl"
+
+const ALIVE: vec4<f32> = vec4<f32>(0.9, 1.0, 0.6, 1.0);
+const DEAD: vec4<f32> = vec4<f32>(0.05, 0.05, 0.1, 1.0);
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+fn alive(cell: vec2<i32>) -> u32 {
+    // The board wraps around at the edges
+    let size = vec2<i32>(params.size);
+    let wrapped = (cell + size) % size;
+    return u32(textureLoad(board, wrapped, 0).g > 0.5);
+}
+
+// A quarter of the cells start out alive
+@fragment
+fn fs_seed(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let cell = vec2<u32>(position.xy);
+    let hash = noise_hash(cell.x, cell.y, 0u, params.seed);
+    return select(DEAD, ALIVE, (hash & 3u) == 0u);
+}
+
+@fragment
+fn fs_step(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let cell = vec2<i32>(position.xy);
+    var neighbours = 0u;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            if x != 0 || y != 0 {
+                neighbours += alive(cell + vec2<i32>(x, y));
+            }
+        }
+    }
+    let lives = neighbours == 3u || (neighbours == 2u && alive(cell) == 1u);
+    return select(DEAD, ALIVE, lives);
+}
@@ -0,0 +1,64 @@
+struct LoadingParams {
+    resolution: vec2<f32>,
+    progress: f32,
+    time: f32,
+    srgb_surface: f32,
+    // Fades the whole screen in, so quick loads don't flash
+    opacity: f32,
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> params: LoadingParams;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle covering the screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Signed distance to a box with rounded corners, negative inside
+fn rounded_box(position: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(position) - half_size + radius;
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+// The colors are linear, a surface that isn't sRGB gets them encoded by hand
+fn encode(linear: vec3<f32>) -> vec3<f32> {
+    if params.srgb_surface > 0.5 {
+        return linear;
+    }
+    let a = 0.055;
+    return mix(linear * 12.92, pow(linear, vec3<f32>(1.0 / 2.4)) * (1.0 + a) - vec3<f32>(a), step(vec3<f32>(0.0031308), linear));
+}
+
+// Dims the last frame of the old scene and draws a bar across the middle,
+// filled up to the progress with a highlight sweeping along the fill
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let center = params.resolution * 0.5;
+    let half_size = vec2<f32>(min(params.resolution.x * 0.3, 320.0), 6.0);
+    let local = position.xy - center;
+    let radius = half_size.y;
+
+    var color = vec3<f32>(0.0);
+    var alpha = 0.6;
+
+    let track = rounded_box(local, half_size, radius);
+    let track_coverage = clamp(0.5 - track, 0.0, 1.0);
+    color = mix(color, vec3<f32>(0.08), track_coverage);
+    alpha = mix(alpha, 0.9, track_coverage);
+
+    let fill_width = half_size.x * clamp(params.progress, 0.0, 1.0);
+    let fill_center = vec2<f32>(fill_width - half_size.x, 0.0);
+    let fill = rounded_box(local - fill_center, vec2<f32>(max(fill_width, radius), half_size.y), radius);
+    let fill_coverage = clamp(0.5 - fill, 0.0, 1.0) * track_coverage;
+    let sweep = fract(params.time * 0.5) * 2.0 * half_size.x - half_size.x;
+    let highlight = exp(-abs(local.x - sweep) * 0.05) * 0.4;
+    let fill_color = vec3<f32>(0.25, 0.55, 1.0) + highlight;
+    color = mix(color, fill_color, fill_coverage);
+    alpha = mix(alpha, 1.0, fill_coverage);
+
+    return vec4<f32>(encode(color), alpha * params.opacity);
+}
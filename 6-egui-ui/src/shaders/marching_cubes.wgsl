@@ -0,0 +1,130 @@
+struct Field {
+    // xyz grid origin, w cell size
+    origin: vec4<f32>,
+    // time, iso level, blob radius, noise amplitude
+    params: vec4<f32>,
+    resolution: u32,
+    capacity: u32,
+    seed: u32,
+}
+
+struct Vertex {
+    position: vec4<f32>,
+    normal: vec4<f32>,
+}
+
+struct DrawIndirect {
+    vertex_count: u32,
+    instance_count: u32,
+    first_vertex: u32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> field: Field;
+// 16 edge indices per case, -1 terminated
+@group(0) @binding(1)
+var<storage, read> triangle_table: array<i32>;
+@group(0) @binding(2)
+var<storage, read_write> vertices: array<Vertex>;
+@group(0) @binding(3)
+var<storage, read_write> triangle_count: atomic<u32>;
+@group(0) @binding(4)
+var<storage, read_write> draw: DrawIndirect;
+// Triangles, dropped triangles
+@group(0) @binding(5)
+var<storage, read_write> counters: array<atomic<u32>>;
+
+// Corners of edge `i` are EDGE_A[i] and EDGE_B[i]; corner `c` sits at (c & 1, (c >> 1) & 1, (c >> 2) & 1)
+const EDGE_A = array<u32, 12>(0u, 0u, 0u, 1u, 1u, 2u, 2u, 3u, 4u, 4u, 5u, 6u);
+const EDGE_B = array<u32, 12>(1u, 2u, 4u, 3u, 5u, 3u, 6u, 7u, 5u, 6u, 7u, 7u);
+
+#include "noise.wgsl"
+
+// Features of the surface noise per world unit
+const NOISE_FREQUENCY: f32 = 1.5;
+
+// Orbiting metaballs roughened by noise; positive inside the surface
+fn density(p: vec3<f32>) -> f32 {
+    let time = field.params.x;
+    let radius = field.params.z;
+    let center = field.origin.xyz + vec3<f32>(f32(field.resolution) * field.origin.w * 0.5);
+    var sum = 0.0;
+    for (var i = 0u; i < 5u; i++) {
+        let phase = f32(i) * 1.2566 + time * (0.4 + 0.15 * f32(i));
+        let offset = vec3<f32>(cos(phase) * 1.8, sin(phase * 1.3) * 1.2, sin(phase) * 1.8);
+        let d = p - (center + offset);
+        sum += radius * radius / max(dot(d, d), 1e-4);
+    }
+    return sum - 1.0 + field.params.w * noise_fbm_simplex(p * NOISE_FREQUENCY, 3u, field.seed);
+}
+
+fn normal_at(p: vec3<f32>) -> vec3<f32> {
+    let e = field.origin.w * 0.5;
+    let gradient = vec3<f32>(
+        density(p + vec3<f32>(e, 0.0, 0.0)) - density(p - vec3<f32>(e, 0.0, 0.0)),
+        density(p + vec3<f32>(0.0, e, 0.0)) - density(p - vec3<f32>(0.0, e, 0.0)),
+        density(p + vec3<f32>(0.0, 0.0, e)) - density(p - vec3<f32>(0.0, 0.0, e)),
+    );
+    // Density grows inwards
+    return -normalize(gradient);
+}
+
+fn corner_position(cell: vec3<u32>, corner: u32) -> vec3<f32> {
+    let offset = vec3<u32>(corner & 1u, (corner >> 1u) & 1u, (corner >> 2u) & 1u);
+    return field.origin.xyz + vec3<f32>(cell + offset) * field.origin.w;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn cs_main(@builtin(global_invocation_id) cell: vec3<u32>) {
+    if any(cell >= vec3<u32>(field.resolution)) {
+        return;
+    }
+    let iso = field.params.y;
+
+    var positions: array<vec3<f32>, 8>;
+    var values: array<f32, 8>;
+    var case_index = 0u;
+    for (var corner = 0u; corner < 8u; corner++) {
+        positions[corner] = corner_position(cell, corner);
+        values[corner] = density(positions[corner]);
+        if values[corner] > iso {
+            case_index |= 1u << corner;
+        }
+    }
+    if case_index == 0u || case_index == 255u {
+        return;
+    }
+
+    let entries = case_index * 16u;
+    var triangles = 0u;
+    while triangles < 5u && triangle_table[entries + triangles * 3u] >= 0 {
+        triangles++;
+    }
+    let first = atomicAdd(&triangle_count, triangles);
+    if first + triangles > field.capacity {
+        return;
+    }
+
+    for (var i = 0u; i < triangles * 3u; i++) {
+        let edge = u32(triangle_table[entries + i]);
+        let a = EDGE_A[edge];
+        let b = EDGE_B[edge];
+        let t = clamp((iso - values[a]) / (values[b] - values[a]), 0.0, 1.0);
+        let position = mix(positions[a], positions[b], t);
+        vertices[first * 3u + i] = Vertex(vec4<f32>(position, 1.0), vec4<f32>(normal_at(position), 0.0));
+    }
+}
+
+// Turns the triangle counter into draw arguments, dropping whatever overflowed the buffer
+@compute @workgroup_size(1)
+fn cs_finalize() {
+    let emitted = atomicLoad(&triangle_count);
+    let triangles = min(emitted, field.capacity);
+    atomicStore(&counters[0], triangles);
+    atomicStore(&counters[1], emitted - triangles);
+    draw.vertex_count = triangles * 3u;
+    draw.instance_count = 1u;
+    draw.first_vertex = 0u;
+    draw.first_instance = 0u;
+}
@@ -0,0 +1,39 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(in.position.xyz, 1.0);
+    out.world_position = in.position.xyz;
+    out.normal = in.normal.xyz;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(in.normal);
+    let view = normalize(camera.eye.xyz - in.world_position);
+    let light = normalize(vec3<f32>(0.4, 1.0, 0.3));
+    let diffuse = max(dot(normal, light), 0.0);
+    let ambient = 0.15 + 0.1 * normal.y;
+    let rim = pow(1.0 - max(dot(normal, view), 0.0), 3.0);
+    let base = vec3<f32>(0.2, 0.75, 0.55);
+    return vec4<f32>(base * (diffuse + ambient) + rim * 0.4, 1.0);
+}
@@ -0,0 +1,249 @@
+#ifdef HALF_LIGHTING
+enable f16;
+// Precision of the accumulated lighting
+alias LightValue = f16;
+#else
+alias LightValue = f32;
+#endif
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+    prev_view_proj: mat4x4<f32>,
+}
+
+struct Object {
+    model: mat4x4<f32>,
+    prev_model: mat4x4<f32>,
+}
+
+struct Material {
+    base_color: vec4<f32>,
+    roughness: f32,
+    // Reflectance at normal incidence, 0 skips the reflection pass
+    reflectivity: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var<storage, read> objects: array<Object>;
+@group(2) @binding(0)
+var<uniform> material: Material;
+
+struct Light {
+    position: vec4<f32>,
+    direction: vec4<f32>,
+    // rgb color, intensity in a
+    color: vec4<f32>,
+    // kind, range, cos(inner angle), cos(outer angle)
+    params: vec4<f32>,
+    shadow_view_proj: mat4x4<f32>,
+    // atlas offset in xy, scale in z, w > 0 when shadowed
+    shadow_rect: vec4<f32>,
+}
+
+struct Lights {
+    count: u32,
+    lights: array<Light>,
+}
+
+@group(3) @binding(0)
+var<storage, read> lights: Lights;
+@group(3) @binding(1)
+var shadow_atlas: texture_depth_2d;
+@group(3) @binding(2)
+var shadow_sampler: sampler_comparison;
+
+struct Cascades {
+    view_proj: array<mat4x4<f32>, 4>,
+    // far view distance of every cascade
+    splits: vec4<f32>,
+    // count, blend band, debug coloring, unused
+    params: vec4<f32>,
+}
+
+@group(3) @binding(3)
+var cascade_maps: texture_depth_2d_array;
+@group(3) @binding(4)
+var<uniform> cascades: Cascades;
+
+const KIND_DIRECTIONAL: u32 = 0u;
+const KIND_SPOT: u32 = 2u;
+const AMBIENT: f32 = 0.15;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_normal: vec3<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) view_depth: f32,
+    @location(3) current_clip: vec4<f32>,
+    @location(4) previous_clip: vec4<f32>,
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    // Screen space motion since the last frame, in UV units
+    @location(1) velocity: vec2<f32>,
+    // Octahedral normal, roughness and reflectivity for the reflection pass
+    @location(2) surface: vec4<f32>,
+}
+
+@vertex
+fn vs_main(in: VertexInput, @builtin(instance_index) instance: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let object = objects[instance];
+    let world_position = object.model * vec4<f32>(in.position, 1.0);
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    // w of a perspective projection is the distance along the view axis
+    out.view_depth = out.clip_position.w;
+    out.current_clip = out.clip_position;
+    out.previous_clip = camera.prev_view_proj * object.prev_model * vec4<f32>(in.position, 1.0);
+    // Uniform scale only, so the model matrix is good enough for normals
+    out.world_normal = (object.model * vec4<f32>(in.normal, 0.0)).xyz;
+    return out;
+}
+
+// Unit normal in two components, exact enough for reflections
+fn octahedral_encode(normal: vec3<f32>) -> vec2<f32> {
+    let p = normal.xy / (abs(normal.x) + abs(normal.y) + abs(normal.z));
+    if normal.z >= 0.0 {
+        return p;
+    }
+    return (1.0 - abs(p.yx)) * select(vec2<f32>(-1.0), vec2<f32>(1.0), p >= vec2<f32>(0.0));
+}
+
+// Fraction of light reaching `world_position`, sampled from the light's atlas tile
+fn spot_shadow(light: Light, world_position: vec3<f32>) -> f32 {
+    if light.shadow_rect.w <= 0.0 {
+        return 1.0;
+    }
+    let clip = light.shadow_view_proj * vec4<f32>(world_position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    if clip.w <= 0.0 || any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    // 3x3 PCF, kept inside the tile so neighbours don't bleed in
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_atlas));
+    let tile_min = light.shadow_rect.xy + texel;
+    let tile_max = light.shadow_rect.xy + light.shadow_rect.z - texel;
+    let atlas_uv = light.shadow_rect.xy + uv * light.shadow_rect.z;
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            let sample_uv = clamp(atlas_uv + offset, tile_min, tile_max);
+            lit += textureSampleCompareLevel(shadow_atlas, shadow_sampler, sample_uv, ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+fn cascade_index(view_depth: f32) -> u32 {
+    let count = u32(cascades.params.x);
+    for (var i = 0u; i < count; i++) {
+        if view_depth < cascades.splits[i] {
+            return i;
+        }
+    }
+    return count;
+}
+
+fn sample_cascade(cascade: u32, world_position: vec3<f32>) -> f32 {
+    let clip = cascades.view_proj[cascade] * vec4<f32>(world_position, 1.0);
+    let uv = clip.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || clip.z > 1.0 {
+        return 1.0;
+    }
+
+    let texel = 1.0 / vec2<f32>(textureDimensions(cascade_maps));
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit += textureSampleCompareLevel(cascade_maps, shadow_sampler, uv + offset, cascade, clip.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+// Fraction of directional light reaching `world_position`, blending into the
+// next cascade over the last part of each one to hide the seams
+fn directional_shadow(world_position: vec3<f32>, view_depth: f32) -> f32 {
+    let count = u32(cascades.params.x);
+    let cascade = cascade_index(view_depth);
+    if cascade >= count {
+        return 1.0;
+    }
+    let shadow = sample_cascade(cascade, world_position);
+
+    let far = cascades.splits[cascade];
+    let band = far * cascades.params.y;
+    if cascade + 1u < count && view_depth > far - band {
+        let t = (view_depth - (far - band)) / band;
+        return mix(shadow, sample_cascade(cascade + 1u, world_position), t);
+    }
+    return shadow;
+}
+
+fn cascade_tint(cascade: u32) -> vec3<f32> {
+    switch cascade {
+        case 0u: { return vec3<f32>(1.0, 0.4, 0.4); }
+        case 1u: { return vec3<f32>(0.4, 1.0, 0.4); }
+        case 2u: { return vec3<f32>(0.4, 0.4, 1.0); }
+        case 3u: { return vec3<f32>(1.0, 1.0, 0.4); }
+        default: { return vec3<f32>(1.0); }
+    }
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let normal = normalize(in.world_normal);
+    var lighting = vec3<LightValue>(LightValue(AMBIENT));
+    for (var i = 0u; i < lights.count; i++) {
+        let light = lights.lights[i];
+        let kind = u32(light.params.x);
+        var light_dir: vec3<f32>;
+        var attenuation = 1.0;
+        if kind == KIND_DIRECTIONAL {
+            light_dir = -light.direction.xyz;
+            if light.shadow_rect.w > 0.0 {
+                attenuation = directional_shadow(in.world_position, in.view_depth);
+            }
+        } else {
+            let to_light = light.position.xyz - in.world_position;
+            let distance = length(to_light);
+            light_dir = to_light / max(distance, 0.0001);
+            let falloff = clamp(1.0 - distance / light.params.y, 0.0, 1.0);
+            attenuation = falloff * falloff;
+            if kind == KIND_SPOT {
+                let cos_angle = dot(-light_dir, light.direction.xyz);
+                attenuation *= smoothstep(light.params.w, light.params.z, cos_angle);
+                attenuation *= spot_shadow(light, in.world_position);
+            }
+        }
+        let n_dot_l = max(dot(normal, light_dir), 0.0);
+        lighting += vec3<LightValue>(light.color.rgb * light.color.a * attenuation * n_dot_l);
+    }
+    var color = material.base_color.rgb * vec3<f32>(lighting);
+    if cascades.params.z > 0.0 {
+        color *= cascade_tint(cascade_index(in.view_depth));
+    }
+    // Divided per fragment, interpolating NDC directly would be wrong
+    let current = in.current_clip.xy / in.current_clip.w;
+    let previous = in.previous_clip.xy / in.previous_clip.w;
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(color, material.base_color.a);
+    out.velocity = (current - previous) * vec2<f32>(0.5, -0.5);
+    out.surface = vec4<f32>(octahedral_encode(normal), material.roughness, material.reflectivity);
+    return out;
+}
@@ -0,0 +1,167 @@
+// Lattice noise shared by shaders through `#include "noise.wgsl"`. Mirrors
+// src/noise.rs operation for operation, keep the two in sync.
+//
+// `period` makes the lattice wrap every `period` cells so the noise tiles, 0
+// disables wrapping.
+
+// Integer hash of a lattice point, stable across CPU and GPU
+fn noise_hash(x: u32, y: u32, z: u32, seed: u32) -> u32 {
+    var h = (x * 0x8da6b343u) ^ (y * 0xd8163841u) ^ (z * 0xcb1ab31fu) ^ seed;
+    h = (h ^ (h >> 13u)) * 0x5bd1e995u;
+    return h ^ (h >> 15u);
+}
+
+fn noise_wrap(cell: f32, offset: i32, period: u32) -> u32 {
+    let c = i32(cell) + offset;
+    if period == 0u {
+        return bitcast<u32>(c);
+    }
+    let p = i32(period);
+    return u32(((c % p) + p) % p);
+}
+
+fn noise_lattice(cell: vec3<f32>, offset: vec3<i32>, period: u32, seed: u32) -> u32 {
+    return noise_hash(
+        noise_wrap(cell.x, offset.x, period),
+        noise_wrap(cell.y, offset.y, period),
+        noise_wrap(cell.z, offset.z, period),
+        seed,
+    );
+}
+
+// Corner `i` sits at (i & 1, (i >> 1) & 1, (i >> 2) & 1)
+fn noise_corner(i: u32) -> vec3<i32> {
+    return vec3<i32>(i32(i & 1u), i32((i >> 1u) & 1u), i32((i >> 2u) & 1u));
+}
+
+fn noise_trilinear(corners: array<f32, 8>, t: vec3<f32>) -> f32 {
+    let x00 = mix(corners[0], corners[1], t.x);
+    let x10 = mix(corners[2], corners[3], t.x);
+    let x01 = mix(corners[4], corners[5], t.x);
+    let x11 = mix(corners[6], corners[7], t.x);
+    return mix(mix(x00, x10, t.y), mix(x01, x11, t.y), t.z);
+}
+
+// Smoothly interpolated random values in [0, 1]
+fn noise_value(p: vec3<f32>, period: u32, seed: u32) -> f32 {
+    let cell = floor(p);
+    let f = p - cell;
+    let t = f * f * (vec3<f32>(3.0) - 2.0 * f);
+    var corners: array<f32, 8>;
+    for (var i = 0u; i < 8u; i++) {
+        corners[i] = f32(noise_lattice(cell, noise_corner(i), period, seed) & 0xffffu) / 65535.0;
+    }
+    return noise_trilinear(corners, t);
+}
+
+// One of the 12 cube edge directions, dotted with `d`
+fn noise_gradient(hash: u32, d: vec3<f32>) -> f32 {
+    let h = hash & 15u;
+    let u = select(d.y, d.x, h < 8u);
+    var v = d.z;
+    if h < 4u {
+        v = d.y;
+    } else if h == 12u || h == 14u {
+        v = d.x;
+    }
+    return select(-u, u, (h & 1u) == 0u) + select(-v, v, (h & 2u) == 0u);
+}
+
+// Improved Perlin noise, roughly in [-1, 1]
+fn noise_perlin(p: vec3<f32>, period: u32, seed: u32) -> f32 {
+    let cell = floor(p);
+    let f = p - cell;
+    let t = f * f * f * (f * (f * 6.0 - vec3<f32>(15.0)) + vec3<f32>(10.0));
+    var corners: array<f32, 8>;
+    for (var i = 0u; i < 8u; i++) {
+        let offset = noise_corner(i);
+        corners[i] = noise_gradient(noise_lattice(cell, offset, period, seed), f - vec3<f32>(offset));
+    }
+    return noise_trilinear(corners, t);
+}
+
+const NOISE_SKEW: f32 = 1.0 / 3.0;
+const NOISE_UNSKEW: f32 = 1.0 / 6.0;
+
+fn noise_simplex_corner(cell: vec3<f32>, offset: vec3<i32>, x: vec3<f32>, seed: u32) -> f32 {
+    var falloff = 0.6 - dot(x, x);
+    if falloff < 0.0 {
+        return 0.0;
+    }
+    falloff = falloff * falloff;
+    return falloff * falloff * noise_gradient(noise_lattice(cell, offset, 0u, seed), x);
+}
+
+// 3D simplex noise, roughly in [-1, 1]. Doesn't tile
+fn noise_simplex(p: vec3<f32>, seed: u32) -> f32 {
+    let s = (p.x + p.y + p.z) * NOISE_SKEW;
+    let cell = floor(p + vec3<f32>(s));
+    let t = (cell.x + cell.y + cell.z) * NOISE_UNSKEW;
+    let x0 = p - (cell - vec3<f32>(t));
+
+    // Which of the six simplices of the skewed cube contains the point
+    var i1: vec3<i32>;
+    var i2: vec3<i32>;
+    if x0.x >= x0.y {
+        if x0.y >= x0.z {
+            i1 = vec3<i32>(1, 0, 0);
+            i2 = vec3<i32>(1, 1, 0);
+        } else if x0.x >= x0.z {
+            i1 = vec3<i32>(1, 0, 0);
+            i2 = vec3<i32>(1, 0, 1);
+        } else {
+            i1 = vec3<i32>(0, 0, 1);
+            i2 = vec3<i32>(1, 0, 1);
+        }
+    } else if x0.y < x0.z {
+        i1 = vec3<i32>(0, 0, 1);
+        i2 = vec3<i32>(0, 1, 1);
+    } else if x0.x < x0.z {
+        i1 = vec3<i32>(0, 1, 0);
+        i2 = vec3<i32>(0, 1, 1);
+    } else {
+        i1 = vec3<i32>(0, 1, 0);
+        i2 = vec3<i32>(1, 1, 0);
+    }
+
+    let x1 = x0 - vec3<f32>(i1) + vec3<f32>(NOISE_UNSKEW);
+    let x2 = x0 - vec3<f32>(i2) + vec3<f32>(2.0 * NOISE_UNSKEW);
+    let x3 = x0 - vec3<f32>(1.0) + vec3<f32>(3.0 * NOISE_UNSKEW);
+    return 32.0 * (noise_simplex_corner(cell, vec3<i32>(0), x0, seed)
+        + noise_simplex_corner(cell, i1, x1, seed)
+        + noise_simplex_corner(cell, i2, x2, seed)
+        + noise_simplex_corner(cell, vec3<i32>(1), x3, seed));
+}
+
+// Octaves of value noise in [0, 1]. The period doubles with the frequency,
+// so it still tiles
+fn noise_fbm_value(p: vec3<f32>, octaves: u32, period: u32, seed: u32) -> f32 {
+    var sum = 0.0;
+    var amplitude = 0.5;
+    var total = 0.0;
+    var frequency = 1.0;
+    var wrap = period;
+    for (var i = 0u; i < octaves; i++) {
+        sum += noise_value(p * frequency, wrap, seed) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+        wrap *= 2u;
+    }
+    return sum / total;
+}
+
+// Octaves of simplex noise, roughly in [-1, 1]
+fn noise_fbm_simplex(p: vec3<f32>, octaves: u32, seed: u32) -> f32 {
+    var sum = 0.0;
+    var amplitude = 0.5;
+    var total = 0.0;
+    var frequency = 1.0;
+    for (var i = 0u; i < octaves; i++) {
+        sum += noise_simplex(p * frequency, seed) * amplitude;
+        total += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    return sum / total;
+}
@@ -0,0 +1,79 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+}
+
+struct Object {
+    model: mat4x4<f32>,
+    // xy: size of the frame buffer in pixels
+    resolution: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var<uniform> object: Object;
+
+// One instance per triangle
+struct TriangleInput {
+    @location(0) position0: vec3<f32>,
+    @location(1) normal0: vec3<f32>,
+    @location(2) position1: vec3<f32>,
+    @location(3) normal1: vec3<f32>,
+    @location(4) position2: vec3<f32>,
+    @location(5) normal2: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // The whole triangle, so each fragment can tell how much of its quad it covers
+    @location(0) @interpolate(flat) corner0: vec4<f32>,
+    @location(1) @interpolate(flat) corner1: vec4<f32>,
+    @location(2) @interpolate(flat) corner2: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, triangle: TriangleInput) -> VertexOutput {
+    let view_model = camera.view_proj * object.model;
+    var out: VertexOutput;
+    out.corner0 = view_model * vec4<f32>(triangle.position0, 1.0);
+    out.corner1 = view_model * vec4<f32>(triangle.position1, 1.0);
+    out.corner2 = view_model * vec4<f32>(triangle.position2, 1.0);
+    var corners = array<vec4<f32>, 3>(out.corner0, out.corner1, out.corner2);
+    out.clip_position = corners[index];
+    return out;
+}
+
+fn to_pixels(clip: vec4<f32>) -> vec2<f32> {
+    let ndc = clip.xy / clip.w;
+    return vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5) * object.resolution.xy;
+}
+
+fn edge(a: vec2<f32>, b: vec2<f32>, p: vec2<f32>) -> f32 {
+    return (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x);
+}
+
+// Red counts the fragments of a pixel, green the lanes their quads kept busy.
+// Every quad a triangle touches runs all four lanes, the uncovered ones as
+// helpers, so a fragment alone in its quad costs four.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var covered = 4.0;
+    // Corners behind the eye have no pixel position, those quads count as full
+    if min(min(in.corner0.w, in.corner1.w), in.corner2.w) > 0.0 {
+        let a = to_pixels(in.corner0);
+        let b = to_pixels(in.corner1);
+        let c = to_pixels(in.corner2);
+        let winding = sign(edge(a, b, c));
+        let quad = floor(in.clip_position.xy * 0.5) * 2.0;
+        covered = 0.0;
+        for (var i = 0u; i < 4u; i++) {
+            let p = quad + vec2<f32>(f32(i & 1u), f32(i >> 1u)) + 0.5;
+            let inside = edge(b, c, p) * winding >= 0.0 && edge(c, a, p) * winding >= 0.0 && edge(a, b, p) * winding >= 0.0;
+            covered += select(0.0, 1.0, inside);
+        }
+        // This pixel was rasterized, whatever the tie-breaking above says
+        covered = max(covered, 1.0);
+    }
+    return vec4<f32>(1.0, 4.0 / covered, 0.0, 0.0);
+}
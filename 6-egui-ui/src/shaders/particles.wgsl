@@ -0,0 +1,128 @@
+struct Particle {
+    // xyz position, w age in seconds
+    position: vec4<f32>,
+    // xyz velocity, w lifetime in seconds
+    velocity: vec4<f32>,
+}
+
+struct Particles {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+    // xyz position, w cone spread
+    emitter: vec4<f32>,
+    // delta, time, gravity, restitution
+    motion: vec4<f32>,
+    // lifetime, speed, size, collision thickness
+    shape: vec4<f32>,
+    count: u32,
+    collide: u32,
+    // Drawn from the CPU side generator every frame
+    seed: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> particles: Particles;
+@group(0) @binding(1)
+var<storage, read_write> state: array<Particle>;
+@group(0) @binding(2)
+var scene_depth: texture_depth_2d;
+// Respawned, collisions
+@group(0) @binding(3)
+var<storage, read_write> counters: array<atomic<u32>>;
+
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(seed: ptr<function, u32>) -> f32 {
+    *seed = hash(*seed);
+    return f32(*seed) / 4294967295.0;
+}
+
+fn spawn(index: u32) -> Particle {
+    var seed = hash(index ^ particles.seed);
+    let spread = particles.emitter.w;
+    let direction = normalize(vec3<f32>(
+        (random(&seed) - 0.5) * spread,
+        1.0,
+        (random(&seed) - 0.5) * spread,
+    ));
+    let speed = particles.shape.y * (0.75 + 0.5 * random(&seed));
+    let lifetime = particles.shape.x * (0.5 + random(&seed));
+
+    var particle: Particle;
+    particle.position = vec4<f32>(particles.emitter.xyz, 0.0);
+    particle.velocity = vec4<f32>(direction * speed, lifetime);
+    return particle;
+}
+
+// View-space position of the scene surface stored in a depth texel
+fn view_position(texel: vec2<u32>, size: vec2<u32>) -> vec3<f32> {
+    let depth = textureLoad(scene_depth, texel, 0);
+    let uv = (vec2<f32>(texel) + 0.5) / vec2<f32>(size);
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    let position = particles.inv_proj * vec4<f32>(ndc, depth, 1.0);
+    return position.xyz / position.w;
+}
+
+// Surface normal from the neighbouring texels, facing the camera
+fn view_normal(texel: vec2<u32>, size: vec2<u32>) -> vec3<f32> {
+    let center = clamp(texel, vec2<u32>(1u), size - 2u);
+    let position = view_position(center, size);
+    let dx = view_position(center + vec2<u32>(1u, 0u), size) - position;
+    let dy = view_position(center + vec2<u32>(0u, 1u), size) - position;
+    let normal = normalize(cross(dy, dx));
+    return select(normal, -normal, dot(normal, position) > 0.0);
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= particles.count {
+        return;
+    }
+    var particle = state[index];
+    let delta = particles.motion.x;
+    let age = particle.position.w + delta;
+    if age >= particle.velocity.w {
+        atomicAdd(&counters[0], 1u);
+        state[index] = spawn(index);
+        return;
+    }
+
+    let previous = particle.position.xyz;
+    var velocity = particle.velocity.xyz + vec3<f32>(0.0, -particles.motion.z, 0.0) * delta;
+    var position = previous + velocity * delta;
+
+    if particles.collide != 0u {
+        let view = (particles.view * vec4<f32>(position, 1.0)).xyz;
+        let clip = particles.proj * vec4<f32>(view, 1.0);
+        let ndc = clip.xyz / clip.w;
+        if clip.w > 0.0 && all(abs(ndc.xy) <= vec2<f32>(1.0)) {
+            let size = textureDimensions(scene_depth);
+            let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+            let texel = min(vec2<u32>(uv * vec2<f32>(size)), size - 1u);
+            let surface = view_position(texel, size);
+            // The camera looks down -z, so a particle behind the surface has the smaller z.
+            // Beyond the thickness it is assumed to be passing behind the object instead.
+            let penetration = surface.z - view.z;
+            if penetration > 0.0 && penetration < particles.shape.w {
+                let normal = normalize((particles.inv_view * vec4<f32>(view_normal(texel, size), 0.0)).xyz);
+                let approach = dot(velocity, normal);
+                atomicAdd(&counters[1], 1u);
+                if approach < 0.0 {
+                    velocity -= (1.0 + particles.motion.w) * approach * normal;
+                }
+                position = previous;
+            }
+        }
+    }
+
+    particle.position = vec4<f32>(position, age);
+    particle.velocity = vec4<f32>(velocity, particle.velocity.w);
+    state[index] = particle;
+}
@@ -0,0 +1,63 @@
+struct Particle {
+    position: vec4<f32>,
+    velocity: vec4<f32>,
+}
+
+struct Particles {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+    emitter: vec4<f32>,
+    motion: vec4<f32>,
+    // lifetime, speed, size, collision thickness
+    shape: vec4<f32>,
+    count: u32,
+    collide: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> particles: Particles;
+@group(0) @binding(1)
+var<storage, read> state: array<Particle>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) fade: f32,
+}
+
+// Camera-facing quad per instance
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex: u32,
+    @builtin(instance_index) instance: u32,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let particle = state[instance];
+    let corner = corners[vertex];
+    let center = particles.view * vec4<f32>(particle.position.xyz, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = particles.proj * (center + vec4<f32>(corner * particles.shape.z, 0.0, 0.0));
+    out.corner = corner;
+    out.fade = 1.0 - clamp(particle.position.w / max(particle.velocity.w, 1e-3), 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let falloff = 1.0 - dot(in.corner, in.corner);
+    if falloff <= 0.0 {
+        discard;
+    }
+    let intensity = falloff * in.fade;
+    return vec4<f32>(vec3<f32>(2.0, 0.9, 0.3) * intensity, intensity);
+}
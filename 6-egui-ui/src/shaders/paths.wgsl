@@ -0,0 +1,55 @@
+// Vector paths in screen space. Fills arrive as plain triangles, strokes as
+// one quad per segment whose coverage is the distance to the segment.
+
+struct Params {
+    screen: vec2<f32>,
+    _padding: vec2<f32>,
+}
+;
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) segment: vec4<f32>,
+    @location(3) half_width: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) segment: vec4<f32>,
+    @location(3) @interpolate(flat) half_width: f32,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    let ndc = in.position / params.screen;
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc.x * 2.0 - 1.0, 1.0 - ndc.y * 2.0, 0.0, 1.0);
+    out.position = in.position;
+    out.color = in.color;
+    out.segment = in.segment;
+    out.half_width = in.half_width;
+    return out;
+}
+
+fn segment_distance(p: vec2<f32>, a: vec2<f32>, b: vec2<f32>) -> f32 {
+    let ab = b - a;
+    let t = clamp(dot(p - a, ab) / max(dot(ab, ab), 1e-6), 0.0, 1.0);
+    return length(p - a - ab * t);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if in.half_width <= 0.0 {
+        return in.color;
+    }
+    // Positions are in pixels, so a one unit ramp is a one pixel edge
+    let distance = segment_distance(in.position, in.segment.xy, in.segment.zw) - in.half_width;
+    let coverage = clamp(0.5 - distance, 0.0, 1.0);
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -1,6 +1,6 @@
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-
+    @location(0) tex_coord: vec2<f32>,
 }
 ;
 
@@ -11,6 +11,8 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
     var out: VertexOutput;
     let pos = vertices[vertex_index];
     out.clip_position = vec4<f32>(pos, 0.0, 1.0);
+    // Relative to the viewport, so integer scaling can place the quad anywhere
+    out.tex_coord = vec2<f32>(pos.x * 0.5 + 0.5, 0.5 - pos.y * 0.5);
     return out;
 }
 
@@ -18,6 +20,7 @@ fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
 struct Uniforms {
     resolution: vec2<f32>,
     srgb_surface: f32,
+    debug_view: u32,
 }
 ;
 
@@ -28,6 +31,42 @@ var s_diffuse: sampler;
 @group(0) @binding(2)
 var<uniform> uniforms: Uniforms;
 
+struct GradingParams {
+    intensity: f32,
+}
+;
+
+@group(1) @binding(0)
+var t_lut: texture_3d<f32>;
+@group(1) @binding(1)
+var s_lut: sampler;
+@group(1) @binding(2)
+var<uniform> grading: GradingParams;
+
+struct PostParams {
+    vignette_intensity: f32,
+    vignette_radius: f32,
+    vignette_softness: f32,
+    aberration_strength: f32,
+    grain_intensity: f32,
+    grain_size: f32,
+    time: f32,
+    shutter: f32,
+    motion_blur_samples: u32,
+    max_coc: f32,
+    bokeh_samples: u32,
+    // Linear per channel gains, w unused
+    white_balance: vec4<f32>,
+}
+;
+
+@group(2) @binding(0)
+var<uniform> post: PostParams;
+@group(2) @binding(1)
+var t_velocity: texture_2d<f32>;
+@group(2) @binding(2)
+var t_coc: texture_2d<f32>;
+
 // References
 // https://github.com/gfx-rs/wgpu/issues/2326#issuecomment-1002301171
 
@@ -40,10 +79,126 @@ fn srgb_to_linear(srgb: vec3<f32>) -> vec3<f32> {
     return mix(srgb / 12.92, pow((srgb + vec3<f32>(a)) / (1.0 + a), vec3<f32>(2.4)), step(vec3<f32>(0.04045), srgb));
 }
 
+// Looks the display color up in the LUT. Coordinates are shrunk by half a
+// texel at both ends so 0 and 1 land on the outermost texel centers.
+fn grade(color: vec3<f32>) -> vec3<f32> {
+    let size = f32(textureDimensions(t_lut).x);
+    let uvw = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)) * ((size - 1.0) / size) + 0.5 / size;
+    let graded = textureSampleLevel(t_lut, s_lut, uvw, 0.0).rgb;
+    return mix(color, graded, grading.intensity);
+}
+
+// Integer hash, stable across GPUs unlike sin based ones
+fn hash(p: vec3<u32>) -> f32 {
+    var h = p.x * 73856093u ^ p.y * 19349663u ^ p.z * 83492791u;
+    h = (h ^ (h >> 16u)) * 0x45d9f3bu;
+    h = (h ^ (h >> 16u)) * 0x45d9f3bu;
+    h = h ^ (h >> 16u);
+    return f32(h) / 4294967295.0;
+}
+
+fn sample_scene(uv: vec2<f32>) -> vec4<f32> {
+    var color = textureSampleLevel(t_diffuse, s_diffuse, uv, 0.0);
+#ifdef CHROMATIC_ABERRATION
+    // Red and blue drift apart towards the edges, like a cheap lens
+    let offset = (uv - vec2<f32>(0.5)) * post.aberration_strength * 2.0;
+    color.r = textureSampleLevel(t_diffuse, s_diffuse, uv + offset, 0.0).r;
+    color.b = textureSampleLevel(t_diffuse, s_diffuse, uv - offset, 0.0).b;
+#endif
+    return color;
+}
+
+fn coc_at(uv: vec2<f32>) -> f32 {
+    let size = textureDimensions(t_coc);
+    let texel = min(vec2<u32>(clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0)) * vec2<f32>(size)), size - vec2<u32>(1u));
+    return textureLoad(t_coc, texel, 0).r;
+}
+
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+// Scatter as gather: every tap on a spiral covering the largest blur radius
+// counts if its own circle of confusion reaches this pixel. Taps behind the
+// pixel are limited to its CoC, so a blurry background can't bleed over a
+// sharp foreground.
+fn bokeh(uv: vec2<f32>) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_coc));
+    let center_coc = coc_at(uv);
+    var color = sample_scene(uv);
+    var weight = 1.0;
+    let samples = max(post.bokeh_samples, 1u);
+    for (var i = 0u; i < samples; i++) {
+        let radius = sqrt((f32(i) + 0.5) / f32(samples)) * post.max_coc;
+        let theta = f32(i) * GOLDEN_ANGLE;
+        let sample_uv = uv + vec2<f32>(cos(theta), sin(theta)) * radius / size;
+        let sample_coc = coc_at(sample_uv);
+        var reach = abs(sample_coc);
+        if sample_coc > center_coc {
+            reach = min(reach, abs(center_coc));
+        }
+        let contribution = smoothstep(radius - 1.0, radius + 1.0, reach);
+        color += sample_scene(sample_uv) * contribution;
+        weight += contribution;
+    }
+    return color / weight;
+}
+
+// What a single tap of the scene sees, with depth of field when enabled
+fn scene_color(uv: vec2<f32>) -> vec4<f32> {
+#ifdef DEPTH_OF_FIELD
+    return bokeh(uv);
+#else
+    return sample_scene(uv);
+#endif
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let tex_coord = in.clip_position.xy / uniforms.resolution;
-    let color = textureSample(t_diffuse, s_diffuse, tex_coord);
+    if uniforms.debug_view != 0u {
+        // Debug views are false color, effects and grading would skew them
+        let debug = textureSampleLevel(t_diffuse, s_diffuse, in.tex_coord, 0.0).rgb;
+        return vec4<f32>(srgb_to_linear(debug), 1.0);
+    }
+#ifdef MOTION_BLUR
+    // Averages taps along this pixel's motion, centered on the pixel so the
+    // smear covers where the object was and where it's heading
+    let size = textureDimensions(t_velocity);
+    let texel = min(vec2<u32>(in.tex_coord * vec2<f32>(size)), size - vec2<u32>(1u));
+    let velocity = textureLoad(t_velocity, texel, 0).xy * post.shutter;
+    let samples = max(post.motion_blur_samples, 2u);
+    var color = vec4<f32>(0.0);
+    for (var i = 0u; i < samples; i++) {
+        let t = f32(i) / f32(samples - 1u) - 0.5;
+        // Multiplies with the bokeh taps when both are on, fine for a playground
+        color += scene_color(in.tex_coord + velocity * t);
+    }
+    color /= f32(samples);
+#else
+    var color = scene_color(in.tex_coord);
+#endif
+#ifdef WHITE_BALANCE
+    color = vec4<f32>(linear_to_srgb(srgb_to_linear(color.rgb) * post.white_balance.rgb), color.a);
+#endif
+    var graded = grade(color.rgb);
+#ifdef VIGNETTE
+    // Round on screen, so the distance is corrected for the aspect ratio
+    let aspect = uniforms.resolution.x / uniforms.resolution.y;
+    let centered = (in.tex_coord - vec2<f32>(0.5)) * vec2<f32>(aspect, 1.0);
+    let dist = length(centered) / length(vec2<f32>(aspect, 1.0) * 0.5);
+    let falloff = smoothstep(post.vignette_radius, post.vignette_radius + post.vignette_softness, dist);
+    graded *= 1.0 - falloff * post.vignette_intensity;
+#endif
+#ifdef FILM_GRAIN
+    let cell = vec2<u32>(in.clip_position.xy / post.grain_size);
+    let frame = u32(post.time * 24.0);
+    let noise = hash(vec3<u32>(cell, frame)) - 0.5;
+    graded = max(graded + vec3<f32>(noise * post.grain_intensity), vec3<f32>(0.0));
+#endif
+
+#ifdef COC_DEBUG
+    // Red in front of the focal plane, blue behind, black in focus
+    let coc = coc_at(in.tex_coord) / post.max_coc;
+    graded = vec3<f32>(max(-coc, 0.0), 0.0, max(coc, 0.0));
+#endif
 
-    return vec4<f32>(srgb_to_linear(color.rgb), color.a);
+    return vec4<f32>(srgb_to_linear(graded), color.a);
 }
@@ -0,0 +1,27 @@
+struct Params {
+    time: f32,
+    seed: u32,
+    size: vec2<u32>,
+}
+;
+
+@group(0) @binding(0)
+var output: texture_storage_2d<rgba8unorm, write>;
+@group(0) @binding(1)
+var<uniform> params: Params;
+
+#include "noise.wgsl"
+
+@compute @workgroup_size(8, 8, 1)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.size.x || id.y >= params.size.y {
+        return;
+    }
+
+    let uv = vec2<f32>(id.xy) / vec2<f32>(params.size);
+    let t = params.time;
+    // Time scrolls through the third dimension so the pattern morphs in place
+    let v = noise_fbm_simplex(vec3<f32>(uv * 6.0, t * 0.2), 4u, params.seed) * 3.0;
+    let color = 0.5 + 0.5 * cos(vec3<f32>(0.0, 2.0, 4.0) + v + t);
+    textureStore(output, vec2<i32>(id.xy), vec4<f32>(color, 1.0));
+}
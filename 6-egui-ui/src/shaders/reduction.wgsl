@@ -0,0 +1,49 @@
+struct Params {
+    count: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read> input: array<u32>;
+@group(0) @binding(1)
+var<storage, read_write> total: atomic<u32>;
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+const WORKGROUP_SIZE: u32 = 256u;
+
+var<workgroup> partials: array<u32, WORKGROUP_SIZE>;
+
+// Every invocation adds its element straight into the global total, so all of
+// them contend for the same atomic
+@compute @workgroup_size(256)
+fn cs_global(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x < params.count {
+        atomicAdd(&total, input[id.x]);
+    }
+}
+
+// Tree reduction in workgroup memory, halving the active invocations every
+// step; only one global atomic per workgroup
+@compute @workgroup_size(256)
+fn cs_shared(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    var value = 0u;
+    if id.x < params.count {
+        value = input[id.x];
+    }
+    partials[local] = value;
+    workgroupBarrier();
+
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride >>= 1u) {
+        if local < stride {
+            partials[local] += partials[local + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if local == 0u {
+        atomicAdd(&total, partials[0]);
+    }
+}
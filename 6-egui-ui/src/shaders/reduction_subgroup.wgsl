@@ -0,0 +1,45 @@
+struct Params {
+    count: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read> input: array<u32>;
+@group(0) @binding(1)
+var<storage, read_write> total: atomic<u32>;
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+const WORKGROUP_SIZE: u32 = 256u;
+// Subgroups are at least 4 wide
+const MAX_SUBGROUPS: u32 = 64u;
+
+var<workgroup> partials: array<u32, MAX_SUBGROUPS>;
+
+// Each subgroup reduces in registers, then one invocation sums the per-subgroup
+// results. Assumes subgroups are laid out along the local index, which holds
+// for 1D workgroups on every backend wgpu targets.
+@compute @workgroup_size(256)
+fn cs_subgroup(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+    @builtin(subgroup_invocation_id) lane: u32,
+    @builtin(subgroup_size) size: u32,
+) {
+    var value = 0u;
+    if id.x < params.count {
+        value = input[id.x];
+    }
+    let sum = subgroupAdd(value);
+    if lane == 0u {
+        partials[local / size] = sum;
+    }
+    workgroupBarrier();
+
+    if local == 0u {
+        var workgroup_sum = 0u;
+        for (var i = 0u; i < WORKGROUP_SIZE / size; i++) {
+            workgroup_sum += partials[i];
+        }
+        atomicAdd(&total, workgroup_sum);
+    }
+}
@@ -29,6 +29,9 @@ fn vs_main(
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
+// White unless textures are tinted by their resident mip
+@group(0) @binding(2)
+var<uniform> tint: vec4<f32>;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
@@ -37,7 +40,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let tex_color = textureSample(t_diffuse, s_diffuse, tex_coords);
 
     // Combine color and texture
-    let final_color = color * tex_color.rgb;
+    let final_color = color * tex_color.rgb * tint.rgb;
 
     // Apply sRGB conversion formula correctly
     let rgb_color = (final_color + 0.055) / 1.055;
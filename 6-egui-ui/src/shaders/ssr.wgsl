@@ -0,0 +1,157 @@
+// Screen space reflections, blended over the lit frame buffer. Rays are
+// marched in world space and compared against the depth buffer; misses and
+// rough surfaces fall back to the environment map.
+
+struct SsrParams {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+    max_steps: u32,
+    // World space length of the ray
+    max_distance: f32,
+    // How far behind the depth buffer a sample still counts as a hit
+    thickness: f32,
+    // Surfaces rougher than this only get the environment
+    max_roughness: f32,
+    // Fraction of the screen over which hits fade out towards the edges
+    edge_fade: f32,
+    intensity: f32,
+    near: f32,
+    far: f32,
+}
+
+@group(0) @binding(0)
+var t_depth: texture_depth_2d;
+// Octahedral normal in xy, roughness in z, reflectivity in w
+@group(0) @binding(1)
+var t_surface: texture_2d<f32>;
+@group(0) @binding(2)
+var t_scene: texture_2d<f32>;
+@group(0) @binding(3)
+var t_environment: texture_2d<f32>;
+@group(0) @binding(4)
+var s_scene: sampler;
+// Wraps horizontally
+@group(0) @binding(5)
+var s_environment: sampler;
+@group(0) @binding(6)
+var<uniform> ssr: SsrParams;
+
+const PI: f32 = 3.14159265;
+const REFINE_STEPS: u32 = 4u;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+fn octahedral_decode(encoded: vec2<f32>) -> vec3<f32> {
+    var n = vec3<f32>(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    let t = max(-n.z, 0.0);
+    n.x += select(t, -t, n.x >= 0.0);
+    n.y += select(t, -t, n.y >= 0.0);
+    return normalize(n);
+}
+
+fn linear_depth(depth: f32) -> f32 {
+    return ssr.near * ssr.far / (ssr.far - depth * (ssr.far - ssr.near));
+}
+
+fn world_position(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let position = ssr.inv_view_proj * ndc;
+    return position.xyz / position.w;
+}
+
+// UV and view distance of a world space point, w <= 0 behind the camera
+fn project(position: vec3<f32>) -> vec3<f32> {
+    let clip = ssr.view_proj * vec4<f32>(position, 1.0);
+    let ndc = clip.xy / clip.w;
+    return vec3<f32>(ndc * vec2<f32>(0.5, -0.5) + 0.5, clip.w);
+}
+
+// How far in front of the depth buffer the ray is at `distance`, negative
+// once it went behind it
+fn depth_delta(origin: vec3<f32>, direction: vec3<f32>, distance: f32, size: vec2<f32>) -> f32 {
+    let sample = project(origin + direction * distance);
+    let pixel = vec2<i32>(clamp(sample.xy, vec2<f32>(0.0), vec2<f32>(0.9999)) * size);
+    return linear_depth(textureLoad(t_depth, pixel, 0)) - sample.z;
+}
+
+// Reflected color in rgb, confidence of the hit in a
+fn trace(origin: vec3<f32>, direction: vec3<f32>) -> vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_depth));
+    let step = ssr.max_distance / f32(ssr.max_steps);
+    var previous = 0.0;
+    for (var i = 1u; i <= ssr.max_steps; i++) {
+        let distance = f32(i) * step;
+        let sample = project(origin + direction * distance);
+        if sample.z <= 0.0 || any(sample.xy < vec2<f32>(0.0)) || any(sample.xy > vec2<f32>(1.0)) {
+            break;
+        }
+        let delta = depth_delta(origin, direction, distance, size);
+        if delta < 0.0 && delta > -ssr.thickness {
+            // Bisect between the last step in front and the first behind
+            var near = previous;
+            var far = distance;
+            for (var j = 0u; j < REFINE_STEPS; j++) {
+                let middle = (near + far) * 0.5;
+                if depth_delta(origin, direction, middle, size) < 0.0 {
+                    far = middle;
+                } else {
+                    near = middle;
+                }
+            }
+            let hit = project(origin + direction * far);
+            let edge = min(hit.xy, 1.0 - hit.xy);
+            let edge_fade = clamp(min(edge.x, edge.y) / max(ssr.edge_fade, 0.0001), 0.0, 1.0);
+            let distance_fade = 1.0 - far / ssr.max_distance;
+            let color = textureSampleLevel(t_scene, s_scene, hit.xy, 0.0).rgb;
+            return vec4<f32>(color, edge_fade * distance_fade);
+        }
+        previous = distance;
+    }
+    return vec4<f32>(0.0);
+}
+
+// Equirectangular lookup, blurrier mips for rougher surfaces when the map
+// has them
+fn environment(direction: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let u = fract(atan2(direction.z, direction.x) / (2.0 * PI));
+    let v = acos(clamp(direction.y, -1.0, 1.0)) / PI;
+    let level = roughness * f32(textureNumLevels(t_environment) - 1u);
+    return textureSampleLevel(t_environment, s_environment, vec2<f32>(u, v), level).rgb;
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy);
+    let depth = textureLoad(t_depth, pixel, 0);
+    let surface = textureLoad(t_surface, pixel, 0);
+    // The sky and non reflective surfaces keep what the mesh pass drew
+    if depth >= 1.0 || surface.w <= 0.0 {
+        discard;
+    }
+
+    let uv = position.xy / vec2<f32>(textureDimensions(t_depth));
+    let origin = world_position(uv, depth);
+    let normal = octahedral_decode(surface.xy);
+    let roughness = surface.z;
+    let view = normalize(origin - ssr.eye.xyz);
+    let direction = reflect(view, normal);
+
+    var reflection = environment(direction, roughness);
+    let glossiness = 1.0 - smoothstep(ssr.max_roughness * 0.5, ssr.max_roughness, roughness);
+    if glossiness > 0.0 {
+        // Nudged off the surface so the first steps don't hit itself
+        let hit = trace(origin + normal * 0.02, direction);
+        reflection = mix(reflection, hit.rgb, hit.a * glossiness);
+    }
+
+    // Schlick, with the reflectivity as the reflectance at normal incidence
+    let cos_theta = clamp(dot(-view, normal), 0.0, 1.0);
+    let fresnel = surface.w + (1.0 - surface.w) * pow(1.0 - cos_theta, 5.0);
+    let weight = clamp(fresnel * ssr.intensity, 0.0, 1.0);
+    return vec4<f32>(reflection, weight);
+}
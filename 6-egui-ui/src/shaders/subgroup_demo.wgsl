@@ -0,0 +1,62 @@
+struct Params {
+    count: u32,
+}
+
+struct Result {
+    odd: atomic<u32>,
+    maximum: atomic<u32>,
+}
+
+@group(0) @binding(0)
+var<storage, read> input: array<u32>;
+@group(0) @binding(1)
+var<storage, read_write> result: Result;
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+// Subgroups are at least 4 wide
+const MAX_SUBGROUPS: u32 = 64u;
+
+var<workgroup> odd_counts: array<u32, MAX_SUBGROUPS>;
+var<workgroup> maxima: array<u32, MAX_SUBGROUPS>;
+
+// Counts odd values with a ballot and finds the maximum with a butterfly of
+// xor shuffles, so no workgroup memory is touched until each subgroup is done
+@compute @workgroup_size(256)
+fn cs_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+    @builtin(subgroup_invocation_id) lane: u32,
+    @builtin(subgroup_size) size: u32,
+) {
+    var value = 0u;
+    let in_range = id.x < params.count;
+    if in_range {
+        value = input[id.x];
+    }
+
+    let ballot = subgroupBallot(in_range && (value & 1u) == 1u);
+    let odd = countOneBits(ballot.x) + countOneBits(ballot.y) + countOneBits(ballot.z) + countOneBits(ballot.w);
+
+    var maximum = value;
+    for (var mask = size / 2u; mask > 0u; mask >>= 1u) {
+        maximum = max(maximum, subgroupShuffleXor(maximum, mask));
+    }
+
+    if lane == 0u {
+        odd_counts[local / size] = odd;
+        maxima[local / size] = maximum;
+    }
+    workgroupBarrier();
+
+    if local == 0u {
+        var workgroup_odd = 0u;
+        var workgroup_max = 0u;
+        for (var i = 0u; i < 256u / size; i++) {
+            workgroup_odd += odd_counts[i];
+            workgroup_max = max(workgroup_max, maxima[i]);
+        }
+        atomicAdd(&result.odd, workgroup_odd);
+        atomicMax(&result.maximum, workgroup_max);
+    }
+}
@@ -0,0 +1,48 @@
+struct Params {
+    count: u32,
+}
+
+struct Result {
+    odd: atomic<u32>,
+    maximum: atomic<u32>,
+}
+
+@group(0) @binding(0)
+var<storage, read> input: array<u32>;
+@group(0) @binding(1)
+var<storage, read_write> result: Result;
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+var<workgroup> odd_count: atomic<u32>;
+var<workgroup> maxima: array<u32, 256>;
+
+// Same result as the subgroup path, using workgroup atomics for the count and
+// a tree in workgroup memory for the maximum
+@compute @workgroup_size(256)
+fn cs_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    var value = 0u;
+    if id.x < params.count {
+        value = input[id.x];
+        if (value & 1u) == 1u {
+            atomicAdd(&odd_count, 1u);
+        }
+    }
+    maxima[local] = value;
+    workgroupBarrier();
+
+    for (var stride = 128u; stride > 0u; stride >>= 1u) {
+        if local < stride {
+            maxima[local] = max(maxima[local], maxima[local + stride]);
+        }
+        workgroupBarrier();
+    }
+
+    if local == 0u {
+        atomicAdd(&result.odd, atomicLoad(&odd_count));
+        atomicMax(&result.maximum, maxima[0]);
+    }
+}
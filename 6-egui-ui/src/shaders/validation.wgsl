@@ -0,0 +1,65 @@
+// Flags pixels of the HDR frame buffer that no lighting math should produce.
+// Every pixel gets a class in the mask, the overlay pass draws them, and the
+// counters are read back on request.
+
+struct Params {
+    // Channels above it count as out of range
+    max_value: f32,
+}
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var mask: texture_storage_2d<r32uint, write>;
+// NaN, Inf, negative and out of range pixels
+@group(0) @binding(2)
+var<storage, read_write> counters: array<atomic<u32>, 4>;
+@group(0) @binding(3)
+var<uniform> params: Params;
+
+const VALID: u32 = 0u;
+const NAN: u32 = 1u;
+const INF: u32 = 2u;
+const NEGATIVE: u32 = 3u;
+const OUT_OF_RANGE: u32 = 4u;
+
+// By the bits, so no compiler can fold `x != x` away
+fn is_nan(x: f32) -> bool {
+    let bits = bitcast<u32>(x);
+    return (bits & 0x7f800000u) == 0x7f800000u && (bits & 0x007fffffu) != 0u;
+}
+
+fn is_inf(x: f32) -> bool {
+    return (bitcast<u32>(x) & 0x7fffffffu) == 0x7f800000u;
+}
+
+// The worst problem wins when channels disagree
+fn classify(color: vec4<f32>) -> u32 {
+    var kind = VALID;
+    for (var i = 0u; i < 4u; i++) {
+        let x = color[i];
+        if is_nan(x) {
+            return NAN;
+        }
+        if is_inf(x) {
+            kind = INF;
+        } else if x < 0.0 && kind != INF {
+            kind = NEGATIVE;
+        } else if i < 3u && x > params.max_value && kind == VALID {
+            kind = OUT_OF_RANGE;
+        }
+    }
+    return kind;
+}
+
+@compute @workgroup_size(16, 16, 1)
+fn cs_classify(@builtin(global_invocation_id) id: vec3<u32>) {
+    if any(id.xy >= textureDimensions(source)) {
+        return;
+    }
+    let kind = classify(textureLoad(source, id.xy, 0));
+    textureStore(mask, id.xy, vec4<u32>(kind, 0u, 0u, 0u));
+    if kind != VALID {
+        atomicAdd(&counters[kind - 1u], 1u);
+    }
+}
@@ -0,0 +1,40 @@
+@group(0) @binding(0)
+var mask: texture_2d<u32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle covering the screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Diagonal bands four pixels wide
+fn stripe(position: vec2<f32>) -> bool {
+    return (u32(position.x + position.y) / 4u) % 2u == 0u;
+}
+
+// NaN is solid magenta, Inf magenta and black stripes, negative values cyan
+// stripes and out of range ones yellow stripes. Written without blending,
+// anything blended with a NaN stays NaN, and discarded where the scene shows.
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let kind = textureLoad(mask, vec2<u32>(position.xy), 0).r;
+    let striped = stripe(position.xy);
+    if kind == 0u || (kind > 2u && !striped) {
+        discard;
+    }
+    switch kind {
+        case 1u: {
+            return vec4<f32>(1.0, 0.0, 1.0, 1.0);
+        }
+        case 2u: {
+            return select(vec4<f32>(0.0, 0.0, 0.0, 1.0), vec4<f32>(1.0, 0.0, 1.0, 1.0), striped);
+        }
+        case 3u: {
+            return vec4<f32>(0.0, 1.0, 1.0, 1.0);
+        }
+        default: {
+            return vec4<f32>(1.0, 1.0, 0.0, 1.0);
+        }
+    }
+}
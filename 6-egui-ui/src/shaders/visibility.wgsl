@@ -0,0 +1,51 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+}
+
+struct GeometryVertex {
+    position: vec4<f32>,
+    normal: vec4<f32>,
+}
+
+struct Instance {
+    model: mat4x4<f32>,
+    color: vec4<f32>,
+    first_vertex: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var<storage, read> vertices: array<GeometryVertex>;
+@group(1) @binding(1)
+var<storage, read> instances: array<Instance>;
+
+// Low bits hold the triangle within the mesh, high bits the instance + 1 so
+// that zero can mean "nothing was drawn here"
+const TRIANGLE_BITS: u32 = 12u;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) @interpolate(flat) id: u32,
+}
+
+// Vertices are pulled from storage instead of a vertex buffer, the same way
+// the shading pass fetches them later
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let instance = instances[instance_index];
+    let position = vertices[instance.first_vertex + vertex].position;
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * instance.model * vec4<f32>(position.xyz, 1.0);
+    out.id = ((instance_index + 1u) << TRIANGLE_BITS) | (vertex / 3u);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) u32 {
+    return in.id;
+}
@@ -0,0 +1,109 @@
+struct Shade {
+    inv_view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+    // direction towards the light
+    light: vec4<f32>,
+    mode: u32,
+}
+
+struct GeometryVertex {
+    position: vec4<f32>,
+    normal: vec4<f32>,
+}
+
+struct Instance {
+    model: mat4x4<f32>,
+    color: vec4<f32>,
+    first_vertex: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> shade: Shade;
+@group(0) @binding(1)
+var ids: texture_2d<u32>;
+@group(1) @binding(0)
+var<storage, read> vertices: array<GeometryVertex>;
+@group(1) @binding(1)
+var<storage, read> instances: array<Instance>;
+
+const TRIANGLE_BITS: u32 = 12u;
+const MODE_SHADED: u32 = 0u;
+const MODE_TRIANGLES: u32 = 1u;
+const MODE_INSTANCES: u32 = 2u;
+const AMBIENT: f32 = 0.15;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// One triangle covering the screen
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex << 1u) & 2u), f32(vertex & 2u));
+    var out: VertexOutput;
+    out.ndc = uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+    return out;
+}
+
+fn hash_color(value: u32) -> vec3<f32> {
+    var h = value * 747796405u + 2891336453u;
+    h = ((h >> ((h >> 28u) + 4u)) ^ h) * 277803737u;
+    h = (h >> 22u) ^ h;
+    return vec3<f32>(f32(h & 255u), f32((h >> 8u) & 255u), f32((h >> 16u) & 255u)) / 255.0;
+}
+
+// Barycentrics of where the view ray through the pixel crosses the triangle
+fn barycentrics(origin: vec3<f32>, direction: vec3<f32>, a: vec3<f32>, b: vec3<f32>, c: vec3<f32>) -> vec3<f32> {
+    let ab = b - a;
+    let ac = c - a;
+    let p = cross(direction, ac);
+    let det = dot(ab, p);
+    if abs(det) < 1e-8 {
+        return vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let t = origin - a;
+    let u = dot(t, p) / det;
+    let v = dot(direction, cross(t, ab)) / det;
+    return vec3<f32>(1.0 - u - v, u, v);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let id = textureLoad(ids, vec2<i32>(in.clip_position.xy), 0).r;
+    if id == 0u {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    let instance_index = (id >> TRIANGLE_BITS) - 1u;
+    let triangle = id & ((1u << TRIANGLE_BITS) - 1u);
+    if shade.mode == MODE_TRIANGLES {
+        return vec4<f32>(hash_color(id), 1.0);
+    }
+    if shade.mode == MODE_INSTANCES {
+        return vec4<f32>(hash_color(instance_index), 1.0);
+    }
+
+    // Fetch and transform the triangle the rasterizer kept for this pixel
+    let instance = instances[instance_index];
+    let first = instance.first_vertex + triangle * 3u;
+    var positions: array<vec3<f32>, 3>;
+    var normals: array<vec3<f32>, 3>;
+    for (var i = 0u; i < 3u; i++) {
+        let vertex = vertices[first + i];
+        positions[i] = (instance.model * vec4<f32>(vertex.position.xyz, 1.0)).xyz;
+        normals[i] = (instance.model * vec4<f32>(vertex.normal.xyz, 0.0)).xyz;
+    }
+
+    let far = shade.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+    let direction = normalize(far.xyz / far.w - shade.eye.xyz);
+    let weights = barycentrics(shade.eye.xyz, direction, positions[0], positions[1], positions[2]);
+    var normal = normalize(normals[0] * weights.x + normals[1] * weights.y + normals[2] * weights.z);
+    // Two-sided, like the quads of the scene
+    if dot(normal, direction) > 0.0 {
+        normal = -normal;
+    }
+
+    let diffuse = max(dot(normal, shade.light.xyz), 0.0);
+    return vec4<f32>(instance.color.rgb * (AMBIENT + diffuse), 1.0);
+}
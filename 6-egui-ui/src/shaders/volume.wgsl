@@ -0,0 +1,94 @@
+struct Volume {
+    inv_view_proj: mat4x4<f32>,
+    eye: vec4<f32>,
+    box_min: vec4<f32>,
+    box_max: vec4<f32>,
+    // density, steps, coverage, time
+    params: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> volume: Volume;
+@group(0) @binding(1)
+var noise: texture_3d<f32>;
+@group(0) @binding(2)
+var noise_sampler: sampler;
+@group(0) @binding(3)
+var scene_depth: texture_depth_2d;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// Single triangle covering the screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+    return out;
+}
+
+fn world_at(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    let position = volume.inv_view_proj * vec4<f32>(ndc, depth, 1.0);
+    return position.xyz / position.w;
+}
+
+// Entry and exit distances of the ray through the volume box
+fn intersect_box(origin: vec3<f32>, direction: vec3<f32>) -> vec2<f32> {
+    let inv = 1.0 / direction;
+    let t0 = (volume.box_min.xyz - origin) * inv;
+    let t1 = (volume.box_max.xyz - origin) * inv;
+    let t_min = min(t0, t1);
+    let t_max = max(t0, t1);
+    return vec2<f32>(max(max(t_min.x, t_min.y), t_min.z), min(min(t_max.x, t_max.y), t_max.z));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let density_scale = volume.params.x;
+    let steps = u32(volume.params.y);
+    let coverage = volume.params.z;
+    let time = volume.params.w;
+
+    let origin = volume.eye.xyz;
+    let direction = normalize(world_at(in.ndc, 1.0) - origin);
+    let hit = intersect_box(origin, direction);
+
+    // Stop marching at opaque geometry
+    let depth = textureLoad(scene_depth, vec2<i32>(in.clip_position.xy), 0);
+    let scene_distance = distance(world_at(in.ndc, depth), origin);
+    let t_near = max(hit.x, 0.0);
+    let t_far = min(hit.y, scene_distance);
+    if t_far <= t_near {
+        discard;
+    }
+
+    let size = volume.box_max.xyz - volume.box_min.xyz;
+    let step = (t_far - t_near) / f32(steps);
+    let scroll = vec3<f32>(0.02, 0.0, 0.01) * time;
+    var transmittance = 1.0;
+    var color = vec3<f32>(0.0);
+    for (var i = 0u; i < steps; i++) {
+        let position = origin + direction * (t_near + (f32(i) + 0.5) * step);
+        let local = (position - volume.box_min.xyz) / size;
+        // Fade towards the box faces so the cloud has no hard edges
+        let edge = min(local, vec3<f32>(1.0) - local);
+        let fade = smoothstep(0.0, 0.2, min(edge.x, min(edge.y, edge.z)));
+        let noise_value = textureSampleLevel(noise, noise_sampler, local + scroll, 0.0).r;
+        let density = max(noise_value - coverage, 0.0) * fade * density_scale;
+
+        let absorbed = exp(-density * step);
+        let light = mix(vec3<f32>(0.35, 0.4, 0.5), vec3<f32>(1.0, 0.97, 0.9), local.y);
+        color += transmittance * (1.0 - absorbed) * light;
+        transmittance *= absorbed;
+        if transmittance < 0.01 {
+            break;
+        }
+    }
+
+    // Premultiplied alpha
+    return vec4<f32>(color, 1.0 - transmittance);
+}
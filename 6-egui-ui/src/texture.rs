@@ -1,8 +1,56 @@
-use anyhow::*;
+use std::path::PathBuf;
+
 use image::GenericImageView;
 use tracing::info;
 use wgpu::util::DeviceExt;
 
+#[derive(Debug, thiserror::Error)]
+pub enum TextureError {
+    #[error("Layers {first}..{end} are out of range for '{label}' with {layers} layers")]
+    LayersOutOfRange {
+        label: String,
+        first: u32,
+        end: u32,
+        layers: u32,
+    },
+    #[error("Data for '{label}' is {len} bytes, expected {expected}")]
+    SizeMismatch {
+        label: String,
+        len: usize,
+        expected: usize,
+    },
+    #[error("Data for '{label}' is {len} bytes, not a whole number of {slice_size} byte slices")]
+    PartialSlice {
+        label: String,
+        len: usize,
+        slice_size: usize,
+    },
+    #[error("'{label}' has {texels} texels, expected {width}x{height}")]
+    TexelCount {
+        label: String,
+        texels: usize,
+        width: u32,
+        height: u32,
+    },
+    /// Formats without a texel size, like depth and stencil combined.
+    #[error("Cannot upload to a {0:?} texture")]
+    NotUploadable(wgpu::TextureFormat),
+    #[error("HDR textures must be Rgba16Float or Rgba32Float, got {0:?}")]
+    UnsupportedFormat(wgpu::TextureFormat),
+    #[error("Failed to decode image '{label}'")]
+    Decode {
+        label: String,
+        #[source]
+        source: image::ImageError,
+    },
+    #[error("Failed to read '{}'", path.display())]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
 pub struct Texture {
     pub label: String,
     #[allow(unused)]
@@ -11,91 +59,76 @@ pub struct Texture {
     pub sampler: wgpu::Sampler,
     pub usage: wgpu::TextureUsages,
     sample_count: u32,
+    view_dimension: wgpu::TextureViewDimension,
 }
 
 impl Texture {
-    pub fn from_bytes(
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        bytes: &[u8],
-        label: &str,
-    ) -> Result<Self> {
-        let img = image::load_from_memory(bytes)?;
-        Self::from_image(device, queue, &img, Some(label))
-    }
-
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
-    ) -> Result<Self> {
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+    ) -> Result<Self, TextureError> {
+        let levels = mip_chain(img);
+        let (width, height) = img.dimensions();
+        let texture = Self::streamed(device, width, height, label.unwrap_or("texture"));
+        for (mip_level, level) in levels.iter().enumerate() {
+            texture.write_mip_rows(queue, mip_level as u32, level, 0, level.height());
+        }
+        Ok(texture)
+    }
 
+    pub fn depth_texture(device: &wgpu::Device, width: u32, height: u32) -> Self {
         let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
+            width: width,
+            height: height,
             depth_or_array_layers: 1,
         };
-        let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
         let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: label.clone(),
+            label: Some("depth_texture"),
             size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format: wgpu::TextureFormat::Depth32Float,
             usage,
             view_formats: &[],
         });
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                aspect: wgpu::TextureAspect::All,
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            &rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            size,
-        );
-
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let view = texture.create_view(&Default::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("depth_sampler"),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
             mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
             ..Default::default()
         });
 
-        Ok(Self {
-            label: label.unwrap_or("texture").to_string(),
+        Self {
+            label: "depth_texture".to_string(),
             texture,
             view,
             sampler,
             usage,
             sample_count: 1,
-        })
+            view_dimension: wgpu::TextureViewDimension::D2,
+        }
     }
 
-    pub fn depth_texture(device: &wgpu::Device, width: u32, height: u32) -> Self {
+    /// Depth texture with a comparison sampler, for shadow map lookups.
+    pub fn shadow_texture(device: &wgpu::Device, width: u32, height: u32, label: &str) -> Self {
         let size = wgpu::Extent3d {
-            width: width,
-            height: height,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
         let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
         let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("depth_texture"),
+            label: Some(label),
             size,
             mip_level_count: 1,
             sample_count: 1,
@@ -107,23 +140,25 @@ impl Texture {
 
         let view = texture.create_view(&Default::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("depth_sampler"),
+            label: Some("shadow_sampler"),
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
             mag_filter: wgpu::FilterMode::Linear,
             min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
             ..Default::default()
         });
 
         Self {
-            label: "depth_texture".to_string(),
+            label: label.to_string(),
             texture,
             view,
             sampler,
             usage,
             sample_count: 1,
+            view_dimension: wgpu::TextureViewDimension::D2,
         }
     }
 
@@ -139,7 +174,11 @@ impl Texture {
             height,
             depth_or_array_layers: 1,
         };
-        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        // Copyable both ways, so a pass can snapshot what it draws over
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::COPY_DST;
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
@@ -169,8 +208,655 @@ impl Texture {
             sampler,
             usage,
             sample_count,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        }
+    }
+
+    /// A screen sized target in `format`, for the extra outputs of a pass.
+    pub fn render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&Default::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            label: label.to_string(),
+            texture,
+            view,
+            sampler,
+            usage,
+            sample_count: 1,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        }
+    }
+}
+
+// Compute targets
+impl Texture {
+    /// A screen sized texture written by compute shaders and read back by
+    /// later passes, or copied out.
+    pub fn storage(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let usage = wgpu::TextureUsages::STORAGE_BINDING
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&Default::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            label: label.to_string(),
+            texture,
+            view,
+            sampler,
+            usage,
+            sample_count: 1,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        }
+    }
+}
+
+// Streamed mips
+impl Texture {
+    /// sRGB color texture with a full mip chain and nothing uploaded yet. Levels
+    /// are written with [`Texture::write_mip_rows`], and
+    /// [`Texture::set_base_mip`] keeps the view on the ones that are there.
+    pub fn streamed(device: &wgpu::Device, width: u32, height: u32, label: &str) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: size.max_mips(wgpu::TextureDimension::D2),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            label: label.to_string(),
+            texture,
+            view,
+            sampler,
+            usage,
+            sample_count: 1,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        }
+    }
+
+    /// Uploads `rows` rows of a mip level starting at `first_row`.
+    pub fn write_mip_rows(
+        &self,
+        queue: &wgpu::Queue,
+        mip_level: u32,
+        level: &image::RgbaImage,
+        first_row: u32,
+        rows: u32,
+    ) {
+        let width = level.width();
+        let start = (first_row * width * 4) as usize;
+        let end = ((first_row + rows) * width * 4) as usize;
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture,
+                mip_level,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: first_row,
+                    z: 0,
+                },
+            },
+            &level.as_raw()[start..end],
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(rows),
+            },
+            wgpu::Extent3d {
+                width,
+                height: rows,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Restricts the view to `base` and the smaller levels below it, so
+    /// sampling never reaches a level that hasn't been uploaded.
+    pub fn set_base_mip(&mut self, base: u32) {
+        self.view = self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&format!("{}_mip_{}", self.label, base)),
+            base_mip_level: base,
+            ..Default::default()
+        });
+    }
+
+    /// Block compressed texture with every level uploaded at once, `levels`
+    /// finest first, as baked offline.
+    pub fn compressed(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        levels: &[Vec<u8>],
+        label: &str,
+    ) -> Self {
+        let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: levels.len() as u32,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &levels.concat(),
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            label: label.to_string(),
+            texture,
+            view,
+            sampler,
+            usage,
+            sample_count: 1,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        }
+    }
+}
+
+/// Every mip level of `img` in RGBA8, level 0 first. The full chain, so
+/// minification and anisotropic filtering have something to pick from.
+/// Downsampled in sRGB space, which slightly darkens high contrast detail but
+/// is good enough here.
+pub fn mip_chain(img: &image::DynamicImage) -> Vec<image::RgbaImage> {
+    let mut level = img.to_rgba8();
+    let (width, height) = level.dimensions();
+    let count = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    }
+    .max_mips(wgpu::TextureDimension::D2);
+    let mut levels = Vec::with_capacity(count as usize);
+    for _ in 1..count {
+        let (width, height) = level.dimensions();
+        let next = image::imageops::resize(
+            &level,
+            (width / 2).max(1),
+            (height / 2).max(1),
+            image::imageops::FilterType::Triangle,
+        );
+        levels.push(std::mem::replace(&mut level, next));
+    }
+    levels.push(level);
+    levels
+}
+
+// Texture arrays
+impl Texture {
+    /// 2D texture with `layers` array layers. `view` covers all of them as a
+    /// `D2Array`, [`Texture::layer_view`] gives access to a single one.
+    pub fn array(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            label: label.to_string(),
+            texture,
+            view,
+            sampler,
+            usage,
+            sample_count: 1,
+            view_dimension: wgpu::TextureViewDimension::D2Array,
+        }
+    }
+
+    /// Array layers, or depth slices of a 3D texture.
+    pub fn layers(&self) -> u32 {
+        self.texture.depth_or_array_layers()
+    }
+
+    /// Single-layer `D2` view, for rendering into or sampling one layer.
+    pub fn layer_view(&self, layer: u32) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(&format!("{}_layer_{}", self.label, layer)),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })
+    }
+
+    /// Uploads tightly packed texel data for a whole layer.
+    pub fn write_layer(
+        &self,
+        queue: &wgpu::Queue,
+        layer: u32,
+        data: &[u8],
+    ) -> Result<(), TextureError> {
+        self.write_layers(queue, layer, 1, data)
+    }
+
+    /// Uploads `count` layers (or 3D slices) starting at `first`.
+    fn write_layers(
+        &self,
+        queue: &wgpu::Queue,
+        first: u32,
+        count: u32,
+        data: &[u8],
+    ) -> Result<(), TextureError> {
+        if first + count > self.layers() {
+            return Err(TextureError::LayersOutOfRange {
+                label: self.label.clone(),
+                first,
+                end: first + count,
+                layers: self.layers(),
+            });
+        }
+        let format = self.texture.format();
+        let texel_size = format
+            .block_copy_size(None)
+            .ok_or(TextureError::NotUploadable(format))?;
+        let (width, height) = (self.texture.width(), self.texture.height());
+        let expected = (width * height * count * texel_size) as usize;
+        if data.len() != expected {
+            return Err(TextureError::SizeMismatch {
+                label: self.label.clone(),
+                len: data.len(),
+                expected,
+            });
+        }
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: first,
+                },
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * texel_size),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: count,
+            },
+        );
+        Ok(())
+    }
+}
+
+// Volumes
+impl Texture {
+    pub fn volume(
+        device: &wgpu::Device,
+        size: [u32; 3],
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: size[2],
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&Default::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            label: label.to_string(),
+            texture,
+            view,
+            sampler,
+            usage,
+            sample_count: 1,
+            view_dimension: wgpu::TextureViewDimension::D3,
         }
     }
+
+    /// Creates a volume and fills it by calling `generator` for every texel.
+    pub fn volume_from_fn<T: bytemuck::Pod>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: [u32; 3],
+        format: wgpu::TextureFormat,
+        label: &str,
+        generator: impl Fn(u32, u32, u32) -> T,
+    ) -> Result<Self, TextureError> {
+        let texture = Self::volume(
+            device,
+            size,
+            format,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label,
+        );
+        let [width, height, depth] = size;
+        let texels: Vec<T> = (0..depth)
+            .flat_map(|z| (0..height).flat_map(move |y| (0..width).map(move |x| (x, y, z))))
+            .map(|(x, y, z)| generator(x, y, z))
+            .collect();
+        texture.write_slices(queue, 0, bytemuck::cast_slice(&texels))?;
+        Ok(texture)
+    }
+
+    /// Uploads tightly packed depth slices starting at slice `first`.
+    pub fn write_slices(
+        &self,
+        queue: &wgpu::Queue,
+        first: u32,
+        data: &[u8],
+    ) -> Result<(), TextureError> {
+        let slice_size = (self.texture.width() * self.texture.height()) as usize
+            * self.texture.format().block_copy_size(None).unwrap_or(1) as usize;
+        if slice_size == 0 || !data.len().is_multiple_of(slice_size) {
+            return Err(TextureError::PartialSlice {
+                label: self.label.clone(),
+                len: data.len(),
+                slice_size,
+            });
+        }
+        self.write_layers(queue, first, (data.len() / slice_size) as u32, data)
+    }
+}
+
+// HDR images
+impl Texture {
+    /// Decodes a Radiance `.hdr` image into a float texture. `format` must be
+    /// `Rgba16Float` or `Rgba32Float`; the latter is not filterable without
+    /// `FLOAT32_FILTERABLE`, so prefer half floats for anything sampled.
+    pub fn from_hdr_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Result<Self, TextureError> {
+        let img = image::load_from_memory_with_format(bytes, image::ImageFormat::Hdr).map_err(
+            |source| TextureError::Decode {
+                label: label.to_string(),
+                source,
+            },
+        )?;
+        let (width, height) = img.dimensions();
+        let rgba = img.into_rgba32f();
+        let texels: &[[f32; 4]] = bytemuck::cast_slice(rgba.as_raw());
+        Self::from_rgba32f(device, queue, width, height, texels, format, label)
+    }
+
+    pub fn from_hdr_path(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: impl AsRef<std::path::Path>,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|source| TextureError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let label = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("hdr_texture");
+        Self::from_hdr_bytes(device, queue, &bytes, format, label)
+    }
+
+    /// Uploads linear RGBA texels, converting to half floats for `Rgba16Float`.
+    /// The sampler repeats horizontally so equirectangular maps wrap around.
+    pub fn from_rgba32f(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        texels: &[[f32; 4]],
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Result<Self, TextureError> {
+        if texels.len() != (width * height) as usize {
+            return Err(TextureError::TexelCount {
+                label: label.to_string(),
+                texels: texels.len(),
+                width,
+                height,
+            });
+        }
+        let data: Vec<u8> = match format {
+            wgpu::TextureFormat::Rgba32Float => bytemuck::cast_slice(texels).to_vec(),
+            wgpu::TextureFormat::Rgba16Float => {
+                let halves: Vec<u16> = texels.iter().flatten().map(|&v| f32_to_f16(v)).collect();
+                bytemuck::cast_slice(&halves).to_vec()
+            }
+            _ => return Err(TextureError::UnsupportedFormat(format)),
+        };
+
+        let usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&Default::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let texture = Self {
+            label: label.to_string(),
+            texture,
+            view,
+            sampler,
+            usage,
+            sample_count: 1,
+            view_dimension: wgpu::TextureViewDimension::D2,
+        };
+        texture.write_layer(queue, 0, &data)?;
+        info!(
+            "Loaded HDR texture '{}' ({}x{} {:?})",
+            label, width, height, format
+        );
+        Ok(texture)
+    }
+}
+
+/// Round-to-nearest f32 to IEEE half conversion; overflow saturates to infinity.
+pub(crate) fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // Subnormal: shift the implicit leading one into the mantissa.
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | (half + round) as u16;
+    }
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    let round = (mantissa >> 12) & 1;
+    sign | (half + round) as u16
 }
 
 // Risizing
@@ -185,15 +871,59 @@ impl Texture {
             size: wgpu::Extent3d {
                 width,
                 height,
-                depth_or_array_layers: 1,
+                depth_or_array_layers: self.layers(),
             },
             mip_level_count: 1,
             sample_count: self.sample_count,
-            dimension: wgpu::TextureDimension::D2,
+            dimension: self.texture.dimension(),
             format,
             usage: self.usage,
             view_formats: &[],
         });
-        self.view = self.texture.create_view(&Default::default());
+        self.view = self.texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(self.view_dimension),
+            ..Default::default()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader_test::gpu_or_skip;
+
+    #[test]
+    fn bad_uploads_report_what_was_wrong() {
+        let Some(gpu) = gpu_or_skip() else {
+            return;
+        };
+        let texels = [[0.0; 4]; 4];
+        let hdr = |format, width| {
+            Texture::from_rgba32f(&gpu.device, &gpu.queue, width, 2, &texels, format, "hdr")
+        };
+        assert!(matches!(
+            hdr(wgpu::TextureFormat::Rgba8Unorm, 2),
+            Err(TextureError::UnsupportedFormat(
+                wgpu::TextureFormat::Rgba8Unorm
+            ))
+        ));
+        assert!(matches!(
+            hdr(wgpu::TextureFormat::Rgba16Float, 3),
+            Err(TextureError::TexelCount { texels: 4, .. })
+        ));
+
+        let texture = hdr(wgpu::TextureFormat::Rgba16Float, 2).unwrap();
+        assert!(matches!(
+            texture.write_layer(&gpu.queue, 0, &[0; 3]),
+            Err(TextureError::SizeMismatch {
+                len: 3,
+                expected: 32,
+                ..
+            })
+        ));
+        assert!(matches!(
+            texture.write_layer(&gpu.queue, 1, &[0; 32]),
+            Err(TextureError::LayersOutOfRange { layers: 1, .. })
+        ));
     }
 }
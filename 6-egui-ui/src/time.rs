@@ -7,6 +7,8 @@ use bevy_ecs::{
     world::World,
 };
 
+use playground_app::FrameCapture;
+
 use crate::gpu::GpuContext;
 
 pub fn setup_time(world: &mut World, schedule: &mut Schedule) -> Result<()> {
@@ -26,6 +28,9 @@ pub struct TimeContext {
     frame_time_history: Vec<f32>,
     pub delta: f32,
     pub total: f32,
+    /// Advances by this much per frame instead of the wall clock, set while
+    /// something needs frames to be reproducible.
+    pub fixed_step: Option<f32>,
 }
 impl TimeContext {
     pub fn new() -> Self {
@@ -34,6 +39,7 @@ impl TimeContext {
             frame_time_history: Vec::new(),
             delta: 0.0,
             total: 0.0,
+            fixed_step: None,
         }
     }
     pub fn update(&mut self) {
@@ -43,6 +49,12 @@ impl TimeContext {
         self.total += delta;
         self.last_frame = now;
     }
+    /// Advances by a fixed `delta` instead of the wall clock.
+    pub fn step(&mut self, delta: f32) {
+        self.delta = delta;
+        self.total += delta;
+        self.last_frame = Instant::now();
+    }
 }
 
 #[derive(Resource)]
@@ -77,8 +89,13 @@ pub fn time_system(
     mut time: ResMut<TimeContext>,
     mut time_history: ResMut<TimeHistory>,
     gpu: Res<GpuContext>,
+    capture: Option<Res<FrameCapture>>,
 ) {
-    time.update();
+    let step = capture.map(|_| FrameCapture::TIME_STEP).or(time.fixed_step);
+    match step {
+        Some(step) => time.step(step),
+        None => time.update(),
+    }
     time_history.update(time.delta);
 
     let average_frame_time = time_history.average_frame_time();
@@ -0,0 +1,727 @@
+//! Parameter animation from a file, for flythroughs and benchmark runs that
+//! look the same every time. A timeline is a set of tracks, each keying one
+//! named [`Parameter`] over time: the camera, a pass toggle or a setting.
+//!
+//! Playback normally advances by the timeline's fixed step every frame and
+//! pins the scene clock to it, so frame N of a run shows the same thing on
+//! every run and every machine, however long the frames take. `--timeline
+//! path` loads a timeline and plays it from the first frame.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::Resource,
+    world::World,
+};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    camera_path::CameraPath,
+    i18n::{tr, trf},
+    input::{gamepad_camera_system, touch_camera_system},
+    pipeline::{
+        ao::AoSettings, god_rays::GodRaySettings, grading::ColorGradingSettings,
+        post::PostSettings, present::PresentSettings, quality::update, ssr::SsrSettings,
+        ui::UiPanels, volume::VolumeSettings,
+    },
+    scene::{camera_aspect_system, Camera},
+    time::{time_system, TimeContext},
+};
+
+/// Loaded at startup when `--timeline` isn't passed, in the working directory.
+pub const TIMELINE_FILE: &str = "timeline.json";
+
+pub fn setup_timeline(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let player = match timeline_from_args()? {
+        Some(path) => {
+            let timeline = Timeline::load(&path)?
+                .with_context(|| format!("{} does not exist", path.display()))?;
+            info!("Playing {} from the first frame", path.display());
+            let mut player = TimelinePlayer::new(path);
+            player.timeline = Some(timeline);
+            player.play();
+            player
+        }
+        None => {
+            let mut player = TimelinePlayer::new(PathBuf::from(TIMELINE_FILE));
+            player.load();
+            player
+        }
+    };
+    world.insert_resource(player);
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(timeline_panel);
+
+    // After the interactive camera controls, so playback wins over them
+    schedule.add_systems(
+        timeline_system
+            .after(time_system)
+            .after(gamepad_camera_system)
+            .after(touch_camera_system)
+            .before(camera_aspect_system),
+    );
+
+    Ok(())
+}
+
+/// Accepts both `--timeline path` and `--timeline=path`.
+fn timeline_from_args() -> Result<Option<PathBuf>> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--timeline" {
+            let value = args.next().context("--timeline needs a path")?;
+            return Ok(Some(PathBuf::from(value)));
+        }
+        if let Some(value) = arg.strip_prefix("--timeline=") {
+            return Ok(Some(PathBuf::from(value)));
+        }
+    }
+    Ok(None)
+}
+
+// =============================== PARAMETERS ===============================
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Bool(bool),
+    Scalar(f32),
+    Vec3([f32; 3]),
+}
+impl Value {
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Self::Bool(_) => ValueKind::Bool,
+            Self::Scalar(_) => ValueKind::Scalar,
+            Self::Vec3(_) => ValueKind::Vec3,
+        }
+    }
+
+    pub fn as_bool(&self) -> bool {
+        matches!(self, Self::Bool(true))
+    }
+
+    pub fn as_f32(&self) -> f32 {
+        match self {
+            Self::Scalar(value) => *value,
+            _ => 0.0,
+        }
+    }
+
+    pub fn as_vec3(&self) -> Vec3 {
+        match self {
+            Self::Vec3(value) => Vec3::from_array(*value),
+            _ => Vec3::ZERO,
+        }
+    }
+
+    /// Switches at the end for anything that can't blend.
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        match (self, other) {
+            (Self::Scalar(a), Self::Scalar(b)) => Self::Scalar(a + (b - a) * t),
+            (Self::Vec3(a), Self::Vec3(b)) => Self::Vec3(
+                Vec3::from_array(*a)
+                    .lerp(Vec3::from_array(*b), t)
+                    .to_array(),
+            ),
+            _ if t < 1.0 => *self,
+            _ => *other,
+        }
+    }
+}
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Scalar(value) => write!(f, "{:.3}", value),
+            Self::Vec3([x, y, z]) => write!(f, "{:.2}, {:.2}, {:.2}", x, y, z),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueKind {
+    Bool,
+    Scalar,
+    Vec3,
+}
+impl ValueKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bool => "bool",
+            Self::Scalar => "number",
+            Self::Vec3 => "[x, y, z]",
+        }
+    }
+}
+
+/// Something a track can animate, by name.
+pub struct Parameter {
+    pub name: &'static str,
+    pub kind: ValueKind,
+    apply: fn(&mut World, Value),
+}
+
+fn camera(world: &mut World, f: impl FnOnce(&mut Camera)) {
+    if let Some(mut camera) = world.get_resource_mut::<Camera>() {
+        f(&mut camera);
+    }
+}
+
+pub const PARAMETERS: &[Parameter] = &[
+    Parameter {
+        name: "camera.eye",
+        kind: ValueKind::Vec3,
+        apply: |world, value| camera(world, |camera| camera.eye = value.as_vec3()),
+    },
+    Parameter {
+        name: "camera.target",
+        kind: ValueKind::Vec3,
+        apply: |world, value| camera(world, |camera| camera.target = value.as_vec3()),
+    },
+    Parameter {
+        name: "camera.fov",
+        kind: ValueKind::Scalar,
+        apply: |world, value| {
+            camera(world, |camera| {
+                camera.fov_y = value.as_f32().clamp(1.0, 179.0).to_radians()
+            })
+        },
+    },
+    Parameter {
+        name: "render_scale",
+        kind: ValueKind::Scalar,
+        apply: |world, value| update::<PresentSettings>(world, |s| s.render_scale = value.as_f32()),
+    },
+    Parameter {
+        name: "ao.enabled",
+        kind: ValueKind::Bool,
+        apply: |world, value| update::<AoSettings>(world, |s| s.enabled = value.as_bool()),
+    },
+    Parameter {
+        name: "ao.intensity",
+        kind: ValueKind::Scalar,
+        apply: |world, value| update::<AoSettings>(world, |s| s.intensity = value.as_f32()),
+    },
+    Parameter {
+        name: "ao.radius",
+        kind: ValueKind::Scalar,
+        apply: |world, value| update::<AoSettings>(world, |s| s.radius = value.as_f32()),
+    },
+    Parameter {
+        name: "ssr.enabled",
+        kind: ValueKind::Bool,
+        apply: |world, value| update::<SsrSettings>(world, |s| s.enabled = value.as_bool()),
+    },
+    Parameter {
+        name: "ssr.intensity",
+        kind: ValueKind::Scalar,
+        apply: |world, value| update::<SsrSettings>(world, |s| s.intensity = value.as_f32()),
+    },
+    Parameter {
+        name: "god_rays.enabled",
+        kind: ValueKind::Bool,
+        apply: |world, value| update::<GodRaySettings>(world, |s| s.enabled = value.as_bool()),
+    },
+    Parameter {
+        name: "god_rays.density",
+        kind: ValueKind::Scalar,
+        apply: |world, value| update::<GodRaySettings>(world, |s| s.density = value.as_f32()),
+    },
+    Parameter {
+        name: "god_rays.exposure",
+        kind: ValueKind::Scalar,
+        apply: |world, value| update::<GodRaySettings>(world, |s| s.exposure = value.as_f32()),
+    },
+    Parameter {
+        name: "volume.enabled",
+        kind: ValueKind::Bool,
+        apply: |world, value| update::<VolumeSettings>(world, |s| s.enabled = value.as_bool()),
+    },
+    Parameter {
+        name: "volume.density",
+        kind: ValueKind::Scalar,
+        apply: |world, value| update::<VolumeSettings>(world, |s| s.density = value.as_f32()),
+    },
+    Parameter {
+        name: "post.vignette",
+        kind: ValueKind::Bool,
+        apply: |world, value| update::<PostSettings>(world, |s| s.vignette = value.as_bool()),
+    },
+    Parameter {
+        name: "post.vignette_intensity",
+        kind: ValueKind::Scalar,
+        apply: |world, value| {
+            update::<PostSettings>(world, |s| s.vignette_intensity = value.as_f32())
+        },
+    },
+    Parameter {
+        name: "post.chromatic_aberration",
+        kind: ValueKind::Bool,
+        apply: |world, value| {
+            update::<PostSettings>(world, |s| s.chromatic_aberration = value.as_bool())
+        },
+    },
+    Parameter {
+        name: "post.film_grain",
+        kind: ValueKind::Bool,
+        apply: |world, value| update::<PostSettings>(world, |s| s.film_grain = value.as_bool()),
+    },
+    Parameter {
+        name: "post.motion_blur",
+        kind: ValueKind::Bool,
+        apply: |world, value| update::<PostSettings>(world, |s| s.motion_blur = value.as_bool()),
+    },
+    Parameter {
+        name: "post.depth_of_field",
+        kind: ValueKind::Bool,
+        apply: |world, value| update::<PostSettings>(world, |s| s.depth_of_field = value.as_bool()),
+    },
+    Parameter {
+        name: "post.focal_distance",
+        kind: ValueKind::Scalar,
+        apply: |world, value| update::<PostSettings>(world, |s| s.focal_distance = value.as_f32()),
+    },
+    Parameter {
+        name: "post.aperture",
+        kind: ValueKind::Scalar,
+        apply: |world, value| update::<PostSettings>(world, |s| s.aperture = value.as_f32()),
+    },
+    Parameter {
+        name: "grading.enabled",
+        kind: ValueKind::Bool,
+        apply: |world, value| {
+            update::<ColorGradingSettings>(world, |s| s.enabled = value.as_bool())
+        },
+    },
+    Parameter {
+        name: "grading.intensity",
+        kind: ValueKind::Scalar,
+        apply: |world, value| {
+            update::<ColorGradingSettings>(world, |s| s.intensity = value.as_f32())
+        },
+    },
+];
+
+pub fn parameter(name: &str) -> Option<&'static Parameter> {
+    PARAMETERS.iter().find(|parameter| parameter.name == name)
+}
+
+// =============================== TIMELINE ===============================
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    /// Holds every key until the next one.
+    Step,
+    #[default]
+    Linear,
+    /// Eases in and out of every key.
+    Smooth,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Key {
+    /// Seconds from the start.
+    pub time: f32,
+    pub value: Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Track {
+    /// One of [`PARAMETERS`].
+    pub parameter: String,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    /// Ordered by time.
+    pub keys: Vec<Key>,
+}
+impl Track {
+    /// Holds the first and last key outside of them. Expects a validated
+    /// track.
+    pub fn sample(&self, time: f32) -> Value {
+        let next = self.keys.partition_point(|key| key.time <= time);
+        match (
+            next.checked_sub(1).map(|i| &self.keys[i]),
+            self.keys.get(next),
+        ) {
+            (Some(a), Some(b)) => {
+                let t = (time - a.time) / (b.time - a.time);
+                let t = match self.interpolation {
+                    Interpolation::Step => 0.0,
+                    Interpolation::Linear => t,
+                    Interpolation::Smooth => t * t * (3.0 - 2.0 * t),
+                };
+                a.value.lerp(&b.value, t)
+            }
+            (Some(last), None) => last.value,
+            (None, Some(first)) => first.value,
+            (None, None) => Value::Bool(false),
+        }
+    }
+}
+
+/// Missing fields take their defaults, so hand-written timelines stay short.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Timeline {
+    /// Seconds per frame while playing with a fixed step.
+    pub step: f32,
+    pub looping: bool,
+    pub tracks: Vec<Track>,
+    /// Flown under the tracks, explicit `camera.*` tracks override it.
+    pub camera_path: Option<CameraPath>,
+}
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            step: 1.0 / 60.0,
+            looping: false,
+            tracks: Vec::new(),
+            camera_path: None,
+        }
+    }
+}
+impl Timeline {
+    /// `None` when there is no timeline file.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let timeline: Self = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        timeline
+            .validate()
+            .with_context(|| format!("Invalid timeline in {}", path.display()))?;
+        Ok(Some(timeline))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.step.is_nan() || self.step <= 0.0 {
+            anyhow::bail!("The step has to be positive, not {}", self.step);
+        }
+        for (index, track) in self.tracks.iter().enumerate() {
+            let parameter = parameter(&track.parameter).with_context(|| {
+                format!(
+                    "Track {} animates unknown parameter '{}'",
+                    index, track.parameter
+                )
+            })?;
+            if track.keys.is_empty() {
+                anyhow::bail!("Track {} ({}) has no keys", index, track.parameter);
+            }
+            for (key_index, key) in track.keys.iter().enumerate() {
+                if key.value.kind() != parameter.kind {
+                    anyhow::bail!(
+                        "Key {} of track {} ({}) is a {}, the parameter takes a {}",
+                        key_index,
+                        index,
+                        track.parameter,
+                        key.value.kind().name(),
+                        parameter.kind.name()
+                    );
+                }
+            }
+            if track
+                .keys
+                .windows(2)
+                .any(|keys| keys[1].time <= keys[0].time)
+            {
+                anyhow::bail!(
+                    "Keys of track {} ({}) have to be in increasing time",
+                    index,
+                    track.parameter
+                );
+            }
+        }
+        if let Some(path) = &self.camera_path {
+            path.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Time of the last key, or the end of the camera path.
+    pub fn duration(&self) -> f32 {
+        let path_end = self
+            .camera_path
+            .as_ref()
+            .map_or(0.0, |path| path.start + path.duration());
+        self.tracks
+            .iter()
+            .filter_map(|track| track.keys.last())
+            .map(|key| key.time)
+            .fold(path_end, f32::max)
+    }
+
+    /// Sets every animated parameter to its value at `time`.
+    pub fn apply(&self, world: &mut World, time: f32) {
+        if let Some((eye, target)) = self
+            .camera_path
+            .as_ref()
+            .and_then(|path| path.pose(time - path.start))
+        {
+            camera(world, |camera| {
+                camera.eye = eye;
+                camera.target = target;
+            });
+        }
+        for track in &self.tracks {
+            if let Some(parameter) = parameter(&track.parameter) {
+                (parameter.apply)(world, track.sample(time));
+            }
+        }
+    }
+}
+
+// =============================== PLAYBACK ===============================
+#[derive(Resource, Clone, PartialEq)]
+pub struct TimelinePlayer {
+    pub path: PathBuf,
+    /// `None` without a file or when it failed to load.
+    pub timeline: Option<Timeline>,
+    pub error: Option<String>,
+    pub playing: bool,
+    /// Seconds from the start.
+    pub time: f32,
+    /// Advance by [`Timeline::step`] per frame and pin the scene clock to
+    /// it, instead of following the wall clock.
+    pub fixed_step: bool,
+    /// Apply the current time without advancing, after a seek or on the
+    /// first frame of playback.
+    seek: bool,
+}
+impl TimelinePlayer {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            timeline: None,
+            error: None,
+            playing: false,
+            time: 0.0,
+            fixed_step: true,
+            seek: false,
+        }
+    }
+
+    /// (Re)loads the file, keeping the current timeline when that fails.
+    pub fn load(&mut self) {
+        match Timeline::load(&self.path) {
+            Ok(timeline) => {
+                if let Some(timeline) = &timeline {
+                    info!(
+                        "Loaded {} with {} tracks over {:.2} s",
+                        self.path.display(),
+                        timeline.tracks.len(),
+                        timeline.duration()
+                    );
+                }
+                self.timeline = timeline;
+                self.error = None;
+                self.time = self.time.min(self.duration());
+                self.seek = true;
+            }
+            Err(e) => {
+                error!("Failed to load the timeline: {:?}", e);
+                self.error = Some(format!("{:#}", e));
+            }
+        }
+    }
+
+    /// Starts over from the beginning.
+    pub fn play(&mut self) {
+        self.time = 0.0;
+        self.playing = true;
+        self.seek = true;
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.duration());
+        self.seek = true;
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.timeline.as_ref().map_or(0.0, Timeline::duration)
+    }
+}
+
+pub fn timeline_system(world: &mut World) {
+    let delta = world.resource::<TimeContext>().delta;
+    world.resource_scope::<TimelinePlayer, _>(|world, mut player| {
+        let Some(timeline) = &player.timeline else {
+            world.resource_mut::<TimeContext>().fixed_step = None;
+            return;
+        };
+        let step = timeline.step;
+        let (looping, duration) = (timeline.looping, timeline.duration());
+
+        if player.playing && !player.seek {
+            player.time += delta;
+            if player.time > duration {
+                if looping && duration > 0.0 {
+                    player.time %= duration;
+                } else {
+                    player.time = duration;
+                    player.playing = false;
+                    info!("Timeline finished after {:.2} s", duration);
+                }
+            }
+        }
+        if player.playing || player.seek {
+            player.seek = false;
+            let player = &*player;
+            if let Some(timeline) = &player.timeline {
+                timeline.apply(world, player.time);
+            }
+        }
+
+        // Takes effect from the next frame on, which is the first to advance
+        let fixed_step = (player.playing && player.fixed_step).then_some(step);
+        let mut time = world.resource_mut::<TimeContext>();
+        if time.fixed_step != fixed_step {
+            time.fixed_step = fixed_step;
+        }
+    });
+}
+
+// =============================== PANEL ===============================
+fn timeline_panel(ctx: &egui::Context, world: &mut World) {
+    let mut player = world.resource::<TimelinePlayer>().clone();
+
+    egui::Window::new(tr("Timeline"))
+        .id(egui::Id::new("Timeline"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(trf!("File: {}", player.path.display()));
+                if ui.button(tr("Reload")).clicked() {
+                    player.load();
+                }
+            });
+            if let Some(error) = &player.error {
+                ui.colored_label(egui::Color32::RED, trf!("Load failed: {}", error));
+            }
+            let Some(timeline) = player.timeline.clone() else {
+                ui.label(tr("No timeline loaded"));
+                return;
+            };
+            let duration = timeline.duration();
+            ui.label(trf!(
+                "{} tracks over {:.2} s",
+                timeline.tracks.len(),
+                duration
+            ));
+
+            ui.horizontal(|ui| {
+                if player.playing {
+                    if ui.button(tr("Pause")).clicked() {
+                        player.playing = false;
+                    }
+                } else if ui.button(tr("Play")).clicked() {
+                    if player.time >= duration {
+                        player.play();
+                    } else {
+                        player.playing = true;
+                    }
+                }
+                if ui.button(tr("Restart")).clicked() {
+                    player.play();
+                }
+            });
+            let mut time = player.time;
+            if ui
+                .add(egui::Slider::new(&mut time, 0.0..=duration).suffix(" s"))
+                .changed()
+            {
+                player.seek(time);
+            }
+            ui.checkbox(
+                &mut player.fixed_step,
+                trf!("Fixed step, {:.2} ms per frame", timeline.step * 1000.0),
+            );
+            if let Some(timeline) = &mut player.timeline {
+                ui.checkbox(&mut timeline.looping, tr("Loop"));
+            }
+
+            ui.separator();
+            egui::Grid::new("timeline_tracks")
+                .striped(true)
+                .show(ui, |ui| {
+                    for track in &timeline.tracks {
+                        ui.label(&track.parameter);
+                        ui.label(trf!("{} keys", track.keys.len()));
+                        ui.label(track.sample(player.time).to_string());
+                        ui.end_row();
+                    }
+                });
+            ui.collapsing(tr("Parameters"), |ui| {
+                for parameter in PARAMETERS {
+                    ui.label(format!("{}: {}", parameter.name, parameter.kind.name()));
+                }
+            });
+        });
+
+    let mut current = world.resource_mut::<TimelinePlayer>();
+    if *current != player {
+        *current = player;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_interpolate_and_bad_timelines_are_rejected() {
+        let timeline: Timeline = serde_json::from_str(
+            r#"{"tracks": [
+                {"parameter": "camera.eye", "keys": [
+                    {"time": 1.0, "value": [0.0, 0.0, 0.0]},
+                    {"time": 3.0, "value": [4.0, 2.0, 0.0]}
+                ]},
+                {"parameter": "post.focal_distance", "interpolation": "smooth", "keys": [
+                    {"time": 0.0, "value": 0.0},
+                    {"time": 2.0, "value": 10.0}
+                ]},
+                {"parameter": "ssr.enabled", "keys": [
+                    {"time": 0.0, "value": false},
+                    {"time": 2.0, "value": true}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+        timeline.validate().unwrap();
+        assert_eq!(timeline.duration(), 3.0);
+        assert_eq!(timeline.step, 1.0 / 60.0);
+
+        let eye = &timeline.tracks[0];
+        assert_eq!(eye.sample(0.0), Value::Vec3([0.0; 3]));
+        assert_eq!(eye.sample(2.0), Value::Vec3([2.0, 1.0, 0.0]));
+        assert_eq!(eye.sample(9.0), Value::Vec3([4.0, 2.0, 0.0]));
+        let focus = &timeline.tracks[1];
+        assert_eq!(focus.sample(1.0), Value::Scalar(5.0));
+        assert!(focus.sample(0.5).as_f32() < 2.5, "eases in");
+        let ssr = &timeline.tracks[2];
+        assert_eq!(ssr.sample(1.99), Value::Bool(false));
+        assert_eq!(ssr.sample(2.0), Value::Bool(true));
+
+        let mut unknown = timeline.clone();
+        unknown.tracks[0].parameter = "camera.roll".to_string();
+        assert!(unknown.validate().is_err());
+        let mut wrong_kind = timeline.clone();
+        wrong_kind.tracks[2].keys[0].value = Value::Scalar(1.0);
+        assert!(wrong_kind.validate().is_err());
+        let mut unordered = timeline;
+        unordered.tracks[1].keys[1].time = 0.0;
+        assert!(unordered.validate().is_err());
+    }
+}
@@ -0,0 +1,310 @@
+//! Where the debug windows are, saved to a file and restored at startup, so
+//! the UI doesn't need arranging again every launch. The layout is written
+//! whenever it changed and covers the position, stacking order and collapsed
+//! state of every window. egui keeps the sizes of resized windows to itself,
+//! those start over.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use bevy_ecs::{
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use egui::collapsing_header::CollapsingState;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    i18n::{tr, trf},
+    pipeline::{
+        render::render_system,
+        ui::{EguiState, UiPanels},
+    },
+    time::TimeContext,
+};
+
+/// Loaded at startup and written on changes, in the working directory.
+pub const UI_LAYOUT_FILE: &str = "ui_layout.json";
+
+pub fn setup_ui_layout(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let path = PathBuf::from(UI_LAYOUT_FILE);
+    let ctx = world.resource::<EguiState>().renderer.context().clone();
+    let (saved, error) = match UiLayout::load(&path) {
+        Ok(Some(layout)) => match layout.apply(&ctx) {
+            Ok(()) => {
+                info!(
+                    "Restored {} windows from {}",
+                    layout.windows.len(),
+                    path.display()
+                );
+                (Some(layout), None)
+            }
+            Err(e) => {
+                warn!("Not restoring the UI layout: {:?}", e);
+                (None, Some(format!("{:#}", e)))
+            }
+        },
+        Ok(None) => (None, None),
+        Err(e) => {
+            warn!("Not restoring the UI layout: {:?}", e);
+            (None, Some(format!("{:#}", e)))
+        }
+    };
+    world.insert_resource(UiLayoutFile {
+        path,
+        saved,
+        error,
+        reset: false,
+        elapsed: 0.0,
+    });
+    world
+        .get_resource_or_insert_with(UiPanels::default)
+        .add_panel(ui_layout_panel);
+
+    schedule.add_systems(ui_layout_system.after(render_system));
+
+    Ok(())
+}
+
+// =============================== LAYOUT ===============================
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WindowLayout {
+    pub id: egui::Id,
+    /// Where the window's pivot, its left top corner for most, was.
+    pub position: [f32; 2],
+    pub pivot: egui::Align2,
+    /// Not collapsed to its title bar.
+    pub open: bool,
+}
+
+/// Every window, back to front.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UiLayout {
+    pub windows: Vec<WindowLayout>,
+}
+impl UiLayout {
+    /// `None` when there is no layout file.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let layout = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(layout))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// The windows egui placed so far.
+    pub fn capture(ctx: &egui::Context) -> Self {
+        let layers: Vec<egui::LayerId> = ctx.memory(|memory| {
+            memory
+                .layer_ids()
+                .filter(|layer| layer.order == egui::Order::Middle)
+                .collect()
+        });
+        let windows = layers
+            .into_iter()
+            .filter_map(|layer| {
+                let state = egui::AreaState::load(ctx, layer.id)?;
+                let position = state.pivot_pos?;
+                let open = CollapsingState::load(ctx, layer.id.with("collapsing"))
+                    .is_none_or(|collapsing| collapsing.is_open());
+                Some(WindowLayout {
+                    id: layer.id,
+                    position: [position.x, position.y],
+                    pivot: state.pivot,
+                    open,
+                })
+            })
+            .collect();
+        Self { windows }
+    }
+
+    /// Puts the windows back where they were. egui only lets its area memory
+    /// be replaced as a whole, and only deserialized, so the layout goes
+    /// through the same JSON egui would read it from.
+    pub fn apply(&self, ctx: &egui::Context) -> Result<()> {
+        let mut states = serde_json::Map::new();
+        let mut order = Vec::with_capacity(self.windows.len());
+        for window in &self.windows {
+            let state = egui::AreaState {
+                pivot_pos: Some(egui::pos2(window.position[0], window.position[1])),
+                pivot: window.pivot,
+                size: None,
+                interactable: true,
+                last_became_visible_at: None,
+            };
+            states.insert(window.id.value().to_string(), serde_json::to_value(state)?);
+            order.push(egui::LayerId::new(egui::Order::Middle, window.id));
+        }
+        let areas = serde_json::from_value(serde_json::json!({
+            "areas": states,
+            "order": order,
+        }))
+        .context("egui didn't take the window positions")?;
+        ctx.memory_mut(|memory| *memory.areas_mut() = areas);
+
+        for window in &self.windows {
+            let id = window.id.with("collapsing");
+            let mut collapsing = CollapsingState::load_with_default_open(ctx, id, window.open);
+            collapsing.set_open(window.open);
+            collapsing.store(ctx);
+        }
+        Ok(())
+    }
+
+    /// Forgets where every window was, they open where their code puts
+    /// them from the next frame on.
+    pub fn reset(ctx: &egui::Context) {
+        let windows = Self::capture(ctx).windows;
+        ctx.memory_mut(|memory| *memory.areas_mut() = Default::default());
+        for window in windows {
+            if let Some(collapsing) = CollapsingState::load(ctx, window.id.with("collapsing")) {
+                collapsing.remove(ctx);
+            }
+        }
+    }
+}
+
+// =============================== FILE ===============================
+#[derive(Resource)]
+pub struct UiLayoutFile {
+    pub path: PathBuf,
+    /// What the file has, to only write it when the layout changed.
+    pub saved: Option<UiLayout>,
+    pub error: Option<String>,
+    /// Reset before the next frame.
+    pub reset: bool,
+    elapsed: f32,
+}
+impl UiLayoutFile {
+    /// Seconds between checks for a changed layout.
+    const POLL_INTERVAL: f32 = 1.0;
+}
+
+/// Runs between frames, so the layout is never changed halfway through one.
+pub fn ui_layout_system(
+    time: Res<TimeContext>,
+    ui: Res<EguiState>,
+    mut file: ResMut<UiLayoutFile>,
+) {
+    let ctx = ui.renderer.context();
+    if file.reset {
+        file.reset = false;
+        UiLayout::reset(ctx);
+        info!("Reset the UI layout");
+    }
+
+    file.elapsed += time.delta;
+    if file.elapsed < UiLayoutFile::POLL_INTERVAL {
+        return;
+    }
+    file.elapsed = 0.0;
+    let layout = UiLayout::capture(ctx);
+    if file.saved.as_ref() == Some(&layout) {
+        return;
+    }
+    match layout.save(&file.path) {
+        Ok(()) => file.error = None,
+        Err(e) => {
+            error!("Failed to save the UI layout: {:?}", e);
+            file.error = Some(format!("{:#}", e));
+        }
+    }
+    // Also after a failure, so it isn't retried every second
+    file.saved = Some(layout);
+}
+
+fn ui_layout_panel(ctx: &egui::Context, world: &mut World) {
+    let mut file = world.resource_mut::<UiLayoutFile>();
+    let mut reset = false;
+
+    egui::Window::new(tr("UI layout"))
+        .id(egui::Id::new("UI layout"))
+        .default_open(false)
+        .show(ctx, |ui| {
+            ui.label(trf!("File: {}", file.path.display()));
+            ui.label(trf!(
+                "{} windows remembered",
+                file.saved.as_ref().map_or(0, |layout| layout.windows.len())
+            ));
+            reset = ui
+                .button(tr("Reset layout"))
+                .on_hover_text(tr("Windows go back to where they first open"))
+                .clicked();
+            if let Some(error) = &file.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        });
+
+    if reset {
+        file.reset = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(ctx: &egui::Context, default_x: f32) {
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            for (name, y) in [("First", 10.0), ("Second", 200.0)] {
+                egui::Window::new(name)
+                    .id(egui::Id::new(name))
+                    .default_pos([default_x, y])
+                    .show(ctx, |ui| ui.label(name));
+            }
+        });
+    }
+
+    #[test]
+    fn layouts_round_trip_through_egui() {
+        let ctx = egui::Context::default();
+        frame(&ctx, 40.0);
+        frame(&ctx, 40.0);
+        let second = egui::Id::new("Second");
+        ctx.memory_mut(|memory| {
+            memory.areas_mut().move_to_top(egui::LayerId::new(
+                egui::Order::Middle,
+                egui::Id::new("First"),
+            ))
+        });
+        let mut collapsed = CollapsingState::load(&ctx, second.with("collapsing")).unwrap();
+        collapsed.set_open(false);
+        collapsed.store(&ctx);
+        frame(&ctx, 40.0);
+
+        let layout = UiLayout::capture(&ctx);
+        assert_eq!(layout.windows.len(), 2);
+        assert_eq!(layout.windows[1].id, egui::Id::new("First"));
+        assert_eq!(layout.windows[1].position, [40.0, 10.0]);
+        assert!(!layout.windows[0].open);
+        let json = serde_json::to_string(&layout).unwrap();
+        let parsed: UiLayout = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, layout);
+
+        // A new run puts the windows where the layout has them instead of
+        // where the code does
+        let restored = egui::Context::default();
+        parsed.apply(&restored).unwrap();
+        frame(&restored, 300.0);
+        assert_eq!(UiLayout::capture(&restored), layout);
+
+        UiLayout::reset(&restored);
+        frame(&restored, 300.0);
+        let reset = UiLayout::capture(&restored);
+        assert!(reset.windows.iter().all(|window| window.open));
+        assert!(reset
+            .windows
+            .iter()
+            .all(|window| window.position[0] == 300.0));
+    }
+}
@@ -1,6 +1,12 @@
-use crate::gpu::GpuContext;
+use crate::gpu::{GpuContext, SurfaceChanged};
 use anyhow::Result;
-use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+use bevy_ecs::{
+    observer::Trigger,
+    schedule::Schedule,
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+use serde::{Deserialize, Serialize};
 use wgpu::util::DeviceExt;
 
 pub fn setup_uniforms(world: &mut World, schedule: &mut Schedule) -> Result<()> {
@@ -10,10 +16,22 @@ pub fn setup_uniforms(world: &mut World, schedule: &mut Schedule) -> Result<()>
 
     let uniforms = Uniforms::new(gpu);
     world.insert_resource(uniforms);
+    world.add_observer(uniforms_surface_changed_observer);
 
     Ok(())
 }
 
+/// Shaders encode their output by hand when the surface isn't sRGB.
+fn uniforms_surface_changed_observer(
+    trigger: Trigger<SurfaceChanged>,
+    gpu: Res<GpuContext>,
+    mut uniforms: ResMut<Uniforms>,
+) {
+    if trigger.event().format_changed() {
+        uniforms.update_srgb_surface(&gpu);
+    }
+}
+
 #[derive(Resource)]
 pub struct Uniforms {
     pub data: UniformsData,
@@ -35,11 +53,38 @@ impl Uniforms {
 
         Self { data, buffer }
     }
+    pub fn update_srgb_surface(&mut self, gpu: &GpuContext) {
+        self.data.srgb_surface = if gpu.config.format.is_srgb() {
+            1.0
+        } else {
+            0.0
+        };
+        gpu.queue
+            .write_buffer(&self.buffer, 0, self.data.as_bytes());
+    }
     pub fn update_resolution(&mut self, gpu: &GpuContext, resolution: [f32; 2]) {
         self.data.resolution = resolution;
         gpu.queue
             .write_buffer(&self.buffer, 0, self.data.as_bytes());
     }
+    pub fn update_debug_view(&mut self, gpu: &GpuContext, view: DebugView) {
+        self.data.debug_view = view as u32;
+        gpu.queue
+            .write_buffer(&self.buffer, 0, self.data.as_bytes());
+    }
+}
+
+/// What the frame shows in place of the shaded scene. The present shader
+/// skips its effects for anything but `None`, so the colors stay exact.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DebugView {
+    #[default]
+    None = 0,
+    /// Fragments rasterized per pixel.
+    Overdraw = 1,
+    /// Share of the shaded 2x2 quad lanes that land on covered pixels.
+    QuadOccupancy = 2,
 }
 
 #[repr(C)]
@@ -47,7 +92,8 @@ impl Uniforms {
 pub struct UniformsData {
     pub resolution: [f32; 2],
     pub srgb_surface: f32,
-    pub _padding: f32, // Add padding to match 16-byte alignment
+    /// A [`DebugView`].
+    pub debug_view: u32,
 }
 
 impl UniformsData {
@@ -55,7 +101,7 @@ impl UniformsData {
         Self {
             resolution,
             srgb_surface: if srgb_surface { 1.0 } else { 0.0 },
-            _padding: 0.0,
+            debug_view: DebugView::None as u32,
         }
     }
 
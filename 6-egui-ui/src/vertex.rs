@@ -1,3 +1,5 @@
+use std::{collections::HashMap, ops::Range};
+
 use anyhow::Result;
 use bevy_ecs::{
     schedule::Schedule,
@@ -29,13 +31,13 @@ pub fn setup_vertex_buffers(world: &mut World, schedule: &mut Schedule) -> Resul
             contents: bytemuck::cast_slice(DEPTH_VERTICES),
             usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         });
-    let num_depth_vertices = DEPTH_VERTICES.len() as u32;
+    let depth_index_buffer = IndexBuffer::new(&gpu.device, "Depth Index Buffer", DEPTH_INDICES);
 
     world.insert_resource(VertexBuffers {
         vertex_buffer,
         depth_vertex_buffer,
+        depth_index_buffer,
         num_vertices,
-        num_depth_vertices,
     });
 
     schedule.add_systems(rotate_vertices_system);
@@ -61,8 +63,8 @@ pub fn rotate_vertices_system(
 pub struct VertexBuffers {
     pub vertex_buffer: wgpu::Buffer,
     pub depth_vertex_buffer: wgpu::Buffer,
+    pub depth_index_buffer: IndexBuffer,
     pub num_vertices: u32,
-    pub num_depth_vertices: u32,
 }
 
 // =================================== VERTEX ===================================
@@ -159,16 +161,12 @@ pub const DEPTH_VERTICES: &[DepthVertex] = &[
     DepthVertex {
         position: [1.0, -1.0, 0.0],
     },
-    DepthVertex {
-        position: [1.0, -1.0, 0.0],
-    },
     DepthVertex {
         position: [1.0, 1.0, 0.0],
     },
-    DepthVertex {
-        position: [-1.0, 1.0, 0.0],
-    },
 ];
+/// Two triangles sharing the diagonal.
+pub const DEPTH_INDICES: &[u16] = &[0, 1, 2, 2, 3, 0];
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -189,3 +187,273 @@ impl DepthVertex {
         }
     }
 }
+
+// ========================== MESH VERTEX ==========================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+impl MeshVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+    const TRIANGLE_ATTRIBS: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+        0 => Float32x3, 1 => Float32x3,
+        2 => Float32x3, 3 => Float32x3,
+        4 => Float32x3, 5 => Float32x3,
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    /// Reads a whole triangle of a non-indexed mesh per instance, as the
+    /// position and normal of each of its three corners.
+    pub fn triangle_desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: 3 * mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::TRIANGLE_ATTRIBS,
+        }
+    }
+}
+
+/// Merges identical vertices of a triangle list, returning the unique ones and
+/// the indices that rebuild the list from them.
+pub fn weld(vertices: &[MeshVertex]) -> (Vec<MeshVertex>, Vec<u32>) {
+    let mut unique = Vec::new();
+    let mut lookup = HashMap::new();
+    let indices = vertices
+        .iter()
+        .map(|vertex| {
+            // Bitwise, so -0.0 and 0.0 stay apart, which never matters for a
+            // corner that was written out twice
+            let key: [u32; 6] = bytemuck::cast(*vertex);
+            *lookup.entry(key).or_insert_with(|| {
+                unique.push(*vertex);
+                unique.len() as u32 - 1
+            })
+        })
+        .collect();
+    (unique, indices)
+}
+
+/// The triangle list an indexed mesh draws, for consumers that read whole
+/// triangles straight from a vertex buffer.
+pub fn unweld(vertices: &[MeshVertex], indices: &[u32]) -> Vec<MeshVertex> {
+    indices
+        .iter()
+        .map(|&index| vertices[index as usize])
+        .collect()
+}
+
+/// Unit cube centered on the origin, two counter-clockwise triangles per face.
+pub fn cube_vertices() -> Vec<MeshVertex> {
+    let faces = [
+        (glam::Vec3::X, glam::Vec3::Y),
+        (glam::Vec3::NEG_X, glam::Vec3::Y),
+        (glam::Vec3::Y, glam::Vec3::Z),
+        (glam::Vec3::NEG_Y, glam::Vec3::Z),
+        (glam::Vec3::Z, glam::Vec3::Y),
+        (glam::Vec3::NEG_Z, glam::Vec3::Y),
+    ];
+
+    faces
+        .iter()
+        .flat_map(|(normal, up)| {
+            let right = up.cross(*normal);
+            let corner = |u: f32, v: f32| MeshVertex {
+                position: (*normal * 0.5 + right * u + *up * v).to_array(),
+                normal: normal.to_array(),
+            };
+            [
+                corner(-0.5, -0.5),
+                corner(0.5, -0.5),
+                corner(0.5, 0.5),
+                corner(-0.5, -0.5),
+                corner(0.5, 0.5),
+                corner(-0.5, 0.5),
+            ]
+        })
+        .collect()
+}
+
+/// Unit quad in the XY plane facing +Z.
+pub fn quad_vertices() -> Vec<MeshVertex> {
+    let corner = |x: f32, y: f32| MeshVertex {
+        position: [x, y, 0.0],
+        normal: [0.0, 0.0, 1.0],
+    };
+    vec![
+        corner(-0.5, -0.5),
+        corner(0.5, -0.5),
+        corner(0.5, 0.5),
+        corner(-0.5, -0.5),
+        corner(0.5, 0.5),
+        corner(-0.5, 0.5),
+    ]
+}
+
+// ========================== INDICES ==========================
+/// An index type an [`IndexBuffer`] can hold.
+pub trait Index: bytemuck::Pod {
+    const FORMAT: wgpu::IndexFormat;
+}
+impl Index for u16 {
+    const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint16;
+}
+impl Index for u32 {
+    const FORMAT: wgpu::IndexFormat = wgpu::IndexFormat::Uint32;
+}
+
+/// Indices into a vertex buffer, so corners shared by several triangles are
+/// stored once.
+pub struct IndexBuffer {
+    pub buffer: wgpu::Buffer,
+    pub format: wgpu::IndexFormat,
+    pub count: u32,
+}
+impl IndexBuffer {
+    pub fn new<I: Index>(device: &wgpu::Device, label: &str, indices: &[I]) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Self {
+            buffer,
+            format: I::FORMAT,
+            count: indices.len() as u32,
+        }
+    }
+
+    /// Binds the indices, the vertex buffers are up to the caller.
+    pub fn bind(&self, render_pass: &mut wgpu::RenderPass) {
+        render_pass.set_index_buffer(self.buffer.slice(..), self.format);
+    }
+
+    /// Draws every index, after [`IndexBuffer::bind`].
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass, instances: Range<u32>) {
+        render_pass.draw_indexed(0..self.count, 0, instances);
+    }
+}
+
+// ========================== DEBUG VERTEX ==========================
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl DebugVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// ========================== HUD INSTANCE ==========================
+/// One screen space quad, see [`HudQuad`](crate::pipeline::hud::HudQuad).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct HudInstance {
+    /// Left, top, width and height in surface pixels.
+    pub rect: [f32; 4],
+    /// Region of the texture, as min and max UV.
+    pub uv_rect: [f32; 4],
+    pub color: [f32; 4],
+    pub border_color: [f32; 4],
+    /// Corner radius and border width in pixels, then the nine-slice border
+    /// in pixels and as a fraction of the texture region.
+    pub shape: [f32; 4],
+}
+
+impl HudInstance {
+    const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Float32x4,
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Float32x4
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// ========================== PATH VERTEX ==========================
+/// Vertex of a tessellated vector path, see
+/// [`VectorPaths`](crate::pipeline::paths::VectorPaths).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PathVertex {
+    /// Surface pixels from the top left.
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+    /// Start and end of the stroke segment this vertex belongs to.
+    pub segment: [f32; 4],
+    /// Half the stroke width, zero for fill triangles.
+    pub half_width: f32,
+}
+
+impl PathVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x4,
+        2 => Float32x4,
+        3 => Float32
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn welding_the_cube_keeps_its_triangles() {
+        let cube = cube_vertices();
+        let (vertices, indices) = weld(&cube);
+        // Four corners per face, the normals keep the faces apart
+        assert_eq!(vertices.len(), 24);
+        assert_eq!(indices.len(), cube.len());
+        let triangles = unweld(&vertices, &indices);
+        let bytes = |vertices: &[MeshVertex]| bytemuck::cast_slice::<_, u8>(vertices).to_vec();
+        assert_eq!(bytes(&triangles), bytes(&cube));
+    }
+}
@@ -0,0 +1,22 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, world::World};
+use playground_app::FrameCapture;
+use winit::window::Window;
+
+pub use playground_core::GpuContext;
+
+pub fn setup_gpu(world: &mut World, _schedule: &mut Schedule, window: Arc<Window>) -> Result<()> {
+    let mut gpu = GpuContext::new(window)?;
+    // Frame captures read the surface back
+    if world.contains_resource::<FrameCapture>() {
+        let capabilities = gpu.surface.get_capabilities(&gpu.adapter);
+        if capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            gpu.config.usage |= wgpu::TextureUsages::COPY_SRC;
+            gpu.surface.configure(&gpu.device, &gpu.config);
+        }
+    }
+    world.insert_resource(gpu);
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use bevy_ecs::{observer::Trigger, schedule::Schedule, system::ResMut, world::World};
+use gpu::{setup_gpu, GpuContext};
+use pipeline::{
+    depth::{setup_depth, DepthTexture},
+    render::setup_rendering,
+    setup_model_pipeline,
+};
+use playground_app::{App, WindowTriggerEvent};
+use tracing_subscriber::{layer::SubscriberExt, EnvFilter};
+use winit::{event::WindowEvent, window::Window};
+
+mod gpu;
+mod mesh;
+mod pipeline;
+
+// =============================== SETUP ===============================
+fn setup(world: &mut World, schedule: &mut Schedule, window: Arc<Window>) -> Result<()> {
+    setup_gpu(world, schedule, window).context("Failed to setup GPU")?;
+    setup_model_pipeline(world, schedule).context("Failed to setup model pipeline")?;
+    setup_depth(world, schedule).context("Failed to setup depth pipeline")?;
+    setup_rendering(world, schedule).context("Failed to setup rendering")?;
+
+    world.add_observer(
+        |trigger: Trigger<WindowTriggerEvent>,
+         mut gpu: ResMut<GpuContext>,
+         mut depth: ResMut<DepthTexture>| {
+            if let WindowEvent::Resized(size) = trigger.event().event {
+                gpu.resize(&size);
+                depth.resize(&gpu.device, gpu.config.width, gpu.config.height);
+            }
+        },
+    );
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let env_filter = EnvFilter::from_default_env()
+        .add_directive("wgpu=warn".parse().unwrap())
+        .add_directive("winit=warn".parse().unwrap())
+        .add_directive("naga=warn".parse().unwrap())
+        .add_directive("debug".parse().unwrap());
+
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::registry()
+            .with(env_filter)
+            .with(tracing_subscriber::fmt::layer()),
+    )
+    .expect("setup tracing");
+    better_panic::install();
+
+    App::new("glTF model").run(setup)?;
+    Ok(())
+}
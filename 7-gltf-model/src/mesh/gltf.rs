@@ -0,0 +1,85 @@
+//! glTF models, from `.gltf` files with their buffers next to them or
+//! embedded, and from `.glb` files. The triangle primitives the default
+//! scene draws are merged into one mesh, with their node transforms applied.
+//! Materials are ignored, the depth pass has no use for them.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use glam::{Mat4, Vec3};
+use tracing::warn;
+
+use super::{Mesh, MeshVertex};
+
+pub fn load(path: &Path) -> Result<Mesh> {
+    let ::gltf::Gltf { document, blob } =
+        ::gltf::Gltf::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let buffers = ::gltf::import_buffers(&document, path.parent(), blob)
+        .with_context(|| format!("Failed to read the buffers of {}", path.display()))?;
+    from_document(&document, &buffers).with_context(|| format!("Failed to load {}", path.display()))
+}
+
+fn from_document(document: &::gltf::Document, buffers: &[::gltf::buffer::Data]) -> Result<Mesh> {
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .context("The model has no scene")?;
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut pending: Vec<_> = scene.nodes().map(|node| (node, Mat4::IDENTITY)).collect();
+    while let Some((node, parent)) = pending.pop() {
+        let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+                    warn!(
+                        "Skipping a {:?} primitive of mesh {}, only triangles are supported",
+                        primitive.mode(),
+                        mesh.index()
+                    );
+                    continue;
+                }
+                let reader =
+                    primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+                let Some(positions) = reader.read_positions() else {
+                    warn!(
+                        "Skipping a primitive of mesh {} without positions",
+                        mesh.index()
+                    );
+                    continue;
+                };
+                let first = vertices.len() as u32;
+                vertices.extend(positions.map(|position| MeshVertex {
+                    position: transform.transform_point3(Vec3::from(position)).to_array(),
+                }));
+                match reader.read_indices() {
+                    Some(read) => indices.extend(read.into_u32().map(|index| first + index)),
+                    None => indices.extend(first..vertices.len() as u32),
+                }
+            }
+        }
+        pending.extend(node.children().map(|child| (child, transform)));
+    }
+
+    Mesh::new(vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gem_model_merges_its_nodes() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/models/gem.gltf");
+        let mesh = load(&path).unwrap();
+        // The pedestal's 36 vertices and 60 indices, then the gem's
+        assert_eq!(mesh.vertices.len(), 60);
+        assert_eq!(mesh.indices.len(), 84);
+        assert!(mesh.indices[60..].iter().all(|&index| index >= 36));
+
+        // The gem sits on top, moved and stretched by its node
+        assert!((mesh.max.y - 2.48).abs() < 1e-5);
+        assert_eq!(mesh.min.y, 0.0);
+    }
+}
@@ -0,0 +1,87 @@
+//! Meshes read from model files, ready to go up to the GPU.
+
+pub mod gltf;
+
+use anyhow::Result;
+use bevy_ecs::system::Resource;
+use glam::Vec3;
+use wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+}
+impl MeshVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x3];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+/// Every triangle of a model, in model space.
+pub struct Mesh {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+    pub min: Vec3,
+    pub max: Vec3,
+}
+impl Mesh {
+    /// Checks the indices and finds the bounds.
+    pub fn new(vertices: Vec<MeshVertex>, indices: Vec<u32>) -> Result<Self> {
+        if indices.is_empty() {
+            anyhow::bail!("The mesh has no triangles");
+        }
+        if let Some(index) = indices
+            .iter()
+            .find(|&&index| index as usize >= vertices.len())
+        {
+            anyhow::bail!("Index {} is past the {} vertices", index, vertices.len());
+        }
+        let (min, max) = vertices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), vertex| {
+                let position = Vec3::from(vertex.position);
+                (min.min(position), max.max(position))
+            },
+        );
+        Ok(Self {
+            vertices,
+            indices,
+            min,
+            max,
+        })
+    }
+
+    pub fn upload(&self, device: &wgpu::Device) -> GpuMesh {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("model_vertices"),
+            contents: bytemuck::cast_slice(&self.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("model_indices"),
+            contents: bytemuck::cast_slice(&self.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        GpuMesh {
+            vertex_buffer,
+            index_buffer,
+            count: self.indices.len() as u32,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct GpuMesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub count: u32,
+}
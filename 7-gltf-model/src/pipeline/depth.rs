@@ -0,0 +1,184 @@
+use anyhow::Result;
+use bevy_ecs::{
+    prelude::resource_changed,
+    schedule::{IntoSystemConfigs, Schedule},
+    system::{Res, ResMut, Resource},
+    world::World,
+};
+
+use crate::gpu::GpuContext;
+
+use super::CameraBuffer;
+
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+pub fn setup_depth(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let camera = world
+        .get_resource::<CameraBuffer>()
+        .ok_or_else(|| anyhow::anyhow!("CameraBuffer resource not found"))?;
+
+    let depth_texture = DepthTexture::new(&gpu.device, gpu.config.width, gpu.config.height);
+    let depth_pipeline = DepthPipeline::new(gpu, &depth_texture, camera);
+    world.insert_resource(depth_texture);
+    world.insert_resource(depth_pipeline);
+
+    schedule.add_systems(depth_changed_system.run_if(resource_changed::<DepthTexture>));
+
+    Ok(())
+}
+
+/// The bind group still points at the old texture after a resize.
+pub fn depth_changed_system(
+    gpu: Res<GpuContext>,
+    depth_texture: Res<DepthTexture>,
+    camera: Res<CameraBuffer>,
+    mut depth_pipeline: ResMut<DepthPipeline>,
+) {
+    depth_pipeline.bind_group =
+        DepthPipeline::bind_group(&gpu, &depth_pipeline.layout, &depth_texture, &camera);
+}
+
+// =============================== TEXTURE ===============================
+/// What the model pass draws into, the size of the surface.
+#[derive(Resource)]
+pub struct DepthTexture {
+    #[allow(unused)]
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+impl DepthTexture {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        Self { texture, view }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        *self = Self::new(device, width, height);
+    }
+}
+
+// =============================== PIPELINE ===============================
+/// Shows the depth texture on the surface, near white and far black.
+#[derive(Resource)]
+pub struct DepthPipeline {
+    pub render_pipeline: wgpu::RenderPipeline,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+impl DepthPipeline {
+    pub fn new(gpu: &GpuContext, depth_texture: &DepthTexture, camera: &CameraBuffer) -> Self {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("depth_shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/depth.wgsl").into()),
+            });
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("depth_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("depth_pipeline_layout"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+        let render_pipeline = gpu
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("depth_pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: gpu.config.format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+        let bind_group = Self::bind_group(gpu, &layout, depth_texture, camera);
+
+        Self {
+            render_pipeline,
+            layout,
+            bind_group,
+        }
+    }
+
+    fn bind_group(
+        gpu: &GpuContext,
+        layout: &wgpu::BindGroupLayout,
+        depth_texture: &DepthTexture,
+        camera: &CameraBuffer,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("depth_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&depth_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: camera.buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+}
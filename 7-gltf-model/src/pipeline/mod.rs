@@ -0,0 +1,206 @@
+use std::{path::PathBuf, time::Instant};
+
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::Schedule,
+    system::{Res, Resource},
+    world::World,
+};
+use glam::{Mat4, Vec3};
+use tracing::info;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    gpu::GpuContext,
+    mesh::{gltf, MeshVertex},
+};
+
+use self::depth::DEPTH_FORMAT;
+
+pub mod depth;
+pub mod render;
+
+/// Shown when no model is passed on the command line.
+const DEFAULT_MODEL: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/models/gem.gltf");
+
+/// Loads the `.gltf` or `.glb` named by the first argument.
+pub fn setup_model_pipeline(world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    let path = std::env::args_os()
+        .nth(1)
+        .map_or_else(|| PathBuf::from(DEFAULT_MODEL), PathBuf::from);
+    let mesh = gltf::load(&path)?;
+    info!(
+        "Loaded {} with {} triangles",
+        path.display(),
+        mesh.indices.len() / 3
+    );
+
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+    let camera = Camera::framing(mesh.min, mesh.max);
+    let camera_buffer = CameraBuffer::new(gpu, &camera.uniform(1.0));
+    let pipeline = ModelPipeline::new(gpu, &camera_buffer);
+    let gpu_mesh = mesh.upload(&gpu.device);
+
+    world.insert_resource(camera);
+    world.insert_resource(camera_buffer);
+    world.insert_resource(pipeline);
+    world.insert_resource(gpu_mesh);
+
+    schedule.add_systems(camera_system);
+
+    Ok(())
+}
+
+/// Turns around the model, a full circle every [`Camera::TURN_SECONDS`].
+pub fn camera_system(gpu: Res<GpuContext>, camera: Res<Camera>, buffer: Res<CameraBuffer>) {
+    let aspect = gpu.config.width as f32 / gpu.config.height.max(1) as f32;
+    gpu.queue.write_buffer(
+        &buffer.buffer,
+        0,
+        bytemuck::cast_slice(&[camera.uniform(aspect)]),
+    );
+}
+
+// =============================== CAMERA ===============================
+#[derive(Resource)]
+pub struct Camera {
+    pub center: Vec3,
+    /// Of a sphere around the whole model.
+    pub radius: f32,
+    pub start: Instant,
+}
+impl Camera {
+    const TURN_SECONDS: f32 = 12.0;
+
+    pub fn framing(min: Vec3, max: Vec3) -> Self {
+        Self {
+            center: (min + max) / 2.0,
+            radius: ((max - min).length() / 2.0).max(1e-3),
+            start: Instant::now(),
+        }
+    }
+
+    /// Near and far hug the model, so the depth view uses its whole range.
+    pub fn uniform(&self, aspect: f32) -> CameraUniform {
+        let angle = self.start.elapsed().as_secs_f32() / Self::TURN_SECONDS * std::f32::consts::TAU;
+        let distance = self.radius * 3.0;
+        let eye = self.center + Vec3::new(angle.sin(), 0.4, angle.cos()).normalize() * distance;
+        let near = distance - self.radius * 1.5;
+        let far = distance + self.radius * 1.5;
+        let view = Mat4::look_at_rh(eye, self.center, Vec3::Y);
+        let projection = Mat4::perspective_rh(45f32.to_radians(), aspect, near, far);
+        CameraUniform {
+            view_proj: (projection * view).to_cols_array_2d(),
+            near,
+            far,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Matches `Camera` in model.wgsl and depth.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view_proj: [[f32; 4]; 4],
+    pub near: f32,
+    pub far: f32,
+    pub _padding: [f32; 2],
+}
+
+#[derive(Resource)]
+pub struct CameraBuffer {
+    pub buffer: wgpu::Buffer,
+}
+impl CameraBuffer {
+    pub fn new(gpu: &GpuContext, uniform: &CameraUniform) -> Self {
+        let buffer = gpu
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("camera_buffer"),
+                contents: bytemuck::cast_slice(&[*uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        Self { buffer }
+    }
+}
+
+// =============================== PIPELINE ===============================
+/// Draws the model into the depth texture, with no color target at all.
+#[derive(Resource)]
+pub struct ModelPipeline {
+    pub render_pipeline: wgpu::RenderPipeline,
+    pub bind_group: wgpu::BindGroup,
+}
+impl ModelPipeline {
+    pub fn new(gpu: &GpuContext, camera: &CameraBuffer) -> Self {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("model_shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/model.wgsl").into()),
+            });
+        let bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("model_bind_group_layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("model_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.buffer.as_entire_binding(),
+            }],
+        });
+        let layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("model_pipeline_layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let render_pipeline = gpu
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("model_pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[MeshVertex::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: None,
+                // Mirrored nodes flip the winding, so nothing is culled
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        Self {
+            render_pipeline,
+            bind_group,
+        }
+    }
+}
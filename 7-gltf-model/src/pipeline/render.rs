@@ -0,0 +1,94 @@
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::Schedule,
+    system::{Res, ResMut},
+    world::World,
+};
+use playground_app::FrameCapture;
+use tracing::error;
+
+use crate::gpu::GpuContext;
+
+use super::{
+    depth::{DepthPipeline, DepthTexture},
+    ModelPipeline,
+};
+use crate::mesh::GpuMesh;
+
+pub fn setup_rendering(_world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    schedule.add_systems(render_system);
+    Ok(())
+}
+
+pub fn render_system(
+    gpu: Res<GpuContext>,
+    (model, mesh): (Res<ModelPipeline>, Res<GpuMesh>),
+    (depth, depth_texture): (Res<DepthPipeline>, Res<DepthTexture>),
+    mut capture: Option<ResMut<FrameCapture>>,
+) {
+    let mut f = || -> Result<()> {
+        let output = gpu.surface.get_current_texture()?;
+        let view = output.texture.create_view(&Default::default());
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("render_encoder"),
+            });
+
+        // MODEL DEPTH
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("model_render_pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&model.render_pipeline);
+            render_pass.set_bind_group(0, &model.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..mesh.count, 0, 0..1);
+        }
+
+        // DEPTH VIEW
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("depth_render_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&depth.render_pipeline);
+            render_pass.set_bind_group(0, &depth.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(capture) = capture.as_mut().filter(|capture| capture.is_due()) {
+            capture.capture(&gpu.device, &gpu.queue, &output.texture);
+        }
+        output.present();
+
+        Ok(())
+    };
+
+    if let Err(e) = f() {
+        error!("Error during rendering: {:?}", e);
+    }
+}
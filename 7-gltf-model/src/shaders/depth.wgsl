@@ -0,0 +1,26 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    near: f32,
+    far: f32,
+}
+
+@group(0) @binding(0)
+var t_depth: texture_depth_2d;
+@group(0) @binding(1)
+var<uniform> camera: Camera;
+
+// One triangle that covers the screen, no vertex buffer needed
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let depth = textureLoad(t_depth, vec2<i32>(position.xy), 0);
+    // Back to the distance from the camera, the raw values crowd near 1
+    let distance = camera.near * camera.far / (camera.far - depth * (camera.far - camera.near));
+    let shade = 1.0 - saturate((distance - camera.near) / (camera.far - camera.near));
+    return vec4<f32>(vec3<f32>(shade), 1.0);
+}
@@ -0,0 +1,14 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+    near: f32,
+    far: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// Depth only, there is no fragment shader
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return camera.view_proj * vec4<f32>(position, 1.0);
+}
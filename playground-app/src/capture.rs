@@ -0,0 +1,175 @@
+//! Frame capture for automated runs. With `PLAYGROUND_CAPTURE` set the harness
+//! hides the window, lets the example render a fixed number of frames, and
+//! exits once the example saved the surface to the given PNG.
+
+use std::{io, path::PathBuf};
+
+use bevy_ecs::system::Resource;
+use tracing::{error, info};
+
+/// Path of the PNG to write.
+pub const CAPTURE_ENV: &str = "PLAYGROUND_CAPTURE";
+/// Frame to capture, counting from 1.
+pub const CAPTURE_FRAMES_ENV: &str = "PLAYGROUND_CAPTURE_FRAMES";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CaptureError {
+    #[error("Can't capture a {0:?} surface")]
+    UnsupportedFormat(wgpu::TextureFormat),
+    #[error("The surface wasn't configured with COPY_SRC")]
+    NotCopyable,
+    #[error("Failed to create {}: {source}", path.display())]
+    CreateDir { path: PathBuf, source: io::Error },
+    #[error("Failed to write {}: {source}", path.display())]
+    Write {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    #[error("Frame capture failed: {0}")]
+    Failed(String),
+    #[error("No frame was captured, the example doesn't support FrameCapture")]
+    NotCaptured,
+}
+
+/// Inserted by the harness before setup when a capture was requested. An
+/// example supports it by adding `COPY_SRC` to its surface usage, stepping
+/// time by [`FrameCapture::TIME_STEP`], and calling [`FrameCapture::capture`]
+/// between submitting and presenting while [`FrameCapture::is_due`].
+#[derive(Resource)]
+pub struct FrameCapture {
+    pub path: PathBuf,
+    pub frame: u32,
+    rendered: u32,
+    outcome: Option<Result<(), String>>,
+}
+impl FrameCapture {
+    pub const DEFAULT_FRAME: u32 = 30;
+    /// Fixed time step to advance by instead of the wall clock, so animated
+    /// scenes land on the same frame every run.
+    pub const TIME_STEP: f32 = 1.0 / 60.0;
+    /// Frames past the requested one after which the harness gives up on an
+    /// example that never captures.
+    const GRACE_FRAMES: u32 = 10;
+
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var_os(CAPTURE_ENV)?;
+        let frame = std::env::var(CAPTURE_FRAMES_ENV)
+            .ok()
+            .and_then(|frames| frames.parse().ok())
+            .unwrap_or(Self::DEFAULT_FRAME)
+            .max(1);
+        Some(Self {
+            path: PathBuf::from(path),
+            frame,
+            rendered: 0,
+            outcome: None,
+        })
+    }
+
+    /// Whether the frame being rendered is the one to save.
+    pub fn is_due(&self) -> bool {
+        self.outcome.is_none() && self.rendered + 1 >= self.frame
+    }
+
+    /// Saves `texture` to [`FrameCapture::path`]. Waits for the GPU, only
+    /// call it after the frame was submitted and before it's presented.
+    pub fn capture(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+        let result = self.write_png(device, queue, texture);
+        match &result {
+            Ok(()) => info!("Captured frame {} to {}", self.frame, self.path.display()),
+            Err(e) => error!("Frame capture failed: {}", e),
+        }
+        self.outcome = Some(result.map_err(|e| e.to_string()));
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        self.rendered += 1;
+    }
+
+    /// `None` while the harness should keep rendering.
+    pub(crate) fn outcome(&self) -> Option<Result<(), CaptureError>> {
+        match &self.outcome {
+            Some(Ok(())) => Some(Ok(())),
+            Some(Err(e)) => Some(Err(CaptureError::Failed(e.clone()))),
+            None if self.rendered >= self.frame + Self::GRACE_FRAMES => {
+                Some(Err(CaptureError::NotCaptured))
+            }
+            None => None,
+        }
+    }
+
+    fn write_png(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+    ) -> Result<(), CaptureError> {
+        let swizzle = match texture.format() {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(CaptureError::UnsupportedFormat(format)),
+        };
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(CaptureError::NotCopyable);
+        }
+
+        let (width, height) = (texture.width(), texture.height());
+        let row_bytes = width * 4;
+        let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame_capture_buffer"),
+            size: padded_row_bytes as u64 * height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame_capture_encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_row_bytes as usize) {
+            pixels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        for pixel in pixels.chunks_exact_mut(4) {
+            if swizzle {
+                pixel.swap(0, 2);
+            }
+            // What's left in alpha isn't shown, the window is opaque
+            pixel[3] = 255;
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|source| CaptureError::CreateDir {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+        image::save_buffer(
+            &self.path,
+            &pixels,
+            width,
+            height,
+            image::ExtendedColorType::Rgba8,
+        )
+        .map_err(|source| CaptureError::Write {
+            path: self.path.clone(),
+            source,
+        })
+    }
+}
@@ -0,0 +1,164 @@
+//! Keyboard and mouse state for the examples. The harness feeds it every
+//! window event before triggering [`WindowTriggerEvent`](crate::WindowTriggerEvent),
+//! and drops the edges after [`FrameEnd`](crate::FrameEnd), so every system
+//! and observer sees the same input for the whole frame.
+
+use std::{collections::HashSet, hash::Hash};
+
+use bevy_ecs::system::Resource;
+use glam::Vec2;
+use winit::{
+    event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
+};
+
+/// Roughly how many pixels a touchpad scrolls for one wheel line.
+const PIXELS_PER_LINE: f32 = 40.0;
+
+/// Keyboard and mouse as of this frame. Inserted by the harness.
+#[derive(Resource, Default)]
+pub struct Input {
+    /// By physical key, so the layout doesn't move WASD around.
+    pub keys: Buttons<KeyCode>,
+    pub mouse_buttons: Buttons<MouseButton>,
+    pub modifiers: ModifiersState,
+    /// Physical pixels, `None` while the cursor is outside the window.
+    pub cursor: Option<Vec2>,
+    /// How far the cursor moved this frame, in physical pixels.
+    pub cursor_delta: Vec2,
+    /// Lines scrolled this frame, positive y away from the user.
+    pub scroll: Vec2,
+}
+impl Input {
+    pub(crate) fn handle(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => {
+                let PhysicalKey::Code(code) = event.physical_key else {
+                    return;
+                };
+                match event.state {
+                    ElementState::Pressed => self.keys.press(code),
+                    ElementState::Released => self.keys.release(code),
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => self.mouse_buttons.press(*button),
+                ElementState::Released => self.mouse_buttons.release(*button),
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = Vec2::new(position.x as f32, position.y as f32);
+                if let Some(last) = self.cursor {
+                    self.cursor_delta += position - last;
+                }
+                self.cursor = Some(position);
+            }
+            WindowEvent::CursorLeft { .. } => self.cursor = None,
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll += match delta {
+                    MouseScrollDelta::LineDelta(x, y) => Vec2::new(*x, *y),
+                    MouseScrollDelta::PixelDelta(position) => {
+                        Vec2::new(position.x as f32, position.y as f32) / PIXELS_PER_LINE
+                    }
+                };
+            }
+            WindowEvent::Focused(false) => {
+                self.keys.release_all();
+                self.mouse_buttons.release_all();
+                self.modifiers = ModifiersState::empty();
+            }
+            _ => {}
+        }
+    }
+
+    /// Drops the edges and deltas once every system saw them.
+    pub(crate) fn end_frame(&mut self) {
+        self.keys.end_frame();
+        self.mouse_buttons.end_frame();
+        self.cursor_delta = Vec2::ZERO;
+        self.scroll = Vec2::ZERO;
+    }
+}
+
+/// Which buttons are held, and which went down or up since the last frame.
+/// A button pressed and released within one frame is both just pressed and
+/// just released, but not pressed.
+pub struct Buttons<T> {
+    pressed: HashSet<T>,
+    just_pressed: HashSet<T>,
+    just_released: HashSet<T>,
+}
+impl<T> Default for Buttons<T> {
+    fn default() -> Self {
+        Self {
+            pressed: HashSet::new(),
+            just_pressed: HashSet::new(),
+            just_released: HashSet::new(),
+        }
+    }
+}
+impl<T: Copy + Eq + Hash> Buttons<T> {
+    pub fn pressed(&self, button: T) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    pub fn just_pressed(&self, button: T) -> bool {
+        self.just_pressed.contains(&button)
+    }
+
+    pub fn just_released(&self, button: T) -> bool {
+        self.just_released.contains(&button)
+    }
+
+    /// Key repeats don't count as presses.
+    fn press(&mut self, button: T) {
+        if self.pressed.insert(button) {
+            self.just_pressed.insert(button);
+        }
+    }
+
+    fn release(&mut self, button: T) {
+        if self.pressed.remove(&button) {
+            self.just_released.insert(button);
+        }
+    }
+
+    /// The window doesn't hear about releases while it is out of focus.
+    fn release_all(&mut self) {
+        self.just_released.extend(self.pressed.drain());
+    }
+
+    fn end_frame(&mut self) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edges_last_one_frame() {
+        let mut keys = Buttons::default();
+        keys.press(KeyCode::Space);
+        // Repeats while held
+        keys.press(KeyCode::Space);
+        assert!(keys.pressed(KeyCode::Space) && keys.just_pressed(KeyCode::Space));
+
+        keys.end_frame();
+        keys.press(KeyCode::Space);
+        assert!(keys.pressed(KeyCode::Space) && !keys.just_pressed(KeyCode::Space));
+
+        // Tapped within one frame
+        keys.press(KeyCode::KeyA);
+        keys.release(KeyCode::KeyA);
+        assert!(keys.just_pressed(KeyCode::KeyA) && keys.just_released(KeyCode::KeyA));
+        assert!(!keys.pressed(KeyCode::KeyA));
+
+        keys.end_frame();
+        keys.release_all();
+        assert!(keys.just_released(KeyCode::Space) && !keys.pressed(KeyCode::Space));
+        assert!(!keys.just_released(KeyCode::KeyA));
+    }
+}
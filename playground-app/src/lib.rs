@@ -0,0 +1,303 @@
+//! Shared winit harness for the ECS based examples. Owns the event loop, the
+//! window and the redraw loop, so an example only provides a setup function
+//! that inserts resources and registers systems.
+
+use std::{
+    error::Error,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::Arc,
+};
+
+use bevy_ecs::{event::Event, schedule::Schedule, world::World};
+use tracing::error;
+use winit::{
+    application::ApplicationHandler,
+    dpi::{LogicalSize, Size},
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::{Window, WindowId},
+};
+
+mod capture;
+mod input;
+mod text_input;
+
+pub use capture::{CaptureError, FrameCapture, CAPTURE_ENV, CAPTURE_FRAMES_ENV};
+pub use input::{Buttons, Input};
+pub use text_input::TextInputEvent;
+use text_input::TextInputTracker;
+
+/// Window events of the main window, triggered on the world so examples can
+/// observe input and resizes. [`Input`] already includes the event when it's
+/// triggered.
+#[derive(Event)]
+pub struct WindowTriggerEvent {
+    pub event: WindowEvent,
+}
+
+/// Triggered on the world right before the schedule runs for a frame, after
+/// the window events that arrived since the last one.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct FrameStart {
+    pub frame: u64,
+}
+
+/// Triggered on the world once the schedule ran and the frame was presented.
+/// Not triggered for a frame that panicked. The edges in [`Input`] are
+/// dropped right after it.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct FrameEnd {
+    pub frame: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedrawPolicy {
+    /// Runs the schedule as fast as presentation allows.
+    Continuous,
+    /// Only runs the schedule after a window event, idling in between.
+    OnEvent,
+}
+
+/// Whatever a setup function failed with.
+pub type SetupError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("Failed to run the event loop")]
+    EventLoop(#[from] winit::error::EventLoopError),
+    #[error("Failed to create the window")]
+    Window(#[from] winit::error::OsError),
+    #[error("Setup failed")]
+    Setup(#[source] SetupError),
+    #[error("A frame panicked")]
+    FramePanicked,
+    #[error(transparent)]
+    Capture(#[from] CaptureError),
+}
+
+pub type SetupFn =
+    Box<dyn FnOnce(&mut World, &mut Schedule, Arc<Window>) -> Result<(), SetupError>>;
+pub type ShutdownFn = Box<dyn FnOnce(&mut World)>;
+
+/// Runs `setup` with default window options.
+pub fn run<E: Into<SetupError>>(
+    setup: impl FnOnce(&mut World, &mut Schedule, Arc<Window>) -> Result<(), E> + 'static,
+) -> Result<(), AppError> {
+    App::new("WGPU Engine").run(setup)
+}
+
+pub struct App {
+    title: String,
+    size: LogicalSize<f64>,
+    min_size: Option<LogicalSize<f64>>,
+    redraw: RedrawPolicy,
+    shutdown: Option<ShutdownFn>,
+}
+impl App {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            size: LogicalSize::new(800.0, 600.0),
+            min_size: Some(LogicalSize::new(400.0, 300.0)),
+            redraw: RedrawPolicy::Continuous,
+            shutdown: None,
+        }
+    }
+    pub fn size(mut self, width: f64, height: f64) -> Self {
+        self.size = LogicalSize::new(width, height);
+        self
+    }
+    pub fn min_size(mut self, min_size: Option<LogicalSize<f64>>) -> Self {
+        self.min_size = min_size;
+        self
+    }
+    pub fn redraw(mut self, redraw: RedrawPolicy) -> Self {
+        self.redraw = redraw;
+        self
+    }
+    /// Runs once when the app closes or a frame panics, after rendering has
+    /// stopped. Use it to wait for the GPU and drop resources in a safe
+    /// order; whatever is left in the world is dropped afterwards.
+    pub fn on_shutdown(mut self, shutdown: impl FnOnce(&mut World) + 'static) -> Self {
+        self.shutdown = Some(Box::new(shutdown));
+        self
+    }
+
+    /// Creates the window on resume, calls `setup` once with it, then runs
+    /// the schedule on every redraw until the window is closed.
+    pub fn run<E: Into<SetupError>>(
+        self,
+        setup: impl FnOnce(&mut World, &mut Schedule, Arc<Window>) -> Result<(), E> + 'static,
+    ) -> Result<(), AppError> {
+        // Waiting is fine for both policies, continuous rendering keeps the
+        // loop awake by requesting a redraw every iteration
+        let event_loop = EventLoop::new()?;
+        event_loop.set_control_flow(ControlFlow::Wait);
+        let mut handler = Handler {
+            app: self,
+            setup: Some(Box::new(|world, schedule, window| {
+                setup(world, schedule, window).map_err(Into::into)
+            })),
+            window: None,
+            world: World::default(),
+            schedule: Schedule::default(),
+            closing: false,
+            error: None,
+            text_input: TextInputTracker::default(),
+            frame: 0,
+        };
+        handler.world.insert_resource(Input::default());
+        event_loop.run_app(&mut handler)?;
+        match handler.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+// =============================== HANDLER ===============================
+struct Handler {
+    app: App,
+    setup: Option<SetupFn>,
+    window: Option<Arc<Window>>,
+    world: World,
+    schedule: Schedule,
+    /// Set once the window is closing, no frames are rendered after it.
+    closing: bool,
+    error: Option<AppError>,
+    text_input: TextInputTracker,
+    /// Number of the next frame, counted from zero.
+    frame: u64,
+}
+
+impl ApplicationHandler for Handler {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        // Only the first resume sets up, later ones (e.g. on mobile) keep the
+        // existing window
+        let Some(setup) = self.setup.take() else {
+            return;
+        };
+
+        let capture = FrameCapture::from_env();
+        let mut attributes = Window::default_attributes()
+            .with_title(self.app.title.as_str())
+            .with_inner_size(Size::Logical(self.app.size))
+            .with_visible(capture.is_none());
+        if let Some(min_size) = self.app.min_size {
+            attributes = attributes.with_min_inner_size(Size::Logical(min_size));
+        }
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(e) => {
+                self.fail(event_loop, AppError::Window(e));
+                return;
+            }
+        };
+
+        if let Some(capture) = capture {
+            self.world.insert_resource(capture);
+        }
+        if let Err(e) = setup(&mut self.world, &mut self.schedule, window.clone()) {
+            self.fail(event_loop, AppError::Setup(e));
+            return;
+        }
+        self.world.flush();
+        self.window = Some(window);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        let Some(window) = self.window.clone() else {
+            return;
+        };
+        if window.id() != window_id {
+            return;
+        }
+
+        if self.closing {
+            return;
+        }
+
+        self.world.resource_mut::<Input>().handle(&event);
+        self.world.trigger(WindowTriggerEvent {
+            event: event.clone(),
+        });
+        if let Some(text) = self.text_input.update(&event) {
+            self.world.trigger(text);
+        }
+        match event {
+            WindowEvent::CloseRequested => {
+                self.closing = true;
+                event_loop.exit();
+            }
+            WindowEvent::RedrawRequested => {
+                // Catching doesn't skip the panic hook, the panic is still
+                // reported by the default hook or the one the example
+                // installed. This only makes sure the shutdown closure tears
+                // the GPU down before the app exits
+                let frame = catch_unwind(AssertUnwindSafe(|| {
+                    self.world.trigger(FrameStart { frame: self.frame });
+                    self.schedule.run(&mut self.world);
+                    self.world.trigger(FrameEnd { frame: self.frame });
+                    self.world.resource_mut::<Input>().end_frame();
+                }));
+                self.frame += 1;
+                if frame.is_err() {
+                    error!("Frame {} panicked, shutting down", self.frame - 1);
+                    self.closing = true;
+                    self.error = Some(AppError::FramePanicked);
+                    event_loop.exit();
+                    return;
+                }
+                if let Some(mut capture) = self.world.get_resource_mut::<FrameCapture>() {
+                    capture.end_frame();
+                    if let Some(outcome) = capture.outcome() {
+                        self.closing = true;
+                        self.error = outcome.err().map(AppError::from);
+                        event_loop.exit();
+                    }
+                }
+            }
+            _ if self.app.redraw == RedrawPolicy::OnEvent => window.request_redraw(),
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+        // A hidden window gets no events, captures always render continuously
+        let continuous = self.app.redraw == RedrawPolicy::Continuous
+            || self.world.contains_resource::<FrameCapture>();
+        if continuous && !self.closing {
+            if let Some(window) = &self.window {
+                window.request_redraw();
+            }
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        self.closing = true;
+        if self.window.is_some() {
+            if let Some(shutdown) = self.app.shutdown.take() {
+                shutdown(&mut self.world);
+            }
+        }
+        self.world.clear_all();
+        // The window goes last, after every surface created from it
+        self.window = None;
+    }
+}
+
+impl Handler {
+    fn fail(&mut self, event_loop: &ActiveEventLoop, e: AppError) {
+        match &e {
+            AppError::Setup(source) => error!("Setup failed: {:?}", source),
+            e => error!("{}: {:?}", e, e.source()),
+        }
+        self.error = Some(e);
+        event_loop.exit();
+    }
+}
@@ -0,0 +1,58 @@
+//! Cleans up IME events before they reach the examples. Platforms disagree on
+//! how input methods report text: some send an `Ime::Commit` for every key
+//! that already produced text through `KeyboardInput`, and some only send
+//! `Enabled`/`Disabled` around a composition. This turns both into one stream
+//! of compositions.
+
+use bevy_ecs::event::Event;
+use winit::{
+    event::{ElementState, Ime, WindowEvent},
+    keyboard::SmolStr,
+};
+
+/// Text from an input method, triggered on the world right after the
+/// [`WindowTriggerEvent`](crate::WindowTriggerEvent) it came from.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub enum TextInputEvent {
+    /// The text being composed changed, empty once it was erased.
+    Preedit(String),
+    /// Finished text to insert, ending the composition if there was one.
+    Commit(String),
+    /// The composition was abandoned without inserting anything.
+    Cancel,
+}
+
+#[derive(Default)]
+pub(crate) struct TextInputTracker {
+    composing: bool,
+    /// Text of the last key press, which some platforms repeat as a commit.
+    last_key_text: Option<SmolStr>,
+}
+impl TextInputTracker {
+    pub fn update(&mut self, event: &WindowEvent) -> Option<TextInputEvent> {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                self.last_key_text = event.text.clone();
+                None
+            }
+            WindowEvent::Ime(Ime::Preedit(text, cursor)) => {
+                // A hidden cursor means the preedit is gone
+                let text = if cursor.is_some() { text.as_str() } else { "" };
+                if text.is_empty() && !self.composing {
+                    return None;
+                }
+                self.composing = true;
+                Some(TextInputEvent::Preedit(text.to_string()))
+            }
+            WindowEvent::Ime(Ime::Commit(text)) => {
+                let composed = std::mem::take(&mut self.composing);
+                let echo = self.last_key_text.take().as_deref() == Some(text.as_str());
+                (composed || !echo).then(|| TextInputEvent::Commit(text.clone()))
+            }
+            WindowEvent::Ime(Ime::Disabled) | WindowEvent::Focused(false) => {
+                std::mem::take(&mut self.composing).then_some(TextInputEvent::Cancel)
+            }
+            _ => None,
+        }
+    }
+}
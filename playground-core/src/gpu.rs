@@ -0,0 +1,620 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use pollster::FutureExt;
+use tracing::{info, warn};
+use wgpu::Adapter;
+use wgpu::AdapterInfo;
+use wgpu::Device;
+use wgpu::Instance;
+use wgpu::Queue;
+use wgpu::Surface;
+use wgpu::SurfaceCapabilities;
+use winit::dpi::PhysicalSize;
+use winit::monitor::MonitorHandle;
+use winit::window::Window;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GpuError {
+    #[error("No adapter found")]
+    NoAdapter,
+    #[error("Failed to create the surface")]
+    Surface(#[from] wgpu::CreateSurfaceError),
+    #[error("Failed to create the device")]
+    Device(#[from] wgpu::RequestDeviceError),
+}
+
+/// How [`GpuContext::with_options`] sets the device and surface up. The
+/// defaults are what [`GpuContext::new`] uses.
+#[derive(Clone, Debug)]
+pub struct GpuOptions {
+    /// Device creation fails without these.
+    pub required_features: wgpu::Features,
+    /// Requested when the adapter has them. Everything depending on one has
+    /// to check `device.features()` and fall back without it.
+    pub optional_features: wgpu::Features,
+    pub vsync: bool,
+    /// Keeps compiled pipelines on disk between runs, where the backend
+    /// supports it.
+    pub pipeline_cache: bool,
+    pub poll_strategy: PollStrategy,
+}
+impl Default for GpuOptions {
+    fn default() -> Self {
+        Self {
+            required_features: wgpu::Features::empty(),
+            optional_features: wgpu::Features::empty(),
+            vsync: true,
+            pipeline_cache: false,
+            poll_strategy: PollStrategy::default(),
+        }
+    }
+}
+
+/// Who calls `device.poll`, which is what runs `map_async` and
+/// `on_submitted_work_done` callbacks. Without polling they only fire when
+/// something else happens to poll or submit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PollStrategy {
+    /// [`GpuContext::poll_after_submit`] polls without blocking, so callbacks
+    /// fire up to a frame after the GPU got done.
+    #[default]
+    PerFrame,
+    /// A thread polls every [`POLL_INTERVAL`], callbacks fire right after
+    /// the GPU got done, on that thread.
+    Thread,
+    /// [`GpuContext::poll_after_submit`] blocks until the GPU is done with
+    /// the frame. No frame overlaps the next, but every readback is
+    /// available the same frame.
+    WaitOnSubmit,
+}
+
+/// How often the [`PollStrategy::Thread`] thread polls.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The surface is created from the shared window, which it keeps alive itself,
+/// so it is `'static` without borrowing from the caller.
+#[cfg_attr(feature = "ecs", derive(bevy_ecs::system::Resource))]
+pub struct GpuContext {
+    pub window: Arc<Window>,
+    pub adapter: Adapter,
+    pub adapter_info: AdapterInfo,
+    /// Shared with the polling thread, see [`PollStrategy::Thread`].
+    pub device: Arc<Device>,
+    pub queue: Queue,
+    /// Shared so textures can be acquired off the main thread.
+    pub surface: Arc<Surface<'static>>,
+    pub config: wgpu::SurfaceConfiguration,
+    pub scale: f64,
+    /// Monitor the surface was last configured for.
+    pub monitor: Option<MonitorHandle>,
+    pub disk_cache: Option<DiskPipelineCache>,
+    /// Picks present modes that wait for vertical blank.
+    pub vsync: bool,
+    poll_strategy: PollStrategy,
+    poll_thread: Option<PollThread>,
+}
+
+/// Triggered when moving to another monitor, or a DPI change, changed how the
+/// surface is configured. Anything built for the surface format has to be rebuilt when
+/// `format` differs from `previous_format`.
+#[cfg_attr(feature = "ecs", derive(bevy_ecs::event::Event))]
+#[derive(Clone, Debug)]
+pub struct SurfaceChanged {
+    pub monitor: Option<String>,
+    pub previous_format: wgpu::TextureFormat,
+    pub format: wgpu::TextureFormat,
+    pub previous_present_mode: wgpu::PresentMode,
+    pub present_mode: wgpu::PresentMode,
+    pub previous_scale: f64,
+    pub scale: f64,
+}
+impl SurfaceChanged {
+    pub fn format_changed(&self) -> bool {
+        self.format != self.previous_format
+    }
+}
+
+impl GpuContext {
+    pub fn new(window: Arc<Window>) -> Result<Self, GpuError> {
+        Self::with_options(window, &GpuOptions::default())
+    }
+
+    pub fn with_options(window: Arc<Window>, options: &GpuOptions) -> Result<Self, GpuError> {
+        let flags = wgpu::InstanceFlags::default();
+        let instance = Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY,
+            flags,
+            ..Default::default()
+        });
+
+        // The surface keeps its own handle to the window, so the two can be
+        // dropped in any order
+        let surface = instance.create_surface(window.clone())?;
+        let adapter = Self::create_adapter(&instance, &surface)?;
+        let (device, queue) = Self::create_device(&adapter, options)?;
+        let device = Arc::new(device);
+        let adapter_info = adapter.get_info();
+        let disk_cache = options
+            .pipeline_cache
+            .then(|| DiskPipelineCache::load(&device, &adapter_info))
+            .flatten();
+        let surface_caps = surface.get_capabilities(&adapter);
+        let vsync = options.vsync;
+        let config = Self::create_surface_config(window.inner_size(), surface_caps, vsync);
+
+        surface.configure(&device, &config);
+
+        let scale = window.scale_factor();
+        let monitor = window.current_monitor();
+
+        let mut gpu = Self {
+            window,
+            adapter,
+            adapter_info,
+            device,
+            queue,
+            surface: Arc::new(surface),
+            config,
+            scale,
+            monitor,
+            disk_cache,
+            vsync,
+            poll_strategy: PollStrategy::PerFrame,
+            poll_thread: None,
+        };
+        gpu.set_poll_strategy(options.poll_strategy);
+        Ok(gpu)
+    }
+
+    fn create_adapter(instance: &Instance, surface: &Surface) -> Result<Adapter, GpuError> {
+        instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: Some(surface),
+                force_fallback_adapter: false,
+            })
+            .block_on()
+            .ok_or(GpuError::NoAdapter)
+    }
+
+    fn create_device(adapter: &Adapter, options: &GpuOptions) -> Result<(Device, Queue), GpuError> {
+        // Optional features are only requested when the adapter supports them
+        let optional = adapter.features() & options.optional_features;
+        if !options.optional_features.is_empty() {
+            info!("Optional features enabled: {:?}", optional);
+            let missing = options.optional_features - optional;
+            if !missing.is_empty() {
+                info!("Optional features unavailable: {:?}", missing);
+            }
+        }
+        let features = options.required_features | optional;
+
+        // The subgroup size range is only reported when asked for
+        let adapter_limits = adapter.limits();
+        let mut limits = wgpu::Limits::default();
+        if features.contains(wgpu::Features::SUBGROUP) {
+            limits.min_subgroup_size = adapter_limits.min_subgroup_size;
+            limits.max_subgroup_size = adapter_limits.max_subgroup_size;
+        }
+        // The default limit has no room for push constants at all
+        if features.contains(wgpu::Features::PUSH_CONSTANTS) {
+            limits.max_push_constant_size = adapter_limits.max_push_constant_size;
+        }
+        adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    required_features: features,
+                    required_limits: limits,
+                    memory_hints: wgpu::MemoryHints::default(),
+                    label: None,
+                },
+                None,
+            )
+            .block_on()
+            .map_err(GpuError::from)
+    }
+
+    fn create_surface_config(
+        size: PhysicalSize<u32>,
+        capabilities: SurfaceCapabilities,
+        vsync: bool,
+    ) -> wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: Self::choose_format(&capabilities),
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: Self::choose_present_mode(&capabilities, vsync),
+            alpha_mode: capabilities.alpha_modes[0],
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        }
+    }
+
+    fn choose_format(capabilities: &SurfaceCapabilities) -> wgpu::TextureFormat {
+        let formats = capabilities.formats.to_vec();
+        let supports_hdr = formats.iter().any(|format| {
+            matches!(
+                format,
+                wgpu::TextureFormat::Bgra8UnormSrgb
+                    | wgpu::TextureFormat::Rgba16Float
+                    | wgpu::TextureFormat::Rgba32Float // Add other HDR formats as needed
+            )
+        });
+        info!("Surface supports HDR: {}", supports_hdr);
+        // List all formats supported by the surface
+        info!("Supported surface formats: {:#?}", formats);
+        let format = formats
+            .iter()
+            .cloned()
+            .max_by(|a, b| {
+                let a_score = GpuContext::format_score(*a);
+                let b_score = GpuContext::format_score(*b);
+                a_score.cmp(&b_score)
+            })
+            .unwrap_or(formats[0]);
+        info!("Using surface format: {:?}", format);
+        format
+    }
+
+    fn choose_present_mode(capabilities: &SurfaceCapabilities, vsync: bool) -> wgpu::PresentMode {
+        capabilities
+            .present_modes
+            .iter()
+            .cloned()
+            .max_by(|a, b| {
+                Self::present_mode_score(*a, vsync).cmp(&Self::present_mode_score(*b, vsync))
+            })
+            .unwrap_or(wgpu::PresentMode::AutoNoVsync)
+    }
+
+    fn format_score(format: wgpu::TextureFormat) -> u32 {
+        match format {
+            // Assign higher scores to preferred formats
+            wgpu::TextureFormat::Bgra8UnormSrgb => 10,
+            wgpu::TextureFormat::Rgba8UnormSrgb => 9,
+            wgpu::TextureFormat::Rgba16Float => 8,
+            wgpu::TextureFormat::Rgba32Float => 7,
+            _ => 0, // Default score for other formats
+        }
+    }
+
+    fn present_mode_score(present_mode: wgpu::PresentMode, vsync: bool) -> u32 {
+        if !vsync {
+            return match present_mode {
+                wgpu::PresentMode::AutoNoVsync => 11,
+                wgpu::PresentMode::Immediate => 10,
+                wgpu::PresentMode::Mailbox => 9,
+                _ => 0,
+            };
+        }
+        match present_mode {
+            wgpu::PresentMode::AutoVsync => 11,
+            wgpu::PresentMode::Mailbox => 10,
+            wgpu::PresentMode::Fifo => 9,
+            wgpu::PresentMode::Immediate => 8,
+            wgpu::PresentMode::AutoNoVsync => 7,
+            _ => 0,
+        }
+    }
+
+    /// Smallest and largest subgroup the device may run, when shaders can use
+    /// subgroup operations at all.
+    pub fn subgroup_sizes(&self) -> Option<(u32, u32)> {
+        if !self.device.features().contains(wgpu::Features::SUBGROUP) {
+            return None;
+        }
+        let limits = self.device.limits();
+        Some((limits.min_subgroup_size, limits.max_subgroup_size))
+    }
+
+    /// Cache to pass to pipeline creation, when the backend supports one.
+    pub fn pipeline_cache(&self) -> Option<&wgpu::PipelineCache> {
+        self.disk_cache.as_ref().map(|disk| &disk.cache)
+    }
+
+    /// Writes the pipeline cache back to disk. Failures only cost compile time
+    /// on the next start, so they are logged rather than returned.
+    pub fn save_pipeline_cache(&self) {
+        if let Some(disk) = &self.disk_cache {
+            if let Err(e) = disk.save() {
+                warn!("Failed to save pipeline cache to {:?}: {:?}", disk.path, e);
+            }
+        }
+    }
+
+    pub fn resize(&mut self, size: &PhysicalSize<u32>) {
+        // Minimizing (e.g. alt-tabbing out of exclusive fullscreen) reports a
+        // zero size, which can't be configured; keep the old surface until
+        // the window is restored
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.surface.configure(&self.device, &self.config);
+    }
+
+    /// Configures the surface again with the current config, after it was
+    /// lost or went out of date.
+    pub fn reconfigure(&self) {
+        self.surface.configure(&self.device, &self.config);
+    }
+
+    /// Queries the surface again after the window moved to another monitor or
+    /// its scale factor changed, and reconfigures it if the best format or
+    /// present mode differs there. Returns what changed, if anything did.
+    ///
+    /// Only formats that `ui_format` can be viewed as are considered. The UI
+    /// renderer bakes its format into its pipeline and holds every UI texture,
+    /// so it keeps drawing through a view of the surface in its own format
+    /// instead of being rebuilt.
+    pub fn refresh_surface(&mut self, ui_format: wgpu::TextureFormat) -> Option<SurfaceChanged> {
+        let monitor = self.window.current_monitor();
+        let scale = self.window.scale_factor();
+        if monitor == self.monitor && scale == self.scale {
+            return None;
+        }
+        let previous_scale = std::mem::replace(&mut self.scale, scale);
+        self.monitor = monitor;
+
+        let mut capabilities = self.surface.get_capabilities(&self.adapter);
+        capabilities
+            .formats
+            .retain(|format| format.remove_srgb_suffix() == ui_format.remove_srgb_suffix());
+        if capabilities.formats.is_empty() {
+            warn!(
+                "No surface format compatible with {:?} on this monitor, keeping the current config",
+                ui_format
+            );
+            return None;
+        }
+        let previous_format = self.config.format;
+        let previous_present_mode = self.config.present_mode;
+        let format = Self::choose_format(&capabilities);
+        let present_mode = Self::choose_present_mode(&capabilities, self.vsync);
+        if format == previous_format && present_mode == previous_present_mode {
+            if scale == previous_scale {
+                return None;
+            }
+        } else {
+            self.config.format = format;
+            self.config.view_formats = if format == ui_format {
+                vec![]
+            } else {
+                vec![ui_format]
+            };
+            self.config.present_mode = present_mode;
+            if !capabilities.alpha_modes.contains(&self.config.alpha_mode) {
+                self.config.alpha_mode = capabilities.alpha_modes[0];
+            }
+            // Extra usages like COPY_SRC for frame captures only stay if the
+            // new monitor's surface still supports them
+            self.config.usage &= capabilities.usages | wgpu::TextureUsages::RENDER_ATTACHMENT;
+            if !self.is_minimized() {
+                self.reconfigure();
+            }
+        }
+
+        let changed = SurfaceChanged {
+            monitor: self.monitor.as_ref().and_then(MonitorHandle::name),
+            previous_format,
+            format: self.config.format,
+            previous_present_mode,
+            present_mode: self.config.present_mode,
+            previous_scale,
+            scale,
+        };
+        info!("Surface changed: {:?}", changed);
+        Some(changed)
+    }
+
+    /// Switches vsync and the frames the CPU may queue ahead, reconfiguring
+    /// the surface when that changes its config. The format stays, so only
+    /// what depends on the present mode has to react to the returned change.
+    pub fn set_present(&mut self, vsync: bool, max_frame_latency: u32) -> Option<SurfaceChanged> {
+        self.vsync = vsync;
+        let capabilities = self.surface.get_capabilities(&self.adapter);
+        let previous_present_mode = self.config.present_mode;
+        let present_mode = Self::choose_present_mode(&capabilities, vsync);
+        let max_frame_latency = max_frame_latency.max(1);
+        if present_mode == previous_present_mode
+            && max_frame_latency == self.config.desired_maximum_frame_latency
+        {
+            return None;
+        }
+        self.config.present_mode = present_mode;
+        self.config.desired_maximum_frame_latency = max_frame_latency;
+        if !self.is_minimized() {
+            self.reconfigure();
+        }
+
+        let changed = SurfaceChanged {
+            monitor: self.monitor.as_ref().and_then(MonitorHandle::name),
+            previous_format: self.config.format,
+            format: self.config.format,
+            previous_present_mode,
+            present_mode,
+            previous_scale: self.scale,
+            scale: self.scale,
+        };
+        info!("Surface changed: {:?}", changed);
+        Some(changed)
+    }
+
+    /// Turning the pipeline cache off also deletes its file, so a cache the
+    /// driver misbehaves with doesn't come back on the next start.
+    pub fn set_pipeline_cache(&mut self, enabled: bool) {
+        match (enabled, &self.disk_cache) {
+            (true, None) => {
+                self.disk_cache = DiskPipelineCache::load(&self.device, &self.adapter_info);
+            }
+            (false, Some(disk)) => {
+                match std::fs::remove_file(&disk.path) {
+                    Ok(()) => info!("Deleted pipeline cache {:?}", disk.path),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => warn!("Failed to delete pipeline cache {:?}: {:?}", disk.path, e),
+                }
+                self.disk_cache = None;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn poll_strategy(&self) -> PollStrategy {
+        self.poll_strategy
+    }
+
+    /// Starts or stops the polling thread as the strategy needs.
+    pub fn set_poll_strategy(&mut self, strategy: PollStrategy) {
+        if strategy == self.poll_strategy {
+            return;
+        }
+        self.poll_strategy = strategy;
+        // Dropping the thread joins it
+        self.poll_thread = None;
+        if strategy == PollStrategy::Thread {
+            match PollThread::spawn(self.device.clone()) {
+                Ok(thread) => self.poll_thread = Some(thread),
+                Err(e) => {
+                    warn!(
+                        "Failed to start the polling thread, polling every frame: {:?}",
+                        e
+                    );
+                    self.poll_strategy = PollStrategy::PerFrame;
+                }
+            }
+        }
+        info!("Polling the device: {:?}", self.poll_strategy);
+    }
+
+    /// Called once a frame after its work was submitted, polls the device as
+    /// the [`PollStrategy`] says.
+    pub fn poll_after_submit(&self) {
+        match self.poll_strategy {
+            PollStrategy::PerFrame => {
+                self.device.poll(wgpu::Maintain::Poll);
+            }
+            PollStrategy::WaitOnSubmit => {
+                self.device.poll(wgpu::Maintain::Wait);
+            }
+            PollStrategy::Thread => {}
+        }
+    }
+
+    /// Nothing is visible, frames are skipped until the window is restored.
+    pub fn is_minimized(&self) -> bool {
+        let size = self.window.inner_size();
+        self.window.is_minimized().unwrap_or(false) || size.width == 0 || size.height == 0
+    }
+}
+
+// =============================== POLLING ===============================
+/// Polls without blocking, a thread waiting on the fence would hold up every
+/// submission until the GPU is idle.
+struct PollThread {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+impl PollThread {
+    fn spawn(device: Arc<Device>) -> std::io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("device-poll".to_string())
+            .spawn(move || {
+                while !thread_stop.load(Ordering::Relaxed) {
+                    device.poll(wgpu::Maintain::Poll);
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+            })?;
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+impl Drop for PollThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+// =============================== PIPELINE CACHE ===============================
+/// Driver pipeline cache persisted between runs. Only some backends support
+/// one (currently Vulkan); elsewhere every start compiles from scratch.
+pub struct DiskPipelineCache {
+    pub cache: wgpu::PipelineCache,
+    pub path: PathBuf,
+}
+impl DiskPipelineCache {
+    pub fn load(device: &Device, adapter: &AdapterInfo) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return None;
+        }
+        // The key identifies the driver, so a different GPU never reads this file
+        let path = cache_dir()?.join(wgpu::util::pipeline_cache_key(adapter)?);
+        let data = std::fs::read(&path).ok();
+        info!(
+            "Pipeline cache {:?}: {}",
+            path,
+            match &data {
+                Some(data) => format!("loaded {} bytes", data.len()),
+                None => "starting empty".to_string(),
+            }
+        );
+
+        // SAFETY: the data was written by `save` for an adapter with the same
+        // cache key, and `fallback` discards it if the driver rejects it
+        let cache = unsafe {
+            device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("pipeline_cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+        Some(Self { cache, path })
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(data) = self.cache.get_data() else {
+            return Ok(());
+        };
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Write then rename, so a crash never leaves a truncated cache behind
+        let temp = self.path.with_extension("tmp");
+        std::fs::write(&temp, &data)?;
+        std::fs::rename(&temp, &self.path)?;
+        info!("Saved {} bytes of pipeline cache", data.len());
+        Ok(())
+    }
+}
+
+/// Per-user cache directory of the platform.
+fn cache_dir() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Caches"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(".cache")))
+    };
+    base.map(|base| base.join("wgpu-playground"))
+}
@@ -0,0 +1,11 @@
+//! GPU setup shared by every example: adapter and device creation, picking
+//! the surface format and present mode, and keeping the surface configured
+//! through resizes and monitor changes. With the `ecs` feature the context is
+//! a bevy resource, so the ECS based examples insert it as is.
+
+mod gpu;
+
+pub use gpu::{
+    DiskPipelineCache, GpuContext, GpuError, GpuOptions, PollStrategy, SurfaceChanged,
+    POLL_INTERVAL,
+};
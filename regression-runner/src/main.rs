@@ -0,0 +1,387 @@
+//! Runs every example for a few frames and compares what it rendered against
+//! the goldens in `goldens/`, printing a summary table at the end.
+//!
+//! The ECS examples save a frame through the harness' `FrameCapture`, the
+//! standalone ones don't share it and are only checked to start and keep
+//! running without crashing.
+//!
+//! ```text
+//! cargo run -p regression-runner -- [--bless] [--frames N] [--only PACKAGE]
+//! ```
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    process::{Command, ExitCode, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use image::{Rgba, RgbaImage};
+use playground_app::{CAPTURE_ENV, CAPTURE_FRAMES_ENV};
+
+// ===== EXAMPLES =====
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Check {
+    /// Runs for [`SMOKE_DURATION`] and passes if it didn't exit on its own.
+    Smoke,
+    /// Captures a frame and compares it against the golden.
+    Golden,
+}
+
+struct Example {
+    package: &'static str,
+    check: Check,
+}
+
+const EXAMPLES: &[Example] = &[
+    Example {
+        package: "showing-window",
+        check: Check::Smoke,
+    },
+    Example {
+        package: "triangle",
+        check: Check::Smoke,
+    },
+    Example {
+        package: "triangle-buffer",
+        check: Check::Smoke,
+    },
+    Example {
+        package: "triangle-texture",
+        check: Check::Smoke,
+    },
+    Example {
+        package: "depth-texture",
+        check: Check::Smoke,
+    },
+    Example {
+        package: "resources-ecs",
+        check: Check::Golden,
+    },
+    Example {
+        package: "egui-ui",
+        check: Check::Golden,
+    },
+    Example {
+        package: "gltf-model",
+        check: Check::Smoke,
+    },
+];
+
+const SMOKE_DURATION: Duration = Duration::from_secs(5);
+/// Generous, the first frames include shader compilation and pipeline setup.
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Largest per channel difference for a pixel to still count as matching.
+const PIXEL_TOLERANCE: u8 = 8;
+/// Fraction of mismatching pixels above which a capture fails.
+const MAX_MISMATCH: f64 = 0.001;
+
+// ===== OPTIONS =====
+struct Options {
+    bless: bool,
+    frames: u32,
+    only: Option<String>,
+}
+impl Options {
+    fn parse() -> Result<Self> {
+        let mut options = Self {
+            bless: false,
+            frames: playground_app::FrameCapture::DEFAULT_FRAME,
+            only: None,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--bless" => options.bless = true,
+                "--frames" => {
+                    let frames = args.next().context("--frames needs a value")?;
+                    options.frames = frames
+                        .parse()
+                        .with_context(|| format!("Invalid frame count {}", frames))?;
+                }
+                "--only" => options.only = Some(args.next().context("--only needs a package")?),
+                _ => bail!("Unknown argument {}", arg),
+            }
+        }
+        if let Some(only) = &options.only {
+            if !EXAMPLES.iter().any(|example| example.package == only) {
+                bail!("No example named {}", only);
+            }
+        }
+        Ok(options)
+    }
+}
+
+// ===== OUTCOME =====
+enum Outcome {
+    Ran,
+    Matched { mismatch: f64 },
+    Blessed,
+    NoGolden,
+    Mismatched { mismatch: f64, diff: PathBuf },
+    Failed(String),
+}
+impl Outcome {
+    fn passed(&self) -> bool {
+        !matches!(self, Self::Mismatched { .. } | Self::Failed(_))
+    }
+}
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ran => write!(f, "ran for {}s", SMOKE_DURATION.as_secs()),
+            Self::Matched { mismatch } => write!(f, "{:.3}% of pixels differ", mismatch * 100.0),
+            Self::Blessed => write!(f, "golden updated"),
+            Self::NoGolden => write!(f, "no golden, run with --bless"),
+            Self::Mismatched { mismatch, diff } => write!(
+                f,
+                "{:.3}% of pixels differ, see {}",
+                mismatch * 100.0,
+                diff.display()
+            ),
+            Self::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+// ===== RUNNER =====
+struct Runner {
+    root: PathBuf,
+    bin_dir: PathBuf,
+    out_dir: PathBuf,
+    options: Options,
+}
+impl Runner {
+    fn new(options: Options) -> Result<Self> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .context("The runner isn't inside the workspace")?
+            .to_path_buf();
+        // The examples are built with the same profile, next to this binary
+        let bin_dir = std::env::current_exe()?
+            .parent()
+            .context("The runner has no parent directory")?
+            .to_path_buf();
+        let out_dir = bin_dir.join("regression");
+        Ok(Self {
+            root,
+            bin_dir,
+            out_dir,
+            options,
+        })
+    }
+
+    fn examples(&self) -> impl Iterator<Item = &'static Example> + '_ {
+        EXAMPLES.iter().filter(|example| {
+            self.options
+                .only
+                .as_ref()
+                .is_none_or(|only| only == example.package)
+        })
+    }
+
+    fn build(&self) -> Result<()> {
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let mut command = Command::new(cargo);
+        command.current_dir(&self.root).arg("build");
+        if !cfg!(debug_assertions) {
+            command.arg("--release");
+        }
+        for example in self.examples() {
+            command.args(["-p", example.package]);
+        }
+        let status = command.status().context("Failed to run cargo")?;
+        if !status.success() {
+            bail!("Building the examples failed");
+        }
+        Ok(())
+    }
+
+    fn run(&self, example: &Example) -> Outcome {
+        let result = match example.check {
+            Check::Smoke => self.smoke(example),
+            Check::Golden => self.golden(example),
+        };
+        result.unwrap_or_else(|e| Outcome::Failed(format!("{:#}", e)))
+    }
+
+    /// Each run gets an empty working directory so configs saved by earlier
+    /// interactive sessions can't change what's rendered. Output goes to
+    /// `<package>.log` next to it.
+    fn command(&self, example: &Example) -> Result<Command> {
+        let work_dir = self.out_dir.join(example.package);
+        if work_dir.exists() {
+            std::fs::remove_dir_all(&work_dir)?;
+        }
+        std::fs::create_dir_all(&work_dir)?;
+        let log = std::fs::File::create(self.log_path(example))?;
+        let mut command = Command::new(self.bin_dir.join(example.package));
+        command
+            .current_dir(work_dir)
+            .stdout(Stdio::null())
+            .stderr(log);
+        Ok(command)
+    }
+
+    fn log_path(&self, example: &Example) -> PathBuf {
+        self.out_dir.join(format!("{}.log", example.package))
+    }
+
+    fn smoke(&self, example: &Example) -> Result<Outcome> {
+        let mut child = self.command(example)?.spawn()?;
+        let start = Instant::now();
+        while start.elapsed() < SMOKE_DURATION {
+            if let Some(status) = child.try_wait()? {
+                bail!(
+                    "exited early with {}, see {}",
+                    status,
+                    self.log_path(example).display()
+                );
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        child.kill()?;
+        child.wait()?;
+        Ok(Outcome::Ran)
+    }
+
+    fn golden(&self, example: &Example) -> Result<Outcome> {
+        let capture = self.out_dir.join(format!("{}.png", example.package));
+        if capture.exists() {
+            std::fs::remove_file(&capture)?;
+        }
+        let mut child = self
+            .command(example)?
+            .env(CAPTURE_ENV, &capture)
+            .env(CAPTURE_FRAMES_ENV, self.options.frames.to_string())
+            .spawn()?;
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if start.elapsed() > CAPTURE_TIMEOUT {
+                child.kill()?;
+                child.wait()?;
+                bail!("timed out after {}s", CAPTURE_TIMEOUT.as_secs());
+            }
+            thread::sleep(Duration::from_millis(100));
+        };
+        if !status.success() {
+            bail!(
+                "exited with {}, see {}",
+                status,
+                self.log_path(example).display()
+            );
+        }
+        let actual = image::open(&capture)
+            .with_context(|| format!("Failed to read {}", capture.display()))?
+            .to_rgba8();
+
+        let golden_path = self
+            .root
+            .join("goldens")
+            .join(format!("{}.png", example.package));
+        if self.options.bless {
+            std::fs::create_dir_all(golden_path.parent().unwrap())?;
+            std::fs::copy(&capture, &golden_path)?;
+            return Ok(Outcome::Blessed);
+        }
+        if !golden_path.exists() {
+            return Ok(Outcome::NoGolden);
+        }
+        let golden = image::open(&golden_path)
+            .with_context(|| format!("Failed to read {}", golden_path.display()))?
+            .to_rgba8();
+        if golden.dimensions() != actual.dimensions() {
+            bail!(
+                "rendered {:?}, the golden is {:?}",
+                actual.dimensions(),
+                golden.dimensions()
+            );
+        }
+
+        let (mismatch, diff) = compare(&golden, &actual);
+        if mismatch <= MAX_MISMATCH {
+            return Ok(Outcome::Matched { mismatch });
+        }
+        let diff_path = self.out_dir.join(format!("{}.diff.png", example.package));
+        diff.save(&diff_path)?;
+        Ok(Outcome::Mismatched {
+            mismatch,
+            diff: diff_path,
+        })
+    }
+}
+
+/// Returns the fraction of pixels differing by more than [`PIXEL_TOLERANCE`]
+/// and an image with those pixels in red over the dimmed golden.
+fn compare(golden: &RgbaImage, actual: &RgbaImage) -> (f64, RgbaImage) {
+    let mut diff = RgbaImage::new(golden.width(), golden.height());
+    let mut mismatched = 0usize;
+    for ((expected, got), out) in golden.pixels().zip(actual.pixels()).zip(diff.pixels_mut()) {
+        let differs = expected
+            .0
+            .iter()
+            .zip(got.0.iter())
+            .any(|(a, b)| a.abs_diff(*b) > PIXEL_TOLERANCE);
+        *out = if differs {
+            mismatched += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = expected.0;
+            Rgba([r / 4, g / 4, b / 4, 255])
+        };
+    }
+    let total = (golden.width() as usize * golden.height() as usize).max(1);
+    (mismatched as f64 / total as f64, diff)
+}
+
+fn main() -> ExitCode {
+    let run = || -> Result<bool> {
+        let runner = Runner::new(Options::parse()?)?;
+        runner.build()?;
+        std::fs::create_dir_all(&runner.out_dir)?;
+
+        let mut results = Vec::new();
+        for example in runner.examples() {
+            println!("Running {}...", example.package);
+            let start = Instant::now();
+            let outcome = runner.run(example);
+            results.push((example.package, outcome, start.elapsed()));
+        }
+
+        let width = results
+            .iter()
+            .map(|(package, ..)| package.len())
+            .max()
+            .unwrap_or(0);
+        println!();
+        println!(
+            "{:<width$}  {:<6}  {:>7}  DETAILS",
+            "EXAMPLE", "RESULT", "TIME"
+        );
+        for (package, outcome, elapsed) in &results {
+            let result = if outcome.passed() { "ok" } else { "FAILED" };
+            println!(
+                "{:<width$}  {:<6}  {:>6.1}s  {}",
+                package,
+                result,
+                elapsed.as_secs_f32(),
+                outcome
+            );
+        }
+        Ok(results.iter().all(|(_, outcome, _)| outcome.passed()))
+    };
+
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! `cargo xtask assets` bakes `assets/` into `baked/`: textures get a full
+//! mip chain and BC1/BC3 compression in a KTX2 container, and
+//! `baked/manifest.json` lists what was baked from what, with the hash and
+//! size of both sides. Outputs are named after their content hash. The asset
+//! server loads a baked texture instead of decoding the source when the
+//! manifest has it, and checks the hashes first. Sources whose hash didn't
+//! change since the last bake are skipped.
+//!
+//! Meshes are generated in code, there are no mesh files to optimize yet.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bc::{self, BcFormat},
+    ktx2,
+};
+
+/// Bumped whenever the baked output changes for the same source, so old
+/// bakes are redone instead of reused.
+pub const BAKE_VERSION: u32 = 2;
+pub const MANIFEST: &str = "manifest.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    /// Relative to `assets/`, with forward slashes.
+    pub source: String,
+    pub source_hash: String,
+    /// What the asset server loads it as, only `texture` so far.
+    pub kind: String,
+    /// Relative to `baked/`.
+    pub output: String,
+    pub output_hash: String,
+    pub output_size: u64,
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub levels: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct BakeStats {
+    pub baked: u32,
+    pub up_to_date: u32,
+    pub skipped: Vec<(String, String)>,
+    pub removed: u32,
+}
+
+pub fn bake(root: &Path, force: bool) -> Result<BakeStats> {
+    let source_dir = root.join("assets");
+    let baked_dir = root.join("baked");
+    std::fs::create_dir_all(&baked_dir)?;
+    let manifest_path = baked_dir.join(MANIFEST);
+    let previous = std::fs::read_to_string(&manifest_path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<Manifest>(&contents).ok())
+        .filter(|manifest| manifest.version == BAKE_VERSION && !force)
+        .unwrap_or_default();
+
+    let mut sources = Vec::new();
+    collect_files(&source_dir, &mut sources)?;
+    sources.sort();
+
+    let mut stats = BakeStats::default();
+    let mut manifest = Manifest {
+        version: BAKE_VERSION,
+        entries: Vec::new(),
+    };
+    for path in sources {
+        let source = path
+            .strip_prefix(&source_dir)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let is_image = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("png" | "jpg" | "jpeg")
+        );
+        if !is_image {
+            continue;
+        }
+        let bytes =
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let source_hash = content_hash(&bytes);
+
+        // A damaged output is baked again rather than kept
+        let unchanged = previous.entries.iter().find(|entry| {
+            entry.source == source
+                && entry.source_hash == source_hash
+                && std::fs::read(baked_dir.join(&entry.output))
+                    .is_ok_and(|output| content_hash(&output) == entry.output_hash)
+        });
+        if let Some(entry) = unchanged {
+            manifest.entries.push(entry.clone());
+            stats.up_to_date += 1;
+            continue;
+        }
+
+        println!("Baking {}...", source);
+        match bake_texture(&bytes, &baked_dir, &source, source_hash) {
+            Ok(entry) => {
+                manifest.entries.push(entry);
+                stats.baked += 1;
+            }
+            Err(e) => stats.skipped.push((source, format!("{:#}", e))),
+        }
+    }
+
+    // Outputs of sources that are gone or no longer bake
+    for entry in std::fs::read_dir(&baked_dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let referenced = manifest.entries.iter().any(|entry| entry.output == name);
+        if path.extension().is_some_and(|e| e == "ktx2") && !referenced {
+            std::fs::remove_file(&path)?;
+            stats.removed += 1;
+        }
+    }
+
+    std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    Ok(stats)
+}
+
+fn bake_texture(
+    bytes: &[u8],
+    baked_dir: &Path,
+    source: &str,
+    source_hash: String,
+) -> Result<Entry> {
+    let image = image::load_from_memory(bytes)?.to_rgba8();
+    let (width, height) = image.dimensions();
+    // wgpu wants the top level of a block compressed texture in whole blocks
+    if width % 4 != 0 || height % 4 != 0 {
+        anyhow::bail!("{}x{} isn't a multiple of 4, left raw", width, height);
+    }
+    let format = BcFormat::for_image(&image);
+    let levels: Vec<Vec<u8>> = mip_chain(image)
+        .iter()
+        .map(|level| bc::compress(level, format))
+        .collect();
+
+    let data = ktx2::write(format, width, height, &levels);
+    let output_hash = content_hash(&data);
+    // Flattened, so sources in subdirectories can't collide with each other
+    let output = format!("{}.{}.ktx2", source.replace('/', "_"), output_hash);
+    let path = baked_dir.join(&output);
+    std::fs::write(&path, &data).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(Entry {
+        source: source.to_string(),
+        source_hash,
+        kind: "texture".to_string(),
+        output,
+        output_hash,
+        output_size: data.len() as u64,
+        format: format.name().to_string(),
+        width,
+        height,
+        levels: levels.len() as u32,
+    })
+}
+
+/// Halves down to 1x1 with the same filter the asset server uses on raw
+/// sources, so baked and raw textures look alike.
+fn mip_chain(image: image::RgbaImage) -> Vec<image::RgbaImage> {
+    let mut levels = vec![image];
+    loop {
+        let (width, height) = levels.last().expect("starts with a level").dimensions();
+        if width == 1 && height == 1 {
+            return levels;
+        }
+        let next = image::imageops::resize(
+            levels.last().expect("starts with a level"),
+            (width / 2).max(1),
+            (height / 2).max(1),
+            image::imageops::FilterType::Triangle,
+        );
+        levels.push(next);
+    }
+}
+
+/// 64 bit FNV-1a as hex, enough to notice a changed file. The asset server
+/// hashes the same way.
+pub fn content_hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
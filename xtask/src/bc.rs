@@ -0,0 +1,184 @@
+//! Block compression to BC1 and BC3. A range fit encoder: the endpoints span
+//! the bounding box of each 4x4 block, slightly inset, and every texel picks
+//! the closest palette entry. Far from the best quality an offline encoder
+//! could get, but fast and good enough for the playground's textures.
+
+use image::RgbaImage;
+
+/// BC1 for opaque textures, BC3 when any texel is translucent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BcFormat {
+    Bc1,
+    Bc3,
+}
+impl BcFormat {
+    pub fn for_image(image: &RgbaImage) -> Self {
+        if image.pixels().all(|pixel| pixel[3] == 255) {
+            Self::Bc1
+        } else {
+            Self::Bc3
+        }
+    }
+
+    pub fn block_bytes(self) -> usize {
+        match self {
+            Self::Bc1 => 8,
+            Self::Bc3 => 16,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Bc1 => "bc1-srgb",
+            Self::Bc3 => "bc3-srgb",
+        }
+    }
+}
+
+/// Compresses `image`, blocks in rows from the top left. Blocks hanging over
+/// the edge of small mips repeat the edge texels.
+pub fn compress(image: &RgbaImage, format: BcFormat) -> Vec<u8> {
+    let blocks_x = image.width().div_ceil(4);
+    let blocks_y = image.height().div_ceil(4);
+    let mut out = Vec::with_capacity((blocks_x * blocks_y) as usize * format.block_bytes());
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let mut texels = [[0u8; 4]; 16];
+            for (i, texel) in texels.iter_mut().enumerate() {
+                let x = (block_x * 4 + i as u32 % 4).min(image.width() - 1);
+                let y = (block_y * 4 + i as u32 / 4).min(image.height() - 1);
+                *texel = image.get_pixel(x, y).0;
+            }
+            if format == BcFormat::Bc3 {
+                out.extend_from_slice(&alpha_block(&texels));
+            }
+            out.extend_from_slice(&color_block(&texels));
+        }
+    }
+    out
+}
+
+fn color_block(texels: &[[u8; 4]; 16]) -> [u8; 8] {
+    let mut min = [255u8; 3];
+    let mut max = [0u8; 3];
+    for texel in texels {
+        for c in 0..3 {
+            min[c] = min[c].min(texel[c]);
+            max[c] = max[c].max(texel[c]);
+        }
+    }
+    // Insetting by a sixteenth of the range moves the endpoints off the
+    // outliers, which lowers the error of everything in between
+    for c in 0..3 {
+        let inset = (max[c] - min[c]) / 16;
+        min[c] += inset;
+        max[c] -= inset;
+    }
+    let (mut c0, mut c1) = (to_565(max), to_565(min));
+    // The four color mode needs c0 > c1, equal endpoints are a flat block
+    if c0 < c1 {
+        std::mem::swap(&mut c0, &mut c1);
+    }
+    let mut indices = 0u32;
+    if c0 != c1 {
+        let (e0, e1) = (from_565(c0), from_565(c1));
+        let palette = [
+            e0,
+            e1,
+            std::array::from_fn(|c| ((2 * e0[c] as u32 + e1[c] as u32) / 3) as u8),
+            std::array::from_fn(|c| ((e0[c] as u32 + 2 * e1[c] as u32) / 3) as u8),
+        ];
+        for (i, texel) in texels.iter().enumerate() {
+            let index = closest(&palette, |entry| {
+                (0..3)
+                    .map(|c| (entry[c] as i32 - texel[c] as i32).pow(2))
+                    .sum()
+            });
+            indices |= (index as u32) << (2 * i);
+        }
+    }
+    let mut block = [0u8; 8];
+    block[0..2].copy_from_slice(&c0.to_le_bytes());
+    block[2..4].copy_from_slice(&c1.to_le_bytes());
+    block[4..8].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+fn alpha_block(texels: &[[u8; 4]; 16]) -> [u8; 8] {
+    let a0 = texels.iter().map(|texel| texel[3]).max().unwrap_or(255);
+    let a1 = texels.iter().map(|texel| texel[3]).min().unwrap_or(255);
+    let mut indices = 0u64;
+    if a0 != a1 {
+        // a0 > a1 selects the eight value mode, six steps in between
+        let palette: [u8; 8] = std::array::from_fn(|i| match i {
+            0 => a0,
+            1 => a1,
+            i => (((8 - i) as u32 * a0 as u32 + (i - 1) as u32 * a1 as u32) / 7) as u8,
+        });
+        for (i, texel) in texels.iter().enumerate() {
+            let index = closest(&palette, |entry| (*entry as i32 - texel[3] as i32).abs());
+            indices |= (index as u64) << (3 * i);
+        }
+    }
+    let mut block = [0u8; 8];
+    block[0] = a0;
+    block[1] = a1;
+    block[2..8].copy_from_slice(&indices.to_le_bytes()[..6]);
+    block
+}
+
+fn closest<T>(palette: &[T], error: impl Fn(&T) -> i32) -> usize {
+    (0..palette.len())
+        .min_by_key(|&i| error(&palette[i]))
+        .expect("palettes aren't empty")
+}
+
+fn to_565(color: [u8; 3]) -> u16 {
+    let r = (color[0] as u16 * 31 + 127) / 255;
+    let g = (color[1] as u16 * 63 + 127) / 255;
+    let b = (color[2] as u16 * 31 + 127) / 255;
+    (r << 11) | (g << 5) | b
+}
+
+fn from_565(color: u16) -> [u8; 3] {
+    let r = (color >> 11) & 31;
+    let g = (color >> 5) & 63;
+    let b = color & 31;
+    [
+        ((r << 3) | (r >> 2)) as u8,
+        ((g << 2) | (g >> 4)) as u8,
+        ((b << 3) | (b >> 2)) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_keep_their_extremes() {
+        // Left half black, right half white and half transparent
+        let image = RgbaImage::from_fn(4, 4, |x, _| match x {
+            0 | 1 => image::Rgba([0, 0, 0, 255]),
+            _ => image::Rgba([255, 255, 255, 128]),
+        });
+        assert_eq!(BcFormat::for_image(&image), BcFormat::Bc3);
+
+        let block = compress(&image, BcFormat::Bc3);
+        assert_eq!(block.len(), 16);
+        assert_eq!((block[0], block[1]), (255, 128));
+        // Alpha indices: 0 selects a0 on the opaque half, 1 selects a1
+        let alpha = u64::from_le_bytes(std::array::from_fn(
+            |i| if i < 6 { block[2 + i] } else { 0 },
+        ));
+        assert_eq!((alpha >> 3) & 7, 0);
+        assert_eq!((alpha >> 6) & 7, 1);
+
+        let c0 = u16::from_le_bytes([block[8], block[9]]);
+        let c1 = u16::from_le_bytes([block[10], block[11]]);
+        assert!(c0 > c1, "four color mode");
+        let colors = u32::from_le_bytes([block[12], block[13], block[14], block[15]]);
+        assert_eq!(colors & 3, 1, "black texels use c1");
+        assert_eq!((colors >> 6) & 3, 0, "white texels use c0");
+    }
+}
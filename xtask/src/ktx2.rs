@@ -0,0 +1,101 @@
+//! Minimal KTX2 writer for block compressed 2D textures with mips, no
+//! supercompression and no key/value data.
+//! <https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html>
+
+use crate::bc::BcFormat;
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const HEADER_BYTES: usize = 80;
+const LEVEL_INDEX_BYTES: usize = 24;
+
+const VK_FORMAT_BC1_RGB_SRGB_BLOCK: u32 = 132;
+const VK_FORMAT_BC3_SRGB_BLOCK: u32 = 138;
+
+// Data format descriptor values
+const KHR_DF_MODEL_BC1A: u8 = 128;
+const KHR_DF_MODEL_BC3: u8 = 130;
+const KHR_DF_PRIMARIES_BT709: u8 = 1;
+const KHR_DF_TRANSFER_SRGB: u8 = 2;
+const KHR_DF_CHANNEL_COLOR: u8 = 0;
+const KHR_DF_CHANNEL_ALPHA: u8 = 15;
+
+/// Serializes `levels`, finest first, of a `width` x `height` texture.
+pub fn write(format: BcFormat, width: u32, height: u32, levels: &[Vec<u8>]) -> Vec<u8> {
+    let (vk_format, model, samples): (u32, u8, &[(u8, u16)]) = match format {
+        BcFormat::Bc1 => (
+            VK_FORMAT_BC1_RGB_SRGB_BLOCK,
+            KHR_DF_MODEL_BC1A,
+            &[(KHR_DF_CHANNEL_COLOR, 0)],
+        ),
+        BcFormat::Bc3 => (
+            VK_FORMAT_BC3_SRGB_BLOCK,
+            KHR_DF_MODEL_BC3,
+            &[(KHR_DF_CHANNEL_ALPHA, 0), (KHR_DF_CHANNEL_COLOR, 64)],
+        ),
+    };
+
+    let mut dfd = Vec::new();
+    let block_size = 24 + 16 * samples.len() as u16;
+    push_u32(&mut dfd, 4 + block_size as u32);
+    push_u32(&mut dfd, 0); // Khronos vendor, basic descriptor type
+    dfd.extend_from_slice(&2u16.to_le_bytes());
+    dfd.extend_from_slice(&block_size.to_le_bytes());
+    dfd.extend_from_slice(&[model, KHR_DF_PRIMARIES_BT709, KHR_DF_TRANSFER_SRGB, 0]);
+    dfd.extend_from_slice(&[3, 3, 0, 0]); // 4x4 blocks, stored minus one
+    dfd.extend_from_slice(&[format.block_bytes() as u8, 0, 0, 0, 0, 0, 0, 0]);
+    for &(channel, bit_offset) in samples {
+        dfd.extend_from_slice(&bit_offset.to_le_bytes());
+        dfd.extend_from_slice(&[63, channel, 0, 0, 0, 0]);
+        push_u32(&mut dfd, 0);
+        push_u32(&mut dfd, u32::MAX);
+    }
+
+    let dfd_offset = HEADER_BYTES + LEVEL_INDEX_BYTES * levels.len();
+    // Levels are stored coarsest first, each aligned to its block size
+    let mut offsets = vec![0; levels.len()];
+    let mut end = dfd_offset + dfd.len();
+    for (level, data) in levels.iter().enumerate().rev() {
+        end = end.next_multiple_of(format.block_bytes());
+        offsets[level] = end;
+        end += data.len();
+    }
+
+    let mut out = Vec::with_capacity(end);
+    out.extend_from_slice(&IDENTIFIER);
+    for value in [
+        vk_format,
+        1, // typeSize, 1 for block compressed formats
+        width,
+        height,
+        0, // pixelDepth
+        0, // layerCount
+        1, // faceCount
+        levels.len() as u32,
+        0, // supercompressionScheme
+        dfd_offset as u32,
+        dfd.len() as u32,
+        0, // kvdByteOffset
+        0, // kvdByteLength
+    ] {
+        push_u32(&mut out, value);
+    }
+    out.extend_from_slice(&0u64.to_le_bytes()); // sgdByteOffset
+    out.extend_from_slice(&0u64.to_le_bytes()); // sgdByteLength
+    for (offset, data) in offsets.iter().zip(levels) {
+        for value in [*offset, data.len(), data.len()] {
+            out.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+    }
+    out.extend_from_slice(&dfd);
+    for (level, data) in levels.iter().enumerate().rev() {
+        out.resize(offsets[level], 0);
+        out.extend_from_slice(data);
+    }
+    out
+}
+
+fn push_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
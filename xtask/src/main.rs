@@ -0,0 +1,266 @@
+//! Workspace chores that are easier in Rust than in a shell script.
+//!
+//! ```text
+//! cargo xtask new-example <name> [--title TITLE]
+//! cargo xtask assets [--force]
+//! ```
+//!
+//! `new-example` scaffolds the next numbered example, wired to the shared
+//! `playground-app` harness with a pipeline module and a full screen shader,
+//! and adds it to the workspace members.
+//!
+//! `assets` bakes `assets/` into `baked/`, see [`assets`]. `--force` rebakes
+//! everything instead of only what changed.
+
+mod assets;
+mod bc;
+mod ktx2;
+
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use anyhow::{bail, Context, Result};
+
+// ===== TEMPLATE =====
+/// Files of a new example, relative to its directory. `{{name}}` and
+/// `{{title}}` are replaced.
+const TEMPLATE: &[(&str, &str)] = &[
+    (
+        "Cargo.toml",
+        include_str!("../templates/example/Cargo.toml"),
+    ),
+    (
+        "src/main.rs",
+        include_str!("../templates/example/src/main.rs"),
+    ),
+    (
+        "src/gpu.rs",
+        include_str!("../templates/example/src/gpu.rs"),
+    ),
+    (
+        "src/pipeline/mod.rs",
+        include_str!("../templates/example/src/pipeline/mod.rs"),
+    ),
+    (
+        "src/pipeline/render.rs",
+        include_str!("../templates/example/src/pipeline/render.rs"),
+    ),
+    (
+        "src/shaders/shader.wgsl",
+        include_str!("../templates/example/src/shaders/shader.wgsl"),
+    ),
+];
+
+// ===== OPTIONS =====
+enum Task {
+    NewExample { name: String, title: Option<String> },
+    Assets { force: bool },
+}
+impl Task {
+    fn parse() -> Result<Self> {
+        let mut args = std::env::args().skip(1);
+        match args.next().as_deref() {
+            Some("new-example") => {
+                let mut name = None;
+                let mut title = None;
+                while let Some(arg) = args.next() {
+                    match arg.as_str() {
+                        "--title" => title = Some(args.next().context("--title needs a value")?),
+                        _ if arg.starts_with('-') => bail!("Unknown argument {}", arg),
+                        _ if name.is_none() => name = Some(arg),
+                        _ => bail!("Unexpected argument {}", arg),
+                    }
+                }
+                let name = name.context("new-example needs a name, e.g. `new-example shadows`")?;
+                Ok(Self::NewExample { name, title })
+            }
+            Some("assets") => {
+                let mut force = false;
+                for arg in args {
+                    match arg.as_str() {
+                        "--force" => force = true,
+                        _ => bail!("Unknown argument {}", arg),
+                    }
+                }
+                Ok(Self::Assets { force })
+            }
+            Some(task) => bail!("Unknown task {}", task),
+            None => bail!(
+                "Usage: cargo xtask new-example <name> [--title TITLE]\n       cargo xtask assets [--force]"
+            ),
+        }
+    }
+}
+
+// ===== NEW EXAMPLE =====
+fn new_example(root: &Path, name: &str, title: Option<&str>) -> Result<PathBuf> {
+    if name.is_empty()
+        || !name.starts_with(|c: char| c.is_ascii_lowercase())
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        bail!(
+            "'{}' isn't a valid package name, use lowercase letters, digits and dashes",
+            name
+        );
+    }
+
+    let manifest_path = root.join("Cargo.toml");
+    let manifest = std::fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+    let numbers = numbered_members(&manifest)?;
+    let taken = numbers.iter().any(|(_, member)| member == name)
+        || members(&manifest)?.any(|member| member == name);
+    if taken {
+        bail!("An example named {} already exists", name);
+    }
+    let number = numbers
+        .iter()
+        .map(|(number, _)| number + 1)
+        .max()
+        .unwrap_or(0);
+    let directory = format!("{}-{}", number, name);
+    let path = root.join(&directory);
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+
+    let title = title.map_or_else(|| default_title(name), str::to_string);
+    for (file, contents) in TEMPLATE {
+        let file = path.join(file);
+        std::fs::create_dir_all(file.parent().expect("template files are in a directory"))?;
+        let contents = contents
+            .replace("{{name}}", name)
+            .replace("{{title}}", &title);
+        std::fs::write(&file, contents)
+            .with_context(|| format!("Failed to write {}", file.display()))?;
+    }
+
+    std::fs::write(&manifest_path, add_member(&manifest, &directory)?)
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    Ok(path)
+}
+
+/// Number and package name of every `N-name` workspace member.
+fn numbered_members(manifest: &str) -> Result<Vec<(u32, String)>> {
+    Ok(members(manifest)?
+        .filter_map(|member| {
+            let (number, name) = member.split_once('-')?;
+            Some((number.parse().ok()?, name.to_string()))
+        })
+        .collect())
+}
+
+fn members(manifest: &str) -> Result<impl Iterator<Item = &str>> {
+    let (_, rest) = manifest
+        .split_once("members = [")
+        .context("No workspace members in Cargo.toml")?;
+    let (list, _) = rest
+        .split_once(']')
+        .context("Unterminated workspace members in Cargo.toml")?;
+    Ok(list
+        .split(',')
+        .map(|member| member.trim().trim_matches('"'))
+        .filter(|member| !member.is_empty()))
+}
+
+/// Inserts `member` after the last numbered member, keeping the shared crates
+/// after the examples.
+fn add_member(manifest: &str, member: &str) -> Result<String> {
+    let start = manifest
+        .find("members = [")
+        .context("No workspace members in Cargo.toml")?;
+    let end = start
+        + manifest[start..]
+            .find(']')
+            .context("Unterminated workspace members in Cargo.toml")?;
+    let last_numbered = manifest[start..end]
+        .match_indices("\n    \"")
+        .map(|(index, _)| start + index)
+        .filter(|&index| manifest[index + 6..].starts_with(|c: char| c.is_ascii_digit()))
+        .last();
+    let line = format!("\n    \"{}\",", member);
+    let at = match last_numbered {
+        Some(index) => index + manifest[index + 1..].find('\n').map_or(0, |end| end + 1),
+        None => start + "members = [".len(),
+    };
+    Ok(format!("{}{}{}", &manifest[..at], line, &manifest[at..]))
+}
+
+fn default_title(name: &str) -> String {
+    name.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn main() -> ExitCode {
+    let run = || -> Result<()> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .context("xtask isn't inside the workspace")?;
+        match Task::parse()? {
+            Task::NewExample { name, title } => {
+                let path = new_example(root, &name, title.as_deref())?;
+                println!("Created {}", path.display());
+                println!("Run it with `cargo run -p {}`", name);
+                println!("Add it to EXAMPLES in regression-runner to have it checked");
+            }
+            Task::Assets { force } => {
+                let stats = assets::bake(root, force)?;
+                for (source, reason) in &stats.skipped {
+                    println!("Skipped {}: {}", source, reason);
+                }
+                println!(
+                    "Baked {}, {} up to date, {} skipped, {} stale outputs removed",
+                    stats.baked,
+                    stats.up_to_date,
+                    stats.skipped.len(),
+                    stats.removed
+                );
+            }
+        }
+        Ok(())
+    };
+
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members_go_after_the_last_example() {
+        let manifest = "[workspace]\nmembers = [\n    \"0-window\",\n    \"1-triangle\",\n    \"playground-app\",\n]\n";
+        let updated = add_member(manifest, "2-shadows").unwrap();
+        assert_eq!(
+            updated,
+            "[workspace]\nmembers = [\n    \"0-window\",\n    \"1-triangle\",\n    \"2-shadows\",\n    \"playground-app\",\n]\n"
+        );
+        assert_eq!(
+            numbered_members(&updated).unwrap(),
+            [
+                (0, "window".to_string()),
+                (1, "triangle".to_string()),
+                (2, "shadows".to_string())
+            ]
+        );
+        assert_eq!(default_title("resources-ecs"), "Resources Ecs");
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use bevy_ecs::{observer::Trigger, schedule::Schedule, system::ResMut, world::World};
+use gpu::{setup_gpu, GpuContext};
+use pipeline::{render::setup_rendering, setup_example_pipeline};
+use playground_app::{App, WindowTriggerEvent};
+use tracing_subscriber::{layer::SubscriberExt, EnvFilter};
+use winit::{event::WindowEvent, window::Window};
+
+mod gpu;
+mod pipeline;
+
+// =============================== SETUP ===============================
+fn setup(world: &mut World, schedule: &mut Schedule, window: Arc<Window>) -> Result<()> {
+    setup_gpu(world, schedule, window).context("Failed to setup GPU")?;
+    setup_example_pipeline(world, schedule).context("Failed to setup example pipeline")?;
+    setup_rendering(world, schedule).context("Failed to setup rendering")?;
+
+    world.add_observer(
+        |trigger: Trigger<WindowTriggerEvent>, mut gpu: ResMut<GpuContext>| {
+            if let WindowEvent::Resized(size) = trigger.event().event {
+                gpu.resize(&size);
+            }
+        },
+    );
+
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let env_filter = EnvFilter::from_default_env()
+        .add_directive("wgpu=warn".parse().unwrap())
+        .add_directive("winit=warn".parse().unwrap())
+        .add_directive("naga=warn".parse().unwrap())
+        .add_directive("debug".parse().unwrap());
+
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::registry()
+            .with(env_filter)
+            .with(tracing_subscriber::fmt::layer()),
+    )
+    .expect("setup tracing");
+    better_panic::install();
+
+    App::new("{{title}}").run(setup)?;
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use bevy_ecs::{schedule::Schedule, system::Resource, world::World};
+
+use crate::gpu::GpuContext;
+
+pub mod render;
+
+pub fn setup_example_pipeline(world: &mut World, _schedule: &mut Schedule) -> Result<()> {
+    let gpu = world
+        .get_resource::<GpuContext>()
+        .ok_or_else(|| anyhow::anyhow!("GpuContext resource not found"))?;
+
+    let pipeline = ExamplePipeline::new(gpu);
+    world.insert_resource(pipeline);
+
+    Ok(())
+}
+
+// =============================== PIPELINE ===============================
+/// Draws a full screen triangle with `shaders/shader.wgsl`. Start the
+/// experiment here.
+#[derive(Resource)]
+pub struct ExamplePipeline {
+    pub render_pipeline: wgpu::RenderPipeline,
+}
+impl ExamplePipeline {
+    pub fn new(gpu: &GpuContext) -> Self {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("example_shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/shader.wgsl").into()),
+            });
+        let layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("example_pipeline_layout"),
+                bind_group_layouts: &[],
+                push_constant_ranges: &[],
+            });
+        let render_pipeline = gpu
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("example_pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: gpu.config.format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            });
+
+        Self { render_pipeline }
+    }
+}
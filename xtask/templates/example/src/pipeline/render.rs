@@ -0,0 +1,64 @@
+use anyhow::Result;
+use bevy_ecs::{
+    schedule::Schedule,
+    system::{Res, ResMut},
+    world::World,
+};
+use playground_app::FrameCapture;
+use tracing::error;
+
+use crate::gpu::GpuContext;
+
+use super::ExamplePipeline;
+
+pub fn setup_rendering(_world: &mut World, schedule: &mut Schedule) -> Result<()> {
+    schedule.add_systems(render_system);
+    Ok(())
+}
+
+pub fn render_system(
+    gpu: Res<GpuContext>,
+    pipeline: Res<ExamplePipeline>,
+    mut capture: Option<ResMut<FrameCapture>>,
+) {
+    let mut f = || -> Result<()> {
+        let output = gpu.surface.get_current_texture()?;
+        let view = output.texture.create_view(&Default::default());
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("render_encoder"),
+            });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("example_render_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&pipeline.render_pipeline);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(capture) = capture.as_mut().filter(|capture| capture.is_due()) {
+            capture.capture(&gpu.device, &gpu.queue, &output.texture);
+        }
+        output.present();
+
+        Ok(())
+    };
+
+    if let Err(e) = f() {
+        error!("Error during rendering: {:?}", e);
+    }
+}